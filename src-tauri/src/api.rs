@@ -1,36 +1,175 @@
 use axum::{
-    extract::State as AxumState,
-    http::StatusCode,
+    extract::{
+        ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
+        ConnectInfo, DefaultBodyLimit, Query, State as AxumState,
+    },
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware,
     response::{
-        sse::{Event as SseEvent, KeepAlive, Sse},
+        sse::{Event as SseEvent, Sse},
         IntoResponse, Json, Response,
     },
     routing::{get, post},
-    Router,
+    Extension, Router,
 };
+use futures_util::future::join_all;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
-use tauri::Emitter;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
 use tokio::sync::{oneshot, watch, Mutex};
 use tokio_stream::StreamExt;
-use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::compression::predicate::{And, DefaultPredicate, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::CorsLayer;
 use uuid::Uuid;
 
-const DEFAULT_PORT: u16 = 21420;
+use crate::approval::{self, Approvals};
+use crate::audit::{self, AuditEntry};
+use crate::auth;
+use crate::author;
+use crate::bind;
+use crate::body_limit;
+use crate::bridge::{self, Bridge};
+use crate::budget;
+use crate::canvas_events::{self, CanvasEvents};
+use crate::clients::{self, ClientRegistry, Transport};
+use crate::connections;
+use crate::constraints::{self, ConstraintSet};
+use crate::diagnostics::{self, BridgeDrift};
+use crate::drain::{self, Drain};
+use crate::embed;
+use crate::hit_test;
+use crate::hosts;
+use crate::integrity;
+use crate::jobs::{self, JobRegistry};
+use crate::legacy_sse::{self, SseSessions};
+use crate::lint;
+use crate::lock::{self, CanvasLock};
+use crate::mdns;
+use crate::model;
+use crate::msgpack;
+use crate::openapi;
+use crate::origins;
+use crate::outline;
+use crate::partition;
+use crate::photo;
+use crate::presence::{self, PresenceRegistry};
+use crate::profiles::ToolExposure;
+use crate::prompts::{self, PromptError};
+use crate::queue::{self, MutationQueue};
+use crate::read_only;
+use crate::region::{self, RegionIndex};
+use crate::render;
+use crate::request_log;
+use crate::resources;
+use crate::rest;
+use crate::restyle;
+use crate::revision::{self, CanvasRead, Revisions};
+use crate::sampling::{self, PendingRequests};
+use crate::session::{self, McpClient, SessionRegistry};
+use crate::settings;
+use crate::sidecar;
+use crate::shape_defaults;
+use crate::sharing::{self, ShareRegistry};
+use crate::text_metrics;
+use crate::tls::{self, PeerAddr, TlsConfig};
+use crate::tool_errors;
+use crate::tool_filter::ToolFilter;
+use crate::trace;
+use crate::translate;
+use crate::tunnel::Tunnel;
+use crate::spatial::SpatialIndex;
+use crate::status::{self, Health};
+use crate::structured;
+use crate::subscriptions::Subscriptions;
+use crate::supervisor;
+use crate::stdio;
+use crate::units::{self, DocumentScale};
+use crate::visibility::{self, TabVisibility};
+use crate::zorder;
+
+pub(crate) const DEFAULT_PORT: u16 = 21420;
+/// Ports tried after `DEFAULT_PORT` when it is taken.
+pub(crate) const PORT_FALLBACK_ATTEMPTS: u16 = 10;
 const REQUEST_TIMEOUT_SECS: u64 = 15;
+/// Responses smaller than this are sent uncompressed.
+const COMPRESS_MIN_BYTES: u16 = 1024;
 
 // --- Shared state ---
 
 pub struct ApiState {
-    pub pending: Arc<Mutex<HashMap<String, oneshot::Sender<serde_json::Value>>>>,
+    /// Tool calls sent to the webview, awaiting its answers.
+    pub bridge: Arc<Mutex<Bridge>>,
+    /// Bridge calls in flight, waited for when the server stops.
+    pub drain: Drain,
     pub app_handle: tauri::AppHandle,
     pub server_shutdown: Arc<Mutex<Option<watch::Sender<bool>>>>,
+    pub server_port: Arc<Mutex<Option<u16>>>,
+    /// Address and scheme the running server was started with.
+    pub listening: Arc<Mutex<Option<bind::Listening>>>,
+    pub embed_key: Vec<u8>,
+    /// Bearer token required on `/mcp`; read from disk on first use.
+    api_token: OnceLock<String>,
+    pub shares: Arc<Mutex<ShareRegistry>>,
+    pub presence: Arc<Mutex<PresenceRegistry>>,
+    pub canvas_lock: Arc<Mutex<CanvasLock>>,
+    pub constraints: Arc<Mutex<ConstraintSet>>,
+    pub scale: Arc<Mutex<Option<DocumentScale>>>,
+    pub spatial: Arc<Mutex<SpatialIndex>>,
+    pub sessions: Arc<Mutex<SessionRegistry>>,
+    pub legacy_sse: Arc<Mutex<SseSessions<Arc<McpClient>>>>,
+    pub subscriptions: Arc<Mutex<Subscriptions>>,
+    /// Shape changes reported by the webview, for every notification stream.
+    pub canvas_events: CanvasEvents,
+    /// Bridged tools the webview does or does not implement.
+    pub bridge_drift: Arc<Mutex<BridgeDrift>>,
+    pub tunnel: Arc<Mutex<Tunnel>>,
+    /// Open `GET /mcp` streams.
+    pub event_streams: Arc<AtomicUsize>,
+    /// Pending "ask before edits" prompts and "always allow" choices.
+    pub approvals: Arc<Mutex<Approvals>>,
+    /// Tools switched off for every agent.
+    pub tool_filter: Arc<Mutex<ToolFilter>>,
+    /// Read-only MCP mode (see `read_only.rs`).
+    pub read_only: Arc<AtomicBool>,
+    /// Sampling requests sent to clients, awaiting their answers.
+    pub sampling: Arc<Mutex<PendingRequests>>,
+    /// Boards hidden from agents (see `visibility.rs`).
+    pub visibility: Arc<TabVisibility>,
+    /// Name of the open document, for the mDNS advertisement.
+    pub mdns_document: watch::Sender<String>,
+    /// Uptime and the last error, for `get_api_status` and `/healthz`.
+    pub health: Arc<Health>,
+    /// Attached MCP clients, for `list_api_clients`.
+    pub clients: Arc<Mutex<ClientRegistry>>,
+    /// Mutating tool calls waiting their turn, per document.
+    pub mutations: MutationQueue,
+    /// Tool calls running in the background (`submit_job`).
+    pub jobs: Arc<Mutex<JobRegistry>>,
+    /// Canvas revisions, for `get_canvas` (see `revision.rs`).
+    pub revisions: Arc<Mutex<Revisions>>,
+    /// Per-board R-trees for `get_shapes_in_region`.
+    pub regions: Arc<Mutex<RegionIndex>>,
 }
 
-type SharedApiState = Arc<ApiState>;
+impl ApiState {
+    pub fn api_token(&self) -> &str {
+        self.api_token
+            .get_or_init(|| auth::load_or_create_token(&self.app_handle))
+    }
 
-// --- Event payload sent to the webview ---
+    /// Base URL of the running server, for links handed to other apps.
+    pub async fn server_origin(&self) -> Option<String> {
+        let port = (*self.server_port.lock().await)?;
+        let listening = (*self.listening.lock().await)?;
+        Some(bind::Listening { port, ..listening }.origin())
+    }
+}
+
+pub(crate) type SharedApiState = Arc<ApiState>;
+
+// --- Request sent down the bridge channel ---
 
 #[derive(Clone, Serialize)]
 pub struct McpToolRequest {
@@ -45,137 +184,295 @@ pub struct McpToolRequest {
 pub fn api_response(
     request_id: String,
     result: serde_json::Value,
+    mcp_tab_id: Option<String>,
     state: tauri::State<'_, SharedApiState>,
 ) {
-    let pending = state.pending.clone();
+    // Before the answer is released, so the next call sees where agents are
+    state.visibility.set_mcp_tab(mcp_tab_id);
+    let bridge = state.bridge.clone();
     tauri::async_runtime::spawn(async move {
-        let mut map = pending.lock().await;
-        if let Some(sender) = map.remove(&request_id) {
-            let _ = sender.send(result);
-        }
+        bridge.lock().await.answer(&request_id, result);
     });
 }
 
 #[tauri::command]
 pub async fn start_api_server(
     state: tauri::State<'_, SharedApiState>,
+    tls: Option<TlsConfig>,
+) -> Result<u16, String> {
+    let started = start_server(&state, tls).await;
+    match &started {
+        Ok(port) => {
+            state.health.set_running(true);
+            connections::update(&state.app_handle, Some(*port));
+        }
+        Err(e) => state.health.record_error(format!("Failed to start: {}", e)),
+    }
+    started
+}
+
+async fn start_server(
+    state: &tauri::State<'_, SharedApiState>,
+    tls: Option<TlsConfig>,
 ) -> Result<u16, String> {
     let mut shutdown_guard = state.server_shutdown.lock().await;
     if shutdown_guard.is_some() {
         return Err("API server is already running".to_string());
     }
 
+    let shared = Arc::clone(state.inner());
     let (shutdown_tx, shutdown_rx) = watch::channel(false);
-    *shutdown_guard = Some(shutdown_tx);
-    drop(shutdown_guard);
+    let settings = settings::current(&state.app_handle);
+    let ip = settings.mcp_bind.ip();
+    // A `tls` argument applies to this start only; the setting otherwise.
+    let tls_files = tls::files(&state.app_handle, &tls.unwrap_or(settings.mcp_tls), ip)?;
 
-    let shared = Arc::clone(state.inner());
-    let port = DEFAULT_PORT;
+    if settings.mcp_sidecar {
+        let port = sidecar::spawn(Arc::clone(&shared), shutdown_rx.clone(), tls_files.clone()).await?;
+        *shutdown_guard = Some(shutdown_tx);
+        *state.server_port.lock().await = Some(port);
+        *state.listening.lock().await = Some(bind::Listening { ip, port, tls: tls_files.is_some() });
+        drop(shutdown_guard);
+        stdio::write_port_file(&state.app_handle, port);
+        stdio::write_cert_file(&state.app_handle, tls_files.as_ref());
+        tauri::async_runtime::spawn(mdns::advertise(
+            Arc::clone(&shared),
+            ip,
+            port,
+            tls_files.is_some(),
+            shutdown_rx.clone(),
+        ));
+        tauri::async_runtime::spawn(presence::run_sweeper(shared, shutdown_rx));
+        return Ok(port);
+    }
 
-    tauri::async_runtime::spawn(async move {
-        let app = build_router(shared);
-        let addr = format!("127.0.0.1:{}", port);
-        let listener = match tokio::net::TcpListener::bind(&addr).await {
-            Ok(l) => l,
-            Err(e) => {
-                log::error!("Failed to bind API server on {}: {}", addr, e);
-                return;
-            }
-        };
+    // Bind while holding the guard so the port we report is the one we serve
+    // on, and a bind failure reaches the caller instead of a log line.
+    bind::check_exposure(ip, state.api_token())?;
+    let tls_config = tls_files.as_ref().map(tls::server_config).transpose()?;
+    let (listener, port) = bind_listener(ip, DEFAULT_PORT, PORT_FALLBACK_ATTEMPTS).await?;
 
-        log::info!("MCP server listening on http://{}/mcp", addr);
+    *shutdown_guard = Some(shutdown_tx);
+    *state.server_port.lock().await = Some(port);
+    *state.listening.lock().await = Some(bind::Listening { ip, port, tls: tls_config.is_some() });
+    drop(shutdown_guard);
+    stdio::write_port_file(&state.app_handle, port);
+    stdio::write_cert_file(&state.app_handle, tls_files.as_ref());
 
-        let mut rx = shutdown_rx;
-        axum::serve(listener, app)
-            .with_graceful_shutdown(async move {
-                while rx.changed().await.is_ok() {
-                    if *rx.borrow() {
-                        break;
-                    }
-                }
-            })
-            .await
-            .unwrap_or_else(|e| log::error!("MCP server error: {}", e));
+    tauri::async_runtime::spawn(presence::run_sweeper(
+        Arc::clone(&shared),
+        shutdown_rx.clone(),
+    ));
+    tauri::async_runtime::spawn(mdns::advertise(
+        Arc::clone(&shared),
+        ip,
+        port,
+        tls_config.is_some(),
+        shutdown_rx.clone(),
+    ));
 
-        log::info!("MCP server stopped");
-    });
+    tauri::async_runtime::spawn(supervisor::run(
+        shared,
+        listener,
+        tls_config,
+        SocketAddr::new(ip, port),
+        shutdown_rx,
+    ));
 
     Ok(port)
 }
 
+/// Bind to `start` on `ip`, moving up one port at a time while the port is
+/// in use.
+pub(crate) async fn bind_listener(
+    ip: IpAddr,
+    start: u16,
+    fallbacks: u16,
+) -> Result<(tokio::net::TcpListener, u16), String> {
+    let last = start.saturating_add(fallbacks);
+    for port in start..=last {
+        match tokio::net::TcpListener::bind((ip, port)).await {
+            Ok(listener) => {
+                if port != start {
+                    log::warn!("Port {} is in use; MCP server using {} instead", start, port);
+                }
+                return Ok((listener, port));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => continue,
+            Err(e) => return Err(format!("Failed to bind API server on port {}: {}", port, e)),
+        }
+    }
+    Err(format!(
+        "Failed to start API server: ports {}-{} are all in use",
+        start, last
+    ))
+}
+
 #[tauri::command]
 pub async fn stop_api_server(
     state: tauri::State<'_, SharedApiState>,
 ) -> Result<(), String> {
     let mut shutdown_guard = state.server_shutdown.lock().await;
     if let Some(tx) = shutdown_guard.take() {
+        let timeout = std::time::Duration::from_secs(drain::TIMEOUT_SECS);
+        let unanswered = state.drain.drain(timeout).await;
+        if unanswered > 0 {
+            log::warn!("Stopping the MCP server with {} tool calls unanswered", unanswered);
+            state.health.record_error(format!(
+                "Stopped with {} tool calls still waiting for the window",
+                unanswered
+            ));
+        }
         let _ = tx.send(true);
+        state.drain.finish();
+        *state.server_port.lock().await = None;
+        *state.listening.lock().await = None;
+        state.health.set_running(false);
+        stdio::remove_port_file(&state.app_handle);
+        connections::update(&state.app_handle, None);
         Ok(())
     } else {
         Err("API server is not running".to_string())
     }
 }
 
+/// The port the MCP server is listening on, if it is running.
 #[tauri::command]
-pub async fn get_api_status(
+pub async fn get_api_port(
     state: tauri::State<'_, SharedApiState>,
-) -> Result<bool, String> {
-    let guard = state.server_shutdown.lock().await;
-    Ok(guard.is_some())
+) -> Result<Option<u16>, String> {
+    Ok(*state.server_port.lock().await)
 }
 
-// --- Router (MCP only) ---
+// --- Router (MCP + embed/share viewers) ---
+
+/// gzip or Brotli, as the client's `Accept-Encoding` allows, for responses
+/// worth it: event streams (which must flush as they go) and images (already
+/// compressed) are left alone.
+fn compression() -> CompressionLayer<And<DefaultPredicate, SizeAbove>> {
+    CompressionLayer::new().compress_when(DefaultPredicate::new().and(SizeAbove::new(COMPRESS_MIN_BYTES)))
+}
 
-fn build_router(state: SharedApiState) -> Router {
+pub(crate) fn build_router(state: SharedApiState) -> Router {
     let cors = CorsLayer::new()
-        .allow_origin(AllowOrigin::predicate(|origin, _| {
-            let origin = origin.as_bytes();
-            origin == b"tauri://localhost"
-                || origin == b"http://localhost"
-                || origin == b"https://localhost"
-                || origin.starts_with(b"http://localhost:")
-                || origin.starts_with(b"http://127.0.0.1:")
-        }))
-        .allow_methods([axum::http::Method::GET, axum::http::Method::POST])
-        .allow_headers([axum::http::header::CONTENT_TYPE]);
+        .allow_origin(origins::allow_origin(state.app_handle.clone()))
+        .allow_methods([
+            axum::http::Method::GET,
+            axum::http::Method::POST,
+            axum::http::Method::PATCH,
+            axum::http::Method::DELETE,
+        ])
+        .allow_headers([
+            axum::http::header::CONTENT_TYPE,
+            axum::http::header::AUTHORIZATION,
+            session::SESSION_HEADER,
+        ])
+        .expose_headers([session::SESSION_HEADER]);
+
+    let mcp = Router::new()
+        .route(
+            "/mcp",
+            post(mcp_post_handler)
+                .get(mcp_sse_handler)
+                .delete(mcp_delete_handler),
+        )
+        .route("/mcp/ws", get(mcp_ws_handler))
+        .route("/sse", get(legacy_sse_handler))
+        .route(legacy_sse::MESSAGES_PATH, post(legacy_messages_handler))
+        .route("/api/v1/canvas", get(rest::get_canvas))
+        .route(
+            "/api/v1/shapes",
+            get(rest::list_shapes).post(rest::create_shape),
+        )
+        .route(
+            "/api/v1/shapes/{id}",
+            get(rest::get_shape)
+                .patch(rest::update_shape)
+                .delete(rest::delete_shape),
+        )
+        .route("/api/v1/tabs", get(rest::list_tabs).post(rest::create_tab))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            auth::require_bearer,
+        ));
 
     Router::new()
-        .route("/mcp", post(mcp_post_handler))
-        .route("/mcp", get(mcp_sse_handler))
+        .merge(mcp)
+        .route(openapi::PATH, get(openapi::openapi_handler))
+        .route(status::PATH, get(status::healthz_handler))
+        .route("/embed/{token}", get(embed::embed_page_handler))
+        .route("/embed/{token}/content", get(embed::embed_content_handler))
+        .route(
+            "/share/{id}",
+            get(sharing::share_page_handler).post(sharing::share_password_handler),
+        )
+        .route("/share/{id}/content", get(sharing::share_content_handler))
+        .layer(DefaultBodyLimit::disable())
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            request_log::record,
+        ))
+        .layer(middleware::from_fn(msgpack::negotiate))
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            body_limit::enforce,
+        ))
+        .layer(compression())
         .layer(cors)
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            drain::refuse_while_draining,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            hosts::require_allowed_host,
+        ))
         .with_state(state)
 }
 
-// --- Bridge: emit tool call to webview, await response ---
+// --- Bridge: send tool call to webview, await response ---
 
-async fn bridge_tool_call(
+pub(crate) async fn bridge_tool_call(
     state: &SharedApiState,
     tool_name: &str,
     arguments: serde_json::Value,
 ) -> Result<serde_json::Value, String> {
+    let _call = state.drain.begin()?;
     let request_id = Uuid::new_v4().to_string();
 
     let (tx, rx) = oneshot::channel();
-    {
-        let mut pending = state.pending.lock().await;
-        pending.insert(request_id.clone(), tx);
-    }
-
     let payload = McpToolRequest {
         request_id: request_id.clone(),
         tool_name: tool_name.to_string(),
         arguments,
     };
-
-    if let Err(e) = state.app_handle.emit("mcp-tool-request", &payload) {
-        log::error!("Failed to emit mcp-tool-request: {}", e);
-        let mut pending = state.pending.lock().await;
-        pending.remove(&request_id);
-        return Err(format!("Failed to emit event: {}", e));
+    let (sent, mut ready) = {
+        let mut bridge = state.bridge.lock().await;
+        (bridge.send(payload, tx), bridge.ready())
+    };
+    let sent = sent.unwrap_or_else(|e| {
+        log::error!("Bridge request {}: {}", request_id, e);
+        state.health.record_error(e);
+        false
+    });
+    if !sent {
+        // The window is loading; the call goes out when it registers again
+        let registered = tokio::time::timeout(
+            std::time::Duration::from_secs(bridge::READY_TIMEOUT_SECS),
+            ready.wait_for(|ready| *ready),
+        )
+        .await
+        .is_ok_and(|waited| waited.is_ok());
+        if !registered {
+            log::error!("Bridge request {}: the window never registered", request_id);
+            state.health.record_error(format!("{}: the window did not load in time", tool_name));
+            state.bridge.lock().await.abandon(&request_id);
+            return Err("The Napkin window did not load in time".to_string());
+        }
     }
 
     match tokio::time::timeout(
-        std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS),
+        std::time::Duration::from_secs(jobs::bridge_timeout_secs(REQUEST_TIMEOUT_SECS)),
         rx,
     )
     .await
@@ -183,12 +480,13 @@ async fn bridge_tool_call(
         Ok(Ok(value)) => Ok(value),
         Ok(Err(_)) => {
             log::error!("Bridge channel closed for request {}", request_id);
+            state.health.record_error(format!("{}: the window dropped the request", tool_name));
             Err("Internal error: bridge channel closed".to_string())
         }
         Err(_) => {
             log::error!("Bridge request {} timed out", request_id);
-            let mut pending = state.pending.lock().await;
-            pending.remove(&request_id);
+            state.health.record_error(format!("{}: the window did not answer in time", tool_name));
+            state.bridge.lock().await.abandon(&request_id);
             Err("Request timed out".to_string())
         }
     }
@@ -196,9 +494,9 @@ async fn bridge_tool_call(
 
 // --- MCP protocol ---
 
-const MCP_PROTOCOL_VERSION: &str = "2025-03-26";
-const MCP_SERVER_NAME: &str = "napkin";
-const MCP_SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub(crate) const MCP_PROTOCOL_VERSION: &str = "2025-03-26";
+pub(crate) const MCP_SERVER_NAME: &str = "napkin";
+pub(crate) const MCP_SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(Deserialize)]
 struct McpJsonRpcRequest {
@@ -210,7 +508,12 @@ struct McpJsonRpcRequest {
     params: serde_json::Value,
 }
 
-fn mcp_error(id: Option<serde_json::Value>, code: i64, message: &str) -> serde_json::Value {
+/// JSON-RPC code for bad params, which includes tools that cannot be called.
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+/// Start of the error for a tool the window's bridge does not implement.
+pub(crate) const TOOL_NOT_IMPLEMENTED: &str = "Tool not implemented by this version of Napkin";
+
+pub(crate) fn mcp_error(id: Option<serde_json::Value>, code: i64, message: &str) -> serde_json::Value {
     serde_json::json!({
         "jsonrpc": "2.0",
         "id": id,
@@ -221,7 +524,7 @@ fn mcp_error(id: Option<serde_json::Value>, code: i64, message: &str) -> serde_j
     })
 }
 
-fn mcp_result(id: Option<serde_json::Value>, result: serde_json::Value) -> serde_json::Value {
+pub(crate) fn mcp_result(id: Option<serde_json::Value>, result: serde_json::Value) -> serde_json::Value {
     serde_json::json!({
         "jsonrpc": "2.0",
         "id": id,
@@ -233,16 +536,22 @@ fn mcp_tools_list() -> serde_json::Value {
     serde_json::json!([
         {
             "name": "get_canvas",
-            "description": "Get the full canvas state including all shapes, viewport, and groups",
+            "description": "Get the full canvas state including all shapes, viewport, and groups, with the board's current revision. Pass a revision you already have as sinceRevision to get only what changed since",
             "inputSchema": {
                 "type": "object",
-                "properties": {},
+                "properties": {
+                    "sinceRevision": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "A revision from an earlier get_canvas. If the board is unchanged the result is just { revision, unchanged: true }; otherwise shapes lists only shapes created or updated since, and deletedIds the shapes removed"
+                    }
+                },
                 "additionalProperties": false,
             }
         },
         {
             "name": "list_shapes",
-            "description": "List all shapes on the canvas, optionally filtered by type",
+            "description": "List the shapes on the canvas, optionally filtered by type, author, region or text, a page at a time on big boards. total is how many shapes match; pass nextOffset back as offset for the next page",
             "inputSchema": {
                 "type": "object",
                 "properties": {
@@ -250,6 +559,36 @@ fn mcp_tools_list() -> serde_json::Value {
                         "type": "string",
                         "description": "Filter by shape type (rectangle, ellipse, triangle, diamond, hexagon, star, cloud, cylinder, sticky, line, arrow, freedraw, text)",
                         "enum": ["rectangle", "ellipse", "triangle", "diamond", "hexagon", "star", "cloud", "cylinder", "sticky", "line", "arrow", "freedraw", "text"]
+                    },
+                    "createdBy": {
+                        "type": "string",
+                        "description": "Only shapes created by this author: a name from a shape's createdBy, or \"user\" / \"agent\" for everything drawn by people or by MCP clients"
+                    },
+                    "region": {
+                        "type": "object",
+                        "description": "Only shapes whose bounds overlap this box, in canvas pixels",
+                        "properties": {
+                            "x": { "type": "number" },
+                            "y": { "type": "number" },
+                            "width": { "type": "number", "minimum": 0 },
+                            "height": { "type": "number", "minimum": 0 }
+                        },
+                        "required": ["x", "y", "width", "height"],
+                        "additionalProperties": false
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Only shapes whose text contains this (case-insensitive)"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Skip this many matching shapes, in stacking order (default 0)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Most shapes to return (default: all that fit the response budget)"
                     }
                 },
                 "additionalProperties": false,
@@ -292,7 +631,8 @@ fn mcp_tools_list() -> serde_json::Value {
                     "stickyColor": { "type": "string", "description": "Sticky note background color" },
                     "rotation": { "type": "number", "description": "Rotation in degrees" },
                     "strokeStyle": { "type": "string", "description": "Stroke style", "enum": ["solid", "dashed", "dotted"] },
-                    "fillStyle": { "type": "string", "description": "Fill style", "enum": ["hachure", "solid", "zigzag", "cross-hatch", "dots"] }
+                    "fillStyle": { "type": "string", "description": "Fill style", "enum": ["hachure", "solid", "zigzag", "cross-hatch", "dots"] },
+                    "autoFit": { "type": "boolean", "description": "Grow the shape so its text always fits" }
                 },
                 "required": ["type", "x", "y"],
                 "additionalProperties": false,
@@ -317,7 +657,8 @@ fn mcp_tools_list() -> serde_json::Value {
                     "text": { "type": "string" },
                     "rotation": { "type": "number" },
                     "strokeStyle": { "type": "string" },
-                    "fillStyle": { "type": "string" }
+                    "fillStyle": { "type": "string" },
+                    "autoFit": { "type": "boolean", "description": "Grow the shape so its text always fits" }
                 },
                 "required": ["id"],
                 "additionalProperties": false,
@@ -600,22 +941,238 @@ fn mcp_tools_list() -> serde_json::Value {
                 },
                 "additionalProperties": false,
             }
+        },
+        {
+            "name": "export_png",
+            "description": "Render the board, or just the listed shapes, as a PNG image the way the user sees it, and return it as an image content block. Use it to look at a diagram or to attach it elsewhere. The image is scaled down if needed to stay within maxDimension pixels on its longest side.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "shapeIds": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Shapes to render (default: the whole board)"
+                    },
+                    "scale": { "type": "number", "minimum": 0.1, "maximum": 4, "description": "Pixels per canvas unit (default 1)" },
+                    "padding": { "type": "number", "minimum": 0, "description": "Margin around the shapes, in canvas pixels (default 40)" },
+                    "backgroundColor": { "type": "string", "description": "CSS color behind the shapes (default #ffffff)" },
+                    "maxDimension": { "type": "integer", "minimum": 64, "maximum": 4096, "description": "Longest side of the image in pixels (default 2048)" },
+                    "renderer": { "type": "string", "enum": ["webview", "native"], "description": "webview (default) draws it as the user sees it; native draws clean strokes and flat fills without the canvas" },
+                    "includeOverlapping": { "type": "boolean", "description": "With shapeIds, frame the image on those shapes but also draw any other shape reaching into the frame, clipped at its edge (default false; always uses the native renderer)" }
+                },
+                "additionalProperties": false,
+            }
+        },
+        {
+            "name": "screenshot_viewport",
+            "description": "Capture exactly what the user is looking at right now: the visible part of the active board at its current pan and zoom, with selection and other on-screen marks, as an image content block. Unlike export_png it does not frame the whole board. The result also gives the viewport, the visible region in canvas coordinates and the ids of the shapes in it.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "maxDimension": { "type": "integer", "minimum": 64, "maximum": 4096, "description": "Longest side of the image in pixels (default 2048)" }
+                },
+                "additionalProperties": false,
+            }
         }
     ])
 }
 
 // --- MCP method dispatch ---
 
+/// Bridged tools plus the ones answered directly by the Rust layer.
+pub(crate) fn all_tools() -> serde_json::Value {
+    let mut tools = mcp_tools_list();
+    if let Some(list) = tools.as_array_mut() {
+        list.extend(lock::lock_tools());
+        list.extend(integrity::integrity_tools());
+        list.extend(constraints::constraint_tools());
+        list.extend(text_metrics::text_tools());
+        list.extend(trace::trace_tools());
+        list.extend(photo::photo_tools());
+        list.extend(units::unit_tools());
+        list.extend(outline::outline_tools());
+        list.extend(partition::partition_tools());
+        list.extend(restyle::restyle_tools());
+        list.extend(translate::translate_tools());
+        list.extend(zorder::zorder_tools());
+        list.extend(jobs::job_tools());
+        list.extend(revision::revision_tools());
+        list.extend(region::region_tools());
+        list.extend(hit_test::hit_test_tools());
+        list.extend(lint::lint_tools());
+        list.iter_mut().for_each(budget::extend_schema);
+        list.iter_mut().for_each(structured::extend_schema);
+    }
+    tools
+}
+
+/// Every tool the webview is expected to answer.
+pub(crate) fn bridged_tools() -> Vec<String> {
+    let tools = mcp_tools_list();
+    let advertised = tools
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|t| t["name"].as_str());
+    advertised
+        .chain(diagnostics::BRIDGE_ONLY_TOOLS.iter().copied())
+        .map(str::to_string)
+        .collect()
+}
+
+pub(crate) fn is_known_tool(name: &str) -> bool {
+    all_tools()
+        .as_array()
+        .is_some_and(|tools| tools.iter().any(|t| t["name"] == name))
+}
+
+/// Tools that change the document. Viewport, selection and snap settings are
+/// view state and stay allowed while the canvas is locked.
+pub(crate) fn is_mutating_tool(name: &str) -> bool {
+    matches!(
+        name,
+        "create_shape"
+            | "update_shape"
+            | "delete_shape"
+            | "create_image"
+            | "create_connection"
+            | "create_tab"
+            | "rename_tab"
+            | "bring_to_front"
+            | "send_to_back"
+            | "bring_forward"
+            | "send_backward"
+            | "group_shapes"
+            | "ungroup"
+            | "clear_canvas"
+            | "batch_operations"
+            | "reorganize"
+            | "repair_document"
+            | "add_constraint"
+            | "remove_constraint"
+            | "fit_shape_to_text"
+            | "trace_image"
+            | "cleanup_photo"
+            | "set_document_scale"
+            | "partition_canvas"
+            | "bulk_update"
+            | "translate_canvas"
+            | "set_shape_order"
+    )
+}
+
+/// Route a tool call: Rust-handled tools first, then the webview bridge.
+async fn call_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: serde_json::Value,
+    client: &McpClient,
+) -> Result<serde_json::Value, String> {
+    let client_name = client.name();
+    let client_name = client_name.as_deref();
+    if let Some(result) = lock::call_lock_tool(state, tool_name, &arguments, client.id()).await {
+        return result;
+    }
+    if let Some(result) = jobs::call_job_tool(state, tool_name, &arguments, client).await {
+        return result;
+    }
+    if let Some(result) = revision::call_revision_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = region::call_region_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = hit_test::call_hit_test_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = lint::call_lint_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = render::call_render_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    let _turn = if is_mutating_tool(tool_name) {
+        lock::check_agent_write(state, client.id()).await?;
+        approval::check(state, tool_name, &arguments, client_name).await?;
+        Some(queue::enter(state, &arguments).await)
+    } else {
+        None
+    };
+    if let Some(result) = integrity::call_integrity_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = constraints::call_constraint_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = text_metrics::call_text_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = trace::call_trace_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = photo::call_photo_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = units::call_unit_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = outline::call_outline_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = partition::call_partition_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = restyle::call_restyle_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+    if let Some(result) = translate::call_translate_tool(state, tool_name, &arguments, client).await {
+        return result;
+    }
+    if let Some(result) = zorder::call_zorder_tool(state, tool_name, &arguments).await {
+        return result;
+    }
+
+    let mut arguments = arguments;
+    let canvas_read = revision::prepare(state, tool_name, &mut arguments).await?;
+    if let Some(unchanged) = canvas_read.as_ref().and_then(CanvasRead::unchanged) {
+        return Ok(unchanged);
+    }
+    let settings = settings::current(&state.app_handle);
+    let budget = budget::take(tool_name, &mut arguments, &settings.response_budget)?;
+    let scale = units::current_scale(state).await;
+    units::convert_arguments(tool_name, &mut arguments, scale.as_ref())?;
+    shape_defaults::apply(&settings.shape_defaults, tool_name, &mut arguments);
+    author::stamp(&author::agent(client_name), tool_name, &mut arguments);
+    text_metrics::prepare_tool_call(state, tool_name, &mut arguments).await?;
+    model::validate_arguments(tool_name, &arguments)?;
+    let mut result = bridge_tool_call(state, tool_name, arguments).await?;
+    if let Some(read) = &canvas_read {
+        result = read.apply(result);
+    }
+    if is_mutating_tool(tool_name) {
+        if let Err(e) = constraints::resolve_via_bridge(state).await {
+            log::warn!("Constraint re-solve after {} failed: {}", tool_name, e);
+        }
+    }
+    match budget {
+        Some(request) => budget::apply(result, &request),
+        None => Ok(result),
+    }
+}
+
 async fn handle_mcp_method(
     state: &SharedApiState,
     req: McpJsonRpcRequest,
+    client: &McpClient,
 ) -> serde_json::Value {
     match req.method.as_str() {
         "initialize" => {
             mcp_result(req.id, serde_json::json!({
                 "protocolVersion": MCP_PROTOCOL_VERSION,
                 "capabilities": {
-                    "tools": {}
+                    "tools": { "listChanged": true },
+                    "resources": { "subscribe": true },
+                    "prompts": {}
                 },
                 "serverInfo": {
                     "name": MCP_SERVER_NAME,
@@ -630,33 +1187,146 @@ async fn handle_mcp_method(
             mcp_result(req.id, serde_json::json!({}))
         }
         "tools/list" => {
+            let settings = settings::current(&state.app_handle);
+            let exposure = &settings.tool_exposure;
+            let tools = exposure.filter_tools(
+                exposure.profile_for(client.name().as_deref()),
+                state.bridge_drift.lock().await.without_missing(all_tools()),
+            );
+            let mut tools = state.tool_filter.lock().await.filter_tools(tools);
+            if read_only::is_enabled(state) {
+                tools = read_only::filter_tools(tools);
+            }
+            let tools = presence::with_focus_point(units::with_lengths(
+                shape_defaults::describe_tools(tools, &settings.shape_defaults),
+            ));
             mcp_result(req.id, serde_json::json!({
-                "tools": mcp_tools_list()
+                "tools": exposure.prefixed(tools)
             }))
         }
         "tools/call" => {
-            let tool_name = req.params.get("name")
+            let exposure = settings::current(&state.app_handle).tool_exposure;
+            let tool_name = exposure.unprefixed(
+                req.params.get("name")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or(""),
+                is_known_tool,
+            );
+            let client_name = client.name();
+            let mut arguments = req.params.get("arguments")
+                .cloned()
+                .unwrap_or(serde_json::json!({}));
+            let audit = |arguments: &serde_json::Value, result: Result<(), &str>| {
+                let entry = AuditEntry::new(
+                    client.id(),
+                    client_name.as_deref(),
+                    tool_name,
+                    arguments,
+                    result,
+                );
+                audit::record(&state.app_handle, entry);
+            };
+
+            if read_only::is_enabled(state)
+                && !read_only::allows_call(tool_name, &arguments, &exposure)
+            {
+                audit(&arguments, Err("Refused in read-only mode"));
+                return read_only::permission_error(req.id, tool_name);
+            }
+            if state.visibility.refuses(tool_name, &arguments) {
+                audit(&arguments, Err("Refused: the board is hidden from agents"));
+                return visibility::permission_error(req.id, tool_name);
+            }
+            if state.bridge_drift.lock().await.is_missing(tool_name) {
+                let msg = format!("{}: {}", TOOL_NOT_IMPLEMENTED, tool_name);
+                audit(&arguments, Err(&msg));
+                return mcp_error(req.id, INVALID_PARAMS, &msg);
+            }
+            let profile = exposure.profile_for(client_name.as_deref());
+            if !exposure.allows(profile, tool_name) {
+                let msg = format!("Tool not available in the '{}' profile: {}", profile, tool_name);
+                audit(&arguments, Err(&msg));
+                return mcp_error(req.id, INVALID_PARAMS, &msg);
+            }
+            if !state.tool_filter.lock().await.allows(tool_name) {
+                let msg = format!("Tool disabled in Napkin: {}", tool_name);
+                audit(&arguments, Err(&msg));
+                return mcp_error(req.id, INVALID_PARAMS, &msg);
+            }
+
+            if let Some(point) = presence::take_focus_point(&mut arguments) {
+                presence::report(state, point, tool_name, client.id()).await;
+            }
+
+            let recorded = arguments.clone();
+            let mut result = tool_errors::check(call_tool(state, tool_name, arguments, client).await);
+            if tool_name == "list_tabs" {
+                result = result.map(|tabs| state.visibility.filter_tabs(tabs));
+            }
+            audit(&recorded, result.as_ref().map(|_| ()).map_err(|e| e.message.as_str()));
+            match result {
+                Ok(content) if tool_name == "export_png" || tool_name == "screenshot_viewport" => {
+                    mcp_result(req.id, structured::image_result(&content))
+                }
+                Ok(content) => mcp_result(req.id, structured::call_result(&content)),
+                Err(error) => mcp_result(req.id, error.call_result()),
+            }
+        }
+        "resources/list" => {
+            mcp_result(req.id, resources::list(state).await)
+        }
+        "resources/read" => {
+            let uri = req.params.get("uri")
+                .and_then(|u| u.as_str())
+                .unwrap_or("");
+            if resources::is_hidden(state, uri) {
+                return visibility::permission_error(req.id, "Reading this resource");
+            }
+            match resources::read(state, uri).await {
+                Ok(Some(result)) => mcp_result(req.id, result),
+                Ok(None) => mcp_error(req.id, -32002, &format!("Resource not found: {}", uri)),
+                Err(msg) => mcp_error(req.id, -32603, &msg),
+            }
+        }
+        "resources/subscribe" | "resources/unsubscribe" => {
+            let uri = req.params.get("uri")
+                .and_then(|u| u.as_str())
+                .unwrap_or("");
+            if !resources::is_resource_uri(uri) {
+                return mcp_error(req.id, -32002, &format!("Resource not found: {}", uri));
+            }
+            if req.method == "resources/subscribe" && resources::is_hidden(state, uri) {
+                return visibility::permission_error(req.id, "Subscribing to this resource");
+            }
+            let mut subs = state.subscriptions.lock().await;
+            if req.method == "resources/subscribe" {
+                subs.subscribe(client.id(), uri);
+            } else {
+                subs.unsubscribe(client.id(), uri);
+            }
+            mcp_result(req.id, serde_json::json!({}))
+        }
+        "prompts/list" => {
+            mcp_result(req.id, prompts::list())
+        }
+        "prompts/get" => {
+            let name = req.params.get("name")
                 .and_then(|n| n.as_str())
                 .unwrap_or("");
             let arguments = req.params.get("arguments")
                 .cloned()
                 .unwrap_or(serde_json::json!({}));
-
-            let result = bridge_tool_call(state, tool_name, arguments).await;
-            match result {
-                Ok(content) => mcp_result(req.id, serde_json::json!({
-                    "content": [{
-                        "type": "text",
-                        "text": serde_json::to_string_pretty(&content).unwrap_or_default()
-                    }]
-                })),
-                Err(msg) => mcp_result(req.id, serde_json::json!({
-                    "isError": true,
-                    "content": [{
-                        "type": "text",
-                        "text": msg
-                    }]
-                })),
+            let hidden = match arguments.get("tabId").and_then(|t| t.as_str()) {
+                Some(tab_id) => state.visibility.is_hidden(tab_id),
+                None => state.visibility.target_hidden(),
+            };
+            if hidden {
+                return visibility::permission_error(req.id, "This prompt");
+            }
+            match prompts::get(state, name, &arguments).await {
+                Ok(result) => mcp_result(req.id, result),
+                Err(PromptError::InvalidParams(msg)) => mcp_error(req.id, -32602, &msg),
+                Err(PromptError::Failed(msg)) => mcp_error(req.id, -32603, &msg),
             }
         }
         _ => {
@@ -665,72 +1335,391 @@ async fn handle_mcp_method(
     }
 }
 
+/// Whether a batched request only reads, so it can run alongside the reads
+/// next to it. Anything else, including tools that only move the viewport or
+/// the agents' tab, runs on its own and in order.
+fn is_read_request(req: &McpJsonRpcRequest, exposure: &ToolExposure) -> bool {
+    match req.method.as_str() {
+        "ping" | "tools/list" | "resources/list" | "resources/read"
+        | "prompts/list" | "prompts/get" => true,
+        "tools/call" => {
+            let name = req.params.get("name").and_then(|n| n.as_str()).unwrap_or("");
+            let arguments = req.params.get("arguments").unwrap_or(&serde_json::Value::Null);
+            read_only::allows_call(exposure.unprefixed(name, is_known_tool), arguments, exposure)
+        }
+        _ => false,
+    }
+}
+
 // --- HTTP handlers ---
 
-async fn mcp_post_handler(
-    AxumState(state): AxumState<SharedApiState>,
-    Json(body): Json<serde_json::Value>,
-) -> Response {
+/// Handle one JSON-RPC message or batch, as received over any transport.
+/// `client` is the connection's MCP client, for attributing the shapes it
+/// creates. `None` when there is nothing to send back (notifications only).
+pub(crate) async fn handle_mcp_message(
+    state: &SharedApiState,
+    body: serde_json::Value,
+    client: &McpClient,
+) -> Option<serde_json::Value> {
+    client.observe(&body);
+    clients::observe(state, client.id(), &body).await;
+    // Answers to our sampling requests are not requests themselves.
+    if sampling::is_response(&body) {
+        sampling::deliver(state, body).await;
+        return None;
+    }
+    let mut body = body;
+    if let serde_json::Value::Array(batch) = &mut body {
+        let mut requests = Vec::new();
+        for message in batch.drain(..) {
+            if sampling::is_response(&message) {
+                sampling::deliver(state, message).await;
+            } else {
+                requests.push(message);
+            }
+        }
+        *batch = requests;
+    }
     if body.is_array() {
         let requests: Vec<McpJsonRpcRequest> = match serde_json::from_value(body) {
             Ok(r) => r,
-            Err(e) => {
-                let err = mcp_error(None, -32700, &format!("Parse error: {}", e));
-                return Json(err).into_response();
-            }
+            Err(e) => return Some(mcp_error(None, -32700, &format!("Parse error: {}", e))),
         };
 
+        let exposure = settings::current(&state.app_handle).tool_exposure;
         let mut results = Vec::new();
-        for req in requests {
-            let result = handle_mcp_method(&state, req).await;
-            if !result.is_null() {
-                results.push(result);
+        let mut requests = requests.into_iter().peekable();
+        while let Some(req) = requests.next() {
+            if !is_read_request(&req, &exposure) {
+                results.push(handle_mcp_method(state, req, client).await);
+                continue;
             }
+            // Reads in a row run together; the next write waits for them
+            let mut reads = vec![req];
+            while let Some(req) = requests.next_if(|r| is_read_request(r, &exposure)) {
+                reads.push(req);
+            }
+            let handled = reads.into_iter().map(|req| handle_mcp_method(state, req, client));
+            results.extend(join_all(handled).await);
         }
-        Json(serde_json::Value::Array(results)).into_response()
+        results.retain(|result| !result.is_null());
+        (!results.is_empty()).then_some(serde_json::Value::Array(results))
     } else {
         let req: McpJsonRpcRequest = match serde_json::from_value(body) {
             Ok(r) => r,
-            Err(e) => {
-                let err = mcp_error(None, -32700, &format!("Parse error: {}", e));
-                return Json(err).into_response();
-            }
+            Err(e) => return Some(mcp_error(None, -32700, &format!("Parse error: {}", e))),
         };
 
         let is_notification = req.id.is_none();
-        let result = handle_mcp_method(&state, req).await;
+        let result = handle_mcp_method(state, req, client).await;
+        (!is_notification && !result.is_null()).then_some(result)
+    }
+}
+
+async fn mcp_post_handler(
+    AxumState(state): AxumState<SharedApiState>,
+    peer: Option<Extension<ConnectInfo<PeerAddr>>>,
+    headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> Response {
+    let is_initialize = body.get("method").and_then(|m| m.as_str()) == Some("initialize");
+    let client = if is_initialize {
+        McpClient::default()
+    } else {
+        match session::require(&state, &headers).await {
+            Ok(session) => McpClient::for_session(&session),
+            Err(rejection) => return rejection,
+        }
+    };
 
-        if is_notification || result.is_null() {
-            StatusCode::ACCEPTED.into_response()
-        } else {
-            Json(result).into_response()
+    let params = body.get("params").cloned().unwrap_or_default();
+    let Some(result) = handle_mcp_message(&state, body, &client).await else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+    let mut response = Json(&result).into_response();
+    if is_initialize && result.get("error").is_none() {
+        let session = state
+            .sessions
+            .lock()
+            .await
+            .open(&params, embed::now_secs());
+        if let Ok(id) = HeaderValue::from_str(&session.id) {
+            response.headers_mut().insert(session::SESSION_HEADER, id);
+        }
+        clients::connect(&state, &session.id, Transport::Http, remote_addr(peer)).await;
+        let client = state.clients.lock().await.identify(&session.id, &params);
+        if let Some(client) = client {
+            clients::announce(&state, &client);
         }
     }
+    response
+}
+
+fn remote_addr(peer: Option<Extension<ConnectInfo<PeerAddr>>>) -> Option<String> {
+    peer.map(|Extension(ConnectInfo(PeerAddr(addr)))| addr.to_string())
+}
+
+async fn mcp_delete_handler(
+    AxumState(state): AxumState<SharedApiState>,
+    headers: HeaderMap,
+) -> Response {
+    session::end(&state, &headers).await
+}
+
+async fn mcp_ws_handler(
+    AxumState(state): AxumState<SharedApiState>,
+    peer: Option<Extension<ConnectInfo<PeerAddr>>>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let shutdown = state
+        .server_shutdown
+        .lock()
+        .await
+        .as_ref()
+        .map(|tx| tx.subscribe());
+    let client = Arc::new(McpClient::default());
+    let (tx, notifications) = tokio::sync::mpsc::unbounded_channel();
+    canvas_events::forward(&state.canvas_events, tx.clone());
+    state.subscriptions.lock().await.attach(client.id(), tx);
+    ws.on_upgrade(move |socket| async move {
+        clients::connect(&state, client.id(), Transport::WebSocket, remote_addr(peer)).await;
+        let handler_state = Arc::clone(&state);
+        let handler_client = Arc::clone(&client);
+        serve_json_rpc_socket(socket, shutdown, Some(notifications), move |body| {
+            let state = Arc::clone(&handler_state);
+            let client = Arc::clone(&handler_client);
+            async move { handle_mcp_message(&state, body, &client).await }
+        })
+        .await;
+        forget_client(&state, client.id()).await;
+    })
+}
+
+/// JSON-RPC over WebSocket: one message or batch per text frame. Requests
+/// are handled concurrently, so a slow tool call does not hold up pings or
+/// other calls; replies carry the request id and may arrive out of order.
+/// Messages from `notifications` are sent as they arrive.
+pub(crate) async fn serve_json_rpc_socket<F, Fut>(
+    mut socket: WebSocket,
+    shutdown: Option<watch::Receiver<bool>>,
+    mut notifications: Option<tokio::sync::mpsc::UnboundedReceiver<serde_json::Value>>,
+    handle: F,
+) where
+    F: Fn(serde_json::Value) -> Fut,
+    Fut: std::future::Future<Output = Option<serde_json::Value>> + Send + 'static,
+{
+    let (reply_tx, mut reply_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    // Without a running server there is nothing to wait for; keep a sender
+    // alive so `changed()` stays pending.
+    let (_idle, idle_rx) = watch::channel(false);
+    let mut shutdown = shutdown.unwrap_or(idle_rx);
+
+    loop {
+        tokio::select! {
+            frame = socket.recv() => {
+                let text = match frame {
+                    Some(Ok(WsMessage::Text(text))) => text,
+                    Some(Ok(WsMessage::Close(_))) | None | Some(Err(_)) => break,
+                    // Pings are answered by the socket itself.
+                    Some(Ok(_)) => continue,
+                };
+                let body = match serde_json::from_str::<serde_json::Value>(text.as_str()) {
+                    Ok(body) => body,
+                    Err(e) => {
+                        let err = mcp_error(None, -32700, &format!("Parse error: {}", e));
+                        let _ = reply_tx.send(err.to_string());
+                        continue;
+                    }
+                };
+                let reply = handle(body);
+                let reply_tx = reply_tx.clone();
+                tokio::spawn(async move {
+                    if let Some(reply) = reply.await {
+                        let _ = reply_tx.send(reply.to_string());
+                    }
+                });
+            }
+            Some(reply) = reply_rx.recv() => {
+                if socket.send(WsMessage::Text(reply.into())).await.is_err() {
+                    break;
+                }
+            }
+            note = next_notification(&mut notifications) => {
+                let Some(note) = note else {
+                    notifications = None;
+                    continue;
+                };
+                if socket.send(WsMessage::Text(note.to_string().into())).await.is_err() {
+                    break;
+                }
+            }
+            changed = shutdown.changed() => {
+                if changed.is_err() || *shutdown.borrow() {
+                    let _ = socket.send(WsMessage::Close(None)).await;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// The next notification, or never when there is no channel.
+pub(crate) async fn next_notification(
+    notifications: &mut Option<tokio::sync::mpsc::UnboundedReceiver<serde_json::Value>>,
+) -> Option<serde_json::Value> {
+    match notifications {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
 }
 
+async fn legacy_sse_handler(
+    AxumState(state): AxumState<SharedApiState>,
+    peer: Option<Extension<ConnectInfo<PeerAddr>>>,
+) -> Response {
+    let shutdown = state
+        .server_shutdown
+        .lock()
+        .await
+        .as_ref()
+        .map(|tx| tx.subscribe());
+    let client = Arc::new(McpClient::default());
+    clients::connect(&state, client.id(), Transport::Sse, remote_addr(peer)).await;
+    let (tx, notifications) = tokio::sync::mpsc::unbounded_channel();
+    canvas_events::forward(&state.canvas_events, tx.clone());
+    state.subscriptions.lock().await.attach(client.id(), tx);
+    let heartbeat = settings::current(&state.app_handle).sse_heartbeat.interval();
+    let sessions = Arc::clone(&state.legacy_sse);
+    legacy_sse::connect(
+        sessions,
+        client,
+        shutdown,
+        Some(notifications),
+        heartbeat,
+        move |client| async move { forget_client(&state, client.id()).await },
+    )
+    .await
+    .into_response()
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LegacyMessageQuery {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+}
+
+/// The reply goes out on the client's `/sse` stream, not in this response.
+async fn legacy_messages_handler(
+    AxumState(state): AxumState<SharedApiState>,
+    Query(query): Query<LegacyMessageQuery>,
+    Json(body): Json<serde_json::Value>,
+) -> Response {
+    let Some((stream, client)) = state.legacy_sse.lock().await.get(&query.session_id) else {
+        return (StatusCode::NOT_FOUND, "Unknown SSE session").into_response();
+    };
+    tokio::spawn(async move {
+        if let Some(reply) = handle_mcp_message(&state, body, &client).await {
+            let _ = stream.send(legacy_sse::message_event(&reply));
+        }
+    });
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// `GET /mcp`: canvas change events, plus resource notifications for the
+/// caller's session when the request names one.
 async fn mcp_sse_handler(
-    AxumState(_state): AxumState<SharedApiState>,
-) -> Sse<impl tokio_stream::Stream<Item = Result<SseEvent, std::convert::Infallible>>> {
-    let stream = tokio_stream::once(Ok(SseEvent::default().data(
-        serde_json::to_string(&serde_json::json!({
-            "jsonrpc": "2.0",
-            "method": "notifications/ready",
-        }))
-        .unwrap(),
-    )));
+    AxumState(state): AxumState<SharedApiState>,
+    headers: HeaderMap,
+) -> Response {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut session_id = None;
+    if headers.contains_key(session::SESSION_HEADER) {
+        let session = match session::require(&state, &headers).await {
+            Ok(session) => session,
+            Err(rejection) => return rejection,
+        };
+        state.subscriptions.lock().await.attach(&session.id, tx.clone());
+        session_id = Some(session.id);
+    }
+    canvas_events::forward(&state.canvas_events, tx.clone());
+    let heartbeat = settings::current(&state.app_handle).sse_heartbeat.interval();
+    state.event_streams.fetch_add(1, Ordering::Relaxed);
+    tokio::spawn(async move {
+        // The receiver is dropped when a write to the client fails.
+        tx.closed().await;
+        state.event_streams.fetch_sub(1, Ordering::Relaxed);
+        if let Some(id) = session_id {
+            state.subscriptions.lock().await.detach(&id, &tx);
+        }
+    });
+    mcp_event_stream(rx, heartbeat).into_response()
+}
 
-    let stream = stream.chain(tokio_stream::pending());
-    Sse::new(stream).keep_alive(KeepAlive::default())
+/// Drop what a connection that ended left behind: its resource
+/// subscriptions, the agent cursors it reported, and its entry in the
+/// client list.
+pub(crate) async fn forget_client(state: &SharedApiState, client_id: &str) {
+    state.subscriptions.lock().await.forget(client_id);
+    state.clients.lock().await.disconnect(client_id);
+    presence::forget_client(state, client_id).await;
+}
+
+/// A `notifications/ready` event, then each message from `notifications`.
+pub(crate) fn mcp_event_stream(
+    notifications: tokio::sync::mpsc::UnboundedReceiver<serde_json::Value>,
+    heartbeat: std::time::Duration,
+) -> Sse<impl tokio_stream::Stream<Item = Result<SseEvent, std::convert::Infallible>>> {
+    let ready = tokio_stream::once(serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/ready",
+    }));
+    let stream = ready
+        .chain(tokio_stream::wrappers::UnboundedReceiverStream::new(notifications))
+        .map(|message| Ok(SseEvent::default().data(message.to_string())));
+    Sse::new(stream).keep_alive(legacy_sse::keep_alive(heartbeat))
 }
 
 // --- Public helpers for lib.rs ---
 
 pub fn create_api_state(app_handle: tauri::AppHandle) -> SharedApiState {
-    Arc::new(ApiState {
-        pending: Arc::new(Mutex::new(HashMap::new())),
+    let (jobs, job_queue) = JobRegistry::new();
+    let state = Arc::new(ApiState {
+        bridge: Arc::new(Mutex::new(Bridge::default())),
+        drain: Drain::default(),
         app_handle,
         server_shutdown: Arc::new(Mutex::new(None)),
-    })
+        server_port: Arc::new(Mutex::new(None)),
+        listening: Arc::new(Mutex::new(None)),
+        embed_key: embed::generate_key(),
+        api_token: OnceLock::new(),
+        shares: Arc::new(Mutex::new(ShareRegistry::default())),
+        presence: Arc::new(Mutex::new(PresenceRegistry::default())),
+        canvas_lock: Arc::new(Mutex::new(CanvasLock::default())),
+        constraints: Arc::new(Mutex::new(ConstraintSet::default())),
+        scale: Arc::new(Mutex::new(None)),
+        spatial: Arc::new(Mutex::new(SpatialIndex::default())),
+        sessions: Arc::new(Mutex::new(SessionRegistry::default())),
+        legacy_sse: Arc::new(Mutex::new(SseSessions::default())),
+        subscriptions: Arc::new(Mutex::new(Subscriptions::default())),
+        canvas_events: canvas_events::channel(),
+        bridge_drift: Arc::new(Mutex::new(BridgeDrift::default())),
+        tunnel: Arc::new(Mutex::new(Tunnel::default())),
+        event_streams: Arc::new(AtomicUsize::new(0)),
+        approvals: Arc::new(Mutex::new(Approvals::default())),
+        tool_filter: Arc::new(Mutex::new(ToolFilter::default())),
+        read_only: Arc::new(AtomicBool::new(false)),
+        sampling: Arc::new(Mutex::new(PendingRequests::default())),
+        visibility: Arc::new(TabVisibility::default()),
+        mdns_document: watch::channel(String::new()).0,
+        health: Arc::new(Health::default()),
+        clients: Arc::new(Mutex::new(ClientRegistry::default())),
+        mutations: MutationQueue::default(),
+        jobs: Arc::new(Mutex::new(jobs)),
+        revisions: Arc::new(Mutex::new(Revisions::default())),
+        regions: Arc::new(Mutex::new(RegionIndex::default())),
+    });
+    jobs::run_jobs(Arc::clone(&state), job_queue);
+    state
 }
 
 #[cfg(test)]
@@ -753,6 +1742,71 @@ mod tests {
         assert_eq!(err["error"]["code"], -32700);
     }
 
+    #[test]
+    fn only_reads_run_concurrently_in_a_batch() {
+        let request = |method: &str, tool: &str| -> McpJsonRpcRequest {
+            serde_json::from_value(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": { "name": tool },
+            }))
+            .unwrap()
+        };
+        let exposure = ToolExposure {
+            prefix: "napkin_".to_string(),
+            ..ToolExposure::default()
+        };
+        assert!(is_read_request(&request("tools/call", "get_shape"), &exposure));
+        assert!(is_read_request(&request("tools/call", "napkin_list_shapes"), &exposure));
+        assert!(is_read_request(&request("resources/read", ""), &exposure));
+        assert!(!is_read_request(&request("tools/call", "update_shape"), &exposure));
+        assert!(!is_read_request(&request("tools/call", "switch_tab"), &exposure));
+        let job = |tool: &str| -> McpJsonRpcRequest {
+            serde_json::from_value(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": { "name": "submit_job", "arguments": { "tool": tool } },
+            }))
+            .unwrap()
+        };
+        assert!(is_read_request(&job("export_png"), &exposure));
+        assert!(!is_read_request(&job("clear_canvas"), &exposure));
+        assert!(!is_read_request(&request("tools/call", "submit_job"), &exposure));
+        assert!(!is_read_request(&request("initialize", ""), &exposure));
+    }
+
+    #[tokio::test]
+    async fn large_responses_are_compressed_when_accepted() {
+        use tower::ServiceExt;
+        let app = Router::new()
+            .route("/big", get(|| async { "shape ".repeat(1000) }))
+            .route("/small", get(|| async { "ok" }))
+            .layer(compression());
+        let encoding = |path: &'static str, accept: Option<&'static str>| {
+            let app = app.clone();
+            async move {
+                let mut request = axum::http::Request::builder().uri(path);
+                if let Some(accept) = accept {
+                    request = request.header(axum::http::header::ACCEPT_ENCODING, accept);
+                }
+                let response = app
+                    .oneshot(request.body(axum::body::Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                response
+                    .headers()
+                    .get(axum::http::header::CONTENT_ENCODING)
+                    .map(|v| v.to_str().unwrap().to_string())
+            }
+        };
+        assert_eq!(encoding("/big", Some("gzip")).await.as_deref(), Some("gzip"));
+        assert_eq!(encoding("/big", Some("br, gzip;q=0.5")).await.as_deref(), Some("br"));
+        assert_eq!(encoding("/big", None).await, None);
+        assert_eq!(encoding("/small", Some("gzip")).await, None);
+    }
+
     #[test]
     fn mcp_result_has_correct_structure() {
         let res = mcp_result(Some(serde_json::json!(42)), serde_json::json!({"ok": true}));
@@ -765,7 +1819,7 @@ mod tests {
     fn mcp_tools_list_returns_expected_count() {
         let tools = mcp_tools_list();
         let arr = tools.as_array().expect("tools list should be an array");
-        assert_eq!(arr.len(), 24);
+        assert_eq!(arr.len(), 26);
     }
 
     #[test]
@@ -779,6 +1833,70 @@ mod tests {
         }
     }
 
+    #[test]
+    fn read_and_view_tools_are_not_mutating() {
+        assert!(is_mutating_tool("create_shape"));
+        assert!(is_mutating_tool("batch_operations"));
+        assert!(!is_mutating_tool("get_canvas"));
+        assert!(!is_mutating_tool("set_viewport"));
+    }
+
+    #[test]
+    fn all_tools_includes_rust_handled_tools() {
+        let tools = all_tools();
+        let names: Vec<&str> = tools
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect();
+        assert!(names.contains(&"acquire_canvas_lock"));
+        assert!(names.contains(&"release_canvas_lock"));
+        assert!(names.contains(&"repair_document"));
+        assert!(names.contains(&"add_constraint"));
+        assert!(names.contains(&"fit_shape_to_text"));
+        assert!(names.contains(&"trace_image"));
+        assert!(names.contains(&"cleanup_photo"));
+        assert!(names.contains(&"set_document_scale"));
+        assert!(names.contains(&"extract_outline"));
+        assert!(names.contains(&"partition_canvas"));
+        assert!(names.contains(&"bulk_update"));
+        assert!(names.contains(&"translate_canvas"));
+        assert!(names.contains(&"get_shape_order"));
+        assert!(names.contains(&"set_shape_order"));
+        let get_canvas = tools
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|t| t["name"] == "get_canvas")
+            .unwrap();
+        assert!(get_canvas["inputSchema"]["properties"]["maxBytes"].is_object());
+        assert!(is_known_tool("create_shape"));
+        assert!(!is_known_tool("napkin_create_shape"));
+    }
+
+    #[test]
+    fn bridged_tools_include_internal_bridge_calls() {
+        let bridged = bridged_tools();
+        assert!(bridged.iter().any(|t| t == "create_shape"));
+        assert!(bridged.iter().any(|t| t == "get_tab"));
+        assert!(!bridged.iter().any(|t| t == "acquire_canvas_lock"));
+    }
+
+    #[tokio::test]
+    async fn bind_listener_falls_back_when_port_is_taken() {
+        let taken = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        let loopback = IpAddr::from([127, 0, 0, 1]);
+        let err = bind_listener(loopback, port, 0).await.unwrap_err();
+        assert!(err.contains("in use"), "{}", err);
+
+        if let Ok((_listener, bound)) = bind_listener(loopback, port, 5).await {
+            assert!(bound > port && bound <= port + 5);
+        }
+    }
+
     #[test]
     fn mcp_tools_list_contains_expected_tools() {
         let tools = mcp_tools_list();
@@ -814,6 +1932,8 @@ mod tests {
             "batch_operations",
             "reorganize",
             "set_snap_settings",
+            "export_png",
+            "screenshot_viewport",
         ];
         for name in &expected {
             assert!(names.contains(name), "missing tool: {}", name);
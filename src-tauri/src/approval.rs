@@ -0,0 +1,195 @@
+//! "Ask before AI edits": user approval of mutating tool calls.
+//!
+//! With `askBeforeEdits` on, every mutating tool waits for the user before
+//! it reaches the canvas. The webview is sent `tool-approval-request` and
+//! answers with `respond_tool_approval`: allow once, always allow that tool
+//! for that client, or deny. "Always" choices live in `ApiState` until the
+//! app quits or the user resets them. A prompt nobody answers is denied
+//! after `APPROVAL_TIMEOUT_SECS`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::api::SharedApiState;
+use crate::audit;
+use crate::settings;
+
+/// How long a prompt waits for the user before the call is denied.
+pub(crate) const APPROVAL_TIMEOUT_SECS: u64 = 60;
+/// Client name for agents that did not send one in `initialize`.
+const UNNAMED_CLIENT: &str = "agent";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Decision {
+    Allow,
+    /// Allow, and stop asking about this tool for this client.
+    AllowAlways,
+    Deny,
+}
+
+#[derive(Default)]
+pub struct Approvals {
+    pending: HashMap<String, oneshot::Sender<Decision>>,
+    /// (client, tool) pairs the user always allows.
+    always: HashSet<(String, String)>,
+}
+
+impl Approvals {
+    pub fn is_always_allowed(&self, client: &str, tool: &str) -> bool {
+        self.always
+            .contains(&(client.to_string(), tool.to_string()))
+    }
+
+    /// Wait for an answer to prompt `id`.
+    fn open(&mut self, id: &str) -> oneshot::Receiver<Decision> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.insert(id.to_string(), tx);
+        rx
+    }
+
+    /// Answer prompt `id`; false if it is unknown or already gone.
+    fn answer(&mut self, id: &str, decision: Decision) -> bool {
+        match self.pending.remove(id) {
+            Some(tx) => tx.send(decision).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Drop prompt `id` without an answer.
+    fn close(&mut self, id: &str) {
+        self.pending.remove(id);
+    }
+
+    fn remember(&mut self, client: &str, tool: &str) {
+        self.always.insert((client.to_string(), tool.to_string()));
+    }
+
+    /// Forget every "always allow" choice.
+    pub fn reset(&mut self) {
+        self.always.clear();
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ApprovalRequest {
+    id: String,
+    tool: String,
+    client: String,
+    arguments: serde_json::Value,
+}
+
+/// Hold a mutating tool call until the user approves it. A no-op unless
+/// `askBeforeEdits` is on.
+pub async fn check(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &serde_json::Value,
+    client_name: Option<&str>,
+) -> Result<(), String> {
+    if !settings::current(&state.app_handle).ask_before_edits {
+        return Ok(());
+    }
+    let client = client_name.unwrap_or(UNNAMED_CLIENT);
+    let id = Uuid::new_v4().to_string();
+    let rx = {
+        let mut approvals = state.approvals.lock().await;
+        if approvals.is_always_allowed(client, tool_name) {
+            return Ok(());
+        }
+        approvals.open(&id)
+    };
+
+    let request = ApprovalRequest {
+        id: id.clone(),
+        tool: tool_name.to_string(),
+        client: client.to_string(),
+        arguments: audit::shorten(arguments),
+    };
+    if let Err(e) = state.app_handle.emit("tool-approval-request", &request) {
+        state.approvals.lock().await.close(&id);
+        return Err(format!("Failed to ask for approval: {}", e));
+    }
+
+    let decision = tokio::time::timeout(Duration::from_secs(APPROVAL_TIMEOUT_SECS), rx).await;
+    let decision = match decision {
+        Ok(Ok(decision)) => decision,
+        _ => {
+            state.approvals.lock().await.close(&id);
+            let _ = state.app_handle.emit("tool-approval-expired", &id);
+            return Err(format!(
+                "The user did not approve {} in time; nothing was changed",
+                tool_name
+            ));
+        }
+    };
+    match decision {
+        Decision::Allow => Ok(()),
+        Decision::AllowAlways => {
+            state.approvals.lock().await.remember(client, tool_name);
+            Ok(())
+        }
+        Decision::Deny => Err(format!(
+            "The user declined {}; nothing was changed",
+            tool_name
+        )),
+    }
+}
+
+// --- Tauri commands ---
+
+/// Answer a `tool-approval-request`.
+#[tauri::command]
+pub async fn respond_tool_approval(
+    id: String,
+    decision: Decision,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<(), String> {
+    if state.approvals.lock().await.answer(&id, decision) {
+        Ok(())
+    } else {
+        Err("This request is no longer waiting for approval".to_string())
+    }
+}
+
+/// Forget every "always allow" choice, so each tool asks again.
+#[tauri::command]
+pub async fn reset_tool_approvals(state: tauri::State<'_, SharedApiState>) -> Result<(), String> {
+    state.approvals.lock().await.reset();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn answers_reach_the_waiting_call_once() {
+        let mut approvals = Approvals::default();
+        let rx = approvals.open("p1");
+        assert!(approvals.answer("p1", Decision::Deny));
+        assert_eq!(rx.await.unwrap(), Decision::Deny);
+        assert!(!approvals.answer("p1", Decision::Allow));
+        assert!(!approvals.answer("unknown", Decision::Allow));
+
+        let rx = approvals.open("p2");
+        approvals.close("p2");
+        assert!(rx.await.is_err());
+    }
+
+    #[test]
+    fn always_allow_is_per_client_and_tool() {
+        let mut approvals = Approvals::default();
+        approvals.remember("sketch-bot", "create_shape");
+        assert!(approvals.is_always_allowed("sketch-bot", "create_shape"));
+        assert!(!approvals.is_always_allowed("sketch-bot", "clear_canvas"));
+        assert!(!approvals.is_always_allowed("other", "create_shape"));
+        approvals.reset();
+        assert!(!approvals.is_always_allowed("sketch-bot", "create_shape"));
+    }
+}
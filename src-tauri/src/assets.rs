@@ -0,0 +1,624 @@
+//! Content-addressed store for the images in SQLite and journal documents.
+//!
+//! An image pasted into a board arrives as a base64 data URL in its `src`.
+//! Documents kept in SQLite or journal storage, and the saved versions of
+//! every document, store `napkin-blob:<sha256>.<ext>` in its place and the
+//! bytes once, as `<app data>/assets/<sha256>.<ext>`, however many boards,
+//! documents and versions use them. Readers (`docstore::read_path`) put the
+//! data URLs back, so the MCP resources and the CLI never see a reference.
+//!
+//! JSON documents stay self-contained: the version 2 container
+//! (`container.rs`) already keeps each distinct image once inside the file,
+//! and the file can be copied to another machine. Converting a SQLite
+//! document to JSON storage makes it portable in the same way.
+//!
+//! The webview holds references too, rather than megabytes of base64 per
+//! image: `load_document` moves the images of an open document into the
+//! store, pasted images go there through `store_asset`, and the canvas
+//! loads them from `napkin-asset://localhost/<sha256>.<ext>` (the
+//! `napkin-asset` URI scheme, `protocol` below). Writers put the data URLs
+//! back wherever a file must stand alone: JSON documents and exports.
+//! Password-protected documents keep their images inline, since the store
+//! is not encrypted.
+//!
+//! Documents written with references, or whose write-ahead log (`wal.rs`)
+//! has them, are listed in `assets/documents.json`. Collecting unused assets
+//! deletes the blobs that none of those documents, their logs, the recovery
+//! autosave or any saved version refers to and that nothing has read or
+//! written for thirty days, so a document moved out from under the list
+//! keeps its images as long as it is opened now and then.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+use tauri::http::{header, Method, Request, Response, StatusCode};
+use tauri::Manager;
+
+use crate::container;
+use crate::docstore;
+use crate::document;
+use crate::encryption;
+use crate::versions;
+use crate::wal;
+
+const ASSETS_DIR: &str = "assets";
+const REGISTRY_FILE: &str = "documents.json";
+const BLOB_SCHEME: &str = "napkin-blob:";
+/// The URI scheme the webview loads stored images from.
+pub const PROTOCOL: &str = "napkin-asset";
+/// How long an unreferenced blob is kept after it was last used.
+const GRACE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The store of the running app; unset in the CLI and headless modes,
+/// which keep images inline.
+static STORE: OnceLock<PathBuf> = OnceLock::new();
+static REGISTRY: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetCollection {
+    pub removed: usize,
+    pub bytes_freed: u64,
+    pub kept: usize,
+}
+
+/// Use the store under the app data directory.
+pub fn init(app: &tauri::AppHandle) {
+    match app.path().app_data_dir() {
+        Ok(dir) => {
+            let _ = STORE.set(dir.join(ASSETS_DIR));
+        }
+        Err(e) => log::warn!("No asset store: {}", e),
+    }
+}
+
+/// Whether `id` names a blob: a SHA-256 hex digest and an extension.
+fn is_blob_id(id: &str) -> bool {
+    let Some((hash, ext)) = id.split_once('.') else {
+        return false;
+    };
+    hash.len() == 64
+        && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        && (1..=5).contains(&ext.len())
+        && ext.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+fn media_type(id: &str) -> &'static str {
+    match id.rsplit('.').next() {
+        Some("png") => "image/png",
+        Some("jpg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Mark the blob at `path` as just used.
+fn touch(path: &Path) {
+    let _ = std::fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_modified(SystemTime::now()));
+}
+
+/// Store `bytes` once and return their id.
+fn put(store: &Path, bytes: &[u8], media_type: &str) -> Result<String, String> {
+    let digest = Sha256::digest(bytes);
+    let hash: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    let id = format!("{}.{}", hash, container::extension(media_type));
+    let path = store.join(&id);
+    if path.exists() {
+        touch(&path);
+        return Ok(id);
+    }
+    std::fs::create_dir_all(store)
+        .map_err(|e| format!("Failed to create {}: {}", store.display(), e))?;
+    let tmp = store.join(format!("{}.tmp", id));
+    std::fs::File::create(&tmp)
+        .and_then(|mut f| f.write_all(bytes).and_then(|()| f.sync_all()))
+        .and_then(|()| std::fs::rename(&tmp, &path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            format!("Failed to store {}: {}", id, e)
+        })?;
+    Ok(id)
+}
+
+fn get(store: &Path, id: &str) -> Option<Vec<u8>> {
+    if !is_blob_id(id) {
+        return None;
+    }
+    let path = store.join(id);
+    let bytes = std::fs::read(&path).ok()?;
+    touch(&path);
+    Some(bytes)
+}
+
+/// Replace the data URL of image `shape` with a reference. Whether it did.
+fn dehydrate_in(store: &Path, shape: &mut Value) -> bool {
+    let Some((media_type, bytes)) = shape["src"].as_str().and_then(container::decode_data_url)
+    else {
+        return false;
+    };
+    match put(store, &bytes, &media_type) {
+        Ok(id) => {
+            shape["src"] = Value::String(format!("{}{}", BLOB_SCHEME, id));
+            true
+        }
+        Err(e) => {
+            log::warn!("Keeping an image inline: {}", e);
+            false
+        }
+    }
+}
+
+/// Put the data URL back into image `shape`. A missing blob leaves the
+/// reference, which the webview shows as a broken image.
+fn hydrate_in(store: &Path, shape: &mut Value) {
+    let Some(id) = shape["src"]
+        .as_str()
+        .and_then(|s| s.strip_prefix(BLOB_SCHEME))
+    else {
+        return;
+    };
+    match get(store, id) {
+        Some(bytes) => {
+            shape["src"] = Value::String(format!(
+                "data:{};base64,{}",
+                media_type(id),
+                STANDARD.encode(bytes)
+            ));
+        }
+        None => log::warn!("Image {} is missing from the asset store", id),
+    }
+}
+
+fn is_reference(shape: &Value) -> bool {
+    shape["src"]
+        .as_str()
+        .is_some_and(|src| src.starts_with(BLOB_SCHEME))
+}
+
+/// Move the images of `collection` into the store. Whether it refers to
+/// the store afterwards.
+pub(crate) fn dehydrate(collection: &mut Value) -> bool {
+    let Some(store) = STORE.get() else {
+        return false;
+    };
+    container::images_mut(collection).fold(false, |any, shape| {
+        dehydrate_in(store, shape);
+        is_reference(shape) | any
+    })
+}
+
+/// Move the image of a single `shape`, as sent by incremental saves, into
+/// the store. Whether it refers to the store afterwards.
+pub(crate) fn dehydrate_shape(shape: &mut Value) -> bool {
+    match STORE.get() {
+        Some(store) if shape["type"] == "image" => {
+            dehydrate_in(store, shape);
+            is_reference(shape)
+        }
+        _ => false,
+    }
+}
+
+/// Collection `json` with the images moved into the store.
+pub(crate) fn dehydrate_json(json: &str) -> Result<String, String> {
+    let mut collection: Value =
+        serde_json::from_str(json).map_err(|e| format!("Invalid document JSON: {}", e))?;
+    Ok(if dehydrate(&mut collection) {
+        collection.to_string()
+    } else {
+        json.to_string()
+    })
+}
+
+/// Collection `json` with its referenced images inlined again.
+pub(crate) fn hydrate_json(json: String) -> String {
+    let Some(store) = STORE.get() else {
+        return json;
+    };
+    if !json.contains(BLOB_SCHEME) {
+        return json;
+    }
+    let Ok(mut collection) = serde_json::from_str::<Value>(&json) else {
+        return json;
+    };
+    for shape in container::images_mut(&mut collection) {
+        hydrate_in(store, shape);
+    }
+    collection.to_string()
+}
+
+/// Image `src` as a data URL if it refers to the store, for exporters and
+/// tools that need the pixels.
+pub(crate) fn inline_src(src: &str) -> Cow<'_, str> {
+    let (Some(store), Some(id)) = (STORE.get(), src.strip_prefix(BLOB_SCHEME)) else {
+        return Cow::Borrowed(src);
+    };
+    match get(store, id) {
+        Some(bytes) => Cow::Owned(format!(
+            "data:{};base64,{}",
+            media_type(id),
+            STANDARD.encode(bytes)
+        )),
+        None => Cow::Borrowed(src),
+    }
+}
+
+/// The blob ids referred to anywhere in `text`.
+fn references(text: &str) -> impl Iterator<Item = &str> + '_ {
+    text.match_indices(BLOB_SCHEME).filter_map(|(at, _)| {
+        let rest = &text[at + BLOB_SCHEME.len()..];
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+            .unwrap_or(rest.len());
+        Some(&rest[..end]).filter(|id| is_blob_id(id))
+    })
+}
+
+/// Whether `text` refers to the store.
+pub(crate) fn has_references(text: &str) -> bool {
+    references(text).next().is_some()
+}
+
+fn read_registry(store: &Path) -> Vec<String> {
+    std::fs::read_to_string(store.join(REGISTRY_FILE))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn write_registry(store: &Path, documents: &[String]) -> Result<(), String> {
+    std::fs::create_dir_all(store)
+        .map_err(|e| format!("Failed to create {}: {}", store.display(), e))?;
+    let json = serde_json::to_string_pretty(documents).map_err(|e| e.to_string())?;
+    std::fs::write(store.join(REGISTRY_FILE), json)
+        .map_err(|e| format!("Failed to write the asset registry: {}", e))
+}
+
+fn register_in(store: &Path, document: &Path) -> Result<(), String> {
+    let _guard = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let mut documents = read_registry(store);
+    let document = document.to_string_lossy().to_string();
+    if documents.contains(&document) {
+        return Ok(());
+    }
+    documents.push(document);
+    write_registry(store, &documents)
+}
+
+/// Remember that `document` refers to the store.
+pub(crate) fn register(document: &Path) {
+    let Some(store) = STORE.get() else {
+        return;
+    };
+    if let Err(e) = register_in(store, document) {
+        log::warn!("{}", e);
+    }
+}
+
+/// Every blob id referred to by the registered documents that still exist,
+/// by `files` (the recovery autosave), by the write-ahead logs of both, and
+/// by the files under `roots`. Documents that are gone are dropped from the
+/// registry.
+fn referenced(
+    store: &Path,
+    roots: &[PathBuf],
+    files: &[PathBuf],
+) -> Result<HashSet<String>, String> {
+    let mut ids = HashSet::new();
+    let _guard = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let documents = read_registry(store);
+    let mut present = Vec::with_capacity(documents.len());
+    for document in documents {
+        let path = Path::new(&document);
+        if !path.exists() {
+            continue;
+        }
+        // An unreadable document could refer to anything
+        let json = docstore::read_stored(path)
+            .map_err(|e| format!("Not collecting assets: {}: {}", document, e))?;
+        ids.extend(references(&json).map(str::to_string));
+        present.push(document);
+    }
+    write_registry(store, &present)?;
+
+    // Password-protected files keep their images inline
+    let files: Vec<&Path> = files
+        .iter()
+        .map(PathBuf::as_path)
+        .filter(|file| file.exists() && !encryption::is_encrypted(file))
+        .collect();
+    for file in &files {
+        let json = docstore::read_stored(file)
+            .map_err(|e| format!("Not collecting assets: {}: {}", file.display(), e))?;
+        ids.extend(references(&json).map(str::to_string));
+    }
+    // Edits not saved yet
+    let documents = present.iter().map(Path::new).chain(files);
+    for log in documents.map(wal::log_path) {
+        if let Ok(text) = std::fs::read_to_string(&log) {
+            ids.extend(references(&text).map(str::to_string));
+        }
+    }
+
+    let mut dirs: Vec<PathBuf> = roots.to_vec();
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                dirs.push(path);
+            } else if let Ok(text) = std::fs::read_to_string(&path) {
+                ids.extend(references(&text).map(str::to_string));
+            }
+        }
+    }
+    Ok(ids)
+}
+
+/// Delete the blobs in `store` outside `referenced` that were last used
+/// before `now` minus the grace period.
+fn sweep(store: &Path, referenced: &HashSet<String>, now: SystemTime) -> AssetCollection {
+    let mut collection = AssetCollection::default();
+    let Ok(entries) = std::fs::read_dir(store) else {
+        return collection;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_blob_id(&name) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let idle = metadata
+            .modified()
+            .ok()
+            .and_then(|used| now.duration_since(used).ok())
+            .is_some_and(|idle| idle > GRACE);
+        if referenced.contains(&name) || !idle {
+            collection.kept += 1;
+        } else if std::fs::remove_file(entry.path()).is_ok() {
+            collection.removed += 1;
+            collection.bytes_freed += metadata.len();
+        }
+    }
+    collection
+}
+
+fn collect(app: &tauri::AppHandle) -> Result<AssetCollection, String> {
+    let store = STORE.get().ok_or("No asset store")?;
+    let roots = versions::root(app).into_iter().collect::<Vec<_>>();
+    let files = document::recovery_path(app).into_iter().collect::<Vec<_>>();
+    let referenced = referenced(store, &roots, &files)?;
+    let collection = sweep(store, &referenced, SystemTime::now());
+    if collection.removed > 0 {
+        log::info!(
+            "Removed {} unused assets ({} bytes)",
+            collection.removed,
+            collection.bytes_freed
+        );
+    }
+    Ok(collection)
+}
+
+/// Collect unused assets at startup.
+pub fn collect_unused(app: &tauri::AppHandle) {
+    if let Err(e) = collect(app) {
+        log::warn!("{}", e);
+    }
+}
+
+// --- Protocol ---
+
+fn status(code: StatusCode) -> Response<Vec<u8>> {
+    let mut response = Response::new(Vec::new());
+    *response.status_mut() = code;
+    response
+}
+
+/// The blob a `napkin-asset://localhost/<id>` request names.
+fn respond(store: Option<&Path>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        return status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    let id = request.uri().path().trim_start_matches('/');
+    let Some(bytes) = store.and_then(|store| get(store, id)) else {
+        return status(StatusCode::NOT_FOUND);
+    };
+    let body = if request.method() == Method::HEAD {
+        Vec::new()
+    } else {
+        bytes
+    };
+    Response::builder()
+        .header(header::CONTENT_TYPE, media_type(id))
+        // Blobs never change; the id is their hash
+        .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+        // Lets exports draw them onto a canvas without tainting it
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .body(body)
+        .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Serve stored images to the webview, off the main thread.
+pub fn protocol<R: tauri::Runtime>(
+    _: tauri::UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+    responder: tauri::UriSchemeResponder,
+) {
+    tauri::async_runtime::spawn_blocking(move || {
+        responder.respond(respond(STORE.get().map(PathBuf::as_path), &request));
+    });
+}
+
+// --- Tauri commands ---
+
+/// Store an image the user added as data URL `data_url` and return what
+/// the canvas should keep as its `src`: a reference, or the data URL itself
+/// when `document` is password-protected or there is no store.
+#[tauri::command]
+pub async fn store_asset(data_url: String, document: Option<String>) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let protected = document.is_some_and(|d| encryption::is_encrypted(Path::new(&d)));
+        let Some(store) = STORE.get().filter(|_| !protected) else {
+            return Ok(data_url);
+        };
+        let Some((media_type, bytes)) = container::decode_data_url(&data_url) else {
+            return Ok(data_url);
+        };
+        put(store, &bytes, &media_type).map(|id| format!("{}{}", BLOB_SCHEME, id))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Delete the stored images no document or version uses any more.
+#[tauri::command]
+pub async fn collect_unused_assets(app: tauri::AppHandle) -> Result<AssetCollection, String> {
+    tauri::async_runtime::spawn_blocking(move || collect(&app))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("napkin-assets-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn image(id: &str, src: &str) -> Value {
+        json!({ "id": id, "type": "image", "src": src, "x": 0, "y": 0 })
+    }
+
+    #[test]
+    fn an_image_used_twice_is_stored_once() {
+        let store = temp_dir();
+        let png = "data:image/png;base64,iVBORw0KGgo=";
+        let mut shapes = vec![
+            image("a", png),
+            image("b", png),
+            image("c", "https://example.com/cat.png"),
+        ];
+        assert!(dehydrate_in(&store, &mut shapes[0]));
+        assert!(dehydrate_in(&store, &mut shapes[1]));
+        assert!(!dehydrate_in(&store, &mut shapes[2]));
+
+        let src = shapes[0]["src"].as_str().unwrap().to_string();
+        assert!(src.starts_with(BLOB_SCHEME) && src.ends_with(".png"));
+        assert_eq!(shapes[1]["src"], src.as_str());
+        assert_eq!(std::fs::read_dir(&store).unwrap().count(), 1);
+
+        let text = Value::Array(shapes.clone()).to_string();
+        let ids: Vec<&str> = references(&text).collect();
+        assert_eq!(ids, [&src[BLOB_SCHEME.len()..]; 2]);
+
+        for shape in &mut shapes {
+            hydrate_in(&store, shape);
+        }
+        assert_eq!(shapes[0]["src"], png);
+        assert_eq!(shapes[1]["src"], png);
+        assert_eq!(shapes[2]["src"], "https://example.com/cat.png");
+
+        // Ids cannot reach outside the store
+        assert!(get(&store, "../documents.json").is_none());
+        let _ = std::fs::remove_dir_all(&store);
+    }
+
+    #[test]
+    fn the_protocol_serves_blobs_by_id() {
+        let store = temp_dir();
+        let id = put(&store, b"\x89PNG", "image/png").unwrap();
+        let request = |method: Method, path: &str| {
+            Request::builder()
+                .method(method)
+                .uri(format!("napkin-asset://localhost/{}", path))
+                .body(Vec::new())
+                .unwrap()
+        };
+
+        let response = respond(Some(&store), &request(Method::GET, &id));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        assert_eq!(response.body(), b"\x89PNG");
+        let head = respond(Some(&store), &request(Method::HEAD, &id));
+        assert_eq!(head.status(), StatusCode::OK);
+        assert!(head.body().is_empty());
+
+        let missing = format!("{}.png", "0".repeat(64));
+        for path in [missing.as_str(), "..%2Fdocuments.json", ""] {
+            let response = respond(Some(&store), &request(Method::GET, path));
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+        assert_eq!(
+            respond(Some(&store), &request(Method::POST, &id)).status(),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            respond(None, &request(Method::GET, &id)).status(),
+            StatusCode::NOT_FOUND
+        );
+        let _ = std::fs::remove_dir_all(&store);
+    }
+
+    #[test]
+    fn only_unreferenced_idle_blobs_are_collected() {
+        let store = temp_dir();
+        let used = put(&store, b"used", "image/png").unwrap();
+        let idle = put(&store, b"idle", "image/png").unwrap();
+        write_registry(&store, &[]).unwrap();
+        let referenced: HashSet<String> = [used.clone()].into();
+
+        // Still within the grace period
+        let swept = sweep(&store, &referenced, SystemTime::now());
+        assert_eq!((swept.removed, swept.kept), (0, 2));
+
+        let later = SystemTime::now() + GRACE + Duration::from_secs(60);
+        let swept = sweep(&store, &referenced, later);
+        assert_eq!((swept.removed, swept.kept), (1, 1));
+        assert_eq!(swept.bytes_freed, b"idle".len() as u64);
+        assert!(store.join(&used).exists());
+        assert!(!store.join(&idle).exists());
+        // The registry is not a blob
+        assert!(store.join(REGISTRY_FILE).exists());
+        let _ = std::fs::remove_dir_all(&store);
+    }
+
+    #[test]
+    fn logs_and_the_recovery_autosave_keep_their_blobs() {
+        let store = temp_dir();
+        let blob = |bytes: &[u8]| put(&store, bytes, "image/png").unwrap();
+        let (saved, logged, recovered, unused) = (blob(b"a"), blob(b"b"), blob(b"c"), blob(b"d"));
+        let shapes =
+            |id: &str| json!({ "shapes": [image("i", &format!("{}{}", BLOB_SCHEME, id))] });
+
+        let document = store.join("board.napkin");
+        std::fs::write(&document, shapes(&saved).to_string()).unwrap();
+        register_in(&store, &document).unwrap();
+        std::fs::write(wal::log_path(&document), shapes(&logged).to_string()).unwrap();
+        let recovery = store.join("autosave.napkin");
+        std::fs::write(&recovery, shapes(&recovered).to_string()).unwrap();
+
+        let ids = referenced(&store, &[], &[recovery]).unwrap();
+        assert_eq!(ids, [saved, logged, recovered].into());
+        assert!(!ids.contains(&unused));
+        let _ = std::fs::remove_dir_all(&store);
+    }
+}
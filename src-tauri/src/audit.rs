@@ -0,0 +1,250 @@
+//! Audit log of MCP tool calls.
+//!
+//! Every `tools/call` is appended as one JSON line to `audit.jsonl` in the
+//! app data directory: when, from which client, the tool and its arguments,
+//! and whether it succeeded. Entries are only ever appended until the user
+//! clears the log, so they can see what agents did to their canvas. Long
+//! strings in the arguments (image data, pasted documents) are shortened.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::embed;
+
+const AUDIT_FILE: &str = "audit.jsonl";
+/// Strings in recorded arguments are cut to this many characters.
+const MAX_STRING_CHARS: usize = 200;
+/// Entries `get_audit_log` returns when no limit is given.
+const DEFAULT_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditStatus {
+    Ok,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The `McpClient` id: an HTTP session or a socket connection.
+    pub client_id: String,
+    /// `clientInfo.name` from `initialize`, when the client sent one.
+    pub client_name: Option<String>,
+    pub tool: String,
+    pub arguments: Value,
+    /// Whether the tool can change the document.
+    pub mutating: bool,
+    pub status: AuditStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(
+        client_id: &str,
+        client_name: Option<&str>,
+        tool: &str,
+        arguments: &Value,
+        result: Result<(), &str>,
+    ) -> Self {
+        Self {
+            timestamp: embed::now_secs(),
+            client_id: client_id.to_string(),
+            client_name: client_name.map(str::to_string),
+            tool: tool.to_string(),
+            arguments: shorten(arguments),
+            mutating: crate::api::is_mutating_tool(tool),
+            status: if result.is_ok() {
+                AuditStatus::Ok
+            } else {
+                AuditStatus::Error
+            },
+            error: result.err().map(str::to_string),
+        }
+    }
+}
+
+/// `value` with every string longer than `MAX_STRING_CHARS` cut short.
+pub(crate) fn shorten(value: &Value) -> Value {
+    match value {
+        Value::String(s) => match s.char_indices().nth(MAX_STRING_CHARS) {
+            Some((end, _)) => {
+                Value::String(format!("{}… ({} chars)", &s[..end], s.chars().count()))
+            }
+            None => value.clone(),
+        },
+        Value::Array(items) => Value::Array(items.iter().map(shorten).collect()),
+        Value::Object(map) => {
+            Value::Object(map.iter().map(|(k, v)| (k.clone(), shorten(v))).collect())
+        }
+        _ => value.clone(),
+    }
+}
+
+/// The log file, kept in the app data dir.
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    /// Serializes appends with reads and clears.
+    file: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn load(app: &tauri::AppHandle) -> Self {
+        Self::at(
+            app.path()
+                .app_data_dir()
+                .ok()
+                .map(|dir| dir.join(AUDIT_FILE)),
+        )
+    }
+
+    fn at(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            file: Mutex::new(()),
+        }
+    }
+
+    pub fn append(&self, entry: &AuditEntry) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let _guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        line.push('\n');
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| format!("Failed to write audit log: {}", e))
+    }
+
+    /// The newest `limit` entries, newest first.
+    pub fn read(&self, limit: usize) -> Result<Vec<AuditEntry>, String> {
+        let Some(path) = &self.path else {
+            return Ok(Vec::new());
+        };
+        let _guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read audit log: {}", e)),
+        };
+        let mut entries: Vec<AuditEntry> = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect();
+        entries.reverse();
+        entries.truncate(limit);
+        Ok(entries)
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let _guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to clear audit log: {}", e))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Append `entry` to the app's audit log.
+pub fn record(app: &tauri::AppHandle, entry: AuditEntry) {
+    if let Some(log) = app.try_state::<AuditLog>() {
+        if let Err(e) = log.append(&entry) {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+// --- Tauri commands ---
+
+/// The most recent tool calls, newest first.
+#[tauri::command]
+pub fn get_audit_log(
+    limit: Option<usize>,
+    log: tauri::State<'_, AuditLog>,
+) -> Result<Vec<AuditEntry>, String> {
+    log.read(limit.unwrap_or(DEFAULT_LIMIT))
+}
+
+#[tauri::command]
+pub fn clear_audit_log(log: tauri::State<'_, AuditLog>) -> Result<(), String> {
+    log.clear()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn entries_are_appended_and_read_newest_first() {
+        let dir = std::env::temp_dir().join(format!("napkin-audit-{}", uuid::Uuid::new_v4()));
+        let log = AuditLog::at(Some(dir.join(AUDIT_FILE)));
+        assert!(log.read(10).unwrap().is_empty());
+
+        let args = json!({ "type": "rectangle", "x": 0, "y": 0 });
+        log.append(&AuditEntry::new(
+            "c1",
+            Some("Agent"),
+            "create_shape",
+            &args,
+            Ok(()),
+        ))
+        .unwrap();
+        log.append(&AuditEntry::new(
+            "c1",
+            None,
+            "get_canvas",
+            &json!({}),
+            Err("No canvas"),
+        ))
+        .unwrap();
+
+        let entries = log.read(10).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].tool, "get_canvas");
+        assert_eq!(entries[0].status, AuditStatus::Error);
+        assert_eq!(entries[0].error.as_deref(), Some("No canvas"));
+        assert!(!entries[0].mutating);
+        assert_eq!(entries[1].client_name.as_deref(), Some("Agent"));
+        assert_eq!(entries[1].arguments, args);
+        assert!(entries[1].mutating);
+        assert_eq!(log.read(1).unwrap().len(), 1);
+
+        log.clear().unwrap();
+        assert!(log.read(10).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn long_strings_are_shortened() {
+        let image = format!("data:image/png;base64,{}", "A".repeat(1000));
+        let shortened = shorten(&json!({ "url": image, "items": ["é".repeat(300)] }));
+        let url = shortened["url"].as_str().unwrap();
+        assert!(url.starts_with("data:image/png;base64,AAA"));
+        assert!(url.ends_with("… (1022 chars)"));
+        assert_eq!(
+            shortened["items"][0],
+            format!("{}… (300 chars)", "é".repeat(200))
+        );
+    }
+}
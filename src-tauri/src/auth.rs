@@ -0,0 +1,166 @@
+//! Bearer token authentication for the MCP endpoint.
+//!
+//! The token is generated on first launch and kept in the app config
+//! directory so MCP client configurations survive restarts. Only the user
+//! may read the file. Embed and share routes carry their own signed tokens
+//! and are not covered.
+
+use axum::{
+    extract::{Request, State as AxumState},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use tauri::Manager;
+
+use crate::api::SharedApiState;
+use crate::embed;
+use crate::tls;
+
+pub(crate) const TOKEN_FILE: &str = "api_token";
+
+fn generate_token() -> String {
+    URL_SAFE_NO_PAD.encode(embed::generate_key())
+}
+
+/// The persisted token, creating it on first use. Falls back to a
+/// per-launch token if the config directory is unavailable.
+pub fn load_or_create_token(app: &tauri::AppHandle) -> String {
+    let Ok(dir) = app.path().app_config_dir() else {
+        log::warn!("No config directory; MCP token will change on restart");
+        return generate_token();
+    };
+    let path = dir.join(TOKEN_FILE);
+    if let Some(token) = std::fs::read_to_string(&path)
+        .ok()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+    {
+        // Tokens saved by older versions were readable by everyone.
+        if let Err(e) = restrict(&path) {
+            log::warn!("Failed to restrict the MCP token file: {}", e);
+        }
+        return token;
+    }
+    let token = generate_token();
+    let saved =
+        std::fs::create_dir_all(&dir).and_then(|_| tls::write_private(&path, token.as_bytes()));
+    if let Err(e) = saved {
+        log::warn!("Failed to save MCP token: {}", e);
+    }
+    token
+}
+
+/// Make `path` readable by its owner only.
+fn restrict(path: &std::path::Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if std::fs::metadata(path)?.permissions().mode() & 0o077 != 0 {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Whether `headers` carry `Authorization: Bearer <token>`.
+pub fn is_authorized(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| {
+            let (scheme, credentials) = v.split_once(' ')?;
+            scheme
+                .eq_ignore_ascii_case("bearer")
+                .then(|| credentials.trim())
+        })
+        .is_some_and(|credentials| constant_time_eq(credentials.as_bytes(), token.as_bytes()))
+}
+
+/// Middleware rejecting requests without the bearer token.
+pub async fn require_bearer(
+    AxumState(state): AxumState<SharedApiState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if is_authorized(request.headers(), state.api_token()) {
+        return next.run(request).await;
+    }
+    unauthorized()
+}
+
+/// The 401 sent for a missing or wrong token.
+pub fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        "Missing or invalid bearer token",
+    )
+        .into_response()
+}
+
+// --- Tauri command ---
+
+/// The token MCP clients must send, for the settings UI to copy.
+#[tauri::command]
+pub fn get_api_token(state: tauri::State<'_, SharedApiState>) -> String {
+    state.api_token().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn accepts_only_the_matching_bearer_token() {
+        assert!(is_authorized(&headers("Bearer s3cret"), "s3cret"));
+        assert!(is_authorized(&headers("bearer s3cret"), "s3cret"));
+        assert!(!is_authorized(&headers("Bearer s3cre"), "s3cret"));
+        assert!(!is_authorized(&headers("Basic s3cret"), "s3cret"));
+        assert!(!is_authorized(&headers("s3cret"), "s3cret"));
+        assert!(!is_authorized(&HeaderMap::new(), "s3cret"));
+    }
+
+    #[test]
+    fn generated_tokens_are_unique_and_url_safe() {
+        let (a, b) = (generate_token(), generate_token());
+        assert_ne!(a, b);
+        assert_eq!(a.len(), 43);
+        assert!(a
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn token_file_is_private_to_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("napkin-auth-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(TOKEN_FILE);
+        let mode =
+            |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        tls::write_private(&path, b"secret").unwrap();
+        assert_eq!(mode(&path), 0o600);
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        restrict(&path).unwrap();
+        assert_eq!(mode(&path), 0o600);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
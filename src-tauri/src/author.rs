@@ -0,0 +1,220 @@
+//! Author attribution for shapes.
+//!
+//! Every shape records who created it in `createdBy` (`{ name, color,
+//! source }`). Shapes drawn in the app are stamped with the local user from
+//! settings; shapes created over MCP are stamped here, before the call
+//! reaches the webview, with the name the client gave in `initialize`
+//! (see `session::McpClient`).
+//! Agents cannot set `createdBy` themselves.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::settings::{self, Settings};
+
+const DEFAULT_USER_COLOR: &str = "#1e88e5";
+const DEFAULT_AGENT_NAME: &str = "Agent";
+/// Agent colors, picked by name so each client keeps its color.
+const AGENT_COLORS: &[&str] = &[
+    "#8e24aa", "#e53935", "#43a047", "#fb8c00", "#00897b", "#3949ab", "#6d4c41", "#d81b60",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthorSource {
+    User,
+    Agent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Author {
+    pub name: String,
+    pub color: String,
+    pub source: AuthorSource,
+}
+
+/// The local user's identity, as configured in settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuthorProfile {
+    /// Display name; the OS user name when empty.
+    pub name: String,
+    /// Legend and highlight color; a default blue when empty.
+    pub color: String,
+}
+
+/// The local user, for shapes drawn in the app.
+pub fn user(settings: &Settings) -> Author {
+    let profile = &settings.author;
+    let name = Some(profile.name.trim())
+        .filter(|n| !n.is_empty())
+        .map(str::to_string)
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| "Me".to_string());
+    let color = Some(profile.color.trim())
+        .filter(|c| !c.is_empty())
+        .unwrap_or(DEFAULT_USER_COLOR)
+        .to_string();
+    Author {
+        name,
+        color,
+        source: AuthorSource::User,
+    }
+}
+
+/// An MCP client, named by its `clientInfo`.
+pub fn agent(client_name: Option<&str>) -> Author {
+    let name = client_name
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .unwrap_or(DEFAULT_AGENT_NAME);
+    let hash = name
+        .bytes()
+        .fold(0usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+    Author {
+        name: name.to_string(),
+        color: AGENT_COLORS[hash % AGENT_COLORS.len()].to_string(),
+        source: AuthorSource::Agent,
+    }
+}
+
+/// Stamp the shapes a creating tool call will add, and drop `createdBy`
+/// from updates so existing attribution cannot be rewritten.
+pub fn stamp(author: &Author, tool_name: &str, arguments: &mut Value) {
+    let Ok(stamp) = serde_json::to_value(author) else {
+        return;
+    };
+    let set = |data: &mut Value| {
+        if let Some(obj) = data.as_object_mut() {
+            obj.insert("createdBy".to_string(), stamp.clone());
+        }
+    };
+    let clear = |data: &mut Value| {
+        if let Some(obj) = data.as_object_mut() {
+            obj.remove("createdBy");
+        }
+    };
+    match tool_name {
+        "create_shape" | "create_image" | "create_connection" => set(arguments),
+        "update_shape" => clear(arguments),
+        "batch_operations" => {
+            let ops = arguments
+                .get_mut("operations")
+                .and_then(|o| o.as_array_mut());
+            for op in ops.into_iter().flatten() {
+                let action = op.get("action").and_then(|a| a.as_str()).unwrap_or("");
+                let create = action == "create";
+                if let Some(data) = op.get_mut("data") {
+                    if create {
+                        set(data);
+                    } else {
+                        clear(data);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The distinct authors of `shapes`, in order of first appearance, with how
+/// many shapes each created.
+pub fn authors_of(shapes: &[Value]) -> Vec<(Author, usize)> {
+    let mut authors: Vec<(Author, usize)> = Vec::new();
+    for shape in shapes {
+        let Some(author) = shape
+            .get("createdBy")
+            .and_then(|a| serde_json::from_value::<Author>(a.clone()).ok())
+        else {
+            continue;
+        };
+        match authors
+            .iter_mut()
+            .find(|(a, _)| a.name == author.name && a.source == author.source)
+        {
+            Some((_, count)) => *count += 1,
+            None => authors.push((author, 1)),
+        }
+    }
+    authors
+}
+
+// --- Tauri command ---
+
+/// The identity stamped on shapes the user draws.
+#[tauri::command]
+pub fn get_author(app: tauri::AppHandle) -> Author {
+    user(&settings::current(&app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn agents_keep_a_color_per_name() {
+        let a = agent(Some("claude-desktop"));
+        assert_eq!(a.source, AuthorSource::Agent);
+        assert_eq!(a, agent(Some("claude-desktop")));
+        assert_eq!(agent(None).name, DEFAULT_AGENT_NAME);
+        assert_eq!(agent(Some("  ")).name, DEFAULT_AGENT_NAME);
+    }
+
+    #[test]
+    fn user_prefers_the_configured_profile() {
+        let settings = Settings {
+            author: AuthorProfile {
+                name: "Ada".to_string(),
+                color: "#ff0000".to_string(),
+            },
+            ..Default::default()
+        };
+        let author = user(&settings);
+        assert_eq!(author.name, "Ada");
+        assert_eq!(author.color, "#ff0000");
+        assert_eq!(author.source, AuthorSource::User);
+        assert_eq!(user(&Settings::default()).color, DEFAULT_USER_COLOR);
+    }
+
+    #[test]
+    fn stamps_created_shapes_and_overrides_client_values() {
+        let author = agent(Some("bot"));
+        let mut args = json!({ "type": "rectangle", "createdBy": { "name": "someone else" } });
+        stamp(&author, "create_shape", &mut args);
+        assert_eq!(args["createdBy"]["name"], "bot");
+        assert_eq!(args["createdBy"]["source"], "agent");
+
+        let mut batch = json!({ "operations": [
+            { "action": "create", "data": { "type": "text" } },
+            { "action": "update", "data": { "id": "a", "createdBy": { "name": "bot" } } }
+        ]});
+        stamp(&author, "batch_operations", &mut batch);
+        assert_eq!(batch["operations"][0]["data"]["createdBy"]["name"], "bot");
+        assert!(batch["operations"][1]["data"].get("createdBy").is_none());
+
+        let mut update = json!({ "id": "a", "createdBy": { "name": "me" } });
+        stamp(&author, "update_shape", &mut update);
+        assert!(update.get("createdBy").is_none());
+    }
+
+    #[test]
+    fn authors_are_counted_in_order() {
+        let bot = serde_json::to_value(agent(Some("bot"))).unwrap();
+        let me = serde_json::to_value(user(&Settings::default())).unwrap();
+        let shapes = vec![
+            json!({ "id": "1", "createdBy": me }),
+            json!({ "id": "2", "createdBy": bot }),
+            json!({ "id": "3", "createdBy": me }),
+            json!({ "id": "4" }),
+        ];
+        let authors = authors_of(&shapes);
+        assert_eq!(authors.len(), 2);
+        assert_eq!(authors[0].0.source, AuthorSource::User);
+        assert_eq!(authors[0].1, 2);
+        assert_eq!(authors[1].0.name, "bot");
+    }
+}
@@ -0,0 +1,160 @@
+//! Where the MCP server listens.
+//!
+//! The server listens on 127.0.0.1 unless the user opts in to LAN access
+//! with the `mcpBind` setting, so an MCP client on another machine or in a
+//! container can reach the canvas. LAN access exposes the canvas to the
+//! network: `/mcp` always needs the bearer token (`auth.rs`), and the server
+//! refuses to start on the LAN with a token too short to be safe there (one
+//! edited by hand, say). Requests may then name the machine by IP address as
+//! well as by the hosts in `allowedHosts` (see `hosts.rs`). Changes take
+//! effect the next time the server starts.
+
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+/// Shortest bearer token accepted for LAN access.
+const MIN_LAN_TOKEN_LEN: usize = 32;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BindConfig {
+    /// Listen on the network instead of loopback only.
+    pub lan: bool,
+    /// Address to listen on with `lan`; every interface (`0.0.0.0`) when
+    /// empty.
+    pub address: String,
+}
+
+impl BindConfig {
+    /// The address to bind.
+    pub fn ip(&self) -> IpAddr {
+        if !self.lan {
+            return IpAddr::V4(Ipv4Addr::LOCALHOST);
+        }
+        self.address
+            .trim()
+            .parse()
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    }
+}
+
+/// Where a running server listens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Listening {
+    /// The bound address.
+    pub ip: IpAddr,
+    pub port: u16,
+    /// Whether the server speaks HTTPS.
+    pub tls: bool,
+}
+
+impl Listening {
+    /// Base URL for links to the server, such as `https://192.168.1.20:21420`.
+    /// A server bound to every interface is named by this machine's address
+    /// on the network, or by loopback when it has none.
+    pub fn origin(&self) -> String {
+        let host = if self.ip.is_unspecified() {
+            network_ip(self.ip).unwrap_or(match self.ip {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            })
+        } else {
+            self.ip
+        };
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{}://{}", scheme, SocketAddr::new(host, self.port))
+    }
+}
+
+/// This machine's address on the network for the family of `unspecified`:
+/// the source address of the default route, found without sending anything.
+pub(crate) fn network_ip(unspecified: IpAddr) -> Option<IpAddr> {
+    let probe = UdpSocket::bind(SocketAddr::new(unspecified, 0)).ok()?;
+    let target: IpAddr = match unspecified {
+        IpAddr::V4(_) => Ipv4Addr::new(192, 0, 2, 1).into(),
+        IpAddr::V6(_) => "2001:db8::1".parse().ok()?,
+    };
+    probe.connect(SocketAddr::new(target, 9)).ok()?;
+    Some(probe.local_addr().ok()?.ip()).filter(|ip| !ip.is_unspecified())
+}
+
+/// Check the `mcpBind` setting.
+pub fn validate(config: &BindConfig) -> Result<(), String> {
+    let address = config.address.trim();
+    if !address.is_empty() && address.parse::<IpAddr>().is_err() {
+        return Err(format!(
+            "Bind address '{}' is not an IP address",
+            config.address
+        ));
+    }
+    Ok(())
+}
+
+/// Refuse to listen beyond loopback with a weak `token`, and warn that the
+/// canvas is reachable from the network.
+pub fn check_exposure(ip: IpAddr, token: &str) -> Result<(), String> {
+    if ip.is_loopback() {
+        return Ok(());
+    }
+    if token.len() < MIN_LAN_TOKEN_LEN {
+        return Err(format!(
+            "Refusing to listen on {}: the MCP token must be at least {} characters for LAN access",
+            ip, MIN_LAN_TOKEN_LEN
+        ));
+    }
+    log::warn!(
+        "MCP server listening on {}: anyone on the network with the token can edit the canvas",
+        ip
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loopback_unless_lan_is_opted_in() {
+        let mut config = BindConfig {
+            lan: false,
+            address: "0.0.0.0".to_string(),
+        };
+        assert_eq!(config.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+        config.lan = true;
+        assert_eq!(config.ip(), IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        config.address = " 192.168.1.20 ".to_string();
+        assert_eq!(config.ip().to_string(), "192.168.1.20");
+        config.address = String::new();
+        assert_eq!(config.ip(), IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        config.address = "my-laptop".to_string();
+        assert!(validate(&config).is_err());
+        config.address = "::".to_string();
+        assert!(validate(&config).is_ok());
+    }
+
+    #[test]
+    fn origin_names_the_bound_scheme_and_host() {
+        let mut listening = Listening {
+            ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 21420,
+            tls: false,
+        };
+        assert_eq!(listening.origin(), "http://127.0.0.1:21420");
+        listening.tls = true;
+        listening.ip = "192.168.1.20".parse().unwrap();
+        assert_eq!(listening.origin(), "https://192.168.1.20:21420");
+        listening.ip = "fd00::20".parse().unwrap();
+        assert_eq!(listening.origin(), "https://[fd00::20]:21420");
+        listening.ip = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        assert!(!listening.origin().contains("0.0.0.0"));
+    }
+
+    #[test]
+    fn lan_access_needs_a_strong_token() {
+        let lan = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        assert!(check_exposure(IpAddr::V4(Ipv4Addr::LOCALHOST), "1234").is_ok());
+        assert!(check_exposure(lan, "1234").is_err());
+        assert!(check_exposure(lan, &"x".repeat(MIN_LAN_TOKEN_LEN)).is_ok());
+    }
+}
@@ -0,0 +1,161 @@
+//! The largest request body the server accepts.
+//!
+//! `create_image` takes base64 image data inline, so a request can be tens
+//! of megabytes. axum's extractors would cut those off at 2 MB with a plain
+//! text error, so that limit is turned off and this middleware enforces the
+//! `requestBodyLimit` setting instead, read on each request. A body over the
+//! limit is refused with `413` and an error the client can parse: a JSON-RPC
+//! error on the MCP endpoints, the usual `{ "error", "code" }` on the REST
+//! API.
+
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::api::{mcp_error, SharedApiState};
+use crate::settings;
+
+const DEFAULT_MAX_MEGABYTES: u64 = 32;
+/// The sidecar forwards at most 64 MB per request.
+const MAX_MEGABYTES: u64 = 64;
+/// JSON-RPC's "Invalid Request".
+const INVALID_REQUEST: i64 = -32600;
+
+/// How big a request body may be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BodyLimit {
+    /// Megabytes, from 1 to 64.
+    pub max_megabytes: u64,
+}
+
+impl Default for BodyLimit {
+    fn default() -> Self {
+        Self {
+            max_megabytes: DEFAULT_MAX_MEGABYTES,
+        }
+    }
+}
+
+impl BodyLimit {
+    pub fn bytes(&self) -> usize {
+        (self.max_megabytes.clamp(1, MAX_MEGABYTES) * 1024 * 1024) as usize
+    }
+}
+
+fn too_large(path: &str, limit: usize) -> Response {
+    let message = format!(
+        "Request body is larger than {} MB (requestBodyLimit.maxMegabytes in settings)",
+        limit / (1024 * 1024)
+    );
+    let body = if path.starts_with("/api/v1/") {
+        json!({ "error": message, "code": "INVALID_ARGUMENT" })
+    } else {
+        let mut error = mcp_error(None, INVALID_REQUEST, &message);
+        error["error"]["data"] = json!({ "reason": "bodyTooLarge", "limitBytes": limit });
+        error
+    };
+    (StatusCode::PAYLOAD_TOO_LARGE, Json(body)).into_response()
+}
+
+/// Refuse a body over `limit` bytes, by its `Content-Length` if it gives
+/// one and otherwise once that much has arrived.
+pub async fn check(request: Request, next: Next, limit: usize) -> Response {
+    let path = request.uri().path().to_string();
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if declared.is_some_and(|length| length > limit) {
+        return too_large(&path, limit);
+    }
+    let (parts, body) = request.into_parts();
+    // A client that hangs up mid-body gets no answer either way
+    let Ok(bytes) = axum::body::to_bytes(body, limit).await else {
+        return too_large(&path, limit);
+    };
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
+
+pub async fn enforce(
+    State(state): State<SharedApiState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let bytes = settings::current(&state.app_handle)
+        .request_body_limit
+        .bytes();
+    check(request, next, bytes).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::DefaultBodyLimit;
+    use axum::routing::post;
+    use axum::{middleware, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn bodies_over_the_limit_get_a_parseable_error() {
+        let app = Router::new()
+            .route(
+                "/mcp",
+                post(|body: String| async move { body.len().to_string() }),
+            )
+            .route("/api/v1/shapes", post(|| async { "created" }))
+            .layer(DefaultBodyLimit::disable())
+            .layer(middleware::from_fn(|request, next| {
+                check(request, next, 4 * 1024 * 1024)
+            }));
+        let send = |path: &'static str, size: usize, declare: bool| {
+            let app = app.clone();
+            async move {
+                let mut request = Request::builder().method("POST").uri(path);
+                if declare {
+                    request = request.header(header::CONTENT_LENGTH, size);
+                }
+                let body = Body::from(vec![b'x'; size]);
+                let response = app.oneshot(request.body(body).unwrap()).await.unwrap();
+                let status = response.status();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (status, String::from_utf8(bytes.to_vec()).unwrap())
+            }
+        };
+
+        // Over axum's own 2 MB default, under ours
+        assert_eq!(
+            send("/mcp", 3 * 1024 * 1024, true).await,
+            (StatusCode::OK, (3 * 1024 * 1024).to_string())
+        );
+        for declare in [true, false] {
+            let (status, body) = send("/mcp", 5 * 1024 * 1024, declare).await;
+            assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+            let error: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(error["error"]["code"], INVALID_REQUEST);
+            assert_eq!(error["error"]["data"]["reason"], "bodyTooLarge");
+            assert_eq!(error["error"]["data"]["limitBytes"], 4 * 1024 * 1024);
+        }
+        let (status, body) = send("/api/v1/shapes", 5 * 1024 * 1024, true).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(body.contains("\"code\":\"INVALID_ARGUMENT\""));
+
+        assert_eq!(BodyLimit { max_megabytes: 0 }.bytes(), 1024 * 1024);
+        assert_eq!(
+            BodyLimit {
+                max_megabytes: 1000
+            }
+            .bytes(),
+            64 * 1024 * 1024
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Tool calls waiting on the webview.
+//!
+//! When its tool handler starts, the webview hands over a [`Channel`] in
+//! `api_register_bridge`; bridged calls are sent down it and answered with
+//! `api_response`. A call stays here until it is answered or times out, so
+//! if the webview reloads mid-call and registers a new channel, every
+//! unanswered call is sent again, in the order it was made. Calls made while
+//! no channel is registered wait for one. The webview ignores a request id it
+//! has already seen, so re-sending never runs a call twice in one page.
+//!
+//! When the page starts to (re)load its channel is dropped, so calls made
+//! during a reload are held rather than sent to a page that is going away.
+//! A held call waits up to [`READY_TIMEOUT_SECS`] for the page to register,
+//! then the usual answer timeout applies.
+
+use serde_json::Value;
+use std::sync::Arc;
+use tauri::ipc::Channel;
+use tauri::Manager;
+use tokio::sync::{oneshot, watch};
+
+use crate::api::{McpToolRequest, SharedApiState};
+
+/// How long a call waits for the window to register a channel.
+pub const READY_TIMEOUT_SECS: u64 = 30;
+
+struct PendingCall {
+    request: McpToolRequest,
+    reply: oneshot::Sender<Value>,
+}
+
+#[derive(Default)]
+pub struct Bridge {
+    channel: Option<Channel<McpToolRequest>>,
+    /// Oldest first.
+    calls: Vec<PendingCall>,
+    /// Whether a channel is registered.
+    ready: watch::Sender<bool>,
+}
+
+impl Bridge {
+    /// Use `channel` from now on, and send it every unanswered call.
+    pub fn attach(&mut self, channel: Channel<McpToolRequest>) -> Result<usize, String> {
+        for call in &self.calls {
+            channel
+                .send(call.request.clone())
+                .map_err(|e| format!("Failed to reach the window: {}", e))?;
+        }
+        self.channel = Some(channel);
+        self.ready.send_replace(true);
+        Ok(self.calls.len())
+    }
+
+    /// Hold calls until the next [`attach`](Self::attach).
+    pub fn detach(&mut self) {
+        self.channel = None;
+        self.ready.send_replace(false);
+    }
+
+    /// Record `request` and send it if a channel is registered; whether it
+    /// was sent. A call that was not sent goes out with the next channel. If
+    /// sending fails the channel is dropped.
+    pub fn send(
+        &mut self,
+        request: McpToolRequest,
+        reply: oneshot::Sender<Value>,
+    ) -> Result<bool, String> {
+        let sent = match &self.channel {
+            Some(channel) => channel
+                .send(request.clone())
+                .map(|_| true)
+                .map_err(|e| format!("Failed to reach the window: {}", e)),
+            None => Ok(false),
+        };
+        if sent.is_err() {
+            self.detach();
+        }
+        self.calls.push(PendingCall { request, reply });
+        sent
+    }
+
+    /// Follows whether a channel is registered.
+    pub fn ready(&self) -> watch::Receiver<bool> {
+        self.ready.subscribe()
+    }
+
+    /// Deliver the webview's answer to request `id`. False if it is no
+    /// longer waiting.
+    pub fn answer(&mut self, id: &str, result: Value) -> bool {
+        match self.calls.iter().position(|c| c.request.request_id == id) {
+            Some(index) => {
+                let call = self.calls.remove(index);
+                call.reply.send(result).is_ok()
+            }
+            None => false,
+        }
+    }
+
+    /// Stop waiting for request `id`.
+    pub fn abandon(&mut self, id: &str) {
+        self.calls.retain(|c| c.request.request_id != id);
+    }
+
+    /// Calls sent to the webview (or waiting for it) and not answered yet.
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+}
+
+/// The page started to load: hold calls until it registers again.
+pub fn on_page_started(app: &tauri::AppHandle) {
+    let Some(state) = app.try_state::<SharedApiState>() else {
+        return;
+    };
+    let state = Arc::clone(&state);
+    tauri::async_runtime::spawn(async move {
+        state.bridge.lock().await.detach();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use tauri::ipc::InvokeResponseBody;
+
+    fn request(id: &str) -> McpToolRequest {
+        McpToolRequest {
+            request_id: id.to_string(),
+            tool_name: "get_shape".to_string(),
+            arguments: json!({ "id": "s1" }),
+        }
+    }
+
+    /// A channel that records the request ids sent down it.
+    fn recording() -> (Channel<McpToolRequest>, Arc<Mutex<Vec<String>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&sent);
+        let channel = Channel::new(move |body| {
+            if let InvokeResponseBody::Json(text) = body {
+                let request: Value = serde_json::from_str(&text).unwrap();
+                log.lock()
+                    .unwrap()
+                    .push(request["request_id"].as_str().unwrap().to_string());
+            }
+            Ok(())
+        });
+        (channel, sent)
+    }
+
+    #[test]
+    fn unanswered_calls_are_sent_again_to_a_new_channel() {
+        let mut bridge = Bridge::default();
+        let ready = bridge.ready();
+        let (tx1, _rx1) = oneshot::channel();
+        assert_eq!(bridge.send(request("r1"), tx1), Ok(false));
+
+        let (first, first_sent) = recording();
+        assert_eq!(bridge.attach(first), Ok(1));
+        assert!(*ready.borrow());
+        let (tx2, mut rx2) = oneshot::channel();
+        assert_eq!(bridge.send(request("r2"), tx2), Ok(true));
+        assert_eq!(*first_sent.lock().unwrap(), ["r1", "r2"]);
+
+        assert!(bridge.answer("r2", json!({ "ok": true })));
+        assert_eq!(rx2.try_recv().unwrap(), json!({ "ok": true }));
+        assert!(!bridge.answer("r2", json!({})));
+
+        // The page reloads before answering r1
+        bridge.detach();
+        assert!(!*ready.borrow());
+        let (tx3, _rx3) = oneshot::channel();
+        assert_eq!(bridge.send(request("r3"), tx3), Ok(false));
+        bridge.abandon("r3");
+        let (second, second_sent) = recording();
+        assert_eq!(bridge.attach(second), Ok(1));
+        assert_eq!(*second_sent.lock().unwrap(), ["r1"]);
+
+        bridge.abandon("r1");
+        assert_eq!(bridge.len(), 0);
+    }
+}
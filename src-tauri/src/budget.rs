@@ -0,0 +1,386 @@
+//! Response size budgets for tools that return shapes.
+//!
+//! On a giant board `get_canvas` and `list_shapes` can return megabytes,
+//! more than an agent can take in. They (and `get_shapes_in_region`) accept `maxBytes` and `maxShapes`,
+//! defaulting to the `responseBudget` setting. When the shapes do not fit,
+//! the response holds the highest-priority shapes that do, a summary of the
+//! whole list and a `nextCursor`; passing it back as `cursor` returns the
+//! next page. Labelled shapes come first, then other shapes, connectors and
+//! freehand strokes, each top to bottom and left to right.
+
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::geometry;
+
+pub const BUDGETED_TOOLS: &[&str] = &["get_canvas", "list_shapes", "get_shapes_in_region"];
+const DEFAULT_MAX_BYTES: usize = 1_000_000;
+/// Fields kept when a single shape is too large for the budget.
+const OUTLINE_FIELDS: &[&str] = &[
+    "id", "type", "x", "y", "x2", "y2", "width", "height", "rotation", "text",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ResponseBudget {
+    /// Largest response in bytes; 0 for no limit.
+    pub max_bytes: usize,
+    /// Most shapes per response; 0 for no limit.
+    pub max_shapes: usize,
+}
+
+impl Default for ResponseBudget {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_shapes: 0,
+        }
+    }
+}
+
+/// Where the next page starts, and the shape order it was computed for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Cursor {
+    offset: usize,
+    order: String,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(cursor: &str) -> Result<Self, String> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| "Invalid cursor".to_string())
+    }
+}
+
+/// The budget for one call, and the page it asks for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    budget: ResponseBudget,
+    cursor: Option<Cursor>,
+}
+
+/// Remove the budget arguments from a call to a budgeted tool, so the
+/// webview never sees them. `None` for other tools.
+pub fn take(
+    tool_name: &str,
+    arguments: &mut Value,
+    default: &ResponseBudget,
+) -> Result<Option<Request>, String> {
+    if !BUDGETED_TOOLS.contains(&tool_name) {
+        return Ok(None);
+    }
+    let mut request = Request {
+        budget: default.clone(),
+        cursor: None,
+    };
+    let Some(args) = arguments.as_object_mut() else {
+        return Ok(Some(request));
+    };
+    for (key, limit) in [
+        ("maxBytes", &mut request.budget.max_bytes),
+        ("maxShapes", &mut request.budget.max_shapes),
+    ] {
+        match args.remove(key) {
+            None | Some(Value::Null) => {}
+            Some(value) => {
+                *limit = value
+                    .as_u64()
+                    .map(|n| n as usize)
+                    .ok_or_else(|| format!("{} must be a non-negative integer", key))?;
+            }
+        }
+    }
+    request.cursor = match args.remove("cursor") {
+        None | Some(Value::Null) => None,
+        Some(Value::String(cursor)) => Some(Cursor::decode(&cursor)?),
+        Some(_) => return Err("cursor must be a string".to_string()),
+    };
+    Ok(Some(request))
+}
+
+fn size(value: &Value) -> usize {
+    serde_json::to_string(value).map_or(0, |s| s.len())
+}
+
+/// Lower sorts first: labelled shapes, other shapes, connectors, strokes.
+fn class(shape: &Value) -> u8 {
+    let labelled = shape
+        .get("text")
+        .and_then(Value::as_str)
+        .is_some_and(|t| !t.trim().is_empty());
+    match shape.get("type").and_then(Value::as_str) {
+        Some("freedraw") => 3,
+        Some("line" | "arrow") => 2,
+        _ if labelled => 0,
+        _ => 1,
+    }
+}
+
+/// Indices of `shapes` in the order pages are filled.
+fn priority_order(shapes: &[Value]) -> Vec<usize> {
+    let keys: Vec<(u8, f64, f64)> = shapes
+        .iter()
+        .map(|shape| {
+            let corner = geometry::shape_bounds(shape).map_or((0.0, 0.0), |b| (b.min_y, b.min_x));
+            (class(shape), corner.0, corner.1)
+        })
+        .collect();
+    let mut order: Vec<usize> = (0..shapes.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (ka, kb) = (keys[a], keys[b]);
+        ka.0.cmp(&kb.0)
+            .then(ka.1.total_cmp(&kb.1))
+            .then(ka.2.total_cmp(&kb.2))
+    });
+    order
+}
+
+/// Identifies the ordered shape list, so a cursor issued for one board is
+/// not applied to another.
+fn fingerprint(shapes: &[Value], order: &[usize]) -> String {
+    let mut hasher = Sha256::new();
+    for &i in order {
+        hasher.update(
+            shapes[i]
+                .get("id")
+                .and_then(Value::as_str)
+                .unwrap_or_default(),
+        );
+        hasher.update(b"\n");
+    }
+    URL_SAFE_NO_PAD.encode(&hasher.finalize()[..12])
+}
+
+/// A shape too large for the budget on its own, cut down to its geometry.
+fn outline(shape: &Value) -> Value {
+    let mut outline: serde_json::Map<String, Value> = OUTLINE_FIELDS
+        .iter()
+        .filter_map(|&key| Some((key.to_string(), shape.get(key)?.clone())))
+        .collect();
+    outline.insert("outlineOnly".to_string(), json!(true));
+    Value::Object(outline)
+}
+
+/// Fit the `shapes` of a budgeted tool's `result` into the budget.
+/// Results that fit, and errors, pass through unchanged.
+pub fn apply(mut result: Value, request: &Request) -> Result<Value, String> {
+    let budget = &request.budget;
+    let shapes = match result.get_mut("shapes").map(Value::take) {
+        Some(Value::Array(shapes)) => shapes,
+        other => {
+            if let Some(other) = other {
+                result["shapes"] = other;
+            }
+            return Ok(result);
+        }
+    };
+    let sizes: Vec<usize> = shapes.iter().map(|s| size(s) + 1).collect();
+    result["shapes"] = json!([]);
+    let base = size(&result);
+
+    let fits_bytes =
+        budget.max_bytes == 0 || base + sizes.iter().sum::<usize>() <= budget.max_bytes;
+    let fits_count = budget.max_shapes == 0 || shapes.len() <= budget.max_shapes;
+    if request.cursor.is_none() && fits_bytes && fits_count {
+        result["shapes"] = Value::Array(shapes);
+        return Ok(result);
+    }
+
+    let order = priority_order(&shapes);
+    let order_id = fingerprint(&shapes, &order);
+    let offset = match &request.cursor {
+        Some(cursor) if cursor.order != order_id || cursor.offset > shapes.len() => {
+            return Err(
+                "The shapes changed since this cursor was issued; start again without a cursor"
+                    .to_string(),
+            )
+        }
+        Some(cursor) => cursor.offset,
+        None => 0,
+    };
+
+    let mut page = Vec::new();
+    let mut outlined = Vec::new();
+    let mut used = base;
+    for &i in &order[offset..] {
+        if budget.max_shapes > 0 && page.len() >= budget.max_shapes {
+            break;
+        }
+        if budget.max_bytes == 0 || used + sizes[i] <= budget.max_bytes {
+            page.push(shapes[i].clone());
+            used += sizes[i];
+            continue;
+        }
+        if page.is_empty() {
+            // Too large on its own: send its outline so every page moves on.
+            page.push(outline(&shapes[i]));
+            outlined.push(shapes[i]["id"].clone());
+        }
+        break;
+    }
+
+    let end = offset + page.len();
+    let mut by_type: BTreeMap<&str, usize> = BTreeMap::new();
+    for shape in &shapes {
+        let kind = shape
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown");
+        *by_type.entry(kind).or_default() += 1;
+    }
+    let mut summary = json!({
+        "totalShapes": shapes.len(),
+        "offset": offset,
+        "returnedShapes": page.len(),
+        "remainingShapes": shapes.len() - end,
+        "remainingBytes": order[end..].iter().map(|&i| sizes[i]).sum::<usize>(),
+        "byType": by_type,
+        "bounds": geometry::combined_bounds(&shapes),
+    });
+    if !outlined.is_empty() {
+        summary["outlineOnly"] = Value::Array(outlined);
+    }
+    result["shapes"] = Value::Array(page);
+    result["truncated"] = json!(true);
+    result["summary"] = summary;
+    if end < shapes.len() {
+        let cursor = Cursor {
+            offset: end,
+            order: order_id,
+        };
+        result["nextCursor"] = json!(cursor.encode());
+    }
+    Ok(result)
+}
+
+/// Add the budget arguments to `tool`'s input schema if it is budgeted.
+pub fn extend_schema(tool: &mut Value) {
+    let budgeted = tool
+        .get("name")
+        .and_then(Value::as_str)
+        .is_some_and(|name| BUDGETED_TOOLS.contains(&name));
+    let Some(properties) = tool["inputSchema"]["properties"].as_object_mut() else {
+        return;
+    };
+    if !budgeted {
+        return;
+    }
+    let added = json!({
+        "maxBytes": { "type": "integer", "minimum": 0, "description": "Largest response in bytes (0: no limit; default: the server's setting, about 1 MB). Larger results are truncated with a summary and a nextCursor" },
+        "maxShapes": { "type": "integer", "minimum": 0, "description": "Most shapes to return (0: no limit)" },
+        "cursor": { "type": "string", "description": "nextCursor from a truncated response, to fetch the next shapes" }
+    });
+    if let Value::Object(added) = added {
+        properties.extend(added);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canvas() -> Value {
+        json!({
+            "shapes": [
+                { "id": "arrow", "type": "arrow", "x": 0.0, "y": 0.0, "x2": 10.0, "y2": 10.0 },
+                { "id": "low", "type": "rectangle", "x": 0.0, "y": 500.0, "width": 10.0, "height": 10.0 },
+                { "id": "high", "type": "rectangle", "x": 0.0, "y": 0.0, "width": 10.0, "height": 10.0 },
+                { "id": "note", "type": "sticky", "x": 900.0, "y": 900.0, "width": 10.0, "height": 10.0, "text": "hi" },
+            ],
+            "shapeCount": 4,
+        })
+    }
+
+    fn request(arguments: Value) -> Request {
+        let mut arguments = arguments;
+        let request = take("get_canvas", &mut arguments, &ResponseBudget::default())
+            .unwrap()
+            .unwrap();
+        assert!(arguments.get("maxShapes").is_none() && arguments.get("cursor").is_none());
+        request
+    }
+
+    fn ids(result: &Value) -> Vec<&str> {
+        result["shapes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["id"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn results_within_budget_are_unchanged() {
+        let result = apply(canvas(), &request(json!({}))).unwrap();
+        assert_eq!(result, canvas());
+        assert!(
+            take("get_shape", &mut json!({}), &ResponseBudget::default())
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn pages_follow_priority_until_the_cursor_runs_out() {
+        let first = apply(canvas(), &request(json!({ "maxShapes": 3 }))).unwrap();
+        assert_eq!(ids(&first), ["note", "high", "low"]);
+        assert_eq!(first["truncated"], true);
+        assert_eq!(first["summary"]["remainingShapes"], 1);
+        assert_eq!(first["summary"]["byType"]["rectangle"], 2);
+        assert_eq!(first["shapeCount"], 4);
+
+        let cursor = first["nextCursor"].as_str().unwrap();
+        let second = apply(
+            canvas(),
+            &request(json!({ "maxShapes": 3, "cursor": cursor })),
+        )
+        .unwrap();
+        assert_eq!(ids(&second), ["arrow"]);
+        assert_eq!(second["summary"]["offset"], 3);
+        assert!(second.get("nextCursor").is_none());
+
+        let mut changed = canvas();
+        changed["shapes"].as_array_mut().unwrap().pop();
+        assert!(apply(changed, &request(json!({ "cursor": cursor }))).is_err());
+    }
+
+    #[test]
+    fn byte_budgets_cut_pages_and_outline_oversized_shapes() {
+        let mut big = canvas();
+        big["shapes"][2]["src"] = json!("x".repeat(5000));
+        let first = apply(big.clone(), &request(json!({ "maxBytes": 400 }))).unwrap();
+        assert_eq!(ids(&first), ["note"]);
+
+        let cursor = first["nextCursor"].as_str().unwrap().to_string();
+        let second = apply(big, &request(json!({ "maxBytes": 400, "cursor": cursor }))).unwrap();
+        assert_eq!(ids(&second), ["high"]);
+        assert_eq!(second["shapes"][0]["outlineOnly"], true);
+        assert!(second["shapes"][0].get("src").is_none());
+        assert_eq!(second["summary"]["outlineOnly"], json!(["high"]));
+    }
+
+    #[test]
+    fn bad_budget_arguments_are_rejected() {
+        let budget = ResponseBudget::default();
+        assert!(take("list_shapes", &mut json!({ "maxBytes": -1 }), &budget).is_err());
+        assert!(take("list_shapes", &mut json!({ "cursor": "%%" }), &budget).is_err());
+        assert_eq!(
+            take("list_shapes", &mut Value::Null, &budget).unwrap(),
+            Some(Request {
+                budget,
+                cursor: None
+            })
+        );
+    }
+}
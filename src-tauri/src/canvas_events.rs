@@ -0,0 +1,130 @@
+//! Canvas change events pushed to connected MCP clients.
+//!
+//! The webview reports which shapes were created, updated or deleted with
+//! each edit (`api_notify_change`); each report goes out as one
+//! `notifications/canvas/changed` message to every open notification stream
+//! (`GET /mcp`, legacy SSE, WebSocket and tunnel connections), whether or not
+//! the client subscribed to a resource. Unlike resource notifications these
+//! carry the changes themselves, so a client can follow the board without
+//! reading it back. Streams held by the sidecar process get them through a
+//! `Broadcast` frame (`sidecar.rs`).
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::subscriptions::Notifier;
+
+/// Events buffered per stream; a client further behind skips the oldest.
+const CAPACITY: usize = 256;
+
+pub type CanvasEvents = broadcast::Sender<Value>;
+
+pub fn channel() -> CanvasEvents {
+    broadcast::channel(CAPACITY).0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// One shape change, as reported by the webview. `shape` is the new state,
+/// absent for deletions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShapeChange {
+    pub kind: ChangeKind,
+    pub shape_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shape: Option<Value>,
+}
+
+/// The `notifications/canvas/changed` message for changes to tab `tab_id`.
+pub fn notification(tab_id: &str, changes: &[ShapeChange]) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/canvas/changed",
+        "params": { "tabId": tab_id, "changes": changes },
+    })
+}
+
+/// Copy events from `events` onto `stream` until either side closes.
+pub fn forward(events: &CanvasEvents, stream: Notifier) {
+    let mut events = events.subscribe();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(message) => {
+                        if stream.send(message).is_err() {
+                            return;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Canvas event stream fell behind; skipped {} events", skipped);
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+                _ = stream.closed() => return,
+            }
+        }
+    });
+}
+
+/// Send shape changes on visible tab `tab_id` to every stream; returns how
+/// many streams the notification went to.
+pub fn publish(events: &CanvasEvents, tab_id: &str, changes: &[ShapeChange]) -> usize {
+    if changes.is_empty() {
+        return 0;
+    }
+    events.send(notification(tab_id, changes)).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn changes_use_the_webview_field_names() {
+        let change: ShapeChange = serde_json::from_value(json!({
+            "kind": "deleted",
+            "shapeId": "s1",
+        }))
+        .unwrap();
+        assert_eq!(change.kind, ChangeKind::Deleted);
+        let message = notification("tab", &[change]);
+        assert_eq!(message["method"], "notifications/canvas/changed");
+        assert_eq!(
+            message["params"],
+            json!({ "tabId": "tab", "changes": [{ "kind": "deleted", "shapeId": "s1" }] })
+        );
+    }
+
+    #[tokio::test]
+    async fn events_reach_every_stream_until_it_closes() {
+        let events = channel();
+        let (a_tx, mut a) = mpsc::unbounded_channel();
+        let (b_tx, b) = mpsc::unbounded_channel();
+        forward(&events, a_tx);
+        forward(&events, b_tx);
+        assert_eq!(events.receiver_count(), 2);
+
+        drop(b);
+        events.send(json!({ "n": 1 })).unwrap();
+        assert_eq!(a.recv().await.unwrap(), json!({ "n": 1 }));
+
+        // The closed stream's forwarder stops and drops its receiver.
+        for _ in 0..100 {
+            if events.receiver_count() == 1 {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(events.receiver_count(), 1);
+    }
+}
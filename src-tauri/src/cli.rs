@@ -0,0 +1,274 @@
+//! Headless export (`napkin export board.napkin --format png --out board.png`).
+//!
+//! The board that was showing when the document was saved is drawn by the
+//! native renderer (`render.rs`, or `pdf.rs` for PDF) and written to a file,
+//! and the process exits without opening a window, so docs pipelines can
+//! turn `.napkin` files into images. Any storage format opens. The user's
+//! settings file, if there is one, supplies the `pngExport`, `svgExport`
+//! and `exportWatermark` settings as it does for the File menu; export
+//! hooks do not run.
+
+use std::path::PathBuf;
+
+use crate::docstore;
+use crate::pdf::{self, PdfExportOptions};
+use crate::render::{self, RenderOptions};
+use crate::settings::{Settings, SETTINGS_FILE};
+use crate::stdio;
+use crate::units::DocumentScale;
+
+const USAGE: &str = "\
+Usage: napkin export <document> [--format png|svg|pdf] [--out <file>] [--scale <n>]
+
+  --format  png, svg or pdf; defaults to the --out file's extension, or png
+  --out     where to write; defaults to the document's name with the format's extension
+  --scale   PNG pixels per canvas pixel, from 0.5 to 4 (default from settings, or 1)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Png,
+    Svg,
+    Pdf,
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Ok(Self::Png),
+            "svg" => Ok(Self::Svg),
+            "pdf" => Ok(Self::Pdf),
+            _ => Err(format!("Unknown format '{}': use png, svg or pdf", name)),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Svg => "svg",
+            Self::Pdf => "pdf",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct ExportArgs {
+    input: PathBuf,
+    format: Format,
+    out: PathBuf,
+    scale: Option<f64>,
+}
+
+/// `args` are the ones after `export`. `Ok(None)` asks for the usage.
+fn parse_args(args: &[String]) -> Result<Option<ExportArgs>, String> {
+    let mut input = None;
+    let mut format = None;
+    let mut out = None;
+    let mut scale = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--format" | "-f" => format = Some(Format::parse(&value(arg)?)?),
+            "--out" | "-o" => out = Some(PathBuf::from(value(arg)?)),
+            "--scale" => {
+                let text = value(arg)?;
+                let n: f64 = text
+                    .parse()
+                    .map_err(|_| format!("--scale must be a number, not '{}'", text))?;
+                scale = Some(n);
+            }
+            flag if flag.starts_with('-') => return Err(format!("Unknown option {}", flag)),
+            path if input.is_none() => input = Some(PathBuf::from(path)),
+            extra => return Err(format!("Unexpected argument '{}'", extra)),
+        }
+    }
+    let input = input.ok_or("Which document? Give the path of a .napkin file")?;
+    let format = match (format, &out) {
+        (Some(format), _) => format,
+        (None, Some(out)) => match out.extension().and_then(|e| e.to_str()) {
+            Some(extension) => Format::parse(extension)?,
+            None => Format::Png,
+        },
+        (None, None) => Format::Png,
+    };
+    let out = out.unwrap_or_else(|| input.with_extension(format.extension()));
+    Ok(Some(ExportArgs {
+        input,
+        format,
+        out,
+        scale,
+    }))
+}
+
+/// The settings the app would use, or the defaults without a settings file.
+fn user_settings() -> Settings {
+    stdio::read_config(SETTINGS_FILE)
+        .and_then(|json| match serde_json::from_str(&json) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                eprintln!("napkin export: ignoring unreadable settings: {}", e);
+                None
+            }
+        })
+        .unwrap_or_default()
+}
+
+/// Draw the document's active board as `args` ask and write it out.
+fn export(args: &ExportArgs, settings: &Settings) -> Result<(), String> {
+    let board = docstore::active_board(&args.input)?;
+    let shapes = board["shapes"].as_array().map_or(&[][..], Vec::as_slice);
+    let watermark = Some(settings.export_watermark.clone()).filter(|w| w.is_visible());
+    let bytes = match args.format {
+        Format::Png => {
+            let mut png_export = settings.png_export.clone();
+            if let Some(scale) = args.scale {
+                png_export.scale = scale;
+                png_export.validate()?;
+            }
+            let options = RenderOptions {
+                watermark,
+                ..png_export.render_options()
+            };
+            render::png(&render::typed(shapes), &options)?.bytes
+        }
+        Format::Svg => {
+            let options = RenderOptions {
+                watermark,
+                outline_text: settings.svg_export.outline_text,
+                ..Default::default()
+            };
+            render::svg(&render::typed(shapes), &options)?.into_bytes()
+        }
+        Format::Pdf => {
+            let scale: Option<DocumentScale> = serde_json::from_value(board["scale"].clone())
+                .ok()
+                .flatten();
+            let options = PdfExportOptions {
+                title: board["metadata"]["title"].as_str().map(str::to_string),
+                watermark,
+                ..Default::default()
+            };
+            pdf::render_pdf(shapes, scale.as_ref(), &options)?
+        }
+    };
+    std::fs::write(&args.out, bytes)
+        .map_err(|e| format!("Failed to write {}: {}", args.out.display(), e))
+}
+
+/// Run `napkin export ...`; `args` are the ones after `export`.
+pub fn run(args: &[String]) -> i32 {
+    let args = match parse_args(args) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(e) => {
+            eprintln!("napkin export: {}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    match export(&args, &user_settings()) {
+        Ok(()) => {
+            println!("{}", args.out.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("napkin export: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn the_format_follows_the_flag_then_the_output_name() {
+        let parsed = parse_args(&args(&["board.napkin"])).unwrap().unwrap();
+        assert_eq!(parsed.format, Format::Png);
+        assert_eq!(parsed.out, PathBuf::from("board.png"));
+
+        let parsed = parse_args(&args(&["board.napkin", "--out", "docs/b.SVG"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.format, Format::Svg);
+
+        let parsed = parse_args(&args(&["--format", "pdf", "board.napkin", "--scale", "2"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.format, Format::Pdf);
+        assert_eq!(parsed.out, PathBuf::from("board.pdf"));
+        assert_eq!(parsed.scale, Some(2.0));
+
+        assert_eq!(parse_args(&args(&["--help"])).unwrap(), None);
+        for bad in [
+            &["board.napkin", "--format", "gif"][..],
+            &["board.napkin", "--out"],
+            &["board.napkin", "--scale", "big"],
+            &["board.napkin", "other.napkin"],
+            &["--out", "b.png"],
+        ] {
+            assert!(parse_args(&args(bad)).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn exports_the_saved_board_in_each_format() {
+        let dir = std::env::temp_dir().join(format!("napkin-cli-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("board.napkin");
+        let board = json!({
+            "metadata": { "title": "Flow" },
+            "shapes": [{ "id": "a", "type": "rectangle", "x": 0, "y": 0,
+                "width": 200, "height": 100, "text": "Start" }],
+        });
+        std::fs::write(&input, board.to_string()).unwrap();
+
+        let settings = Settings::default();
+        for (format, magic) in [
+            (Format::Png, &b"\x89PNG"[..]),
+            (Format::Svg, b"<svg"),
+            (Format::Pdf, b"%PDF"),
+        ] {
+            let out = dir.join(format!("out.{}", format.extension()));
+            let args = ExportArgs {
+                input: input.clone(),
+                format,
+                out: out.clone(),
+                scale: Some(2.0),
+            };
+            export(&args, &settings).unwrap();
+            assert!(
+                std::fs::read(&out).unwrap().starts_with(magic),
+                "{:?}",
+                format
+            );
+        }
+
+        let too_big = ExportArgs {
+            input: input.clone(),
+            format: Format::Png,
+            out: dir.join("big.png"),
+            scale: Some(10.0),
+        };
+        assert!(export(&too_big, &settings).is_err());
+        let missing = ExportArgs {
+            input: dir.join("missing.napkin"),
+            ..too_big
+        };
+        assert!(export(&missing, &settings).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,204 @@
+//! The MCP clients attached to the server, for showing which agents are
+//! connected.
+//!
+//! A client is recorded when its connection opens: an HTTP session at
+//! `initialize`, a WebSocket, legacy SSE stream or tunnel when it is
+//! accepted. Its name and version come from `initialize`, at which point
+//! `mcp-client-connected` is emitted to the webview. Every message it sends
+//! updates `lastSeen`. The remote address is unknown when the server runs in
+//! a separate process, and for the tunnel, whose peer is the relay.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use tauri::Emitter;
+
+use crate::api::SharedApiState;
+use crate::embed;
+
+/// Emitted with an [`ApiClient`] once a client has introduced itself.
+pub const CONNECTED_EVENT: &str = "mcp-client-connected";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Http,
+    WebSocket,
+    Sse,
+    Tunnel,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiClient {
+    /// The session or connection id.
+    pub id: String,
+    pub transport: Transport,
+    /// `clientInfo` from `initialize`.
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub remote_addr: Option<String>,
+    pub connected_at: u64,
+    pub last_seen: u64,
+}
+
+#[derive(Default)]
+pub struct ClientRegistry {
+    clients: HashMap<String, ApiClient>,
+}
+
+impl ClientRegistry {
+    pub fn connect(
+        &mut self,
+        id: &str,
+        transport: Transport,
+        remote_addr: Option<String>,
+        now: u64,
+    ) {
+        self.clients.insert(
+            id.to_string(),
+            ApiClient {
+                id: id.to_string(),
+                transport,
+                name: None,
+                version: None,
+                remote_addr,
+                connected_at: now,
+                last_seen: now,
+            },
+        );
+    }
+
+    /// Record `clientInfo` from `initialize` `params`; the client, if it is
+    /// connected.
+    pub fn identify(&mut self, id: &str, params: &Value) -> Option<ApiClient> {
+        let client = self.clients.get_mut(id)?;
+        let text = |pointer: &str| {
+            params
+                .pointer(pointer)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        client.name = text("/clientInfo/name");
+        client.version = text("/clientInfo/version");
+        Some(client.clone())
+    }
+
+    pub fn touch(&mut self, id: &str, now: u64) {
+        if let Some(client) = self.clients.get_mut(id) {
+            client.last_seen = now;
+        }
+    }
+
+    pub fn disconnect(&mut self, id: &str) {
+        self.clients.remove(id);
+    }
+
+    /// Forget HTTP clients whose session has ended or expired.
+    pub fn retain_sessions(&mut self, is_open: impl Fn(&str) -> bool) {
+        self.clients
+            .retain(|id, c| c.transport != Transport::Http || is_open(id));
+    }
+
+    /// Connected clients, most recently active first.
+    pub fn list(&self) -> Vec<ApiClient> {
+        let mut clients: Vec<ApiClient> = self.clients.values().cloned().collect();
+        clients.sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then(a.id.cmp(&b.id)));
+        clients
+    }
+}
+
+/// Record a newly accepted connection.
+pub async fn connect(
+    state: &SharedApiState,
+    id: &str,
+    transport: Transport,
+    remote_addr: Option<String>,
+) {
+    state
+        .clients
+        .lock()
+        .await
+        .connect(id, transport, remote_addr, embed::now_secs());
+}
+
+/// Note activity from `id`, and announce it if `body` is (or contains) its
+/// `initialize`.
+pub async fn observe(state: &SharedApiState, id: &str, body: &Value) {
+    let messages = match body {
+        Value::Array(batch) => batch.iter().collect(),
+        message => vec![message],
+    };
+    let initialize = messages
+        .into_iter()
+        .find(|m| m.get("method").and_then(Value::as_str) == Some("initialize"));
+    let mut clients = state.clients.lock().await;
+    clients.touch(id, embed::now_secs());
+    let params = initialize.and_then(|m| m.get("params")).cloned();
+    if let Some(client) = params.and_then(|params| clients.identify(id, &params)) {
+        drop(clients);
+        announce(state, &client);
+    }
+}
+
+pub fn announce(state: &SharedApiState, client: &ApiClient) {
+    log::info!(
+        "MCP client connected: {} over {:?}{}",
+        client.name.as_deref().unwrap_or("unnamed client"),
+        client.transport,
+        client
+            .remote_addr
+            .as_deref()
+            .map(|addr| format!(" from {}", addr))
+            .unwrap_or_default()
+    );
+    if let Err(e) = state.app_handle.emit(CONNECTED_EVENT, client) {
+        log::warn!("Failed to emit {}: {}", CONNECTED_EVENT, e);
+    }
+}
+
+// --- Tauri command ---
+
+/// Clients attached to the MCP server, most recently active first.
+#[tauri::command]
+pub async fn list_api_clients(
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<Vec<ApiClient>, String> {
+    let sessions = state.sessions.lock().await;
+    let mut clients = state.clients.lock().await;
+    clients.retain_sessions(|id| sessions.get(id).is_some());
+    Ok(clients.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn clients_are_identified_and_listed_by_activity() {
+        let mut registry = ClientRegistry::default();
+        registry.connect(
+            "ws",
+            Transport::WebSocket,
+            Some("10.0.0.2:5000".to_string()),
+            10,
+        );
+        registry.connect("http", Transport::Http, None, 20);
+        assert_eq!(registry.identify("unknown", &json!({})), None);
+
+        let params = json!({ "clientInfo": { "name": "cursor", "version": "1.4" } });
+        let client = registry.identify("ws", &params).unwrap();
+        assert_eq!(client.name.as_deref(), Some("cursor"));
+        assert_eq!(client.version.as_deref(), Some("1.4"));
+
+        registry.touch("ws", 30);
+        let ids: Vec<String> = registry.list().into_iter().map(|c| c.id).collect();
+        assert_eq!(ids, ["ws", "http"]);
+
+        registry.retain_sessions(|_| false);
+        assert_eq!(registry.list().len(), 1);
+        registry.disconnect("ws");
+        assert!(registry.list().is_empty());
+    }
+}
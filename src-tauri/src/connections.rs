@@ -0,0 +1,151 @@
+//! The "AI Connections" menu: whether the MCP server is running and on
+//! which port, items to start and stop it, and one to copy a client
+//! configuration for it.
+//!
+//! The status line and which of Start/Stop is enabled follow the server:
+//! [`update`] is called wherever the server starts, stops or moves port,
+//! whether from Settings, the menu, or the sidecar's supervisor. Starting or
+//! stopping from the menu is also reported to the webview, which remembers
+//! it the way the Settings toggle does, for the next launch.
+
+use serde_json::json;
+use tauri::menu::{MenuItem, PredefinedMenuItem, Submenu};
+use tauri::{Emitter, Manager};
+
+use crate::api::{self, SharedApiState};
+use crate::settings;
+
+const STATUS_MENU_ID: &str = "ai_connections_status";
+pub const START_MENU_ID: &str = "ai_connections_start";
+pub const STOP_MENU_ID: &str = "ai_connections_stop";
+pub const COPY_CONFIG_MENU_ID: &str = "ai_connections_copy_config";
+/// Emitted with the config text; the webview puts it on the clipboard.
+const COPY_CONFIG_EVENT: &str = "menu-copy-mcp-config";
+/// Emitted with `{ running, port }` when the menu starts or stops the server.
+const SERVER_CHANGED_EVENT: &str = "mcp-server-changed";
+
+struct MenuItems {
+    status: MenuItem<tauri::Wry>,
+    start: MenuItem<tauri::Wry>,
+    stop: MenuItem<tauri::Wry>,
+}
+
+fn status_label(port: Option<u16>) -> String {
+    match port {
+        Some(port) => format!("Running on port {}", port),
+        None => "Stopped".to_string(),
+    }
+}
+
+/// A client configuration for the server, ready to paste into an MCP
+/// client's settings.
+fn mcp_config(port: u16, https: bool, token: &str) -> String {
+    let config = json!({
+        "mcpServers": {
+            "napkin": {
+                "url": format!("{}://127.0.0.1:{}/mcp", if https { "https" } else { "http" }, port),
+                "headers": { "Authorization": format!("Bearer {}", token) },
+            }
+        }
+    });
+    serde_json::to_string_pretty(&config).unwrap_or_default()
+}
+
+/// The menu, showing a stopped server until it starts.
+pub fn submenu(app: &tauri::App) -> tauri::Result<Submenu<tauri::Wry>> {
+    let status = MenuItem::with_id(app, STATUS_MENU_ID, status_label(None), false, None::<&str>)?;
+    let start = MenuItem::with_id(app, START_MENU_ID, "Start Server", true, None::<&str>)?;
+    let stop = MenuItem::with_id(app, STOP_MENU_ID, "Stop Server", false, None::<&str>)?;
+    let copy = MenuItem::with_id(
+        app,
+        COPY_CONFIG_MENU_ID,
+        "Copy MCP Config",
+        true,
+        None::<&str>,
+    )?;
+    let submenu = Submenu::with_items(
+        app,
+        "AI Connections",
+        true,
+        &[
+            &status,
+            &PredefinedMenuItem::separator(app)?,
+            &start,
+            &stop,
+            &PredefinedMenuItem::separator(app)?,
+            &copy,
+        ],
+    )?;
+    app.manage(MenuItems {
+        status,
+        start,
+        stop,
+    });
+    Ok(submenu)
+}
+
+/// Show that the server is running on `port`, or stopped.
+pub fn update(app: &tauri::AppHandle, port: Option<u16>) {
+    if let Some(items) = app.try_state::<MenuItems>() {
+        let _ = items.status.set_text(status_label(port));
+        let _ = items.start.set_enabled(port.is_none());
+        let _ = items.stop.set_enabled(port.is_some());
+    }
+}
+
+fn announce(app: &tauri::AppHandle, port: Option<u16>) {
+    let _ = app.emit(
+        SERVER_CHANGED_EVENT,
+        json!({ "running": port.is_some(), "port": port }),
+    );
+}
+
+pub fn on_menu_click(app: &tauri::AppHandle, id: &str) {
+    let app = app.clone();
+    let id = id.to_string();
+    tauri::async_runtime::spawn(async move {
+        let Some(state) = app.try_state::<SharedApiState>() else {
+            return;
+        };
+        let result = match id.as_str() {
+            START_MENU_ID => api::start_api_server(state, None)
+                .await
+                .map(|port| announce(&app, Some(port))),
+            STOP_MENU_ID => api::stop_api_server(state)
+                .await
+                .map(|_| announce(&app, None)),
+            COPY_CONFIG_MENU_ID => {
+                let port = state.server_port.lock().await.unwrap_or(api::DEFAULT_PORT);
+                let https = settings::current(&app).mcp_tls.enabled;
+                let config = mcp_config(port, https, state.api_token());
+                app.emit(COPY_CONFIG_EVENT, config)
+                    .map_err(|e| e.to_string())
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            log::warn!("AI Connections menu: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_points_clients_at_the_server() {
+        let config: serde_json::Value =
+            serde_json::from_str(&mcp_config(21421, false, "secret")).unwrap();
+        assert_eq!(
+            config["mcpServers"]["napkin"],
+            json!({
+                "url": "http://127.0.0.1:21421/mcp",
+                "headers": { "Authorization": "Bearer secret" },
+            })
+        );
+        assert!(mcp_config(21420, true, "t").contains("https://127.0.0.1:21420/mcp"));
+        assert_eq!(status_label(Some(21420)), "Running on port 21420");
+        assert_eq!(status_label(None), "Stopped");
+    }
+}
@@ -0,0 +1,517 @@
+//! Geometry constraints between shapes.
+//!
+//! A constraint makes one shape (the follower) track another (the target):
+//! same left edge, same width, a fixed offset, or pinned inside a frame.
+//! Constraints live for the session in `ApiState`. After anything moves, the
+//! solver propagates target positions to followers and returns the shape
+//! updates to apply; chains settle over a few passes and cycles stop at
+//! `MAX_PASSES` instead of oscillating forever.
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::embed::fetch_shapes;
+use crate::geometry::Obb;
+use crate::integrity::ShapeUpdate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::Emitter;
+
+const MAX_PASSES: usize = 8;
+const EPSILON: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConstraintKind {
+    AlignLeft,
+    EqualWidth,
+    FixedOffset,
+    PinToFrame,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Constraint {
+    pub id: String,
+    pub kind: ConstraintKind,
+    /// The shape that gets moved/resized.
+    pub shape_id: String,
+    /// The shape it follows (for pin-to-frame, the frame).
+    pub target_id: String,
+    pub dx: f64,
+    pub dy: f64,
+}
+
+/// Box geometry the solver works on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Geom {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    rotation: f64,
+}
+
+impl Geom {
+    fn from_shape(shape: &serde_json::Value) -> Option<Geom> {
+        let num = |k: &str| shape.get(k).and_then(|v| v.as_f64());
+        Some(Geom {
+            x: num("x")?,
+            y: num("y")?,
+            width: num("width").unwrap_or(0.0),
+            height: num("height").unwrap_or(0.0),
+            rotation: num("rotation").unwrap_or(0.0),
+        })
+    }
+
+    /// Visual left edge, accounting for rotation.
+    fn left(&self) -> f64 {
+        Obb {
+            cx: self.x + self.width / 2.0,
+            cy: self.y + self.height / 2.0,
+            half_width: self.width / 2.0,
+            half_height: self.height / 2.0,
+            rotation: self.rotation,
+        }
+        .aabb()
+        .min_x
+    }
+}
+
+#[derive(Default)]
+pub struct ConstraintSet {
+    constraints: Vec<Constraint>,
+}
+
+impl ConstraintSet {
+    pub fn is_empty(&self) -> bool {
+        self.constraints.is_empty()
+    }
+
+    pub fn list(&self) -> &[Constraint] {
+        &self.constraints
+    }
+
+    /// Add a constraint. Offsets for fixed-offset and pin-to-frame default to
+    /// the shapes' current relative position.
+    pub fn add(
+        &mut self,
+        kind: ConstraintKind,
+        shape_id: &str,
+        target_id: &str,
+        offset: (Option<f64>, Option<f64>),
+        shapes: &[serde_json::Value],
+    ) -> Result<Constraint, String> {
+        if shape_id == target_id {
+            return Err("A shape cannot be constrained to itself".to_string());
+        }
+        let find = |id: &str| {
+            shapes
+                .iter()
+                .find(|s| s.get("id").and_then(|v| v.as_str()) == Some(id))
+                .and_then(Geom::from_shape)
+                .ok_or_else(|| format!("Shape not found: {}", id))
+        };
+        let shape = find(shape_id)?;
+        let target = find(target_id)?;
+
+        let constraint = Constraint {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            kind,
+            shape_id: shape_id.to_string(),
+            target_id: target_id.to_string(),
+            dx: offset.0.unwrap_or(shape.x - target.x),
+            dy: offset.1.unwrap_or(shape.y - target.y),
+        };
+        self.constraints.push(constraint.clone());
+        Ok(constraint)
+    }
+
+    pub fn remove(&mut self, id: &str) -> bool {
+        let before = self.constraints.len();
+        self.constraints.retain(|c| c.id != id);
+        self.constraints.len() != before
+    }
+
+    /// Drop constraints that reference shapes which no longer exist.
+    fn prune(&mut self, geoms: &HashMap<String, Geom>) {
+        self.constraints
+            .retain(|c| geoms.contains_key(&c.shape_id) && geoms.contains_key(&c.target_id));
+    }
+
+    /// Re-solve all constraints against `shapes` and return the updates needed.
+    pub fn solve(&mut self, shapes: &[serde_json::Value]) -> Vec<ShapeUpdate> {
+        let original: HashMap<String, Geom> = shapes
+            .iter()
+            .filter_map(|s| {
+                let id = s.get("id")?.as_str()?.to_string();
+                Some((id, Geom::from_shape(s)?))
+            })
+            .collect();
+        self.prune(&original);
+
+        let mut geoms = original.clone();
+        for _ in 0..MAX_PASSES {
+            let mut changed = false;
+            for c in &self.constraints {
+                let target = geoms[&c.target_id];
+                let current = geoms[&c.shape_id];
+                let next = apply(c, current, target);
+                if !approx_eq(current, next) {
+                    geoms.insert(c.shape_id.clone(), next);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut updates: Vec<ShapeUpdate> = geoms
+            .iter()
+            .filter_map(|(id, g)| {
+                let before = original[id];
+                if approx_eq(before, *g) {
+                    return None;
+                }
+                Some(ShapeUpdate {
+                    id: id.clone(),
+                    changes: changes_for(shapes, id, before, *g),
+                })
+            })
+            .collect();
+        updates.sort_by(|a, b| a.id.cmp(&b.id));
+        updates
+    }
+}
+
+fn apply(c: &Constraint, shape: Geom, target: Geom) -> Geom {
+    match c.kind {
+        ConstraintKind::AlignLeft => Geom {
+            x: shape.x + target.left() - shape.left(),
+            ..shape
+        },
+        ConstraintKind::EqualWidth => Geom {
+            width: target.width,
+            ..shape
+        },
+        ConstraintKind::FixedOffset => Geom {
+            x: target.x + c.dx,
+            y: target.y + c.dy,
+            ..shape
+        },
+        ConstraintKind::PinToFrame => {
+            // Keep the offset, but never let the shape hang outside its frame.
+            let max_x = (target.x + target.width - shape.width).max(target.x);
+            let max_y = (target.y + target.height - shape.height).max(target.y);
+            Geom {
+                x: (target.x + c.dx).clamp(target.x, max_x),
+                y: (target.y + c.dy).clamp(target.y, max_y),
+                ..shape
+            }
+        }
+    }
+}
+
+fn approx_eq(a: Geom, b: Geom) -> bool {
+    (a.x - b.x).abs() < EPSILON
+        && (a.y - b.y).abs() < EPSILON
+        && (a.width - b.width).abs() < EPSILON
+        && (a.height - b.height).abs() < EPSILON
+}
+
+/// Build an update_shape-style change set. Lines and arrows move their second
+/// endpoint along with the first.
+fn changes_for(
+    shapes: &[serde_json::Value],
+    id: &str,
+    before: Geom,
+    after: Geom,
+) -> serde_json::Value {
+    let mut changes = serde_json::Map::new();
+    let dx = after.x - before.x;
+    let dy = after.y - before.y;
+    if dx.abs() >= EPSILON {
+        changes.insert("x".into(), after.x.into());
+    }
+    if dy.abs() >= EPSILON {
+        changes.insert("y".into(), after.y.into());
+    }
+    if (after.width - before.width).abs() >= EPSILON {
+        changes.insert("width".into(), after.width.into());
+    }
+
+    let shape = shapes
+        .iter()
+        .find(|s| s.get("id").and_then(|v| v.as_str()) == Some(id));
+    if let Some(shape) = shape {
+        if let (Some(x2), Some(y2)) = (
+            shape.get("x2").and_then(|v| v.as_f64()),
+            shape.get("y2").and_then(|v| v.as_f64()),
+        ) {
+            changes.insert("x2".into(), (x2 + dx).into());
+            changes.insert("y2".into(), (y2 + dy).into());
+        }
+    }
+    serde_json::Value::Object(changes)
+}
+
+fn notify(state: &SharedApiState, count: usize) {
+    let _ = state
+        .app_handle
+        .emit("constraints-changed", serde_json::json!({ "count": count }));
+}
+
+// --- Bridge helpers ---
+
+async fn apply_updates(state: &SharedApiState, updates: &[ShapeUpdate]) -> Result<(), String> {
+    for update in updates {
+        let mut args = update.changes.clone();
+        args["id"] = serde_json::Value::String(update.id.clone());
+        bridge_tool_call(state, "update_shape", args).await?;
+    }
+    Ok(())
+}
+
+/// Re-solve after an MCP write. Cheap no-op when there are no constraints.
+pub async fn resolve_via_bridge(state: &SharedApiState) -> Result<usize, String> {
+    if state.constraints.lock().await.is_empty() {
+        return Ok(0);
+    }
+    let shapes = fetch_shapes(state).await?;
+    let updates = state.constraints.lock().await.solve(&shapes);
+    apply_updates(state, &updates).await?;
+    Ok(updates.len())
+}
+
+// --- MCP tools (handled in Rust) ---
+
+pub fn constraint_tools() -> Vec<serde_json::Value> {
+    vec![
+        serde_json::json!({
+            "name": "add_constraint",
+            "description": "Keep a shape tidy relative to another as the diagram is edited. align-left: same left edge. equal-width: same width. fixed-offset: keep the current (or given) x/y offset from the target. pin-to-frame: keep the offset inside a frame shape.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "type": { "type": "string", "enum": ["align-left", "equal-width", "fixed-offset", "pin-to-frame"] },
+                    "shapeId": { "type": "string", "description": "Shape that follows" },
+                    "targetId": { "type": "string", "description": "Shape (or frame) it follows" },
+                    "dx": { "type": "number", "description": "Offset from target x (defaults to current)" },
+                    "dy": { "type": "number", "description": "Offset from target y (defaults to current)" }
+                },
+                "required": ["type", "shapeId", "targetId"]
+            }
+        }),
+        serde_json::json!({
+            "name": "list_constraints",
+            "description": "List active geometry constraints.",
+            "inputSchema": { "type": "object", "properties": {} }
+        }),
+        serde_json::json!({
+            "name": "remove_constraint",
+            "description": "Remove a geometry constraint by ID.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string", "description": "Constraint ID" }
+                },
+                "required": ["id"]
+            }
+        }),
+    ]
+}
+
+pub async fn call_constraint_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &serde_json::Value,
+) -> Option<Result<serde_json::Value, String>> {
+    match tool_name {
+        "add_constraint" => Some(add_constraint(state, arguments).await),
+        "list_constraints" => {
+            let constraints = state.constraints.lock().await;
+            Some(Ok(serde_json::json!({ "constraints": constraints.list() })))
+        }
+        "remove_constraint" => {
+            let Some(id) = arguments.get("id").and_then(|v| v.as_str()) else {
+                return Some(Err("Missing required field: id".to_string()));
+            };
+            let mut constraints = state.constraints.lock().await;
+            if constraints.remove(id) {
+                notify(state, constraints.list().len());
+                Some(Ok(serde_json::json!({ "success": true, "id": id })))
+            } else {
+                Some(Err(format!("Constraint not found: {}", id)))
+            }
+        }
+        _ => None,
+    }
+}
+
+async fn add_constraint(
+    state: &SharedApiState,
+    arguments: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let kind: ConstraintKind = arguments
+        .get("type")
+        .cloned()
+        .ok_or("Missing required field: type")
+        .and_then(|t| serde_json::from_value(t).map_err(|_| "Unknown constraint type"))?;
+    let str_arg = |k: &str| {
+        arguments
+            .get(k)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("Missing required field: {}", k))
+    };
+    let shape_id = str_arg("shapeId")?;
+    let target_id = str_arg("targetId")?;
+    let offset = (
+        arguments.get("dx").and_then(|v| v.as_f64()),
+        arguments.get("dy").and_then(|v| v.as_f64()),
+    );
+
+    let shapes = fetch_shapes(state).await?;
+    let (constraint, updates, count) = {
+        let mut constraints = state.constraints.lock().await;
+        let constraint = constraints.add(kind, shape_id, target_id, offset, &shapes)?;
+        let updates = constraints.solve(&shapes);
+        (constraint, updates, constraints.list().len())
+    };
+    notify(state, count);
+    apply_updates(state, &updates).await?;
+
+    Ok(serde_json::json!({
+        "constraint": constraint,
+        "updatedShapes": updates.len(),
+    }))
+}
+
+// --- Tauri command ---
+
+/// Solve against the frontend's shapes after a user edit; the frontend applies
+/// the returned updates.
+#[tauri::command]
+pub async fn solve_constraints(
+    shapes: Vec<serde_json::Value>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<Vec<ShapeUpdate>, String> {
+    let mut constraints = state.constraints.lock().await;
+    let before = constraints.list().len();
+    let updates = constraints.solve(&shapes);
+    if constraints.list().len() != before {
+        notify(state.inner(), constraints.list().len());
+    }
+    Ok(updates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(id: &str, x: f64, y: f64, w: f64, h: f64) -> serde_json::Value {
+        serde_json::json!({ "id": id, "type": "rectangle", "x": x, "y": y, "width": w, "height": h })
+    }
+
+    #[test]
+    fn align_left_and_equal_width_follow_target() {
+        let shapes = vec![
+            rect("a", 100.0, 0.0, 200.0, 50.0),
+            rect("b", 40.0, 100.0, 80.0, 50.0),
+        ];
+        let mut set = ConstraintSet::default();
+        set.add(ConstraintKind::AlignLeft, "b", "a", (None, None), &shapes)
+            .unwrap();
+        set.add(ConstraintKind::EqualWidth, "b", "a", (None, None), &shapes)
+            .unwrap();
+
+        let updates = set.solve(&shapes);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(
+            updates[0].changes,
+            serde_json::json!({ "x": 100.0, "width": 200.0 })
+        );
+    }
+
+    #[test]
+    fn align_left_uses_rotated_edges() {
+        // A 200x100 box turned 90 degrees shows its left edge 50 in from x.
+        let mut target = rect("a", 0.0, 0.0, 200.0, 100.0);
+        target["rotation"] = 90.0.into();
+        let shapes = vec![target, rect("b", 0.0, 200.0, 40.0, 40.0)];
+        let mut set = ConstraintSet::default();
+        set.add(ConstraintKind::AlignLeft, "b", "a", (None, None), &shapes)
+            .unwrap();
+
+        let updates = set.solve(&shapes);
+        let x = updates[0].changes["x"].as_f64().unwrap();
+        assert!((x - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fixed_offset_captures_current_position_and_chains() {
+        let shapes = vec![
+            rect("a", 0.0, 0.0, 10.0, 10.0),
+            rect("b", 20.0, 0.0, 10.0, 10.0),
+            rect("c", 40.0, 5.0, 10.0, 10.0),
+        ];
+        let mut set = ConstraintSet::default();
+        set.add(ConstraintKind::FixedOffset, "c", "b", (None, None), &shapes)
+            .unwrap();
+        set.add(ConstraintKind::FixedOffset, "b", "a", (None, None), &shapes)
+            .unwrap();
+        assert!(set.solve(&shapes).is_empty());
+
+        // Move a: b follows, then c follows b on the next pass.
+        let moved = vec![
+            rect("a", 100.0, 100.0, 10.0, 10.0),
+            shapes[1].clone(),
+            shapes[2].clone(),
+        ];
+        let updates = set.solve(&moved);
+        let c = updates.iter().find(|u| u.id == "c").unwrap();
+        assert_eq!(c.changes, serde_json::json!({ "x": 140.0, "y": 105.0 }));
+    }
+
+    #[test]
+    fn pin_to_frame_stays_inside_frame() {
+        let shapes = vec![
+            rect("frame", 0.0, 0.0, 100.0, 100.0),
+            rect("p", 90.0, 10.0, 30.0, 10.0),
+        ];
+        let mut set = ConstraintSet::default();
+        set.add(
+            ConstraintKind::PinToFrame,
+            "p",
+            "frame",
+            (None, None),
+            &shapes,
+        )
+        .unwrap();
+        let updates = set.solve(&shapes);
+        assert_eq!(updates[0].changes, serde_json::json!({ "x": 70.0 }));
+    }
+
+    #[test]
+    fn constraints_on_deleted_shapes_are_pruned() {
+        let shapes = vec![
+            rect("a", 0.0, 0.0, 10.0, 10.0),
+            rect("b", 5.0, 0.0, 10.0, 10.0),
+        ];
+        let mut set = ConstraintSet::default();
+        set.add(ConstraintKind::AlignLeft, "b", "a", (None, None), &shapes)
+            .unwrap();
+        assert!(set.solve(&shapes[1..]).is_empty());
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn self_and_unknown_targets_are_rejected() {
+        let shapes = vec![rect("a", 0.0, 0.0, 10.0, 10.0)];
+        let mut set = ConstraintSet::default();
+        assert!(set
+            .add(ConstraintKind::AlignLeft, "a", "a", (None, None), &shapes)
+            .is_err());
+        assert!(set
+            .add(ConstraintKind::AlignLeft, "a", "zz", (None, None), &shapes)
+            .is_err());
+    }
+}
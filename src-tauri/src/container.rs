@@ -0,0 +1,336 @@
+//! The v2 `.napkin` file format: a zip container.
+//!
+//! Version 1 files are the collection JSON as text, with images inlined as
+//! base64 data URLs. A version 2 file is a zip archive holding
+//!
+//! - `manifest.json`: `{ "format": "napkin", "formatVersion": 2, ... }`,
+//!   naming the document entry and listing the assets;
+//! - `document.json`: the collection, compressed with zstd, with each
+//!   image's `src` replaced by `napkin-asset:<entry>`;
+//! - `assets/<hash>.<ext>`: each distinct image, stored as its own bytes.
+//!
+//! Readers hand out the collection JSON with the images inlined again, so the
+//! webview, the MCP resources and the exporters see the same JSON as before.
+//! Whole-document saves (`docstore::write_path`) always write version 2, so
+//! a version 1 file is migrated the first time it is saved. Archives are
+//! read and written with the `zip` crate, so entries may be stored,
+//! deflated or zstd-compressed, and ZIP64 is used once an entry or the
+//! archive outgrows 4 GiB.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// The format version this build writes, and the newest it reads.
+pub const FORMAT_VERSION: u32 = 2;
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DOCUMENT_ENTRY: &str = "document.json";
+const ASSETS_DIR: &str = "assets/";
+/// An image `src` pointing into the container.
+const ASSET_SCHEME: &str = "napkin-asset:";
+
+/// Signature every zip archive starts with.
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    format: String,
+    format_version: u32,
+    document: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Asset {
+    path: String,
+    media_type: String,
+}
+
+/// Whether `path` is a version 2 document.
+pub fn is_container(path: &Path) -> bool {
+    let mut header = [0u8; 4];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok_and(|_| u32::from_le_bytes(header) == LOCAL_HEADER)
+}
+
+// --- Zip archives ---
+
+struct Entry {
+    name: String,
+    data: Vec<u8>,
+    method: CompressionMethod,
+}
+
+fn zip(entries: &[Entry]) -> Result<Vec<u8>, String> {
+    let failed = |e: ZipError| format!("Failed to write the container: {}", e);
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for entry in entries {
+        let options = SimpleFileOptions::default()
+            .compression_method(entry.method)
+            .large_file(entry.data.len() as u64 >= u64::from(u32::MAX));
+        writer
+            .start_file(entry.name.as_str(), options)
+            .map_err(failed)?;
+        writer
+            .write_all(&entry.data)
+            .map_err(|e| format!("Failed to write the container: {}", e))?;
+    }
+    Ok(writer.finish().map_err(failed)?.into_inner())
+}
+
+/// The entries of a zip archive by name, checked against their CRCs.
+fn unzip(bytes: &[u8]) -> Result<HashMap<String, Vec<u8>>, String> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(bytes)).map_err(|_| "Not a Napkin container".to_string())?;
+    let mut entries = HashMap::new();
+    for i in 0..archive.len() {
+        let mut file = archive
+            .by_index(i)
+            .map_err(|e| format!("Corrupt container directory: {}", e))?;
+        let name = file.name().to_string();
+        let mut data = Vec::with_capacity(file.size().min(1 << 26) as usize);
+        file.read_to_end(&mut data)
+            .map_err(|e| format!("Corrupt container entry {}: {}", name, e))?;
+        entries.insert(name, data);
+    }
+    Ok(entries)
+}
+
+// --- Documents ---
+
+pub(crate) fn extension(media_type: &str) -> &str {
+    match media_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        _ => "bin",
+    }
+}
+
+/// The media type and bytes of a base64 data URL.
+pub(crate) fn decode_data_url(src: &str) -> Option<(String, Vec<u8>)> {
+    let (header, data) = src.strip_prefix("data:")?.split_once(',')?;
+    let media_type = header.strip_suffix(";base64")?;
+    let bytes = STANDARD.decode(data).ok()?;
+    Some((media_type.to_string(), bytes))
+}
+
+/// The image shapes of every board in `collection`.
+pub(crate) fn images_mut(collection: &mut Value) -> impl Iterator<Item = &mut Value> + '_ {
+    collection["documents"]
+        .as_array_mut()
+        .into_iter()
+        .flatten()
+        .filter_map(|doc| doc["shapes"].as_array_mut())
+        .flatten()
+        .filter(|shape| shape["type"] == "image")
+}
+
+/// Collection `json` as a version 2 container.
+pub fn pack(json: &str) -> Result<Vec<u8>, String> {
+    let mut collection: Value =
+        serde_json::from_str(json).map_err(|e| format!("Invalid document JSON: {}", e))?;
+    let mut assets: Vec<Asset> = Vec::new();
+    let mut entries = Vec::new();
+    for shape in images_mut(&mut collection) {
+        let Some((media_type, bytes)) = shape["src"].as_str().and_then(decode_data_url) else {
+            continue;
+        };
+        let digest = Sha256::digest(&bytes);
+        let hash: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+        let path = format!("{}{}.{}", ASSETS_DIR, hash, extension(&media_type));
+        shape["src"] = Value::String(format!("{}{}", ASSET_SCHEME, path));
+        if assets.iter().any(|a| a.path == path) {
+            continue;
+        }
+        assets.push(Asset {
+            path: path.clone(),
+            media_type,
+        });
+        // Images are compressed already
+        entries.push(Entry {
+            name: path,
+            data: bytes,
+            method: CompressionMethod::Stored,
+        });
+    }
+    let manifest = Manifest {
+        format: "napkin".to_string(),
+        format_version: FORMAT_VERSION,
+        document: DOCUMENT_ENTRY.to_string(),
+        assets,
+    };
+    let document = if entries.is_empty() {
+        json.as_bytes().to_vec()
+    } else {
+        collection.to_string().into_bytes()
+    };
+    let mut archive = vec![
+        Entry {
+            name: MANIFEST_ENTRY.to_string(),
+            data: serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?,
+            method: CompressionMethod::Deflated,
+        },
+        Entry {
+            name: DOCUMENT_ENTRY.to_string(),
+            data: document,
+            method: CompressionMethod::Zstd,
+        },
+    ];
+    archive.append(&mut entries);
+    zip(&archive)
+}
+
+/// The collection JSON in a version 2 container, with its images inlined.
+pub fn unpack(bytes: &[u8]) -> Result<String, String> {
+    let mut entries = unzip(bytes)?;
+    let manifest: Manifest = entries
+        .get(MANIFEST_ENTRY)
+        .and_then(|m| serde_json::from_slice(m).ok())
+        .ok_or("The container has no manifest")?;
+    if manifest.format != "napkin" {
+        return Err(format!("Not a Napkin container: {}", manifest.format));
+    }
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(format!(
+            "This document needs a newer Napkin (format version {})",
+            manifest.format_version
+        ));
+    }
+    let document = entries
+        .remove(&manifest.document)
+        .ok_or("The container has no document")?;
+    let json = String::from_utf8(document).map_err(|e| e.to_string())?;
+    if manifest.assets.is_empty() {
+        return Ok(json);
+    }
+    let mut collection: Value =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid document JSON: {}", e))?;
+    let media_types: HashMap<&str, &str> = manifest
+        .assets
+        .iter()
+        .map(|a| (a.path.as_str(), a.media_type.as_str()))
+        .collect();
+    for shape in images_mut(&mut collection) {
+        let Some(path) = shape["src"]
+            .as_str()
+            .and_then(|s| s.strip_prefix(ASSET_SCHEME))
+        else {
+            continue;
+        };
+        let (Some(bytes), Some(media_type)) = (entries.get(path), media_types.get(path)) else {
+            return Err(format!("The container is missing {}", path));
+        };
+        shape["src"] = Value::String(format!(
+            "data:{};base64,{}",
+            media_type,
+            STANDARD.encode(bytes)
+        ));
+    }
+    Ok(collection.to_string())
+}
+
+/// Read the version 2 document at `path`.
+pub fn read(path: &Path) -> Result<String, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    unpack(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn png_url() -> String {
+        let image = image::RgbaImage::from_pixel(4, 4, image::Rgba([200, 0, 0, 255]));
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        format!(
+            "data:image/png;base64,{}",
+            STANDARD.encode(bytes.into_inner())
+        )
+    }
+
+    #[test]
+    fn images_are_stored_once_as_binary_entries() {
+        let src = png_url();
+        let image =
+            |id: &str| json!({ "id": id, "type": "image", "src": src, "width": 4, "height": 4 });
+        let collection = json!({
+            "type": "collection",
+            "documents": [
+                { "shapes": [image("a"), { "id": "t", "type": "text", "text": "Hi" }] },
+                { "shapes": [image("b")] },
+            ],
+        });
+        let packed = pack(&collection.to_string()).unwrap();
+        assert_eq!(
+            u32::from_le_bytes(packed[..4].try_into().unwrap()),
+            LOCAL_HEADER
+        );
+        // One copy of the image, and no base64
+        let entries = unzip(&packed).unwrap();
+        assert_eq!(entries.len(), 3);
+        let document = String::from_utf8(entries[DOCUMENT_ENTRY].clone()).unwrap();
+        assert!(!document.contains("base64"));
+        assert!(document.contains(ASSET_SCHEME));
+        let manifest: Value = serde_json::from_slice(&entries[MANIFEST_ENTRY]).unwrap();
+        assert_eq!(manifest["formatVersion"], 2);
+        let mut archive = ZipArchive::new(Cursor::new(&packed)).unwrap();
+        let compression = archive.by_name(DOCUMENT_ENTRY).unwrap().compression();
+        assert_eq!(compression, CompressionMethod::Zstd);
+        assert_eq!(manifest["assets"][0]["mediaType"], "image/png");
+
+        let unpacked: Value = serde_json::from_str(&unpack(&packed).unwrap()).unwrap();
+        assert_eq!(unpacked, collection);
+    }
+
+    #[test]
+    fn damaged_or_newer_containers_are_refused() {
+        let json = json!({ "shapes": [] }).to_string();
+        let mut packed = pack(&json).unwrap();
+        assert_eq!(unpack(&packed).unwrap(), json);
+
+        let manifest = serde_json::to_vec(&json!({
+            "format": "napkin", "formatVersion": 3, "document": DOCUMENT_ENTRY,
+        }))
+        .unwrap();
+        let newer = zip(&[
+            Entry {
+                name: MANIFEST_ENTRY.into(),
+                data: manifest,
+                method: CompressionMethod::Stored,
+            },
+            Entry {
+                name: DOCUMENT_ENTRY.into(),
+                data: json.into_bytes(),
+                method: CompressionMethod::Deflated,
+            },
+        ])
+        .unwrap();
+        assert!(unpack(&newer).unwrap_err().contains("newer Napkin"));
+
+        // Flip the last byte of the compressed document
+        let last = {
+            let mut archive = ZipArchive::new(Cursor::new(&packed)).unwrap();
+            let document = archive.by_name(DOCUMENT_ENTRY).unwrap();
+            (document.data_start() + document.compressed_size() - 1) as usize
+        };
+        packed[last] ^= 0xff;
+        assert!(unpack(&packed).is_err());
+        assert!(unpack(b"{}").is_err());
+    }
+}
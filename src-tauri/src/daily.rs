@@ -0,0 +1,375 @@
+//! Today's napkin: one board per day, in a folder of date-named files.
+//!
+//! `open_daily_note` (File → Today's Napkin, or the tray) returns the path
+//! of `<folder>/<YYYY-MM-DD>.napkin`, creating it on the first open of the
+//! day. A new day's board starts with the unfinished stickies of the latest
+//! earlier one, where they were. A sticky counts as finished when it has
+//! checkboxes and all are ticked (`[x]`), or when it starts with `✓`, `✔` or
+//! "done"; empty stickies are left behind.
+//!
+//! The same board is a system-wide shortcut away (`CmdOrCtrl+Shift+D` unless
+//! changed in settings). A shortcut another app already holds is logged and
+//! skipped at startup; `set_daily_shortcut` reports it instead of saving.
+
+use chrono::{Local, NaiveDate, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use tauri::{Emitter, Manager};
+
+use crate::docstore;
+use crate::settings;
+
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
+
+pub const MENU_ID: &str = "daily_note";
+pub const TRAY_MENU_ID: &str = "tray-daily-note";
+/// Asks the webview to open today's board.
+pub const MENU_EVENT: &str = "menu-daily-note";
+/// Global shortcut for today's board until one is set in settings.
+pub const DEFAULT_SHORTCUT: &str = "CmdOrCtrl+Shift+D";
+
+const EXTENSION: &str = "napkin";
+const DEFAULT_FOLDER: &str = "Napkin Daily";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+/// Text that marks a sticky without checkboxes as finished.
+const DONE_MARKERS: &[&str] = &["✓", "✔", "done"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DailyNotes {
+    /// Folder for the daily files; `Documents/Napkin Daily` when unset.
+    pub folder: Option<String>,
+    /// Copy unfinished stickies from the previous day's board.
+    pub carry_over: bool,
+    /// System-wide shortcut for today's board; `None` turns it off.
+    pub shortcut: Option<String>,
+}
+
+impl Default for DailyNotes {
+    fn default() -> Self {
+        Self {
+            folder: None,
+            carry_over: true,
+            shortcut: Some(DEFAULT_SHORTCUT.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyNote {
+    pub path: String,
+    /// Whether the file was created by this call.
+    pub created: bool,
+    /// Stickies copied from the previous board.
+    pub carried_over: usize,
+}
+
+fn file_name(date: NaiveDate) -> String {
+    format!("{}.{}", date.format(DATE_FORMAT), EXTENSION)
+}
+
+/// The date a daily file is for, from its name.
+fn file_date(path: &Path) -> Option<NaiveDate> {
+    if path.extension()? != EXTENSION {
+        return None;
+    }
+    NaiveDate::parse_from_str(path.file_stem()?.to_str()?, DATE_FORMAT).ok()
+}
+
+/// The latest daily file in `folder` before `today`.
+fn previous_note(folder: &Path, today: NaiveDate) -> Option<PathBuf> {
+    std::fs::read_dir(folder)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter_map(|path| Some((file_date(&path)?, path)))
+        .filter(|(date, _)| *date < today)
+        .max_by_key(|(date, _)| *date)
+        .map(|(_, path)| path)
+}
+
+fn is_unfinished(text: &str) -> bool {
+    let text = text.trim().to_lowercase();
+    if text.is_empty() {
+        return false;
+    }
+    if text.contains("[ ]") {
+        return true;
+    }
+    if text.contains("[x]") {
+        return false;
+    }
+    !DONE_MARKERS.iter().any(|marker| text.starts_with(marker))
+}
+
+/// The unfinished stickies on a saved board (every board of a collection),
+/// without their groups.
+fn unfinished_stickies(file: &Value) -> Vec<Value> {
+    let documents = match file.get("documents").and_then(Value::as_array) {
+        Some(documents) => documents.iter().collect(),
+        None => vec![file],
+    };
+    documents
+        .into_iter()
+        .filter_map(|doc| doc.get("shapes").and_then(Value::as_array))
+        .flatten()
+        .filter(|shape| shape.get("type").and_then(Value::as_str) == Some("sticky"))
+        .filter(|shape| {
+            shape
+                .get("text")
+                .and_then(Value::as_str)
+                .is_some_and(is_unfinished)
+        })
+        .map(|shape| {
+            let mut shape = shape.clone();
+            if let Some(fields) = shape.as_object_mut() {
+                fields.remove("groupId");
+            }
+            shape
+        })
+        .collect()
+}
+
+/// A new board for `date` holding `shapes`.
+fn new_document(date: NaiveDate, shapes: Vec<Value>) -> Value {
+    let now = Local::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    json!({
+        "version": "1.0.0",
+        "appName": "napkin",
+        "shapes": shapes,
+        "viewport": { "x": 0, "y": 0, "zoom": 1 },
+        "metadata": {
+            "created": now,
+            "modified": now,
+            "title": date.format(DATE_FORMAT).to_string(),
+        },
+    })
+}
+
+/// Create the file for `today` in `folder` unless it exists.
+fn ensure_note(folder: &Path, today: NaiveDate, carry_over: bool) -> Result<DailyNote, String> {
+    let path = folder.join(file_name(today));
+    let mut note = DailyNote {
+        path: path.to_string_lossy().into_owned(),
+        created: false,
+        carried_over: 0,
+    };
+    if path.exists() {
+        return Ok(note);
+    }
+    std::fs::create_dir_all(folder)
+        .map_err(|e| format!("Failed to create {}: {}", folder.display(), e))?;
+
+    let mut shapes = Vec::new();
+    if let Some(previous) = previous_note(folder, today).filter(|_| carry_over) {
+        match docstore::read_path(&previous)
+            .and_then(|text| serde_json::from_str::<Value>(&text).map_err(|e| e.to_string()))
+        {
+            Ok(file) => shapes = unfinished_stickies(&file),
+            Err(e) => log::warn!("Not carrying over from {}: {}", previous.display(), e),
+        }
+    }
+    let document = new_document(today, shapes);
+    let text = serde_json::to_string_pretty(&document).map_err(|e| e.to_string())?;
+    std::fs::write(&path, text)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    note.created = true;
+    note.carried_over = document["shapes"].as_array().map_or(0, Vec::len);
+    Ok(note)
+}
+
+fn folder(app: &tauri::AppHandle, config: &DailyNotes) -> Result<PathBuf, String> {
+    match config
+        .folder
+        .as_deref()
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+    {
+        Some(folder) => Ok(PathBuf::from(folder)),
+        None => app
+            .path()
+            .document_dir()
+            .map(|dir| dir.join(DEFAULT_FOLDER))
+            .map_err(|e| format!("No documents folder for daily notes: {}", e)),
+    }
+}
+
+/// A shortcut that can be grabbed system-wide. Without Ctrl, Alt or
+/// Cmd it would swallow ordinary typing in every other app.
+fn parse_shortcut(text: &str) -> Result<Shortcut, String> {
+    let shortcut: Shortcut = text
+        .parse()
+        .map_err(|e| format!("Invalid shortcut \"{}\": {}", text, e))?;
+    let held = Modifiers::CONTROL | Modifiers::ALT | Modifiers::SUPER | Modifiers::META;
+    if !shortcut.mods.intersects(held) {
+        return Err(format!(
+            "Shortcut \"{}\" needs Ctrl, Alt or Cmd to be used system-wide",
+            text
+        ));
+    }
+    Ok(shortcut)
+}
+
+/// Grab `shortcut` for today's board, then let go of `previous`. When the
+/// grab fails (typically because another app holds the keys) `previous`
+/// stays bound.
+fn bind_shortcut(
+    app: &tauri::AppHandle,
+    previous: Option<&str>,
+    shortcut: Option<&str>,
+) -> Result<(), String> {
+    let previous = previous.and_then(|p| parse_shortcut(p).ok());
+    let shortcut = shortcut.map(parse_shortcut).transpose()?;
+    if previous == shortcut {
+        return Ok(());
+    }
+    let global = app.global_shortcut();
+    if let Some(shortcut) = shortcut {
+        global
+            .on_shortcut(shortcut, |app, _, event| {
+                if event.state == ShortcutState::Pressed {
+                    show_today(app);
+                }
+            })
+            .map_err(|e| format!("Could not register {}: {}", shortcut, e))?;
+    }
+    if let Some(previous) = previous.filter(|p| global.is_registered(*p)) {
+        let _ = global.unregister(previous);
+    }
+    Ok(())
+}
+
+/// Bind the configured shortcut at startup.
+pub fn register_shortcut(app: &tauri::AppHandle) {
+    let config = settings::current(app).daily_notes;
+    if let Err(e) = bind_shortcut(app, None, config.shortcut.as_deref()) {
+        log::warn!("Today's napkin shortcut is off: {}", e);
+    }
+}
+
+/// Bring the window forward on today's board, from the tray or shortcut.
+pub fn show_today(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+        let _ = window.emit(MENU_EVENT, ());
+    }
+}
+
+// --- Tauri commands ---
+
+/// Today's board, created (with yesterday's unfinished stickies) if needed.
+#[tauri::command]
+pub async fn open_daily_note(app: tauri::AppHandle) -> Result<DailyNote, String> {
+    let config = settings::current(&app).daily_notes;
+    let folder = folder(&app, &config)?;
+    let today = Local::now().date_naive();
+    let note = tauri::async_runtime::spawn_blocking(move || {
+        ensure_note(&folder, today, config.carry_over)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    if note.created {
+        log::info!(
+            "Created daily note {} ({} stickies carried over)",
+            note.path,
+            note.carried_over
+        );
+    }
+    Ok(note)
+}
+
+/// Change the global shortcut for today's board, or turn it off with
+/// `null`. Nothing is saved unless the new shortcut could be registered.
+#[tauri::command]
+pub fn set_daily_shortcut(
+    app: tauri::AppHandle,
+    store: tauri::State<'_, settings::SettingsStore>,
+    shortcut: Option<String>,
+) -> Result<settings::Settings, String> {
+    let shortcut = shortcut.filter(|s| !s.trim().is_empty());
+    let mut config = store.get().daily_notes;
+    bind_shortcut(&app, config.shortcut.as_deref(), shortcut.as_deref())?;
+    config.shortcut = shortcut;
+    store.update(json!({ "dailyNotes": config }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, DATE_FORMAT).unwrap()
+    }
+
+    fn sticky(id: &str, text: &str) -> Value {
+        json!({ "id": id, "type": "sticky", "text": text, "groupId": "g1" })
+    }
+
+    #[test]
+    fn finished_stickies_are_recognised() {
+        assert!(is_unfinished("Call the printer"));
+        assert!(is_unfinished("[x] draft\n[ ] review"));
+        assert!(!is_unfinished("[x] draft\n[X] review"));
+        assert!(!is_unfinished("✓ Call the printer"));
+        assert!(!is_unfinished("Done: budget"));
+        assert!(!is_unfinished("  "));
+    }
+
+    #[test]
+    fn only_unfinished_stickies_carry_over() {
+        let board = json!({ "shapes": [
+            sticky("a", "todo"),
+            sticky("b", "done"),
+            { "id": "c", "type": "text", "text": "todo" },
+        ]});
+        let carried = unfinished_stickies(&board);
+        assert_eq!(
+            carried,
+            vec![json!({ "id": "a", "type": "sticky", "text": "todo" })]
+        );
+
+        let collection = json!({ "documents": [
+            { "shapes": [sticky("a", "one")] },
+            { "shapes": [sticky("b", "two")] },
+        ]});
+        assert_eq!(unfinished_stickies(&collection).len(), 2);
+    }
+
+    #[test]
+    fn global_shortcuts_need_a_held_modifier() {
+        assert!(parse_shortcut(DEFAULT_SHORTCUT).is_ok());
+        assert!(parse_shortcut("Alt+F9").is_ok());
+        assert!(parse_shortcut("Shift+D").is_err());
+        assert!(parse_shortcut("D").is_err());
+        assert!(parse_shortcut("CmdOrCtrl+Nope").is_err());
+    }
+
+    #[test]
+    fn a_new_day_starts_from_the_latest_earlier_note() {
+        let dir = std::env::temp_dir().join(format!("napkin-daily-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = new_document(date("2026-10-12"), vec![sticky("old", "stale")]);
+        let latest = new_document(
+            date("2026-10-14"),
+            vec![sticky("a", "open"), sticky("b", "✔ shipped")],
+        );
+        std::fs::write(dir.join("2026-10-12.napkin"), old.to_string()).unwrap();
+        std::fs::write(dir.join("2026-10-14.napkin"), latest.to_string()).unwrap();
+        std::fs::write(dir.join("notes.napkin"), "{}").unwrap();
+
+        let note = ensure_note(&dir, date("2026-10-16"), true).unwrap();
+        assert!(note.created);
+        assert_eq!(note.carried_over, 1);
+        assert!(note.path.ends_with("2026-10-16.napkin"));
+        let saved: Value =
+            serde_json::from_str(&std::fs::read_to_string(&note.path).unwrap()).unwrap();
+        assert_eq!(saved["shapes"][0]["id"], "a");
+        assert_eq!(saved["metadata"]["title"], "2026-10-16");
+
+        let again = ensure_note(&dir, date("2026-10-16"), true).unwrap();
+        assert!(!again.created);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,158 @@
+//! Bridge handshake and the diagnostics report.
+//!
+//! Bridged tools are answered by `handleToolCall` in the webview. When its
+//! handler starts, the webview reports the tool names it implements and the
+//! channel to send calls down (`api_register_bridge`, see `bridge.rs`). Tools the server would bridge but the webview
+//! lacks are logged, hidden from `tools/list` and refused when called, rather
+//! than advertised and left to time out. Both directions of the mismatch are
+//! part of the diagnostics report.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use tauri::ipc::Channel;
+
+use crate::api::{self, McpToolRequest, SharedApiState};
+use crate::settings;
+
+/// Tools Rust code bridges to that `mcp_tools_list` does not advertise.
+pub const BRIDGE_ONLY_TOOLS: &[&str] = &[
+    "get_tab",
+    "set_document_scale",
+    "apply_partition",
+    "apply_shape_order",
+];
+
+/// How the webview's tool handlers compare with what the server bridges.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeDrift {
+    /// Whether the webview has reported its tools yet. Until then nothing is
+    /// hidden.
+    pub registered: bool,
+    /// Bridged tools the webview does not implement.
+    pub missing: Vec<String>,
+    /// Tools the webview implements that nothing bridges to.
+    pub unknown: Vec<String>,
+}
+
+impl BridgeDrift {
+    pub fn compare(bridged: &[String], implemented: &[String]) -> Self {
+        let missing = bridged
+            .iter()
+            .filter(|tool| !implemented.contains(tool))
+            .cloned()
+            .collect();
+        let unknown = implemented
+            .iter()
+            .filter(|tool| !bridged.contains(tool))
+            .cloned()
+            .collect();
+        Self {
+            registered: true,
+            missing,
+            unknown,
+        }
+    }
+
+    pub fn is_missing(&self, tool: &str) -> bool {
+        self.missing.iter().any(|t| t == tool)
+    }
+
+    /// `tools` (a `tools/list` array) without the missing tools.
+    pub fn without_missing(&self, mut tools: Value) -> Value {
+        if let Some(list) = tools.as_array_mut() {
+            list.retain(|tool| {
+                tool.get("name")
+                    .and_then(Value::as_str)
+                    .map_or(true, |name| !self.is_missing(name))
+            });
+        }
+        tools
+    }
+}
+
+// --- Tauri commands ---
+
+/// The webview's tool handler is listening on `on_request` and implements
+/// `tools`.
+#[tauri::command]
+pub async fn api_register_bridge(
+    tools: Vec<String>,
+    on_request: Channel<McpToolRequest>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<BridgeDrift, String> {
+    let resent = state.bridge.lock().await.attach(on_request)?;
+    if resent > 0 {
+        log::info!("Webview registered again; re-sent {} unanswered tool calls", resent);
+    }
+    let drift = BridgeDrift::compare(&api::bridged_tools(), &tools);
+    if !drift.missing.is_empty() {
+        log::warn!(
+            "Webview does not implement {} bridged tools; hiding them: {}",
+            drift.missing.len(),
+            drift.missing.join(", ")
+        );
+    }
+    if !drift.unknown.is_empty() {
+        log::warn!(
+            "Webview implements tools the server never bridges: {}",
+            drift.unknown.join(", ")
+        );
+    }
+    *state.bridge_drift.lock().await = drift.clone();
+    Ok(drift)
+}
+
+/// A snapshot of the MCP server's health, for bug reports.
+#[tauri::command]
+pub async fn get_diagnostics(state: tauri::State<'_, SharedApiState>) -> Result<Value, String> {
+    let port = *state.server_port.lock().await;
+    let sessions = state.sessions.lock().await.list().len();
+    let settings = settings::current(&state.app_handle);
+    let bridge = state.bridge_drift.lock().await.clone();
+    Ok(json!({
+        "appVersion": env!("CARGO_PKG_VERSION"),
+        "mcp": {
+            "running": port.is_some(),
+            "port": port,
+            "sidecar": settings.mcp_sidecar,
+            "sessions": sessions,
+        },
+        "bridge": bridge,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn drift_is_reported_in_both_directions() {
+        let drift = BridgeDrift::compare(
+            &names(&["get_canvas", "create_shape", "get_tab"]),
+            &names(&["get_canvas", "get_tab", "paint_bucket"]),
+        );
+        assert!(drift.registered);
+        assert_eq!(drift.missing, names(&["create_shape"]));
+        assert_eq!(drift.unknown, names(&["paint_bucket"]));
+    }
+
+    #[test]
+    fn missing_tools_are_hidden() {
+        let drift = BridgeDrift::compare(
+            &names(&["get_canvas", "create_shape"]),
+            &names(&["get_canvas"]),
+        );
+        let tools = json!([{ "name": "get_canvas" }, { "name": "create_shape" }, { "name": "add_constraint" }]);
+        assert_eq!(
+            drift.without_missing(tools),
+            json!([{ "name": "get_canvas" }, { "name": "add_constraint" }])
+        );
+        assert!(!BridgeDrift::default().is_missing("create_shape"));
+    }
+}
@@ -0,0 +1,658 @@
+//! SQLite storage for large documents.
+//!
+//! A `.napkin` file is either the JSON collection format (a version 2 zip
+//! container, see `container.rs`, or version 1 plain JSON) or, when the
+//! user switches a document to database storage, a SQLite file holding the same
+//! collection split into rows: one per document (without its shapes) and one
+//! per shape, ordered by `z`. The webview always sees the JSON collection
+//! (`load_document` in `document.rs`), full saves only touch rows whose JSON
+//! changed (`save_document`), and autosave sends just the shapes that changed
+//! since the last write (`apply_document_changes`). The same commands serve
+//! journaled documents, which keep an event log instead (`journal.rs`).
+//! Password-protected documents (`encryption.rs`) are JSON documents, but
+//! only `document.rs` reads and writes them.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::assets;
+use crate::container;
+use crate::document;
+use crate::encryption;
+use crate::journal;
+use crate::resources;
+use crate::subscriptions;
+use crate::versions;
+use crate::wal;
+
+const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+/// Password-protected documents are only read and written by `document.rs`.
+const PROTECTED: &str = "The document is password-protected";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS documents (idx INTEGER PRIMARY KEY, json TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS shapes (
+        doc INTEGER NOT NULL,
+        id TEXT NOT NULL,
+        z INTEGER NOT NULL,
+        json TEXT NOT NULL,
+        PRIMARY KEY (doc, id)
+    );
+    CREATE INDEX IF NOT EXISTS shapes_order ON shapes (doc, z);
+";
+
+/// Whether `path` is a SQLite-backed document.
+pub fn is_sqlite(path: &Path) -> bool {
+    use std::io::Read;
+    let mut header = [0u8; 16];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok_and(|_| &header == SQLITE_MAGIC)
+}
+
+fn sql_err(e: rusqlite::Error) -> String {
+    format!("Document database error: {}", e)
+}
+
+fn open(path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(path).map_err(sql_err)?;
+    conn.execute_batch(SCHEMA).map_err(sql_err)?;
+    Ok(conn)
+}
+
+/// Single documents are stored as a one-document collection.
+pub(crate) fn as_collection(value: Value) -> Result<Map<String, Value>, String> {
+    let Value::Object(obj) = value else {
+        return Err("Document must be a JSON object".to_string());
+    };
+    if obj.get("type").and_then(|t| t.as_str()) == Some("collection") {
+        return Ok(obj);
+    }
+    let metadata = obj
+        .get("metadata")
+        .cloned()
+        .unwrap_or_else(|| json!({ "title": "Untitled" }));
+    let version = obj.get("version").cloned().unwrap_or(json!("1.0.0"));
+    let mut collection = Map::new();
+    collection.insert("version".to_string(), version);
+    collection.insert("appName".to_string(), json!("napkin"));
+    collection.insert("type".to_string(), json!("collection"));
+    collection.insert("activeDocumentIndex".to_string(), json!(0));
+    collection.insert("metadata".to_string(), metadata);
+    collection.insert(
+        "documents".to_string(),
+        Value::Array(vec![Value::Object(obj)]),
+    );
+    Ok(collection)
+}
+
+/// Split a document into its row JSON and its shapes.
+pub(crate) fn split_document(doc: &Value) -> (String, Vec<Value>) {
+    let mut doc = doc.clone();
+    let shapes = match doc.as_object_mut().and_then(|o| o.remove("shapes")) {
+        Some(Value::Array(shapes)) => shapes,
+        _ => Vec::new(),
+    };
+    (doc.to_string(), shapes)
+}
+
+pub(crate) fn shape_id(shape: &Value) -> Option<&str> {
+    shape.get("id").and_then(|v| v.as_str())
+}
+
+fn read_meta(conn: &Connection) -> Result<Map<String, Value>, String> {
+    let text: Option<String> = conn
+        .query_row("SELECT value FROM meta WHERE key = 'collection'", [], |r| {
+            r.get(0)
+        })
+        .optional()
+        .map_err(sql_err)?;
+    Ok(text
+        .and_then(|t| serde_json::from_str::<Map<String, Value>>(&t).ok())
+        .unwrap_or_default())
+}
+
+fn write_meta(conn: &Connection, meta: &Map<String, Value>) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO meta (key, value) VALUES ('collection', ?1)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![Value::Object(meta.clone()).to_string()],
+    )
+    .map_err(sql_err)?;
+    Ok(())
+}
+
+fn write_document_row(conn: &Connection, index: usize, json: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO documents (idx, json) VALUES (?1, ?2)
+         ON CONFLICT(idx) DO UPDATE SET json = excluded.json WHERE json <> excluded.json",
+        params![index as i64, json],
+    )
+    .map_err(sql_err)?;
+    Ok(())
+}
+
+fn truncate_documents(conn: &Connection, count: usize) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM documents WHERE idx >= ?1",
+        params![count as i64],
+    )
+    .map_err(sql_err)?;
+    conn.execute("DELETE FROM shapes WHERE doc >= ?1", params![count as i64])
+        .map_err(sql_err)?;
+    Ok(())
+}
+
+/// Write a whole collection, touching only rows whose content changed.
+fn write_collection(conn: &mut Connection, collection: Value) -> Result<(), String> {
+    let mut meta = as_collection(collection)?;
+    let documents = match meta.remove("documents") {
+        Some(Value::Array(docs)) => docs,
+        _ => Vec::new(),
+    };
+
+    let tx = conn.transaction().map_err(sql_err)?;
+    write_meta(&tx, &meta)?;
+    truncate_documents(&tx, documents.len())?;
+    for (index, doc) in documents.iter().enumerate() {
+        let (json, shapes) = split_document(doc);
+        write_document_row(&tx, index, &json)?;
+
+        let mut stored: HashMap<String, (i64, String)> = HashMap::new();
+        {
+            let mut stmt = tx
+                .prepare("SELECT id, z, json FROM shapes WHERE doc = ?1")
+                .map_err(sql_err)?;
+            let rows = stmt
+                .query_map(params![index as i64], |r| {
+                    Ok((r.get::<_, String>(0)?, (r.get(1)?, r.get(2)?)))
+                })
+                .map_err(sql_err)?;
+            for row in rows {
+                let (id, entry) = row.map_err(sql_err)?;
+                stored.insert(id, entry);
+            }
+        }
+
+        {
+            let mut upsert = tx
+                .prepare(
+                    "INSERT INTO shapes (doc, id, z, json) VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT(doc, id) DO UPDATE SET z = excluded.z, json = excluded.json",
+                )
+                .map_err(sql_err)?;
+            for (z, shape) in shapes.iter().enumerate() {
+                let Some(id) = shape_id(shape) else { continue };
+                let json = shape.to_string();
+                let unchanged = stored
+                    .remove(id)
+                    .is_some_and(|(old_z, old_json)| old_z == z as i64 && old_json == json);
+                if !unchanged {
+                    upsert
+                        .execute(params![index as i64, id, z as i64, json])
+                        .map_err(sql_err)?;
+                }
+            }
+            let mut delete = tx
+                .prepare("DELETE FROM shapes WHERE doc = ?1 AND id = ?2")
+                .map_err(sql_err)?;
+            for id in stored.keys() {
+                delete.execute(params![index as i64, id]).map_err(sql_err)?;
+            }
+        }
+    }
+    tx.commit().map_err(sql_err)
+}
+
+/// Reassemble the JSON collection.
+fn read_collection(conn: &Connection) -> Result<Value, String> {
+    let mut meta = read_meta(conn)?;
+    let mut documents = Vec::new();
+    let mut doc_stmt = conn
+        .prepare("SELECT idx, json FROM documents ORDER BY idx")
+        .map_err(sql_err)?;
+    let mut shape_stmt = conn
+        .prepare("SELECT json FROM shapes WHERE doc = ?1 ORDER BY z, id")
+        .map_err(sql_err)?;
+    let rows: Vec<(i64, String)> = doc_stmt
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+        .map_err(sql_err)?
+        .collect::<Result<_, _>>()
+        .map_err(sql_err)?;
+    for (index, json) in rows {
+        let mut doc: Value =
+            serde_json::from_str(&json).map_err(|e| format!("Corrupt document row: {}", e))?;
+        let shapes: Vec<Value> = shape_stmt
+            .query_map(params![index], |r| r.get::<_, String>(0))
+            .map_err(sql_err)?
+            .map(|json| {
+                let json = json.map_err(sql_err)?;
+                serde_json::from_str(&json).map_err(|e| format!("Corrupt shape row: {}", e))
+            })
+            .collect::<Result<_, String>>()?;
+        doc["shapes"] = Value::Array(shapes);
+        documents.push(doc);
+    }
+    meta.insert("documents".to_string(), Value::Array(documents));
+    Ok(Value::Object(meta))
+}
+
+/// Changes since the last write, as sent by autosave (and recorded in
+/// journaled documents).
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DocumentChanges {
+    /// Top-level collection fields to merge (`activeDocumentIndex`, ...).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<Map<String, Value>>,
+    /// Documents beyond this count are dropped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document_count: Option<usize>,
+    pub documents: Vec<DocumentChange>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DocumentChange {
+    pub index: usize,
+    /// The document without `shapes`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document: Option<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub upserts: Vec<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+    /// Full back-to-front id order, when it changed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<Vec<String>>,
+}
+
+fn apply_changes(conn: &mut Connection, changes: &DocumentChanges) -> Result<(), String> {
+    let tx = conn.transaction().map_err(sql_err)?;
+    if let Some(fields) = &changes.collection {
+        let mut meta = read_meta(&tx)?;
+        for (key, value) in fields {
+            if key != "documents" {
+                meta.insert(key.clone(), value.clone());
+            }
+        }
+        write_meta(&tx, &meta)?;
+    }
+    if let Some(count) = changes.document_count {
+        truncate_documents(&tx, count)?;
+    }
+    for change in &changes.documents {
+        let doc = change.index as i64;
+        if let Some(document) = &change.document {
+            let (json, _) = split_document(document);
+            write_document_row(&tx, change.index, &json)?;
+        }
+        for id in &change.removed {
+            tx.execute(
+                "DELETE FROM shapes WHERE doc = ?1 AND id = ?2",
+                params![doc, id],
+            )
+            .map_err(sql_err)?;
+        }
+        for shape in &change.upserts {
+            let Some(id) = shape_id(shape) else { continue };
+            // New shapes go on top; existing ones keep their place.
+            tx.execute(
+                "INSERT INTO shapes (doc, id, z, json)
+                 VALUES (?1, ?2, (SELECT COALESCE(MAX(z), -1) + 1 FROM shapes WHERE doc = ?1), ?3)
+                 ON CONFLICT(doc, id) DO UPDATE SET json = excluded.json",
+                params![doc, id, shape.to_string()],
+            )
+            .map_err(sql_err)?;
+        }
+        if let Some(order) = &change.order {
+            let mut stmt = tx
+                .prepare("UPDATE shapes SET z = ?3 WHERE doc = ?1 AND id = ?2")
+                .map_err(sql_err)?;
+            for (z, id) in order.iter().enumerate() {
+                stmt.execute(params![doc, id, z as i64]).map_err(sql_err)?;
+            }
+        }
+    }
+    tx.commit().map_err(sql_err)
+}
+
+/// Write `bytes` to `path` and flush them to disk.
+fn write_synced(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let failed = |e: std::io::Error| format!("Failed to write file: {}", e);
+    let mut file = std::fs::File::create(path).map_err(failed)?;
+    file.write_all(bytes).map_err(failed)?;
+    file.sync_all().map_err(failed)
+}
+
+/// Write to a sibling temp file and move it over `path`, so a failed
+/// save or conversion never leaves a half-written document.
+fn replace_file(
+    path: &Path,
+    write: impl FnOnce(&Path) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let _ = std::fs::remove_file(&tmp);
+    write(&tmp).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to replace document: {}", e))
+}
+
+/// Write `bytes` to `path` as documents are saved: to a flushed sibling temp
+/// file renamed over it.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    replace_file(path, |tmp| write_synced(tmp, bytes))
+}
+
+/// The collection JSON of the document at `path`.
+pub(crate) fn read_path(path: &Path) -> Result<String, String> {
+    read_stored(path).map(assets::hydrate_json)
+}
+
+/// The collection JSON as stored at `path`, with images still referring to
+/// the asset store.
+pub(crate) fn read_stored(path: &Path) -> Result<String, String> {
+    if journal::is_journal(path) {
+        Ok(journal::read(path)?.to_string())
+    } else if is_sqlite(path) {
+        let conn = open(path)?;
+        Ok(read_collection(&conn)?.to_string())
+    } else if container::is_container(path) {
+        container::read(path)
+    } else if encryption::is_encrypted(path) {
+        Err(PROTECTED.to_string())
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))
+    }
+}
+
+/// Save collection `json` over the document at `path`, in its current format.
+/// JSON documents are written as version 2 containers, whichever version
+/// they were.
+pub(crate) fn write_path(path: &Path, json: String) -> Result<(), String> {
+    if journal::is_journal(path) {
+        journal::write(path, parse_stored(path, &json)?)
+    } else if is_sqlite(path) {
+        let mut conn = open(path)?;
+        write_collection(&mut conn, parse_stored(path, &json)?)
+    } else if encryption::is_encrypted(path) {
+        Err(PROTECTED.to_string())
+    } else {
+        write_protected(path, &json, None)
+    }
+}
+
+/// Save collection `json` to `path` as a version 2 container, encrypted
+/// with `password` if there is one.
+pub(crate) fn write_protected(
+    path: &Path,
+    json: &str,
+    password: Option<&str>,
+) -> Result<(), String> {
+    // Containers stand alone, so images from the asset store go in too
+    let packed = container::pack(&assets::hydrate_json(json.to_string()))?;
+    let bytes = match password {
+        Some(password) => encryption::seal(&packed, password)?,
+        None => packed,
+    };
+    write_atomic(path, &bytes)
+}
+
+/// The board that was showing when the document at `path` was saved.
+pub(crate) fn active_board(path: &Path) -> Result<Value, String> {
+    let collection = as_collection(parse(&read_path(path)?)?)?;
+    let index = collection
+        .get("activeDocumentIndex")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as usize;
+    let documents = collection.get("documents").and_then(Value::as_array);
+    documents
+        .and_then(|docs| docs.get(index).or_else(|| docs.first()))
+        .cloned()
+        .ok_or_else(|| "The document has no boards".to_string())
+}
+
+fn parse(json: &str) -> Result<Value, String> {
+    serde_json::from_str(json).map_err(|e| format!("Invalid document JSON: {}", e))
+}
+
+/// Collection `json`, to be stored at `path`, with its images moved into
+/// the asset store.
+fn parse_stored(path: &Path, json: &str) -> Result<Value, String> {
+    let mut collection = parse(json)?;
+    if assets::dehydrate(&mut collection) {
+        assets::register(path);
+    }
+    Ok(collection)
+}
+
+/// "journal", "sqlite" or "json" (for either version of the JSON format).
+pub(crate) fn format_of(path: &Path) -> &'static str {
+    if journal::is_journal(path) {
+        "journal"
+    } else if is_sqlite(path) {
+        "sqlite"
+    } else {
+        "json"
+    }
+}
+
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// --- Tauri commands ---
+
+/// Switch a document between JSON, SQLite and journal storage. `json` is
+/// the current collection; when omitted the file's own contents are
+/// converted.
+#[tauri::command]
+pub async fn convert_document(
+    path: String,
+    format: String,
+    json: Option<String>,
+) -> Result<(), String> {
+    blocking(move || {
+        let path = Path::new(&path);
+        if encryption::is_encrypted(path) {
+            return Err("Password-protected documents stay in JSON storage".to_string());
+        }
+        let json = match json {
+            Some(json) => json,
+            None => read_path(path)?,
+        };
+        match format.as_str() {
+            "sqlite" => {
+                let collection = parse_stored(path, &json)?;
+                replace_file(path, |tmp| write_collection(&mut open(tmp)?, collection))
+            }
+            "journal" => {
+                let collection = parse_stored(path, &json)?;
+                replace_file(path, |tmp| journal::create(tmp, collection))
+            }
+            "json" => replace_file(path, |tmp| {
+                let collection = parse(&assets::hydrate_json(json))?;
+                let pretty = serde_json::to_string_pretty(&collection).map_err(|e| e.to_string())?;
+                write_synced(tmp, &container::pack(&pretty)?)
+            }),
+            other => Err(format!("Unknown document format: {}", other)),
+        }
+    })
+    .await
+}
+
+/// Incremental save for SQLite documents.
+#[tauri::command]
+pub async fn apply_document_changes(
+    app: tauri::AppHandle,
+    path: String,
+    mut changes: DocumentChanges,
+) -> Result<(), String> {
+    let uri = resources::file_uri(&path);
+    let saved = path.clone();
+    blocking(move || {
+        let path = Path::new(&path);
+        let mut stored = false;
+        for shape in changes.documents.iter_mut().flat_map(|c| c.upserts.iter_mut()) {
+            stored |= assets::dehydrate_shape(shape);
+        }
+        if stored {
+            assets::register(path);
+        }
+        if journal::is_journal(path) {
+            journal::apply(path, &changes)?;
+        } else if is_sqlite(path) {
+            apply_changes(&mut open(path)?, &changes)?;
+        } else {
+            return Err("Incremental saves need a SQLite document".to_string());
+        }
+        wal::clear(path);
+        Ok(())
+    })
+    .await?;
+    document::forget(&app, &saved);
+    subscriptions::notify_app(&app, vec![uri]);
+    versions::record(&app, saved);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(id: &str, x: i64) -> Value {
+        json!({ "id": id, "type": "rectangle", "x": x, "y": 0, "width": 10, "height": 10 })
+    }
+
+    fn collection(shapes: Vec<Value>) -> Value {
+        json!({
+            "version": "1.0.0",
+            "appName": "napkin",
+            "type": "collection",
+            "activeDocumentIndex": 0,
+            "metadata": { "title": "Collection" },
+            "documents": [{
+                "version": "1.0.0",
+                "appName": "napkin",
+                "shapes": shapes,
+                "viewport": { "x": 0, "y": 0, "zoom": 1 },
+                "metadata": { "title": "Board" }
+            }]
+        })
+    }
+
+    fn shape_ids(collection: &Value) -> Vec<&str> {
+        collection["documents"][0]["shapes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["id"].as_str().unwrap())
+            .collect()
+    }
+
+    fn memory() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn
+    }
+
+    #[test]
+    fn round_trips_collections() {
+        let mut conn = memory();
+        let original = collection(vec![rect("b", 1), rect("a", 2)]);
+        write_collection(&mut conn, original.clone()).unwrap();
+        assert_eq!(read_collection(&conn).unwrap(), original);
+    }
+
+    #[test]
+    fn full_writes_only_touch_changed_rows() {
+        let mut conn = memory();
+        write_collection(&mut conn, collection(vec![rect("a", 0), rect("b", 0)])).unwrap();
+        let before = conn.total_changes();
+        write_collection(&mut conn, collection(vec![rect("a", 0), rect("b", 5)])).unwrap();
+        // The meta row, plus shape "b".
+        assert_eq!(conn.total_changes() - before, 2);
+
+        write_collection(&mut conn, collection(vec![rect("b", 5)])).unwrap();
+        assert_eq!(shape_ids(&read_collection(&conn).unwrap()), ["b"]);
+    }
+
+    #[test]
+    fn incremental_changes_apply() {
+        let mut conn = memory();
+        write_collection(&mut conn, collection(vec![rect("a", 0), rect("b", 0)])).unwrap();
+        let changes: DocumentChanges = serde_json::from_value(json!({
+            "collection": { "activeDocumentIndex": 0 },
+            "documents": [{ "index": 0, "upserts": [rect("c", 1), rect("a", 9)], "removed": ["b"] }]
+        }))
+        .unwrap();
+        apply_changes(&mut conn, &changes).unwrap();
+        let doc = read_collection(&conn).unwrap();
+        assert_eq!(shape_ids(&doc), ["a", "c"]);
+        assert_eq!(doc["documents"][0]["shapes"][0]["x"], 9);
+        assert_eq!(doc["metadata"]["title"], "Collection");
+
+        let reorder: DocumentChanges = serde_json::from_value(json!({
+            "documents": [{ "index": 0, "order": ["c", "a"] }]
+        }))
+        .unwrap();
+        apply_changes(&mut conn, &reorder).unwrap();
+        assert_eq!(shape_ids(&read_collection(&conn).unwrap()), ["c", "a"]);
+    }
+
+    #[test]
+    fn single_documents_become_collections() {
+        let single = json!({
+            "version": "1.0.0",
+            "appName": "napkin",
+            "shapes": [rect("a", 0)],
+            "viewport": { "x": 0, "y": 0, "zoom": 1 },
+            "metadata": { "title": "Solo" }
+        });
+        let mut conn = memory();
+        write_collection(&mut conn, single).unwrap();
+        let doc = read_collection(&conn).unwrap();
+        assert_eq!(doc["type"], "collection");
+        assert_eq!(shape_ids(&doc), ["a"]);
+    }
+
+    #[test]
+    fn converts_files_both_ways() {
+        let dir = std::env::temp_dir().join(format!("napkin-docstore-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("board.napkin");
+        let original = collection(vec![rect("a", 0)]);
+        std::fs::write(&path, original.to_string()).unwrap();
+        let path_str = path.to_string_lossy().to_string();
+
+        tauri::async_runtime::block_on(convert_document(path_str.clone(), "sqlite".into(), None))
+            .unwrap();
+        assert!(is_sqlite(&path));
+        let read = read_path(&path).unwrap();
+        assert_eq!(parse(&read).unwrap(), original);
+
+        tauri::async_runtime::block_on(convert_document(path_str.clone(), "journal".into(), None))
+            .unwrap();
+        assert_eq!(format_of(&path), "journal");
+        assert_eq!(parse(&read_path(&path).unwrap()).unwrap(), original);
+
+        tauri::async_runtime::block_on(convert_document(path_str.clone(), "json".into(), None))
+            .unwrap();
+        assert!(container::is_container(&path));
+        assert_eq!(format_of(&path), "json");
+        assert_eq!(parse(&read_path(&path).unwrap()).unwrap(), original);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,441 @@
+//! Saving and loading documents.
+//!
+//! The webview hands whole collections to `save_document` and gets them back
+//! from `load_document`; how a file stores them (JSON, SQLite or event log)
+//! is `docstore.rs`'s business. A JSON document is written to a sibling temp
+//! file, flushed to disk and renamed over the original, so a crash part-way
+//! through a save leaves the previous version rather than a truncated file;
+//! SQLite and event-log documents get the same from their transactions.
+//! Edits made since the last save are replayed from the document's
+//! write-ahead log when it is opened again (`wal.rs`).
+//!
+//! Boards that were never saved are autosaved to a recovery file in the app
+//! data directory (`save_recovery`), which stays out of the recent files.
+//!
+//! [`Documents`] keeps a digest of what was last loaded from or saved to each
+//! path, so `document_is_dirty` can tell whether a board still matches its
+//! file without reading it again, and the password of each protected
+//! document opened this session (`encryption.rs`), so saves encrypt it again
+//! without asking. Passwords never leave memory.
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::assets;
+use crate::container;
+use crate::docstore;
+use crate::encryption::{self, EncryptionError};
+use crate::resources;
+use crate::subscriptions;
+use crate::thumbnails;
+use crate::versions;
+use crate::wal;
+
+const RECOVERY_FILE: &str = "autosave.napkin";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocumentError {
+    /// There is no file at the path.
+    NotFound(String),
+    /// The file exists but could not be read.
+    Unreadable(String),
+    /// The contents are not a Napkin document.
+    Invalid(String),
+    /// Saving failed; the file on disk is unchanged.
+    WriteFailed(String),
+    /// The document is password-protected and no password was given.
+    PasswordRequired(String),
+    /// The password does not open the document.
+    WrongPassword,
+}
+
+impl DocumentError {
+    fn kind(&self) -> &'static str {
+        match self {
+            DocumentError::NotFound(_) => "notFound",
+            DocumentError::Unreadable(_) => "unreadable",
+            DocumentError::Invalid(_) => "invalid",
+            DocumentError::WriteFailed(_) => "writeFailed",
+            DocumentError::PasswordRequired(_) => "passwordRequired",
+            DocumentError::WrongPassword => "wrongPassword",
+        }
+    }
+}
+
+impl std::fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DocumentError::NotFound(path) => write!(f, "Document not found: {}", path),
+            DocumentError::Unreadable(e) => write!(f, "Failed to read document: {}", e),
+            DocumentError::Invalid(e) => write!(f, "Not a Napkin document: {}", e),
+            DocumentError::WriteFailed(e) => write!(f, "Failed to save document: {}", e),
+            DocumentError::PasswordRequired(path) => {
+                write!(f, "{} is password-protected", path)
+            }
+            DocumentError::WrongPassword => write!(f, "Wrong password"),
+        }
+    }
+}
+
+/// Sent to the webview as `{ kind, message }`.
+impl Serialize for DocumentError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("DocumentError", 2)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadedDocument {
+    /// The collection JSON, whatever the file's format.
+    pub json: String,
+    /// "json", "sqlite" or "journal".
+    pub format: &'static str,
+    /// Whether the file is password-protected.
+    pub encrypted: bool,
+}
+
+/// What each open document looked like when last loaded or saved.
+#[derive(Default)]
+pub struct Documents {
+    digests: Mutex<HashMap<String, [u8; 32]>>,
+    passwords: Mutex<HashMap<String, String>>,
+}
+
+impl Documents {
+    fn record(&self, path: &str, digest: [u8; 32]) {
+        if let Ok(mut digests) = self.digests.lock() {
+            digests.insert(path.to_string(), digest);
+        }
+    }
+
+    fn password(&self, path: &str) -> Option<String> {
+        self.passwords.lock().ok()?.get(path).cloned()
+    }
+
+    fn set_password(&self, path: &str, password: Option<String>) {
+        if let Ok(mut passwords) = self.passwords.lock() {
+            match password {
+                Some(password) => passwords.insert(path.to_string(), password),
+                None => passwords.remove(path),
+            };
+        }
+    }
+
+    /// Whether `digest` differs from the file's; unknown files are dirty.
+    fn is_dirty(&self, path: &str, digest: &[u8; 32]) -> bool {
+        self.digests
+            .lock()
+            .map_or(true, |digests| digests.get(path) != Some(digest))
+    }
+}
+
+/// Digest of a collection, the same whichever way its JSON was spaced.
+fn digest(json: &str) -> Result<[u8; 32], DocumentError> {
+    let value: Value =
+        serde_json::from_str(json).map_err(|e| DocumentError::Invalid(e.to_string()))?;
+    let collection = docstore::as_collection(value).map_err(DocumentError::Invalid)?;
+    let canonical = Value::Object(collection).to_string();
+    Ok(Sha256::digest(canonical.as_bytes()).into())
+}
+
+fn load(path: &str, password: Option<&str>) -> Result<LoadedDocument, DocumentError> {
+    let file = Path::new(path);
+    if !file.exists() {
+        return Err(DocumentError::NotFound(path.to_string()));
+    }
+    if !encryption::is_encrypted(file) {
+        let json = docstore::read_path(file).map_err(DocumentError::Unreadable)?;
+        let json = wal::recover(file, json);
+        // The webview loads the images from the asset store
+        let json = assets::dehydrate_json(&json).unwrap_or(json);
+        return Ok(LoadedDocument {
+            json,
+            format: docstore::format_of(file),
+            encrypted: false,
+        });
+    }
+    let password = password.ok_or_else(|| DocumentError::PasswordRequired(path.to_string()))?;
+    let bytes = std::fs::read(file).map_err(|e| DocumentError::Unreadable(e.to_string()))?;
+    let packed = encryption::open(&bytes, password).map_err(|e| match e {
+        EncryptionError::WrongPassword => DocumentError::WrongPassword,
+        e => DocumentError::Unreadable(e.to_string()),
+    })?;
+    Ok(LoadedDocument {
+        json: container::unpack(&packed).map_err(DocumentError::Invalid)?,
+        format: "json",
+        encrypted: true,
+    })
+}
+
+/// Write collection `json` to `path`, encrypted if it has a `password`.
+fn write(path: &str, json: String, password: Option<&str>) -> Result<(), DocumentError> {
+    let file = Path::new(path);
+    if password.is_none() && encryption::is_encrypted(file) {
+        return Err(DocumentError::PasswordRequired(path.to_string()));
+    }
+    if password.is_some() && file.exists() && docstore::format_of(file) != "json" {
+        return Err(DocumentError::WriteFailed(
+            "Only JSON documents can be password-protected".to_string(),
+        ));
+    }
+    match password {
+        Some(password) => docstore::write_protected(file, &json, Some(password)),
+        None => docstore::write_path(file, json),
+    }
+    .map_err(DocumentError::WriteFailed)?;
+    wal::clear(file);
+    Ok(())
+}
+
+/// The last known state of `path` is no longer known, as after an
+/// incremental save.
+pub fn forget(app: &tauri::AppHandle, path: &str) {
+    if let Some(documents) = app.try_state::<Documents>() {
+        if let Ok(mut digests) = documents.digests.lock() {
+            digests.remove(path);
+        }
+    }
+}
+
+pub(crate) fn recovery_path(app: &tauri::AppHandle) -> Result<PathBuf, DocumentError> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| DocumentError::WriteFailed(format!("No app data directory: {}", e)))?;
+    std::fs::create_dir_all(&dir).map_err(|e| DocumentError::WriteFailed(e.to_string()))?;
+    Ok(dir.join(RECOVERY_FILE))
+}
+
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, DocumentError> + Send + 'static,
+) -> Result<T, DocumentError> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| DocumentError::Unreadable(e.to_string()))?
+}
+
+// --- Tauri commands ---
+
+/// The document at `path` as collection JSON, with its storage format and
+/// any edits a crash kept from being saved. A protected document needs its
+/// `password`, unless it was already opened this session.
+#[tauri::command]
+pub async fn load_document(
+    app: tauri::AppHandle,
+    path: String,
+    password: Option<String>,
+) -> Result<LoadedDocument, DocumentError> {
+    let documents = app.state::<Documents>();
+    let password = password.or_else(|| documents.password(&path));
+    let loaded = blocking({
+        let path = path.clone();
+        let password = password.clone();
+        move || load(&path, password.as_deref())
+    })
+    .await?;
+    if loaded.encrypted {
+        documents.set_password(&path, password);
+    }
+    resources::remember_file(&app, &path);
+    if let Ok(digest) = digest(&loaded.json) {
+        documents.record(&path, digest);
+    }
+    versions::show(&app, &path);
+    Ok(loaded)
+}
+
+/// Save a whole collection to `path`, keeping the file's current format.
+/// Protected documents are encrypted with the password they were opened
+/// with; a `password` protects a new file, as when saving a copy of one.
+#[tauri::command]
+pub async fn save_document(
+    app: tauri::AppHandle,
+    path: String,
+    json: String,
+    password: Option<String>,
+) -> Result<(), DocumentError> {
+    let digest = digest(&json)?;
+    let documents = app.state::<Documents>();
+    let password = password
+        .filter(|p| !p.is_empty())
+        .or_else(|| documents.password(&path));
+    blocking({
+        let path = path.clone();
+        let password = password.clone();
+        move || write(&path, json, password.as_deref())
+    })
+    .await?;
+    documents.record(&path, digest);
+    resources::remember_file(&app, &path);
+    subscriptions::notify_app(&app, vec![resources::file_uri(&path)]);
+    if password.is_some() {
+        documents.set_password(&path, password);
+    } else {
+        versions::record(&app, path.clone());
+        thumbnails::refresh(&app, path);
+    }
+    Ok(())
+}
+
+/// Protect the document at `path` with `password`, or remove its
+/// protection when there is none, saving collection `json` to it. Earlier
+/// versions and the thumbnail of a newly protected document are deleted.
+#[tauri::command]
+pub async fn set_document_password(
+    app: tauri::AppHandle,
+    path: String,
+    json: String,
+    password: Option<String>,
+) -> Result<(), DocumentError> {
+    let digest = digest(&json)?;
+    let password = password.filter(|p| !p.is_empty());
+    let file = Path::new(&path);
+    if file.exists() && !encryption::is_encrypted(file) && docstore::format_of(file) != "json" {
+        return Err(DocumentError::WriteFailed(
+            "Only JSON documents can be password-protected".to_string(),
+        ));
+    }
+    blocking({
+        let path = path.clone();
+        let password = password.clone();
+        move || {
+            let file = Path::new(&path);
+            docstore::write_protected(file, &json, password.as_deref())
+                .map_err(DocumentError::WriteFailed)?;
+            wal::clear(file);
+            Ok(())
+        }
+    })
+    .await?;
+    let documents = app.state::<Documents>();
+    documents.record(&path, digest);
+    if password.is_some() {
+        versions::discard(&app, &path);
+        thumbnails::discard(&app, &path);
+    } else {
+        versions::record(&app, path.clone());
+        thumbnails::refresh(&app, path.clone());
+    }
+    documents.set_password(&path, password);
+    Ok(())
+}
+
+/// Whether the document at `path` is password-protected.
+#[tauri::command]
+pub fn document_is_protected(path: String) -> bool {
+    encryption::is_encrypted(Path::new(&path))
+}
+
+/// Autosave a board that has no file of its own.
+#[tauri::command]
+pub async fn save_recovery(app: tauri::AppHandle, json: String) -> Result<(), DocumentError> {
+    digest(&json)?;
+    let path = recovery_path(&app)?;
+    blocking(move || docstore::write_path(&path, json).map_err(DocumentError::WriteFailed)).await
+}
+
+/// The recovery autosave, if there is one.
+#[tauri::command]
+pub async fn load_recovery(app: tauri::AppHandle) -> Result<Option<String>, DocumentError> {
+    let path = recovery_path(&app)?;
+    blocking(move || match load(&path.to_string_lossy(), None) {
+        Ok(loaded) => Ok(Some(loaded.json)),
+        Err(DocumentError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    })
+    .await
+}
+
+/// Whether collection `json` differs from what was last loaded from or
+/// saved to `path`.
+#[tauri::command]
+pub fn document_is_dirty(
+    path: String,
+    json: String,
+    documents: tauri::State<'_, Documents>,
+) -> Result<bool, DocumentError> {
+    Ok(documents.is_dirty(&path, &digest(&json)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn dirtiness_ignores_formatting_but_not_content() {
+        let board = json!({ "shapes": [{ "id": "a", "type": "rectangle" }] });
+        let documents = Documents::default();
+        let saved = digest(&board.to_string()).unwrap();
+        assert!(documents.is_dirty("b.napkin", &saved));
+        documents.record("b.napkin", saved);
+
+        let pretty = serde_json::to_string_pretty(&board).unwrap();
+        assert!(!documents.is_dirty("b.napkin", &digest(&pretty).unwrap()));
+        let moved = json!({ "shapes": [{ "id": "a", "type": "rectangle", "x": 5 }] });
+        assert!(documents.is_dirty("b.napkin", &digest(&moved.to_string()).unwrap()));
+        assert!(documents.is_dirty("other.napkin", &saved));
+
+        assert!(matches!(digest("{"), Err(DocumentError::Invalid(_))));
+        assert!(matches!(digest("[1]"), Err(DocumentError::Invalid(_))));
+    }
+
+    #[test]
+    fn errors_say_what_went_wrong() {
+        let dir = std::env::temp_dir().join(format!("napkin-document-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let missing = dir.join("missing.napkin").to_string_lossy().to_string();
+        let error = load(&missing, None).unwrap_err();
+        assert!(matches!(error, DocumentError::NotFound(_)));
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({ "kind": "notFound", "message": format!("Document not found: {}", missing) })
+        );
+
+        let path = dir.join("board.napkin");
+        docstore::write_path(&path, json!({ "shapes": [] }).to_string()).unwrap();
+        let loaded = load(&path.to_string_lossy(), None).unwrap();
+        assert_eq!(loaded.format, "json");
+        // Saved through a temp file that does not outlive the save
+        assert!(!dir.join("board.napkin.tmp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn protected_documents_need_their_password() {
+        let dir = std::env::temp_dir().join(format!("napkin-document-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("secret.napkin").to_string_lossy().to_string();
+        let board = json!({ "shapes": [{ "id": "a", "type": "text", "text": "salary" }] });
+        write(&path, board.to_string(), Some("hunter2")).unwrap();
+        assert!(!std::fs::read(&path).unwrap().windows(6).any(|w| w == b"salary"));
+
+        let error = load(&path, None).unwrap_err();
+        assert_eq!(
+            serde_json::to_value(&error).unwrap()["kind"],
+            "passwordRequired"
+        );
+        assert_eq!(load(&path, Some("hunter3")).unwrap_err(), DocumentError::WrongPassword);
+        let loaded = load(&path, Some("hunter2")).unwrap();
+        assert!(loaded.encrypted);
+        assert_eq!(serde_json::from_str::<Value>(&loaded.json).unwrap(), board);
+
+        // Nothing else reads it, or saves over it without the password
+        assert!(docstore::read_path(Path::new(&path)).is_err());
+        assert!(matches!(
+            write(&path, board.to_string(), None),
+            Err(DocumentError::PasswordRequired(_))
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,124 @@
+//! Stopping the server without orphaning tool calls.
+//!
+//! Every call sent to the webview (`bridge_tool_call`) is counted while it
+//! waits for its answer. `stop_api_server` first starts draining: requests
+//! that arrive from then on are refused with `503` and a JSON-RPC error
+//! saying the server is shutting down, and new bridge calls fail the same
+//! way. It then waits up to [`TIMEOUT_SECS`] for the calls already sent to
+//! be answered before closing the listener, so an agent mid-call gets its
+//! result rather than a dropped connection.
+
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::api::{mcp_error, SharedApiState};
+
+/// How long stopping waits for unanswered calls.
+pub const TIMEOUT_SECS: u64 = 10;
+/// JSON-RPC "Server error", for requests refused while stopping.
+const SERVER_ERROR: i64 = -32000;
+const SHUTTING_DOWN: &str = "Napkin's MCP server is shutting down";
+
+#[derive(Default)]
+pub struct Drain {
+    draining: AtomicBool,
+    /// Bridge calls waiting for the webview.
+    in_flight: watch::Sender<usize>,
+}
+
+/// A counted call; uncounted when dropped.
+pub struct InFlight<'a>(&'a Drain);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.send_modify(|n| *n -= 1);
+    }
+}
+
+impl Drain {
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Count a call until the guard is dropped; refused while draining.
+    pub fn begin(&self) -> Result<InFlight<'_>, String> {
+        // Counted before the check, so `drain` waits for any call that
+        // got past it
+        self.in_flight.send_modify(|n| *n += 1);
+        let call = InFlight(self);
+        if self.is_draining() {
+            return Err(SHUTTING_DOWN.to_string());
+        }
+        Ok(call)
+    }
+
+    /// Refuse new calls and wait up to `timeout` for the counted ones; how
+    /// many are still unanswered.
+    pub async fn drain(&self, timeout: Duration) -> usize {
+        self.draining.store(true, Ordering::SeqCst);
+        let mut in_flight = self.in_flight.subscribe();
+        let _ = tokio::time::timeout(timeout, in_flight.wait_for(|n| *n == 0)).await;
+        *self.in_flight.borrow()
+    }
+
+    /// Accept calls again, once the server has stopped.
+    pub fn finish(&self) {
+        self.draining.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Refuse requests while the server is stopping.
+pub async fn refuse_while_draining(
+    State(state): State<SharedApiState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.drain.is_draining() {
+        return next.run(request).await;
+    }
+    let body = if request.uri().path().starts_with("/api/v1/") {
+        json!({ "error": SHUTTING_DOWN, "code": "INTERNAL" })
+    } else {
+        let mut error = mcp_error(None, SERVER_ERROR, SHUTTING_DOWN);
+        error["error"]["data"] = json!({ "reason": "shuttingDown" });
+        error
+    };
+    (StatusCode::SERVICE_UNAVAILABLE, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn stopping_waits_for_calls_already_made() {
+        let drain = Arc::new(Drain::default());
+        assert_eq!(drain.drain(Duration::ZERO).await, 0);
+        drain.finish();
+
+        let call = drain.begin().unwrap();
+        let stopping = tokio::spawn({
+            let drain = Arc::clone(&drain);
+            async move { drain.drain(Duration::from_secs(5)).await }
+        });
+        while !drain.is_draining() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(drain.begin().err().as_deref(), Some(SHUTTING_DOWN));
+        drop(call);
+        assert_eq!(stopping.await.unwrap(), 0);
+
+        // A call that never finishes holds up stopping only until the timeout
+        drain.finish();
+        let _stuck = drain.begin().unwrap();
+        assert_eq!(drain.drain(Duration::from_millis(10)).await, 1);
+    }
+}
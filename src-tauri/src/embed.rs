@@ -0,0 +1,257 @@
+//! Embeddable live board views.
+//!
+//! `/embed/{token}` serves a read-only viewer for the current canvas. Tokens
+//! are HMAC-signed claims (expiry + viewport lock) so a snippet pasted into a
+//! wiki stops working once it expires, without any server-side bookkeeping.
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::export::{self, InteractiveExportOptions, InteractiveFormat};
+use crate::watermark;
+use axum::{
+    extract::{Path, State as AxumState},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const DEFAULT_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+/// Longest lifetime a snippet may ask for.
+const MAX_TTL_SECS: u64 = 10 * 365 * 24 * 60 * 60;
+const DEFAULT_WIDTH: u32 = 800;
+const DEFAULT_HEIGHT: u32 = 600;
+
+// --- Tokens ---
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmbedClaims {
+    /// Expiry as seconds since the Unix epoch.
+    pub exp: u64,
+    /// Read-only viewport lock (no pan/zoom in the viewer).
+    #[serde(default)]
+    pub lock: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EmbedTokenError {
+    Malformed,
+    BadSignature,
+    Expired,
+}
+
+impl std::fmt::Display for EmbedTokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmbedTokenError::Malformed => write!(f, "Malformed embed token"),
+            EmbedTokenError::BadSignature => write!(f, "Invalid embed token signature"),
+            EmbedTokenError::Expired => write!(f, "Embed token has expired"),
+        }
+    }
+}
+
+pub fn sign_token(key: &[u8], claims: &EmbedClaims) -> String {
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap_or_default());
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(payload.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+    format!("{}.{}", payload, signature)
+}
+
+pub fn verify_token(key: &[u8], token: &str, now: u64) -> Result<EmbedClaims, EmbedTokenError> {
+    let (payload, signature) = token.split_once('.').ok_or(EmbedTokenError::Malformed)?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| EmbedTokenError::Malformed)?;
+
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature)
+        .map_err(|_| EmbedTokenError::BadSignature)?;
+
+    let bytes = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| EmbedTokenError::Malformed)?;
+    let claims: EmbedClaims =
+        serde_json::from_slice(&bytes).map_err(|_| EmbedTokenError::Malformed)?;
+    if claims.exp <= now {
+        return Err(EmbedTokenError::Expired);
+    }
+    Ok(claims)
+}
+
+/// Random per-process signing key. Restarting the app invalidates old snippets.
+pub fn generate_key() -> Vec<u8> {
+    let mut key = Vec::with_capacity(32);
+    key.extend_from_slice(uuid::Uuid::new_v4().as_bytes());
+    key.extend_from_slice(uuid::Uuid::new_v4().as_bytes());
+    key
+}
+
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Expiry for a snippet made at `now` that lasts `ttl_secs` (a week by
+/// default).
+fn expiry(now: u64, ttl_secs: Option<u64>) -> Result<u64, String> {
+    let ttl = ttl_secs.unwrap_or(DEFAULT_TTL_SECS);
+    if ttl > MAX_TTL_SECS {
+        return Err(format!(
+            "Embed lifetime of {} seconds is longer than the maximum of {}",
+            ttl, MAX_TTL_SECS
+        ));
+    }
+    Ok(now.saturating_add(ttl))
+}
+
+// --- Tauri commands ---
+
+/// Build an `<iframe>` snippet pointing at a freshly signed embed URL.
+#[tauri::command]
+pub async fn get_embed_snippet(
+    ttl_secs: Option<u64>,
+    lock_viewport: Option<bool>,
+    width: Option<u32>,
+    height: Option<u32>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<String, String> {
+    let origin = state
+        .server_origin()
+        .await
+        .ok_or_else(|| "API server is not running".to_string())?;
+
+    let claims = EmbedClaims {
+        exp: expiry(now_secs(), ttl_secs)?,
+        lock: lock_viewport.unwrap_or(true),
+    };
+    let token = sign_token(&state.embed_key, &claims);
+
+    Ok(format!(
+        "<iframe src=\"{}/embed/{}\" width=\"{}\" height=\"{}\" style=\"border:0\" loading=\"lazy\" title=\"Napkin board\"></iframe>",
+        origin,
+        token,
+        width.unwrap_or(DEFAULT_WIDTH),
+        height.unwrap_or(DEFAULT_HEIGHT),
+    ))
+}
+
+// --- HTTP handlers ---
+
+pub async fn embed_page_handler(
+    AxumState(state): AxumState<SharedApiState>,
+    Path(token): Path<String>,
+) -> Response {
+    let claims = match verify_token(&state.embed_key, &token, now_secs()) {
+        Ok(c) => c,
+        Err(e) => return token_error_response(e),
+    };
+
+    let shapes = match fetch_shapes(&state).await {
+        Ok(s) => s,
+        Err(e) => return (StatusCode::BAD_GATEWAY, e).into_response(),
+    };
+
+    let options = InteractiveExportOptions {
+        format: Some(InteractiveFormat::Html),
+        lock_viewport: claims.lock,
+        refresh_url: Some(format!("/embed/{}/content", token)),
+        watermark: watermark::resolve(&state.app_handle, None),
+        ..Default::default()
+    };
+    let html = export::render_interactive(&shapes, &options);
+    ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response()
+}
+
+pub async fn embed_content_handler(
+    AxumState(state): AxumState<SharedApiState>,
+    Path(token): Path<String>,
+) -> Response {
+    if let Err(e) = verify_token(&state.embed_key, &token, now_secs()) {
+        return token_error_response(e);
+    }
+
+    match fetch_shapes(&state).await {
+        Ok(shapes) => (
+            [(header::CONTENT_TYPE, "image/svg+xml; charset=utf-8")],
+            export::render_content(&shapes),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e).into_response(),
+    }
+}
+
+pub(crate) async fn fetch_shapes(state: &SharedApiState) -> Result<Vec<serde_json::Value>, String> {
+    let canvas = bridge_tool_call(state, "get_canvas", serde_json::json!({})).await?;
+    if let Some(err) = canvas.get("error").and_then(|e| e.as_str()) {
+        return Err(err.to_string());
+    }
+    Ok(canvas
+        .get("shapes")
+        .and_then(|s| s.as_array())
+        .cloned()
+        .unwrap_or_default())
+}
+
+fn token_error_response(err: EmbedTokenError) -> Response {
+    let status = match err {
+        EmbedTokenError::Expired => StatusCode::GONE,
+        _ => StatusCode::UNAUTHORIZED,
+    };
+    (status, err.to_string()).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_roundtrip() {
+        let key = generate_key();
+        let claims = EmbedClaims { exp: 2_000, lock: true };
+        let token = sign_token(&key, &claims);
+        assert_eq!(verify_token(&key, &token, 1_000), Ok(claims));
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let key = generate_key();
+        let token = sign_token(&key, &EmbedClaims { exp: 1_000, lock: false });
+        assert_eq!(verify_token(&key, &token, 1_000), Err(EmbedTokenError::Expired));
+    }
+
+    #[test]
+    fn lifetimes_beyond_the_maximum_are_refused() {
+        assert_eq!(expiry(1_000, None), Ok(1_000 + DEFAULT_TTL_SECS));
+        assert_eq!(expiry(1_000, Some(MAX_TTL_SECS)), Ok(1_000 + MAX_TTL_SECS));
+        assert!(expiry(1_000, Some(MAX_TTL_SECS + 1)).is_err());
+        assert!(expiry(1_000, Some(u64::MAX)).is_err());
+        assert_eq!(expiry(u64::MAX, Some(60)), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn token_from_other_key_is_rejected() {
+        let token = sign_token(&generate_key(), &EmbedClaims { exp: 2_000, lock: false });
+        assert_eq!(
+            verify_token(&generate_key(), &token, 1_000),
+            Err(EmbedTokenError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn tampered_claims_are_rejected() {
+        let key = generate_key();
+        let token = sign_token(&key, &EmbedClaims { exp: 2_000, lock: true });
+        let signature = token.split_once('.').unwrap().1;
+        let forged_payload = URL_SAFE_NO_PAD.encode(br#"{"exp":9999999999,"lock":false}"#);
+        let forged = format!("{}.{}", forged_payload, signature);
+        assert_eq!(verify_token(&key, &forged, 1_000), Err(EmbedTokenError::BadSignature));
+        assert_eq!(verify_token(&key, "garbage", 1_000), Err(EmbedTokenError::Malformed));
+    }
+}
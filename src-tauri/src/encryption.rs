@@ -0,0 +1,263 @@
+//! Password-protected documents.
+//!
+//! A protected `.napkin` file is the version 2 container (`container.rs`)
+//! encrypted with AES-256-GCM, behind a header naming how the key was
+//! derived:
+//!
+//! ```text
+//! "NAPKENC" 0x01 | kdf (1 byte) | memory KiB, passes, lanes (u32 LE each) | salt (16) | nonce (12) | ciphertext + tag
+//! ```
+//!
+//! The key is Argon2id of the password over a random salt, with the cost it
+//! was derived with stored in the header so it can be raised later without
+//! breaking older files. The header is the AEAD's associated data, so it
+//! cannot be altered either.
+//! Every save draws a new salt and nonce. A wrong password and a damaged
+//! file look the same to AES-GCM, and both are reported as a wrong password.
+//!
+//! Only the webview, which asks for the password, opens these files: the
+//! MCP resources, thumbnails, version history and the write-ahead log leave
+//! them alone rather than keep readable copies.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::io::Read;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"NAPKENC\x01";
+const KDF_ARGON2ID: u8 = 1;
+/// RFC 9106's second recommended option: 64 MiB, three passes, four lanes.
+pub(crate) const ARGON2_COST: Argon2Cost = Argon2Cost {
+    memory_kib: 64 * 1024,
+    passes: 3,
+    lanes: 4,
+};
+/// Refuses files that would need more than 1 GiB or minutes to open.
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_PASSES: u32 = 16;
+const MAX_LANES: u32 = 16;
+const SALT_LEN: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncryptionError {
+    /// The password does not open the file, or the file is damaged.
+    WrongPassword,
+    /// The file is not something this build can decrypt.
+    Unsupported(String),
+}
+
+impl std::fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncryptionError::WrongPassword => write!(f, "Wrong password"),
+            EncryptionError::Unsupported(e) => write!(f, "Cannot decrypt document: {}", e),
+        }
+    }
+}
+
+/// Whether `path` is a password-protected document.
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut header = [0u8; MAGIC.len()];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok_and(|_| &header == MAGIC)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Argon2Cost {
+    pub memory_kib: u32,
+    pub passes: u32,
+    pub lanes: u32,
+}
+
+impl Argon2Cost {
+    /// Bytes the cost takes in the header.
+    const LEN: usize = 12;
+
+    fn to_bytes(self) -> [u8; Self::LEN] {
+        let mut bytes = [0u8; Self::LEN];
+        for (chunk, value) in
+            bytes
+                .chunks_exact_mut(4)
+                .zip([self.memory_kib, self.passes, self.lanes])
+        {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// The cost in a header, refusing costs no build writes.
+    fn parse(bytes: &[u8]) -> Result<Self, EncryptionError> {
+        let mut values = bytes
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+        let mut next = || values.next().unwrap_or(0);
+        let cost = Argon2Cost {
+            memory_kib: next(),
+            passes: next(),
+            lanes: next(),
+        };
+        let bounded = cost.memory_kib <= MAX_MEMORY_KIB
+            && (1..=MAX_PASSES).contains(&cost.passes)
+            && (1..=MAX_LANES).contains(&cost.lanes);
+        if bounded {
+            Ok(cost)
+        } else {
+            Err(EncryptionError::Unsupported(format!(
+                "Argon2id cost {:?}",
+                cost
+            )))
+        }
+    }
+}
+
+/// An Argon2id hasher with `cost`, producing `output_len` bytes.
+pub(crate) fn argon2id(cost: Argon2Cost, output_len: usize) -> Result<Argon2<'static>, String> {
+    let params = Params::new(cost.memory_kib, cost.passes, cost.lanes, Some(output_len))
+        .map_err(|e| format!("Invalid Argon2id cost: {}", e))?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+fn key(cost: Argon2Cost, password: &str, salt: &[u8]) -> Result<LessSafeKey, String> {
+    let mut key = [0u8; 32];
+    argon2id(cost, key.len())?
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| "Invalid key".to_string())?;
+    Ok(LessSafeKey::new(key))
+}
+
+fn seal_with(plaintext: &[u8], password: &str, cost: Argon2Cost) -> Result<Vec<u8>, String> {
+    let rng = SystemRandom::new();
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut salt)
+        .and_then(|()| rng.fill(&mut nonce))
+        .map_err(|_| "No randomness available".to_string())?;
+
+    let mut out = Vec::with_capacity(64 + plaintext.len());
+    out.extend_from_slice(MAGIC);
+    out.push(KDF_ARGON2ID);
+    out.extend_from_slice(&cost.to_bytes());
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    let header_len = out.len();
+    let mut sealed = plaintext.to_vec();
+    key(cost, password, &salt)?
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(&out[..header_len]),
+            &mut sealed,
+        )
+        .map_err(|_| "Encryption failed".to_string())?;
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+/// `plaintext` encrypted with `password`.
+pub fn seal(plaintext: &[u8], password: &str) -> Result<Vec<u8>, String> {
+    seal_with(plaintext, password, ARGON2_COST)
+}
+
+/// The plaintext of an encrypted file's `bytes`.
+pub fn open(bytes: &[u8], password: &str) -> Result<Vec<u8>, EncryptionError> {
+    let truncated = || EncryptionError::Unsupported("not an encrypted document".into());
+    if bytes.len() <= MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(truncated());
+    }
+    let id = bytes[MAGIC.len()];
+    if id != KDF_ARGON2ID {
+        return Err(EncryptionError::Unsupported(format!(
+            "unknown key derivation {}",
+            id
+        )));
+    }
+    let at = MAGIC.len() + 1;
+    let header_len = at + Argon2Cost::LEN + SALT_LEN + NONCE_LEN;
+    if bytes.len() < header_len {
+        return Err(truncated());
+    }
+    let (header, sealed) = bytes.split_at(header_len);
+    let cost = Argon2Cost::parse(&header[at..at + Argon2Cost::LEN])?;
+    let salt = &header[at + Argon2Cost::LEN..at + Argon2Cost::LEN + SALT_LEN];
+    let nonce = Nonce::try_assume_unique_for_key(&header[header_len - NONCE_LEN..])
+        .map_err(|_| EncryptionError::Unsupported("bad nonce".into()))?;
+
+    let key = key(cost, password, salt).map_err(EncryptionError::Unsupported)?;
+    let mut plaintext = sealed.to_vec();
+    let len = key
+        .open_in_place(nonce, Aad::from(header), &mut plaintext)
+        .map_err(|_| EncryptionError::WrongPassword)?
+        .len();
+    plaintext.truncate(len);
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap enough for tests.
+    const TEST_COST: Argon2Cost = Argon2Cost {
+        memory_kib: 64,
+        passes: 1,
+        lanes: 1,
+    };
+
+    #[test]
+    fn only_the_right_password_opens_a_document() {
+        let sealed = seal_with(b"board", "hunter2", TEST_COST).unwrap();
+        assert_eq!(sealed[MAGIC.len()], KDF_ARGON2ID);
+        assert_eq!(&sealed[..MAGIC.len()], MAGIC);
+        assert!(!sealed.windows(5).any(|w| w == b"board"));
+        assert_eq!(open(&sealed, "hunter2").unwrap(), b"board");
+        assert_eq!(
+            open(&sealed, "hunter3"),
+            Err(EncryptionError::WrongPassword)
+        );
+
+        // Fresh salt and nonce each time
+        assert_ne!(seal_with(b"board", "hunter2", TEST_COST).unwrap(), sealed);
+
+        // Changing the memory cost in the header breaks the tag
+        let mut tampered = sealed.clone();
+        tampered[MAGIC.len() + 1] ^= 1;
+        assert_eq!(
+            open(&tampered, "hunter2"),
+            Err(EncryptionError::WrongPassword)
+        );
+
+        let mut unknown = sealed;
+        unknown[MAGIC.len()] = 9;
+        assert!(matches!(
+            open(&unknown, "hunter2"),
+            Err(EncryptionError::Unsupported(_))
+        ));
+        assert!(matches!(
+            open(b"{}", "hunter2"),
+            Err(EncryptionError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn costs_no_build_writes_are_refused() {
+        let greedy = Argon2Cost {
+            memory_kib: MAX_MEMORY_KIB + 1,
+            ..ARGON2_COST
+        };
+        assert!(matches!(
+            Argon2Cost::parse(&greedy.to_bytes()),
+            Err(EncryptionError::Unsupported(_))
+        ));
+        let idle = Argon2Cost {
+            passes: 0,
+            ..ARGON2_COST
+        };
+        assert!(matches!(
+            Argon2Cost::parse(&idle.to_bytes()),
+            Err(EncryptionError::Unsupported(_))
+        ));
+        assert_eq!(Argon2Cost::parse(&ARGON2_COST.to_bytes()), Ok(ARGON2_COST));
+    }
+}
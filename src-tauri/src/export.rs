@@ -0,0 +1,957 @@
+//! Native export of canvas content.
+//!
+//! The webview exporters rasterize through rough.js; the writers here build
+//! documents directly from the serialized shape JSON so they can run without
+//! a canvas (and, eventually, without a webview at all).
+
+use crate::assets;
+use crate::author::{self, AuthorSource};
+use crate::export_hooks;
+use crate::geometry;
+use crate::text_metrics;
+use crate::watermark::{self, Watermark};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+
+const CONTENT_MARGIN: f64 = 20.0;
+const DEFAULT_FONT_FAMILY: &str = "Arial, sans-serif";
+const HIGHLIGHT_COLOR: &str = "#ff3d7f";
+
+// --- Options ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InteractiveFormat {
+    /// Standalone SVG with an inline script (search via the `/` key).
+    Svg,
+    /// HTML page wrapping the SVG with a search box.
+    Html,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InteractiveExportOptions {
+    /// Output flavour. When omitted it is derived from the file extension.
+    pub format: Option<InteractiveFormat>,
+    pub title: Option<String>,
+    pub padding: f64,
+    pub background_color: String,
+    /// Disable pan/zoom so viewers always see the exported framing.
+    pub lock_viewport: bool,
+    /// When set, the page polls this URL for fresh content markup
+    /// (see `render_content`) and swaps it in place.
+    pub refresh_url: Option<String>,
+    pub refresh_interval_secs: u64,
+    /// Add a legend naming who created the shapes (see `author.rs`).
+    pub author_legend: bool,
+    /// Banner or stamp drawn over the board (see `watermark.rs`). When
+    /// omitted the `exportWatermark` setting applies.
+    pub watermark: Option<Watermark>,
+}
+
+impl Default for InteractiveExportOptions {
+    fn default() -> Self {
+        Self {
+            format: None,
+            title: None,
+            padding: 40.0,
+            background_color: "#ffffff".to_string(),
+            lock_viewport: false,
+            refresh_url: None,
+            refresh_interval_secs: 5,
+            author_legend: false,
+            watermark: None,
+        }
+    }
+}
+
+// --- Tauri commands ---
+
+/// Write the given shapes as an interactive SVG or HTML document, then run
+/// the hooks of export preset `preset`.
+#[tauri::command]
+pub fn export_interactive_svg(
+    path: String,
+    shapes: Vec<Value>,
+    options: Option<InteractiveExportOptions>,
+    preset: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    let mut options = options.unwrap_or_default();
+    options.watermark = watermark::resolve(&app, options.watermark.take());
+    if options.format.is_none() {
+        let lower = path.to_ascii_lowercase();
+        options.format = Some(if lower.ends_with(".html") || lower.ends_with(".htm") {
+            InteractiveFormat::Html
+        } else {
+            InteractiveFormat::Svg
+        });
+    }
+
+    let document = render_interactive(&shapes, &options);
+    std::fs::write(&path, document).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    export_hooks::after_export(&app, path, preset.as_deref());
+    Ok(())
+}
+
+// --- Bounds ---
+
+pub use crate::geometry::Bounds;
+
+/// Content bounds of a set of serialized shapes, including the stroke margin.
+/// Rotated shapes contribute their rotated footprint.
+/// Mirrors `getContentBounds` in `src/lib/export/renderExport.ts`.
+pub fn content_bounds(shapes: &[Value]) -> Bounds {
+    match geometry::combined_bounds(shapes) {
+        Some(b) => b.expand(CONTENT_MARGIN),
+        None => Bounds { min_x: 0.0, min_y: 0.0, max_x: 100.0, max_y: 100.0 },
+    }
+}
+
+// --- Interactive document ---
+
+/// Render shapes as an SVG (or HTML page) with pan/zoom, clickable links and
+/// text search. Shapes carrying an `http`, `https` or `mailto` `link` are
+/// wrapped in anchors; other schemes (`javascript:`, `data:`) are dropped.
+pub fn render_interactive(shapes: &[Value], options: &InteractiveExportOptions) -> String {
+    let shapes = &text_metrics::auto_fitted(shapes);
+    let bounds = content_bounds(shapes);
+    let pad = options.padding.max(0.0);
+    let vx = bounds.min_x - pad;
+    let vy = bounds.min_y - pad;
+    let vw = bounds.width() + pad * 2.0;
+    let vh = bounds.height() + pad * 2.0;
+
+    let html = options.format == Some(InteractiveFormat::Html);
+    let title = options.title.clone().unwrap_or_else(|| "Napkin board".to_string());
+
+    let mut svg = String::new();
+    let size = if html {
+        "width=\"100%\" height=\"100%\"".to_string()
+    } else {
+        format!("width=\"{}\" height=\"{}\"", fmt(vw), fmt(vh))
+    };
+    let mut data_attrs = String::new();
+    if options.lock_viewport {
+        data_attrs.push_str(" data-lock=\"true\"");
+    }
+    if let Some(url) = &options.refresh_url {
+        let _ = write!(
+            data_attrs,
+            " data-refresh-url=\"{}\" data-refresh-interval=\"{}\"",
+            escape(url),
+            options.refresh_interval_secs.max(1) * 1000
+        );
+    }
+    let _ = writeln!(
+        svg,
+        "<svg id=\"napkin-board\" xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" {} viewBox=\"{} {} {} {}\"{}>",
+        size,
+        fmt(vx),
+        fmt(vy),
+        fmt(vw),
+        fmt(vh),
+        data_attrs,
+    );
+    let _ = writeln!(svg, "<title>{}</title>", escape(&title));
+    let _ = writeln!(
+        svg,
+        "<style>.napkin-shape a {{ cursor: pointer; }} .napkin-match {{ filter: drop-shadow(0 0 6px {}); }}</style>",
+        HIGHLIGHT_COLOR
+    );
+    let _ = writeln!(
+        svg,
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+        fmt(vx - vw * 50.0),
+        fmt(vy - vh * 50.0),
+        fmt(vw * 101.0),
+        fmt(vh * 101.0),
+        escape(&options.background_color),
+    );
+    let _ = writeln!(svg, "<g id=\"napkin-content\">\n{}</g>", render_content(shapes));
+    if options.author_legend {
+        svg.push_str(&author_legend(shapes, vx + 8.0, vy + 8.0));
+    }
+    if let Some(watermark) = &options.watermark {
+        svg.push_str(&watermark.svg(vx, vy, vw, vh));
+    }
+
+    if html {
+        svg.push_str("</svg>\n");
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{title}</title>\n<style>\nhtml, body {{ margin: 0; height: 100%; overflow: hidden; background: {bg}; font-family: {font}; }}\n#napkin-toolbar {{ position: fixed; top: 8px; right: 8px; z-index: 1; }}\n#napkin-search {{ padding: 4px 8px; border: 1px solid #ccc; border-radius: 4px; }}\n</style>\n</head>\n<body>\n<div id=\"napkin-toolbar\"><input id=\"napkin-search\" type=\"search\" placeholder=\"Search board\" aria-label=\"Search board\"></div>\n{svg}<script>\n{script}</script>\n</body>\n</html>\n",
+            title = escape(&title),
+            bg = escape(&options.background_color),
+            font = DEFAULT_FONT_FAMILY,
+            svg = svg,
+            script = INTERACTIVE_SCRIPT,
+        )
+    } else {
+        let _ = write!(svg, "<script><![CDATA[\n{}]]></script>\n</svg>\n", INTERACTIVE_SCRIPT);
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}", svg)
+    }
+}
+
+/// Marker definitions and shape markup without the surrounding document.
+/// Live viewers fetch this to refresh `#napkin-content` in place.
+pub fn render_content(shapes: &[Value]) -> String {
+    let mut writer = SvgWriter::default();
+    for shape in &text_metrics::auto_fitted(shapes) {
+        writer.shape(shape);
+    }
+    format!("{}{}", writer.defs(), writer.body)
+}
+
+const LEGEND_ROW_HEIGHT: f64 = 20.0;
+
+/// A key of the shapes' authors, one colored row each, with its top-left
+/// corner at (`x`, `y`). Empty when no shape is attributed.
+fn author_legend(shapes: &[Value], x: f64, y: f64) -> String {
+    let authors = author::authors_of(shapes);
+    if authors.is_empty() {
+        return String::new();
+    }
+    let rows: Vec<String> = authors
+        .iter()
+        .map(|(a, count)| {
+            let kind = match a.source {
+                AuthorSource::User => "",
+                AuthorSource::Agent => " (agent)",
+            };
+            format!("{}{} \u{2014} {}", a.name, kind, count)
+        })
+        .collect();
+    let width = rows
+        .iter()
+        .map(|r| r.chars().count() as f64 * 7.0)
+        .fold(0.0, f64::max)
+        + 36.0;
+    let height = rows.len() as f64 * LEGEND_ROW_HEIGHT + 12.0;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<g id=\"napkin-legend\" font-family=\"{}\" font-size=\"12\">",
+        DEFAULT_FONT_FAMILY
+    );
+    let _ = writeln!(
+        out,
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"6\" fill=\"#ffffff\" fill-opacity=\"0.9\" stroke=\"#cccccc\"/>",
+        fmt(x),
+        fmt(y),
+        fmt(width),
+        fmt(height)
+    );
+    for (i, ((a, _), row)) in authors.iter().zip(&rows).enumerate() {
+        let cy = y + 6.0 + LEGEND_ROW_HEIGHT * (i as f64 + 0.5);
+        let _ = writeln!(
+            out,
+            "<circle cx=\"{}\" cy=\"{}\" r=\"5\" fill=\"{}\"/><text x=\"{}\" y=\"{}\" dominant-baseline=\"middle\" fill=\"#333333\">{}</text>",
+            fmt(x + 14.0),
+            fmt(cy),
+            escape(&a.color),
+            fmt(x + 26.0),
+            fmt(cy),
+            escape(row)
+        );
+    }
+    out.push_str("</g>\n");
+    out
+}
+
+/// Pan (drag), zoom (wheel), reset (double-click), search, and optional
+/// live refresh. `napkinSearch`
+/// is exposed on `window` so host pages can drive it.
+const INTERACTIVE_SCRIPT: &str = r#"(function () {
+  var svg = document.getElementById('napkin-board');
+  if (!svg) return;
+  var vb = svg.viewBox.baseVal;
+  var home = { x: vb.x, y: vb.y, width: vb.width, height: vb.height };
+  var drag = null;
+  var locked = svg.getAttribute('data-lock') === 'true';
+
+  function toBoard(evt) {
+    var r = svg.getBoundingClientRect();
+    return {
+      x: vb.x + (evt.clientX - r.left) / r.width * vb.width,
+      y: vb.y + (evt.clientY - r.top) / r.height * vb.height
+    };
+  }
+
+  svg.addEventListener('wheel', function (evt) {
+    if (locked) return;
+    evt.preventDefault();
+    var p = toBoard(evt);
+    var k = evt.deltaY < 0 ? 0.9 : 1.1;
+    vb.x = p.x - (p.x - vb.x) * k;
+    vb.y = p.y - (p.y - vb.y) * k;
+    vb.width *= k;
+    vb.height *= k;
+  }, { passive: false });
+
+  svg.addEventListener('pointerdown', function (evt) {
+    if (locked || (evt.target.closest && evt.target.closest('a'))) return;
+    drag = { x: evt.clientX, y: evt.clientY };
+  });
+  window.addEventListener('pointermove', function (evt) {
+    if (!drag) return;
+    var r = svg.getBoundingClientRect();
+    vb.x -= (evt.clientX - drag.x) / r.width * vb.width;
+    vb.y -= (evt.clientY - drag.y) / r.height * vb.height;
+    drag = { x: evt.clientX, y: evt.clientY };
+  });
+  window.addEventListener('pointerup', function () { drag = null; });
+  svg.addEventListener('dblclick', function () {
+    if (locked) return;
+    vb.x = home.x; vb.y = home.y; vb.width = home.width; vb.height = home.height;
+  });
+
+  function search(query) {
+    var q = (query || '').toLowerCase();
+    var shapes = svg.querySelectorAll('.napkin-shape');
+    var first = null;
+    var count = 0;
+    for (var i = 0; i < shapes.length; i++) {
+      var text = (shapes[i].getAttribute('data-text') || '').toLowerCase();
+      var hit = q.length > 0 && text.indexOf(q) !== -1;
+      shapes[i].classList.toggle('napkin-match', hit);
+      if (hit) { count++; if (!first) first = shapes[i]; }
+    }
+    if (first && !locked) {
+      var b = first.getBBox();
+      vb.x = b.x + b.width / 2 - vb.width / 2;
+      vb.y = b.y + b.height / 2 - vb.height / 2;
+    }
+    return count;
+  }
+  window.napkinSearch = search;
+
+  var refreshUrl = svg.getAttribute('data-refresh-url');
+  if (refreshUrl && window.fetch) {
+    var interval = parseInt(svg.getAttribute('data-refresh-interval'), 10) || 5000;
+    setInterval(function () {
+      fetch(refreshUrl).then(function (res) {
+        return res.ok ? res.text() : null;
+      }).then(function (markup) {
+        var content = document.getElementById('napkin-content');
+        if (markup !== null && content) content.innerHTML = markup;
+      }).catch(function () {});
+    }, interval);
+  }
+
+  var input = document.getElementById('napkin-search');
+  if (input) {
+    input.addEventListener('input', function () { search(input.value); });
+  } else {
+    window.addEventListener('keydown', function (evt) {
+      if (evt.key !== '/') return;
+      evt.preventDefault();
+      search(window.prompt('Search board') || '');
+    });
+  }
+})();
+"#;
+
+// --- SVG writer ---
+
+#[derive(Default)]
+struct SvgWriter {
+    body: String,
+    markers: BTreeSet<(String, String)>,
+}
+
+impl SvgWriter {
+    fn defs(&self) -> String {
+        if self.markers.is_empty() {
+            return String::new();
+        }
+        let mut out = String::from("<defs>\n");
+        for (kind, color) in &self.markers {
+            let id = marker_id(kind, color);
+            let c = escape(color);
+            let body = match kind.as_str() {
+                "circle" => format!("<circle cx=\"5\" cy=\"5\" r=\"4\" fill=\"{}\"/>", c),
+                "square" => format!("<rect x=\"1\" y=\"1\" width=\"8\" height=\"8\" fill=\"{}\"/>", c),
+                "diamond" => format!("<path d=\"M5 0 L10 5 L5 10 L0 5 Z\" fill=\"{}\"/>", c),
+                "open-arrow" => format!("<path d=\"M0 0 L10 5 L0 10\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"/>", c),
+                _ => format!("<path d=\"M0 0 L10 5 L0 10 Z\" fill=\"{}\"/>", c),
+            };
+            let _ = writeln!(
+                out,
+                "<marker id=\"{}\" viewBox=\"0 0 10 10\" refX=\"5\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto-start-reverse\">{}</marker>",
+                id, body
+            );
+        }
+        out.push_str("</defs>\n");
+        out
+    }
+
+    fn shape(&mut self, shape: &Value) {
+        let id = str_prop(shape, "id");
+        let text = str_prop(shape, "text");
+        let link = safe_link(str_prop(shape, "link")).unwrap_or("");
+
+        let _ = write!(
+            self.body,
+            "<g class=\"napkin-shape\" data-id=\"{}\" data-text=\"{}\"{}>",
+            escape(id),
+            escape(text),
+            rotation_attr(shape),
+        );
+        if !link.is_empty() {
+            let _ = write!(
+                self.body,
+                "<a href=\"{0}\" xlink:href=\"{0}\" target=\"_blank\">",
+                escape(link)
+            );
+        }
+
+        self.geometry(shape);
+        self.label(shape);
+
+        if !link.is_empty() {
+            self.body.push_str("</a>");
+        }
+        self.body.push_str("</g>\n");
+    }
+
+    fn geometry(&mut self, shape: &Value) {
+        let x = num(shape, "x", 0.0);
+        let y = num(shape, "y", 0.0);
+        let w = num(shape, "width", 0.0);
+        let h = num(shape, "height", 0.0);
+        let paint = paint_attrs(shape);
+
+        match str_prop(shape, "type") {
+            "rectangle" => {
+                let _ = write!(
+                    self.body,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>",
+                    fmt(x), fmt(y), fmt(w), fmt(h), paint
+                );
+            }
+            "sticky" => {
+                let fill = escape(str_or(shape, "stickyColor", "#fff9c4"));
+                let _ = write!(
+                    self.body,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"none\" opacity=\"{}\"/>",
+                    fmt(x), fmt(y), fmt(w), fmt(h), fill, fmt(num(shape, "opacity", 1.0))
+                );
+            }
+            "ellipse" => {
+                let _ = write!(
+                    self.body,
+                    "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" {}/>",
+                    fmt(x + w / 2.0), fmt(y + h / 2.0), fmt(w / 2.0), fmt(h / 2.0), paint
+                );
+            }
+            "triangle" | "diamond" | "hexagon" | "star" => {
+                let pts = polygon_points(shape)
+                    .iter()
+                    .map(|(px, py)| format!("{},{}", fmt(*px), fmt(*py)))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let _ = write!(self.body, "<polygon points=\"{}\" {}/>", pts, paint);
+            }
+            "cylinder" => {
+                let ry = (h * 0.1).min(w / 4.0);
+                let _ = write!(
+                    self.body,
+                    "<path d=\"M{x0} {y0} A{rx} {ry} 0 0 0 {x1} {y0} L{x1} {y1} A{rx} {ry} 0 0 1 {x0} {y1} Z\" {p}/><path d=\"M{x0} {y0} A{rx} {ry} 0 0 0 {x1} {y0}\" fill=\"none\" {s}/>",
+                    x0 = fmt(x),
+                    x1 = fmt(x + w),
+                    y0 = fmt(y + ry),
+                    y1 = fmt(y + h - ry),
+                    rx = fmt(w / 2.0),
+                    ry = fmt(ry),
+                    p = paint,
+                    s = stroke_attrs(shape),
+                );
+            }
+            "cloud" => {
+                let _ = write!(self.body, "<path d=\"{}\" {}/>", cloud_path(x, y, w, h), paint);
+            }
+            "line" | "arrow" => self.connector(shape),
+            "freedraw" => {
+                let pts = points(shape, "points")
+                    .iter()
+                    .map(|(px, py)| format!("{},{}", fmt(*px), fmt(*py)))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let _ = write!(
+                    self.body,
+                    "<polyline points=\"{}\" fill=\"none\" stroke-linecap=\"round\" stroke-linejoin=\"round\" {}/>",
+                    pts,
+                    stroke_attrs(shape)
+                );
+            }
+            "image" => {
+                let src = escape(&assets::inline_src(str_prop(shape, "src")));
+                let _ = write!(
+                    self.body,
+                    "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" href=\"{}\" xlink:href=\"{}\" opacity=\"{}\" preserveAspectRatio=\"none\"/>",
+                    fmt(x), fmt(y), fmt(w), fmt(h), src, src, fmt(num(shape, "opacity", 1.0))
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn connector(&mut self, shape: &Value) {
+        let x1 = num(shape, "x", 0.0);
+        let y1 = num(shape, "y", 0.0);
+        let x2 = num(shape, "x2", x1);
+        let y2 = num(shape, "y2", y1);
+        let control = points(shape, "controlPoints");
+
+        let d = match (str_prop(shape, "routingMode"), control.first()) {
+            ("curved", Some((cx, cy))) => format!(
+                "M{} {} Q{} {} {} {}",
+                fmt(x1), fmt(y1), fmt(*cx), fmt(*cy), fmt(x2), fmt(y2)
+            ),
+            ("elbow", Some(_)) => {
+                let mut d = format!("M{} {}", fmt(x1), fmt(y1));
+                for (px, py) in &control {
+                    let _ = write!(d, " L{} {}", fmt(*px), fmt(*py));
+                }
+                let _ = write!(d, " L{} {}", fmt(x2), fmt(y2));
+                d
+            }
+            _ => format!("M{} {} L{} {}", fmt(x1), fmt(y1), fmt(x2), fmt(y2)),
+        };
+
+        let color = str_or(shape, "strokeColor", "#000000").to_string();
+        let is_arrow = str_prop(shape, "type") == "arrow";
+        let start = endpoint_kind(shape, "startEndpoint", is_arrow && bool_prop(shape, "arrowheadStart"));
+        let end = endpoint_kind(shape, "endEndpoint", is_arrow && bool_prop(shape, "arrowheadEnd"));
+
+        let mut markers = String::new();
+        if let Some(kind) = start {
+            let _ = write!(markers, " marker-start=\"url(#{})\"", marker_id(&kind, &color));
+            self.markers.insert((kind, color.clone()));
+        }
+        if let Some(kind) = end {
+            let _ = write!(markers, " marker-end=\"url(#{})\"", marker_id(&kind, &color));
+            self.markers.insert((kind, color.clone()));
+        }
+
+        let _ = write!(
+            self.body,
+            "<path d=\"{}\" fill=\"none\" {}{}/>",
+            d,
+            stroke_attrs(shape),
+            markers
+        );
+    }
+
+    fn label(&mut self, shape: &Value) {
+        let text = str_prop(shape, "text");
+        if text.is_empty() {
+            return;
+        }
+
+        let shape_type = str_prop(shape, "type");
+        let font_size = num_or(shape, "fontSize", text_metrics::default_font_size(shape_type));
+        let family = escape(str_or(shape, "fontFamily", DEFAULT_FONT_FAMILY));
+        let color = match str_prop(shape, "type") {
+            "sticky" => "#333333",
+            _ => str_or(shape, "strokeColor", "#000000"),
+        };
+
+        let x = num(shape, "x", 0.0);
+        let y = num(shape, "y", 0.0);
+        let (cx, cy, anchor) = match str_prop(shape, "type") {
+            "line" | "arrow" => (
+                (x + num(shape, "x2", x)) / 2.0,
+                (y + num(shape, "y2", y)) / 2.0,
+                "middle",
+            ),
+            "text" => (x, y, "start"),
+            _ => {
+                let w = num(shape, "width", 0.0);
+                let h = num(shape, "height", 0.0);
+                match str_prop(shape, "textAlign") {
+                    "left" => (x + text_metrics::TEXT_PADDING, y + h / 2.0, "start"),
+                    "right" => (x + w - text_metrics::TEXT_PADDING, y + h / 2.0, "end"),
+                    _ => (x + w / 2.0, y + h / 2.0, "middle"),
+                }
+            }
+        };
+
+        // Box shapes wrap inside their padding like the canvas does.
+        let lines: Vec<String> = match str_prop(shape, "type") {
+            "line" | "arrow" | "text" => text.lines().map(str::to_string).collect(),
+            _ => text_metrics::wrap_lines(
+                text,
+                num(shape, "width", 0.0) - text_metrics::TEXT_PADDING * 2.0,
+                font_size,
+                str_or(shape, "fontFamily", "sans-serif"),
+            ),
+        };
+        let line_height = font_size * text_metrics::LINE_HEIGHT;
+        // Text shapes anchor at their top-left; everything else centers the block.
+        let first_y = if str_prop(shape, "type") == "text" {
+            y + font_size
+        } else {
+            cy - line_height * (lines.len() as f64 - 1.0) / 2.0 + font_size * 0.35
+        };
+
+        let _ = write!(
+            self.body,
+            "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"{}\" fill=\"{}\" text-anchor=\"{}\">",
+            fmt(cx),
+            fmt(first_y),
+            fmt(font_size),
+            family,
+            escape(color),
+            anchor,
+        );
+        for (i, line) in lines.iter().enumerate() {
+            let dy = if i == 0 { 0.0 } else { line_height };
+            let _ = write!(
+                self.body,
+                "<tspan x=\"{}\" dy=\"{}\">{}</tspan>",
+                fmt(cx),
+                fmt(dy),
+                escape(line)
+            );
+        }
+        self.body.push_str("</text>");
+    }
+}
+
+// --- Geometry helpers ---
+
+pub(crate) fn polygon_points(shape: &Value) -> Vec<(f64, f64)> {
+    let x = num(shape, "x", 0.0);
+    let y = num(shape, "y", 0.0);
+    let w = num(shape, "width", 0.0);
+    let h = num(shape, "height", 0.0);
+    let cx = x + w / 2.0;
+    let cy = y + h / 2.0;
+
+    match str_prop(shape, "type") {
+        "triangle" => vec![(cx, y), (x, y + h), (x + w, y + h)],
+        "diamond" => vec![(cx, y), (x + w, cy), (cx, y + h), (x, cy)],
+        "hexagon" => (0..6)
+            .map(|i| {
+                let angle = std::f64::consts::PI / 3.0 * i as f64;
+                (cx + w / 2.0 * angle.cos(), cy + h / 2.0 * angle.sin())
+            })
+            .collect(),
+        "star" => {
+            let outer = w.min(h) / 2.0;
+            let inner = outer * 0.4;
+            let aspect = if h != 0.0 { w / h } else { 1.0 };
+            (0..10)
+                .map(|i| {
+                    let angle = std::f64::consts::PI / 5.0 * i as f64 - std::f64::consts::FRAC_PI_2;
+                    let r = if i % 2 == 0 { outer } else { inner };
+                    (cx + r * angle.cos() * aspect, cy + r * angle.sin())
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn cloud_path(x: f64, y: f64, w: f64, h: f64) -> String {
+    // Six bumps around an ellipse, each an arc between neighbouring anchors.
+    let cx = x + w / 2.0;
+    let cy = y + h / 2.0;
+    let anchors: Vec<(f64, f64)> = (0..6)
+        .map(|i| {
+            let angle = std::f64::consts::PI / 3.0 * i as f64 + std::f64::consts::PI / 6.0;
+            (cx + w * 0.4 * angle.cos(), cy + h * 0.35 * angle.sin())
+        })
+        .collect();
+    let r = w.min(h) * 0.28;
+    let mut d = format!("M{} {}", fmt(anchors[0].0), fmt(anchors[0].1));
+    for i in 1..=anchors.len() {
+        let (px, py) = anchors[i % anchors.len()];
+        let _ = write!(d, " A{} {} 0 0 1 {} {}", fmt(r), fmt(r), fmt(px), fmt(py));
+    }
+    d.push_str(" Z");
+    d
+}
+
+fn endpoint_kind(shape: &Value, key: &str, legacy_arrowhead: bool) -> Option<String> {
+    match shape.get(key).and_then(|e| e.get("shape")).and_then(|s| s.as_str()) {
+        Some("none") => None,
+        Some(kind) => Some(kind.to_string()),
+        None if legacy_arrowhead => Some("arrow".to_string()),
+        None => None,
+    }
+}
+
+fn marker_id(kind: &str, color: &str) -> String {
+    let sanitized: String = color
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("napkin-{}-{}", kind, sanitized)
+}
+
+// --- Attribute helpers ---
+
+fn rotation_attr(shape: &Value) -> String {
+    let rotation = geometry::rotation(shape);
+    if rotation == 0.0 {
+        return String::new();
+    }
+    let (cx, cy) = geometry::rotation_center(shape);
+    format!(" transform=\"rotate({} {} {})\"", fmt(rotation), fmt(cx), fmt(cy))
+}
+
+fn stroke_attrs(shape: &Value) -> String {
+    let mut attrs = format!(
+        "stroke=\"{}\" stroke-width=\"{}\" opacity=\"{}\"",
+        escape(str_or(shape, "strokeColor", "#000000")),
+        fmt(num(shape, "strokeWidth", 2.0)),
+        fmt(num(shape, "opacity", 1.0)),
+    );
+    if let Some(dash) = dash_array(str_prop(shape, "strokeStyle")) {
+        let _ = write!(attrs, " stroke-dasharray=\"{}\"", dash);
+    }
+    attrs
+}
+
+fn paint_attrs(shape: &Value) -> String {
+    let fill = str_or(shape, "fillColor", "transparent");
+    let fill = if fill == "transparent" { "none" } else { fill };
+    format!("fill=\"{}\" {}", escape(fill), stroke_attrs(shape))
+}
+
+/// Dash patterns matching `applyStrokeStyle` in `src/lib/canvas/strokeStyles.ts`.
+fn dash_array(style: &str) -> Option<&'static str> {
+    match style {
+        "dashed" => Some("10 5"),
+        "dotted" => Some("2 3"),
+        "dashed-small" => Some("5 3"),
+        "dashed-large" => Some("20 10"),
+        "dash-dot" => Some("10 5 2 5"),
+        "dash-dot-dot" => Some("10 5 2 5 2 5"),
+        _ => None,
+    }
+}
+
+// --- JSON accessors ---
+
+fn num(shape: &Value, key: &str, default: f64) -> f64 {
+    shape.get(key).and_then(|v| v.as_f64()).unwrap_or(default)
+}
+
+/// Like `num`, but treats zero as missing (matches `s.width || 200` in JS).
+fn num_or(shape: &Value, key: &str, default: f64) -> f64 {
+    let v = num(shape, key, 0.0);
+    if v == 0.0 {
+        default
+    } else {
+        v
+    }
+}
+
+fn str_prop<'a>(shape: &'a Value, key: &str) -> &'a str {
+    shape.get(key).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+/// `link` if it is safe to put in an exported page's anchor.
+fn safe_link(link: &str) -> Option<&str> {
+    let link = link.trim();
+    let (scheme, _) = link.split_once(':')?;
+    ["http", "https", "mailto"]
+        .iter()
+        .any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+        .then_some(link)
+}
+
+fn str_or<'a>(shape: &'a Value, key: &str, default: &'a str) -> &'a str {
+    match str_prop(shape, key) {
+        "" => default,
+        s => s,
+    }
+}
+
+fn bool_prop(shape: &Value, key: &str) -> bool {
+    shape.get(key).and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+fn points(shape: &Value, key: &str) -> Vec<(f64, f64)> {
+    shape
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|p| Some((p.get("x")?.as_f64()?, p.get("y")?.as_f64()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Format a coordinate compactly (at most two decimals, no trailing zeros).
+pub(crate) fn fmt(v: f64) -> String {
+    let rounded = (v * 100.0).round() / 100.0;
+    if rounded == rounded.trunc() {
+        format!("{}", rounded as i64)
+    } else {
+        format!("{}", rounded)
+    }
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn content_bounds_includes_margin() {
+        let shapes = vec![json!({"type": "rectangle", "x": 10, "y": 20, "width": 100, "height": 50})];
+        let b = content_bounds(&shapes);
+        assert_eq!(b, Bounds { min_x: -10.0, min_y: 0.0, max_x: 130.0, max_y: 90.0 });
+    }
+
+    #[test]
+    fn content_bounds_covers_line_control_points() {
+        let shapes = vec![json!({
+            "type": "arrow", "x": 0, "y": 0, "x2": 100, "y2": 0,
+            "controlPoints": [{"x": 50, "y": 200}]
+        })];
+        let b = content_bounds(&shapes);
+        assert_eq!(b.max_y, 220.0);
+    }
+
+    #[test]
+    fn content_bounds_uses_rotated_footprint() {
+        let shapes = vec![json!({
+            "type": "rectangle", "x": 0, "y": 0, "width": 200, "height": 100, "rotation": 90
+        })];
+        let b = content_bounds(&shapes);
+        assert!((b.min_y - -70.0).abs() < 1e-9 && (b.max_y - 170.0).abs() < 1e-9);
+        assert!((b.min_x - 30.0).abs() < 1e-9 && (b.max_x - 170.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn interactive_svg_wraps_linked_shapes_in_anchors() {
+        let shapes = vec![json!({
+            "id": "shape_1", "type": "rectangle", "x": 0, "y": 0, "width": 100, "height": 50,
+            "text": "Docs", "link": "https://example.com/?a=1&b=2"
+        })];
+        let svg = render_interactive(&shapes, &InteractiveExportOptions::default());
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.contains("href=\"https://example.com/?a=1&amp;b=2\""));
+        assert!(svg.contains("data-text=\"Docs\""));
+        assert!(svg.contains("<![CDATA["));
+    }
+
+    #[test]
+    fn interactive_export_drops_script_links() {
+        let linked = |link: &str| {
+            vec![json!({
+                "id": "shape_1", "type": "rectangle", "x": 0, "y": 0, "width": 100, "height": 50,
+                "link": link
+            })]
+        };
+        for link in [
+            "javascript:alert(document.cookie)",
+            " JavaScript:alert(1)",
+            "data:text/html,<script>alert(1)</script>",
+            "java\tscript:alert(1)",
+            "/relative/page",
+        ] {
+            let html = render_interactive(
+                &linked(link),
+                &InteractiveExportOptions {
+                    format: Some(InteractiveFormat::Html),
+                    ..Default::default()
+                },
+            );
+            assert!(!html.contains("<a href"), "{}", link);
+        }
+        let svg = render_interactive(
+            &linked("mailto:team@example.com"),
+            &InteractiveExportOptions::default(),
+        );
+        assert!(svg.contains("href=\"mailto:team@example.com\""));
+    }
+
+    #[test]
+    fn interactive_html_has_search_box() {
+        let options = InteractiveExportOptions {
+            format: Some(InteractiveFormat::Html),
+            title: Some("Plan <v2>".to_string()),
+            ..Default::default()
+        };
+        let html = render_interactive(&[], &options);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("id=\"napkin-search\""));
+        assert!(html.contains("<title>Plan &lt;v2&gt;</title>"));
+    }
+
+    #[test]
+    fn watermark_covers_the_exported_framing() {
+        let shapes = vec![json!({ "type": "rectangle", "x": 0, "y": 0, "width": 100, "height": 50 })];
+        let options = InteractiveExportOptions {
+            watermark: Some(Watermark {
+                enabled: true,
+                placement: watermark::Placement::Bottom,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let svg = render_interactive(&shapes, &options);
+        let overlay = &svg[svg.find("napkin-watermark").unwrap()..];
+        assert!(svg.find("napkin-content").unwrap() < svg.find("napkin-watermark").unwrap());
+        // viewBox starts at -60 (content margin plus padding) and is 220 wide.
+        assert!(overlay.contains("<rect x=\"-60\""));
+        assert!(overlay.contains("width=\"220\""));
+        assert!(overlay.contains("CONFIDENTIAL — internal</text>"));
+        assert!(!render_interactive(&shapes, &InteractiveExportOptions::default())
+            .contains("napkin-watermark"));
+    }
+
+    #[test]
+    fn author_legend_lists_each_author_once() {
+        let bot = json!({ "name": "bot", "color": "#8e24aa", "source": "agent" });
+        let shapes = vec![
+            json!({ "id": "a", "type": "rectangle", "x": 0, "y": 0, "width": 10, "height": 10, "createdBy": bot }),
+            json!({ "id": "b", "type": "rectangle", "x": 20, "y": 0, "width": 10, "height": 10, "createdBy": bot }),
+        ];
+        let options = InteractiveExportOptions {
+            author_legend: true,
+            ..Default::default()
+        };
+        let svg = render_interactive(&shapes, &options);
+        assert!(svg.contains("napkin-legend"));
+        assert_eq!(svg.matches("bot (agent) \u{2014} 2").count(), 1);
+        assert!(!render_interactive(&shapes, &InteractiveExportOptions::default())
+            .contains("napkin-legend"));
+    }
+
+    #[test]
+    fn arrows_register_markers_per_color() {
+        let shapes = vec![json!({
+            "id": "a", "type": "arrow", "x": 0, "y": 0, "x2": 10, "y2": 10,
+            "strokeColor": "#ff0000", "arrowheadEnd": true
+        })];
+        let svg = render_interactive(&shapes, &InteractiveExportOptions::default());
+        assert!(svg.contains("marker-end=\"url(#napkin-arrow-_ff0000)\""));
+        assert!(svg.contains("<marker id=\"napkin-arrow-_ff0000\""));
+    }
+}
@@ -0,0 +1,440 @@
+//! Post-processing hooks for exported files.
+//!
+//! `exportPresets` in settings names sets of commands to run on a file once
+//! an export has written it: compress it with optipng, upload it, copy its
+//! path, and so on. An export may name its preset; otherwise it uses the
+//! first preset listing its format (file extension). A preset's hooks run
+//! in order; a failing hook stops the ones after it. Hooks run without a
+//! shell, in the file's directory, with a minimal environment, no stdin and
+//! a timeout, confined by `sandbox.rs`: they may write only in the file's
+//! folder and the temp directory, and reach the network only with
+//! `allowNetwork`. Every outcome is reported to the webview as an
+//! `export-hook-finished` event.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tauri::Emitter;
+
+use crate::sandbox::Sandbox;
+use crate::settings;
+
+const HOOK_EVENT: &str = "export-hook-finished";
+/// Characters of hook output kept for the notification.
+const MAX_OUTPUT_CHARS: usize = 2000;
+/// Variables passed through from Napkin's environment; everything else is
+/// cleared.
+const INHERITED_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USERPROFILE",
+    "SYSTEMROOT",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "LANG",
+];
+
+/// A named set of hooks that exports can choose.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExportPreset {
+    pub name: String,
+    /// Formats (`png`, `svg`, `pdf`, `html`) whose exports use this preset
+    /// when they name none.
+    pub formats: Vec<String>,
+    pub hooks: Vec<ExportHook>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExportHook {
+    pub name: String,
+    pub enabled: bool,
+    /// Program and arguments. `{file}`, `{dir}`, `{name}` and `{format}`
+    /// are replaced with the exported file's path, directory, file name and
+    /// extension.
+    pub command: Vec<String>,
+    pub timeout_secs: u64,
+    /// Let the hook open network connections, to upload the file say.
+    pub allow_network: bool,
+}
+
+impl Default for ExportHook {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            command: Vec::new(),
+            timeout_secs: 30,
+            allow_network: false,
+        }
+    }
+}
+
+/// The preset an export of `format` uses: the one called `name`, or else
+/// the first listing `format`.
+pub fn find_preset<'a>(
+    presets: &'a [ExportPreset],
+    name: Option<&str>,
+    format: &str,
+) -> Result<Option<&'a ExportPreset>, String> {
+    match name {
+        Some(name) => presets
+            .iter()
+            .find(|p| p.name == name)
+            .map(Some)
+            .ok_or_else(|| format!("No export preset named '{}'", name)),
+        None => Ok(presets
+            .iter()
+            .find(|p| p.formats.iter().any(|f| f.eq_ignore_ascii_case(format)))),
+    }
+}
+
+impl ExportHook {
+    fn label(&self) -> &str {
+        match (self.name.trim(), self.command.first()) {
+            ("", Some(program)) => program,
+            (name, _) => name,
+        }
+    }
+
+    fn runs(&self) -> bool {
+        self.enabled && !self.command.is_empty()
+    }
+
+    /// The command line for `path`, placeholders filled in.
+    fn argv(&self, path: &Path) -> Vec<String> {
+        let file = path.display().to_string();
+        let dir = path
+            .parent()
+            .map(|d| d.display().to_string())
+            .unwrap_or_default();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let format = format_of(path);
+        self.command
+            .iter()
+            .map(|arg| {
+                arg.replace("{file}", &file)
+                    .replace("{dir}", &dir)
+                    .replace("{name}", &name)
+                    .replace("{format}", &format)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookOutcome {
+    pub hook: String,
+    pub path: String,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    /// The end of stdout and stderr.
+    pub output: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The export format of `path`: its lowercase extension.
+fn format_of(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// The last `MAX_OUTPUT_CHARS` characters of the hook's output.
+fn tail(stdout: &[u8], stderr: &[u8]) -> String {
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(stdout),
+        String::from_utf8_lossy(stderr)
+    );
+    let text = text.trim();
+    let count = text.chars().count();
+    match text
+        .char_indices()
+        .nth(count.saturating_sub(MAX_OUTPUT_CHARS))
+    {
+        Some((start, _)) if start > 0 => format!("…{}", &text[start..]),
+        _ => text.to_string(),
+    }
+}
+
+/// A command for `program` with only `INHERITED_ENV` set, no stdin and
+/// piped output, killed when dropped. On its own it is not a sandbox: the
+/// program can do anything the user can. Hooks are also confined by
+/// `Sandbox`; snippet scripts and the translation command are not.
+pub(crate) fn restricted_command(program: &str, args: &[String]) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new(program);
+    command
+        .args(args)
+        .env_clear()
+        .envs(
+            INHERITED_ENV
+                .iter()
+                .filter_map(|k| Some((k, std::env::var_os(k)?))),
+        )
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    command
+}
+
+/// Run one hook on `path` and wait for it, up to its timeout.
+pub async fn run_hook(hook: &ExportHook, path: &Path) -> HookOutcome {
+    let mut outcome = HookOutcome {
+        hook: hook.label().to_string(),
+        path: path.display().to_string(),
+        success: false,
+        exit_code: None,
+        output: String::new(),
+        error: None,
+    };
+    let argv = hook.argv(path);
+    let Some((program, args)) = argv.split_first() else {
+        outcome.error = Some("The hook has no command".to_string());
+        return outcome;
+    };
+
+    let sandbox = Sandbox {
+        writable: path.parent().map(Path::to_path_buf).into_iter().collect(),
+        network: hook.allow_network,
+    };
+    let mut command = match sandbox.command(program, args) {
+        Ok(command) => command,
+        Err(e) => {
+            outcome.error = Some(e);
+            return outcome;
+        }
+    };
+    command
+        .env("NAPKIN_EXPORT_FILE", path)
+        .env("NAPKIN_EXPORT_FORMAT", format_of(path));
+    if let Some(dir) = path.parent().filter(|d| d.is_dir()) {
+        command.current_dir(dir);
+    }
+    // Held until the hook is done, or dropped with it on a timeout.
+    let (child, _guard) = match Sandbox::spawn(&mut command) {
+        Ok(started) => started,
+        Err(e) => {
+            outcome.error = Some(format!("Failed to start {}: {}", program, e));
+            return outcome;
+        }
+    };
+
+    let timeout = Duration::from_secs(hook.timeout_secs.max(1));
+    match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) => {
+            outcome.success = output.status.success();
+            outcome.exit_code = output.status.code();
+            outcome.output = tail(&output.stdout, &output.stderr);
+            if !outcome.success {
+                outcome.error = Some(match output.status.code() {
+                    Some(code) => format!("{} exited with status {}", program, code),
+                    None => format!("{} was terminated", program),
+                });
+            }
+        }
+        Ok(Err(e)) => outcome.error = Some(format!("Failed to run {}: {}", program, e)),
+        // Dropping the future kills the child.
+        Err(_) => {
+            outcome.error = Some(format!(
+                "{} timed out after {}s",
+                program,
+                timeout.as_secs()
+            ))
+        }
+    }
+    outcome
+}
+
+/// Run the enabled `hooks` on `path` in order, stopping at the first
+/// failure.
+pub async fn run_hooks(hooks: &[ExportHook], path: &Path) -> Vec<HookOutcome> {
+    let mut outcomes = Vec::new();
+    for hook in hooks.iter().filter(|h| h.runs()) {
+        let outcome = run_hook(hook, path).await;
+        let failed = !outcome.success;
+        outcomes.push(outcome);
+        if failed {
+            break;
+        }
+    }
+    outcomes
+}
+
+/// Run the hooks of `preset`, or of the preset for the file's format, on a
+/// freshly exported file in the background, reporting each outcome to the
+/// webview.
+pub fn after_export(app: &tauri::AppHandle, path: impl Into<PathBuf>, preset: Option<&str>) {
+    let path = path.into();
+    let presets = settings::current(app).export_presets;
+    let hooks = match find_preset(&presets, preset, &format_of(&path)) {
+        Ok(Some(preset)) => preset.hooks.clone(),
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("Not running export hooks on {}: {}", path.display(), e);
+            let outcome = HookOutcome {
+                hook: preset.unwrap_or_default().to_string(),
+                path: path.display().to_string(),
+                success: false,
+                exit_code: None,
+                output: String::new(),
+                error: Some(e),
+            };
+            let _ = app.emit(HOOK_EVENT, &outcome);
+            return;
+        }
+    };
+    if hooks.is_empty() {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        for outcome in run_hooks(&hooks, &path).await {
+            match &outcome.error {
+                Some(e) => log::warn!("Export hook {} failed: {}", outcome.hook, e),
+                None => log::info!("Export hook {} ran on {}", outcome.hook, outcome.path),
+            }
+            let _ = app.emit(HOOK_EVENT, &outcome);
+        }
+    });
+}
+
+// --- Tauri command ---
+
+/// Run the export hooks on a file the webview exported itself (PNG, SVG).
+#[tauri::command]
+pub fn run_export_hooks(path: String, preset: Option<String>, app: tauri::AppHandle) {
+    after_export(&app, path, preset.as_deref());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(command: &[&str]) -> ExportHook {
+        ExportHook {
+            command: command.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn placeholders() {
+        let mut optipng = hook(&["optipng", "-o2", "{file}", "--dir={dir}", "{name}.{format}"]);
+        let path = Path::new("/exports/board.PNG");
+        assert_eq!(
+            optipng.argv(path),
+            vec![
+                "optipng",
+                "-o2",
+                "/exports/board.PNG",
+                "--dir=/exports",
+                "board.PNG.png"
+            ]
+        );
+        assert_eq!(optipng.label(), "optipng");
+        assert!(optipng.runs());
+        optipng.enabled = false;
+        assert!(!optipng.runs());
+        assert!(!hook(&[]).runs());
+    }
+
+    #[test]
+    fn exports_use_the_named_preset_or_their_formats() {
+        let preset = |name: &str, formats: &[&str]| ExportPreset {
+            name: name.to_string(),
+            formats: formats.iter().map(|f| f.to_string()).collect(),
+            hooks: vec![hook(&[name])],
+        };
+        let presets = vec![
+            preset("web", &["PNG", "svg"]),
+            preset("print", &["pdf"]),
+            preset("upload", &[]),
+        ];
+        let name =
+            |name, format| find_preset(&presets, name, format).map(|p| p.map(|p| p.name.as_str()));
+        assert_eq!(name(None, "png"), Ok(Some("web")));
+        assert_eq!(name(None, "pdf"), Ok(Some("print")));
+        assert_eq!(name(None, "html"), Ok(None));
+        assert_eq!(name(Some("upload"), "png"), Ok(Some("upload")));
+        assert_eq!(name(Some("print"), "png"), Ok(Some("print")));
+        assert!(name(Some("missing"), "png").is_err());
+    }
+
+    #[test]
+    fn output_is_cut_from_the_front() {
+        assert_eq!(tail(b"ok\n", b""), "ok");
+        let long = "x".repeat(MAX_OUTPUT_CHARS + 10);
+        let cut = tail(long.as_bytes(), b"done");
+        assert!(cut.starts_with('…'));
+        assert!(cut.ends_with("done"));
+        assert_eq!(cut.chars().count(), MAX_OUTPUT_CHARS + 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hooks_run_in_order_until_one_fails() {
+        let dir = std::env::temp_dir().join(format!("napkin-hooks-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("board.png");
+        std::fs::write(&path, b"png").unwrap();
+        std::env::set_var("NAPKIN_HOOK_SECRET", "leak");
+
+        let hooks = vec![
+            hook(&[
+                "sh",
+                "-c",
+                "echo \"$NAPKIN_EXPORT_FORMAT $(basename \"$PWD\") ${NAPKIN_HOOK_SECRET:-none}\"",
+            ]),
+            ExportHook {
+                enabled: false,
+                ..hook(&["false"])
+            },
+            hook(&["sh", "-c", "echo oops >&2; exit 3"]),
+            hook(&["touch", "{file}.never"]),
+        ];
+        let outcomes = run_hooks(&hooks, &path).await;
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes[0].success);
+        let expected = format!("png {} none", dir.file_name().unwrap().to_string_lossy());
+        assert_eq!(outcomes[0].output, expected);
+        assert!(!outcomes[1].success);
+        assert_eq!(outcomes[1].exit_code, Some(3));
+        assert_eq!(outcomes[1].output, "oops");
+        assert!(!dir.join("board.png.never").exists());
+
+        let slow = ExportHook {
+            timeout_secs: 1,
+            ..hook(&["sleep", "5"])
+        };
+        let outcome = run_hook(&slow, &path).await;
+        assert!(!outcome.success);
+        assert_eq!(outcome.error.as_deref(), Some("sleep timed out after 1s"));
+
+        let missing = run_hook(&hook(&["napkin-no-such-program"]), &path).await;
+        assert!(missing.error.unwrap().starts_with("Failed to start"));
+
+        // Hooks write next to the export, not elsewhere.
+        let copy = run_hook(&hook(&["cp", "{file}", "{file}.bak"]), &path).await;
+        assert!(copy.success);
+        #[cfg(target_os = "linux")]
+        {
+            let outside = std::env::current_dir()
+                .unwrap()
+                .join(format!("napkin-hook-{}", uuid::Uuid::new_v4()));
+            let escape = hook(&["cp", "{file}", &outside.display().to_string()]);
+            assert!(!run_hook(&escape, &path).await.success);
+            assert!(!outside.exists());
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
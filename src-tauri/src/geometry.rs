@@ -0,0 +1,478 @@
+//! Rotation-aware shape geometry.
+//!
+//! Shapes rotate about the centre of their unrotated box (lines and arrows
+//! about the midpoint of their endpoints), matching the canvas renderer and
+//! the SVG exporter. Everything that needs a shape's footprint — export
+//! bounds, constraint alignment, hit testing — should go through here rather
+//! than reading `x`/`width` directly. The webview mirror lives in
+//! `src/lib/shapes/transform.ts`.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Axis-aligned bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bounds {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Bounds {
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+
+    /// Smallest bounds containing all `points`, or `None` when empty.
+    pub fn from_points(points: impl IntoIterator<Item = (f64, f64)>) -> Option<Bounds> {
+        points.into_iter().fold(None, |acc, (x, y)| {
+            Some(match acc {
+                None => Bounds {
+                    min_x: x,
+                    min_y: y,
+                    max_x: x,
+                    max_y: y,
+                },
+                Some(b) => Bounds {
+                    min_x: b.min_x.min(x),
+                    min_y: b.min_y.min(y),
+                    max_x: b.max_x.max(x),
+                    max_y: b.max_y.max(y),
+                },
+            })
+        })
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    /// Whether the two overlap (touching edges don't count).
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.min_x < other.max_x
+            && other.min_x < self.max_x
+            && self.min_y < other.max_y
+            && other.min_y < self.max_y
+    }
+
+    pub fn expand(&self, margin: f64) -> Bounds {
+        Bounds {
+            min_x: self.min_x - margin,
+            min_y: self.min_y - margin,
+            max_x: self.max_x + margin,
+            max_y: self.max_y + margin,
+        }
+    }
+}
+
+/// Oriented bounding box: an unrotated box turned `rotation` degrees about
+/// its centre.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Obb {
+    pub cx: f64,
+    pub cy: f64,
+    pub half_width: f64,
+    pub half_height: f64,
+    pub rotation: f64,
+}
+
+impl Obb {
+    /// Corners in order top-left, top-right, bottom-right, bottom-left
+    /// (before rotation).
+    pub fn corners(&self) -> [(f64, f64); 4] {
+        let (hw, hh) = (self.half_width, self.half_height);
+        [(-hw, -hh), (hw, -hh), (hw, hh), (-hw, hh)].map(|(dx, dy)| {
+            rotate_point(
+                (self.cx + dx, self.cy + dy),
+                (self.cx, self.cy),
+                self.rotation,
+            )
+        })
+    }
+
+    pub fn aabb(&self) -> Bounds {
+        Bounds::from_points(self.corners()).expect("four corners")
+    }
+
+    pub fn contains(&self, p: (f64, f64)) -> bool {
+        let (lx, ly) = rotate_point(p, (self.cx, self.cy), -self.rotation);
+        (lx - self.cx).abs() <= self.half_width && (ly - self.cy).abs() <= self.half_height
+    }
+}
+
+/// Rotate `p` by `degrees` (clockwise on screen, y down) about `center`.
+pub fn rotate_point(p: (f64, f64), center: (f64, f64), degrees: f64) -> (f64, f64) {
+    if degrees == 0.0 {
+        return p;
+    }
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (dx, dy) = (p.0 - center.0, p.1 - center.1);
+    (
+        center.0 + dx * cos - dy * sin,
+        center.1 + dx * sin + dy * cos,
+    )
+}
+
+fn num(shape: &Value, key: &str, default: f64) -> f64 {
+    shape.get(key).and_then(|v| v.as_f64()).unwrap_or(default)
+}
+
+fn shape_type(shape: &Value) -> &str {
+    shape.get("type").and_then(|v| v.as_str()).unwrap_or("")
+}
+
+fn points(shape: &Value, key: &str) -> Vec<(f64, f64)> {
+    shape
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|p| Some((p.get("x")?.as_f64()?, p.get("y")?.as_f64()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn rotation(shape: &Value) -> f64 {
+    num(shape, "rotation", 0.0)
+}
+
+/// Width and height of a box shape. Text without a stored size gets the
+/// editor's defaults.
+fn box_size(shape: &Value) -> (f64, f64) {
+    let positive = |key: &str, default: f64| {
+        Some(num(shape, key, default))
+            .filter(|v| *v > 0.0)
+            .unwrap_or(default)
+    };
+    match shape_type(shape) {
+        "text" => (positive("width", 200.0), positive("height", 30.0)),
+        _ => (num(shape, "width", 0.0), num(shape, "height", 0.0)),
+    }
+}
+
+/// Outline points of a shape before rotation is applied.
+pub fn local_points(shape: &Value) -> Vec<(f64, f64)> {
+    let x = num(shape, "x", 0.0);
+    let y = num(shape, "y", 0.0);
+    match shape_type(shape) {
+        "line" | "arrow" => {
+            let mut pts = vec![(x, y), (num(shape, "x2", x), num(shape, "y2", y))];
+            pts.extend(points(shape, "controlPoints"));
+            pts
+        }
+        "freedraw" => points(shape, "points"),
+        _ => {
+            let (w, h) = box_size(shape);
+            vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h)]
+        }
+    }
+}
+
+/// The point a shape rotates about.
+pub fn rotation_center(shape: &Value) -> (f64, f64) {
+    let x = num(shape, "x", 0.0);
+    let y = num(shape, "y", 0.0);
+    match shape_type(shape) {
+        "line" | "arrow" => (
+            (x + num(shape, "x2", x)) / 2.0,
+            (y + num(shape, "y2", y)) / 2.0,
+        ),
+        "freedraw" => Bounds::from_points(points(shape, "points"))
+            .map(|b| ((b.min_x + b.max_x) / 2.0, (b.min_y + b.max_y) / 2.0))
+            .unwrap_or((x, y)),
+        _ => {
+            let (w, h) = box_size(shape);
+            (x + w / 2.0, y + h / 2.0)
+        }
+    }
+}
+
+/// Outline points with rotation applied, in canvas coordinates.
+pub fn world_points(shape: &Value) -> Vec<(f64, f64)> {
+    let angle = rotation(shape);
+    let center = rotation_center(shape);
+    local_points(shape)
+        .into_iter()
+        .map(|p| rotate_point(p, center, angle))
+        .collect()
+}
+
+/// Axis-aligned bounds of the rotated shape (no stroke margin).
+pub fn shape_bounds(shape: &Value) -> Option<Bounds> {
+    Bounds::from_points(world_points(shape))
+}
+
+/// Oriented box around the shape's unrotated outline.
+pub fn shape_obb(shape: &Value) -> Option<Obb> {
+    let local = Bounds::from_points(local_points(shape))?;
+    // Curved connectors don't rotate about their box centre, so carry the
+    // centre round the shape's pivot.
+    let (cx, cy) = rotate_point(
+        (
+            (local.min_x + local.max_x) / 2.0,
+            (local.min_y + local.max_y) / 2.0,
+        ),
+        rotation_center(shape),
+        rotation(shape),
+    );
+    Some(Obb {
+        cx,
+        cy,
+        half_width: local.width() / 2.0,
+        half_height: local.height() / 2.0,
+        rotation: rotation(shape),
+    })
+}
+
+/// Distance from `p` to the segment from `a` to `b`.
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_sq).clamp(0.0, 1.0)
+    };
+    let (nx, ny) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - nx).powi(2) + (p.1 - ny).powi(2)).sqrt()
+}
+
+/// Whether the canvas point `p` is on `shape`: inside a closed shape (an
+/// ellipse or diamond by its outline, other shapes by their box), or within
+/// `tolerance` plus half the stroke width of a line, connector or freehand
+/// stroke. Curved connectors are taken as straight runs through their
+/// control points.
+pub fn hits(shape: &Value, p: (f64, f64), tolerance: f64) -> bool {
+    let kind = shape_type(shape);
+    if matches!(kind, "line" | "arrow" | "freedraw") {
+        let mut path = world_points(shape);
+        if kind != "freedraw" && path.len() > 2 {
+            // Start, control points, end
+            let end = path.remove(1);
+            path.push(end);
+        }
+        let reach = tolerance + num(shape, "strokeWidth", 2.0) / 2.0;
+        return match path.as_slice() {
+            [] => false,
+            [only] => segment_distance(p, *only, *only) <= reach,
+            _ => path
+                .windows(2)
+                .any(|w| segment_distance(p, w[0], w[1]) <= reach),
+        };
+    }
+    let Some(obb) = shape_obb(shape) else {
+        return false;
+    };
+    let (lx, ly) = rotate_point(p, (obb.cx, obb.cy), -obb.rotation);
+    let (dx, dy) = ((lx - obb.cx).abs(), (ly - obb.cy).abs());
+    let (hw, hh) = (obb.half_width, obb.half_height);
+    if dx > hw || dy > hh {
+        return false;
+    }
+    match kind {
+        "ellipse" => (dx / hw).powi(2) + (dy / hh).powi(2) <= 1.0,
+        "diamond" => dx / hw + dy / hh <= 1.0,
+        _ => true,
+    }
+}
+
+/// Union of the rotated bounds of all shapes.
+pub fn combined_bounds(shapes: &[Value]) -> Option<Bounds> {
+    shapes
+        .iter()
+        .filter_map(shape_bounds)
+        .reduce(|a, b| a.union(&b))
+}
+
+// --- Tauri command ---
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShapeGeometry {
+    pub id: String,
+    pub bounds: Bounds,
+    pub obb: Obb,
+    pub corners: [(f64, f64); 4],
+}
+
+/// Rotation-aware bounds for each shape, for callers (alignment, measuring)
+/// that want the visual footprint rather than the stored box.
+#[tauri::command]
+pub fn shape_geometry(shapes: Vec<Value>) -> Vec<ShapeGeometry> {
+    shapes
+        .iter()
+        .filter_map(|shape| {
+            let id = shape.get("id")?.as_str()?.to_string();
+            let obb = shape_obb(shape)?;
+            Some(ShapeGeometry {
+                id,
+                bounds: shape_bounds(shape)?,
+                obb,
+                corners: obb.corners(),
+            })
+        })
+        .collect()
+}
+
+/// IDs of shapes whose oriented box contains the canvas point, topmost
+/// first. A coarse, rotation-aware pre-filter for precise hit testing.
+#[tauri::command]
+pub fn hit_test_shapes(shapes: Vec<Value>, x: f64, y: f64) -> Vec<String> {
+    shapes
+        .iter()
+        .rev()
+        .filter(|shape| shape_obb(shape).is_some_and(|obb| obb.contains((x, y))))
+        .filter_map(|shape| Some(shape.get("id")?.as_str()?.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    fn rect(x: f64, y: f64, w: f64, h: f64, rotation: f64) -> Value {
+        json!({ "id": "r", "type": "rectangle", "x": x, "y": y, "width": w, "height": h, "rotation": rotation })
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6 * (1.0 + a.abs().max(b.abs()))
+    }
+
+    #[test]
+    fn quarter_turn_swaps_extents() {
+        let b = shape_bounds(&rect(0.0, 0.0, 200.0, 100.0, 90.0)).unwrap();
+        assert!(close(b.min_x, 50.0) && close(b.max_x, 150.0));
+        assert!(close(b.min_y, -50.0) && close(b.max_y, 150.0));
+    }
+
+    #[test]
+    fn lines_rotate_about_their_midpoint() {
+        let line = json!({ "type": "line", "x": 0, "y": 0, "x2": 100, "y2": 0, "rotation": 90 });
+        let b = shape_bounds(&line).unwrap();
+        assert!(close(b.min_x, 50.0) && close(b.max_x, 50.0));
+        assert!(close(b.min_y, -50.0) && close(b.max_y, 50.0));
+    }
+
+    #[test]
+    fn curved_connector_obb_follows_its_pivot() {
+        let arrow = json!({
+            "type": "arrow", "x": 0, "y": 0, "x2": 100, "y2": 0,
+            "controlPoints": [{ "x": 50, "y": 100 }], "rotation": 180
+        });
+        let obb = shape_obb(&arrow).unwrap();
+        assert!(close(obb.cx, 50.0) && close(obb.cy, -50.0));
+        let b = shape_bounds(&arrow).unwrap();
+        assert_eq!(obb.aabb().min_y.round(), b.min_y.round());
+    }
+
+    #[test]
+    fn hit_test_returns_topmost_first() {
+        let mut rotated = rect(0.0, 0.0, 200.0, 20.0, 45.0);
+        rotated["id"] = "rotated".into();
+        let shapes = vec![rect(90.0, 0.0, 20.0, 20.0, 0.0), rotated];
+        assert_eq!(
+            hit_test_shapes(shapes.clone(), 100.0, 10.0),
+            vec!["rotated", "r"]
+        );
+        assert!(hit_test_shapes(shapes, 195.0, 5.0).is_empty());
+    }
+
+    #[test]
+    fn hits_follow_each_outline() {
+        let ellipse = json!({ "type": "ellipse", "x": 0, "y": 0, "width": 100, "height": 100 });
+        assert!(hits(&ellipse, (50.0, 50.0), 0.0));
+        assert!(!hits(&ellipse, (5.0, 5.0), 0.0));
+        let diamond = json!({ "type": "diamond", "x": 0, "y": 0, "width": 100, "height": 100 });
+        assert!(!hits(&diamond, (10.0, 10.0), 0.0));
+        assert!(hits(&diamond, (50.0, 10.0), 0.0));
+        assert!(hits(&rect(0.0, 0.0, 200.0, 20.0, 45.0), (100.0, 10.0), 0.0));
+        assert!(!hits(&rect(0.0, 0.0, 200.0, 20.0, 45.0), (195.0, 5.0), 0.0));
+
+        let arrow = json!({ "type": "arrow", "x": 0, "y": 0, "x2": 100, "y2": 0, "controlPoints": [{ "x": 50, "y": 50 }] });
+        assert!(hits(&arrow, (50.0, 49.5), 0.0));
+        assert!(!hits(&arrow, (50.0, 0.0), 4.0));
+        let line = json!({ "type": "line", "x": 0, "y": 0, "x2": 100, "y2": 0 });
+        assert!(hits(&line, (50.0, 4.0), 4.0));
+        assert!(!hits(&line, (50.0, 10.0), 4.0));
+    }
+
+    #[test]
+    fn obb_hit_test_respects_rotation() {
+        let obb = shape_obb(&rect(0.0, 0.0, 200.0, 20.0, 45.0)).unwrap();
+        // Centre is always inside; the unrotated box's far corner no longer is.
+        assert!(obb.contains((100.0, 10.0)));
+        assert!(!obb.contains((195.0, 5.0)));
+    }
+
+    proptest! {
+        #[test]
+        fn rotation_round_trips(
+            px in -1e4..1e4f64, py in -1e4..1e4f64,
+            cx in -1e4..1e4f64, cy in -1e4..1e4f64,
+            angle in -720.0..720.0f64,
+        ) {
+            let (rx, ry) = rotate_point((px, py), (cx, cy), angle);
+            let (bx, by) = rotate_point((rx, ry), (cx, cy), -angle);
+            prop_assert!((bx - px).abs() < 1e-6 && (by - py).abs() < 1e-6);
+        }
+
+        #[test]
+        fn rotation_preserves_distance_to_center(
+            px in -1e4..1e4f64, py in -1e4..1e4f64, angle in -360.0..360.0f64,
+        ) {
+            let (rx, ry) = rotate_point((px, py), (0.0, 0.0), angle);
+            let before = (px * px + py * py).sqrt();
+            let after = (rx * rx + ry * ry).sqrt();
+            prop_assert!((before - after).abs() < 1e-6 * (1.0 + before));
+        }
+
+        #[test]
+        fn rotated_bounds_contain_all_corners_and_keep_center(
+            x in -1e3..1e3f64, y in -1e3..1e3f64,
+            w in 0.0..1e3f64, h in 0.0..1e3f64, angle in 0.0..360.0f64,
+        ) {
+            let shape = rect(x, y, w, h, angle);
+            let b = shape_bounds(&shape).unwrap();
+            let obb = shape_obb(&shape).unwrap();
+            let outer = b.expand(1e-6);
+            for (cx, cy) in obb.corners() {
+                prop_assert!(cx >= outer.min_x && cx <= outer.max_x);
+                prop_assert!(cy >= outer.min_y && cy <= outer.max_y);
+            }
+            prop_assert!(close((b.min_x + b.max_x) / 2.0, x + w / 2.0));
+            prop_assert!(close((b.min_y + b.max_y) / 2.0, y + h / 2.0));
+            // A rotated box never has a smaller AABB than its narrow side.
+            prop_assert!(b.width() + 1e-6 >= w.min(h) && b.height() + 1e-6 >= w.min(h));
+        }
+
+        #[test]
+        fn obb_contains_agrees_with_unrotated_box(
+            w in 1.0..500.0f64, h in 1.0..500.0f64, angle in 0.0..360.0f64,
+            px in -600.0..600.0f64, py in -600.0..600.0f64,
+        ) {
+            let shape = rect(0.0, 0.0, w, h, angle);
+            let obb = shape_obb(&shape).unwrap();
+            let (lx, ly) = rotate_point((px, py), (w / 2.0, h / 2.0), -angle);
+            let inside_local = (0.0..=w).contains(&lx) && (0.0..=h).contains(&ly);
+            // Skip points sitting right on the edge where rounding decides.
+            let margin = lx.abs().min((lx - w).abs()).min(ly.abs()).min((ly - h).abs());
+            prop_assume!(margin > 1e-6);
+            prop_assert_eq!(obb.contains((px, py)), inside_local);
+        }
+    }
+}
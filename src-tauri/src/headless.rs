@@ -0,0 +1,1073 @@
+//! Headless MCP server (`napkin --headless --mcp-port 21420 board.napkin`).
+//!
+//! For boards edited by agents on a machine without a display. No window
+//! or webview is created: the document is loaded into [`Board`], a model of
+//! the collection kept in Rust, and the core canvas tools (shapes,
+//! connections, tabs, z-order, batches and a native `export_png`) are
+//! answered from it over the same `/mcp` endpoint, with the same arguments,
+//! results and error codes as the app. Every change is saved back to the
+//! file at once, in its own storage format. Tools that need the app's
+//! windows, history or settings are not offered.
+//!
+//! The server listens on loopback unless `--mcp-bind` names another
+//! address. A bearer token is required when `NAPKIN_MCP_TOKEN` is set, and
+//! always when listening beyond loopback. Tabs hidden from agents stay
+//! hidden.
+
+use axum::{
+    body::Bytes,
+    extract::{Request, State as AxumState},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::api::{
+    self, mcp_error, mcp_result, DEFAULT_PORT, MCP_PROTOCOL_VERSION, MCP_SERVER_NAME,
+    MCP_SERVER_VERSION,
+};
+use crate::auth;
+use crate::author;
+use crate::bind;
+use crate::docstore;
+use crate::geometry::{self, Bounds};
+use crate::hosts;
+use crate::model;
+use crate::render;
+use crate::session::SESSION_HEADER;
+use crate::structured;
+use crate::tls;
+use crate::tool_errors;
+
+pub(crate) const HEADLESS_FLAG: &str = "--headless";
+const TOKEN_ENV: &str = "NAPKIN_MCP_TOKEN";
+
+/// The tools a [`Board`] answers; the rest need the app.
+const HEADLESS_TOOLS: &[&str] = &[
+    "get_canvas",
+    "list_shapes",
+    "get_shape",
+    "create_shape",
+    "update_shape",
+    "delete_shape",
+    "create_connection",
+    "list_tabs",
+    "create_tab",
+    "switch_tab",
+    "rename_tab",
+    "bring_to_front",
+    "send_to_back",
+    "clear_canvas",
+    "batch_operations",
+    "export_png",
+];
+
+/// Shape properties `update_shape` may change, as in the webview.
+const UPDATABLE: &[&str] = &[
+    "x",
+    "y",
+    "width",
+    "height",
+    "x2",
+    "y2",
+    "strokeColor",
+    "strokeWidth",
+    "strokeStyle",
+    "fillColor",
+    "fillStyle",
+    "opacity",
+    "roughness",
+    "rotation",
+    "text",
+    "textAlign",
+    "verticalAlign",
+    "fontSize",
+    "fontFamily",
+    "stickyColor",
+    "routingMode",
+    "labelPosition",
+    "autoFit",
+];
+
+const USAGE: &str = "\
+Usage: napkin --headless <document> [--mcp-port <port>] [--mcp-bind <address>]
+
+  --mcp-port  port to serve MCP on (default 21420)
+  --mcp-bind  address to listen on (default 127.0.0.1); needs NAPKIN_MCP_TOKEN";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    document: PathBuf,
+    port: u16,
+    bind: IpAddr,
+}
+
+/// `args` are the process's, without the program name.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut document = None;
+    let mut port = DEFAULT_PORT;
+    let mut bind = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match arg.as_str() {
+            HEADLESS_FLAG => {}
+            "--mcp-port" => {
+                let text = value(arg)?;
+                port = text
+                    .parse()
+                    .map_err(|_| format!("--mcp-port must be a port number, not '{}'", text))?;
+            }
+            "--mcp-bind" => {
+                let text = value(arg)?;
+                bind = text
+                    .parse()
+                    .map_err(|_| format!("--mcp-bind must be an IP address, not '{}'", text))?;
+            }
+            flag if flag.starts_with('-') => return Err(format!("Unknown option {}", flag)),
+            path if document.is_none() => document = Some(PathBuf::from(path)),
+            extra => return Err(format!("Unexpected argument '{}'", extra)),
+        }
+    }
+    let document = document.ok_or("Which document? Give the path of a .napkin file")?;
+    Ok(Options {
+        document,
+        port,
+        bind,
+    })
+}
+
+// --- Document model ---
+
+/// A document held in memory while agents edit it.
+pub(crate) struct Board {
+    path: PathBuf,
+    collection: Map<String, Value>,
+    /// The tab tools act on, like the webview's MCP tab cursor.
+    active: usize,
+    /// Bumped by every change, for `get_canvas`'s `sinceRevision`.
+    revision: u64,
+    next_id: u64,
+}
+
+fn tab_id(index: usize) -> String {
+    format!("tab-{}", index + 1)
+}
+
+fn shape_not_found(id: &str) -> String {
+    format!("Shape not found: {}", id)
+}
+
+fn arg_str<'a>(arguments: &'a Value, key: &str) -> Result<&'a str, String> {
+    arguments
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Missing required field: {}", key))
+}
+
+impl Board {
+    pub(crate) fn open(path: PathBuf) -> Result<Self, String> {
+        let json = docstore::read_path(&path)?;
+        let value =
+            serde_json::from_str(&json).map_err(|e| format!("Invalid document JSON: {}", e))?;
+        let collection = docstore::as_collection(value)?;
+        let mut board = Self {
+            path,
+            collection,
+            active: 0,
+            revision: 0,
+            next_id: 0,
+        };
+        let len = board.documents().len();
+        if len == 0 {
+            return Err("The document has no boards".to_string());
+        }
+        let saved = board.collection["activeDocumentIndex"]
+            .as_u64()
+            .map_or(0, |i| i.min(len as u64 - 1) as usize);
+        board.active = (saved..len)
+            .chain(0..saved)
+            .find(|&i| !board.is_hidden(i))
+            .unwrap_or(saved);
+        Ok(board)
+    }
+
+    fn documents(&self) -> &[Value] {
+        self.collection
+            .get("documents")
+            .and_then(Value::as_array)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    fn documents_mut(&mut self) -> &mut Vec<Value> {
+        let documents = self
+            .collection
+            .entry("documents")
+            .or_insert_with(|| json!([]));
+        if !documents.is_array() {
+            *documents = json!([]);
+        }
+        documents.as_array_mut().expect("documents is an array")
+    }
+
+    fn is_hidden(&self, index: usize) -> bool {
+        self.documents()
+            .get(index)
+            .is_some_and(|doc| doc["metadata"]["hiddenFromApi"] == true)
+    }
+
+    fn title(&self, index: usize) -> &str {
+        self.documents()
+            .get(index)
+            .and_then(|doc| doc["metadata"]["title"].as_str())
+            .unwrap_or("Untitled")
+    }
+
+    /// The active tab, unless it is hidden from agents.
+    fn document(&self) -> Result<&Value, String> {
+        if self.is_hidden(self.active) {
+            return Err("Refused: the board is hidden from agents".to_string());
+        }
+        self.documents()
+            .get(self.active)
+            .ok_or_else(|| "The active board no longer exists".to_string())
+    }
+
+    fn shapes(&self) -> Result<&[Value], String> {
+        Ok(self.document()?["shapes"]
+            .as_array()
+            .map_or(&[], Vec::as_slice))
+    }
+
+    fn shapes_mut(&mut self) -> Result<&mut Vec<Value>, String> {
+        self.document()?;
+        let active = self.active;
+        let document = self.documents_mut()[active]
+            .as_object_mut()
+            .ok_or("The board is not a JSON object")?;
+        let shapes = document.entry("shapes").or_insert_with(|| json!([]));
+        if !shapes.is_array() {
+            *shapes = json!([]);
+        }
+        Ok(shapes.as_array_mut().expect("shapes is an array"))
+    }
+
+    fn shape(&self, id: &str) -> Result<&Value, String> {
+        self.shapes()?
+            .iter()
+            .find(|s| docstore::shape_id(s) == Some(id))
+            .ok_or_else(|| shape_not_found(id))
+    }
+
+    fn position(&self, id: &str) -> Result<usize, String> {
+        self.shapes()?
+            .iter()
+            .position(|s| docstore::shape_id(s) == Some(id))
+            .ok_or_else(|| shape_not_found(id))
+    }
+
+    /// A new shape ID in the webview's style.
+    fn new_id(&mut self) -> String {
+        self.next_id += 1;
+        format!(
+            "shape_{}_{}",
+            chrono::Utc::now().timestamp_millis(),
+            self.next_id
+        )
+    }
+
+    /// Write the collection back to its file.
+    fn save(&self) -> Result<(), String> {
+        let json = Value::Object(self.collection.clone()).to_string();
+        docstore::write_path(&self.path, json)
+    }
+
+    /// Answer `tool_name` with `arguments` (already checked and stamped),
+    /// saving the document when the tool changes it.
+    pub(crate) fn call(&mut self, tool_name: &str, arguments: &Value) -> Result<Value, String> {
+        let result = match tool_name {
+            "get_canvas" => self.get_canvas(arguments),
+            "list_shapes" => self.list_shapes(arguments),
+            "get_shape" => self.shape(arg_str(arguments, "id")?).cloned(),
+            "create_shape" => self.create_shape(arguments),
+            "update_shape" => self.update_shape(arguments),
+            "delete_shape" => self.delete_shape(arguments),
+            "create_connection" => self.create_connection(arguments),
+            "list_tabs" => Ok(self.list_tabs()),
+            "create_tab" => Ok(self.create_tab(arguments)),
+            "switch_tab" => self.switch_tab(arguments),
+            "rename_tab" => self.rename_tab(arguments),
+            "bring_to_front" | "send_to_back" => self.restack(tool_name, arguments),
+            "clear_canvas" => {
+                self.shapes_mut()?.clear();
+                Ok(json!({ "success": true }))
+            }
+            "batch_operations" => self.batch_operations(arguments),
+            "export_png" => render::png_tool_result(self.shapes()?, arguments, None),
+            _ => Err(format!(
+                "Tool not available in headless mode: {}",
+                tool_name
+            )),
+        }?;
+        if api::is_mutating_tool(tool_name) {
+            self.revision += 1;
+            self.save()?;
+        }
+        Ok(result)
+    }
+
+    fn get_canvas(&self, arguments: &Value) -> Result<Value, String> {
+        if arguments["sinceRevision"].as_u64() == Some(self.revision) {
+            return Ok(json!({ "revision": self.revision, "unchanged": true }));
+        }
+        let document = self.document()?;
+        let shapes = self.shapes()?;
+        Ok(json!({
+            "shapes": shapes,
+            "viewport": document.get("viewport").cloned().unwrap_or(json!({ "x": 0, "y": 0, "zoom": 1 })),
+            "groups": document.get("groups").cloned().unwrap_or(json!([])),
+            "shapeCount": shapes.len(),
+            "revision": self.revision,
+        }))
+    }
+
+    fn list_shapes(&self, arguments: &Value) -> Result<Value, String> {
+        let region = match arguments.get("region") {
+            None => None,
+            Some(region) => {
+                let n = |k: &str| region[k].as_f64();
+                let (Some(x), Some(y), Some(w), Some(h)) =
+                    (n("x"), n("y"), n("width"), n("height"))
+                else {
+                    return Err("region needs numeric x, y, width and height".to_string());
+                };
+                Some(Bounds {
+                    min_x: x,
+                    min_y: y,
+                    max_x: x + w,
+                    max_y: y + h,
+                })
+            }
+        };
+        let kind = arguments["type"].as_str();
+        let author = arguments["createdBy"]
+            .as_str()
+            .map(|a| a.trim().to_lowercase());
+        let text = arguments["text"].as_str().map(str::to_lowercase);
+        let matching: Vec<&Value> = self
+            .shapes()?
+            .iter()
+            .filter(|s| kind.map_or(true, |kind| s["type"] == kind))
+            .filter(|s| {
+                author.as_deref().map_or(true, |wanted| {
+                    let by = &s["createdBy"];
+                    by["source"] == wanted
+                        || by["name"]
+                            .as_str()
+                            .is_some_and(|n| n.to_lowercase() == wanted)
+                })
+            })
+            .filter(|s| {
+                region.as_ref().map_or(true, |region| {
+                    geometry::shape_bounds(s).is_some_and(|b| b.intersects(region))
+                })
+            })
+            .filter(|s| {
+                text.as_deref().map_or(true, |needle| {
+                    s["text"]
+                        .as_str()
+                        .is_some_and(|t| t.to_lowercase().contains(needle))
+                })
+            })
+            .collect();
+        let total = matching.len();
+        let offset = match arguments.get("offset") {
+            None => 0,
+            Some(offset) => offset
+                .as_u64()
+                .ok_or("offset must be a non-negative integer")?
+                as usize,
+        };
+        let end = match arguments.get("limit") {
+            None => total,
+            Some(limit) => match limit.as_u64() {
+                Some(limit) if limit >= 1 => (offset + limit as usize).min(total),
+                _ => return Err("limit must be a positive integer".to_string()),
+            },
+        };
+        let page = &matching[offset.min(end)..end];
+        let mut result = json!({ "shapes": page, "count": page.len(), "total": total });
+        if end < total {
+            result["nextOffset"] = json!(end);
+        }
+        Ok(result)
+    }
+
+    /// A shape from `create_shape` arguments, with the webview's defaults.
+    fn build_shape(&mut self, params: &Value) -> Result<Value, String> {
+        let kind = arg_str(params, "type")?.to_string();
+        let param = |key: &str, default: Value| params.get(key).cloned().unwrap_or(default);
+        let mut shape = json!({
+            "id": self.new_id(),
+            "type": kind,
+            "x": param("x", json!(0)),
+            "y": param("y", json!(0)),
+            "strokeColor": param("strokeColor", json!("#000000")),
+            "strokeWidth": param("strokeWidth", json!(2)),
+            "strokeStyle": param("strokeStyle", json!("solid")),
+            "fillColor": param("fillColor", json!("transparent")),
+            "fillStyle": param("fillStyle", json!("hachure")),
+            "opacity": param("opacity", json!(1)),
+            "roughness": param("roughness", json!(1)),
+            "rotation": param("rotation", json!(0)),
+            "textAlign": param("textAlign", json!("center")),
+            "verticalAlign": param("verticalAlign", json!("middle")),
+        });
+        for key in ["text", "autoFit", "createdBy"] {
+            if let Some(value) = params.get(key) {
+                shape[key] = value.clone();
+            }
+        }
+        let x = params["x"].as_f64().unwrap_or(0.0);
+        let end_x = || param("x2", json!(x + params["width"].as_f64().unwrap_or(200.0)));
+        let end_y = || param("y2", param("y", json!(0)));
+        let extra = match kind.as_str() {
+            "sticky" => {
+                let color = param("stickyColor", json!("#fff9c4"));
+                json!({
+                    "width": param("width", json!(200)),
+                    "height": param("height", json!(200)),
+                    "stickyColor": color,
+                    "fontSize": param("fontSize", json!(16)),
+                    "text": param("text", json!("")),
+                    "fillColor": color,
+                })
+            }
+            "text" => json!({
+                "text": param("text", json!("Text")),
+                "fontSize": param("fontSize", json!(20)),
+                "fontFamily": param("fontFamily", json!("sans-serif")),
+                "width": param("width", json!(200)),
+                "height": param("height", json!(30)),
+                "strokeWidth": 0,
+                "fillColor": "transparent",
+            }),
+            "line" => json!({
+                "x2": end_x(),
+                "y2": end_y(),
+                "routingMode": param("routingMode", json!("direct")),
+            }),
+            "arrow" => json!({
+                "x2": end_x(),
+                "y2": end_y(),
+                "arrowheadStart": false,
+                "arrowheadEnd": true,
+                "routingMode": param("routingMode", json!("direct")),
+                "startEndpoint": param("startEndpoint", json!({ "shape": "none", "size": 1 })),
+                "endEndpoint": param("endEndpoint", json!({ "shape": "arrow", "size": 1 })),
+            }),
+            "freedraw" => json!({
+                "points": params["points"].as_array().cloned().unwrap_or_default(),
+            }),
+            _ => json!({
+                "width": param("width", json!(200)),
+                "height": param("height", json!(150)),
+            }),
+        };
+        if let (Some(shape), Value::Object(extra)) = (shape.as_object_mut(), extra) {
+            shape.extend(extra);
+        }
+        Ok(shape)
+    }
+
+    fn create_shape(&mut self, arguments: &Value) -> Result<Value, String> {
+        let shape = self.build_shape(arguments)?;
+        self.shapes_mut()?.push(shape.clone());
+        Ok(shape)
+    }
+
+    fn update_shape(&mut self, arguments: &Value) -> Result<Value, String> {
+        let id = arg_str(arguments, "id")?;
+        let index = self.position(id)?;
+        let shape = &mut self.shapes_mut()?[index];
+        for key in UPDATABLE {
+            if let Some(value) = arguments.get(*key) {
+                shape[*key] = value.clone();
+            }
+        }
+        // Dangling bindings are cleared with null by repair_document
+        for key in ["bindStart", "bindEnd"] {
+            if arguments.get(key) == Some(&Value::Null) {
+                if let Some(shape) = shape.as_object_mut() {
+                    shape.remove(key);
+                }
+            }
+        }
+        Ok(shape.clone())
+    }
+
+    fn delete_shape(&mut self, arguments: &Value) -> Result<Value, String> {
+        let id = arg_str(arguments, "id")?;
+        let index = self.position(id)?;
+        self.shapes_mut()?.remove(index);
+        Ok(json!({ "success": true, "id": id }))
+    }
+
+    fn create_connection(&mut self, arguments: &Value) -> Result<Value, String> {
+        let from_id = arg_str(arguments, "fromShapeId")?;
+        let to_id = arg_str(arguments, "toShapeId")?;
+        let center = |id: &str, role: &str| -> Result<(f64, f64), String> {
+            let shape = self
+                .shape(id)
+                .map_err(|_| format!("{} shape not found: {}", role, id))?;
+            let b = geometry::shape_bounds(shape).unwrap_or(Bounds {
+                min_x: 0.0,
+                min_y: 0.0,
+                max_x: 0.0,
+                max_y: 0.0,
+            });
+            Ok(((b.min_x + b.max_x) / 2.0, (b.min_y + b.max_y) / 2.0))
+        };
+        let from = center(from_id, "Source")?;
+        let to = center(to_id, "Target")?;
+        let kind = if arguments["connectionType"] == "line" {
+            "line"
+        } else {
+            "arrow"
+        };
+        let mut params = json!({
+            "type": kind,
+            "x": from.0,
+            "y": from.1,
+            "x2": to.0,
+            "y2": to.1,
+            "strokeColor": arguments.get("strokeColor").cloned().unwrap_or(json!("#000000")),
+            "strokeWidth": arguments.get("strokeWidth").cloned().unwrap_or(json!(2)),
+            "routingMode": arguments.get("routingMode").cloned().unwrap_or(json!("direct")),
+        });
+        for key in ["text", "createdBy"] {
+            if let Some(value) = arguments.get(key) {
+                params[key] = value.clone();
+            }
+        }
+        let mut shape = self.build_shape(&params)?;
+        shape["bindStart"] = json!({ "shapeId": from_id, "point": "center" });
+        shape["bindEnd"] = json!({ "shapeId": to_id, "point": "center" });
+        self.shapes_mut()?.push(shape.clone());
+        Ok(shape)
+    }
+
+    fn list_tabs(&self) -> Value {
+        let saved = self.collection["activeDocumentIndex"].as_u64().unwrap_or(0) as usize;
+        let tabs: Vec<Value> = (0..self.documents().len())
+            .filter(|&i| !self.is_hidden(i))
+            .map(|i| {
+                json!({
+                    "id": tab_id(i),
+                    "title": self.title(i),
+                    "isDirty": false,
+                    "isActive": i == saved,
+                    "isMcpActive": i == self.active,
+                })
+            })
+            .collect();
+        json!({
+            "tabs": tabs,
+            "activeTabId": tab_id(saved),
+            "mcpActiveTabId": tab_id(self.active),
+        })
+    }
+
+    fn tab_index(&self, id: &str) -> Result<usize, String> {
+        (0..self.documents().len())
+            .find(|&i| tab_id(i) == id && !self.is_hidden(i))
+            .ok_or_else(|| format!("Tab not found: {}", id))
+    }
+
+    fn create_tab(&mut self, arguments: &Value) -> Value {
+        let title = arguments["title"]
+            .as_str()
+            .unwrap_or("Untitled")
+            .to_string();
+        let documents = self.documents_mut();
+        documents.push(json!({
+            "shapes": [],
+            "viewport": { "x": 0, "y": 0, "zoom": 1 },
+            "metadata": { "title": title },
+        }));
+        self.active = documents.len() - 1;
+        json!({ "id": tab_id(self.active), "title": title })
+    }
+
+    fn switch_tab(&mut self, arguments: &Value) -> Result<Value, String> {
+        let id = arg_str(arguments, "tabId")?;
+        self.active = self.tab_index(id)?;
+        Ok(json!({ "success": true, "mcpActiveTabId": id }))
+    }
+
+    fn rename_tab(&mut self, arguments: &Value) -> Result<Value, String> {
+        let id = arg_str(arguments, "tabId")?;
+        let title = arg_str(arguments, "title")?;
+        let index = self.tab_index(id)?;
+        let document = &mut self.documents_mut()[index];
+        if !document["metadata"].is_object() {
+            document["metadata"] = json!({});
+        }
+        document["metadata"]["title"] = json!(title);
+        Ok(json!({ "success": true, "tabId": id, "title": title }))
+    }
+
+    fn restack(&mut self, tool_name: &str, arguments: &Value) -> Result<Value, String> {
+        let id = arg_str(arguments, "id")?;
+        let index = self.position(id)?;
+        let shapes = self.shapes_mut()?;
+        let shape = shapes.remove(index);
+        if tool_name == "bring_to_front" {
+            shapes.push(shape);
+        } else {
+            shapes.insert(0, shape);
+        }
+        Ok(json!({ "success": true, "id": id }))
+    }
+
+    fn batch_operations(&mut self, arguments: &Value) -> Result<Value, String> {
+        let operations = arguments["operations"]
+            .as_array()
+            .ok_or("Missing required field: operations (array)")?;
+        let mut results = Vec::new();
+        for op in operations {
+            let data = &op["data"];
+            let id = data["id"].as_str();
+            let result = match (op["action"].as_str(), id) {
+                (Some("create"), _) => match data.get("type") {
+                    None => Err("Missing type for create".to_string()),
+                    Some(_) => self
+                        .create_shape(data)
+                        .map(|shape| json!({ "action": "created", "shape": shape })),
+                },
+                (Some("update"), Some(id)) => self.position(id).and_then(|index| {
+                    let shape = &mut self.shapes_mut()?[index];
+                    if let (Some(shape), Some(data)) = (shape.as_object_mut(), data.as_object()) {
+                        shape.extend(data.iter().map(|(k, v)| (k.clone(), v.clone())));
+                    }
+                    Ok(json!({ "action": "updated", "id": id }))
+                }),
+                (Some("delete"), Some(id)) => self.position(id).and_then(|index| {
+                    self.shapes_mut()?.remove(index);
+                    Ok(json!({ "action": "deleted", "id": id }))
+                }),
+                (Some(action @ ("update" | "delete")), None) => {
+                    Err(format!("Missing id for {}", action))
+                }
+                (action, _) => Err(format!("Unknown action: {}", action.unwrap_or("(none)"))),
+            };
+            results.push(result.unwrap_or_else(|error| {
+                let code = tool_errors::ErrorCode::classify(&error);
+                json!({ "error": error, "code": code })
+            }));
+        }
+        Ok(json!({ "results": results }))
+    }
+}
+
+// --- MCP over HTTP ---
+
+struct Headless {
+    board: AsyncMutex<Board>,
+    token: Option<String>,
+    /// Client names from `initialize`, by session ID, for author stamps.
+    clients: Mutex<HashMap<String, Option<String>>>,
+}
+
+type SharedHeadless = Arc<Headless>;
+
+fn tools() -> Value {
+    let tools = api::all_tools()
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|tool| {
+            tool["name"]
+                .as_str()
+                .is_some_and(|name| HEADLESS_TOOLS.contains(&name))
+        })
+        .cloned()
+        .collect();
+    Value::Array(tools)
+}
+
+/// Answer one JSON-RPC message; `None` for notifications. `session` is the
+/// caller's `Mcp-Session-Id`, and a new one is returned from `initialize`.
+async fn handle_message(
+    state: &Headless,
+    message: Value,
+    session: Option<&str>,
+) -> (Option<Value>, Option<String>) {
+    let id = message.get("id").cloned();
+    let method = message["method"].as_str().unwrap_or("");
+    let params = &message["params"];
+    let reply = match method {
+        "initialize" => {
+            let session = uuid::Uuid::new_v4().to_string();
+            let client = params["clientInfo"]["name"].as_str().map(str::to_string);
+            if let Ok(mut clients) = state.clients.lock() {
+                clients.insert(session.clone(), client);
+            }
+            let result = mcp_result(
+                id,
+                json!({
+                    "protocolVersion": MCP_PROTOCOL_VERSION,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": MCP_SERVER_NAME, "version": MCP_SERVER_VERSION },
+                }),
+            );
+            return (Some(result), Some(session));
+        }
+        _ if id.is_none() => return (None, None),
+        "ping" => mcp_result(id, json!({})),
+        "tools/list" => mcp_result(id, json!({ "tools": tools() })),
+        "tools/call" => {
+            let tool_name = params["name"].as_str().unwrap_or("");
+            if !HEADLESS_TOOLS.contains(&tool_name) {
+                let msg = format!("Tool not available in headless mode: {}", tool_name);
+                return (Some(mcp_error(id, -32602, &msg)), None);
+            }
+            let client = session
+                .and_then(|s| state.clients.lock().ok()?.get(s).cloned())
+                .flatten();
+            let mut arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+            author::stamp(&author::agent(client.as_deref()), tool_name, &mut arguments);
+            let result = match model::validate_arguments(tool_name, &arguments) {
+                Ok(()) => state.board.lock().await.call(tool_name, &arguments),
+                Err(e) => Err(e),
+            };
+            match tool_errors::check(result) {
+                Ok(content) if tool_name == "export_png" => {
+                    mcp_result(id, structured::image_result(&content))
+                }
+                Ok(content) => mcp_result(id, structured::call_result(&content)),
+                Err(error) => mcp_result(id, error.call_result()),
+            }
+        }
+        _ => mcp_error(id, -32601, &format!("Method not found: {}", method)),
+    };
+    (Some(reply), None)
+}
+
+async fn mcp_post(
+    AxumState(state): AxumState<SharedHeadless>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let message: Value = match serde_json::from_slice(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            let error = mcp_error(None, -32700, &format!("Parse error: {}", e));
+            return (StatusCode::BAD_REQUEST, Json(error)).into_response();
+        }
+    };
+    let session = headers
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim);
+    let (reply, new_session) = match message {
+        Value::Array(batch) => {
+            let mut replies = Vec::new();
+            for message in batch {
+                if let (Some(reply), _) = handle_message(&state, message, session).await {
+                    replies.push(reply);
+                }
+            }
+            ((!replies.is_empty()).then_some(Value::Array(replies)), None)
+        }
+        message => handle_message(&state, message, session).await,
+    };
+    let mut response = match reply {
+        Some(reply) => Json(reply).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    };
+    if let Some(session) = new_session.and_then(|s| HeaderValue::from_str(&s).ok()) {
+        response.headers_mut().insert(SESSION_HEADER, session);
+    }
+    response
+}
+
+async fn require_token(
+    AxumState(state): AxumState<SharedHeadless>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match &state.token {
+        Some(token) if !auth::is_authorized(request.headers(), token) => auth::unauthorized(),
+        _ => next.run(request).await,
+    }
+}
+
+fn router(state: SharedHeadless, lan: bool) -> Router {
+    Router::new()
+        .route("/mcp", post(mcp_post))
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            require_token,
+        ))
+        .layer(middleware::from_fn(move |request: Request, next: Next| {
+            hosts::filter(request, next, &[], lan)
+        }))
+        .with_state(state)
+}
+
+async fn serve(options: Options) -> Result<(), String> {
+    let token = std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty());
+    if !options.bind.is_loopback() {
+        bind::check_exposure(options.bind, token.as_deref().unwrap_or(""))?;
+    }
+    let board = Board::open(options.document.clone())?;
+    let (listener, port) = api::bind_listener(options.bind, options.port, 0).await?;
+    let lan = !options.bind.is_loopback();
+    let state = Arc::new(Headless {
+        board: AsyncMutex::new(board),
+        token,
+        clients: Mutex::new(HashMap::new()),
+    });
+    eprintln!(
+        "napkin --headless: serving {} on http://{}:{}/mcp",
+        options.document.display(),
+        options.bind,
+        port
+    );
+    let shutdown = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    tls::serve(listener, None, router(state, lan), shutdown)
+        .await
+        .map_err(|e| format!("Server error: {}", e))
+}
+
+/// Run `napkin --headless ...`; `args` are the process's, without the
+/// program name.
+pub fn run(args: &[String]) -> i32 {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("napkin --headless: {}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("napkin --headless: {}", e);
+            return 1;
+        }
+    };
+    match runtime.block_on(serve(options)) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("napkin --headless: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_file(contents: Value) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("napkin-headless-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("board.napkin");
+        std::fs::write(&path, contents.to_string()).unwrap();
+        path
+    }
+
+    fn saved(path: &std::path::Path) -> Value {
+        serde_json::from_str(&docstore::read_path(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn options_default_to_loopback_on_the_app_port() {
+        let args = |list: &[&str]| list.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let options = parse_args(&args(&["--headless", "board.napkin"])).unwrap();
+        assert_eq!(options.port, DEFAULT_PORT);
+        assert!(options.bind.is_loopback());
+        let options = parse_args(&args(&[
+            "--headless",
+            "--mcp-port",
+            "9000",
+            "--mcp-bind",
+            "0.0.0.0",
+            "b.napkin",
+        ]))
+        .unwrap();
+        assert_eq!(options.port, 9000);
+        assert_eq!(options.document, PathBuf::from("b.napkin"));
+        assert!(parse_args(&args(&["--headless"])).is_err());
+        assert!(parse_args(&args(&["--headless", "b.napkin", "--mcp-port", "x"])).is_err());
+    }
+
+    #[test]
+    fn edits_are_saved_to_the_document() {
+        let path = board_file(json!({
+            "metadata": { "title": "Plan" },
+            "shapes": [{ "id": "a", "type": "rectangle", "x": 0, "y": 0, "width": 100, "height": 100 }],
+        }));
+        let mut board = Board::open(path.clone()).unwrap();
+        let b = board
+            .call(
+                "create_shape",
+                &json!({ "type": "ellipse", "x": 300, "y": 0 }),
+            )
+            .unwrap();
+        assert_eq!(b["width"], 200);
+        let b = b["id"].as_str().unwrap().to_string();
+        let arrow = board
+            .call(
+                "create_connection",
+                &json!({ "fromShapeId": "a", "toShapeId": b }),
+            )
+            .unwrap();
+        assert_eq!(arrow["bindEnd"]["shapeId"], b.as_str());
+        assert_eq!(
+            (arrow["x"].as_f64(), arrow["x2"].as_f64()),
+            (Some(50.0), Some(400.0))
+        );
+        board
+            .call(
+                "update_shape",
+                &json!({ "id": "a", "text": "Start", "bogus": 1 }),
+            )
+            .unwrap();
+        board.call("send_to_back", &json!({ "id": b })).unwrap();
+
+        let file = saved(&path);
+        let shapes = file["documents"][0]["shapes"].as_array().unwrap();
+        assert_eq!(shapes.len(), 3);
+        assert_eq!(shapes[0]["id"], b.as_str());
+        assert_eq!(shapes[1]["text"], "Start");
+        assert!(shapes[1].get("bogus").is_none());
+
+        let err = board
+            .call("delete_shape", &json!({ "id": "zz" }))
+            .unwrap_err();
+        assert_eq!(err, "Shape not found: zz");
+        let results = board
+            .call(
+                "batch_operations",
+                &json!({ "operations": [
+                    { "action": "delete", "data": { "id": "a" } },
+                    { "action": "update", "data": { "id": "zz", "x": 1 } },
+                ] }),
+            )
+            .unwrap();
+        assert_eq!(results["results"][0]["action"], "deleted");
+        assert_eq!(results["results"][1]["code"], "SHAPE_NOT_FOUND");
+        assert_eq!(
+            saved(&path)["documents"][0]["shapes"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn hidden_tabs_stay_hidden() {
+        let path = board_file(json!({
+            "type": "collection",
+            "activeDocumentIndex": 0,
+            "documents": [
+                { "shapes": [], "metadata": { "title": "Secret", "hiddenFromApi": true } },
+                { "shapes": [], "metadata": { "title": "Open" } },
+            ],
+        }));
+        let mut board = Board::open(path.clone()).unwrap();
+        let tabs = board.call("list_tabs", &json!({})).unwrap();
+        assert_eq!(tabs["tabs"].as_array().unwrap().len(), 1);
+        assert_eq!(tabs["mcpActiveTabId"], "tab-2");
+        assert!(board
+            .call("switch_tab", &json!({ "tabId": "tab-1" }))
+            .is_err());
+
+        let tab = board
+            .call("create_tab", &json!({ "title": "New" }))
+            .unwrap();
+        board
+            .call(
+                "rename_tab",
+                &json!({ "tabId": tab["id"], "title": "Renamed" }),
+            )
+            .unwrap();
+        assert_eq!(saved(&path)["documents"][2]["metadata"]["title"], "Renamed");
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn out_of_range_active_index_opens_a_visible_tab() {
+        let path = board_file(json!({
+            "type": "collection",
+            "activeDocumentIndex": 7,
+            "documents": [
+                { "shapes": [], "metadata": { "title": "Open" } },
+                { "shapes": [], "metadata": { "title": "Secret", "hiddenFromApi": true } },
+            ],
+        }));
+        let mut board = Board::open(path.clone()).unwrap();
+        assert_eq!(board.active, 0);
+        assert!(board.call("list_shapes", &json!({})).is_ok());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn serves_the_headless_tool_set() {
+        let path = board_file(json!({ "shapes": [] }));
+        let state = Headless {
+            board: AsyncMutex::new(Board::open(path.clone()).unwrap()),
+            token: None,
+            clients: Mutex::new(HashMap::new()),
+        };
+        let init = json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": { "clientInfo": { "name": "builder" } } });
+        let (_, session) = handle_message(&state, init, None).await;
+        let session = session.unwrap();
+
+        let list = json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" });
+        let (reply, _) = handle_message(&state, list, Some(&session)).await;
+        let names: Vec<String> = reply.unwrap()["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names.len(), HEADLESS_TOOLS.len());
+        assert!(!names.contains(&"screenshot_viewport".to_string()));
+
+        let call = json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {
+            "name": "create_shape", "arguments": { "type": "sticky", "x": 0, "y": 0, "text": "Hi" } } });
+        let (reply, _) = handle_message(&state, call, Some(&session)).await;
+        let shape = &reply.unwrap()["result"]["structuredContent"];
+        assert_eq!(shape["createdBy"]["name"], "builder");
+
+        let call = json!({ "jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {
+            "name": "screenshot_viewport", "arguments": {} } });
+        let (reply, _) = handle_message(&state, call, Some(&session)).await;
+        assert_eq!(reply.unwrap()["error"]["code"], -32602);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,723 @@
+//! Version history compaction.
+//!
+//! The webview records snapshots in the `VersionHistory` format from
+//! `src/lib/storage/schema.ts`: every `baselineInterval`-th snapshot stores
+//! the full documents and the rest store deltas against their predecessor.
+//! Compaction materialises every snapshot, applies the retention policy,
+//! drops snapshots that changed nothing, and re-encodes the survivors as a
+//! fresh baseline/delta chain. Delta handling mirrors
+//! `src/lib/storage/versionHistory.ts`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::settings::SettingsStore;
+
+const DEFAULT_BASELINE_INTERVAL: usize = 10;
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// How much version history to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetentionPolicy {
+    /// Newest snapshots to keep.
+    pub max_revisions: usize,
+    /// Drop snapshots older than this, always keeping the newest.
+    pub max_age_days: Option<u32>,
+    /// Drop the oldest snapshots until the serialised history fits.
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_revisions: 50,
+            max_age_days: Some(90),
+            max_bytes: Some(8 * 1024 * 1024),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactionReport {
+    pub snapshots_before: usize,
+    pub snapshots_after: usize,
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+    pub reclaimed_bytes: usize,
+}
+
+/// A snapshot's metadata with its documents materialised.
+struct Revision {
+    meta: Map<String, Value>,
+    docs: Vec<Value>,
+    /// Position in the original history, to tell when a predecessor was
+    /// dropped and the stored summary no longer describes the delta.
+    original: usize,
+}
+
+fn shapes(doc: &Value) -> &[Value] {
+    doc.get("shapes")
+        .and_then(|s| s.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+}
+
+fn shape_id(shape: &Value) -> Option<&str> {
+    shape.get("id").and_then(|id| id.as_str())
+}
+
+/// Mirror of `applyDocumentDelta`.
+fn apply_document_delta(doc: &Value, delta: &Value) -> Value {
+    let ids = |key: &str| -> Vec<String> {
+        delta
+            .get(key)
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let removed = ids("removed");
+    let added: Vec<Value> = delta
+        .get("added")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut result: Vec<Value> = shapes(doc)
+        .iter()
+        .filter(|s| shape_id(s).is_some_and(|id| !removed.iter().any(|r| r == id)))
+        .cloned()
+        .collect();
+
+    let modified = delta.get("modified").and_then(|v| v.as_array());
+    for m in modified.into_iter().flatten() {
+        let (Some(id), Some(changes)) = (
+            m.get("id").and_then(|v| v.as_str()),
+            m.get("changes").and_then(|v| v.as_object()),
+        ) else {
+            continue;
+        };
+        let Some(obj) = result
+            .iter_mut()
+            .find(|s| shape_id(s) == Some(id))
+            .and_then(|s| s.as_object_mut())
+        else {
+            continue;
+        };
+        for (key, value) in changes {
+            if value.is_null() {
+                obj.remove(key);
+            } else {
+                obj.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    for shape in added {
+        let id = shape_id(&shape).map(str::to_string);
+        match result.iter_mut().find(|s| shape_id(s) == id.as_deref()) {
+            Some(existing) => *existing = shape,
+            None => result.push(shape),
+        }
+    }
+
+    let mut doc = doc.clone();
+    doc["shapes"] = Value::Array(result);
+    if let Some(viewport) = delta.get("viewport").filter(|v| !v.is_null()) {
+        doc["viewport"] = viewport.clone();
+    }
+    doc
+}
+
+/// Mirror of `computeDocumentDelta`; `None` when nothing changed.
+fn document_delta(old: &Value, new: &Value, index: usize) -> Option<Value> {
+    let old_map: HashMap<&str, &Value> = shapes(old)
+        .iter()
+        .filter_map(|s| Some((shape_id(s)?, s)))
+        .collect();
+    let new_ids: Vec<&str> = shapes(new).iter().filter_map(shape_id).collect();
+
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+    for shape in shapes(new) {
+        let Some(id) = shape_id(shape) else { continue };
+        let Some(old_shape) = old_map.get(id) else {
+            added.push(shape.clone());
+            continue;
+        };
+        let empty = Map::new();
+        let before = old_shape.as_object().unwrap_or(&empty);
+        let after = shape.as_object().unwrap_or(&empty);
+        let mut changes = Map::new();
+        for key in before.keys().chain(after.keys()) {
+            if before.get(key) != after.get(key) && !changes.contains_key(key) {
+                changes.insert(key.clone(), after.get(key).cloned().unwrap_or(Value::Null));
+            }
+        }
+        if !changes.is_empty() {
+            modified.push(serde_json::json!({ "id": id, "changes": changes }));
+        }
+    }
+    let removed: Vec<&str> = shapes(old)
+        .iter()
+        .filter_map(shape_id)
+        .filter(|id| !new_ids.contains(id))
+        .collect();
+    let viewport = (old.get("viewport") != new.get("viewport")).then(|| new["viewport"].clone());
+
+    if added.is_empty() && removed.is_empty() && modified.is_empty() && viewport.is_none() {
+        return None;
+    }
+    let mut delta = serde_json::json!({
+        "index": index,
+        "added": added,
+        "removed": removed,
+        "modified": modified,
+    });
+    if let Some(viewport) = viewport {
+        delta["viewport"] = viewport;
+    }
+    Some(delta)
+}
+
+/// Mirror of `generateSummary`.
+fn summary(deltas: &[Value], doc_count: usize) -> String {
+    let count = |d: &Value, key: &str| d[key].as_array().map_or(0, Vec::len);
+    let parts: Vec<String> = deltas
+        .iter()
+        .filter_map(|d| {
+            let items: Vec<String> = [("added", "+"), ("removed", "-"), ("modified", "~")]
+                .iter()
+                .filter(|(key, _)| count(d, key) > 0)
+                .map(|(key, sign)| format!("{}{}", sign, count(d, key)))
+                .collect();
+            if items.is_empty() {
+                return None;
+            }
+            let label = if doc_count > 1 {
+                format!("Tab {}: ", d["index"].as_u64().unwrap_or(0) + 1)
+            } else {
+                String::new()
+            };
+            Some(format!("{}{} shapes", label, items.join(", ")))
+        })
+        .collect();
+    if parts.is_empty() {
+        "Minor changes".to_string()
+    } else {
+        parts.join("; ")
+    }
+}
+
+/// Materialise every snapshot, as `reconstructState` would.
+fn revisions(snapshots: &[Value]) -> Result<Vec<Revision>, String> {
+    let mut result: Vec<Revision> = Vec::with_capacity(snapshots.len());
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        let mut meta = snapshot
+            .as_object()
+            .cloned()
+            .ok_or("Invalid history: snapshot is not an object")?;
+        let full = meta.remove("fullState");
+        let delta = meta.remove("delta");
+        let docs = match (full, result.last()) {
+            (Some(Value::Array(docs)), _) => docs,
+            (_, Some(prev)) => {
+                let mut docs = prev.docs.clone();
+                let doc_deltas = delta
+                    .as_ref()
+                    .and_then(|d| d.get("documents"))
+                    .and_then(|d| d.as_array());
+                for d in doc_deltas.into_iter().flatten() {
+                    let index = d.get("index").and_then(|i| i.as_u64()).unwrap_or(u64::MAX);
+                    if let Some(doc) = docs.get_mut(index as usize) {
+                        *doc = apply_document_delta(doc, d);
+                    }
+                }
+                docs
+            }
+            (_, None) => return Err("Invalid history: no baseline before first delta".to_string()),
+        };
+        result.push(Revision {
+            meta,
+            docs,
+            original: i,
+        });
+    }
+    Ok(result)
+}
+
+/// Same shapes, viewports and titles. Serialised documents carry a fresh
+/// `modified` time on every save, so whole-document equality never holds.
+fn same_content(a: &[Value], b: &[Value]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| {
+            a.get("shapes") == b.get("shapes")
+                && a.get("viewport") == b.get("viewport")
+                && a.pointer("/metadata/title") == b.pointer("/metadata/title")
+        })
+}
+
+/// Re-encode revisions as a baseline/delta chain.
+fn encode(revisions: &[Revision], interval: usize) -> Vec<Value> {
+    revisions
+        .iter()
+        .enumerate()
+        .map(|(i, rev)| {
+            let mut meta = rev.meta.clone();
+            let prev = i.checked_sub(1).map(|p| &revisions[p]);
+            match prev {
+                Some(prev) if i % interval != 0 && prev.docs.len() == rev.docs.len() => {
+                    let deltas: Vec<Value> = prev
+                        .docs
+                        .iter()
+                        .zip(&rev.docs)
+                        .enumerate()
+                        .filter_map(|(index, (old, new))| document_delta(old, new, index))
+                        .collect();
+                    if prev.original + 1 != rev.original {
+                        meta.insert(
+                            "summary".to_string(),
+                            Value::String(summary(&deltas, rev.docs.len())),
+                        );
+                    }
+                    meta.insert(
+                        "delta".to_string(),
+                        serde_json::json!({ "documents": deltas }),
+                    );
+                }
+                _ => {
+                    meta.insert("fullState".to_string(), Value::Array(rev.docs.clone()));
+                }
+            }
+            Value::Object(meta)
+        })
+        .collect()
+}
+
+fn serialized_len(value: &Value) -> usize {
+    serde_json::to_vec(value).map_or(0, |v| v.len())
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Milliseconds since the epoch for the UTC `toISOString()` format.
+fn parse_timestamp(s: &str) -> Option<i64> {
+    let field = |range: std::ops::Range<usize>| s.get(range)?.parse::<i64>().ok();
+    if s.len() < 20 || !s.ends_with('Z') {
+        return None;
+    }
+    let days = days_from_civil(field(0..4)?, field(5..7)?, field(8..10)?);
+    let secs = field(11..13)? * 3600 + field(14..16)? * 60 + field(17..19)?;
+    let millis = s
+        .get(20..s.len() - 1)
+        .filter(|f| !f.is_empty())
+        .and_then(|f| f.get(..3.min(f.len()))?.parse::<i64>().ok())
+        .unwrap_or(0);
+    Some(days * DAY_MS + secs * 1000 + millis)
+}
+
+/// Apply `policy` to a serialised `VersionHistory`.
+pub fn compact(
+    history: &Value,
+    policy: &RetentionPolicy,
+    now_ms: i64,
+) -> Result<(Value, CompactionReport), String> {
+    let snapshots = history
+        .get("snapshots")
+        .and_then(|s| s.as_array())
+        .ok_or("Invalid history: missing snapshots")?;
+    let interval = history
+        .get("baselineInterval")
+        .and_then(|i| i.as_u64())
+        .filter(|i| *i > 0)
+        .map_or(DEFAULT_BASELINE_INTERVAL, |i| i as usize);
+    let bytes_before = serialized_len(history);
+
+    let mut revs = revisions(snapshots)?;
+
+    // Consolidate: a snapshot identical to the one before it adds nothing.
+    let mut kept: Vec<Revision> = Vec::with_capacity(revs.len());
+    for rev in revs.drain(..) {
+        if kept
+            .last()
+            .is_some_and(|prev| same_content(&prev.docs, &rev.docs))
+        {
+            continue;
+        }
+        kept.push(rev);
+    }
+    let mut revs = kept;
+
+    if let Some(days) = policy.max_age_days {
+        let cutoff = now_ms - i64::from(days) * DAY_MS;
+        let newest = revs.len().saturating_sub(1);
+        let mut i = 0;
+        revs.retain(|rev| {
+            let keep = i == newest
+                || rev
+                    .meta
+                    .get("timestamp")
+                    .and_then(|t| t.as_str())
+                    .and_then(parse_timestamp)
+                    .map_or(true, |t| t >= cutoff);
+            i += 1;
+            keep
+        });
+    }
+
+    let max_revisions = policy.max_revisions.max(1);
+    if revs.len() > max_revisions {
+        revs.drain(..revs.len() - max_revisions);
+    }
+
+    let mut compacted = history.clone();
+    loop {
+        compacted["snapshots"] = Value::Array(encode(&revs, interval));
+        let fits = policy
+            .max_bytes
+            .map_or(true, |max| serialized_len(&compacted) as u64 <= max);
+        if fits || revs.len() <= 1 {
+            break;
+        }
+        revs.remove(0);
+    }
+
+    let bytes_after = serialized_len(&compacted);
+    let report = CompactionReport {
+        snapshots_before: snapshots.len(),
+        snapshots_after: revs.len(),
+        bytes_before,
+        bytes_after,
+        reclaimed_bytes: bytes_before.saturating_sub(bytes_after),
+    };
+    Ok((compacted, report))
+}
+
+/// What changed in one document over a digest period.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentDigest {
+    pub title: String,
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Digest {
+    /// Snapshots taken during the period.
+    pub snapshots: usize,
+    /// Documents whose shapes changed, in tab order.
+    pub documents: Vec<DocumentDigest>,
+    pub summary: String,
+}
+
+/// Summarise the changes in a serialised `VersionHistory` since `since_ms`,
+/// comparing the last snapshot before then with the newest. Documents are
+/// matched by position, as deltas are.
+pub fn digest(history: &Value, since_ms: i64) -> Result<Digest, String> {
+    let snapshots = history
+        .get("snapshots")
+        .and_then(|s| s.as_array())
+        .ok_or("Invalid history: missing snapshots")?;
+    let revs = revisions(snapshots)?;
+    let timestamp = |rev: &Revision| {
+        rev.meta
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .and_then(parse_timestamp)
+    };
+    let recent = revs
+        .iter()
+        .filter(|rev| timestamp(rev).is_some_and(|t| t >= since_ms))
+        .count();
+    let empty = Vec::new();
+    let before = revs
+        .iter()
+        .rev()
+        .find(|rev| timestamp(rev).is_some_and(|t| t < since_ms))
+        .map_or(&empty, |rev| &rev.docs);
+    let latest = revs.last().map_or(&empty, |rev| &rev.docs);
+
+    let documents: Vec<DocumentDigest> = latest
+        .iter()
+        .enumerate()
+        .filter_map(|(index, new)| {
+            let delta = document_delta(before.get(index).unwrap_or(&Value::Null), new, index)?;
+            let count = |key: &str| delta[key].as_array().map_or(0, Vec::len);
+            let doc = DocumentDigest {
+                title: new
+                    .pointer("/metadata/title")
+                    .and_then(|t| t.as_str())
+                    .filter(|t| !t.is_empty())
+                    .unwrap_or("Untitled")
+                    .to_string(),
+                added: count("added"),
+                removed: count("removed"),
+                modified: count("modified"),
+            };
+            // A viewport change alone is not worth reporting.
+            (doc.added + doc.removed + doc.modified > 0).then_some(doc)
+        })
+        .collect();
+
+    let summary = if documents.is_empty() {
+        "No changes".to_string()
+    } else {
+        let boards: Vec<String> = documents
+            .iter()
+            .map(|d| {
+                let items: Vec<String> = [("+", d.added), ("-", d.removed), ("~", d.modified)]
+                    .iter()
+                    .filter(|(_, n)| *n > 0)
+                    .map(|(sign, n)| format!("{}{}", sign, n))
+                    .collect();
+                format!("{} ({})", d.title, items.join(", "))
+            })
+            .collect();
+        format!(
+            "{} {} changed: {}",
+            documents.len(),
+            if documents.len() == 1 {
+                "board"
+            } else {
+                "boards"
+            },
+            boards.join("; ")
+        )
+    };
+    Ok(Digest {
+        snapshots: recent,
+        documents,
+        summary,
+    })
+}
+
+// --- Tauri command ---
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactedHistory {
+    pub history: Value,
+    pub report: CompactionReport,
+}
+
+/// Compact a version history with the given policy, or the one in settings.
+#[tauri::command]
+pub fn compact_history(
+    history: Value,
+    policy: Option<RetentionPolicy>,
+    store: tauri::State<'_, SettingsStore>,
+) -> Result<CompactedHistory, String> {
+    let policy = policy.unwrap_or_else(|| store.get().history_retention);
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64);
+    let (history, report) = compact(&history, &policy, now_ms)?;
+    if report.reclaimed_bytes > 0 {
+        log::info!(
+            "Compacted version history: {} -> {} snapshots, {} bytes reclaimed",
+            report.snapshots_before,
+            report.snapshots_after,
+            report.reclaimed_bytes
+        );
+    }
+    Ok(CompactedHistory { history, report })
+}
+
+/// Summarise the changes since `since` (ms since the epoch) for the digest.
+#[tauri::command]
+pub fn build_digest(history: Value, since: i64) -> Result<Digest, String> {
+    digest(&history, since)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn doc(shapes: Value) -> Value {
+        json!({
+            "version": "1.0.0",
+            "appName": "napkin",
+            "shapes": shapes,
+            "viewport": { "x": 0, "y": 0, "zoom": 1 },
+            "metadata": { "created": "", "modified": "", "title": "Board" }
+        })
+    }
+
+    fn rect(id: &str, x: i64) -> Value {
+        json!({ "id": id, "type": "rectangle", "x": x, "y": 0, "width": 10, "height": 10 })
+    }
+
+    fn snapshot(day: u32, full: Option<Value>, delta: Option<Value>) -> Value {
+        let mut s = json!({
+            "id": format!("snapshot_{}", day),
+            "timestamp": format!("2026-01-{:02}T12:00:00.000Z", day),
+            "summary": "original",
+            "activeDocumentIndex": 0
+        });
+        if let Some(full) = full {
+            s["fullState"] = json!([full]);
+        }
+        if let Some(delta) = delta {
+            s["delta"] = json!({ "documents": [delta] });
+        }
+        s
+    }
+
+    /// Day 1 baseline with `a`, day 2 adds `b`, day 3 changes nothing,
+    /// day 4 moves `a`.
+    fn history() -> Value {
+        json!({
+            "maxSnapshots": 50,
+            "baselineInterval": 10,
+            "snapshots": [
+                snapshot(1, Some(doc(json!([rect("a", 0)]))), None),
+                snapshot(2, None, Some(json!({ "index": 0, "added": [rect("b", 50)], "removed": [], "modified": [] }))),
+                snapshot(3, None, Some(json!({ "index": 0, "added": [], "removed": [], "modified": [] }))),
+                snapshot(4, None, Some(json!({ "index": 0, "added": [], "removed": [], "modified": [{ "id": "a", "changes": { "x": 99 } }] }))),
+            ]
+        })
+    }
+
+    fn final_docs(history: &Value) -> Vec<Value> {
+        let snaps = history["snapshots"].as_array().unwrap();
+        revisions(snaps).unwrap().pop().unwrap().docs
+    }
+
+    fn unlimited() -> RetentionPolicy {
+        RetentionPolicy {
+            max_revisions: 100,
+            max_age_days: None,
+            max_bytes: None,
+        }
+    }
+
+    fn now() -> i64 {
+        parse_timestamp("2026-01-10T00:00:00.000Z").unwrap()
+    }
+
+    #[test]
+    fn no_op_snapshots_are_consolidated() {
+        let (compacted, report) = compact(&history(), &unlimited(), now()).unwrap();
+        assert_eq!(report.snapshots_before, 4);
+        assert_eq!(report.snapshots_after, 3);
+        assert!(report.reclaimed_bytes > 0);
+        assert_eq!(final_docs(&compacted), final_docs(&history()));
+        assert_eq!(final_docs(&compacted)[0]["shapes"][0]["x"], 99);
+    }
+
+    #[test]
+    fn revision_limit_rebases_on_a_new_baseline() {
+        let policy = RetentionPolicy {
+            max_revisions: 1,
+            ..unlimited()
+        };
+        let (compacted, report) = compact(&history(), &policy, now()).unwrap();
+        assert_eq!(report.snapshots_after, 1);
+        let only = &compacted["snapshots"][0];
+        assert_eq!(only["id"], "snapshot_4");
+        assert_eq!(only["fullState"][0]["shapes"].as_array().unwrap().len(), 2);
+        assert!(only.get("delta").is_none());
+    }
+
+    #[test]
+    fn age_limit_keeps_newest_and_resummarises() {
+        let policy = RetentionPolicy {
+            max_age_days: Some(7),
+            ..unlimited()
+        };
+        // Cutoff is Jan 3: days 1 and 2 go, day 3 was a no-op.
+        let (compacted, _) = compact(&history(), &policy, now()).unwrap();
+        let snaps = compacted["snapshots"].as_array().unwrap();
+        assert_eq!(snaps.len(), 1);
+        assert_eq!(snaps[0]["id"], "snapshot_4");
+
+        let far_future = parse_timestamp("2027-01-01T00:00:00Z").unwrap();
+        let (compacted, _) = compact(&history(), &policy, far_future).unwrap();
+        assert_eq!(compacted["snapshots"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn dropped_predecessor_updates_summary() {
+        // With day 2 also a no-op, day 4 directly follows day 1 and its
+        // stored summary no longer describes its delta.
+        let mut h = history();
+        h["snapshots"][1]["delta"]["documents"][0]["added"] = json!([]);
+        let (compacted, _) = compact(&h, &unlimited(), now()).unwrap();
+        let snaps = compacted["snapshots"].as_array().unwrap();
+        assert_eq!(snaps.len(), 2);
+        assert_eq!(snaps[1]["summary"], "~1 shapes");
+    }
+
+    #[test]
+    fn byte_budget_drops_oldest() {
+        let (full, _) = compact(&history(), &unlimited(), now()).unwrap();
+        let policy = RetentionPolicy {
+            max_bytes: Some(serialized_len(&full) as u64 - 1),
+            ..unlimited()
+        };
+        let (compacted, report) = compact(&history(), &policy, now()).unwrap();
+        assert!(report.snapshots_after < 3);
+        assert_eq!(final_docs(&compacted), final_docs(&history()));
+    }
+
+    #[test]
+    fn timestamps_parse_as_utc() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00.000Z"), Some(0));
+        assert_eq!(
+            parse_timestamp("1970-01-02T00:00:01.500Z"),
+            Some(DAY_MS + 1500)
+        );
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn digest_compares_with_the_last_snapshot_before_the_period() {
+        // Since Jan 3: the day 4 move, against day 2's `a` and `b`.
+        let since = parse_timestamp("2026-01-03T00:00:00Z").unwrap();
+        let changes = digest(&history(), since).unwrap();
+        assert_eq!(changes.snapshots, 2);
+        assert_eq!(
+            changes.documents,
+            [DocumentDigest {
+                title: "Board".to_string(),
+                added: 0,
+                removed: 0,
+                modified: 1,
+            }]
+        );
+        assert_eq!(changes.summary, "1 board changed: Board (~1)");
+
+        // Everything is new when the history starts inside the period.
+        let changes = digest(&history(), 0).unwrap();
+        assert_eq!(changes.documents[0].added, 2);
+
+        let quiet = digest(&history(), now()).unwrap();
+        assert_eq!(quiet.snapshots, 0);
+        assert_eq!(quiet.summary, "No changes");
+    }
+
+    #[test]
+    fn delta_without_baseline_is_rejected() {
+        let h = json!({ "snapshots": [snapshot(1, None, Some(json!({ "index": 0 })))] });
+        assert!(compact(&h, &unlimited(), now()).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! `get_shape_at_point`: which shape is at a point on the board.
+//!
+//! The board is read through the webview in stacking order and tested here
+//! with `geometry::hits`, so rotation and each shape's outline count the way
+//! they are drawn. Only the topmost shape comes back in full, with the ids
+//! of the shapes underneath it, so an agent can ask what is under a label
+//! or an arrow's end without taking in the whole canvas.
+
+use serde_json::{json, Value};
+
+use crate::api::SharedApiState;
+use crate::geometry;
+use crate::resources;
+
+/// How near a line or stroke a point must be to count, by default.
+const DEFAULT_TOLERANCE: f64 = 4.0;
+
+pub fn hit_test_tools() -> Vec<Value> {
+    vec![json!({
+        "name": "get_shape_at_point",
+        "description": "The topmost shape at a point of the board, as drawn: stacking order, rotation and each shape's outline (an ellipse's corners are not inside it) are taken into account, and lines and strokes count within tolerance. Returns the shape, or null, and the ids of the other shapes at the point, topmost first. Use excludeTypes to look past connectors, e.g. for the box under an arrow's label.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "x": { "type": "number", "description": "Canvas x coordinate" },
+                "y": { "type": "number", "description": "Canvas y coordinate" },
+                "tolerance": { "type": "number", "minimum": 0, "description": "How close to a line or stroke counts, in canvas pixels (default 4)" },
+                "excludeTypes": { "type": "array", "items": { "type": "string" }, "description": "Shape types to ignore, e.g. [\"arrow\", \"line\"]" },
+                "tabId": { "type": "string", "description": "Tab to read (default: the tab agents are working on)" }
+            },
+            "required": ["x", "y"],
+            "additionalProperties": false
+        }
+    })]
+}
+
+pub async fn call_hit_test_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "get_shape_at_point" => Some(get_shape_at_point(state, arguments).await),
+        _ => None,
+    }
+}
+
+/// Shapes at `point`, topmost first.
+fn shapes_at<'a>(
+    shapes: &'a [Value],
+    point: (f64, f64),
+    tolerance: f64,
+    excluded: &[&str],
+) -> Vec<&'a Value> {
+    shapes
+        .iter()
+        .rev()
+        .filter(|shape| {
+            let kind = shape.get("type").and_then(Value::as_str).unwrap_or("");
+            !excluded.contains(&kind)
+        })
+        .filter(|shape| geometry::hits(shape, point, tolerance))
+        .collect()
+}
+
+async fn get_shape_at_point(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let coordinate = |key: &str| {
+        arguments
+            .get(key)
+            .and_then(Value::as_f64)
+            .ok_or_else(|| format!("Missing required field: {}", key))
+    };
+    let point = (coordinate("x")?, coordinate("y")?);
+    let tolerance = match arguments.get("tolerance") {
+        None => DEFAULT_TOLERANCE,
+        Some(value) => value
+            .as_f64()
+            .filter(|t| *t >= 0.0)
+            .ok_or("tolerance must be a non-negative number")?,
+    };
+    let excluded: Vec<&str> = arguments
+        .get("excludeTypes")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+
+    let tab_id = arguments.get("tabId").and_then(Value::as_str);
+    let canvas = resources::canvas(state, tab_id).await?;
+    let shapes = canvas
+        .get("shapes")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let hits = shapes_at(shapes, point, tolerance, &excluded);
+    let below: Vec<&Value> = hits.iter().skip(1).filter_map(|s| s.get("id")).collect();
+    Ok(json!({
+        "shape": hits.first(),
+        "below": below,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_topmost_matching_shape_wins() {
+        let shapes = vec![
+            json!({ "id": "box", "type": "rectangle", "x": 0, "y": 0, "width": 100, "height": 100 }),
+            json!({ "id": "round", "type": "ellipse", "x": 0, "y": 0, "width": 100, "height": 100 }),
+            json!({ "id": "arrow", "type": "arrow", "x": 0, "y": 50, "x2": 200, "y2": 50 }),
+        ];
+        let ids = |hits: Vec<&Value>| -> Vec<String> {
+            hits.iter()
+                .map(|s| s["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(
+            ids(shapes_at(&shapes, (50.0, 50.0), 4.0, &[])),
+            ["arrow", "round", "box"]
+        );
+        assert_eq!(
+            ids(shapes_at(&shapes, (50.0, 50.0), 4.0, &["arrow"])),
+            ["round", "box"]
+        );
+        // The ellipse's corner is outside it
+        assert_eq!(ids(shapes_at(&shapes, (5.0, 5.0), 4.0, &[])), ["box"]);
+        assert!(shapes_at(&shapes, (150.0, 90.0), 4.0, &[]).is_empty());
+    }
+}
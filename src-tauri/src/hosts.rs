@@ -0,0 +1,276 @@
+//! `Host` header checks against DNS rebinding.
+//!
+//! The server listens on 127.0.0.1 by default, but a web page can still reach it
+//! by pointing a domain it controls at that address: the browser then treats
+//! the server as the page's own origin, so CORS does not stop it. Such
+//! requests name the attacker's domain in `Host`, so every request must name
+//! a loopback host (`localhost`, `127.0.0.1`, `[::1]`) or one of the
+//! `allowedHosts` in settings; anything else is refused with 403. With LAN
+//! access on (see `bind.rs`), an IP address is accepted too: a rebinding
+//! attack needs a domain name, and LAN clients often have nothing else.
+
+use axum::{
+    extract::{Request, State as AxumState},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::net::IpAddr;
+
+use crate::api::SharedApiState;
+use crate::settings;
+
+const LOOPBACK_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[::1]"];
+
+/// Split `host[:port]` into a lowercase name and the port, if any; `None`
+/// for a malformed IPv6 literal.
+fn split(host: &str) -> Option<(String, Option<&str>)> {
+    let host = host.trim();
+    let (name, port) = if host.starts_with('[') {
+        let (name, rest) = host.split_once(']')?;
+        let port = match rest {
+            "" => None,
+            rest => Some(rest.strip_prefix(':')?),
+        };
+        (&host[..=name.len()], port)
+    } else {
+        match host.rsplit_once(':') {
+            Some((name, port)) => (name, Some(port)),
+            None => (host, None),
+        }
+    };
+    // `localhost.` is the same host as `localhost`.
+    let name = name.strip_suffix('.').unwrap_or(name);
+    Some((name.to_ascii_lowercase(), port))
+}
+
+/// Whether `name` (as returned by `split`) is an IP address.
+fn is_ip_literal(name: &str) -> bool {
+    let name = name
+        .strip_prefix('[')
+        .and_then(|n| n.strip_suffix(']'))
+        .unwrap_or(name);
+    name.parse::<IpAddr>().is_ok()
+}
+
+/// Whether a request for `host` may be served: a loopback name on any
+/// port, an IP address when `lan` is on, or a configured host (on its port,
+/// when the entry has one).
+pub fn is_allowed(host: &str, configured: &[String], lan: bool) -> bool {
+    let Some((name, port)) = split(host) else {
+        return false;
+    };
+    if port.is_some_and(|p| p.parse::<u16>().is_err()) {
+        return false;
+    }
+    if LOOPBACK_HOSTS.contains(&name.as_str()) || (lan && is_ip_literal(&name)) {
+        return true;
+    }
+    configured.iter().any(|entry| match split(entry) {
+        None => false,
+        Some((allowed, _)) if allowed.is_empty() => false,
+        Some((allowed, None)) => allowed == name,
+        Some((allowed, Some(allowed_port))) => allowed == name && Some(allowed_port) == port,
+    })
+}
+
+/// The host a request was sent to: `Host`, or the URI authority (HTTP/2).
+fn request_host(request: &Request) -> Option<&str> {
+    request
+        .headers()
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| request.uri().authority().map(|a| a.as_str()))
+}
+
+/// Pass `request` on if its host is allowed, refuse it otherwise.
+pub async fn filter(request: Request, next: Next, configured: &[String], lan: bool) -> Response {
+    match request_host(&request) {
+        Some(host) if is_allowed(host, configured, lan) => next.run(request).await,
+        host => {
+            log::warn!("Refused request for host {:?}", host.unwrap_or(""));
+            (StatusCode::FORBIDDEN, "Host not allowed").into_response()
+        }
+    }
+}
+
+/// Middleware for the app's router; see `filter`.
+pub async fn require_allowed_host(
+    AxumState(state): AxumState<SharedApiState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let settings = settings::current(&state.app_handle);
+    filter(
+        request,
+        next,
+        &settings.allowed_hosts,
+        settings.mcp_bind.lan,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn hosts(list: &[&str]) -> Vec<String> {
+        list.iter().map(|h| h.to_string()).collect()
+    }
+
+    #[test]
+    fn loopback_hosts_are_allowed_on_any_port() {
+        for host in [
+            "localhost",
+            "localhost:21420",
+            "LocalHost:21420",
+            "localhost.:21420",
+            "127.0.0.1:21421",
+            "[::1]:21420",
+            "[::1]",
+        ] {
+            assert!(is_allowed(host, &[], false), "{}", host);
+        }
+    }
+
+    #[test]
+    fn rebound_domains_are_refused() {
+        for host in [
+            "evil.example:21420",
+            "evil.example",
+            "localhost.evil.example:21420",
+            "127.0.0.1.evil.example",
+            "127.0.0.2:21420",
+            "0.0.0.0:21420",
+            "localhost:21420:80",
+            "localhost:http",
+            "[::1].evil.example",
+            "[::1",
+            "",
+        ] {
+            assert!(!is_allowed(host, &[], false), "{}", host);
+        }
+    }
+
+    #[test]
+    fn configured_hosts_match_name_and_optional_port() {
+        let configured = hosts(&["Napkin.lan", "dev.box:8080", " "]);
+        assert!(is_allowed("napkin.lan:21420", &configured, false));
+        assert!(is_allowed("napkin.lan", &configured, false));
+        assert!(is_allowed("dev.box:8080", &configured, false));
+        assert!(!is_allowed("dev.box:21420", &configured, false));
+        assert!(!is_allowed("dev.box", &configured, false));
+        assert!(!is_allowed("napkin.lan.evil.example", &configured, false));
+    }
+
+    #[test]
+    fn lan_mode_accepts_ip_addresses_only() {
+        for host in ["192.168.1.20:21420", "10.0.0.7", "[fe80::1]:21420"] {
+            assert!(is_allowed(host, &[], true), "{}", host);
+            assert!(!is_allowed(host, &[], false), "{}", host);
+        }
+        for host in [
+            "evil.example:21420",
+            "192.168.1.20.evil.example",
+            "[fe80::1",
+        ] {
+            assert!(!is_allowed(host, &[], true), "{}", host);
+        }
+    }
+
+    #[tokio::test]
+    async fn router_refuses_requests_for_other_hosts() {
+        let configured = hosts(&["napkin.lan"]);
+        let app = Router::new()
+            .route("/mcp", get(|| async { "ok" }))
+            .layer(middleware::from_fn(move |request, next| {
+                let configured = configured.clone();
+                async move { filter(request, next, &configured, false).await }
+            }));
+        let status = |host: Option<&'static str>| {
+            let app = app.clone();
+            async move {
+                let mut request = Request::builder().uri("/mcp");
+                if let Some(host) = host {
+                    request = request.header(header::HOST, host);
+                }
+                let response = app
+                    .oneshot(request.body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                response.status()
+            }
+        };
+        assert_eq!(status(Some("127.0.0.1:21420")).await, StatusCode::OK);
+        assert_eq!(status(Some("napkin.lan:21420")).await, StatusCode::OK);
+        assert_eq!(
+            status(Some("attacker.example:21420")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status(None).await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn default_settings_refuse_dns_rebinding() {
+        // A page on attacker.example that rebinds its name to 127.0.0.1
+        // reaches the server with its own name in Host.
+        let defaults = settings::Settings::default();
+        assert!(defaults.allowed_hosts.is_empty());
+        assert!(!defaults.mcp_bind.lan);
+        let app = Router::new()
+            .route("/mcp", get(|| async { "ok" }))
+            .layer(middleware::from_fn(move |request, next| {
+                let defaults = defaults.clone();
+                async move {
+                    filter(
+                        request,
+                        next,
+                        &defaults.allowed_hosts,
+                        defaults.mcp_bind.lan,
+                    )
+                    .await
+                }
+            }));
+        let status = |uri: &'static str, host: Option<&'static str>| {
+            let app = app.clone();
+            async move {
+                let mut request = Request::builder().uri(uri);
+                if let Some(host) = host {
+                    request = request.header(header::HOST, host);
+                }
+                let response = app
+                    .oneshot(request.body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                response.status()
+            }
+        };
+        for host in ["localhost:21420", "127.0.0.1:21420", "[::1]:21420"] {
+            assert_eq!(status("/mcp", Some(host)).await, StatusCode::OK, "{}", host);
+        }
+        for host in [
+            "attacker.example:21420",
+            "attacker.example",
+            "localhost.attacker.example:21420",
+            "192.168.1.20:21420",
+        ] {
+            assert_eq!(
+                status("/mcp", Some(host)).await,
+                StatusCode::FORBIDDEN,
+                "{}",
+                host
+            );
+        }
+        // Without Host (HTTP/2), the authority in the URI is checked instead
+        assert_eq!(
+            status("http://attacker.example:21420/mcp", None).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status("http://localhost:21420/mcp", None).await,
+            StatusCode::OK
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Document integrity checks for connector bindings.
+//!
+//! Lines and arrows can carry `bindStart`/`bindEnd` references to other
+//! shapes. When the referenced shape is deleted outside the select tool (MCP,
+//! a hand-edited file, an old save) the binding dangles. `plan_repair` finds
+//! those and decides what to change according to the configured policy; the
+//! caller applies the plan.
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::settings::{self, BindingRepairPolicy};
+use serde::Serialize;
+use std::collections::HashSet;
+
+const BINDING_KEYS: [(&str, &str); 2] = [("bindStart", "start"), ("bindEnd", "end")];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BindingFix {
+    pub shape_id: String,
+    /// "start" or "end"
+    pub end: &'static str,
+    pub missing_shape_id: String,
+    pub action: BindingRepairPolicy,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShapeUpdate {
+    pub id: String,
+    pub changes: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepairPlan {
+    pub fixes: Vec<BindingFix>,
+    pub updates: Vec<ShapeUpdate>,
+    pub removed_ids: Vec<String>,
+}
+
+pub fn plan_repair(shapes: &[serde_json::Value], policy: BindingRepairPolicy) -> RepairPlan {
+    let ids: HashSet<&str> = shapes
+        .iter()
+        .filter_map(|s| s.get("id").and_then(|id| id.as_str()))
+        .collect();
+
+    let mut plan = RepairPlan::default();
+    for shape in shapes {
+        let Some(id) = shape.get("id").and_then(|id| id.as_str()) else {
+            continue;
+        };
+        let mut changes = serde_json::Map::new();
+        for (key, end) in BINDING_KEYS {
+            let Some(target) = shape
+                .get(key)
+                .and_then(|b| b.get("shapeId"))
+                .and_then(|t| t.as_str())
+            else {
+                continue;
+            };
+            if ids.contains(target) {
+                continue;
+            }
+            plan.fixes.push(BindingFix {
+                shape_id: id.to_string(),
+                end,
+                missing_shape_id: target.to_string(),
+                action: policy,
+            });
+            changes.insert(key.to_string(), serde_json::Value::Null);
+        }
+
+        if changes.is_empty() {
+            continue;
+        }
+        match policy {
+            BindingRepairPolicy::Unbind => plan.updates.push(ShapeUpdate {
+                id: id.to_string(),
+                changes: serde_json::Value::Object(changes),
+            }),
+            BindingRepairPolicy::Remove => plan.removed_ids.push(id.to_string()),
+        }
+    }
+    plan
+}
+
+// --- Tauri command ---
+
+/// Check `shapes` for dangling bindings and return the repair plan for the
+/// frontend to apply. Uses the configured policy unless one is given.
+#[tauri::command]
+pub fn repair_document(
+    shapes: Vec<serde_json::Value>,
+    policy: Option<BindingRepairPolicy>,
+    app: tauri::AppHandle,
+) -> RepairPlan {
+    let policy = policy.unwrap_or_else(|| settings::current(&app).binding_repair_policy);
+    plan_repair(&shapes, policy)
+}
+
+// --- MCP tool (handled in Rust, applied through the bridge) ---
+
+pub fn integrity_tools() -> Vec<serde_json::Value> {
+    vec![serde_json::json!({
+        "name": "repair_document",
+        "description": "Find lines/arrows bound to shapes that no longer exist and fix them (unbind or remove, per the user's setting). Returns what was fixed.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "dryRun": { "type": "boolean", "description": "Report problems without changing anything" }
+            }
+        }
+    })]
+}
+
+pub async fn call_integrity_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &serde_json::Value,
+) -> Option<Result<serde_json::Value, String>> {
+    match tool_name {
+        "repair_document" => {
+            let dry_run = arguments
+                .get("dryRun")
+                .and_then(|d| d.as_bool())
+                .unwrap_or(false);
+            Some(repair_via_bridge(state, dry_run).await)
+        }
+        _ => None,
+    }
+}
+
+async fn repair_via_bridge(
+    state: &SharedApiState,
+    dry_run: bool,
+) -> Result<serde_json::Value, String> {
+    let canvas = bridge_tool_call(state, "get_canvas", serde_json::json!({})).await?;
+    if let Some(err) = canvas.get("error").and_then(|e| e.as_str()) {
+        return Err(err.to_string());
+    }
+    let shapes = canvas
+        .get("shapes")
+        .and_then(|s| s.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let policy = settings::current(&state.app_handle).binding_repair_policy;
+    let plan = plan_repair(&shapes, policy);
+
+    if !dry_run {
+        for update in &plan.updates {
+            let mut args = update.changes.clone();
+            args["id"] = serde_json::Value::String(update.id.clone());
+            bridge_tool_call(state, "update_shape", args).await?;
+        }
+        for id in &plan.removed_ids {
+            bridge_tool_call(state, "delete_shape", serde_json::json!({ "id": id })).await?;
+        }
+    }
+
+    Ok(serde_json::json!({
+        "dryRun": dry_run,
+        "fixed": if dry_run { 0 } else { plan.fixes.len() },
+        "fixes": plan.fixes,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shapes() -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({ "id": "a", "type": "rectangle" }),
+            serde_json::json!({
+                "id": "arrow1", "type": "arrow",
+                "bindStart": { "shapeId": "a", "point": "center" },
+                "bindEnd": { "shapeId": "gone", "point": "center" }
+            }),
+            serde_json::json!({
+                "id": "line1", "type": "line",
+                "bindStart": { "shapeId": "a", "point": "top" }
+            }),
+        ]
+    }
+
+    #[test]
+    fn unbind_policy_clears_only_dangling_end() {
+        let plan = plan_repair(&shapes(), BindingRepairPolicy::Unbind);
+        assert_eq!(plan.fixes.len(), 1);
+        assert_eq!(plan.fixes[0].end, "end");
+        assert_eq!(plan.fixes[0].missing_shape_id, "gone");
+        assert_eq!(
+            plan.updates,
+            vec![ShapeUpdate {
+                id: "arrow1".into(),
+                changes: serde_json::json!({ "bindEnd": null }),
+            }]
+        );
+        assert!(plan.removed_ids.is_empty());
+    }
+
+    #[test]
+    fn remove_policy_deletes_connector() {
+        let plan = plan_repair(&shapes(), BindingRepairPolicy::Remove);
+        assert_eq!(plan.removed_ids, vec!["arrow1".to_string()]);
+        assert!(plan.updates.is_empty());
+    }
+
+    #[test]
+    fn intact_document_needs_no_repair() {
+        let mut shapes = shapes();
+        shapes.push(serde_json::json!({ "id": "gone", "type": "ellipse" }));
+        assert_eq!(
+            plan_repair(&shapes, BindingRepairPolicy::Unbind),
+            RepairPlan::default()
+        );
+    }
+}
@@ -0,0 +1,388 @@
+//! Tool calls run in the background.
+//!
+//! `submit_job` starts any other tool and returns a job id at once, so a
+//! call that takes a while (a large `create_image`, `trace_image`, a
+//! `reorganize` of thousands of shapes) does not hold an HTTP request open
+//! or hit the bridge timeout. Clients poll `get_job_status` and read the
+//! outcome with `get_job_result`. A job runs as a `tools/call` from the
+//! client that submitted it, so read-only mode, hidden boards, tool
+//! profiles, approvals, the canvas lock and the audit log apply as usual;
+//! only the wait for the webview is longer ([`JOB_TIMEOUT_SECS`]). The last
+//! [`MAX_FINISHED_JOBS`] finished jobs are kept.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::api::{self, SharedApiState};
+use crate::embed;
+use crate::read_only::PERMISSION_DENIED;
+use crate::session::McpClient;
+use crate::settings;
+use crate::tool_errors::{ErrorCode, ToolError};
+
+/// How long a job waits for the webview to answer each bridged call.
+pub const JOB_TIMEOUT_SECS: u64 = 600;
+/// Finished jobs kept for `get_job_result`; older ones are forgotten.
+const MAX_FINISHED_JOBS: usize = 50;
+
+const JOB_TOOLS: &[&str] = &["submit_job", "get_job_status", "get_job_result"];
+
+tokio::task_local! {
+    /// Set while a job runs.
+    static IN_JOB: ();
+}
+
+/// How long to wait for the webview: `default`, or longer inside a job.
+pub fn bridge_timeout_secs(default: u64) -> u64 {
+    match IN_JOB.try_with(|_| ()) {
+        Ok(()) => JOB_TIMEOUT_SECS,
+        Err(_) => default,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+struct Job {
+    tool: String,
+    submitted_at: u64,
+    finished_at: Option<u64>,
+    outcome: Option<Result<Value, ToolError>>,
+}
+
+impl Job {
+    fn status(&self) -> JobStatus {
+        match &self.outcome {
+            None => JobStatus::Running,
+            Some(Ok(_)) => JobStatus::Succeeded,
+            Some(Err(_)) => JobStatus::Failed,
+        }
+    }
+
+    fn describe(&self, id: &str) -> Value {
+        let mut status = json!({
+            "jobId": id,
+            "tool": self.tool,
+            "status": self.status(),
+            "submittedAt": self.submitted_at,
+        });
+        if let Some(finished_at) = self.finished_at {
+            status["finishedAt"] = json!(finished_at);
+        }
+        status
+    }
+}
+
+/// A submitted job on its way to [`run_jobs`].
+pub struct QueuedJob {
+    id: String,
+    tool: String,
+    arguments: Value,
+    client: McpClient,
+}
+
+pub struct JobRegistry {
+    jobs: HashMap<String, Job>,
+    runner: mpsc::UnboundedSender<QueuedJob>,
+}
+
+impl JobRegistry {
+    /// A registry, and the queue to hand to [`run_jobs`].
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<QueuedJob>) {
+        let (runner, queue) = mpsc::unbounded_channel();
+        let registry = Self {
+            jobs: HashMap::new(),
+            runner,
+        };
+        (registry, queue)
+    }
+
+    fn start(&mut self, job: QueuedJob, now: u64) -> Result<(), String> {
+        let (id, tool) = (job.id.clone(), job.tool.clone());
+        self.runner
+            .send(job)
+            .map_err(|_| "Jobs are not running".to_string())?;
+        self.jobs.insert(
+            id,
+            Job {
+                tool,
+                submitted_at: now,
+                finished_at: None,
+                outcome: None,
+            },
+        );
+        Ok(())
+    }
+
+    fn finish(&mut self, id: &str, outcome: Result<Value, ToolError>, now: u64) {
+        if let Some(job) = self.jobs.get_mut(id) {
+            job.finished_at = Some(now);
+            job.outcome = Some(outcome);
+        }
+        let mut finished: Vec<(u64, String)> = self
+            .jobs
+            .iter()
+            .filter_map(|(id, job)| Some((job.finished_at?, id.clone())))
+            .collect();
+        if finished.len() > MAX_FINISHED_JOBS {
+            finished.sort();
+            for (_, id) in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+                self.jobs.remove(id);
+            }
+        }
+    }
+
+    fn status(&self, id: &str) -> Result<Value, String> {
+        self.jobs
+            .get(id)
+            .map(|job| job.describe(id))
+            .ok_or_else(|| format!("Job not found: {}", id))
+    }
+
+    /// A finished job's result. A failed job's is `{ error, code }`, which
+    /// `tools/call` reports as the failure it was.
+    fn result(&self, id: &str) -> Result<Value, String> {
+        let job = self
+            .jobs
+            .get(id)
+            .ok_or_else(|| format!("Job not found: {}", id))?;
+        match &job.outcome {
+            None => Err(format!(
+                "Job {} is still running; poll get_job_status until it finishes",
+                id
+            )),
+            Some(Ok(result)) => Ok(result.clone()),
+            Some(Err(error)) => Ok(json!({ "error": error.message, "code": error.code })),
+        }
+    }
+}
+
+/// The outcome of a `tools/call` `reply`.
+fn outcome(reply: &Value) -> Result<Value, ToolError> {
+    if let Some(error) = reply.get("error") {
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("Request refused");
+        let code = match error.get("code").and_then(Value::as_i64) {
+            Some(PERMISSION_DENIED) => ErrorCode::PermissionDenied,
+            _ => ErrorCode::InvalidArgument,
+        };
+        return Err(ToolError {
+            code,
+            message: message.to_string(),
+        });
+    }
+    let result = reply.get("result").cloned().unwrap_or(Value::Null);
+    let text = result
+        .pointer("/content/0/text")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    if result.get("isError").and_then(Value::as_bool) == Some(true) {
+        let mut error = ToolError::new(text);
+        if let Some(code) = result.pointer("/structuredContent/code") {
+            error.code = serde_json::from_value(code.clone()).unwrap_or(error.code);
+        }
+        return Err(error);
+    }
+    Ok(match result.get("structuredContent") {
+        Some(structured) => structured.clone(),
+        None => serde_json::from_str(text).unwrap_or_else(|_| json!(text)),
+    })
+}
+
+/// Run each job from `queue` as it is submitted. Jobs reach this task through
+/// a queue, rather than being spawned by `submit_job`, because a job is
+/// itself a `tools/call`.
+pub fn run_jobs(state: SharedApiState, mut queue: mpsc::UnboundedReceiver<QueuedJob>) {
+    tauri::async_runtime::spawn(async move {
+        while let Some(job) = queue.recv().await {
+            tauri::async_runtime::spawn(run(Arc::clone(&state), job));
+        }
+    });
+}
+
+async fn run(state: SharedApiState, job: QueuedJob) {
+    let QueuedJob {
+        id,
+        tool,
+        arguments,
+        client,
+    } = job;
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": { "name": tool, "arguments": arguments },
+    });
+    let reply = IN_JOB
+        .scope((), api::handle_mcp_message(&state, request, &client))
+        .await
+        .unwrap_or(Value::Null);
+    let outcome = outcome(&reply);
+    if let Err(error) = &outcome {
+        log::info!("Job {} ({}) failed: {}", id, tool, error.message);
+    }
+    state
+        .jobs
+        .lock()
+        .await
+        .finish(&id, outcome, embed::now_secs());
+}
+
+pub fn job_tools() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "submit_job",
+            "description": "Start another tool in the background and return a jobId at once, instead of waiting for it. Use it for calls that can take long: create_image with a large image, trace_image, cleanup_photo, or reorganize/batch_operations on thousands of shapes. Poll get_job_status, then read the outcome with get_job_result. The tool runs with the same permissions and checks as calling it directly.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "tool": { "type": "string", "description": "Name of the tool to run" },
+                    "arguments": { "type": "object", "description": "The tool's arguments" }
+                },
+                "required": ["tool"],
+                "additionalProperties": false
+            }
+        }),
+        json!({
+            "name": "get_job_status",
+            "description": "Whether a submitted job is running, succeeded or failed, with when it was submitted and finished (Unix seconds).",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "jobId": { "type": "string", "description": "ID returned by submit_job" }
+                },
+                "required": ["jobId"],
+                "additionalProperties": false
+            }
+        }),
+        json!({
+            "name": "get_job_result",
+            "description": "The result of a finished job, exactly as its tool would have returned it. If the job failed, this call fails with the same error and code. Fails while the job is still running.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "jobId": { "type": "string", "description": "ID returned by submit_job" }
+                },
+                "required": ["jobId"],
+                "additionalProperties": false
+            }
+        }),
+    ]
+}
+
+pub async fn call_job_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+    client: &McpClient,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "submit_job" => Some(submit_job(state, arguments, client).await),
+        "get_job_status" => Some(match job_id(arguments) {
+            Ok(id) => state.jobs.lock().await.status(id),
+            Err(e) => Err(e),
+        }),
+        "get_job_result" => Some(match job_id(arguments) {
+            Ok(id) => state.jobs.lock().await.result(id),
+            Err(e) => Err(e),
+        }),
+        _ => None,
+    }
+}
+
+fn job_id(arguments: &Value) -> Result<&str, String> {
+    arguments
+        .get("jobId")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "Missing required field: jobId".to_string())
+}
+
+async fn submit_job(
+    state: &SharedApiState,
+    arguments: &Value,
+    client: &McpClient,
+) -> Result<Value, String> {
+    let tool = arguments
+        .get("tool")
+        .and_then(Value::as_str)
+        .ok_or("Missing required field: tool")?;
+    let exposure = settings::current(&state.app_handle).tool_exposure;
+    let tool = exposure.unprefixed(tool, api::is_known_tool);
+    if !api::is_known_tool(tool) {
+        return Err(format!("Unknown tool: {}", tool));
+    }
+    if JOB_TOOLS.contains(&tool) {
+        return Err(format!("{} cannot run as a job", tool));
+    }
+    let tool_arguments = arguments.get("arguments").cloned().unwrap_or(json!({}));
+
+    let job = QueuedJob {
+        id: uuid::Uuid::new_v4().to_string(),
+        tool: tool.to_string(),
+        arguments: tool_arguments,
+        client: client.detached(),
+    };
+    let id = job.id.clone();
+    let mut jobs = state.jobs.lock().await;
+    jobs.start(job, embed::now_secs())?;
+    jobs.status(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: &str, tool: &str) -> QueuedJob {
+        QueuedJob {
+            id: id.to_string(),
+            tool: tool.to_string(),
+            arguments: json!({}),
+            client: McpClient::default(),
+        }
+    }
+
+    #[test]
+    fn jobs_report_their_outcome() {
+        let (mut jobs, _queue) = JobRegistry::new();
+        jobs.start(job("j1", "reorganize"), 10).unwrap();
+        assert_eq!(jobs.status("j1").unwrap()["status"], "running");
+        assert!(jobs.result("j1").unwrap_err().contains("still running"));
+
+        let reply = json!({ "result": { "content": [{ "type": "text", "text": "{}" }], "structuredContent": { "moved": 3 } } });
+        jobs.finish("j1", outcome(&reply), 12);
+        assert_eq!(jobs.status("j1").unwrap()["finishedAt"], 12);
+        assert_eq!(jobs.result("j1").unwrap(), json!({ "moved": 3 }));
+
+        jobs.start(job("j2", "get_shape"), 13).unwrap();
+        let reply = json!({ "result": ToolError::new("Shape not found: s1").call_result() });
+        jobs.finish("j2", outcome(&reply), 14);
+        assert_eq!(jobs.status("j2").unwrap()["status"], "failed");
+        assert_eq!(jobs.result("j2").unwrap()["code"], "SHAPE_NOT_FOUND");
+
+        assert!(jobs.status("j3").is_err());
+    }
+
+    #[test]
+    fn only_recent_finished_jobs_are_kept() {
+        let (mut jobs, _queue) = JobRegistry::new();
+        jobs.start(job("running", "trace_image"), 0).unwrap();
+        for n in 0..=MAX_FINISHED_JOBS as u64 {
+            let id = format!("j{}", n);
+            jobs.start(job(&id, "get_canvas"), n).unwrap();
+            jobs.finish(&id, Ok(json!({})), n + 1);
+        }
+        assert!(jobs.status("j0").is_err());
+        assert!(jobs.status("j1").is_ok());
+        assert!(jobs.status("running").is_ok());
+    }
+}
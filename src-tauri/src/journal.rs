@@ -0,0 +1,591 @@
+//! Event-sourced document storage.
+//!
+//! A journaled `.napkin` file is a SQLite database holding the document's
+//! history instead of its current state: a `replace` event with the
+//! collection it started from, then one `changes` event per save that changed
+//! something (in the `docstore::DocumentChanges` format). Every
+//! `SNAPSHOT_EVERY` events the replayed collection is stored as a snapshot, so
+//! loading replays from the latest snapshot rather than from the first event.
+//!
+//! Each event's hash covers its content and the previous event's hash, so
+//! editing, removing or reordering past events breaks the chain. Loading
+//! warns when it does; `export_event_log` writes the whole log with the
+//! verification result.
+
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::docstore::{self, DocumentChange, DocumentChanges};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS journal (
+        seq INTEGER PRIMARY KEY,
+        at INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        hash TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS snapshots (seq INTEGER PRIMARY KEY, json TEXT NOT NULL);
+";
+
+/// Events between snapshots.
+const SNAPSHOT_EVERY: i64 = 100;
+
+struct Event {
+    seq: i64,
+    at: i64,
+    kind: String,
+    payload: String,
+    hash: String,
+}
+
+fn sql_err(e: rusqlite::Error) -> String {
+    format!("Document journal error: {}", e)
+}
+
+fn sha256_hex(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+        hasher.update(b"\n");
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn event_hash(previous: &str, seq: i64, at: i64, kind: &str, payload: &str) -> String {
+    sha256_hex(&[
+        previous.as_bytes(),
+        seq.to_string().as_bytes(),
+        at.to_string().as_bytes(),
+        kind.as_bytes(),
+        payload.as_bytes(),
+    ])
+}
+
+/// Whether `path` is a journaled document.
+pub fn is_journal(path: &Path) -> bool {
+    docstore::is_sqlite(path)
+        && Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .ok()
+            .and_then(|conn| {
+                conn.query_row(
+                    "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'journal'",
+                    [],
+                    |_| Ok(()),
+                )
+                .optional()
+                .ok()
+                .flatten()
+            })
+            .is_some()
+}
+
+fn open(path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(path).map_err(sql_err)?;
+    conn.execute_batch(SCHEMA).map_err(sql_err)?;
+    Ok(conn)
+}
+
+fn events(conn: &Connection, after: i64) -> Result<Vec<Event>, String> {
+    let mut stmt = conn
+        .prepare("SELECT seq, at, kind, payload, hash FROM journal WHERE seq > ?1 ORDER BY seq")
+        .map_err(sql_err)?;
+    let rows = stmt
+        .query_map(params![after], |r| {
+            Ok(Event {
+                seq: r.get(0)?,
+                at: r.get(1)?,
+                kind: r.get(2)?,
+                payload: r.get(3)?,
+                hash: r.get(4)?,
+            })
+        })
+        .map_err(sql_err)?;
+    rows.collect::<Result<_, _>>().map_err(sql_err)
+}
+
+fn append(conn: &Connection, kind: &str, payload: &Value) -> Result<i64, String> {
+    let head: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT seq, hash FROM journal ORDER BY seq DESC LIMIT 1",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()
+        .map_err(sql_err)?;
+    let (seq, previous) = head.map_or((1, String::new()), |(seq, hash)| (seq + 1, hash));
+    let at = chrono::Utc::now().timestamp_millis();
+    let payload = payload.to_string();
+    let hash = event_hash(&previous, seq, at, kind, &payload);
+    conn.execute(
+        "INSERT INTO journal (seq, at, kind, payload, hash) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![seq, at, kind, payload, hash],
+    )
+    .map_err(sql_err)?;
+    Ok(seq)
+}
+
+/// The first event whose hash or position does not check out.
+fn first_broken(events: &[Event]) -> Option<i64> {
+    let mut previous = String::new();
+    for (i, event) in events.iter().enumerate() {
+        let expected = event_hash(&previous, event.seq, event.at, &event.kind, &event.payload);
+        if event.seq != i as i64 + 1 || event.hash != expected {
+            return Some(event.seq);
+        }
+        previous = event.hash.clone();
+    }
+    None
+}
+
+/// Apply `changes` to a collection the way `docstore` applies them to rows.
+fn apply_to(collection: &mut Value, changes: &DocumentChanges) {
+    let Some(obj) = collection.as_object_mut() else {
+        return;
+    };
+    for (key, value) in changes.collection.iter().flatten() {
+        if key != "documents" {
+            obj.insert(key.clone(), value.clone());
+        }
+    }
+    let Some(docs) = obj
+        .entry("documents")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+    else {
+        return;
+    };
+    if let Some(count) = changes.document_count {
+        docs.truncate(count);
+    }
+    for change in &changes.documents {
+        while docs.len() <= change.index {
+            docs.push(json!({ "shapes": [] }));
+        }
+        let doc = &mut docs[change.index];
+        if let Some(Value::Object(document)) = &change.document {
+            let shapes = doc.get("shapes").cloned().unwrap_or_else(|| json!([]));
+            let mut document = document.clone();
+            document.insert("shapes".to_string(), shapes);
+            *doc = Value::Object(document);
+        }
+        if !doc.get("shapes").is_some_and(Value::is_array) {
+            doc["shapes"] = json!([]);
+        }
+        let Some(shapes) = doc["shapes"].as_array_mut() else {
+            continue;
+        };
+        shapes.retain(|s| {
+            docstore::shape_id(s).map_or(true, |id| !change.removed.iter().any(|r| r == id))
+        });
+        for shape in &change.upserts {
+            let Some(id) = docstore::shape_id(shape) else {
+                continue;
+            };
+            match shapes
+                .iter_mut()
+                .find(|s| docstore::shape_id(s) == Some(id))
+            {
+                Some(existing) => *existing = shape.clone(),
+                // New shapes go on top.
+                None => shapes.push(shape.clone()),
+            }
+        }
+        if let Some(order) = &change.order {
+            let position: HashMap<&str, usize> = order
+                .iter()
+                .enumerate()
+                .map(|(z, id)| (id.as_str(), z))
+                .collect();
+            shapes.sort_by_key(|s| {
+                docstore::shape_id(s)
+                    .and_then(|id| position.get(id).copied())
+                    .unwrap_or(usize::MAX)
+            });
+        }
+    }
+}
+
+fn shape_ids(shapes: &[Value]) -> Vec<&str> {
+    shapes.iter().filter_map(docstore::shape_id).collect()
+}
+
+/// The smallest changes turning `before` into `after`, or `None` when they
+/// are the same.
+fn diff(before: &Value, after: &Value) -> Option<DocumentChanges> {
+    let empty = Map::new();
+    let before_meta = before.as_object().unwrap_or(&empty);
+    let after_meta = after.as_object().unwrap_or(&empty);
+    let fields: Map<String, Value> = after_meta
+        .iter()
+        .filter(|(key, value)| *key != "documents" && before_meta.get(*key) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+
+    let documents = |c: &Value| c["documents"].as_array().cloned().unwrap_or_default();
+    let (old_docs, new_docs) = (documents(before), documents(after));
+    let mut changes = DocumentChanges {
+        collection: (!fields.is_empty()).then_some(fields),
+        document_count: (new_docs.len() < old_docs.len()).then_some(new_docs.len()),
+        documents: Vec::new(),
+    };
+
+    for (index, doc) in new_docs.iter().enumerate() {
+        let (row, shapes) = docstore::split_document(doc);
+        let (old_row, old_shapes) = old_docs
+            .get(index)
+            .map(docstore::split_document)
+            .unwrap_or_default();
+        let old_by_id: HashMap<&str, &Value> = old_shapes
+            .iter()
+            .filter_map(|s| Some((docstore::shape_id(s)?, s)))
+            .collect();
+        let (old_ids, new_ids) = (shape_ids(&old_shapes), shape_ids(&shapes));
+
+        let upserts: Vec<Value> = shapes
+            .iter()
+            .filter(|s| docstore::shape_id(s).is_some_and(|id| old_by_id.get(id) != Some(s)))
+            .cloned()
+            .collect();
+        let removed: Vec<String> = old_ids
+            .iter()
+            .filter(|id| !new_ids.contains(id))
+            .map(|id| id.to_string())
+            .collect();
+        // Where applying removals and upserts alone would leave the shapes.
+        let implied: Vec<&str> = old_ids
+            .iter()
+            .filter(|id| new_ids.contains(id))
+            .chain(new_ids.iter().filter(|id| !old_by_id.contains_key(*id)))
+            .copied()
+            .collect();
+        let order = (implied != new_ids).then(|| new_ids.iter().map(|id| id.to_string()).collect());
+        let document = if row != old_row {
+            serde_json::from_str(&row).ok()
+        } else {
+            None
+        };
+
+        if document.is_some() || !upserts.is_empty() || !removed.is_empty() || order.is_some() {
+            changes.documents.push(DocumentChange {
+                index,
+                document,
+                upserts,
+                removed,
+                order,
+            });
+        }
+    }
+
+    let unchanged = changes.collection.is_none()
+        && changes.document_count.is_none()
+        && changes.documents.is_empty();
+    (!unchanged).then_some(changes)
+}
+
+/// The latest snapshot whose content matches the digest recorded for it.
+fn latest_snapshot(conn: &Connection) -> Result<Option<(i64, Value)>, String> {
+    let row: Option<(i64, String, String)> = conn
+        .query_row(
+            "SELECT s.seq, s.json, j.payload FROM snapshots s JOIN journal j ON j.seq = s.seq
+             ORDER BY s.seq DESC LIMIT 1",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+        )
+        .optional()
+        .map_err(sql_err)?;
+    let Some((seq, json, payload)) = row else {
+        return Ok(None);
+    };
+    let recorded = serde_json::from_str::<Value>(&payload).unwrap_or_default();
+    if recorded["digest"] != sha256_hex(&[json.as_bytes()]) {
+        log::warn!(
+            "Journal snapshot {} does not match its digest; replaying from the start",
+            seq
+        );
+        return Ok(None);
+    }
+    let collection = serde_json::from_str(&json).map_err(|e| format!("Corrupt snapshot: {}", e))?;
+    Ok(Some((seq, collection)))
+}
+
+fn replay(conn: &Connection) -> Result<(Value, i64), String> {
+    let (mut collection, mut last) =
+        latest_snapshot(conn)?.map_or((Value::Null, 0), |(seq, c)| (c, seq));
+    let snapshot_seq = last;
+    for event in events(conn, last)? {
+        let payload: Value = serde_json::from_str(&event.payload)
+            .map_err(|e| format!("Corrupt journal event {}: {}", event.seq, e))?;
+        match event.kind.as_str() {
+            "replace" => collection = Value::Object(docstore::as_collection(payload)?),
+            "changes" => {
+                let changes: DocumentChanges = serde_json::from_value(payload)
+                    .map_err(|e| format!("Corrupt journal event {}: {}", event.seq, e))?;
+                apply_to(&mut collection, &changes);
+            }
+            _ => {}
+        }
+        last = event.seq;
+    }
+    if collection.is_null() {
+        return Err("The document journal is empty".to_string());
+    }
+    Ok((collection, last - snapshot_seq))
+}
+
+/// Record the change from `before` to `after`, snapshotting when due.
+fn record(
+    conn: &Connection,
+    before: &Value,
+    after: &Value,
+    since_snapshot: i64,
+) -> Result<(), String> {
+    let Some(changes) = diff(before, after) else {
+        return Ok(());
+    };
+    let payload = serde_json::to_value(&changes).map_err(|e| e.to_string())?;
+    append(conn, "changes", &payload)?;
+    if since_snapshot + 1 >= SNAPSHOT_EVERY {
+        let json = after.to_string();
+        let seq = append(
+            conn,
+            "snapshot",
+            &json!({ "digest": sha256_hex(&[json.as_bytes()]) }),
+        )?;
+        conn.execute(
+            "INSERT INTO snapshots (seq, json) VALUES (?1, ?2)",
+            params![seq, json],
+        )
+        .map_err(sql_err)?;
+    }
+    Ok(())
+}
+
+fn start(conn: &Connection, collection: Value) -> Result<(), String> {
+    let collection = Value::Object(docstore::as_collection(collection)?);
+    append(conn, "replace", &collection)?;
+    Ok(())
+}
+
+fn read_in(conn: &Connection) -> Result<Value, String> {
+    if let Some(seq) = first_broken(&events(conn, 0)?) {
+        log::warn!("Document journal fails verification at event {}", seq);
+    }
+    Ok(replay(conn)?.0)
+}
+
+fn write_in(conn: &mut Connection, collection: Value) -> Result<(), String> {
+    let after = Value::Object(docstore::as_collection(collection)?);
+    let tx = conn.transaction().map_err(sql_err)?;
+    let (before, since_snapshot) = replay(&tx)?;
+    record(&tx, &before, &after, since_snapshot)?;
+    tx.commit().map_err(sql_err)
+}
+
+fn apply_in(conn: &mut Connection, changes: &DocumentChanges) -> Result<(), String> {
+    let tx = conn.transaction().map_err(sql_err)?;
+    let (before, since_snapshot) = replay(&tx)?;
+    let mut after = before.clone();
+    apply_to(&mut after, changes);
+    record(&tx, &before, &after, since_snapshot)?;
+    tx.commit().map_err(sql_err)
+}
+
+fn export_in(conn: &Connection) -> Result<Value, String> {
+    let events = events(conn, 0)?;
+    let broken_at = first_broken(&events);
+    let head = events.last().map(|e| e.hash.clone());
+    let entries: Vec<Value> = events
+        .into_iter()
+        .map(|e| {
+            json!({
+                "seq": e.seq,
+                "at": e.at,
+                "kind": e.kind,
+                "payload": serde_json::from_str::<Value>(&e.payload).unwrap_or(Value::String(e.payload)),
+                "hash": e.hash,
+            })
+        })
+        .collect();
+    Ok(json!({
+        "format": "napkin-event-log",
+        "version": 1,
+        "exportedAt": chrono::Utc::now().timestamp_millis(),
+        "verified": broken_at.is_none(),
+        "brokenAt": broken_at,
+        "head": head,
+        "events": entries,
+    }))
+}
+
+/// Start a journal at `path` with `collection` as its first event.
+pub fn create(path: &Path, collection: Value) -> Result<(), String> {
+    start(&open(path)?, collection)
+}
+
+/// The current collection, replayed from the journal.
+pub fn read(path: &Path) -> Result<Value, String> {
+    read_in(&open(path)?)
+}
+
+/// Record a full save as the changes it makes.
+pub fn write(path: &Path, collection: Value) -> Result<(), String> {
+    write_in(&mut open(path)?, collection)
+}
+
+/// Record an incremental save.
+pub fn apply(path: &Path, changes: &DocumentChanges) -> Result<(), String> {
+    apply_in(&mut open(path)?, changes)
+}
+
+// --- Tauri command ---
+
+/// Write the journal of document `path` to `destination` as JSON, with
+/// whether its hash chain verifies. Returns the number of events.
+#[tauri::command]
+pub async fn export_event_log(path: String, destination: String) -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = Path::new(&path);
+        if !is_journal(path) {
+            return Err("This document is not stored as an event log".to_string());
+        }
+        let log = export_in(&open(path)?)?;
+        let count = log["events"].as_array().map_or(0, Vec::len);
+        let pretty = serde_json::to_string_pretty(&log).map_err(|e| e.to_string())?;
+        std::fs::write(&destination, pretty)
+            .map_err(|e| format!("Failed to write event log: {}", e))?;
+        Ok(count)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(id: &str, x: i64) -> Value {
+        json!({ "id": id, "type": "rectangle", "x": x, "y": 0, "width": 10, "height": 10 })
+    }
+
+    fn collection(shapes: Vec<Value>) -> Value {
+        json!({
+            "version": "1.0.0",
+            "appName": "napkin",
+            "type": "collection",
+            "activeDocumentIndex": 0,
+            "metadata": { "title": "Collection" },
+            "documents": [{ "shapes": shapes, "metadata": { "title": "Board" } }]
+        })
+    }
+
+    fn memory(initial: Value) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        start(&conn, initial).unwrap();
+        conn
+    }
+
+    fn kinds(conn: &Connection) -> Vec<String> {
+        events(conn, 0)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect()
+    }
+
+    #[test]
+    fn saves_are_recorded_as_changes_and_replayed() {
+        let mut conn = memory(collection(vec![rect("a", 0), rect("b", 0)]));
+        let edited = collection(vec![rect("b", 5), rect("c", 1)]);
+        write_in(&mut conn, edited.clone()).unwrap();
+        assert_eq!(read_in(&conn).unwrap(), edited);
+
+        // Saving the same state again adds nothing.
+        write_in(&mut conn, edited.clone()).unwrap();
+        assert_eq!(kinds(&conn), ["replace", "changes"]);
+
+        let changes: DocumentChanges = serde_json::from_value(json!({
+            "collection": { "activeDocumentIndex": 0 },
+            "documents": [{ "index": 0, "upserts": [rect("d", 2)], "order": ["d", "b", "c"] }]
+        }))
+        .unwrap();
+        apply_in(&mut conn, &changes).unwrap();
+        let doc = read_in(&conn).unwrap();
+        let ids: Vec<&str> = doc["documents"][0]["shapes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["d", "b", "c"]);
+    }
+
+    #[test]
+    fn diffs_are_minimal() {
+        let before = collection(vec![rect("a", 0), rect("b", 0), rect("c", 0)]);
+        let after = collection(vec![rect("a", 0), rect("c", 3), rect("d", 0)]);
+        let changes = diff(&before, &after).unwrap();
+        assert!(changes.collection.is_none());
+        let change = &changes.documents[0];
+        assert!(change.document.is_none());
+        assert_eq!(change.upserts, [rect("c", 3), rect("d", 0)]);
+        assert_eq!(change.removed, ["b"]);
+        assert!(change.order.is_none());
+
+        let mut replayed = before.clone();
+        apply_to(&mut replayed, &changes);
+        assert_eq!(replayed, after);
+        assert!(diff(&after, &after).is_none());
+    }
+
+    #[test]
+    fn snapshots_are_taken_and_used() {
+        let mut conn = memory(collection(vec![]));
+        let mut x = 0;
+        while latest_snapshot(&conn).unwrap().is_none() {
+            x += 1;
+            assert!(x <= SNAPSHOT_EVERY, "no snapshot after {} saves", x);
+            write_in(&mut conn, collection(vec![rect("a", x)])).unwrap();
+        }
+        let current = collection(vec![rect("a", x)]);
+        assert_eq!(replay(&conn).unwrap(), (current.clone(), 0));
+
+        // A doctored snapshot is ignored in favor of the events.
+        conn.execute("UPDATE snapshots SET json = '{}'", [])
+            .unwrap();
+        assert!(latest_snapshot(&conn).unwrap().is_none());
+        assert_eq!(read_in(&conn).unwrap(), current);
+    }
+
+    #[test]
+    fn tampering_breaks_the_chain() {
+        let mut conn = memory(collection(vec![rect("a", 0)]));
+        write_in(&mut conn, collection(vec![rect("a", 1)])).unwrap();
+        write_in(&mut conn, collection(vec![rect("a", 2)])).unwrap();
+        assert_eq!(export_in(&conn).unwrap()["verified"], true);
+
+        conn.execute(
+            "UPDATE journal SET payload = replace(payload, '\"x\":1', '\"x\":7') WHERE seq = 2",
+            [],
+        )
+        .unwrap();
+        let log = export_in(&conn).unwrap();
+        assert_eq!(log["verified"], false);
+        assert_eq!(log["brokenAt"], 2);
+
+        let mut conn = memory(collection(vec![rect("a", 0)]));
+        write_in(&mut conn, collection(vec![rect("a", 1)])).unwrap();
+        write_in(&mut conn, collection(vec![rect("a", 2)])).unwrap();
+        conn.execute("DELETE FROM journal WHERE seq = 2", [])
+            .unwrap();
+        assert_eq!(export_in(&conn).unwrap()["brokenAt"], 3);
+    }
+}
@@ -0,0 +1,232 @@
+//! Legacy HTTP+SSE transport (MCP 2024-11-05).
+//!
+//! Pre-2025 clients open `GET /sse`, whose first event (`endpoint`) names the
+//! URL to POST messages to: `/messages?sessionId=<id>`. Each POST is
+//! acknowledged with 202 and its reply arrives on the stream as a `message`
+//! event. The stream is the session; it ends when the client disconnects or
+//! the server stops.
+//!
+//! Idle streams (these and `GET /mcp`) send a keep-alive comment every
+//! `Heartbeat::interval_secs`. A client that vanished without closing its
+//! connection is noticed when that write fails, and its session is dropped.
+
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::StreamExt;
+
+pub const MESSAGES_PATH: &str = "/messages";
+const DEFAULT_HEARTBEAT_SECS: u64 = 15;
+const MAX_HEARTBEAT_SECS: u64 = 300;
+
+/// Keep-alive settings for event streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Heartbeat {
+    /// Seconds between keep-alive comments on an idle stream. Shorter
+    /// intervals free dead connections sooner.
+    pub interval_secs: u64,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            interval_secs: DEFAULT_HEARTBEAT_SECS,
+        }
+    }
+}
+
+impl Heartbeat {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs.clamp(1, MAX_HEARTBEAT_SECS))
+    }
+}
+
+/// Keep-alive comments every `interval`.
+pub fn keep_alive(interval: Duration) -> KeepAlive {
+    KeepAlive::new().interval(interval)
+}
+
+/// Open streams by session id, each with transport-specific `T`.
+pub struct SseSessions<T = ()> {
+    streams: HashMap<String, (mpsc::UnboundedSender<SseEvent>, T)>,
+}
+
+impl<T> Default for SseSessions<T> {
+    fn default() -> Self {
+        Self {
+            streams: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone> SseSessions<T> {
+    /// Register a new stream; its first event is the `endpoint` announcement.
+    pub fn open(&mut self, extra: T) -> (String, mpsc::UnboundedReceiver<SseEvent>) {
+        self.streams.retain(|_, (tx, _)| !tx.is_closed());
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let _ = tx.send(
+            SseEvent::default()
+                .event("endpoint")
+                .data(format!("{}?sessionId={}", MESSAGES_PATH, id)),
+        );
+        self.streams.insert(id.clone(), (tx, extra));
+        (id, rx)
+    }
+
+    /// The stream for `id`, if its client is still connected.
+    pub fn get(&self, id: &str) -> Option<(mpsc::UnboundedSender<SseEvent>, T)> {
+        self.streams
+            .get(id)
+            .filter(|(tx, _)| !tx.is_closed())
+            .cloned()
+    }
+
+    pub fn close(&mut self, id: &str) {
+        self.streams.remove(id);
+    }
+
+    /// How many streams still have a client.
+    pub fn connected(&self) -> usize {
+        self.streams
+            .values()
+            .filter(|(tx, _)| !tx.is_closed())
+            .count()
+    }
+}
+
+/// A JSON-RPC reply as a `message` event.
+pub fn message_event(reply: &serde_json::Value) -> SseEvent {
+    SseEvent::default().event("message").data(reply.to_string())
+}
+
+/// Open a stream in `sessions`, closed when `shutdown` fires. Messages from
+/// `notifications` are sent on it as `message` events. Once the stream ends,
+/// its session is dropped and `on_close` runs with its `extra`.
+pub async fn connect<T, F, Fut>(
+    sessions: Arc<Mutex<SseSessions<T>>>,
+    extra: T,
+    shutdown: Option<watch::Receiver<bool>>,
+    mut notifications: Option<mpsc::UnboundedReceiver<serde_json::Value>>,
+    heartbeat: Duration,
+    on_close: F,
+) -> Sse<impl tokio_stream::Stream<Item = Result<SseEvent, Infallible>>>
+where
+    T: Clone + Send + 'static,
+    F: FnOnce(T) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let (id, rx) = sessions.lock().await.open(extra.clone());
+    let stream = sessions.lock().await.get(&id).map(|(tx, _)| tx);
+    if let Some(stream) = stream {
+        let mut shutdown = shutdown;
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    note = crate::api::next_notification(&mut notifications) => match note {
+                        Some(note) => {
+                            let _ = stream.send(message_event(&note));
+                        }
+                        None => notifications = None,
+                    },
+                    // The receiver is dropped when a write to the client fails.
+                    _ = stream.closed() => break,
+                    _ = stopped(&mut shutdown) => break,
+                }
+            }
+            // Dropping the last sender ends the stream.
+            drop(stream);
+            sessions.lock().await.close(&id);
+            on_close(extra).await;
+        });
+    }
+    Sse::new(UnboundedReceiverStream::new(rx).map(Ok)).keep_alive(keep_alive(heartbeat))
+}
+
+/// Resolves once `shutdown` fires; never without a receiver.
+async fn stopped(shutdown: &mut Option<watch::Receiver<bool>>) {
+    let Some(rx) = shutdown else {
+        return std::future::pending().await;
+    };
+    while !*rx.borrow() {
+        if rx.changed().await.is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_are_found_until_their_client_goes_away() {
+        let mut sessions = SseSessions::<u8>::default();
+        let (id, rx) = sessions.open(7);
+        let (tx, extra) = sessions.get(&id).unwrap();
+        assert_eq!(extra, 7);
+        assert!(tx
+            .send(message_event(&serde_json::json!({ "id": 1 })))
+            .is_ok());
+
+        drop(rx);
+        assert!(sessions.get(&id).is_none());
+        sessions.open(8);
+        assert_eq!(sessions.streams.len(), 1);
+    }
+
+    #[test]
+    fn stream_opens_with_the_endpoint_event() {
+        let mut sessions = SseSessions::<()>::default();
+        let (id, mut rx) = sessions.open(());
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_err());
+        assert_eq!(id.len(), 32);
+        sessions.close(&id);
+        assert!(sessions.get(&id).is_none());
+    }
+
+    #[tokio::test]
+    async fn dead_streams_are_dropped_and_cleaned_up() {
+        let sessions = Arc::new(Mutex::new(SseSessions::<u8>::default()));
+        let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+        let sse = connect(
+            Arc::clone(&sessions),
+            7,
+            None,
+            None,
+            Heartbeat::default().interval(),
+            move |extra| async move {
+                let _ = closed_tx.send(extra);
+            },
+        )
+        .await;
+        assert_eq!(sessions.lock().await.connected(), 1);
+
+        // What hyper does when a write to the client fails.
+        drop(sse);
+        assert_eq!(closed_rx.await.unwrap(), 7);
+        assert_eq!(sessions.lock().await.connected(), 0);
+        assert!(sessions.lock().await.streams.is_empty());
+    }
+
+    #[test]
+    fn heartbeat_interval_is_clamped() {
+        let interval = |secs| {
+            Heartbeat {
+                interval_secs: secs,
+            }
+            .interval()
+        };
+        assert_eq!(interval(0), Duration::from_secs(1));
+        assert_eq!(Heartbeat::default().interval(), Duration::from_secs(15));
+        assert_eq!(interval(3600), Duration::from_secs(300));
+    }
+}
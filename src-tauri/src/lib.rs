@@ -2,18 +2,213 @@
 use tauri::{Emitter, Manager, menu::{AboutMetadata, Menu, MenuItem, Submenu, PredefinedMenuItem}};
 
 mod api;
+mod approval;
+mod assets;
+mod audit;
+mod auth;
+mod author;
+mod bind;
+mod body_limit;
+mod bridge;
+mod budget;
+mod canvas_events;
+mod cli;
+mod clients;
+mod connections;
+mod container;
+mod constraints;
+mod daily;
+mod diagnostics;
+mod docstore;
+mod document;
+mod drain;
+mod embed;
+mod encryption;
+mod export;
+mod export_hooks;
+mod geometry;
+mod headless;
+mod history;
+mod hit_test;
+mod hosts;
+mod integrity;
+mod jobs;
+mod journal;
+mod legacy_sse;
+mod lint;
+mod lock;
+mod mdns;
+mod model;
+mod msgpack;
+mod openapi;
+mod origins;
+mod outline;
+mod partition;
+mod pdf;
+mod photo;
+mod presence;
+mod profiles;
+mod prompts;
+mod queue;
+mod read_only;
+mod region;
+mod render;
+mod request_log;
+mod resources;
+mod rest;
+mod restyle;
+mod revision;
+mod sampling;
+mod sandbox;
+mod scheduler;
+mod session;
+mod settings;
+mod shape_defaults;
+mod sidecar;
+mod sharing;
+mod snippets;
+mod spatial;
+mod startup;
+mod status;
+mod stdio;
+mod structured;
+mod subscriptions;
+mod supervisor;
+mod text_metrics;
+mod thumbnails;
+mod tls;
+mod tool_errors;
+mod tool_filter;
+mod trace;
+mod translate;
+mod tunnel;
+mod units;
+mod versions;
+mod visibility;
+mod wal;
+mod watermark;
+mod zorder;
+
+/// Run as a stdio MCP server proxying to the running app (`--mcp-stdio`).
+pub fn run_mcp_stdio() -> i32 {
+  stdio::run()
+}
+
+/// Serve MCP for a document without opening a window (`--headless`).
+pub fn run_headless(args: &[String]) -> i32 {
+  headless::run(args)
+}
+
+/// Export a document without opening a window (`export ...`); `args` are
+/// the ones after `export`.
+pub fn run_cli_export(args: &[String]) -> i32 {
+  cli::run(args)
+}
+
+/// Run as the MCP sidecar process started by the app (`--mcp-sidecar`).
+pub fn run_mcp_sidecar() -> i32 {
+  sidecar::run()
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
   tauri::Builder::default()
+    .manage(startup::Startup::new())
+    .manage(document::Documents::default())
     .plugin(tauri_plugin_dialog::init())
     .plugin(tauri_plugin_fs::init())
+    .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+    .register_asynchronous_uri_scheme_protocol(assets::PROTOCOL, assets::protocol)
     .invoke_handler(tauri::generate_handler![
       api::api_response,
       api::start_api_server,
       api::stop_api_server,
-      api::get_api_status,
+      status::get_api_status,
+      api::get_api_port,
+      audit::get_audit_log,
+      audit::clear_audit_log,
+      request_log::get_api_logs,
+      auth::get_api_token,
+      embed::get_embed_snippet,
+      lock::acquire_canvas_lock,
+      lock::release_canvas_lock,
+      lock::get_canvas_lock,
+      mdns::set_mdns_document,
+      presence::list_agent_cursors,
+      session::list_mcp_sessions,
+      clients::list_api_clients,
+      queue::get_mutation_queue,
+      tunnel::start_tunnel,
+      tunnel::stop_tunnel,
+      tunnel::get_tunnel_status,
+      sharing::create_share_link,
+      sharing::list_share_links,
+      sharing::revoke_share_link,
+      export::export_interactive_svg,
+      render::export_selection,
+      render::export_svg,
+      render::render_png,
+      render::render_svg,
+      render::render_thumbnail,
+      pdf::export_pdf,
+      pdf::export_pdf_tabs,
+      approval::respond_tool_approval,
+      approval::reset_tool_approvals,
+      watermark::export_watermark_svg,
+      export_hooks::run_export_hooks,
+      tool_filter::get_enabled_tools,
+      tool_filter::set_enabled_tools,
+      tool_filter::set_disabled_tools,
+      tool_filter::reset_enabled_tools,
+      read_only::get_mcp_read_only,
+      read_only::set_mcp_read_only,
+      snippets::expand_snippet,
+      snippets::list_snippets,
+      snippets::save_snippet,
+      snippets::delete_snippet,
+      integrity::repair_document,
+      constraints::solve_constraints,
+      photo::cleanup_photo,
+      geometry::shape_geometry,
+      geometry::hit_test_shapes,
+      units::set_document_scale,
+      units::get_ruler_ticks,
+      spatial::update_spatial_index,
+      spatial::query_visible_shapes,
+      history::compact_history,
+      history::build_digest,
+      document::load_document,
+      document::save_document,
+      document::document_is_dirty,
+      document::set_document_password,
+      document::document_is_protected,
+      document::save_recovery,
+      document::load_recovery,
+      docstore::convert_document,
+      docstore::apply_document_changes,
+      assets::store_asset,
+      assets::collect_unused_assets,
+      thumbnails::get_document_thumbnail,
+      versions::list_document_versions,
+      versions::restore_document_version,
+      journal::export_event_log,
+      author::get_author,
+      daily::open_daily_note,
+      daily::set_daily_shortcut,
+      subscriptions::api_notify_change,
+      visibility::api_set_tab_visibility,
+      diagnostics::api_register_bridge,
+      diagnostics::get_diagnostics,
+      settings::get_settings,
+      settings::update_settings,
+      profiles::list_tool_profiles,
+      startup::get_startup_timings,
+      startup::report_startup_phase,
     ])
+    .on_page_load(|webview, payload| match payload.event() {
+      tauri::webview::PageLoadEvent::Started => bridge::on_page_started(webview.app_handle()),
+      tauri::webview::PageLoadEvent::Finished => startup::on_page_loaded(webview.app_handle()),
+    })
     .setup(|app| {
       if cfg!(debug_assertions) {
         app.handle().plugin(
@@ -23,8 +218,10 @@ pub fn run() {
         )?;
       }
 
+      let startup = app.state::<startup::Startup>();
+
       // Build the menu
-      let menu = build_menu(app)?;
+      let menu = startup.measure("menu", || build_menu(app))?;
       app.set_menu(menu)?;
 
       // Handle menu events
@@ -32,10 +229,23 @@ pub fn run() {
         handle_menu_event(app, event);
       });
 
+      // Tray icon with the remote agent kill switch
+      startup.measure("tray", || tunnel::build_tray(app))?;
+
+      app.manage(startup.measure("settings", || settings::SettingsStore::load(app.handle())));
+      app.manage(startup.measure("recent-files", || resources::RecentFiles::load(app.handle())));
+      assets::init(app.handle());
+      startup.measure("daily-shortcut", || daily::register_shortcut(app.handle()));
+      app.manage(startup.measure("audit-log", || audit::AuditLog::load(app.handle())));
+      app.manage(startup.measure("api-log", || request_log::ApiLog::load(app.handle())));
+
       // Create and manage API state
-      let api_state = api::create_api_state(app.handle().clone());
+      let api_state = startup.measure("api-state", || api::create_api_state(app.handle().clone()));
       app.manage(api_state);
 
+      // Everything else waits for the first page load (see startup.rs)
+      startup.mark("setup-complete");
+
       Ok(())
     })
     .run(tauri::generate_context!())
@@ -82,8 +292,15 @@ fn build_menu(app: &tauri::App) -> Result<Menu<tauri::Wry>, tauri::Error> {
   let open_item = MenuItem::with_id(app, "open", "Open...", true, Some("CmdOrCtrl+O"))?;
   let save_item = MenuItem::with_id(app, "save", "Save", true, Some("CmdOrCtrl+S"))?;
   let save_as_item = MenuItem::with_id(app, "save_as", "Save As...", true, Some("CmdOrCtrl+Shift+S"))?;
+  let daily_note_item = MenuItem::with_id(app, daily::MENU_ID, "Today's Napkin", true, Some(daily::DEFAULT_SHORTCUT))?;
+  let revert_menu = versions::submenu(app)?;
+  let set_password_item = MenuItem::with_id(app, "set_password", "Set Password...", true, None::<&str>)?;
   let export_png_item = MenuItem::with_id(app, "export_png", "Export PNG...", true, None::<&str>)?;
   let export_svg_item = MenuItem::with_id(app, "export_svg", "Export SVG...", true, None::<&str>)?;
+  let export_interactive_item = MenuItem::with_id(app, "export_interactive", "Export Interactive SVG/HTML...", true, None::<&str>)?;
+  let export_pdf_item = MenuItem::with_id(app, "export_pdf", "Export PDF...", true, None::<&str>)?;
+  let export_pdf_tabs_item = MenuItem::with_id(app, "export_pdf_tabs", "Export All Tabs as PDF...", true, None::<&str>)?;
+  let export_selection_item = MenuItem::with_id(app, "export_selection", "Export Selection...", true, None::<&str>)?;
 
   let file_menu = Submenu::with_items(
     app,
@@ -94,9 +311,16 @@ fn build_menu(app: &tauri::App) -> Result<Menu<tauri::Wry>, tauri::Error> {
       &open_item,
       &save_item,
       &save_as_item,
+      &revert_menu,
+      &set_password_item,
+      &daily_note_item,
       &PredefinedMenuItem::separator(app)?,
       &export_png_item,
       &export_svg_item,
+      &export_interactive_item,
+      &export_pdf_item,
+      &export_pdf_tabs_item,
+      &export_selection_item,
     ],
   )?;
 
@@ -107,6 +331,7 @@ fn build_menu(app: &tauri::App) -> Result<Menu<tauri::Wry>, tauri::Error> {
   let copy_item = MenuItem::with_id(app, "copy", "Copy", true, Some("CmdOrCtrl+C"))?;
   let paste_item = MenuItem::with_id(app, "paste", "Paste", true, Some("CmdOrCtrl+V"))?;
   let delete_item = MenuItem::with_id(app, "delete", "Delete", true, Some("Backspace"))?;
+  let read_only_item = read_only::menu_item(app)?;
 
   let edit_menu = Submenu::with_items(
     app,
@@ -120,6 +345,8 @@ fn build_menu(app: &tauri::App) -> Result<Menu<tauri::Wry>, tauri::Error> {
       &copy_item,
       &paste_item,
       &delete_item,
+      &PredefinedMenuItem::separator(app)?,
+      &read_only_item,
     ],
   )?;
 
@@ -143,6 +370,8 @@ fn build_menu(app: &tauri::App) -> Result<Menu<tauri::Wry>, tauri::Error> {
     ],
   )?;
 
+  let connections_menu = connections::submenu(app)?;
+
   // Build the main menu
   let menu = Menu::with_items(
     app,
@@ -151,6 +380,7 @@ fn build_menu(app: &tauri::App) -> Result<Menu<tauri::Wry>, tauri::Error> {
       &file_menu,
       &edit_menu,
       &view_menu,
+      &connections_menu,
     ],
   )?;
 
@@ -175,12 +405,30 @@ fn handle_menu_event(app: &tauri::AppHandle, event: tauri::menu::MenuEvent) {
       "save_as" => {
         let _ = window.emit("menu-save-as", ());
       }
+      "set_password" => {
+        let _ = window.emit("menu-set-password", ());
+      }
+      daily::MENU_ID => {
+        let _ = window.emit(daily::MENU_EVENT, ());
+      }
       "export_png" => {
         let _ = window.emit("menu-export-png", ());
       }
       "export_svg" => {
         let _ = window.emit("menu-export-svg", ());
       }
+      "export_interactive" => {
+        let _ = window.emit("menu-export-interactive", ());
+      }
+      "export_pdf" => {
+        let _ = window.emit("menu-export-pdf", ());
+      }
+      "export_pdf_tabs" => {
+        let _ = window.emit("menu-export-pdf-tabs", ());
+      }
+      "export_selection" => {
+        let _ = window.emit("menu-export-selection", ());
+      }
       "undo" => {
         let _ = window.emit("menu-undo", ());
       }
@@ -214,6 +462,15 @@ fn handle_menu_event(app: &tauri::AppHandle, event: tauri::menu::MenuEvent) {
       "acknowledgments" => {
         let _ = window.emit("menu-acknowledgments", ());
       }
+      read_only::MENU_ID => {
+        read_only::on_menu_click(app);
+      }
+      id @ (connections::START_MENU_ID | connections::STOP_MENU_ID | connections::COPY_CONFIG_MENU_ID) => {
+        connections::on_menu_click(app, id);
+      }
+      id if id.starts_with(versions::MENU_PREFIX) => {
+        versions::on_menu_click(app, id);
+      }
       _ => {}
     }
   }
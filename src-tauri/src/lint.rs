@@ -0,0 +1,498 @@
+//! `lint_canvas`: a checkup of a board for the mistakes agents (and people)
+//! tend to leave behind.
+//!
+//! The board is read through the webview once and checked here. Each rule
+//! yields findings naming the shapes involved, so an agent can fix them with
+//! the usual tools and lint again:
+//!
+//! - `danglingBinding`: a line or arrow bound to a shape that was deleted
+//!   (`repair_document` fixes these);
+//! - `zeroSize`: a shape with no width or height, invisible but selectable;
+//! - `overlap`: two shapes partly covering each other; one inside the other
+//!   is taken to be on purpose and not reported;
+//! - `offscreenOrphan`: an unconnected shape far from everything else on the
+//!   board, usually lost off-screen by a bad coordinate;
+//! - `lowContrast`: text too close in colour to what it is drawn on, by the
+//!   WCAG contrast ratio.
+
+use rstar::primitives::{GeomWithData, Rectangle};
+use rstar::{RTree, AABB};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+
+use crate::api::SharedApiState;
+use crate::geometry::{shape_bounds, Bounds};
+use crate::integrity;
+use crate::resources;
+use crate::settings::BindingRepairPolicy;
+
+const RULES: [&str; 5] = [
+    "danglingBinding",
+    "zeroSize",
+    "overlap",
+    "offscreenOrphan",
+    "lowContrast",
+];
+
+/// Below this, in canvas pixels, a shape has no size.
+const MIN_SIZE: f64 = 1.0;
+/// The share of the smaller shape two shapes must cover to overlap.
+const MIN_OVERLAP: f64 = 0.1;
+/// How far, in canvas pixels, a shape must be from every other one to be an
+/// orphan.
+const ORPHAN_GAP: f64 = 2000.0;
+/// WCAG's minimum contrast ratios, for normal and large (24px+) text.
+const MIN_CONTRAST: f64 = 4.5;
+const MIN_CONTRAST_LARGE: f64 = 3.0;
+const LARGE_TEXT: f64 = 24.0;
+/// What the board is drawn on.
+const BACKGROUND: [f64; 3] = [1.0, 1.0, 1.0];
+/// A sticky note's colour when it has none of its own (yellow).
+const STICKY_DEFAULT: &str = "#fff9c4";
+
+fn kind(shape: &Value) -> &str {
+    shape.get("type").and_then(Value::as_str).unwrap_or("")
+}
+
+fn id(shape: &Value) -> &str {
+    shape.get("id").and_then(Value::as_str).unwrap_or("")
+}
+
+fn is_connector(shape: &Value) -> bool {
+    matches!(kind(shape), "line" | "arrow")
+}
+
+struct Finding {
+    rule: &'static str,
+    severity: &'static str,
+    shape_ids: Vec<String>,
+    message: String,
+}
+
+impl Finding {
+    fn to_json(&self) -> Value {
+        json!({
+            "rule": self.rule,
+            "severity": self.severity,
+            "shapeIds": self.shape_ids,
+            "message": self.message,
+        })
+    }
+}
+
+fn dangling_bindings(shapes: &[Value]) -> Vec<Finding> {
+    integrity::plan_repair(shapes, BindingRepairPolicy::Unbind)
+        .fixes
+        .into_iter()
+        .map(|fix| Finding {
+            rule: "danglingBinding",
+            severity: "error",
+            message: format!(
+                "The {} of {} is bound to {}, which no longer exists",
+                fix.end, fix.shape_id, fix.missing_shape_id
+            ),
+            shape_ids: vec![fix.shape_id],
+        })
+        .collect()
+}
+
+fn is_zero_size(shape: &Value, bounds: Option<&Bounds>) -> bool {
+    let Some(bounds) = bounds else {
+        return true;
+    };
+    let (width, height) = (bounds.width(), bounds.height());
+    match kind(shape) {
+        // Flat is fine for these, as long as they go somewhere
+        "line" | "arrow" | "freedraw" => width < MIN_SIZE && height < MIN_SIZE,
+        // Text is sized by its font
+        "text" => false,
+        _ => width < MIN_SIZE || height < MIN_SIZE,
+    }
+}
+
+fn zero_size(shapes: &[Value]) -> Vec<Finding> {
+    shapes
+        .iter()
+        .filter(|shape| is_zero_size(shape, shape_bounds(shape).as_ref()))
+        .map(|shape| Finding {
+            rule: "zeroSize",
+            severity: "warning",
+            shape_ids: vec![id(shape).to_string()],
+            message: format!("{} ({}) has no size", id(shape), kind(shape)),
+        })
+        .collect()
+}
+
+type Entry = GeomWithData<Rectangle<[f64; 2]>, usize>;
+
+/// The shapes with a size, by index into `shapes`, and their bounds.
+fn index(shapes: &[Value]) -> (RTree<Entry>, Vec<Option<Bounds>>) {
+    let bounds: Vec<Option<Bounds>> = shapes
+        .iter()
+        .map(|shape| shape_bounds(shape).filter(|b| !is_zero_size(shape, Some(b))))
+        .collect();
+    let entries = bounds
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| {
+            let b = b.as_ref()?;
+            let rect = Rectangle::from_corners([b.min_x, b.min_y], [b.max_x, b.max_y]);
+            Some(GeomWithData::new(rect, i))
+        })
+        .collect();
+    (RTree::bulk_load(entries), bounds)
+}
+
+fn envelope(bounds: &Bounds) -> AABB<[f64; 2]> {
+    AABB::from_corners([bounds.min_x, bounds.min_y], [bounds.max_x, bounds.max_y])
+}
+
+fn contains(outer: &Bounds, inner: &Bounds) -> bool {
+    outer.min_x <= inner.min_x
+        && outer.min_y <= inner.min_y
+        && outer.max_x >= inner.max_x
+        && outer.max_y >= inner.max_y
+}
+
+fn overlaps(shapes: &[Value], tree: &RTree<Entry>, bounds: &[Option<Bounds>]) -> Vec<Finding> {
+    let solid = |i: usize| !is_connector(&shapes[i]) && kind(&shapes[i]) != "freedraw";
+    let mut findings = Vec::new();
+    for (i, a) in bounds.iter().enumerate() {
+        let Some(a) = a.as_ref().filter(|_| solid(i)) else {
+            continue;
+        };
+        let mut others: Vec<usize> = tree
+            .locate_in_envelope_intersecting(&envelope(a))
+            .map(|e| e.data)
+            .filter(|&j| j > i && solid(j))
+            .collect();
+        others.sort_unstable();
+        for j in others {
+            let Some(b) = bounds[j].as_ref() else {
+                continue;
+            };
+            if contains(a, b) || contains(b, a) {
+                continue;
+            }
+            let width = a.max_x.min(b.max_x) - a.min_x.max(b.min_x);
+            let height = a.max_y.min(b.max_y) - a.min_y.max(b.min_y);
+            let smaller = (a.width() * a.height()).min(b.width() * b.height());
+            if width <= 0.0 || height <= 0.0 || width * height < smaller * MIN_OVERLAP {
+                continue;
+            }
+            let (first, second) = (id(&shapes[i]), id(&shapes[j]));
+            findings.push(Finding {
+                rule: "overlap",
+                severity: "warning",
+                shape_ids: vec![first.to_string(), second.to_string()],
+                message: format!(
+                    "{} and {} overlap by {:.0}% of the smaller one",
+                    first,
+                    second,
+                    100.0 * width * height / smaller
+                ),
+            });
+        }
+    }
+    findings
+}
+
+/// Shapes a connector is bound to.
+fn bound_ids(shapes: &[Value]) -> HashSet<&str> {
+    shapes
+        .iter()
+        .flat_map(|shape| ["bindStart", "bindEnd"].map(|key| shape.get(key)))
+        .flatten()
+        .filter_map(|binding| binding.get("shapeId").and_then(Value::as_str))
+        .collect()
+}
+
+fn orphans(shapes: &[Value], tree: &RTree<Entry>, bounds: &[Option<Bounds>]) -> Vec<Finding> {
+    // With only a couple of shapes there is no "rest of the board"
+    if tree.size() < 3 {
+        return Vec::new();
+    }
+    let bound = bound_ids(shapes);
+    let mut findings = Vec::new();
+    for (i, shape) in shapes.iter().enumerate() {
+        let Some(b) = bounds[i].as_ref() else {
+            continue;
+        };
+        if is_connector(shape) || shape.get("groupId").is_some() || bound.contains(id(shape)) {
+            continue;
+        }
+        let near = tree
+            .locate_in_envelope_intersecting(&envelope(&b.expand(ORPHAN_GAP)))
+            .any(|e| e.data != i);
+        if !near {
+            findings.push(Finding {
+                rule: "offscreenOrphan",
+                severity: "info",
+                shape_ids: vec![id(shape).to_string()],
+                message: format!(
+                    "{} ({}) at ({:.0}, {:.0}) is unconnected and over {}px from any other shape",
+                    id(shape),
+                    kind(shape),
+                    b.min_x,
+                    b.min_y,
+                    ORPHAN_GAP
+                ),
+            });
+        }
+    }
+    findings
+}
+
+/// Parse a CSS colour: hex, `rgb()`/`rgba()`, `transparent`, `white` or
+/// `black`. Channels are 0–1; the second value is the alpha.
+fn parse_color(color: &str) -> Option<([f64; 3], f64)> {
+    let color = color.trim().to_ascii_lowercase();
+    match color.as_str() {
+        "transparent" => return Some(([0.0; 3], 0.0)),
+        "white" => return Some(([1.0; 3], 1.0)),
+        "black" => return Some(([0.0; 3], 1.0)),
+        _ => {}
+    }
+    if let Some(hex) = color.strip_prefix('#') {
+        let digits: Vec<u32> = hex.chars().map(|c| c.to_digit(16)).collect::<Option<_>>()?;
+        let channels: Vec<f64> = match digits.len() {
+            3 | 4 => digits.iter().map(|d| (d * 17) as f64 / 255.0).collect(),
+            6 | 8 => digits
+                .chunks(2)
+                .map(|pair| (pair[0] * 16 + pair[1]) as f64 / 255.0)
+                .collect(),
+            _ => return None,
+        };
+        let alpha = channels.get(3).copied().unwrap_or(1.0);
+        return Some(([channels[0], channels[1], channels[2]], alpha));
+    }
+    let inner = color
+        .strip_prefix("rgba(")
+        .or_else(|| color.strip_prefix("rgb("))?
+        .strip_suffix(')')?;
+    let parts: Vec<f64> = inner
+        .split(',')
+        .map(|part| part.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match parts.as_slice() {
+        [r, g, b] => Some(([r / 255.0, g / 255.0, b / 255.0], 1.0)),
+        [r, g, b, a] => Some(([r / 255.0, g / 255.0, b / 255.0], *a)),
+        _ => None,
+    }
+}
+
+/// `color` as seen over `under`.
+fn blend((rgb, alpha): ([f64; 3], f64), under: [f64; 3]) -> [f64; 3] {
+    let alpha = alpha.clamp(0.0, 1.0);
+    [0, 1, 2].map(|c| rgb[c] * alpha + under[c] * (1.0 - alpha))
+}
+
+fn luminance(rgb: [f64; 3]) -> f64 {
+    let linear = rgb.map(|c| {
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    0.2126 * linear[0] + 0.7152 * linear[1] + 0.0722 * linear[2]
+}
+
+/// The WCAG contrast ratio between two colours, from 1 to 21.
+fn contrast(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// The text colour of a text shape or sticky note and what it is drawn on.
+fn text_colors(shape: &Value) -> Option<([f64; 3], [f64; 3])> {
+    let text = shape.get("text").and_then(Value::as_str)?;
+    if text.trim().is_empty() {
+        return None;
+    }
+    let color = |key: &str| shape.get(key).and_then(Value::as_str).and_then(parse_color);
+    let background = match kind(shape) {
+        "text" => color("fillColor").map_or(BACKGROUND, |fill| blend(fill, BACKGROUND)),
+        "sticky" => {
+            let fill = color("stickyColor")
+                .or_else(|| color("fillColor"))
+                .or_else(|| parse_color(STICKY_DEFAULT))?;
+            blend(fill, BACKGROUND)
+        }
+        _ => return None,
+    };
+    Some((blend(color("strokeColor")?, background), background))
+}
+
+fn low_contrast(shapes: &[Value]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for shape in shapes {
+        let Some((text, background)) = text_colors(shape) else {
+            continue;
+        };
+        let font_size = shape
+            .get("fontSize")
+            .and_then(Value::as_f64)
+            .unwrap_or(16.0);
+        let minimum = if font_size >= LARGE_TEXT {
+            MIN_CONTRAST_LARGE
+        } else {
+            MIN_CONTRAST
+        };
+        let ratio = contrast(text, background);
+        if ratio < minimum {
+            findings.push(Finding {
+                rule: "lowContrast",
+                severity: "warning",
+                shape_ids: vec![id(shape).to_string()],
+                message: format!(
+                    "The text of {} has a contrast ratio of {:.1}:1 with its background (at least {}:1 is readable)",
+                    id(shape),
+                    ratio,
+                    minimum
+                ),
+            });
+        }
+    }
+    findings
+}
+
+/// Run `rules` over `shapes`.
+fn lint(shapes: &[Value], rules: &[&str]) -> Vec<Finding> {
+    let (tree, bounds) = index(shapes);
+    let mut findings = Vec::new();
+    for rule in rules {
+        findings.extend(match *rule {
+            "danglingBinding" => dangling_bindings(shapes),
+            "zeroSize" => zero_size(shapes),
+            "overlap" => overlaps(shapes, &tree, &bounds),
+            "offscreenOrphan" => orphans(shapes, &tree, &bounds),
+            "lowContrast" => low_contrast(shapes),
+            _ => Vec::new(),
+        });
+    }
+    findings
+}
+
+pub fn lint_tools() -> Vec<Value> {
+    vec![json!({
+        "name": "lint_canvas",
+        "description": "Check a board for common problems and list them with the ids of the shapes involved: lines and arrows bound to deleted shapes (danglingBinding), shapes with no width or height (zeroSize), shapes partly covering each other (overlap; one inside another is not reported), unconnected shapes far from everything else (offscreenOrphan), and text too faint to read against its background (lowContrast, by the WCAG contrast ratio). Run it after building a diagram and fix what it finds.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "rules": { "type": "array", "items": { "type": "string", "enum": RULES }, "description": "Only run these checks (default: all)" },
+                "tabId": { "type": "string", "description": "Tab to check (default: the tab agents are working on)" }
+            },
+            "additionalProperties": false
+        }
+    })]
+}
+
+pub async fn call_lint_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "lint_canvas" => Some(lint_canvas(state, arguments).await),
+        _ => None,
+    }
+}
+
+async fn lint_canvas(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let rules: Vec<&str> = match arguments.get("rules").and_then(Value::as_array) {
+        None => RULES.to_vec(),
+        Some(names) => names
+            .iter()
+            .map(|name| {
+                name.as_str()
+                    .and_then(|name| RULES.iter().find(|rule| **rule == name))
+                    .copied()
+                    .ok_or_else(|| {
+                        format!(
+                            "Unknown rule {}; expected one of {}",
+                            name,
+                            RULES.join(", ")
+                        )
+                    })
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    let tab_id = arguments.get("tabId").and_then(Value::as_str);
+    let canvas = resources::canvas(state, tab_id).await?;
+    let shapes = canvas
+        .get("shapes")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let findings = lint(shapes, &rules);
+    let mut counts = Map::new();
+    for rule in &rules {
+        let count = findings.iter().filter(|f| f.rule == *rule).count();
+        counts.insert(rule.to_string(), json!(count));
+    }
+    Ok(json!({
+        "shapeCount": shapes.len(),
+        "counts": counts,
+        "findings": findings.iter().map(Finding::to_json).collect::<Vec<_>>(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(id: &str, x: f64, y: f64, w: f64, h: f64) -> Value {
+        json!({ "id": id, "type": "rectangle", "x": x, "y": y, "width": w, "height": h })
+    }
+
+    fn found(shapes: &[Value], rule: &str) -> Vec<Vec<String>> {
+        lint(shapes, &[rule])
+            .into_iter()
+            .map(|f| f.shape_ids)
+            .collect()
+    }
+
+    #[test]
+    fn each_rule_names_the_shapes_at_fault() {
+        let mut faint = json!({ "id": "faint", "type": "text", "x": 500, "y": 0, "text": "hi", "strokeColor": "#dddddd" });
+        let shapes = vec![
+            rect("a", 0.0, 0.0, 100.0, 100.0),
+            rect("b", 50.0, 50.0, 100.0, 100.0),
+            rect("inner", 10.0, 10.0, 20.0, 20.0),
+            rect("flat", 300.0, 0.0, 100.0, 0.0),
+            json!({ "id": "arrow", "type": "arrow", "x": 0, "y": 0, "x2": 10, "y2": 10,
+                    "bindStart": { "shapeId": "a" }, "bindEnd": { "shapeId": "gone" } }),
+            rect("lost", 90000.0, 90000.0, 50.0, 50.0),
+            faint.clone(),
+        ];
+        assert_eq!(found(&shapes, "danglingBinding"), [["arrow"]]);
+        assert_eq!(found(&shapes, "zeroSize"), [["flat"]]);
+        assert_eq!(found(&shapes, "overlap"), [["a", "b"]]);
+        assert_eq!(found(&shapes, "offscreenOrphan"), [["lost"]]);
+        assert_eq!(found(&shapes, "lowContrast"), [["faint"]]);
+
+        // Dark text, or the same text on a dark background, reads fine
+        faint["strokeColor"] = json!("#222");
+        assert!(found(&[faint.clone()], "lowContrast").is_empty());
+        faint["fillColor"] = json!("rgb(0, 0, 0)");
+        faint["strokeColor"] = json!("#dddddd");
+        assert!(found(&[faint], "lowContrast").is_empty());
+    }
+
+    #[test]
+    fn contrast_follows_wcag() {
+        let black = parse_color("#000").unwrap().0;
+        let white = parse_color("#ffffff").unwrap().0;
+        assert!((contrast(black, white) - 21.0).abs() < 1e-9);
+        assert_eq!(contrast(white, white), 1.0);
+        assert_eq!(parse_color("transparent").unwrap().1, 0.0);
+        assert_eq!(
+            parse_color("rgba(255, 0, 0, 0.5)").unwrap(),
+            ([1.0, 0.0, 0.0], 0.5)
+        );
+        assert!(parse_color("#12345").is_none());
+    }
+}
@@ -0,0 +1,408 @@
+//! Cooperative, turn-based write lock between the user and MCP agents.
+//!
+//! Locking is opt-in: nothing is locked until someone asks. While the user
+//! holds the lock (e.g. mid-drag) mutating MCP tools are rejected with a
+//! retry-after hint; while an agent holds it the UI refrains from editing.
+//! Every lock has a TTL so a missed release can't wedge the canvas.
+//!
+//! Agents hold the lock one MCP session at a time: while one session holds
+//! it, writes from the user and from every other session are refused, and
+//! only that session can extend or release it. A caller without a session
+//! (a REST request, say) is a new session on every call, so it can take the
+//! lock but not write under it or release it afterwards.
+
+use crate::api::SharedApiState;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+
+const DEFAULT_USER_TTL: Duration = Duration::from_secs(10);
+const DEFAULT_AGENT_TTL: Duration = Duration::from_secs(10);
+/// Upper bound on how long an agent may hold the canvas in one go.
+const MAX_AGENT_TTL: Duration = Duration::from_secs(60);
+/// Upper bound for the user, whose locks are renewed while a gesture lasts.
+const MAX_USER_TTL: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockOwner {
+    User,
+    Agent,
+}
+
+impl std::fmt::Display for LockOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockOwner::User => write!(f, "user"),
+            LockOwner::Agent => write!(f, "agent"),
+        }
+    }
+}
+
+/// Who holds the lock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Holder {
+    User,
+    /// An agent, by the id of its MCP session.
+    Agent(String),
+}
+
+impl Holder {
+    pub fn owner(&self) -> LockOwner {
+        match self {
+            Holder::User => LockOwner::User,
+            Holder::Agent(_) => LockOwner::Agent,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LockHeld {
+    pub owner: LockOwner,
+    pub retry_after: Duration,
+}
+
+impl std::fmt::Display for LockHeld {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Canvas locked by {} (retry after {}s)",
+            self.owner,
+            retry_after_secs(self.retry_after)
+        )
+    }
+}
+
+fn retry_after_secs(d: Duration) -> u64 {
+    // Round up so a client never retries before the lock lapses.
+    d.as_millis().div_ceil(1000).max(1) as u64
+}
+
+#[derive(Default)]
+pub struct CanvasLock {
+    held: Option<(Holder, Instant)>,
+}
+
+impl CanvasLock {
+    fn current(&self, now: Instant) -> Option<&(Holder, Instant)> {
+        self.held.as_ref().filter(|(_, expires)| *expires > now)
+    }
+
+    /// Take or extend the lock. Fails if anyone else holds it.
+    pub fn acquire(&mut self, holder: Holder, ttl: Duration, now: Instant) -> Result<(), LockHeld> {
+        self.check(&holder, now)?;
+        self.held = Some((holder, now + ttl));
+        Ok(())
+    }
+
+    /// Release the lock if `holder` holds it. Returns whether anything
+    /// changed.
+    pub fn release(&mut self, holder: &Holder, now: Instant) -> bool {
+        match self.current(now) {
+            Some((current, _)) if current == holder => {
+                self.held = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Check whether `holder` may write right now.
+    pub fn check(&self, holder: &Holder, now: Instant) -> Result<(), LockHeld> {
+        match self.current(now) {
+            Some((current, expires)) if current != holder => Err(LockHeld {
+                owner: current.owner(),
+                retry_after: *expires - now,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn holder(&self, now: Instant) -> Option<LockOwner> {
+        self.current(now).map(|(holder, _)| holder.owner())
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LockChanged {
+    owner: Option<LockOwner>,
+    ttl_ms: Option<u64>,
+}
+
+fn notify(state: &SharedApiState, owner: Option<LockOwner>, ttl: Option<Duration>) {
+    let _ = state.app_handle.emit(
+        "canvas-lock-changed",
+        LockChanged {
+            owner,
+            ttl_ms: ttl.map(|t| t.as_millis() as u64),
+        },
+    );
+}
+
+/// The TTL `owner` gets when asking for `ttl`, within its bounds.
+fn lock_ttl(owner: LockOwner, ttl: Option<Duration>) -> Duration {
+    match owner {
+        LockOwner::User => ttl.unwrap_or(DEFAULT_USER_TTL).min(MAX_USER_TTL),
+        LockOwner::Agent => ttl.unwrap_or(DEFAULT_AGENT_TTL).min(MAX_AGENT_TTL),
+    }
+}
+
+/// The `ttlSeconds` an agent asked for. Clamped before it becomes a
+/// `Duration`, which cannot hold infinite or huge values.
+fn requested_ttl(arguments: &serde_json::Value) -> Option<Duration> {
+    arguments
+        .get("ttlSeconds")
+        .and_then(|t| t.as_f64())
+        .filter(|t| *t > 0.0)
+        .map(|t| Duration::from_secs_f64(t.min(MAX_AGENT_TTL.as_secs_f64())))
+}
+
+pub async fn acquire(
+    state: &SharedApiState,
+    holder: Holder,
+    ttl: Option<Duration>,
+) -> Result<Duration, LockHeld> {
+    let owner = holder.owner();
+    let ttl = lock_ttl(owner, ttl);
+    state
+        .canvas_lock
+        .lock()
+        .await
+        .acquire(holder, ttl, Instant::now())?;
+    notify(state, Some(owner), Some(ttl));
+    Ok(ttl)
+}
+
+pub async fn release(state: &SharedApiState, holder: &Holder) -> bool {
+    let released = state
+        .canvas_lock
+        .lock()
+        .await
+        .release(holder, Instant::now());
+    if released {
+        notify(state, None, None);
+    }
+    released
+}
+
+/// Reject writes from the agent session `session` while the user or another
+/// session holds the lock.
+pub async fn check_agent_write(state: &SharedApiState, session: &str) -> Result<(), String> {
+    state
+        .canvas_lock
+        .lock()
+        .await
+        .check(&Holder::Agent(session.to_string()), Instant::now())
+        .map_err(|held| held.to_string())
+}
+
+// --- MCP tools (handled in Rust, not bridged) ---
+
+pub fn lock_tools() -> Vec<serde_json::Value> {
+    vec![
+        serde_json::json!({
+            "name": "acquire_canvas_lock",
+            "description": "Request a short exclusive editing window so a batch of changes isn't interleaved with edits from the user or other agents. Held by this MCP session only. Fails with a retry-after hint if the user or another agent holds the canvas.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "ttlSeconds": { "type": "number", "description": "How long to hold the lock (default 10, max 60)" }
+                }
+            }
+        }),
+        serde_json::json!({
+            "name": "release_canvas_lock",
+            "description": "Release a lock this session took with acquire_canvas_lock.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {}
+            }
+        }),
+    ]
+}
+
+pub async fn call_lock_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &serde_json::Value,
+    session: &str,
+) -> Option<Result<serde_json::Value, String>> {
+    let holder = Holder::Agent(session.to_string());
+    match tool_name {
+        "acquire_canvas_lock" => {
+            let ttl = requested_ttl(arguments);
+            Some(
+                acquire(state, holder, ttl)
+                    .await
+                    .map(|ttl| serde_json::json!({ "locked": true, "ttlSeconds": ttl.as_secs_f64() }))
+                    .map_err(|held| held.to_string()),
+            )
+        }
+        "release_canvas_lock" => {
+            let released = release(state, &holder).await;
+            Some(Ok(serde_json::json!({ "released": released })))
+        }
+        _ => None,
+    }
+}
+
+// --- Tauri commands ---
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockStatus {
+    pub owner: Option<LockOwner>,
+}
+
+/// The holder the app takes or releases the lock as. An agent lock taken
+/// from the app belongs to no MCP session.
+fn app_holder(owner: LockOwner) -> Holder {
+    match owner {
+        LockOwner::User => Holder::User,
+        LockOwner::Agent => Holder::Agent(String::new()),
+    }
+}
+
+/// Take the canvas lock on behalf of `owner`. Errors include a retry-after
+/// hint when someone else holds it.
+#[tauri::command]
+pub async fn acquire_canvas_lock(
+    owner: LockOwner,
+    ttl_ms: Option<u64>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<(), String> {
+    acquire(
+        state.inner(),
+        app_holder(owner),
+        ttl_ms.map(Duration::from_millis),
+    )
+    .await
+    .map(|_| ())
+    .map_err(|held| held.to_string())
+}
+
+#[tauri::command]
+pub async fn release_canvas_lock(
+    owner: LockOwner,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<bool, String> {
+    Ok(release(state.inner(), &app_holder(owner)).await)
+}
+
+#[tauri::command]
+pub async fn get_canvas_lock(
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<LockStatus, String> {
+    Ok(LockStatus {
+        owner: state.canvas_lock.lock().await.holder(Instant::now()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(session: &str) -> Holder {
+        Holder::Agent(session.to_string())
+    }
+
+    #[test]
+    fn user_lock_blocks_agent_writes_with_retry_hint() {
+        let mut lock = CanvasLock::default();
+        let now = Instant::now();
+        lock.acquire(Holder::User, Duration::from_secs(5), now)
+            .unwrap();
+
+        let held = lock
+            .check(&agent("a"), now + Duration::from_millis(500))
+            .unwrap_err();
+        assert_eq!(held.owner, LockOwner::User);
+        assert_eq!(held.to_string(), "Canvas locked by user (retry after 5s)");
+        assert!(lock.check(&Holder::User, now).is_ok());
+    }
+
+    #[test]
+    fn lock_lapses_after_ttl() {
+        let mut lock = CanvasLock::default();
+        let now = Instant::now();
+        lock.acquire(agent("a"), Duration::from_secs(2), now)
+            .unwrap();
+        assert!(lock
+            .acquire(Holder::User, Duration::from_secs(2), now)
+            .is_err());
+        assert!(lock
+            .acquire(
+                Holder::User,
+                Duration::from_secs(2),
+                now + Duration::from_secs(2)
+            )
+            .is_ok());
+        assert_eq!(
+            lock.holder(now + Duration::from_secs(3)),
+            Some(LockOwner::User)
+        );
+    }
+
+    #[test]
+    fn only_holder_can_release() {
+        let mut lock = CanvasLock::default();
+        let now = Instant::now();
+        lock.acquire(agent("a"), Duration::from_secs(2), now)
+            .unwrap();
+        assert!(!lock.release(&Holder::User, now));
+        assert!(!lock.release(&agent("b"), now));
+        assert!(lock.release(&agent("a"), now));
+        assert_eq!(lock.holder(now), None);
+    }
+
+    #[test]
+    fn one_agent_session_holds_the_lock_at_a_time() {
+        let mut lock = CanvasLock::default();
+        let now = Instant::now();
+        lock.acquire(agent("a"), Duration::from_secs(5), now)
+            .unwrap();
+
+        let held = lock.check(&agent("b"), now).unwrap_err();
+        assert_eq!(held.owner, LockOwner::Agent);
+        assert!(lock
+            .acquire(agent("b"), Duration::from_secs(5), now)
+            .is_err());
+        assert!(lock.check(&agent("a"), now).is_ok());
+
+        // The holder extends its own lock; the others wait for it.
+        let later = now + Duration::from_secs(4);
+        lock.acquire(agent("a"), Duration::from_secs(5), later)
+            .unwrap();
+        assert!(lock
+            .check(&agent("b"), now + Duration::from_secs(6))
+            .is_err());
+        assert!(lock
+            .acquire(
+                agent("b"),
+                Duration::from_secs(5),
+                later + Duration::from_secs(5)
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn huge_ttls_are_clamped() {
+        let ttl = |t: serde_json::Value| requested_ttl(&serde_json::json!({ "ttlSeconds": t }));
+        assert_eq!(ttl(serde_json::json!(1e300)), Some(MAX_AGENT_TTL));
+        assert_eq!(ttl(serde_json::json!(5)), Some(Duration::from_secs(5)));
+        assert_eq!(ttl(serde_json::json!(-1)), None);
+        assert_eq!(ttl(serde_json::json!("soon")), None);
+
+        let forever = Some(Duration::from_millis(u64::MAX));
+        assert_eq!(lock_ttl(LockOwner::User, forever), MAX_USER_TTL);
+        assert_eq!(lock_ttl(LockOwner::Agent, forever), MAX_AGENT_TTL);
+        let mut lock = CanvasLock::default();
+        lock.acquire(
+            Holder::User,
+            lock_ttl(LockOwner::User, forever),
+            Instant::now(),
+        )
+        .unwrap();
+    }
+}
@@ -2,5 +2,18 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+  if std::env::args().any(|arg| arg == "--mcp-stdio") {
+    std::process::exit(app_lib::run_mcp_stdio());
+  }
+  if std::env::args().any(|arg| arg == "--mcp-sidecar") {
+    std::process::exit(app_lib::run_mcp_sidecar());
+  }
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  if args.iter().any(|arg| arg == "--headless") {
+    std::process::exit(app_lib::run_headless(&args));
+  }
+  if args.first().map(String::as_str) == Some("export") {
+    std::process::exit(app_lib::run_cli_export(&args[1..]));
+  }
   app_lib::run();
 }
@@ -0,0 +1,220 @@
+//! mDNS (DNS-SD) advertisement of the MCP server on the LAN.
+//!
+//! While the server listens on the network (`mcpBind.lan`), it is announced
+//! as an `_mcp._tcp` service so other tools can find running instances
+//! without being told an address. The TXT record carries the endpoint path,
+//! the scheme and the name of the open document, which the webview reports
+//! with `set_mdns_document`; a new name is announced straight away. The
+//! `mdns-sd` daemon does the responding and says goodbye when the server
+//! stops. It only answers on the interface of the bound address, or on every
+//! interface when bound to the wildcard.
+
+use mdns_sd::{IfKind, ServiceDaemon, ServiceInfo};
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::api::{SharedApiState, MCP_SERVER_VERSION};
+
+const SERVICE: &str = "_mcp._tcp.local.";
+
+/// The service record for one running server.
+fn service_info(
+    machine: &str,
+    ip: IpAddr,
+    port: u16,
+    https: bool,
+    document: &str,
+) -> Result<ServiceInfo, String> {
+    let scheme = if https { "https" } else { "http" };
+    let properties = [
+        ("path", "/mcp"),
+        ("scheme", scheme),
+        ("version", MCP_SERVER_VERSION),
+        ("doc", document),
+    ];
+    let addresses: &[IpAddr] = if ip.is_unspecified() { &[] } else { &[ip] };
+    let info = ServiceInfo::new(
+        SERVICE,
+        &format!("Napkin on {} ({})", machine, port),
+        &format!("napkin-{}-{}.local.", port, machine),
+        addresses,
+        port,
+        &properties[..],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(if ip.is_unspecified() {
+        info.enable_addr_auto()
+    } else {
+        info
+    })
+}
+
+/// A daemon answering only where the server is reachable.
+fn daemon(ip: IpAddr) -> Result<ServiceDaemon, String> {
+    let daemon = ServiceDaemon::new().map_err(|e| e.to_string())?;
+    let restrict = match ip {
+        IpAddr::V4(v4) if v4.is_unspecified() => Some(IfKind::IPv6),
+        IpAddr::V6(v6) if v6.is_unspecified() => None,
+        _ => Some(IfKind::All),
+    };
+    if let Some(kind) = restrict {
+        daemon.disable_interface(kind).map_err(|e| e.to_string())?;
+    }
+    if !ip.is_unspecified() {
+        daemon
+            .enable_interface(IfKind::Addr(ip))
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(daemon)
+}
+
+/// A label safe for DNS: ASCII letters, digits and hyphens.
+fn host_label(name: &str) -> String {
+    let label: String = name
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let label = label.trim_matches('-');
+    if label.is_empty() {
+        "napkin".to_string()
+    } else {
+        label.to_string()
+    }
+}
+
+fn machine_name() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| {
+            let out = std::process::Command::new("hostname").output().ok()?;
+            Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+        })
+        .unwrap_or_default()
+}
+
+/// Advertise the server on `ip`:`port` until `shutdown` fires. Does nothing
+/// for loopback addresses.
+pub async fn advertise(
+    state: SharedApiState,
+    ip: IpAddr,
+    port: u16,
+    https: bool,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    if ip.is_loopback() {
+        return;
+    }
+    let machine = host_label(&machine_name());
+    let mut documents = state.mdns_document.subscribe();
+    let document = documents.borrow_and_update().clone();
+    let started = daemon(ip).and_then(|daemon| {
+        let info = service_info(&machine, ip, port, https, &document)?;
+        let fullname = info.get_fullname().to_string();
+        daemon.register(info).map_err(|e| e.to_string())?;
+        Ok((daemon, fullname))
+    });
+    let (daemon, fullname) = match started {
+        Ok(started) => started,
+        Err(e) => {
+            log::warn!("Not advertising the MCP server over mDNS: {}", e);
+            return;
+        }
+    };
+    log::info!("Advertising {} over mDNS", fullname);
+
+    loop {
+        tokio::select! {
+            changed = documents.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                let document = documents.borrow_and_update().clone();
+                // Registering the same name again replaces and re-announces it
+                let registered = service_info(&machine, ip, port, https, &document)
+                    .and_then(|info| daemon.register(info).map_err(|e| e.to_string()));
+                if let Err(e) = registered {
+                    log::warn!("mDNS update failed: {}", e);
+                }
+            }
+            changed = shutdown.changed() => {
+                if changed.is_err() || *shutdown.borrow() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Wait for the goodbye to go out before stopping the daemon
+    let name = fullname.clone();
+    let stopped = tokio::task::spawn_blocking(move || {
+        if let Ok(status) = daemon.unregister(&name) {
+            let _ = status.recv_timeout(Duration::from_secs(1));
+        }
+        let _ = daemon.shutdown();
+    })
+    .await;
+    if let Err(e) = stopped {
+        log::debug!("mDNS shutdown failed: {}", e);
+    }
+    log::info!("Stopped advertising {} over mDNS", fullname);
+}
+
+// --- Tauri commands ---
+
+/// Called by the webview when the open document's name changes.
+#[tauri::command]
+pub fn set_mdns_document(name: String, state: tauri::State<'_, SharedApiState>) {
+    state.mdns_document.send_if_modified(|current| {
+        if *current == name {
+            return false;
+        }
+        *current = name;
+        true
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn records_carry_the_port_and_document() {
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20));
+        let info = service_info("studio", ip, 21420, false, "Roadmap").unwrap();
+        assert_eq!(
+            info.get_fullname(),
+            "Napkin on studio (21420)._mcp._tcp.local."
+        );
+        assert_eq!(info.get_hostname(), "napkin-21420-studio.local.");
+        assert_eq!(info.get_port(), 21420);
+        assert!(info.get_addresses().contains(&ip));
+        assert_eq!(info.get_property_val_str("path"), Some("/mcp"));
+        assert_eq!(info.get_property_val_str("scheme"), Some("http"));
+        assert_eq!(info.get_property_val_str("doc"), Some("Roadmap"));
+
+        // The wildcard advertises whatever the interfaces hold
+        let any = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        let info = service_info("studio", any, 21420, true, "").unwrap();
+        assert!(info.is_addr_auto());
+        assert!(info.get_addresses().is_empty());
+        assert_eq!(info.get_property_val_str("scheme"), Some("https"));
+    }
+
+    #[test]
+    fn host_labels_are_dns_safe() {
+        assert_eq!(host_label("Ian's MacBook.local"), "ian-s-macbook");
+        assert_eq!(host_label(""), "napkin");
+    }
+}
@@ -0,0 +1,343 @@
+//! Typed mirror of the webview's document model (`src/lib/types.ts`).
+//!
+//! Tool arguments are checked against these types before they are bridged,
+//! so a malformed shape is rejected with a useful message instead of
+//! reaching the canvas half-built. Fields the backend does not model are
+//! kept in `extra`, so a shape read from the canvas serializes back
+//! unchanged.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::author::Author;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShapeType {
+    Rectangle,
+    Ellipse,
+    Line,
+    Arrow,
+    Freedraw,
+    Text,
+    Triangle,
+    Diamond,
+    Hexagon,
+    Star,
+    Cloud,
+    Cylinder,
+    Sticky,
+    Image,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StrokeStyle {
+    Solid,
+    Dashed,
+    Dotted,
+    DashedSmall,
+    DashedLarge,
+    DashDot,
+    DashDotDot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FillStyle {
+    Hachure,
+    Solid,
+    Zigzag,
+    CrossHatch,
+    Dots,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoutingMode {
+    Direct,
+    Elbow,
+    Curved,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionPoint {
+    Top,
+    Right,
+    Bottom,
+    Left,
+    Center,
+}
+
+/// Where a connector end is attached.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Binding {
+    pub shape_id: String,
+    pub point: ConnectionPoint,
+}
+
+/// A link from a shape to another tab, as left by `partition_canvas`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShapeLink {
+    pub tab_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shape_id: Option<String>,
+}
+
+/// A shape on the canvas. Only `type` is required: the webview fills in
+/// the id and defaults when it creates one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Shape {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: ShapeType,
+    #[serde(default)]
+    pub x: f64,
+    #[serde(default)]
+    pub y: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x2: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y2: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stroke_color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stroke_width: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stroke_style: Option<StrokeStyle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill_color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill_style: Option<FillStyle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_start: Option<Binding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_end: Option<Binding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing_mode: Option<RoutingMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<ShapeLink>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<Author>,
+    /// Type-specific fields not modelled above (points, arrowheads, ...).
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionType {
+    #[default]
+    Arrow,
+    Line,
+}
+
+/// The arguments of `create_connection`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Connection {
+    pub from_shape_id: String,
+    pub to_shape_id: String,
+    #[serde(default)]
+    pub connection_type: ConnectionType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing_mode: Option<RoutingMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stroke_color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stroke_width: Option<f64>,
+}
+
+/// Pan and zoom of a tab. Missing fields take the defaults of a new tab.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Viewport {
+    pub x: f64,
+    pub y: f64,
+    pub zoom: f64,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport {
+            x: 0.0,
+            y: 0.0,
+            zoom: 1.0,
+        }
+    }
+}
+
+/// An open tab, as reported by `list_tabs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tab {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub is_dirty: bool,
+    #[serde(default)]
+    pub is_active: bool,
+    #[serde(default)]
+    pub is_mcp_active: bool,
+}
+
+/// The result of `list_tabs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TabList {
+    pub tabs: Vec<Tab>,
+    #[serde(default)]
+    pub active_tab_id: Option<String>,
+    #[serde(default)]
+    pub mcp_active_tab_id: Option<String>,
+}
+
+fn parse<T: serde::de::DeserializeOwned>(what: &str, value: &Value) -> Result<T, String> {
+    T::deserialize(value).map_err(|e| format!("Invalid {}: {}", what, e))
+}
+
+/// Check the arguments of a tool that builds shapes against the model.
+/// Tools not listed here are passed through unchecked.
+pub fn validate_arguments(tool_name: &str, arguments: &Value) -> Result<(), String> {
+    match tool_name {
+        "create_shape" => parse::<Shape>("shape", arguments).map(drop),
+        "create_connection" => parse::<Connection>("connection", arguments).map(drop),
+        "set_viewport" => parse::<Viewport>("viewport", arguments).map(drop),
+        "batch_operations" => {
+            let operations = arguments
+                .get("operations")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten();
+            for (i, op) in operations.enumerate() {
+                if op.get("action").and_then(Value::as_str) == Some("create") {
+                    let data = op.get("data").unwrap_or(&Value::Null);
+                    parse::<Shape>(&format!("shape in operation {}", i), data)?;
+                }
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn roundtrip<T: Serialize + serde::de::DeserializeOwned>(value: Value) {
+        let typed: T = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&typed).unwrap(), value);
+    }
+
+    #[test]
+    fn shapes_roundtrip_losslessly() {
+        roundtrip::<Shape>(json!({
+            "id": "shape_1",
+            "type": "rectangle",
+            "x": 10.0,
+            "y": 20.0,
+            "width": 200.0,
+            "height": 150.0,
+            "strokeColor": "#000000",
+            "strokeWidth": 2.0,
+            "strokeStyle": "dash-dot",
+            "fillColor": "transparent",
+            "fillStyle": "cross-hatch",
+            "opacity": 1.0,
+            "roughness": 1,
+            "rotation": 0.0,
+            "text": "Hello",
+            "textAlign": "center",
+            "link": { "tabId": "tab_2" },
+            "createdBy": { "name": "Me", "color": "#1e88e5", "source": "user" },
+        }));
+        roundtrip::<Shape>(json!({
+            "id": "shape_2",
+            "type": "arrow",
+            "x": 0.0,
+            "y": 0.0,
+            "x2": 100.0,
+            "y2": 50.0,
+            "bindStart": { "shapeId": "shape_1", "point": "right" },
+            "bindEnd": { "shapeId": "shape_3", "point": "left" },
+            "routingMode": "elbow",
+            "controlPoints": [{ "x": 50, "y": 0 }],
+            "arrowheadEnd": true,
+        }));
+    }
+
+    #[test]
+    fn connections_viewports_and_tabs_roundtrip() {
+        roundtrip::<Connection>(json!({
+            "fromShapeId": "a",
+            "toShapeId": "b",
+            "connectionType": "line",
+            "routingMode": "curved",
+            "text": "uses",
+        }));
+        roundtrip::<Viewport>(json!({ "x": -40.0, "y": 12.5, "zoom": 2.0 }));
+        roundtrip::<TabList>(json!({
+            "tabs": [
+                { "id": "t1", "title": "Main", "isDirty": true, "isActive": true, "isMcpActive": false },
+                { "id": "t2", "title": "Notes", "isDirty": false, "isActive": false, "isMcpActive": true },
+            ],
+            "activeTabId": "t1",
+            "mcpActiveTabId": "t2",
+        }));
+    }
+
+    #[test]
+    fn rejects_malformed_arguments() {
+        validate_arguments(
+            "create_shape",
+            &json!({ "type": "rectangle", "x": 1, "y": 2 }),
+        )
+        .unwrap();
+        let err =
+            validate_arguments("create_shape", &json!({ "type": "blob", "x": 1 })).unwrap_err();
+        assert!(
+            err.starts_with("Invalid shape: unknown variant `blob`"),
+            "{}",
+            err
+        );
+        assert!(
+            validate_arguments("create_shape", &json!({ "type": "text", "x": "left" })).is_err()
+        );
+
+        assert!(validate_arguments("create_connection", &json!({ "fromShapeId": "a" })).is_err());
+        assert!(validate_arguments("set_viewport", &json!({ "zoom": "big" })).is_err());
+        validate_arguments("set_viewport", &json!({ "zoom": 2 })).unwrap();
+
+        let batch = json!({ "operations": [
+            { "action": "delete", "data": { "id": "a" } },
+            { "action": "create", "data": { "type": "ellipse", "fillStyle": "plaid" } },
+        ]});
+        let err = validate_arguments("batch_operations", &batch).unwrap_err();
+        assert!(err.starts_with("Invalid shape in operation 1"), "{}", err);
+    }
+}
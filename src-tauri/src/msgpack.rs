@@ -0,0 +1,207 @@
+//! MessagePack for clients that would rather not parse JSON.
+//!
+//! The handlers all speak JSON; this middleware translates at the edge. A
+//! request whose `Content-Type` is `application/msgpack` is decoded and
+//! handed on as JSON, and a JSON response goes back as MessagePack when the
+//! request's `Accept` lists `application/msgpack`. The two are independent,
+//! so a client can send either and ask for either. Event streams and
+//! anything else that isn't JSON pass through untouched.
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::{json, Value};
+
+use crate::api::mcp_error;
+
+const CONTENT_TYPE: &str = "application/msgpack";
+/// Names other clients use for the same format.
+const ALIASES: [&str; 2] = ["application/x-msgpack", "application/vnd.msgpack"];
+/// JSON-RPC's "Parse error".
+const PARSE_ERROR: i64 = -32700;
+
+/// The media type of a header value, without parameters.
+fn essence(value: &str) -> String {
+    value
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn is_msgpack(media_type: &str) -> bool {
+    media_type == CONTENT_TYPE || ALIASES.contains(&media_type)
+}
+
+fn sends_msgpack(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| is_msgpack(&essence(v)))
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| essence(v) == "application/json")
+}
+
+/// Whether `Accept` lists MessagePack (and doesn't refuse it with `q=0`).
+fn accepts_msgpack(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|item| {
+            let refused = item.split(';').skip(1).any(|param| {
+                param
+                    .trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f64>().ok())
+                    == Some(0.0)
+            });
+            is_msgpack(&essence(item)) && !refused
+        })
+}
+
+fn unreadable(path: &str, error: String) -> Response {
+    let message = format!("Request body is not valid MessagePack: {}", error);
+    let body = if path.starts_with("/api/v1/") {
+        json!({ "error": message, "code": "INVALID_ARGUMENT" })
+    } else {
+        mcp_error(None, PARSE_ERROR, &message)
+    };
+    (StatusCode::BAD_REQUEST, Json(body)).into_response()
+}
+
+/// A MessagePack request, as the JSON the handlers expect.
+async fn decode(request: Request) -> Result<Request, String> {
+    let (mut parts, body) = request.into_parts();
+    // The body limit has already been applied
+    let bytes = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| e.to_string())?;
+    let value: Value = rmp_serde::from_slice(&bytes).map_err(|e| e.to_string())?;
+    let json = serde_json::to_vec(&value).map_err(|e| e.to_string())?;
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Ok(Request::from_parts(parts, Body::from(json)))
+}
+
+/// A JSON response, as MessagePack.
+async fn encode(response: Response) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let packed = serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .and_then(|value| rmp_serde::to_vec_named(&value).ok());
+    let Some(packed) = packed else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("accept"));
+    Response::from_parts(parts, Body::from(packed))
+}
+
+pub async fn negotiate(request: Request, next: Next) -> Response {
+    let wants_msgpack = accepts_msgpack(request.headers());
+    let request = if sends_msgpack(request.headers()) {
+        let path = request.uri().path().to_string();
+        match decode(request).await {
+            Ok(request) => request,
+            Err(e) => return unreadable(&path, e),
+        }
+    } else {
+        request
+    };
+    let response = next.run(request).await;
+    if wants_msgpack && is_json(response.headers()) {
+        encode(response).await
+    } else {
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::{middleware, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn bodies_are_translated_both_ways() {
+        let app = Router::new()
+            .route(
+                "/mcp",
+                post(|Json(body): Json<Value>| async move { Json(json!({ "echo": body })) }),
+            )
+            .layer(middleware::from_fn(negotiate));
+        let send = |content_type: &'static str, accept: &'static str, body: Vec<u8>| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder()
+                    .method("POST")
+                    .uri("/mcp")
+                    .header(header::CONTENT_TYPE, content_type)
+                    .header(header::ACCEPT, accept)
+                    .body(Body::from(body))
+                    .unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let content_type = response.headers()[header::CONTENT_TYPE].clone();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (status, content_type, bytes.to_vec())
+            }
+        };
+        let message = json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" });
+        let packed = rmp_serde::to_vec_named(&message).unwrap();
+        let expected = json!({ "echo": message });
+
+        let (status, content_type, body) = send(CONTENT_TYPE, CONTENT_TYPE, packed.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, CONTENT_TYPE);
+        assert_eq!(rmp_serde::from_slice::<Value>(&body).unwrap(), expected);
+
+        // Either side can be JSON
+        let (_, content_type, body) =
+            send("application/x-msgpack", "application/json", packed).await;
+        assert_eq!(content_type, "application/json");
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), expected);
+        let json_body = serde_json::to_vec(&message).unwrap();
+        let (_, content_type, body) = send(
+            "application/json",
+            "application/json;q=0.5, application/msgpack",
+            json_body.clone(),
+        )
+        .await;
+        assert_eq!(content_type, CONTENT_TYPE);
+        assert_eq!(rmp_serde::from_slice::<Value>(&body).unwrap(), expected);
+        let (_, content_type, _) =
+            send("application/json", "application/msgpack;q=0", json_body).await;
+        assert_eq!(content_type, "application/json");
+
+        let (status, _, body) = send(CONTENT_TYPE, "*/*", vec![0xc1]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error["error"]["code"], PARSE_ERROR);
+    }
+}
@@ -0,0 +1,325 @@
+//! The OpenAPI 3.1 document for the REST API, served at `/api/openapi.json`.
+//!
+//! It is built from the MCP tool schemas, so it cannot drift from what the
+//! tools accept: each route's request body or query parameters are its
+//! tool's `inputSchema` (with the shape defaults and length strings
+//! `tools/list` advertises), and the `Shape` schema is `create_shape`'s
+//! properties plus the fields Napkin fills in. The document needs no token,
+//! so code generators and Postman can import it by URL; the routes it
+//! describes do.
+
+use axum::{extract::State as AxumState, Json};
+use serde_json::{json, Map, Value};
+
+use crate::api::{self, SharedApiState, MCP_SERVER_VERSION};
+use crate::rest::{Operation, OPERATIONS};
+use crate::settings;
+use crate::shape_defaults::{self, ShapeDefaults};
+use crate::units;
+
+pub const PATH: &str = "/api/openapi.json";
+
+fn input_schema(tools: &Value, tool: &str) -> Value {
+    tools
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|t| t.get("name").and_then(Value::as_str) == Some(tool))
+        .and_then(|t| t.get("inputSchema"))
+        .cloned()
+        .unwrap_or_else(|| json!({ "type": "object" }))
+}
+
+fn description(tools: &Value, tool: &str) -> Option<Value> {
+    tools
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|t| t.get("name").and_then(Value::as_str) == Some(tool))
+        .and_then(|t| t.get("description"))
+        .cloned()
+}
+
+/// A shape as the API returns it: everything `create_shape` takes, the
+/// full set of types, and the fields set by Napkin.
+fn shape_schema(tools: &Value) -> Value {
+    let mut schema = input_schema(tools, "create_shape");
+    let types = input_schema(tools, "list_shapes")
+        .pointer("/properties/type/enum")
+        .cloned()
+        .unwrap_or_else(|| json!([]));
+    if let Some(properties) = schema["properties"].as_object_mut() {
+        properties.insert(
+            "id".to_string(),
+            json!({ "type": "string", "description": "Shape ID" }),
+        );
+        properties.insert(
+            "type".to_string(),
+            json!({ "type": "string", "enum": types }),
+        );
+        properties.insert(
+            "createdBy".to_string(),
+            json!({ "type": "string", "description": "Who drew the shape: a client name, or \"user\"" }),
+        );
+    }
+    schema["required"] = json!(["id", "type", "x", "y"]);
+    schema["additionalProperties"] = json!(true);
+    schema
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// The schema of a tool's successful result.
+fn result_schema(tool: &str) -> Value {
+    match tool {
+        "create_shape" | "get_shape" | "update_shape" => reference("Shape"),
+        "list_shapes" => json!({
+            "type": "object",
+            "properties": {
+                "shapes": { "type": "array", "items": reference("Shape") },
+                "count": { "type": "integer" },
+                "total": { "type": "integer" },
+                "nextOffset": { "type": "integer" },
+            },
+        }),
+        "list_tabs" => json!({
+            "type": "object",
+            "properties": {
+                "tabs": { "type": "array", "items": reference("Tab") },
+                "activeTabId": { "type": ["string", "null"] },
+                "mcpActiveTabId": { "type": ["string", "null"] },
+            },
+        }),
+        _ => json!({ "type": "object" }),
+    }
+}
+
+fn error_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": reference("Error") } },
+    })
+}
+
+fn responses(operation: &Operation, has_body: bool) -> Value {
+    let mut responses = Map::new();
+    let success = if operation.status == 204 {
+        json!({ "description": "Done" })
+    } else {
+        json!({
+            "description": "The tool's result",
+            "content": { "application/json": { "schema": result_schema(operation.tool) } },
+        })
+    };
+    responses.insert(operation.status.to_string(), success);
+    let bad_request = if has_body {
+        "The body is not a JSON object, or the tool is disabled or outside the client's profile"
+    } else {
+        "The tool is disabled or outside the client's profile"
+    };
+    responses.insert("400".to_string(), error_response(bad_request));
+    responses.insert(
+        "401".to_string(),
+        json!({ "description": "Missing or wrong bearer token" }),
+    );
+    responses.insert(
+        "403".to_string(),
+        error_response("Refused: read-only mode, a hidden board, or declined"),
+    );
+    let not_found = if operation.path.contains('{') {
+        "No such shape, or a tool this version of Napkin does not implement"
+    } else {
+        "A tool this version of Napkin does not implement"
+    };
+    responses.insert("404".to_string(), error_response(not_found));
+    responses.insert(
+        "409".to_string(),
+        error_response("The canvas is locked by another client"),
+    );
+    responses.insert(
+        "422".to_string(),
+        error_response("The tool rejected the arguments"),
+    );
+    responses.insert(
+        "504".to_string(),
+        error_response("The Napkin window did not answer in time"),
+    );
+    responses.insert(
+        "500".to_string(),
+        error_response("The server failed to handle the call"),
+    );
+    Value::Object(responses)
+}
+
+/// The OpenAPI operation for one route, given the MCP tool list.
+fn operation(tools: &Value, operation: &Operation) -> Value {
+    let mut schema = input_schema(tools, operation.tool);
+    let mut parameters = Vec::new();
+
+    // Path parameters come out of the tool's arguments
+    let path_names: Vec<&str> = operation
+        .path
+        .split('/')
+        .filter_map(|s| s.strip_prefix('{')?.strip_suffix('}'))
+        .collect();
+    for name in &path_names {
+        let property = schema
+            .get_mut("properties")
+            .and_then(Value::as_object_mut)
+            .and_then(|p| p.remove(*name))
+            .unwrap_or_else(|| json!({ "type": "string" }));
+        parameters.push(json!({
+            "name": name,
+            "in": "path",
+            "required": true,
+            "schema": property,
+        }));
+    }
+    if let Some(required) = schema.get_mut("required").and_then(Value::as_array_mut) {
+        required.retain(|r| !r.as_str().is_some_and(|r| path_names.contains(&r)));
+    }
+
+    let has_body = matches!(operation.method, "post" | "patch");
+    let mut entry = json!({
+        "operationId": operation.tool,
+        "tags": [if operation.path.contains("/tabs") { "Tabs" } else { "Canvas" }],
+    });
+    if let Some(description) = description(tools, operation.tool) {
+        entry["description"] = description;
+    }
+    if has_body {
+        let required = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .is_some_and(|r| !r.is_empty());
+        entry["requestBody"] = json!({
+            "required": required,
+            "content": { "application/json": { "schema": schema } },
+        });
+    } else {
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        for (name, property) in schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+        {
+            parameters.push(json!({
+                "name": name,
+                "in": "query",
+                "required": required.contains(&name.as_str()),
+                "schema": property,
+            }));
+        }
+    }
+    if !parameters.is_empty() {
+        entry["parameters"] = Value::Array(parameters);
+    }
+    entry["responses"] = responses(operation, has_body);
+    entry
+}
+
+/// The whole document, with `create_shape`'s descriptions stating
+/// `defaults`.
+pub fn document(defaults: &ShapeDefaults) -> Value {
+    let tools = units::with_lengths(shape_defaults::describe_tools(api::all_tools(), defaults));
+    let mut paths = Map::new();
+    for op in OPERATIONS {
+        let item = paths
+            .entry(op.path.to_string())
+            .or_insert_with(|| json!({}));
+        item[op.method] = operation(&tools, op);
+    }
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Napkin",
+            "version": MCP_SERVER_VERSION,
+            "description": "REST access to the open Napkin canvas. Each route runs the MCP tool of the same name, with the same permissions.",
+        },
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+            },
+            "schemas": {
+                "Shape": shape_schema(&tools),
+                "Tab": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "title": { "type": "string" },
+                        "isDirty": { "type": "boolean" },
+                        "isActive": { "type": "boolean" },
+                        "isMcpActive": { "type": "boolean" },
+                    },
+                },
+                "Error": {
+                    "type": "object",
+                    "properties": {
+                        "error": { "type": "string" },
+                        "code": {
+                            "type": "string",
+                            "enum": ["SHAPE_NOT_FOUND", "INVALID_ARGUMENT", "TIMEOUT", "CANCELLED", "CANVAS_LOCKED", "PERMISSION_DENIED", "INTERNAL"],
+                        },
+                        "reason": { "type": "string", "description": "Why a call was refused, e.g. readOnly or hiddenTab" },
+                    },
+                    "required": ["error"],
+                },
+            },
+        },
+        "security": [{ "bearer": [] }],
+    })
+}
+
+pub async fn openapi_handler(AxumState(state): AxumState<SharedApiState>) -> Json<Value> {
+    let defaults = settings::current(&state.app_handle).shape_defaults;
+    Json(document(&defaults))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_route_is_described_from_its_tool() {
+        let doc = document(&ShapeDefaults::default());
+        for op in OPERATIONS {
+            let entry = &doc["paths"][op.path][op.method];
+            assert_eq!(entry["operationId"], op.tool, "{} {}", op.method, op.path);
+            assert!(entry["responses"][op.status.to_string()].is_object());
+        }
+
+        let create = &doc["paths"]["/api/v1/shapes"]["post"];
+        let body = &create["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(body["required"], json!(["type", "x", "y"]));
+
+        // The id moves from the body to the path
+        let update = &doc["paths"]["/api/v1/shapes/{id}"]["patch"];
+        let body = &update["requestBody"]["content"]["application/json"]["schema"];
+        assert!(body["properties"].get("id").is_none());
+        assert_eq!(body["required"], json!([]));
+        assert_eq!(update["parameters"][0]["in"], "path");
+
+        let list = &doc["paths"]["/api/v1/shapes"]["get"];
+        let names: Vec<&str> = list["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert!(names.contains(&"type") && names.contains(&"createdBy"));
+        assert_eq!(
+            doc["components"]["schemas"]["Shape"]["properties"]["type"]["enum"][0],
+            "rectangle"
+        );
+    }
+}
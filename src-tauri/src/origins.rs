@@ -0,0 +1,103 @@
+//! Which web pages may call the server from a browser.
+//!
+//! CORS only matters to browser-based clients: desktop agents don't send an
+//! `Origin`, and they are never refused here. A page is let in when it is the
+//! app's own webview or served from localhost, or when its origin is one of
+//! the `allowedOrigins` in settings (`https://tools.example.com`, with a
+//! port if it isn't the scheme's default). The list is read on each
+//! request, so an origin added through `update_settings` works at once.
+
+use axum::http::{HeaderValue, Uri};
+use tower_http::cors::AllowOrigin;
+
+use crate::settings;
+
+/// Origins allowed whatever the settings say.
+fn is_built_in(origin: &[u8]) -> bool {
+    origin == b"tauri://localhost"
+        || origin == b"http://localhost"
+        || origin == b"https://localhost"
+        || origin.starts_with(b"http://localhost:")
+        || origin.starts_with(b"http://127.0.0.1:")
+}
+
+/// `entry` as a browser would send it in `Origin`: lowercase
+/// `scheme://host[:port]`, with no path.
+fn normalize(entry: &str) -> Result<String, String> {
+    let invalid = |why: &str| format!("Allowed origin '{}' {}", entry, why);
+    let uri: Uri = entry.trim().parse().map_err(|_| invalid("is not a URL"))?;
+    let (Some(scheme), Some(authority)) = (uri.scheme_str(), uri.authority()) else {
+        return Err(invalid(
+            "needs a scheme and host, like https://tools.example.com",
+        ));
+    };
+    if scheme != "http" && scheme != "https" {
+        return Err(invalid("must use http or https"));
+    }
+    if uri.path_and_query().is_some_and(|p| p.as_str() != "/") {
+        return Err(invalid("must not have a path"));
+    }
+    if authority.as_str().contains('@') {
+        return Err(invalid("must not have a user name"));
+    }
+    Ok(format!("{}://{}", scheme, authority).to_ascii_lowercase())
+}
+
+pub fn validate(origins: &[String]) -> Result<(), String> {
+    origins
+        .iter()
+        .try_for_each(|origin| normalize(origin).map(|_| ()))
+}
+
+/// Whether a page at `origin` may call the server.
+pub fn is_allowed(origin: &HeaderValue, configured: &[String]) -> bool {
+    let origin = origin.as_bytes();
+    is_built_in(origin)
+        || configured
+            .iter()
+            .filter_map(|entry| normalize(entry).ok())
+            .any(|entry| entry.as_bytes().eq_ignore_ascii_case(origin))
+}
+
+/// The CORS origin check for the app's router.
+pub fn allow_origin(app: tauri::AppHandle) -> AllowOrigin {
+    AllowOrigin::predicate(move |origin, _| {
+        is_allowed(origin, &settings::current(&app).allowed_origins)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_origins_are_allowed_alongside_localhost() {
+        let configured = vec![
+            "https://Tools.Example.com/".to_string(),
+            "http://10.0.0.5:8080".to_string(),
+        ];
+        let allowed =
+            |origin: &'static str| is_allowed(&HeaderValue::from_static(origin), &configured);
+        assert!(allowed("tauri://localhost"));
+        assert!(allowed("http://localhost:5173"));
+        assert!(allowed("https://tools.example.com"));
+        assert!(allowed("http://10.0.0.5:8080"));
+        assert!(!allowed("http://tools.example.com"));
+        assert!(!allowed("http://10.0.0.5:9090"));
+        assert!(!allowed("https://evil.example.com"));
+        assert!(!is_allowed(
+            &HeaderValue::from_static("https://tools.example.com"),
+            &[]
+        ));
+
+        assert!(validate(&configured).is_ok());
+        for bad in [
+            "tools.example.com",
+            "ftp://tools.example.com",
+            "https://tools.example.com/app",
+            "https://user@tools.example.com",
+        ] {
+            assert!(validate(&[bad.to_string()]).is_err(), "{}", bad);
+        }
+    }
+}
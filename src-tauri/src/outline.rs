@@ -0,0 +1,475 @@
+//! Canvas-to-text outline (`extract_outline`).
+//!
+//! Collects the text on a board (text shapes, stickies and shape labels) and
+//! nests it under the frames and groups that hold it. A frame is any closed
+//! shape that encloses others belonging to the same group; groups come from
+//! the document. Siblings are put in reading order with a recursive XY-cut:
+//! items are split at whitespace between rows, or between columns when the
+//! gutter is clearly wider, so a distant cluster is read as a whole before
+//! the next one and a grid is read row by row.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::api::SharedApiState;
+use crate::geometry::{self, Bounds};
+use crate::resources;
+
+/// Shapes that can enclose others. Stickies and images hold their own
+/// content, and lines never enclose anything.
+pub(crate) const FRAME_TYPES: &[&str] = &[
+    "rectangle",
+    "ellipse",
+    "triangle",
+    "diamond",
+    "hexagon",
+    "star",
+    "cloud",
+    "cylinder",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Shape,
+    Group,
+}
+
+struct Entity {
+    id: String,
+    kind: Kind,
+    shape_type: Option<String>,
+    text: Option<String>,
+    bounds: Bounds,
+    /// The group the entity belongs to directly.
+    scope: Option<String>,
+    can_enclose: bool,
+}
+
+fn area(b: &Bounds) -> f64 {
+    b.width() * b.height()
+}
+
+pub(crate) fn encloses(outer: &Bounds, inner: &Bounds) -> bool {
+    outer.min_x <= inner.min_x
+        && outer.min_y <= inner.min_y
+        && outer.max_x >= inner.max_x
+        && outer.max_y >= inner.max_y
+        && area(outer) > area(inner)
+}
+
+fn text_of(shape: &Value) -> Option<String> {
+    shape
+        .get("text")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+}
+
+/// Shapes and groups as outline entities; group bounds cover every member.
+fn entities(shapes: &[Value], groups: &[Value]) -> Vec<Entity> {
+    let str_field = |v: &Value, key: &str| v.get(key).and_then(Value::as_str).map(str::to_string);
+    let parent_group: HashMap<String, Option<String>> = groups
+        .iter()
+        .filter_map(|g| Some((str_field(g, "id")?, str_field(g, "parentGroupId"))))
+        .collect();
+    let mut member_of: HashMap<String, String> = HashMap::new();
+    for group in groups {
+        let Some(id) = str_field(group, "id") else {
+            continue;
+        };
+        let members = group.get("shapeIds").and_then(Value::as_array);
+        for member in members.into_iter().flatten().filter_map(Value::as_str) {
+            member_of.insert(member.to_string(), id.clone());
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut group_bounds: HashMap<String, Bounds> = HashMap::new();
+    for shape in shapes {
+        let (Some(id), Some(bounds)) = (str_field(shape, "id"), geometry::shape_bounds(shape))
+        else {
+            continue;
+        };
+        let shape_type = str_field(shape, "type").unwrap_or_default();
+        let scope = member_of
+            .get(&id)
+            .cloned()
+            .or_else(|| str_field(shape, "groupId"));
+        // Every enclosing group grows to cover the shape.
+        let mut group = scope.clone();
+        let mut depth = 0;
+        while let Some(g) = group.filter(|_| depth <= parent_group.len()) {
+            group_bounds
+                .entry(g.clone())
+                .and_modify(|b| *b = b.union(&bounds))
+                .or_insert(bounds);
+            group = parent_group.get(&g).cloned().flatten();
+            depth += 1;
+        }
+        result.push(Entity {
+            id,
+            kind: Kind::Shape,
+            can_enclose: FRAME_TYPES.contains(&shape_type.as_str()),
+            shape_type: Some(shape_type),
+            text: text_of(shape),
+            bounds,
+            scope,
+        });
+    }
+    for (id, parent) in parent_group {
+        if let Some(bounds) = group_bounds.remove(&id) {
+            result.push(Entity {
+                id,
+                kind: Kind::Group,
+                shape_type: None,
+                text: None,
+                bounds,
+                scope: parent,
+                can_enclose: false,
+            });
+        }
+    }
+    result
+}
+
+/// Each entity's parent: the smallest frame in the same group that encloses
+/// it, else its group. Frames only ever enclose strictly smaller things and
+/// groups only their members, so this is a tree.
+fn parents(entities: &[Entity]) -> Vec<Option<usize>> {
+    let groups: HashMap<&str, usize> = entities
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.kind == Kind::Group)
+        .map(|(i, e)| (e.id.as_str(), i))
+        .collect();
+    entities
+        .iter()
+        .enumerate()
+        .map(|(i, entity)| {
+            let frame = entities
+                .iter()
+                .enumerate()
+                .filter(|(j, f)| {
+                    *j != i
+                        && f.can_enclose
+                        && f.scope == entity.scope
+                        && encloses(&f.bounds, &entity.bounds)
+                })
+                .min_by(|(_, a), (_, b)| area(&a.bounds).total_cmp(&area(&b.bounds)))
+                .map(|(j, _)| j);
+            frame.or_else(|| groups.get(entity.scope.as_deref()?).copied())
+        })
+        .collect()
+}
+
+// --- Reading order ---
+
+/// An entity's index and bounds.
+type Item = (usize, Bounds);
+
+/// Columns are read one at a time only when the gutter between them is this
+/// much wider than the space between rows; otherwise the board is read row
+/// by row, like text.
+const COLUMN_BIAS: f64 = 2.0;
+
+/// Split `items` at gaps along one axis. Returns the widest gap and the runs
+/// between gaps at least half as wide, or `None` when nothing separates them.
+fn split(items: &[Item], vertical: bool) -> Option<(f64, Vec<Vec<Item>>)> {
+    let span = |b: &Bounds| {
+        if vertical {
+            (b.min_y, b.max_y)
+        } else {
+            (b.min_x, b.max_x)
+        }
+    };
+    let mut sorted = items.to_vec();
+    sorted.sort_by(|a, b| span(&a.1).0.total_cmp(&span(&b.1).0));
+
+    // Gaps between the merged intervals, as (index where the next run starts, size).
+    let mut gaps = Vec::new();
+    let mut end = span(&sorted[0].1).1;
+    for (i, (_, b)) in sorted.iter().enumerate().skip(1) {
+        let (start, stop) = span(b);
+        if start > end {
+            gaps.push((i, start - end));
+        }
+        end = end.max(stop);
+    }
+    let widest = gaps.iter().map(|(_, g)| *g).fold(0.0, f64::max);
+    if widest <= 0.0 {
+        return None;
+    }
+    let mut runs = Vec::new();
+    let mut from = 0;
+    for (at, _) in gaps.into_iter().filter(|(_, g)| *g >= widest / 2.0) {
+        runs.push(sorted[from..at].to_vec());
+        from = at;
+    }
+    runs.push(sorted[from..].to_vec());
+    Some((widest, runs))
+}
+
+fn xy_cut(items: &[Item], out: &mut Vec<usize>) {
+    if items.len() <= 1 {
+        out.extend(items.iter().map(|(i, _)| *i));
+        return;
+    }
+    let rows = split(items, true);
+    let columns = split(items, false);
+    let runs = match (rows, columns) {
+        (Some((row_gap, rows)), Some((column_gap, columns))) => {
+            if column_gap > row_gap * COLUMN_BIAS {
+                columns
+            } else {
+                rows
+            }
+        }
+        (Some((_, runs)), None) | (None, Some((_, runs))) => runs,
+        (None, None) => {
+            // Overlapping everywhere: top to bottom, then left to right.
+            let mut sorted = items.to_vec();
+            sorted.sort_by(|a, b| {
+                a.1.min_y
+                    .total_cmp(&b.1.min_y)
+                    .then(a.1.min_x.total_cmp(&b.1.min_x))
+            });
+            out.extend(sorted.iter().map(|(i, _)| *i));
+            return;
+        }
+    };
+    for run in runs {
+        xy_cut(&run, out);
+    }
+}
+
+/// Indices of `bounds` in reading order.
+pub fn reading_order(bounds: &[Bounds]) -> Vec<usize> {
+    let items: Vec<Item> = bounds.iter().copied().enumerate().collect();
+    let mut order = Vec::with_capacity(items.len());
+    xy_cut(&items, &mut order);
+    order
+}
+
+// --- Outline ---
+
+/// Nested outline of `shapes`, plus the same outline as indented Markdown.
+pub fn outline(shapes: &[Value], groups: &[Value]) -> Value {
+    let entities = entities(shapes, groups);
+    let parents = parents(&entities);
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); entities.len()];
+    let mut roots = Vec::new();
+    for (i, parent) in parents.iter().enumerate() {
+        match parent {
+            Some(p) => children[*p].push(i),
+            None => roots.push(i),
+        }
+    }
+
+    let ctx = Context {
+        entities: &entities,
+        children: &children,
+    };
+    let nodes = ctx.nodes(&roots);
+    let mut text = String::new();
+    render(&nodes, 0, &mut text);
+    json!({ "outline": nodes, "text": text })
+}
+
+struct Context<'a> {
+    entities: &'a [Entity],
+    children: &'a [Vec<usize>],
+}
+
+impl Context<'_> {
+    /// Outline nodes for `indices` in reading order, dropping anything with
+    /// no text of its own or below it.
+    fn nodes(&self, indices: &[usize]) -> Vec<Value> {
+        let bounds: Vec<Bounds> = indices.iter().map(|&i| self.entities[i].bounds).collect();
+        reading_order(&bounds)
+            .into_iter()
+            .filter_map(|k| self.node(indices[k]))
+            .collect()
+    }
+
+    fn node(&self, index: usize) -> Option<Value> {
+        let entity = &self.entities[index];
+        let children = self.nodes(&self.children[index]);
+        if entity.text.is_none() && children.is_empty() {
+            return None;
+        }
+        let kind = match entity.kind {
+            Kind::Group => "group",
+            Kind::Shape if children.is_empty() => "text",
+            Kind::Shape => "frame",
+        };
+        let mut node = json!({ "id": entity.id, "kind": kind });
+        if let Some(shape_type) = &entity.shape_type {
+            node["type"] = json!(shape_type);
+        }
+        if let Some(text) = &entity.text {
+            node["text"] = json!(text);
+        }
+        if !children.is_empty() {
+            node["children"] = Value::Array(children);
+        }
+        Some(node)
+    }
+}
+
+fn render(nodes: &[Value], depth: usize, out: &mut String) {
+    for node in nodes {
+        let label = match node.get("text").and_then(Value::as_str) {
+            Some(text) => text.split_whitespace().collect::<Vec<_>>().join(" "),
+            None => format!(
+                "[{}]",
+                node.get("type")
+                    .or_else(|| node.get("kind"))
+                    .and_then(Value::as_str)
+                    .unwrap_or("group")
+            ),
+        };
+        out.push_str(&"  ".repeat(depth));
+        out.push_str("- ");
+        out.push_str(&label);
+        out.push('\n');
+        if let Some(children) = node.get("children").and_then(Value::as_array) {
+            render(children, depth + 1, out);
+        }
+    }
+}
+
+// --- MCP tool ---
+
+pub fn outline_tools() -> Vec<Value> {
+    vec![json!({
+        "name": "extract_outline",
+        "description": "Read the board as notes: the text of text shapes, stickies and shape labels in reading order (left to right, top to bottom, finishing one cluster before the next), nested under the frames (shapes that enclose others) and groups that hold it. Returns the tree and the same outline as indented Markdown. Use it instead of get_canvas when you need the content rather than the geometry.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "tabId": { "type": "string", "description": "Tab to read (default: the tab agents are working on)" }
+            }
+        }
+    })]
+}
+
+pub async fn call_outline_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "extract_outline" => Some(extract_outline(state, arguments).await),
+        _ => None,
+    }
+}
+
+async fn extract_outline(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let tab_id = arguments.get("tabId").and_then(Value::as_str);
+    let canvas = resources::canvas(state, tab_id).await?;
+    let list = |key: &str| {
+        canvas
+            .get(key)
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default()
+    };
+    Ok(outline(&list("shapes"), &list("groups")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(id: &str, kind: &str, x: f64, y: f64, w: f64, h: f64, text: &str) -> Value {
+        let mut s = json!({ "id": id, "type": kind, "x": x, "y": y, "width": w, "height": h });
+        if !text.is_empty() {
+            s["text"] = json!(text);
+        }
+        s
+    }
+
+    fn note(id: &str, x: f64, y: f64) -> Value {
+        shape(id, "sticky", x, y, 100.0, 60.0, id)
+    }
+
+    fn texts(nodes: &Value) -> Vec<String> {
+        let mut out = Vec::new();
+        for node in nodes.as_array().unwrap() {
+            if let Some(t) = node.get("text").and_then(Value::as_str) {
+                out.push(t.to_string());
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn grids_read_row_by_row() {
+        let shapes = vec![
+            note("b1", 150.0, 0.0),
+            note("a2", 0.0, 100.0),
+            note("a1", 0.0, 0.0),
+            note("b2", 150.0, 100.0),
+        ];
+        let result = outline(&shapes, &[]);
+        assert_eq!(texts(&result["outline"]), ["a1", "b1", "a2", "b2"]);
+    }
+
+    #[test]
+    fn distant_clusters_are_read_one_at_a_time() {
+        // Two 2x1 columns far apart: each column is finished first.
+        let shapes = vec![
+            note("left-1", 0.0, 0.0),
+            note("right-1", 800.0, 0.0),
+            note("left-2", 0.0, 80.0),
+            note("right-2", 800.0, 80.0),
+        ];
+        let result = outline(&shapes, &[]);
+        assert_eq!(
+            texts(&result["outline"]),
+            ["left-1", "left-2", "right-1", "right-2"]
+        );
+    }
+
+    #[test]
+    fn frames_and_groups_nest_their_contents() {
+        let shapes = vec![
+            shape("frame", "rectangle", 0.0, 0.0, 400.0, 300.0, "Ideas"),
+            note("inside", 20.0, 40.0),
+            note("outside", 600.0, 40.0),
+            shape("empty", "rectangle", 600.0, 200.0, 50.0, 50.0, ""),
+            json!({ "id": "g1", "type": "text", "x": 20.0, "y": 700.0, "width": 80.0, "height": 20.0, "text": "grouped", "groupId": "grp" }),
+            json!({ "id": "g2", "type": "text", "x": 120.0, "y": 700.0, "width": 80.0, "height": 20.0, "text": "too\nmany lines" }),
+        ];
+        let groups = vec![json!({ "id": "grp", "shapeIds": ["g1", "g2"], "parentGroupId": null })];
+        let result = outline(&shapes, &groups);
+        let nodes = result["outline"].as_array().unwrap();
+
+        assert_eq!(nodes.len(), 3, "{}", result["text"]);
+        assert_eq!(nodes[0]["kind"], "frame");
+        assert_eq!(texts(&nodes[0]["children"]), ["inside"]);
+        assert_eq!(nodes[1]["text"], "outside");
+        assert_eq!(nodes[2]["kind"], "group");
+        assert_eq!(texts(&nodes[2]["children"]), ["grouped", "too\nmany lines"]);
+        assert_eq!(
+            result["text"],
+            "- Ideas\n  - inside\n- outside\n- [group]\n  - grouped\n  - too many lines\n"
+        );
+    }
+
+    #[test]
+    fn frames_do_not_reach_into_other_groups() {
+        let shapes = vec![
+            shape("frame", "rectangle", 0.0, 0.0, 400.0, 300.0, ""),
+            json!({ "id": "member", "type": "text", "x": 20.0, "y": 20.0, "width": 80.0, "height": 20.0, "text": "member" }),
+        ];
+        let groups = vec![json!({ "id": "grp", "shapeIds": ["member"] })];
+        let result = outline(&shapes, &groups);
+        let nodes = result["outline"].as_array().unwrap();
+        // The untitled frame holds the group, not the member directly.
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0]["children"][0]["kind"], "group");
+        assert_eq!(result["text"], "- [rectangle]\n  - [group]\n    - member\n");
+    }
+}
@@ -0,0 +1,826 @@
+//! Splitting a giant board into linked tabs (`partition_canvas`).
+//!
+//! A clustering hint picks the partitions: top-level frames (closed shapes
+//! enclosing others, as in `outline.rs`), top-level groups, or clusters of
+//! shapes joined by connectors. Groups always move whole. Each partition
+//! becomes a new tab, and the source tab keeps everything else plus a
+//! reference card linking to each new tab. A connector whose ends land on
+//! different tabs is cut in two: each half runs from its shape to a link card
+//! pointing at the shape on the other tab. Cards carry a `link` (`tabId`,
+//! plus `shapeId` for cut connectors) that agents can follow.
+//!
+//! The plan is computed here and handed to the webview in a single
+//! `apply_partition` bridge call, so the board is never left half split.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::geometry::{self, Bounds};
+use crate::model::{TabList, Viewport};
+use crate::outline::{self, FRAME_TYPES};
+use crate::resources;
+
+const CARD_WIDTH: f64 = 180.0;
+const CARD_HEIGHT: f64 = 60.0;
+/// Space between a shape and the link cards for its cut connectors, and
+/// between cards stacked beside the same shape.
+const CARD_GAP: f64 = 60.0;
+const CARD_SPACING: f64 = 20.0;
+/// Room left around a partition when its tab opens.
+const VIEW_MARGIN: f64 = 40.0;
+const MAX_TITLE_LEN: usize = 40;
+const DEFAULT_MIN_SHAPES: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Hint {
+    Frames,
+    Groups,
+    Connectivity,
+}
+
+impl Hint {
+    fn describe(self) -> &'static str {
+        match self {
+            Hint::Frames => "frames",
+            Hint::Groups => "groups",
+            Hint::Connectivity => "connected clusters",
+        }
+    }
+}
+
+/// A tab to create, holding one partition.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewTab {
+    pub id: String,
+    pub title: String,
+    pub shapes: Vec<Value>,
+    pub groups: Vec<Value>,
+    pub viewport: Viewport,
+}
+
+/// Everything `apply_partition` changes. On the source tab `remove` is
+/// applied before `add`, so a cut connector that stays there appears in
+/// both.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Plan {
+    pub tabs: Vec<NewTab>,
+    pub remove: Vec<String>,
+    pub add: Vec<Value>,
+    pub remove_groups: Vec<String>,
+    /// Connectors cut in two at a tab boundary.
+    pub cut_connectors: usize,
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str)
+}
+
+fn bound_shape<'a>(shape: &'a Value, binding: &str) -> Option<&'a str> {
+    shape
+        .get(binding)
+        .and_then(|b| b.get("shapeId"))
+        .and_then(Value::as_str)
+}
+
+/// Lines and arrows bound to a shape. Unless grouped, these follow the
+/// shapes they connect rather than moving on their own.
+fn is_connector(shape: &Value) -> bool {
+    matches!(str_field(shape, "type"), Some("line" | "arrow"))
+        && (bound_shape(shape, "bindStart").is_some() || bound_shape(shape, "bindEnd").is_some())
+}
+
+fn bounds_of(shape: &Value) -> Bounds {
+    geometry::shape_bounds(shape).unwrap_or(Bounds {
+        min_x: 0.0,
+        min_y: 0.0,
+        max_x: 0.0,
+        max_y: 0.0,
+    })
+}
+
+fn center(b: &Bounds) -> (f64, f64) {
+    ((b.min_x + b.max_x) / 2.0, (b.min_y + b.max_y) / 2.0)
+}
+
+fn new_id(prefix: &str) -> String {
+    format!("{}_{}", prefix, uuid::Uuid::new_v4().simple())
+}
+
+// --- Units ---
+
+/// Shapes that move together: a top-level group, or a single shape.
+struct Unit {
+    shapes: Vec<usize>,
+    bounds: Option<Bounds>,
+    grouped: bool,
+}
+
+struct Units {
+    list: Vec<Unit>,
+    /// Each shape's unit; `None` for ungrouped connectors.
+    of_shape: Vec<Option<usize>>,
+    /// The unit each group belongs to, by group id.
+    of_group: HashMap<String, usize>,
+}
+
+fn units(shapes: &[Value], groups: &[Value]) -> Units {
+    let parent: HashMap<&str, Option<&str>> = groups
+        .iter()
+        .filter_map(|g| Some((str_field(g, "id")?, str_field(g, "parentGroupId"))))
+        .collect();
+    let root = |group: &str| -> String {
+        let mut root = group;
+        for _ in 0..parent.len() {
+            match parent.get(root).copied().flatten() {
+                Some(p) if parent.contains_key(p) => root = p,
+                _ => break,
+            }
+        }
+        root.to_string()
+    };
+    let mut member_of: HashMap<&str, &str> = HashMap::new();
+    for group in groups {
+        let Some(id) = str_field(group, "id") else {
+            continue;
+        };
+        let members = group.get("shapeIds").and_then(Value::as_array);
+        for member in members.into_iter().flatten().filter_map(Value::as_str) {
+            member_of.insert(member, id);
+        }
+    }
+
+    let mut units = Units {
+        list: Vec::new(),
+        of_shape: Vec::with_capacity(shapes.len()),
+        of_group: HashMap::new(),
+    };
+    for (i, shape) in shapes.iter().enumerate() {
+        let group = str_field(shape, "id")
+            .and_then(|id| member_of.get(id).copied())
+            .or_else(|| str_field(shape, "groupId"))
+            .map(root);
+        if group.is_none() && is_connector(shape) {
+            units.of_shape.push(None);
+            continue;
+        }
+        let existing = group.as_ref().and_then(|g| units.of_group.get(g).copied());
+        let index = existing.unwrap_or_else(|| {
+            units.list.push(Unit {
+                shapes: Vec::new(),
+                bounds: None,
+                grouped: group.is_some(),
+            });
+            units.list.len() - 1
+        });
+        if let Some(group) = group {
+            units.of_group.insert(group, index);
+        }
+        let unit = &mut units.list[index];
+        unit.shapes.push(i);
+        if let Some(b) = geometry::shape_bounds(shape) {
+            unit.bounds = Some(unit.bounds.map_or(b, |u| u.union(&b)));
+        }
+        units.of_shape.push(Some(index));
+    }
+    // Nested groups move with their outermost group.
+    for id in parent.keys() {
+        if let Some(&unit) = units.of_group.get(&root(id)) {
+            units.of_group.insert(id.to_string(), unit);
+        }
+    }
+    units
+}
+
+// --- Clustering ---
+
+/// A partition's units, and the frame that titles it.
+struct Cluster {
+    frame: Option<usize>,
+    units: Vec<usize>,
+}
+
+fn area(b: &Bounds) -> f64 {
+    b.width() * b.height()
+}
+
+/// Each top-level frame with every unit inside it.
+fn by_frames(shapes: &[Value], units: &[Unit]) -> Vec<Cluster> {
+    let frames: Vec<usize> = (0..units.len())
+        .filter(|&u| {
+            let unit = &units[u];
+            !unit.grouped
+                && unit.bounds.is_some()
+                && str_field(&shapes[unit.shapes[0]], "type")
+                    .is_some_and(|t| FRAME_TYPES.contains(&t))
+        })
+        .collect();
+    let encloses = |f: usize, u: usize| {
+        f != u
+            && match (units[f].bounds, units[u].bounds) {
+                (Some(outer), Some(inner)) => outline::encloses(&outer, &inner),
+                _ => false,
+            }
+    };
+    let top: Vec<usize> = frames
+        .iter()
+        .copied()
+        .filter(|&f| !frames.iter().any(|&g| encloses(g, f)))
+        .filter(|&f| (0..units.len()).any(|u| encloses(f, u)))
+        .collect();
+
+    let mut clusters: Vec<Cluster> = top
+        .iter()
+        .map(|&f| Cluster {
+            frame: Some(f),
+            units: vec![f],
+        })
+        .collect();
+    for u in (0..units.len()).filter(|u| !top.contains(u)) {
+        // Overlapping frames: the smallest one that holds it.
+        let smallest = top
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| encloses(**f, u))
+            .min_by(|(_, a), (_, b)| {
+                let size = |f: usize| units[f].bounds.as_ref().map_or(0.0, area);
+                size(**a).total_cmp(&size(**b))
+            });
+        if let Some((k, _)) = smallest {
+            clusters[k].units.push(u);
+        }
+    }
+    clusters
+}
+
+/// Each top-level group.
+fn by_groups(units: &[Unit]) -> Vec<Cluster> {
+    (0..units.len())
+        .filter(|&u| units[u].grouped)
+        .map(|u| Cluster {
+            frame: None,
+            units: vec![u],
+        })
+        .collect()
+}
+
+/// Units joined by connectors, left out when nothing connects them.
+fn by_connectivity(shapes: &[Value], units: &Units, index: &HashMap<&str, usize>) -> Vec<Cluster> {
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut parent: Vec<usize> = (0..units.list.len()).collect();
+    for (i, shape) in shapes.iter().enumerate() {
+        let ends = ["bindStart", "bindEnd"]
+            .iter()
+            .filter_map(|binding| index.get(bound_shape(shape, binding)?))
+            .filter_map(|&s| units.of_shape[s]);
+        let joined: Vec<usize> = units.of_shape[i].into_iter().chain(ends).collect();
+        for pair in joined.windows(2) {
+            let (a, b) = (find(&mut parent, pair[0]), find(&mut parent, pair[1]));
+            parent[a] = b;
+        }
+    }
+
+    let mut clusters: Vec<Cluster> = Vec::new();
+    let mut cluster_of: HashMap<usize, usize> = HashMap::new();
+    for u in 0..units.list.len() {
+        let root = find(&mut parent, u);
+        let k = *cluster_of.entry(root).or_insert_with(|| {
+            clusters.push(Cluster {
+                frame: None,
+                units: Vec::new(),
+            });
+            clusters.len() - 1
+        });
+        clusters[k].units.push(u);
+    }
+    clusters.retain(|c| c.units.len() > 1);
+    clusters
+}
+
+/// The frame's label, else the first text in reading order, else a number.
+fn title(shapes: &[Value], members: &[usize], frame: Option<&Value>, n: usize) -> String {
+    let text = |shape: &Value| {
+        str_field(shape, "text")
+            .and_then(|t| t.lines().map(str::trim).find(|l| !l.is_empty()))
+            .map(str::to_string)
+    };
+    let first = || {
+        let labelled: Vec<(usize, Bounds)> = members
+            .iter()
+            .filter(|&&i| text(&shapes[i]).is_some())
+            .filter_map(|&i| Some((i, geometry::shape_bounds(&shapes[i])?)))
+            .collect();
+        let bounds: Vec<Bounds> = labelled.iter().map(|(_, b)| *b).collect();
+        let first = *outline::reading_order(&bounds).first()?;
+        text(&shapes[labelled[first].0])
+    };
+    let title = frame
+        .and_then(text)
+        .or_else(first)
+        .unwrap_or_else(|| format!("Part {}", n + 1));
+    if title.chars().count() > MAX_TITLE_LEN {
+        let cut: String = title.chars().take(MAX_TITLE_LEN - 1).collect();
+        format!("{}…", cut.trim_end())
+    } else {
+        title
+    }
+}
+
+// --- Cards ---
+
+fn card(cx: f64, cy: f64, text: &str, link: Value) -> Value {
+    json!({
+        "id": new_id("shape"),
+        "type": "rectangle",
+        "x": cx - CARD_WIDTH / 2.0,
+        "y": cy - CARD_HEIGHT / 2.0,
+        "width": CARD_WIDTH,
+        "height": CARD_HEIGHT,
+        "strokeColor": "#1971c2",
+        "strokeWidth": 2,
+        "strokeStyle": "dashed",
+        "fillColor": "#e7f5ff",
+        "fillStyle": "solid",
+        "opacity": 1,
+        "roughness": 0,
+        "rotation": 0,
+        "text": format!("↗ {}", text),
+        "link": link,
+    })
+}
+
+/// A card beside `from` on the side facing `toward`; `slot` stacks several
+/// cards beside the same shape.
+fn link_card(from: &Value, toward: &Value, slot: usize, text: &str, link: Value) -> Value {
+    let b = bounds_of(from);
+    let (fx, fy) = center(&b);
+    let (tx, ty) = center(&bounds_of(toward));
+    let (dx, dy) = (tx - fx, ty - fy);
+    let offset = slot as f64;
+    let (cx, cy) = if dx.abs() >= dy.abs() {
+        let x = if dx >= 0.0 {
+            b.max_x + CARD_GAP + CARD_WIDTH / 2.0
+        } else {
+            b.min_x - CARD_GAP - CARD_WIDTH / 2.0
+        };
+        (x, fy + offset * (CARD_HEIGHT + CARD_SPACING))
+    } else {
+        let y = if dy >= 0.0 {
+            b.max_y + CARD_GAP + CARD_HEIGHT / 2.0
+        } else {
+            b.min_y - CARD_GAP - CARD_HEIGHT / 2.0
+        };
+        (fx + offset * (CARD_WIDTH + CARD_SPACING), y)
+    };
+    card(cx, cy, text, link)
+}
+
+/// Bind one end of `connector` to the centre of `card`.
+fn rebind(connector: &mut Value, binding: &str, (x, y): (&str, &str), card: &Value) {
+    let (cx, cy) = center(&bounds_of(card));
+    connector[binding] = json!({ "shapeId": card["id"], "point": "center" });
+    connector[x] = json!(cx);
+    connector[y] = json!(cy);
+    // Bends were routed for the old end.
+    if let Some(obj) = connector.as_object_mut() {
+        obj.remove("controlPoints");
+    }
+}
+
+// --- Plan ---
+
+/// Where a shape ends up: a partition, or the source tab (`None`).
+type Loc = Option<usize>;
+
+/// Split `shapes` (in z-order) on tab `source_id` by `hint`. Partitions with
+/// fewer than `min_shapes` shapes stay where they are.
+pub fn plan(
+    shapes: &[Value],
+    groups: &[Value],
+    (source_id, source_title): (&str, &str),
+    hint: Hint,
+    min_shapes: usize,
+) -> Result<Plan, String> {
+    let index: HashMap<&str, usize> = shapes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, s)| Some((str_field(s, "id")?, i)))
+        .collect();
+    let units = units(shapes, groups);
+    let mut clusters = match hint {
+        Hint::Frames => by_frames(shapes, &units.list),
+        Hint::Groups => by_groups(&units.list),
+        Hint::Connectivity => by_connectivity(shapes, &units, &index),
+    };
+    let members = |c: &Cluster| -> Vec<usize> {
+        let mut members: Vec<usize> = c
+            .units
+            .iter()
+            .flat_map(|&u| units.list[u].shapes.iter().copied())
+            .collect();
+        members.sort_unstable();
+        members
+    };
+    clusters.retain(|c| members(c).len() >= min_shapes.max(1));
+    if clusters.is_empty() {
+        return Err(format!(
+            "Nothing to partition: no {} with at least {} shapes",
+            hint.describe(),
+            min_shapes
+        ));
+    }
+
+    let mut unit_loc: Vec<Loc> = vec![None; units.list.len()];
+    let mut tabs = Vec::with_capacity(clusters.len());
+    for (k, cluster) in clusters.iter().enumerate() {
+        for &u in &cluster.units {
+            unit_loc[u] = Some(k);
+        }
+        let frame = cluster.frame.map(|f| &shapes[units.list[f].shapes[0]]);
+        tabs.push(NewTab {
+            id: new_id("tab"),
+            title: title(shapes, &members(cluster), frame, k),
+            shapes: Vec::new(),
+            groups: Vec::new(),
+            viewport: Viewport::default(),
+        });
+    }
+    let shape_loc = |i: usize| units.of_shape[i].and_then(|u| unit_loc[u]);
+    let tab_of = |loc: Loc, tabs: &[NewTab]| -> (String, String) {
+        match loc {
+            Some(k) => (tabs[k].id.clone(), tabs[k].title.clone()),
+            None => (source_id.to_string(), source_title.to_string()),
+        }
+    };
+
+    let mut plan = Plan::default();
+    let mut cards_beside: HashMap<usize, usize> = HashMap::new();
+    for (i, shape) in shapes.iter().enumerate() {
+        let id = str_field(shape, "id").unwrap_or_default().to_string();
+        if units.of_shape[i].is_some() {
+            if let Some(k) = shape_loc(i) {
+                tabs[k].shapes.push(shape.clone());
+                plan.remove.push(id);
+            }
+            continue;
+        }
+
+        // A connector follows the shapes it is bound to.
+        let end = |binding: &str| index.get(bound_shape(shape, binding)?).copied();
+        let (from, to) = match (end("bindStart"), end("bindEnd")) {
+            (Some(s), Some(e)) if shape_loc(s) != shape_loc(e) => (s, e),
+            (s, e) => {
+                if let Some(k) = s.or(e).and_then(shape_loc) {
+                    tabs[k].shapes.push(shape.clone());
+                    plan.remove.push(id);
+                }
+                continue;
+            }
+        };
+        let (a, b) = (shape_loc(from), shape_loc(to));
+        let (a_tab, a_title) = tab_of(a, &tabs);
+        let (b_tab, b_title) = tab_of(b, &tabs);
+        let mut slot = |s: usize| {
+            let n = cards_beside.entry(s).or_insert(0);
+            *n += 1;
+            *n - 1
+        };
+
+        let start_card = link_card(
+            &shapes[from],
+            &shapes[to],
+            slot(from),
+            &b_title,
+            json!({ "tabId": b_tab, "shapeId": shapes[to]["id"] }),
+        );
+        let mut start_half = shape.clone();
+        rebind(&mut start_half, "bindEnd", ("x2", "y2"), &start_card);
+
+        let end_card = link_card(
+            &shapes[to],
+            &shapes[from],
+            slot(to),
+            &a_title,
+            json!({ "tabId": a_tab, "shapeId": shapes[from]["id"] }),
+        );
+        let mut end_half = shape.clone();
+        end_half["id"] = json!(new_id("shape"));
+        rebind(&mut end_half, "bindStart", ("x", "y"), &end_card);
+
+        plan.remove.push(id);
+        plan.cut_connectors += 1;
+        for (loc, card, half) in [(a, start_card, start_half), (b, end_card, end_half)] {
+            let target = match loc {
+                Some(k) => &mut tabs[k].shapes,
+                None => &mut plan.add,
+            };
+            target.extend([card, half]);
+        }
+    }
+
+    for group in groups {
+        let Some(id) = str_field(group, "id") else {
+            continue;
+        };
+        if let Some(k) = units.of_group.get(id).and_then(|&u| unit_loc[u]) {
+            tabs[k].groups.push(group.clone());
+            plan.remove_groups.push(id.to_string());
+        }
+    }
+
+    for (k, cluster) in clusters.iter().enumerate() {
+        let moved: Vec<Value> = members(cluster)
+            .iter()
+            .map(|&i| shapes[i].clone())
+            .collect();
+        if let Some(b) = geometry::combined_bounds(&moved) {
+            let (cx, cy) = center(&b);
+            let link = json!({ "tabId": tabs[k].id });
+            plan.add.push(card(cx, cy, &tabs[k].title, link));
+        }
+        if let Some(b) = geometry::combined_bounds(&tabs[k].shapes) {
+            tabs[k].viewport = Viewport {
+                x: b.min_x - VIEW_MARGIN,
+                y: b.min_y - VIEW_MARGIN,
+                zoom: 1.0,
+            };
+        }
+    }
+    plan.tabs = tabs;
+    Ok(plan)
+}
+
+// --- MCP tool ---
+
+pub fn partition_tools() -> Vec<Value> {
+    vec![json!({
+        "name": "partition_canvas",
+        "description": "Split a large board into linked tabs. Depending on `by`, each top-level frame (a shape that encloses others), each top-level group, or each cluster of shapes joined by connectors becomes a new tab; the board keeps the rest plus a reference card linking to each new tab. A connector between shapes that end up on different tabs is cut in two, each half ending at a link card for the shape on the other tab. Cards carry a `link` with the target tabId (and shapeId). Applied as one change.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "by": {
+                    "type": "string",
+                    "description": "How to cluster the board",
+                    "enum": ["frames", "groups", "connectivity"]
+                },
+                "tabId": { "type": "string", "description": "Tab to split (default: the tab agents are working on)" },
+                "minShapes": { "type": "integer", "minimum": 1, "description": "Smaller clusters stay on the board (default: 2)" },
+                "dryRun": { "type": "boolean", "description": "Report the partitions without changing anything" }
+            },
+            "required": ["by"]
+        }
+    })]
+}
+
+pub async fn call_partition_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "partition_canvas" => Some(partition_canvas(state, arguments).await),
+        _ => None,
+    }
+}
+
+/// The id and title of tab `tab_id`, or of the tab agents are working on.
+async fn source_tab(
+    state: &SharedApiState,
+    tab_id: Option<&str>,
+) -> Result<(String, String), String> {
+    let list: TabList =
+        serde_json::from_value(bridge_tool_call(state, "list_tabs", json!({})).await?)
+            .map_err(|e| format!("Unexpected list_tabs result: {}", e))?;
+    let id = tab_id
+        .or(list.mcp_active_tab_id.as_deref())
+        .or(list.active_tab_id.as_deref())
+        .ok_or("No tab to partition")?;
+    let tab = list
+        .tabs
+        .iter()
+        .find(|t| t.id == id)
+        .ok_or_else(|| format!("Tab not found: {}", id))?;
+    Ok((tab.id.clone(), tab.title.clone()))
+}
+
+async fn partition_canvas(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let hint: Hint = serde_json::from_value(arguments.get("by").cloned().unwrap_or(Value::Null))
+        .map_err(|_| "by must be one of: frames, groups, connectivity".to_string())?;
+    let min_shapes = arguments
+        .get("minShapes")
+        .and_then(Value::as_u64)
+        .map_or(DEFAULT_MIN_SHAPES, |n| n as usize);
+    let dry_run = arguments
+        .get("dryRun")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+
+    let (tab_id, title) = source_tab(state, arguments.get("tabId").and_then(Value::as_str)).await?;
+    let canvas = resources::canvas(state, Some(&tab_id)).await?;
+    let list = |key: &str| {
+        canvas
+            .get(key)
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let plan = plan(
+        &list("shapes"),
+        &list("groups"),
+        (&tab_id, &title),
+        hint,
+        min_shapes,
+    )?;
+
+    if !dry_run {
+        let result = bridge_tool_call(
+            state,
+            "apply_partition",
+            json!({ "tabId": tab_id, "plan": plan }),
+        )
+        .await?;
+        if let Some(err) = result.get("error").and_then(Value::as_str) {
+            return Err(err.to_string());
+        }
+    }
+
+    let tabs: Vec<Value> = plan
+        .tabs
+        .iter()
+        .map(|tab| {
+            let mut summary = json!({ "title": tab.title, "shapeCount": tab.shapes.len() });
+            if !dry_run {
+                summary["id"] = json!(tab.id);
+            }
+            summary
+        })
+        .collect();
+    Ok(json!({
+        "dryRun": dry_run,
+        "sourceTabId": tab_id,
+        "tabs": tabs,
+        "cutConnectors": plan.cut_connectors,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: (&str, &str) = ("tab_main", "Main");
+
+    fn shape(id: &str, kind: &str, x: f64, y: f64, w: f64, h: f64, text: &str) -> Value {
+        let mut s = json!({ "id": id, "type": kind, "x": x, "y": y, "width": w, "height": h });
+        if !text.is_empty() {
+            s["text"] = json!(text);
+        }
+        s
+    }
+
+    fn arrow(id: &str, from: &str, to: &str) -> Value {
+        json!({
+            "id": id, "type": "arrow", "x": 0.0, "y": 0.0, "x2": 10.0, "y2": 10.0,
+            "bindStart": { "shapeId": from, "point": "center" },
+            "bindEnd": { "shapeId": to, "point": "center" },
+        })
+    }
+
+    fn ids(shapes: &[Value]) -> Vec<&str> {
+        shapes.iter().filter_map(|s| s["id"].as_str()).collect()
+    }
+
+    #[test]
+    fn frames_become_tabs_and_crossing_connectors_are_cut() {
+        let shapes = vec![
+            shape("left", "rectangle", 0.0, 0.0, 400.0, 300.0, "Backend"),
+            shape("api", "sticky", 20.0, 40.0, 100.0, 60.0, "API"),
+            shape("right", "rectangle", 600.0, 0.0, 400.0, 300.0, ""),
+            shape("ui", "sticky", 620.0, 40.0, 100.0, 60.0, "Web UI"),
+            shape("loose", "sticky", 0.0, 600.0, 100.0, 60.0, "Loose"),
+            arrow("calls", "ui", "api"),
+            arrow("inside", "left", "api"),
+        ];
+        let plan = plan(&shapes, &[], SOURCE, Hint::Frames, 2).unwrap();
+
+        assert_eq!(plan.tabs.len(), 2);
+        assert_eq!(plan.tabs[0].title, "Backend");
+        assert_eq!(plan.tabs[1].title, "Web UI");
+        assert_eq!(plan.cut_connectors, 1);
+        assert_eq!(
+            plan.remove,
+            ["left", "api", "right", "ui", "calls", "inside"]
+        );
+
+        // Each side of the cut connector ends at a card linking to the other tab.
+        let backend = &plan.tabs[0].shapes;
+        assert_eq!(ids(backend)[..2], ["left", "api"]);
+        let card = backend
+            .iter()
+            .find(|s| s["link"]["shapeId"] == "ui")
+            .unwrap();
+        assert_eq!(card["link"]["tabId"], plan.tabs[1].id.as_str());
+        let half = backend
+            .iter()
+            .find(|s| s["bindStart"]["shapeId"] == card["id"])
+            .unwrap();
+        assert_eq!(half["bindEnd"]["shapeId"], "api");
+        assert!(ids(backend).contains(&"inside"));
+
+        let web = &plan.tabs[1].shapes;
+        let half = web.iter().find(|s| s["id"] == "calls").unwrap();
+        let card = web
+            .iter()
+            .find(|s| s["id"] == half["bindEnd"]["shapeId"])
+            .unwrap();
+        assert_eq!(
+            card["link"],
+            json!({ "tabId": plan.tabs[0].id, "shapeId": "api" })
+        );
+        assert!(card["x"].as_f64().unwrap() < 620.0);
+
+        // The board keeps a reference card per tab.
+        assert_eq!(plan.add.len(), 2);
+        assert_eq!(plan.add[0]["link"], json!({ "tabId": plan.tabs[0].id }));
+        assert_eq!(plan.add[0]["text"], "↗ Backend");
+    }
+
+    #[test]
+    fn groups_move_whole_with_their_definitions() {
+        let shapes = vec![
+            json!({ "id": "a", "type": "text", "x": 0.0, "y": 0.0, "width": 80.0, "height": 20.0, "text": "a", "groupId": "inner" }),
+            json!({ "id": "b", "type": "text", "x": 100.0, "y": 0.0, "width": 80.0, "height": 20.0, "text": "b", "groupId": "outer" }),
+            shape("c", "sticky", 0.0, 400.0, 100.0, 60.0, "c"),
+            arrow("link", "a", "c"),
+        ];
+        let groups = vec![
+            json!({ "id": "outer", "shapeIds": ["b"] }),
+            json!({ "id": "inner", "shapeIds": ["a"], "parentGroupId": "outer" }),
+        ];
+        let plan = plan(&shapes, &groups, SOURCE, Hint::Groups, 2).unwrap();
+
+        assert_eq!(plan.tabs.len(), 1);
+        assert_eq!(plan.tabs[0].groups, groups);
+        assert_eq!(plan.remove_groups, ["outer", "inner"]);
+        assert_eq!(plan.cut_connectors, 1);
+        // The half left on the board runs from `c`'s card, not from `a`.
+        let half = plan.add.iter().find(|s| s["type"] == "arrow").unwrap();
+        assert_eq!(half["bindEnd"]["shapeId"], "c");
+    }
+
+    #[test]
+    fn connected_clusters_split_and_loose_shapes_stay() {
+        let shapes = vec![
+            shape("a", "rectangle", 0.0, 0.0, 50.0, 50.0, ""),
+            shape("b", "rectangle", 100.0, 0.0, 50.0, 50.0, ""),
+            shape("c", "rectangle", 0.0, 500.0, 50.0, 50.0, ""),
+            shape("d", "rectangle", 100.0, 500.0, 50.0, 50.0, ""),
+            shape("alone", "rectangle", 900.0, 900.0, 50.0, 50.0, ""),
+            arrow("ab", "a", "b"),
+            arrow("cd", "c", "d"),
+        ];
+        let plan = plan(&shapes, &[], SOURCE, Hint::Connectivity, 2).unwrap();
+
+        assert_eq!(plan.tabs.len(), 2);
+        assert_eq!(ids(&plan.tabs[0].shapes), ["a", "b", "ab"]);
+        assert_eq!(
+            plan.tabs[0].viewport,
+            Viewport {
+                x: -40.0,
+                y: -40.0,
+                zoom: 1.0
+            }
+        );
+        assert_eq!(ids(&plan.tabs[1].shapes), ["c", "d", "cd"]);
+        assert_eq!(plan.tabs[1].title, "Part 2");
+        assert!(!plan.remove.contains(&"alone".to_string()));
+        assert_eq!(plan.cut_connectors, 0);
+    }
+
+    #[test]
+    fn small_clusters_are_left_alone() {
+        let shapes = vec![
+            shape("frame", "rectangle", 0.0, 0.0, 400.0, 300.0, ""),
+            shape("note", "sticky", 20.0, 40.0, 100.0, 60.0, "note"),
+        ];
+        assert!(plan(&shapes, &[], SOURCE, Hint::Frames, 3)
+            .unwrap_err()
+            .contains("frames"));
+        assert!(plan(&shapes, &[], SOURCE, Hint::Groups, 1).is_err());
+        assert_eq!(
+            plan(&shapes, &[], SOURCE, Hint::Frames, 2).unwrap().tabs[0].title,
+            "note"
+        );
+    }
+}
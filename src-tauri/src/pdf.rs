@@ -0,0 +1,877 @@
+//! Vector PDF export.
+//!
+//! Writes a single-page PDF sized to the content. When the document has a
+//! real-world scale the page is printed at true size (or at a drawing ratio
+//! such as 1:50); otherwise canvas pixels map to CSS pixels at 96 dpi.
+//! Shapes are drawn clean rather than hand-drawn, which suits plans that are
+//! meant to be measured. `export_pdf_tabs` writes every open tab into one
+//! document instead, a page per tab (each sized and scaled on its own) with
+//! the tab's title as its page label.
+
+use crate::api::SharedApiState;
+use crate::export::{content_bounds, polygon_points};
+use crate::export_hooks;
+use crate::geometry;
+use crate::text_metrics;
+use crate::units::{self, DocumentScale};
+use crate::watermark::{self, Watermark};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt::Write as _;
+
+const POINTS_PER_MM: f64 = 72.0 / 25.4;
+/// Canvas pixels are CSS pixels: 96 per inch.
+const POINTS_PER_PIXEL: f64 = 0.75;
+/// PDF viewers refuse pages beyond 200 inches.
+const MAX_PAGE_POINTS: f64 = 14_400.0;
+/// Bezier handle length for a quarter ellipse.
+const KAPPA: f64 = 0.552_284_75;
+/// A watermark image is stored once, right after the font, followed by its
+/// alpha mask.
+const WATERMARK_IMAGE: usize = 4;
+/// Watermark images are stored no larger than this either way.
+const MAX_IMAGE_SIDE: u32 = 512;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PdfExportOptions {
+    /// Drawing ratio: 50 prints at 1:50. Ignored without a document scale.
+    pub drawing_ratio: f64,
+    pub title: Option<String>,
+    /// Extra margin around the content, in canvas pixels.
+    pub padding: f64,
+    /// Banner or stamp drawn over the page (see `watermark.rs`). When
+    /// omitted the `exportWatermark` setting applies.
+    pub watermark: Option<Watermark>,
+}
+
+/// One tab of a multi-page export.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfPage {
+    pub title: String,
+    pub shapes: Vec<Value>,
+    #[serde(default)]
+    pub scale: Option<DocumentScale>,
+}
+
+/// A page's drawing, ready to be written into a document.
+struct PageContent {
+    content: String,
+    resources: String,
+    width: f64,
+    height: f64,
+}
+
+impl Default for PdfExportOptions {
+    fn default() -> Self {
+        Self {
+            drawing_ratio: 1.0,
+            title: None,
+            padding: 0.0,
+            watermark: None,
+        }
+    }
+}
+
+/// PDF points per canvas pixel.
+pub fn points_per_pixel(scale: Option<&DocumentScale>, drawing_ratio: f64) -> f64 {
+    match scale {
+        Some(s) => s.mm_per_pixel() * POINTS_PER_MM / drawing_ratio.max(f64::MIN_POSITIVE),
+        None => POINTS_PER_PIXEL,
+    }
+}
+
+// --- Tauri command ---
+
+#[tauri::command]
+pub async fn export_pdf(
+    path: String,
+    shapes: Vec<Value>,
+    options: Option<PdfExportOptions>,
+    preset: Option<String>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<(), String> {
+    let mut options = options.unwrap_or_default();
+    options.watermark = watermark::resolve(&state.app_handle, options.watermark.take());
+    let scale = units::current_scale(state.inner()).await;
+    let bytes = render_pdf(&shapes, scale.as_ref(), &options)?;
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    export_hooks::after_export(&state.app_handle, path, preset.as_deref());
+    Ok(())
+}
+
+/// Write `tabs` as one PDF, a page per tab. Each tab brings its own scale,
+/// since only the active one's is known here.
+#[tauri::command]
+pub async fn export_pdf_tabs(
+    path: String,
+    tabs: Vec<PdfPage>,
+    options: Option<PdfExportOptions>,
+    preset: Option<String>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<(), String> {
+    let mut options = options.unwrap_or_default();
+    options.watermark = watermark::resolve(&state.app_handle, options.watermark.take());
+    let bytes = render_pdf_pages(&tabs, &options)?;
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    export_hooks::after_export(&state.app_handle, path, preset.as_deref());
+    Ok(())
+}
+
+// --- Document ---
+
+pub fn render_pdf(
+    shapes: &[Value],
+    scale: Option<&DocumentScale>,
+    options: &PdfExportOptions,
+) -> Result<Vec<u8>, String> {
+    let page = render_page(shapes, scale, options)?;
+    let title = options.title.as_deref().unwrap_or("Napkin board");
+    Ok(assemble(&[page], None, title, &shared_objects(options)))
+}
+
+/// `pages` as one document, labelled with their titles.
+pub fn render_pdf_pages(pages: &[PdfPage], options: &PdfExportOptions) -> Result<Vec<u8>, String> {
+    if pages.is_empty() {
+        return Err("There are no tabs to export".to_string());
+    }
+    let rendered = pages
+        .iter()
+        .map(|page| {
+            render_page(&page.shapes, page.scale.as_ref(), options)
+                .map_err(|e| format!("Tab '{}': {}", page.title, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let labels: Vec<&str> = pages.iter().map(|page| page.title.as_str()).collect();
+    let title = options.title.as_deref().unwrap_or("Napkin boards");
+    Ok(assemble(
+        &rendered,
+        Some(&labels),
+        title,
+        &shared_objects(options),
+    ))
+}
+
+fn render_page(
+    shapes: &[Value],
+    scale: Option<&DocumentScale>,
+    options: &PdfExportOptions,
+) -> Result<PageContent, String> {
+    let shapes = text_metrics::auto_fitted(shapes);
+    let bounds = content_bounds(&shapes).expand(options.padding.max(0.0));
+    let k = points_per_pixel(scale, options.drawing_ratio);
+    let page_w = bounds.width() * k;
+    let page_h = bounds.height() * k;
+    if page_w > MAX_PAGE_POINTS || page_h > MAX_PAGE_POINTS {
+        return Err(format!(
+            "The page would be {:.0} x {:.0} mm, larger than PDF allows; use a larger drawing ratio",
+            page_w / POINTS_PER_MM,
+            page_h / POINTS_PER_MM
+        ));
+    }
+
+    // Flip to canvas coordinates (y down, origin at the content's top-left).
+    let mut content = format!(
+        "q\n{} 0 0 {} {} {} cm\n1 J 1 j\n",
+        num(k),
+        num(-k),
+        num(-bounds.min_x * k),
+        num(page_h + bounds.min_y * k)
+    );
+    for shape in &shapes {
+        draw_shape(&mut content, shape);
+    }
+    content.push_str("Q\n");
+
+    let mut resources = "/Font << /F1 3 0 R >>".to_string();
+    if let Some(watermark) = options.watermark.as_ref().filter(|w| w.is_visible()) {
+        draw_watermark(&mut content, watermark, page_w, page_h);
+        resources = format!(
+            "/Font << /F1 3 0 R /F2 << /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >> >> /ExtGState << /WM << /ca {o} /CA {o} >> >>",
+            o = num(watermark.opacity())
+        );
+        if watermark.decoded_image().is_some() {
+            let _ = write!(resources, " /XObject << /WMI {} 0 R >>", WATERMARK_IMAGE);
+        }
+    }
+
+    Ok(PageContent {
+        content,
+        resources,
+        width: page_w,
+        height: page_h,
+    })
+}
+
+/// Draw `watermark` over the whole page, in page space (y up).
+fn draw_watermark(out: &mut String, watermark: &Watermark, page_w: f64, page_h: f64) {
+    let layout = watermark.layout(0.0, 0.0, page_w, page_h);
+    out.push_str("q\n/WM gs\n");
+    if let Some((x, y, w, h)) = layout.band {
+        let (r, g, b) = rgb(&watermark.color).unwrap_or((0.0, 0.0, 0.0));
+        let _ = writeln!(
+            out,
+            "{} {} {} rg\n{} {} {} {} re f",
+            num(r),
+            num(g),
+            num(b),
+            num(x),
+            num(page_h - y - h),
+            num(w),
+            num(h)
+        );
+    }
+    // Layout angles are clockwise with y down; PDF turns counter-clockwise
+    // with y up, so the same number works once the page is flipped.
+    let theta = -layout.angle.to_radians();
+    let (sin, cos) = (theta.sin(), theta.cos());
+    if watermark.decoded_image().is_some() {
+        // Turn about the center, then stretch the unit square over the box
+        let (w, h) = (layout.text_width, Watermark::image_height(layout.font_size));
+        let _ = writeln!(
+            out,
+            "{} {} {} {} {} {} cm\n{} 0 0 {} {} {} cm\n/WMI Do\nQ",
+            num(cos),
+            num(sin),
+            num(-sin),
+            num(cos),
+            num(layout.center.0),
+            num(page_h - layout.center.1),
+            num(w),
+            num(h),
+            num(-w / 2.0),
+            num(-h / 2.0)
+        );
+        return;
+    }
+    let (r, g, b) = rgb(watermark.text_color()).unwrap_or((0.0, 0.0, 0.0));
+    // Start the baseline so the text's center lands on the layout center.
+    let (dx, dy) = (-layout.text_width / 2.0, -layout.font_size * 0.35);
+    let (cx, cy) = (layout.center.0, page_h - layout.center.1);
+    let _ = writeln!(
+        out,
+        "BT\n/F2 {} Tf\n{} {} {} rg\n{} {} {} {} {} {} Tm\n{} Tj\nET\nQ",
+        num(layout.font_size),
+        num(r),
+        num(g),
+        num(b),
+        num(cos),
+        num(sin),
+        num(-sin),
+        num(cos),
+        num(cx + dx * cos - dy * sin),
+        num(cy + dx * sin + dy * cos),
+        pdf_string(watermark.text())
+    );
+}
+
+/// The watermark image and its alpha mask, as raw 8-bit samples; nothing
+/// for a text watermark.
+fn shared_objects(options: &PdfExportOptions) -> Vec<String> {
+    let Some(image) = options
+        .watermark
+        .as_ref()
+        .filter(|w| w.is_visible())
+        .and_then(Watermark::decoded_image)
+    else {
+        return Vec::new();
+    };
+    let image = if image.width().max(image.height()) > MAX_IMAGE_SIDE {
+        image.thumbnail(MAX_IMAGE_SIDE, MAX_IMAGE_SIDE)
+    } else {
+        image
+    };
+    let image = image.to_rgba8();
+    let (width, height) = image.dimensions();
+    // One char per byte; `latin1` writes them back unchanged
+    let mut color = String::with_capacity(image.len() / 4 * 3);
+    let mut alpha = String::with_capacity(image.len() / 4);
+    for pixel in image.pixels() {
+        color.extend(pixel.0[..3].iter().map(|&b| b as char));
+        alpha.push(pixel.0[3] as char);
+    }
+    let sample = |space: &str, extra: &str, data: &str| {
+        format!(
+            "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /{} /BitsPerComponent 8{} /Length {} >>\nstream\n{}\nendstream",
+            width,
+            height,
+            space,
+            extra,
+            data.chars().count(),
+            data
+        )
+    };
+    vec![
+        sample(
+            "DeviceRGB",
+            &format!(" /SMask {} 0 R", WATERMARK_IMAGE + 1),
+            &color,
+        ),
+        sample("DeviceGray", "", &alpha),
+    ]
+}
+
+/// Objects 1-3 are the catalog, page tree and shared font, then any
+/// `shared` objects (the watermark image); each page then takes two (the
+/// page and its content stream) and the info dictionary comes last.
+fn assemble(
+    pages: &[PageContent],
+    labels: Option<&[&str]>,
+    title: &str,
+    shared: &[String],
+) -> Vec<u8> {
+    let first_page = 4 + shared.len();
+    let page_ref = |i: usize| format!("{} 0 R", first_page + i * 2);
+    let page_labels = labels
+        .map(|labels| {
+            let nums: Vec<String> = labels
+                .iter()
+                .enumerate()
+                .map(|(i, label)| format!("{} << /P {} >>", i, pdf_string(label)))
+                .collect();
+            format!(" /PageLabels << /Nums [{}] >>", nums.join(" "))
+        })
+        .unwrap_or_default();
+    let kids: Vec<String> = (0..pages.len()).map(page_ref).collect();
+
+    let mut objects = vec![
+        format!("<< /Type /Catalog /Pages 2 0 R{} >>", page_labels),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+    objects.extend_from_slice(shared);
+    for (i, page) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << {} >> /Contents {} 0 R >>",
+            num(page.width),
+            num(page.height),
+            page.resources,
+            first_page + 1 + i * 2
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            page.content.chars().count() + 1,
+            page.content
+        ));
+    }
+    objects.push(format!(
+        "<< /Title {} /Producer (Napkin) >>",
+        pdf_string(title)
+    ));
+
+    let mut out: Vec<u8> = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        out.extend_from_slice(&latin1(body));
+        out.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = out.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        objects.len(),
+        xref
+    );
+    out.extend_from_slice(trailer.as_bytes());
+    out
+}
+
+// --- Shapes ---
+
+fn draw_shape(out: &mut String, shape: &Value) {
+    if prop_num(shape, "opacity", 1.0) <= 0.0 {
+        return;
+    }
+    out.push_str("q\n");
+    let rotation = geometry::rotation(shape);
+    if rotation != 0.0 {
+        let (cx, cy) = geometry::rotation_center(shape);
+        let (sin, cos) = rotation.to_radians().sin_cos();
+        // Translate to the pivot, rotate, translate back.
+        let _ = writeln!(
+            out,
+            "{} {} {} {} {} {} cm",
+            num(cos),
+            num(sin),
+            num(-sin),
+            num(cos),
+            num(cx - cx * cos + cy * sin),
+            num(cy - cx * sin - cy * cos)
+        );
+    }
+
+    let x = prop_num(shape, "x", 0.0);
+    let y = prop_num(shape, "y", 0.0);
+    let w = prop_num(shape, "width", 0.0);
+    let h = prop_num(shape, "height", 0.0);
+    let shape_type = prop_str(shape, "type");
+    match shape_type {
+        "rectangle" | "cylinder" => {
+            let _ = writeln!(out, "{} {} {} {} re", num(x), num(y), num(w), num(h));
+            paint(out, shape, true);
+        }
+        "sticky" => {
+            let color = rgb(prop_str(shape, "stickyColor")).unwrap_or((1.0, 0.976, 0.769));
+            let _ = writeln!(
+                out,
+                "{} {} {} rg {} {} {} {} re f",
+                num(color.0),
+                num(color.1),
+                num(color.2),
+                num(x),
+                num(y),
+                num(w),
+                num(h)
+            );
+        }
+        "ellipse" | "cloud" => {
+            ellipse_path(out, x + w / 2.0, y + h / 2.0, w / 2.0, h / 2.0);
+            paint(out, shape, true);
+        }
+        "triangle" | "diamond" | "hexagon" | "star" => {
+            polyline(out, &polygon_points(shape));
+            out.push_str("h\n");
+            paint(out, shape, true);
+        }
+        "line" | "arrow" => {
+            let mut pts = vec![(x, y)];
+            pts.extend(prop_points(shape, "controlPoints"));
+            pts.push((prop_num(shape, "x2", x), prop_num(shape, "y2", y)));
+            polyline(out, &pts);
+            paint(out, shape, false);
+            if shape_type == "arrow"
+                && shape.get("arrowheadEnd").and_then(|v| v.as_bool()) != Some(false)
+            {
+                arrowhead(out, shape, pts[pts.len() - 2], pts[pts.len() - 1]);
+            }
+        }
+        "freedraw" => {
+            polyline(out, &prop_points(shape, "points"));
+            paint(out, shape, false);
+        }
+        _ => {}
+    }
+    label(out, shape);
+    out.push_str("Q\n");
+}
+
+fn polyline(out: &mut String, pts: &[(f64, f64)]) {
+    for (i, (px, py)) in pts.iter().enumerate() {
+        let op = if i == 0 { "m" } else { "l" };
+        let _ = writeln!(out, "{} {} {}", num(*px), num(*py), op);
+    }
+}
+
+fn ellipse_path(out: &mut String, cx: f64, cy: f64, rx: f64, ry: f64) {
+    let (ox, oy) = (rx * KAPPA, ry * KAPPA);
+    let _ = writeln!(out, "{} {} m", num(cx + rx), num(cy));
+    for (c1, c2, end) in [
+        ((cx + rx, cy + oy), (cx + ox, cy + ry), (cx, cy + ry)),
+        ((cx - ox, cy + ry), (cx - rx, cy + oy), (cx - rx, cy)),
+        ((cx - rx, cy - oy), (cx - ox, cy - ry), (cx, cy - ry)),
+        ((cx + ox, cy - ry), (cx + rx, cy - oy), (cx + rx, cy)),
+    ] {
+        let _ = writeln!(
+            out,
+            "{} {} {} {} {} {} c",
+            num(c1.0),
+            num(c1.1),
+            num(c2.0),
+            num(c2.1),
+            num(end.0),
+            num(end.1)
+        );
+    }
+}
+
+fn arrowhead(out: &mut String, shape: &Value, from: (f64, f64), tip: (f64, f64)) {
+    let angle = (tip.1 - from.1).atan2(tip.0 - from.0);
+    let size = 10.0 + prop_num(shape, "strokeWidth", 2.0) * 2.0;
+    let wing = |da: f64| {
+        (
+            tip.0 - size * (angle + da).cos(),
+            tip.1 - size * (angle + da).sin(),
+        )
+    };
+    polyline(out, &[wing(0.45), tip, wing(-0.45)]);
+    out.push_str("h\n");
+    let color = rgb(prop_str(shape, "strokeColor")).unwrap_or((0.0, 0.0, 0.0));
+    let _ = writeln!(
+        out,
+        "{} {} {} rg f",
+        num(color.0),
+        num(color.1),
+        num(color.2)
+    );
+}
+
+/// Stroke (and, for closed shapes, fill) the current path.
+fn paint(out: &mut String, shape: &Value, closed: bool) {
+    let stroke = rgb(prop_str(shape, "strokeColor"));
+    let fill = if closed {
+        rgb(prop_str(shape, "fillColor"))
+    } else {
+        None
+    };
+    let width = prop_num(shape, "strokeWidth", 2.0);
+
+    if let Some((r, g, b)) = fill {
+        let _ = write!(out, "{} {} {} rg ", num(r), num(g), num(b));
+    }
+    if let Some((r, g, b)) = stroke.filter(|_| width > 0.0) {
+        let _ = write!(out, "{} {} {} RG {} w ", num(r), num(g), num(b), num(width));
+        match prop_str(shape, "strokeStyle") {
+            "dashed" => {
+                let _ = write!(out, "[{} {}] 0 d ", num(width * 4.0), num(width * 2.0));
+            }
+            "dotted" => {
+                let _ = write!(out, "[0 {}] 0 d ", num(width * 2.0));
+            }
+            _ => {}
+        }
+        out.push_str(if fill.is_some() { "B\n" } else { "S\n" });
+    } else {
+        out.push_str(if fill.is_some() { "f\n" } else { "n\n" });
+    }
+}
+
+fn label(out: &mut String, shape: &Value) {
+    let text = prop_str(shape, "text");
+    if text.is_empty() {
+        return;
+    }
+    let shape_type = prop_str(shape, "type");
+    let font_size = match prop_num(shape, "fontSize", 0.0) {
+        s if s > 0.0 => s,
+        _ => text_metrics::default_font_size(shape_type),
+    };
+    let family = "Helvetica";
+    let x = prop_num(shape, "x", 0.0);
+    let y = prop_num(shape, "y", 0.0);
+    let w = prop_num(shape, "width", 0.0);
+    let h = prop_num(shape, "height", 0.0);
+
+    let lines: Vec<String> = match shape_type {
+        "line" | "arrow" | "text" => text.lines().map(str::to_string).collect(),
+        _ => text_metrics::wrap_lines(
+            text,
+            w - text_metrics::TEXT_PADDING * 2.0,
+            font_size,
+            family,
+        ),
+    };
+    let line_height = font_size * text_metrics::LINE_HEIGHT;
+    let (anchor_x, first_y, align) = match shape_type {
+        "text" => (x, y + font_size, "left"),
+        "line" | "arrow" => {
+            let cy = (y + prop_num(shape, "y2", y)) / 2.0;
+            (
+                (x + prop_num(shape, "x2", x)) / 2.0,
+                cy - line_height * (lines.len() as f64 - 1.0) / 2.0 + font_size * 0.35,
+                "center",
+            )
+        }
+        _ => {
+            let first =
+                y + h / 2.0 - line_height * (lines.len() as f64 - 1.0) / 2.0 + font_size * 0.35;
+            match prop_str(shape, "textAlign") {
+                "left" => (x + text_metrics::TEXT_PADDING, first, "left"),
+                "right" => (x + w - text_metrics::TEXT_PADDING, first, "right"),
+                _ => (x + w / 2.0, first, "center"),
+            }
+        }
+    };
+    let color = match shape_type {
+        "sticky" => (0.2, 0.2, 0.2),
+        _ => rgb(prop_str(shape, "strokeColor")).unwrap_or((0.0, 0.0, 0.0)),
+    };
+
+    let _ = writeln!(out, "{} {} {} rg", num(color.0), num(color.1), num(color.2));
+    for (i, line) in lines.iter().enumerate() {
+        let width = text_metrics::line_width(line, font_size, family);
+        let lx = match align {
+            "center" => anchor_x - width / 2.0,
+            "right" => anchor_x - width,
+            _ => anchor_x,
+        };
+        let ly = first_y + line_height * i as f64;
+        // Text space is flipped back upright inside our y-down page.
+        let _ = writeln!(
+            out,
+            "BT /F1 {} Tf 1 0 0 -1 {} {} Tm {} Tj ET",
+            num(font_size),
+            num(lx),
+            num(ly),
+            pdf_string(line)
+        );
+    }
+}
+
+// --- Helpers ---
+
+fn prop_num(shape: &Value, key: &str, default: f64) -> f64 {
+    shape.get(key).and_then(|v| v.as_f64()).unwrap_or(default)
+}
+
+fn prop_str<'a>(shape: &'a Value, key: &str) -> &'a str {
+    shape.get(key).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+fn prop_points(shape: &Value, key: &str) -> Vec<(f64, f64)> {
+    shape
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|p| Some((p.get("x")?.as_f64()?, p.get("y")?.as_f64()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse `#rgb` / `#rrggbb` into 0-1 components; `None` for transparent or
+/// anything else we can't paint.
+fn rgb(color: &str) -> Option<(f64, f64, f64)> {
+    let hex = color.strip_prefix('#')?;
+    let expanded: String = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 | 8 => hex[..6].to_string(),
+        _ => return None,
+    };
+    let v = u32::from_str_radix(&expanded, 16).ok()?;
+    let channel = |shift: u32| ((v >> shift) & 0xff) as f64 / 255.0;
+    Some((channel(16), channel(8), channel(0)))
+}
+
+/// Compact number formatting for content streams.
+fn num(v: f64) -> String {
+    let rounded = (v * 1000.0).round() / 1000.0;
+    if rounded == rounded.trunc() {
+        format!("{}", rounded as i64)
+    } else {
+        format!("{}", rounded)
+    }
+}
+
+/// A PDF literal string. Dashes, curly quotes and a few other common
+/// characters are mapped to their WinAnsi codes; anything else outside
+/// Latin-1 becomes `?`. The stream is written as Latin-1 to match WinAnsi
+/// closely enough for labels.
+fn pdf_string(s: &str) -> String {
+    let mut out = String::from("(");
+    for c in s.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if (c as u32) < 0x20 => {}
+            c if (c as u32) <= 0xff => out.push(c),
+            c => out.push(winansi(c).unwrap_or('?')),
+        }
+    }
+    out.push(')');
+    out
+}
+
+/// The WinAnsi code, as a Latin-1 char, of a typographic character that
+/// WinAnsi places in 0x80-0x9f.
+fn winansi(c: char) -> Option<char> {
+    let code: u8 = match c {
+        '€' => 0x80,
+        '…' => 0x85,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '™' => 0x99,
+        _ => return None,
+    };
+    Some(code as char)
+}
+
+fn latin1(s: &str) -> Vec<u8> {
+    s.chars()
+        .map(|c| if (c as u32) <= 0xff { c as u8 } else { b'?' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Unit;
+    use serde_json::json;
+
+    fn media_box(pdf: &[u8]) -> Vec<f64> {
+        let text = String::from_utf8_lossy(pdf);
+        let start = text.find("/MediaBox [").unwrap() + "/MediaBox [".len();
+        let end = start + text[start..].find(']').unwrap();
+        text[start..end]
+            .split_whitespace()
+            .map(|v| v.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn scaled_document_prints_at_true_size() {
+        // 100 px = 1 m; a 4 m wall plus the 20 px content margin each side.
+        let scale = DocumentScale {
+            unit: Unit::M,
+            pixels_per_unit: 100.0,
+        };
+        let shapes =
+            vec![json!({ "type": "rectangle", "x": 0, "y": 0, "width": 400, "height": 10 })];
+        let options = PdfExportOptions {
+            drawing_ratio: 100.0,
+            ..Default::default()
+        };
+        let pdf = render_pdf(&shapes, Some(&scale), &options).unwrap();
+        let mb = media_box(&pdf);
+        // 4.4 m at 1:100 is 44 mm.
+        assert!((mb[2] / POINTS_PER_MM - 44.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn unscaled_document_uses_96_dpi() {
+        let shapes =
+            vec![json!({ "type": "ellipse", "x": 20, "y": 20, "width": 56, "height": 56 })];
+        let pdf = render_pdf(&shapes, None, &PdfExportOptions::default()).unwrap();
+        assert_eq!(media_box(&pdf), vec![0.0, 0.0, 72.0, 72.0]);
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+    }
+
+    #[test]
+    fn oversized_pages_are_rejected() {
+        let scale = DocumentScale {
+            unit: Unit::M,
+            pixels_per_unit: 1.0,
+        };
+        let shapes =
+            vec![json!({ "type": "rectangle", "x": 0, "y": 0, "width": 100, "height": 100 })];
+        assert!(render_pdf(&shapes, Some(&scale), &PdfExportOptions::default()).is_err());
+    }
+
+    #[test]
+    fn xref_offsets_point_at_objects() {
+        let shapes = vec![json!({ "type": "text", "x": 0, "y": 0, "text": "Kitchen (3.2m)" })];
+        let pdf = render_pdf(&shapes, None, &PdfExportOptions::default()).unwrap();
+        let text = String::from_utf8_lossy(&pdf).to_string();
+        assert!(text.contains("(Kitchen \\(3.2m\\)) Tj"));
+        let xref = text.find("xref\n").unwrap();
+        let first_entry = text[xref..].lines().nth(3).unwrap();
+        let offset: usize = first_entry[..10].parse().unwrap();
+        assert!(pdf[offset..].starts_with(b"1 0 obj"));
+    }
+
+    #[test]
+    fn watermark_is_stamped_over_the_page() {
+        let shapes =
+            vec![json!({ "type": "rectangle", "x": 0, "y": 0, "width": 400, "height": 300 })];
+        let plain = render_pdf(&shapes, None, &PdfExportOptions::default()).unwrap();
+        assert!(!String::from_utf8_lossy(&plain).contains("/WM gs"));
+
+        let options = PdfExportOptions {
+            watermark: Some(Watermark {
+                enabled: true,
+                opacity: 0.5,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let pdf = render_pdf(&shapes, None, &options).unwrap();
+        let text: String = pdf.iter().map(|&b| b as char).collect();
+        assert!(text.contains("/ExtGState << /WM << /ca 0.5 /CA 0.5 >> >>"));
+        assert!(text.contains("/BaseFont /Helvetica-Bold"));
+        // Drawn after the shapes, outside their transform.
+        assert!(text.find("Q\nq\n/WM gs").is_some());
+        assert!(text.contains("(CONFIDENTIAL \u{97} internal) Tj"));
+    }
+
+    #[test]
+    fn image_watermarks_are_embedded_once() {
+        let logo = image::DynamicImage::new_rgba8(30, 10);
+        let options = PdfExportOptions {
+            watermark: Some(Watermark {
+                enabled: true,
+                placement: watermark::Placement::BottomRight,
+                image: Some(crate::photo::encode_png_data_url(&logo).unwrap()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let shapes =
+            vec![json!({ "type": "rectangle", "x": 0, "y": 0, "width": 400, "height": 300 })];
+        let page = PdfPage {
+            title: "Plan".to_string(),
+            shapes,
+            scale: None,
+        };
+        let pdf = render_pdf_pages(&[page.clone(), page], &options).unwrap();
+        let text = String::from_utf8_lossy(&pdf).to_string();
+        assert_eq!(text.matches("/Subtype /Image").count(), 2);
+        assert!(text.contains("/Width 30 /Height 10 /ColorSpace /DeviceRGB /BitsPerComponent 8 /SMask 5 0 R /Length 900"));
+        assert!(text.contains("/Kids [6 0 R 8 0 R]"));
+        assert_eq!(text.matches("/XObject << /WMI 4 0 R >>").count(), 2);
+        assert!(text.contains("/WMI Do"));
+        assert!(!text.contains("Tj\nET\nQ"));
+    }
+
+    #[test]
+    fn each_tab_gets_a_labelled_page() {
+        let scale = DocumentScale {
+            unit: Unit::M,
+            pixels_per_unit: 100.0,
+        };
+        let tabs = vec![
+            PdfPage {
+                title: "Ground floor".to_string(),
+                shapes: vec![
+                    json!({ "type": "rectangle", "x": 0, "y": 0, "width": 400, "height": 10 }),
+                ],
+                scale: Some(scale),
+            },
+            PdfPage {
+                title: "Ideas (rough)".to_string(),
+                shapes: vec![
+                    json!({ "type": "ellipse", "x": 20, "y": 20, "width": 56, "height": 56 }),
+                ],
+                scale: None,
+            },
+        ];
+        let pdf = render_pdf_pages(&tabs, &PdfExportOptions::default()).unwrap();
+        let text = String::from_utf8_lossy(&pdf).to_string();
+        assert!(text.contains("/Kids [4 0 R 6 0 R] /Count 2"));
+        assert!(text.contains(
+            "/PageLabels << /Nums [0 << /P (Ground floor) >> 1 << /P (Ideas \\(rough\\)) >>] >>"
+        ));
+        // Each page keeps its own size and scale
+        let second = pdf.windows(11).rposition(|w| w == b"/MediaBox [").unwrap();
+        assert_eq!(media_box(&pdf[second..]), vec![0.0, 0.0, 72.0, 72.0]);
+        assert!(media_box(&pdf)[2] > 1000.0);
+
+        // Every xref entry lands on its object
+        let xref = text.find("xref\n").unwrap();
+        for (i, entry) in text[xref..].lines().skip(3).take(8).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+        assert!(render_pdf_pages(&[], &PdfExportOptions::default()).is_err());
+    }
+}
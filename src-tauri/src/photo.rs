@@ -0,0 +1,515 @@
+//! Whiteboard photo cleanup.
+//!
+//! Perspective correction (from given or detected board corners), background
+//! flattening (divide out a blurred estimate of the lighting) and a contrast
+//! stretch, producing a new PNG. Also home to the small image helpers shared
+//! with `trace`.
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::assets;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use image::{imageops::FilterType, DynamicImage, GrayImage, Rgb, RgbImage};
+use serde_json::Value;
+
+/// Longest side processed; bigger photos are scaled down first.
+const MAX_CLEANUP_DIM: u32 = 2048;
+/// Working size for corner detection and background estimation.
+const ANALYSIS_DIM: u32 = 256;
+/// Detected boards smaller than this fraction of the photo are ignored.
+const MIN_BOARD_AREA: f64 = 0.3;
+/// Percentiles clipped by the contrast stretch.
+const STRETCH_LOW: f64 = 0.02;
+const STRETCH_HIGH: f64 = 0.98;
+
+/// Board corners as fractions of image width/height, in order
+/// top-left, top-right, bottom-right, bottom-left.
+pub type Quad = [(f64, f64); 4];
+
+// --- Shared helpers ---
+
+/// Decode a base64 `data:` URL.
+pub fn decode_data_url(src: &str) -> Result<DynamicImage, String> {
+    let src = assets::inline_src(src);
+    let (header, data) = src
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(','))
+        .ok_or("Only embedded (data URL) images are supported")?;
+    if !header.ends_with(";base64") {
+        return Err("Image data URL must be base64 encoded".to_string());
+    }
+    let bytes = STANDARD
+        .decode(data.trim())
+        .map_err(|e| format!("Invalid image data: {}", e))?;
+    image::load_from_memory(&bytes).map_err(|e| format!("Failed to decode image: {}", e))
+}
+
+pub fn encode_png_data_url(img: &DynamicImage) -> Result<String, String> {
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut bytes, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        STANDARD.encode(bytes.into_inner())
+    ))
+}
+
+/// Otsu's threshold: the gray level that best separates the histogram into
+/// two classes.
+pub fn otsu_threshold(gray: &GrayImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for p in gray.pixels() {
+        histogram[p.0[0] as usize] += 1;
+    }
+    let total = gray.pixels().len() as f64;
+    let sum_all: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| i as f64 * c as f64)
+        .sum();
+
+    let (mut best_t, mut best_var) = (0u8, -1.0);
+    let (mut weight_bg, mut sum_bg) = (0.0, 0.0);
+    for (t, &count) in histogram.iter().enumerate() {
+        weight_bg += count as f64;
+        if weight_bg == 0.0 {
+            continue;
+        }
+        let weight_fg = total - weight_bg;
+        if weight_fg == 0.0 {
+            break;
+        }
+        sum_bg += t as f64 * count as f64;
+        let mean_bg = sum_bg / weight_bg;
+        let mean_fg = (sum_all - sum_bg) / weight_fg;
+        let var = weight_bg * weight_fg * (mean_bg - mean_fg).powi(2);
+        if var > best_var {
+            best_var = var;
+            best_t = t as u8;
+        }
+    }
+    best_t
+}
+
+// --- Pipeline ---
+
+pub fn cleanup(img: &DynamicImage, corners: Option<Quad>) -> RgbImage {
+    let img = if img.width().max(img.height()) > MAX_CLEANUP_DIM {
+        img.resize(MAX_CLEANUP_DIM, MAX_CLEANUP_DIM, FilterType::Triangle)
+    } else {
+        img.clone()
+    };
+    let rgb = img.to_rgb8();
+
+    let quad = corners.or_else(|| detect_board(&rgb));
+    let warped = match quad {
+        Some(q) => warp_perspective(&rgb, q),
+        None => rgb,
+    };
+    let mut flat = flatten_background(&warped);
+    stretch_contrast(&mut flat);
+    flat
+}
+
+/// Find the whiteboard as the extreme points of the bright region.
+fn detect_board(rgb: &RgbImage) -> Option<Quad> {
+    let small = DynamicImage::ImageRgb8(rgb.clone())
+        .resize(ANALYSIS_DIM, ANALYSIS_DIM, FilterType::Triangle)
+        .to_luma8();
+    let threshold = otsu_threshold(&small);
+    let (w, h) = (small.width() as f64, small.height() as f64);
+
+    let mut tl = (f64::MAX, (0.0, 0.0));
+    let mut br = (f64::MIN, (0.0, 0.0));
+    let mut tr = (f64::MIN, (0.0, 0.0));
+    let mut bl = (f64::MAX, (0.0, 0.0));
+    for (x, y, p) in small.enumerate_pixels() {
+        if p.0[0] <= threshold {
+            continue;
+        }
+        let (fx, fy) = ((x as f64 + 0.5) / w, (y as f64 + 0.5) / h);
+        let (sum, diff) = (fx + fy, fx - fy);
+        if sum < tl.0 {
+            tl = (sum, (fx, fy));
+        }
+        if sum > br.0 {
+            br = (sum, (fx, fy));
+        }
+        if diff > tr.0 {
+            tr = (diff, (fx, fy));
+        }
+        if diff < bl.0 {
+            bl = (diff, (fx, fy));
+        }
+    }
+    let quad = [tl.1, tr.1, br.1, bl.1];
+    (quad_area(&quad) >= MIN_BOARD_AREA).then_some(quad)
+}
+
+fn quad_area(q: &Quad) -> f64 {
+    // Shoelace formula.
+    (0..4)
+        .map(|i| {
+            let (a, b) = (q[i], q[(i + 1) % 4]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f64>()
+        .abs()
+        / 2.0
+}
+
+fn dist(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+/// Solve the homography mapping the unit square's corners onto `quad`.
+pub fn homography(quad: &[(f64, f64); 4]) -> Option<[f64; 8]> {
+    let unit = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+    let mut m = [[0.0f64; 9]; 8];
+    for i in 0..4 {
+        let (u, v) = unit[i];
+        let (x, y) = quad[i];
+        m[2 * i] = [u, v, 1.0, 0.0, 0.0, 0.0, -u * x, -v * x, x];
+        m[2 * i + 1] = [0.0, 0.0, 0.0, u, v, 1.0, -u * y, -v * y, y];
+    }
+    // Gaussian elimination with partial pivoting.
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+        if m[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        m.swap(col, pivot);
+        let pivot_row = m[col];
+        for (r, row) in m.iter_mut().enumerate() {
+            if r != col {
+                let factor = row[col] / pivot_row[col];
+                for (cell, p) in row.iter_mut().zip(pivot_row).skip(col) {
+                    *cell -= factor * p;
+                }
+            }
+        }
+    }
+    let mut h = [0.0; 8];
+    for (i, (slot, row)) in h.iter_mut().zip(m).enumerate() {
+        *slot = row[8] / row[i];
+    }
+    Some(h)
+}
+
+fn project(h: &[f64; 8], u: f64, v: f64) -> (f64, f64) {
+    let w = h[6] * u + h[7] * v + 1.0;
+    (
+        (h[0] * u + h[1] * v + h[2]) / w,
+        (h[3] * u + h[4] * v + h[5]) / w,
+    )
+}
+
+fn warp_perspective(rgb: &RgbImage, quad: Quad) -> RgbImage {
+    let (w, h) = (rgb.width() as f64, rgb.height() as f64);
+    let px: [(f64, f64); 4] = quad.map(|(x, y)| (x * w, y * h));
+    let Some(hm) = homography(&px) else {
+        return rgb.clone();
+    };
+
+    // Output keeps the board's average edge lengths.
+    let out_w = ((dist(px[0], px[1]) + dist(px[3], px[2])) / 2.0)
+        .round()
+        .max(1.0) as u32;
+    let out_h = ((dist(px[0], px[3]) + dist(px[1], px[2])) / 2.0)
+        .round()
+        .max(1.0) as u32;
+
+    RgbImage::from_fn(out_w, out_h, |x, y| {
+        let (sx, sy) = project(
+            &hm,
+            (x as f64 + 0.5) / out_w as f64,
+            (y as f64 + 0.5) / out_h as f64,
+        );
+        sample_bilinear(rgb, sx - 0.5, sy - 0.5)
+    })
+}
+
+fn sample_bilinear(img: &RgbImage, x: f64, y: f64) -> Rgb<u8> {
+    let max_x = img.width() as f64 - 1.0;
+    let max_y = img.height() as f64 - 1.0;
+    let (x, y) = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(max_x as u32), (y0 + 1).min(max_y as u32));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+    let mut out = [0u8; 3];
+    for (c, slot) in out.iter_mut().enumerate() {
+        let p = |px: u32, py: u32| img.get_pixel(px, py).0[c] as f64;
+        let top = p(x0, y0) * (1.0 - fx) + p(x1, y0) * fx;
+        let bottom = p(x0, y1) * (1.0 - fx) + p(x1, y1) * fx;
+        *slot = (top * (1.0 - fy) + bottom * fy).round() as u8;
+    }
+    Rgb(out)
+}
+
+/// Divide out uneven lighting: estimate the background with a heavy blur on a
+/// small copy, then scale each pixel so the background becomes white.
+fn flatten_background(rgb: &RgbImage) -> RgbImage {
+    let (w, h) = rgb.dimensions();
+    let small = DynamicImage::ImageRgb8(rgb.clone()).resize(
+        ANALYSIS_DIM / 4,
+        ANALYSIS_DIM / 4,
+        FilterType::Triangle,
+    );
+    // Max-filter before blurring so ink strokes don't darken the estimate.
+    let small = dilate(&small.to_rgb8());
+    let background = DynamicImage::ImageRgb8(image::imageops::blur(&small, 3.0))
+        .resize_exact(w, h, FilterType::Triangle)
+        .to_rgb8();
+
+    RgbImage::from_fn(w, h, |x, y| {
+        let p = rgb.get_pixel(x, y).0;
+        let b = background.get_pixel(x, y).0;
+        Rgb([0, 1, 2].map(|c| {
+            let bg = (b[c] as f64).max(1.0);
+            (p[c] as f64 / bg * 255.0).min(255.0) as u8
+        }))
+    })
+}
+
+fn dilate(img: &RgbImage) -> RgbImage {
+    let (w, h) = img.dimensions();
+    RgbImage::from_fn(w, h, |x, y| {
+        let mut best = [0u8; 3];
+        for ny in y.saturating_sub(1)..=(y + 1).min(h - 1) {
+            for nx in x.saturating_sub(1)..=(x + 1).min(w - 1) {
+                let p = img.get_pixel(nx, ny).0;
+                for c in 0..3 {
+                    best[c] = best[c].max(p[c]);
+                }
+            }
+        }
+        Rgb(best)
+    })
+}
+
+/// Stretch luminance between the low/high percentiles to the full range.
+fn stretch_contrast(rgb: &mut RgbImage) {
+    let mut histogram = [0u64; 256];
+    for p in rgb.pixels() {
+        histogram[luma(p) as usize] += 1;
+    }
+    let total = rgb.pixels().len() as f64;
+    let percentile = |q: f64| {
+        let mut seen = 0.0;
+        for (i, &count) in histogram.iter().enumerate() {
+            seen += count as f64;
+            if seen >= total * q {
+                return i as f64;
+            }
+        }
+        255.0
+    };
+    let (lo, hi) = (percentile(STRETCH_LOW), percentile(STRETCH_HIGH));
+    if hi - lo < 1.0 {
+        return;
+    }
+    for p in rgb.pixels_mut() {
+        for c in p.0.iter_mut() {
+            *c = ((*c as f64 - lo) / (hi - lo) * 255.0).clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+fn luma(p: &Rgb<u8>) -> u8 {
+    (0.299 * p.0[0] as f64 + 0.587 * p.0[1] as f64 + 0.114 * p.0[2] as f64).round() as u8
+}
+
+fn parse_corners(value: Option<&Value>) -> Result<Option<Quad>, String> {
+    let Some(value) = value.filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    let points: Vec<(f64, f64)> = serde_json::from_value::<Vec<[f64; 2]>>(value.clone())
+        .map_err(|_| "corners must be four [x, y] pairs")?
+        .into_iter()
+        .map(|[x, y]| (x, y))
+        .collect();
+    let quad: Quad = points
+        .try_into()
+        .map_err(|_| "corners must be four [x, y] pairs")?;
+    if quad
+        .iter()
+        .any(|(x, y)| !(0.0..=1.0).contains(x) || !(0.0..=1.0).contains(y))
+    {
+        return Err("corners must be fractions of the image size (0-1)".to_string());
+    }
+    Ok(Some(quad))
+}
+
+async fn cleanup_data_url(
+    src: String,
+    corners: Option<Quad>,
+) -> Result<(String, u32, u32), String> {
+    tokio::task::spawn_blocking(move || {
+        let img = decode_data_url(&src)?;
+        let cleaned = cleanup(&img, corners);
+        let (w, h) = cleaned.dimensions();
+        Ok((
+            encode_png_data_url(&DynamicImage::ImageRgb8(cleaned))?,
+            w,
+            h,
+        ))
+    })
+    .await
+    .map_err(|e| format!("Cleanup task failed: {}", e))?
+}
+
+// --- Tauri command ---
+
+/// Clean up a photo given as a data URL and return the cleaned PNG data URL.
+/// `corners` are [x, y] fractions (top-left, top-right, bottom-right,
+/// bottom-left); when omitted the board is detected automatically.
+#[tauri::command]
+pub async fn cleanup_photo(src: String, corners: Option<Value>) -> Result<String, String> {
+    let corners = parse_corners(corners.as_ref())?;
+    cleanup_data_url(src, corners).await.map(|(url, _, _)| url)
+}
+
+// --- MCP tool ---
+
+pub fn photo_tools() -> Vec<Value> {
+    vec![serde_json::json!({
+        "name": "cleanup_photo",
+        "description": "Clean up a whiteboard photo: straighten the perspective, flatten uneven lighting and boost contrast. Adds the cleaned image on top of the original (or replaces it). Run before trace_image for best results.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "shapeId": { "type": "string", "description": "ID of the image shape" },
+                "corners": {
+                    "type": "array",
+                    "description": "Board corners as [x, y] fractions of the image (top-left, top-right, bottom-right, bottom-left). Detected automatically if omitted.",
+                    "items": { "type": "array", "items": { "type": "number" }, "minItems": 2, "maxItems": 2 },
+                    "minItems": 4,
+                    "maxItems": 4
+                },
+                "replace": { "type": "boolean", "description": "Delete the original image (default: false)" }
+            },
+            "required": ["shapeId"]
+        }
+    })]
+}
+
+pub async fn call_photo_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "cleanup_photo" => Some(cleanup_photo_tool(state, arguments).await),
+        _ => None,
+    }
+}
+
+async fn cleanup_photo_tool(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let shape_id = arguments
+        .get("shapeId")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing required field: shapeId")?;
+    let corners = parse_corners(arguments.get("corners"))?;
+    let replace = arguments
+        .get("replace")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    let shape = bridge_tool_call(state, "get_shape", serde_json::json!({ "id": shape_id })).await?;
+    if let Some(err) = shape.get("error").and_then(|e| e.as_str()) {
+        return Err(err.to_string());
+    }
+    if shape.get("type").and_then(|t| t.as_str()) != Some("image") {
+        return Err(format!("Shape {} is not an image", shape_id));
+    }
+    let src = shape
+        .get("src")
+        .and_then(|s| s.as_str())
+        .unwrap_or("")
+        .to_string();
+
+    let (url, w, h) = cleanup_data_url(src, corners).await?;
+
+    // Keep the original's width; the height follows the corrected aspect ratio.
+    let num = |k: &str| shape.get(k).and_then(|v| v.as_f64()).unwrap_or(0.0);
+    let width = num("width");
+    let height = width * h as f64 / w.max(1) as f64;
+    let created = bridge_tool_call(
+        state,
+        "create_image",
+        serde_json::json!({ "url": url, "x": num("x"), "y": num("y"), "width": width, "height": height }),
+    )
+    .await?;
+    if let Some(err) = created.get("error").and_then(|e| e.as_str()) {
+        return Err(err.to_string());
+    }
+    if replace {
+        bridge_tool_call(state, "delete_shape", serde_json::json!({ "id": shape_id })).await?;
+    }
+
+    Ok(serde_json::json!({
+        "id": created.get("id"),
+        "width": width,
+        "height": height,
+        "replaced": replace,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn homography_maps_unit_square_to_quad() {
+        let quad = [(10.0, 20.0), (110.0, 10.0), (120.0, 90.0), (5.0, 100.0)];
+        let h = homography(&quad).unwrap();
+        for (uv, expected) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+            .iter()
+            .zip(quad)
+        {
+            let (x, y) = project(&h, uv.0, uv.1);
+            assert!((x - expected.0).abs() < 1e-6 && (y - expected.1).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn flattening_evens_out_a_lighting_gradient() {
+        // Board lit from the left: 250 fading to 150.
+        let img = RgbImage::from_fn(200, 100, |x, _| {
+            let v = (250.0 - x as f64 * 0.5) as u8;
+            Rgb([v, v, v])
+        });
+        let flat = flatten_background(&img);
+        let left = flat.get_pixel(20, 50).0[0] as i32;
+        let right = flat.get_pixel(180, 50).0[0] as i32;
+        assert!((left - right).abs() < 15, "left {} right {}", left, right);
+    }
+
+    #[test]
+    fn detects_bright_board_on_dark_background() {
+        let img = RgbImage::from_fn(200, 200, |x, y| {
+            if (40..160).contains(&x) && (30..170).contains(&y) {
+                Rgb([240, 240, 240])
+            } else {
+                Rgb([30, 30, 30])
+            }
+        });
+        let quad = detect_board(&img).unwrap();
+        assert!((quad[0].0 - 0.2).abs() < 0.02 && (quad[0].1 - 0.15).abs() < 0.02);
+        assert!((quad[2].0 - 0.8).abs() < 0.02 && (quad[2].1 - 0.85).abs() < 0.02);
+    }
+
+    #[test]
+    fn cleanup_with_corners_crops_to_board() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(100, 100, Rgb([200, 200, 200])));
+        let corners = [(0.1, 0.1), (0.9, 0.1), (0.9, 0.6), (0.1, 0.6)];
+        let out = cleanup(&img, Some(corners));
+        assert_eq!(out.dimensions(), (80, 50));
+    }
+
+    #[test]
+    fn corners_are_validated() {
+        assert!(parse_corners(Some(&serde_json::json!([[0, 0], [1, 0], [1, 1]]))).is_err());
+        assert!(parse_corners(Some(&serde_json::json!([[0, 0], [2, 0], [1, 1], [0, 1]]))).is_err());
+        assert!(parse_corners(None).unwrap().is_none());
+    }
+}
@@ -0,0 +1,222 @@
+//! Agent presence ("cursors") for MCP clients.
+//!
+//! Every tool accepts an optional `focusPoint` argument. When present it is
+//! stripped before the call reaches the webview and forwarded as an
+//! `agent-cursor` event, so the canvas can show where an agent is working.
+//! Entries that haven't been refreshed within `PRESENCE_TTL_SECS` are expired
+//! by a sweeper task and announced with `agent-cursor-expired`, as are the
+//! cursors of a connection that goes away.
+
+use crate::api::SharedApiState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::watch;
+
+pub const PRESENCE_TTL_SECS: u64 = 30;
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_AGENT: &str = "agent";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FocusPoint {
+    pub x: f64,
+    pub y: f64,
+    /// Display name for the cursor; defaults to "agent".
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentCursor {
+    pub agent: String,
+    pub x: f64,
+    pub y: f64,
+    pub tool: String,
+    pub updated_at: u64,
+    /// The `McpClient` that reported it.
+    #[serde(skip)]
+    pub client: String,
+}
+
+#[derive(Default)]
+pub struct PresenceRegistry {
+    cursors: HashMap<String, AgentCursor>,
+}
+
+impl PresenceRegistry {
+    pub fn update(&mut self, point: FocusPoint, tool: &str, client: &str, now: u64) -> AgentCursor {
+        let agent = point
+            .label
+            .filter(|l| !l.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_AGENT.to_string());
+        let cursor = AgentCursor {
+            agent: agent.clone(),
+            x: point.x,
+            y: point.y,
+            tool: tool.to_string(),
+            updated_at: now,
+            client: client.to_string(),
+        };
+        self.cursors.insert(agent, cursor.clone());
+        cursor
+    }
+
+    /// Drop entries older than the TTL and return the names that expired.
+    pub fn sweep(&mut self, now: u64) -> Vec<String> {
+        let stale: Vec<String> = self
+            .cursors
+            .values()
+            .filter(|c| now.saturating_sub(c.updated_at) >= PRESENCE_TTL_SECS)
+            .map(|c| c.agent.clone())
+            .collect();
+        for agent in &stale {
+            self.cursors.remove(agent);
+        }
+        stale
+    }
+
+    /// Drop the cursors reported by `client` and return their names.
+    pub fn forget_client(&mut self, client: &str) -> Vec<String> {
+        let gone: Vec<String> = self
+            .cursors
+            .values()
+            .filter(|c| c.client == client)
+            .map(|c| c.agent.clone())
+            .collect();
+        for agent in &gone {
+            self.cursors.remove(agent);
+        }
+        gone
+    }
+
+    pub fn list(&self) -> Vec<AgentCursor> {
+        self.cursors.values().cloned().collect()
+    }
+}
+
+/// Remove `focusPoint` from tool arguments, returning it if it parsed.
+pub fn take_focus_point(arguments: &mut serde_json::Value) -> Option<FocusPoint> {
+    let raw = arguments.as_object_mut()?.remove("focusPoint")?;
+    serde_json::from_value(raw).ok()
+}
+
+/// Advertise the optional `focusPoint` argument on every tool schema.
+pub fn with_focus_point(mut tools: serde_json::Value) -> serde_json::Value {
+    if let Some(list) = tools.as_array_mut() {
+        for tool in list {
+            if let Some(props) = tool
+                .pointer_mut("/inputSchema/properties")
+                .and_then(|p| p.as_object_mut())
+            {
+                props.insert("focusPoint".to_string(), focus_point_schema());
+            }
+        }
+    }
+    tools
+}
+
+fn focus_point_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "description": "Optional canvas coordinate the agent is working at. Shown to the user as an agent cursor.",
+        "properties": {
+            "x": { "type": "number" },
+            "y": { "type": "number" },
+            "label": { "type": "string", "description": "Cursor label (defaults to \"agent\")" }
+        },
+        "required": ["x", "y"]
+    })
+}
+
+/// Record presence for a tool call and notify the frontend.
+pub async fn report(state: &SharedApiState, point: FocusPoint, tool: &str, client: &str) {
+    let cursor = state
+        .presence
+        .lock()
+        .await
+        .update(point, tool, client, crate::embed::now_secs());
+    let _ = state.app_handle.emit("agent-cursor", &cursor);
+}
+
+/// Expire the cursors of a client whose connection ended.
+pub async fn forget_client(state: &SharedApiState, client: &str) {
+    let gone = state.presence.lock().await.forget_client(client);
+    announce_expired(state, gone);
+}
+
+fn announce_expired(state: &SharedApiState, agents: Vec<String>) {
+    for agent in agents {
+        let _ = state.app_handle.emit(
+            "agent-cursor-expired",
+            serde_json::json!({ "agent": agent }),
+        );
+    }
+}
+
+/// Periodically expire stale cursors until the server shuts down.
+pub async fn run_sweeper(state: SharedApiState, mut shutdown: watch::Receiver<bool>) {
+    let mut ticker = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            changed = shutdown.changed() => {
+                if changed.is_err() || *shutdown.borrow() {
+                    break;
+                }
+            }
+        }
+        let expired = state.presence.lock().await.sweep(crate::embed::now_secs());
+        announce_expired(&state, expired);
+    }
+}
+
+#[tauri::command]
+pub async fn list_agent_cursors(
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<Vec<AgentCursor>, String> {
+    Ok(state.presence.lock().await.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn focus_point_is_stripped_from_arguments() {
+        let mut args = serde_json::json!({ "id": "a", "focusPoint": { "x": 10, "y": 20 } });
+        let point = take_focus_point(&mut args).unwrap();
+        assert_eq!((point.x, point.y), (10.0, 20.0));
+        assert_eq!(args, serde_json::json!({ "id": "a" }));
+        assert!(take_focus_point(&mut args).is_none());
+    }
+
+    #[test]
+    fn stale_cursors_are_swept() {
+        let mut registry = PresenceRegistry::default();
+        let point = |label: &str| FocusPoint {
+            x: 0.0,
+            y: 0.0,
+            label: Some(label.to_string()),
+        };
+        registry.update(point("old"), "add_shape", "c1", 100);
+        registry.update(point("fresh"), "add_shape", "c2", 120);
+
+        assert!(registry.sweep(125).is_empty());
+        assert_eq!(registry.sweep(130), vec!["old".to_string()]);
+        assert_eq!(registry.list().len(), 1);
+
+        assert!(registry.forget_client("c1").is_empty());
+        assert_eq!(registry.forget_client("c2"), vec!["fresh".to_string()]);
+        assert!(registry.list().is_empty());
+    }
+
+    #[test]
+    fn schemas_gain_focus_point() {
+        let tools = with_focus_point(serde_json::json!([
+            { "name": "t", "inputSchema": { "type": "object", "properties": {} } }
+        ]));
+        assert!(tools[0]["inputSchema"]["properties"]["focusPoint"].is_object());
+    }
+}
@@ -0,0 +1,342 @@
+//! Tool exposure profiles.
+//!
+//! A profile decides which MCP tools a client sees in `tools/list` and may
+//! call. Settings pick a default profile and can override it per client,
+//! keyed by the name the client gives in `initialize` (see
+//! `session::McpClient`), so a new agent can be tried with fewer tools.
+//! Besides the built-in profiles, users can define their own as an allow
+//! list, a deny list, or both.
+//!
+//! An optional prefix (`napkin_create_shape`) keeps tool names apart when a
+//! client aggregates several servers. Calls are accepted with or without it.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::read_only;
+use crate::settings::SettingsStore;
+
+pub const FULL: &str = "full";
+/// Tool names are limited to 64 characters; leave room for the longest.
+const MAX_PREFIX_LEN: usize = 32;
+
+/// Tools for drawing on the current board. Leaves out tabs, locks, imports,
+/// repair and anything that wipes or rescales the document.
+const DIAGRAMMING_TOOLS: &[&str] = &[
+    "get_canvas",
+    "get_shape",
+    "list_shapes",
+    "get_changes",
+    "get_shapes_in_region",
+    "get_shape_at_point",
+    "lint_canvas",
+    "export_png",
+    "screenshot_viewport",
+    "extract_outline",
+    "get_common_properties",
+    "create_shape",
+    "update_shape",
+    "bulk_update",
+    "translate_canvas",
+    "delete_shape",
+    "create_connection",
+    "batch_operations",
+    "group_shapes",
+    "ungroup",
+    "bring_to_front",
+    "send_to_back",
+    "bring_forward",
+    "send_backward",
+    "get_shape_order",
+    "set_shape_order",
+    "reorganize",
+    "fit_shape_to_text",
+    "add_constraint",
+    "remove_constraint",
+    "list_constraints",
+    "select_shapes",
+    "set_viewport",
+];
+
+const TAB_TOOLS: &[&str] = &[
+    "list_tabs",
+    "create_tab",
+    "rename_tab",
+    "switch_tab",
+    "partition_canvas",
+];
+
+enum Rule {
+    All,
+    Only(&'static [&'static str]),
+    Except(&'static [&'static str]),
+}
+
+const BUILT_IN: &[(&str, Rule)] = &[
+    (FULL, Rule::All),
+    ("read-only", Rule::Only(read_only::READ_ONLY_TOOLS)),
+    ("diagramming-only", Rule::Only(DIAGRAMMING_TOOLS)),
+    ("no-tabs", Rule::Except(TAB_TOOLS)),
+];
+
+/// A user-defined profile. Without `allow`, every tool not in `deny` is
+/// exposed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolProfile {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow: Option<Vec<String>>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl ToolProfile {
+    fn allows(&self, tool: &str) -> bool {
+        self.allow
+            .as_ref()
+            .map_or(true, |allow| allow.iter().any(|t| t == tool))
+            && !self.deny.iter().any(|t| t == tool)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ToolExposure {
+    /// Profile for clients without an override.
+    pub default_profile: String,
+    /// User-defined profiles, in addition to the built-in ones.
+    pub profiles: Vec<ToolProfile>,
+    /// Profile per client name.
+    pub clients: BTreeMap<String, String>,
+    /// Prepended to every advertised tool name.
+    pub prefix: String,
+}
+
+impl Default for ToolExposure {
+    fn default() -> Self {
+        Self {
+            default_profile: FULL.to_string(),
+            profiles: Vec::new(),
+            clients: BTreeMap::new(),
+            prefix: String::new(),
+        }
+    }
+}
+
+impl ToolExposure {
+    /// The profile that applies to `client`.
+    pub fn profile_for(&self, client: Option<&str>) -> &str {
+        client
+            .and_then(|name| self.clients.get(name))
+            .unwrap_or(&self.default_profile)
+    }
+
+    /// Whether `profile` exposes `tool`. Unknown profiles expose nothing.
+    pub fn allows(&self, profile: &str, tool: &str) -> bool {
+        if let Some((_, rule)) = BUILT_IN.iter().find(|(name, _)| *name == profile) {
+            return match rule {
+                Rule::All => true,
+                Rule::Only(tools) => tools.contains(&tool),
+                Rule::Except(tools) => !tools.contains(&tool),
+            };
+        }
+        match self.profiles.iter().find(|p| p.name == profile) {
+            Some(custom) => custom.allows(tool),
+            None => {
+                log::warn!("Unknown tool profile '{}'; exposing no tools", profile);
+                false
+            }
+        }
+    }
+
+    /// `tools` (a `tools/list` array) without the tools `profile` hides.
+    pub fn filter_tools(&self, profile: &str, mut tools: Value) -> Value {
+        if let Some(list) = tools.as_array_mut() {
+            list.retain(|tool| {
+                tool.get("name")
+                    .and_then(Value::as_str)
+                    .is_some_and(|name| self.allows(profile, name))
+            });
+        }
+        tools
+    }
+
+    /// `tools` with the prefix added to each name. Apply last: other
+    /// decorations look tools up by their bare names.
+    pub fn prefixed(&self, mut tools: Value) -> Value {
+        if self.prefix.is_empty() {
+            return tools;
+        }
+        for tool in tools.as_array_mut().into_iter().flatten() {
+            if let Some(name) = tool.get("name").and_then(Value::as_str) {
+                tool["name"] = Value::String(format!("{}{}", self.prefix, name));
+            }
+        }
+        tools
+    }
+
+    /// The bare name for a called tool: the prefix is stripped when what
+    /// remains is a tool (`is_tool`), so bare names keep working even when
+    /// they happen to start with the prefix.
+    pub fn unprefixed<'a>(&self, name: &'a str, is_tool: impl Fn(&str) -> bool) -> &'a str {
+        match name.strip_prefix(self.prefix.as_str()) {
+            Some(bare) if !self.prefix.is_empty() && !is_tool(name) && is_tool(bare) => bare,
+            _ => name,
+        }
+    }
+
+    /// Names of all profiles, built-in first.
+    pub fn profile_names(&self) -> Vec<String> {
+        BUILT_IN
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .chain(self.profiles.iter().map(|p| p.name.clone()))
+            .collect()
+    }
+}
+
+/// Reject unnamed or duplicate profiles, references to unknown ones and
+/// prefixes that would make invalid tool names.
+pub fn validate(exposure: &ToolExposure) -> Result<(), String> {
+    let prefix = &exposure.prefix;
+    if prefix.len() > MAX_PREFIX_LEN
+        || !prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!(
+            "Tool name prefix must be at most {} letters, digits, '_' or '-'",
+            MAX_PREFIX_LEN
+        ));
+    }
+    for (i, profile) in exposure.profiles.iter().enumerate() {
+        let name = profile.name.trim();
+        if name.is_empty() {
+            return Err("Tool profiles need a name".to_string());
+        }
+        if BUILT_IN.iter().any(|(built_in, _)| *built_in == name)
+            || exposure.profiles[..i]
+                .iter()
+                .any(|p| p.name == profile.name)
+        {
+            return Err(format!("Duplicate tool profile: {}", name));
+        }
+    }
+    let names = exposure.profile_names();
+    let known = |profile: &str| names.iter().any(|n| n == profile);
+    if !known(&exposure.default_profile) {
+        return Err(format!(
+            "Unknown default tool profile: {}",
+            exposure.default_profile
+        ));
+    }
+    if let Some((client, profile)) = exposure.clients.iter().find(|(_, p)| !known(p)) {
+        return Err(format!("Unknown tool profile for {}: {}", client, profile));
+    }
+    Ok(())
+}
+
+// --- Tauri command ---
+
+/// Built-in and user-defined profile names, for the settings dialog.
+#[tauri::command]
+pub fn list_tool_profiles(store: tauri::State<'_, SettingsStore>) -> Vec<String> {
+    store.get().tool_exposure.profile_names()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn exposure() -> ToolExposure {
+        ToolExposure {
+            default_profile: "read-only".to_string(),
+            profiles: vec![ToolProfile {
+                name: "stickies".to_string(),
+                allow: Some(vec!["create_shape".to_string(), "get_canvas".to_string()]),
+                deny: vec!["get_canvas".to_string()],
+            }],
+            clients: BTreeMap::from([("trusted".to_string(), FULL.to_string())]),
+            prefix: String::new(),
+        }
+    }
+
+    #[test]
+    fn clients_get_their_override_or_the_default() {
+        let exposure = exposure();
+        assert_eq!(exposure.profile_for(Some("trusted")), FULL);
+        assert_eq!(exposure.profile_for(Some("new-agent")), "read-only");
+        assert_eq!(exposure.profile_for(None), "read-only");
+    }
+
+    #[test]
+    fn profiles_decide_which_tools_are_exposed() {
+        let exposure = exposure();
+        assert!(exposure.allows(FULL, "clear_canvas"));
+        assert!(exposure.allows("read-only", "get_canvas"));
+        assert!(!exposure.allows("read-only", "create_shape"));
+        assert!(exposure.allows("diagramming-only", "create_connection"));
+        assert!(!exposure.allows("diagramming-only", "create_tab"));
+        assert!(!exposure.allows("no-tabs", "switch_tab"));
+        assert!(exposure.allows("no-tabs", "clear_canvas"));
+        assert!(exposure.allows("stickies", "create_shape"));
+        assert!(!exposure.allows("stickies", "get_canvas"));
+        assert!(!exposure.allows("missing", "get_canvas"));
+    }
+
+    #[test]
+    fn tool_lists_are_filtered() {
+        let tools = json!([{ "name": "get_canvas" }, { "name": "delete_shape" }]);
+        let filtered = exposure().filter_tools("read-only", tools);
+        assert_eq!(filtered, json!([{ "name": "get_canvas" }]));
+    }
+
+    #[test]
+    fn prefixes_are_advertised_and_optional_when_called() {
+        let exposure = ToolExposure {
+            prefix: "napkin_".to_string(),
+            ..ToolExposure::default()
+        };
+        let tools = exposure.prefixed(json!([{ "name": "create_shape" }]));
+        assert_eq!(tools, json!([{ "name": "napkin_create_shape" }]));
+
+        let is_tool = |name: &str| ["create_shape", "napkin_notes"].contains(&name);
+        assert_eq!(
+            exposure.unprefixed("napkin_create_shape", is_tool),
+            "create_shape"
+        );
+        assert_eq!(exposure.unprefixed("create_shape", is_tool), "create_shape");
+        assert_eq!(exposure.unprefixed("napkin_notes", is_tool), "napkin_notes");
+        assert_eq!(
+            exposure.unprefixed("napkin_missing", is_tool),
+            "napkin_missing"
+        );
+    }
+
+    #[test]
+    fn validation_rejects_unknown_and_duplicate_profiles() {
+        assert!(validate(&ToolExposure::default()).is_ok());
+        assert!(validate(&exposure()).is_ok());
+
+        let mut bad = exposure();
+        bad.default_profile = "missing".to_string();
+        assert!(validate(&bad).is_err());
+
+        let mut bad = exposure();
+        bad.clients
+            .insert("agent".to_string(), "missing".to_string());
+        assert!(validate(&bad).unwrap_err().contains("agent"));
+
+        let mut bad = exposure();
+        bad.profiles[0].name = "read-only".to_string();
+        assert!(validate(&bad).is_err());
+
+        let mut bad = exposure();
+        bad.prefix = "napkin.".to_string();
+        assert!(validate(&bad).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+//! MCP prompts (`prompts/list`, `prompts/get`).
+//!
+//! Built-in prompt templates for common board tasks. Each is filled in with
+//! live canvas data read through the webview bridge when the client asks for
+//! it, so the agent starts from what is on the board right now.
+
+use serde_json::{json, Value};
+
+use crate::api::SharedApiState;
+use crate::export::content_bounds;
+use crate::outline;
+use crate::resources;
+
+/// Space left between existing content and a new diagram.
+const PLACEMENT_GAP: f64 = 100.0;
+
+pub enum PromptError {
+    /// Unknown prompt or missing argument.
+    InvalidParams(String),
+    /// The canvas could not be read.
+    Failed(String),
+}
+
+struct Argument {
+    name: &'static str,
+    description: &'static str,
+    required: bool,
+}
+
+struct Prompt {
+    name: &'static str,
+    title: &'static str,
+    description: &'static str,
+    arguments: &'static [Argument],
+}
+
+const TAB_ARGUMENT: Argument = Argument {
+    name: "tabId",
+    description: "Tab to use (default: the tab agents are working on)",
+    required: false,
+};
+
+const PROMPTS: &[Prompt] = &[
+    Prompt {
+        name: "summarize_canvas",
+        title: "Summarize this canvas",
+        description: "Summarize the board's content, structure and open questions",
+        arguments: &[TAB_ARGUMENT],
+    },
+    Prompt {
+        name: "clean_up_sketch",
+        title: "Turn this sketch into a clean diagram",
+        description: "Tidy a rough sketch: align and size shapes consistently, connect related shapes and remove the hand-drawn look",
+        arguments: &[TAB_ARGUMENT],
+    },
+    Prompt {
+        name: "flowchart_from_description",
+        title: "Create a flowchart from this description",
+        description: "Draw a flowchart of a process described in words, next to what is already on the board",
+        arguments: &[
+            Argument {
+                name: "description",
+                description: "The process to chart",
+                required: true,
+            },
+            Argument {
+                name: "direction",
+                description: "Flow direction: top-down (default) or left-right",
+                required: false,
+            },
+            TAB_ARGUMENT,
+        ],
+    },
+];
+
+/// Result of `prompts/list`.
+pub fn list() -> Value {
+    let prompts: Vec<Value> = PROMPTS
+        .iter()
+        .map(|p| {
+            let arguments: Vec<Value> = p
+                .arguments
+                .iter()
+                .map(|a| {
+                    json!({
+                        "name": a.name,
+                        "description": a.description,
+                        "required": a.required,
+                    })
+                })
+                .collect();
+            json!({
+                "name": p.name,
+                "title": p.title,
+                "description": p.description,
+                "arguments": arguments,
+            })
+        })
+        .collect();
+    json!({ "prompts": prompts })
+}
+
+/// Result of `prompts/get`.
+pub async fn get(
+    state: &SharedApiState,
+    name: &str,
+    arguments: &Value,
+) -> Result<Value, PromptError> {
+    let prompt = PROMPTS
+        .iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| PromptError::InvalidParams(format!("Unknown prompt: {}", name)))?;
+    let arg = |key: &str| {
+        arguments
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    };
+    if let Some(missing) = prompt
+        .arguments
+        .iter()
+        .find(|a| a.required && arg(a.name).is_none())
+    {
+        return Err(PromptError::InvalidParams(format!(
+            "Missing required argument: {}",
+            missing.name
+        )));
+    }
+
+    let canvas = resources::canvas(state, arg("tabId"))
+        .await
+        .map_err(PromptError::Failed)?;
+    let text = match prompt.name {
+        "summarize_canvas" => summarize_canvas(&canvas),
+        "clean_up_sketch" => clean_up_sketch(&canvas),
+        _ => flowchart_from_description(
+            &canvas,
+            arg("description").unwrap_or_default(),
+            arg("direction").unwrap_or("top-down"),
+        ),
+    };
+    Ok(json!({
+        "description": prompt.description,
+        "messages": [{ "role": "user", "content": { "type": "text", "text": text } }],
+    }))
+}
+
+fn shapes(canvas: &Value) -> Vec<Value> {
+    canvas
+        .get("shapes")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn groups(canvas: &Value) -> Vec<Value> {
+    canvas
+        .get("groups")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Shape counts by type, most common first: "4 sticky, 2 arrow".
+fn shape_counts(shapes: &[Value]) -> String {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for shape in shapes {
+        let kind = shape.get("type").and_then(Value::as_str).unwrap_or("shape");
+        match counts.iter_mut().find(|(k, _)| k == kind) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((kind.to_string(), 1)),
+        }
+    }
+    counts.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+    counts
+        .iter()
+        .map(|(kind, n)| format!("{} {}", n, kind))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// One line per shape with just what a layout needs.
+fn compact_shapes(shapes: &[Value]) -> String {
+    let mut out = String::new();
+    for shape in shapes {
+        let mut line = json!({});
+        for key in [
+            "id",
+            "type",
+            "x",
+            "y",
+            "width",
+            "height",
+            "text",
+            "roughness",
+        ] {
+            match shape.get(key) {
+                Some(Value::Number(n)) => {
+                    line[key] = json!(n.as_f64().map_or(0.0, f64::round));
+                }
+                Some(value) if !value.is_null() => line[key] = value.clone(),
+                _ => {}
+            }
+        }
+        for key in ["bindStart", "bindEnd"] {
+            if let Some(id) = shape.pointer(&format!("/{}/shapeId", key)) {
+                line[key] = id.clone();
+            }
+        }
+        out.push_str(&line.to_string());
+        out.push('\n');
+    }
+    out
+}
+
+fn summarize_canvas(canvas: &Value) -> String {
+    let shapes = shapes(canvas);
+    if shapes.is_empty() {
+        return "The canvas is empty. Say so, and offer to help start a diagram.".to_string();
+    }
+    let outline = outline::outline(&shapes, &groups(canvas));
+    format!(
+        "Summarize this Napkin board for me. It has {} shapes ({}).\n\n\
+         Its text, in reading order and nested under the frames and groups that hold it:\n\n{}\n\
+         Give a short overview of what the board is about, then the main points grouped \
+         the way the board groups them, then any open questions or loose ends you notice. \
+         Shapes without text (arrows, sketches) are not listed; use get_canvas if their \
+         layout matters.",
+        shapes.len(),
+        shape_counts(&shapes),
+        outline["text"].as_str().unwrap_or_default()
+    )
+}
+
+fn clean_up_sketch(canvas: &Value) -> String {
+    let shapes = shapes(canvas);
+    if shapes.is_empty() {
+        return "The canvas is empty, so there is no sketch to clean up. Say so.".to_string();
+    }
+    format!(
+        "Turn the rough sketch on this Napkin board into a clean diagram.\n\n\
+         The board has {} shapes, one per line (bindings name the shapes a line or arrow \
+         connects):\n\n{}\n\
+         1. Work out what the sketch means: which shapes are nodes, which text labels which \
+         node, and which arrows or lines connect them.\n\
+         2. Make nodes of the same role the same type and size, and snap them onto a common \
+         grid with even spacing (update_shape, or reorganize for larger boards).\n\
+         3. Replace freehand strokes that stand for connections with create_connection \
+         arrows between the nodes they join, and delete the strokes.\n\
+         4. Set roughness to 0 on everything for a clean look.\n\
+         Do all edits in as few batch_operations calls as possible, keep every piece of \
+         text, and finish by describing what you changed.",
+        shapes.len(),
+        compact_shapes(&shapes)
+    )
+}
+
+fn flowchart_from_description(canvas: &Value, description: &str, direction: &str) -> String {
+    let shapes = shapes(canvas);
+    let (x, y) = if shapes.is_empty() {
+        (0.0, 0.0)
+    } else {
+        let bounds = content_bounds(&shapes);
+        (bounds.max_x + PLACEMENT_GAP, bounds.min_y)
+    };
+    let flow = if direction == "left-right" {
+        "left to right, one column per step"
+    } else {
+        "top to bottom, one row per step"
+    };
+    format!(
+        "Create a flowchart on this Napkin board for the following process:\n\n{}\n\n\
+         Layout: flow {}, starting at x={:.0}, y={:.0} so it does not overlap the {} \
+         shapes already on the board.\n\
+         - Start and end: ellipse. Steps: rectangle. Decisions: diamond, with the \
+         question as its text.\n\
+         - Keep labels short; size shapes to fit (about 160x70, decisions 140x100) and \
+         leave 60 between shapes.\n\
+         - Create all shapes in one batch_operations call, then connect them with \
+         create_connection arrows in flow order; label decision branches (for example \
+         Yes / No) with the arrow's text.\n\
+         - Finish with set_viewport on the new flowchart and list the steps you drew.",
+        description,
+        flow,
+        x,
+        y,
+        shapes.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompts_are_listed_with_their_arguments() {
+        let list = list();
+        let prompts = list["prompts"].as_array().unwrap();
+        assert_eq!(prompts.len(), PROMPTS.len());
+        let flowchart = prompts
+            .iter()
+            .find(|p| p["name"] == "flowchart_from_description")
+            .unwrap();
+        assert_eq!(flowchart["arguments"][0]["name"], "description");
+        assert_eq!(flowchart["arguments"][0]["required"], true);
+    }
+
+    #[test]
+    fn summary_includes_the_outline() {
+        let canvas = json!({ "shapes": [
+            { "id": "a", "type": "sticky", "x": 0, "y": 0, "width": 100, "height": 60, "text": "Ship it" },
+            { "id": "b", "type": "arrow", "x": 0, "y": 100, "width": 50, "height": 0 },
+        ]});
+        let text = summarize_canvas(&canvas);
+        assert!(text.contains("2 shapes (1 sticky, 1 arrow)"), "{}", text);
+        assert!(text.contains("- Ship it"), "{}", text);
+    }
+
+    #[test]
+    fn flowcharts_are_placed_beside_existing_content() {
+        let canvas = json!({ "shapes": [
+            { "id": "a", "type": "rectangle", "x": 0, "y": 50, "width": 200, "height": 100 },
+        ]});
+        let text = flowchart_from_description(&canvas, "Order pizza", "left-right");
+        assert!(text.contains("Order pizza"));
+        assert!(text.contains("left to right"));
+        let bounds = content_bounds(&shapes(&canvas));
+        assert!(text.contains(&format!("x={:.0}", bounds.max_x + PLACEMENT_GAP)));
+    }
+
+    #[test]
+    fn compact_shapes_round_coordinates() {
+        let line = compact_shapes(&[json!({
+            "id": "l", "type": "line", "x": 1.4, "y": 2.6, "points": [[0, 0]],
+            "bindStart": { "shapeId": "a" }
+        })]);
+        assert_eq!(
+            line.trim(),
+            r#"{"bindStart":"a","id":"l","type":"line","x":1.0,"y":3.0}"#
+        );
+    }
+}
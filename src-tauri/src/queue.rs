@@ -0,0 +1,152 @@
+//! One-at-a-time edits per document.
+//!
+//! The webview answers bridged calls in the order they arrive, but a tool
+//! handled here can take several round trips (read the canvas, work out the
+//! change, apply it), and two such calls from different agents, or from one
+//! batch, would otherwise interleave. Every mutating tool call therefore
+//! waits its turn in a FIFO lane for the document it edits (the tab named by
+//! `tabId`, otherwise the agents' tab) and holds it until it finishes.
+//! Approval prompts are answered before a call joins the queue, so an open
+//! prompt does not hold up other edits. `get_mutation_queue` reports how
+//! many calls each lane holds.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as FairMutex, OwnedMutexGuard};
+
+use crate::api::SharedApiState;
+
+/// Lane for calls made before the webview has reported any tab.
+const NO_TAB: &str = "";
+
+#[derive(Default)]
+struct Lane {
+    /// Fair: waiters are served in the order they arrived.
+    turn: Arc<FairMutex<()>>,
+    /// Calls running or waiting.
+    depth: usize,
+}
+
+/// Queue depth of one document, for `get_mutation_queue`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueDepth {
+    pub tab_id: Option<String>,
+    pub depth: usize,
+}
+
+#[derive(Default)]
+pub struct MutationQueue {
+    lanes: Arc<Mutex<HashMap<String, Lane>>>,
+}
+
+/// A call's turn; the next call in the lane goes ahead once it is dropped.
+pub struct Turn {
+    lanes: Arc<Mutex<HashMap<String, Lane>>>,
+    document: String,
+    /// `None` while waiting, so a call abandoned in the queue still leaves it.
+    _guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        let mut lanes = self.lanes.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(lane) = lanes.get_mut(&self.document) {
+            lane.depth -= 1;
+            if lane.depth == 0 {
+                lanes.remove(&self.document);
+            }
+        }
+    }
+}
+
+impl MutationQueue {
+    /// Wait until every earlier call for `document` has finished.
+    pub async fn enter(&self, document: &str) -> Turn {
+        let turn = {
+            let mut lanes = self.lanes.lock().unwrap_or_else(|e| e.into_inner());
+            let lane = lanes.entry(document.to_string()).or_default();
+            lane.depth += 1;
+            Arc::clone(&lane.turn)
+        };
+        let mut entered = Turn {
+            lanes: Arc::clone(&self.lanes),
+            document: document.to_string(),
+            _guard: None,
+        };
+        entered._guard = Some(turn.lock_owned().await);
+        entered
+    }
+
+    /// Documents with calls running or waiting, busiest first.
+    pub fn depths(&self) -> Vec<QueueDepth> {
+        let lanes = self.lanes.lock().unwrap_or_else(|e| e.into_inner());
+        let mut depths: Vec<QueueDepth> = lanes
+            .iter()
+            .map(|(document, lane)| QueueDepth {
+                tab_id: (document != NO_TAB).then(|| document.clone()),
+                depth: lane.depth,
+            })
+            .collect();
+        depths.sort_by(|a, b| b.depth.cmp(&a.depth).then(a.tab_id.cmp(&b.tab_id)));
+        depths
+    }
+}
+
+/// Wait for the turn of a mutating call with `arguments`.
+pub async fn enter(state: &SharedApiState, arguments: &serde_json::Value) -> Turn {
+    let document = state.visibility.document_for(arguments);
+    state
+        .mutations
+        .enter(document.as_deref().unwrap_or(NO_TAB))
+        .await
+}
+
+// --- Tauri command ---
+
+/// Mutating tool calls running or waiting, per document.
+#[tauri::command]
+pub fn get_mutation_queue(state: tauri::State<'_, SharedApiState>) -> Vec<QueueDepth> {
+    state.mutations.depths()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn calls_for_a_document_run_in_arrival_order() {
+        let queue = Arc::new(MutationQueue::default());
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let first = queue.enter("t1").await;
+
+        let mut waiting = Vec::new();
+        for n in 0..3 {
+            let (queue, order) = (Arc::clone(&queue), Arc::clone(&order));
+            waiting.push(tokio::spawn(async move {
+                let _turn = queue.enter("t1").await;
+                order.lock().unwrap().push(n);
+            }));
+            // Let each call join the lane before the next
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // Another document is not held up
+        drop(queue.enter("t2").await);
+        assert_eq!(
+            queue.depths(),
+            [QueueDepth {
+                tab_id: Some("t1".to_string()),
+                depth: 4
+            }]
+        );
+
+        drop(first);
+        for call in waiting {
+            call.await.unwrap();
+        }
+        assert_eq!(*order.lock().unwrap(), [0, 1, 2]);
+        assert!(queue.depths().is_empty());
+    }
+}
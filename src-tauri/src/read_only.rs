@@ -0,0 +1,237 @@
+//! Read-only MCP mode.
+//!
+//! A switch (Edit → MCP Read-Only Mode, or Settings) that lets agents look
+//! but not touch: only `READ_ONLY_TOOLS` are listed and callable (plus
+//! `submit_job` for one of them), and every other call fails with a JSON-RPC error whose `data` says why, so a client
+//! can tell it apart from a broken tool. The viewport is read through
+//! `get_canvas`. The switch lasts until Napkin quits; flipping it announces
+//! `notifications/tools/list_changed` and `mcp-read-only-changed` to the
+//! webview.
+
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+use tauri::menu::CheckMenuItem;
+use tauri::{Emitter, Manager};
+
+use crate::api::{self, SharedApiState};
+use crate::profiles::ToolExposure;
+use crate::tool_filter;
+
+pub const MENU_ID: &str = "mcp_read_only";
+/// JSON-RPC error code for calls refused by read-only mode.
+pub const PERMISSION_DENIED: i64 = -32001;
+
+/// The tools agents keep in read-only mode. The `read-only` tool profile
+/// offers the same list.
+pub(crate) const READ_ONLY_TOOLS: &[&str] = &[
+    "get_canvas",
+    "list_shapes",
+    "get_shape",
+    "list_tabs",
+    "list_constraints",
+    "extract_outline",
+    "get_common_properties",
+    "get_shape_order",
+    "get_changes",
+    "get_shapes_in_region",
+    "get_shape_at_point",
+    "lint_canvas",
+    "export_png",
+    "screenshot_viewport",
+    "get_job_status",
+    "get_job_result",
+];
+
+/// The menu item, kept so its check mark follows the setting.
+struct MenuState(CheckMenuItem<tauri::Wry>);
+
+/// A job only reads when the tool it wraps does.
+const SUBMIT_JOB: &str = "submit_job";
+
+pub fn allows(tool: &str) -> bool {
+    READ_ONLY_TOOLS.contains(&tool)
+}
+
+/// Whether a call to `tool` with `arguments` only reads, classifying
+/// `submit_job` by its `tool` argument.
+pub fn allows_call(tool: &str, arguments: &Value, exposure: &ToolExposure) -> bool {
+    if tool != SUBMIT_JOB {
+        return allows(tool);
+    }
+    arguments
+        .get("tool")
+        .and_then(Value::as_str)
+        .map(|inner| exposure.unprefixed(inner, api::is_known_tool))
+        .is_some_and(allows)
+}
+
+/// `tools` (a `tools/list` array) without the tools read-only mode refuses.
+pub fn filter_tools(mut tools: Value) -> Value {
+    if let Some(list) = tools.as_array_mut() {
+        list.retain(|tool| {
+            tool.get("name")
+                .and_then(Value::as_str)
+                .is_some_and(|name| name == SUBMIT_JOB || allows(name))
+        });
+    }
+    tools
+}
+
+/// The error for a call to `tool` while read-only.
+pub fn permission_error(id: Option<Value>, tool: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {
+            "code": PERMISSION_DENIED,
+            "message": format!("Napkin is in read-only mode: {} is not allowed", tool),
+            "data": {
+                "reason": "readOnly",
+                "tool": tool,
+                "allowedTools": READ_ONLY_TOOLS,
+            },
+        },
+    })
+}
+
+pub fn is_enabled(state: &SharedApiState) -> bool {
+    state.read_only.load(Ordering::Relaxed)
+}
+
+/// Turn read-only mode on or off and tell clients, the menu and the webview.
+pub fn set(state: &SharedApiState, enabled: bool) {
+    let was = state.read_only.swap(enabled, Ordering::Relaxed);
+    if let Some(menu) = state.app_handle.try_state::<MenuState>() {
+        let _ = menu.0.set_checked(enabled);
+    }
+    if was != enabled {
+        log::info!("MCP read-only mode {}", if enabled { "on" } else { "off" });
+        let _ = state.canvas_events.send(tool_filter::list_changed());
+        let _ = state.app_handle.emit("mcp-read-only-changed", enabled);
+    }
+}
+
+/// The Edit menu's check item.
+pub fn menu_item(app: &tauri::App) -> tauri::Result<CheckMenuItem<tauri::Wry>> {
+    let item = CheckMenuItem::with_id(
+        app,
+        MENU_ID,
+        "MCP Read-Only Mode",
+        true,
+        false,
+        None::<&str>,
+    )?;
+    app.manage(MenuState(item.clone()));
+    Ok(item)
+}
+
+/// The menu item was clicked; it has already toggled its check mark.
+pub fn on_menu_click(app: &tauri::AppHandle) {
+    let Some(state) = app.try_state::<SharedApiState>() else {
+        return;
+    };
+    let enabled = match app.try_state::<MenuState>() {
+        Some(menu) => menu.0.is_checked().unwrap_or(!is_enabled(&state)),
+        None => !is_enabled(&state),
+    };
+    set(&state, enabled);
+}
+
+// --- Tauri commands ---
+
+#[tauri::command]
+pub fn get_mcp_read_only(state: tauri::State<'_, SharedApiState>) -> bool {
+    is_enabled(state.inner())
+}
+
+#[tauri::command]
+pub fn set_mcp_read_only(enabled: bool, state: tauri::State<'_, SharedApiState>) {
+    set(state.inner(), enabled);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_reads_are_listed() {
+        let tools = json!([
+            { "name": "get_canvas" },
+            { "name": "create_shape" },
+            { "name": "list_tabs" },
+            { "name": "set_viewport" },
+        ]);
+        assert_eq!(
+            filter_tools(tools),
+            json!([{ "name": "get_canvas" }, { "name": "list_tabs" }])
+        );
+        assert!(allows("get_shape"));
+        assert!(!allows("clear_canvas"));
+    }
+
+    #[test]
+    fn every_tool_is_classified() {
+        // View and session state: neither reads the board nor edits it
+        const VIEW_TOOLS: &[&str] = &[
+            "set_viewport",
+            "select_shapes",
+            "switch_tab",
+            "set_snap_settings",
+            "acquire_canvas_lock",
+            "release_canvas_lock",
+        ];
+        let tools = api::all_tools();
+        let names: Vec<&str> = tools
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect();
+        for name in &names {
+            let kinds = [
+                allows(name),
+                api::is_mutating_tool(name),
+                VIEW_TOOLS.contains(name),
+                *name == SUBMIT_JOB,
+            ];
+            assert_eq!(
+                kinds.iter().filter(|k| **k).count(),
+                1,
+                "{} must be exactly one of read-only, mutating, view or job",
+                name
+            );
+        }
+        for tool in READ_ONLY_TOOLS {
+            assert!(names.contains(tool), "{} is not a tool", tool);
+        }
+    }
+
+    #[test]
+    fn jobs_read_only_when_their_tool_does() {
+        let exposure = ToolExposure {
+            prefix: "napkin_".to_string(),
+            ..ToolExposure::default()
+        };
+        let job = |tool: &str| json!({ "tool": tool, "arguments": {} });
+        assert!(allows_call("submit_job", &job("export_png"), &exposure));
+        assert!(allows_call("submit_job", &job("napkin_lint_canvas"), &exposure));
+        assert!(!allows_call("submit_job", &job("reorganize"), &exposure));
+        assert!(!allows_call("submit_job", &job("submit_job"), &exposure));
+        assert!(!allows_call("submit_job", &json!({}), &exposure));
+        assert!(allows_call("get_job_result", &json!({}), &exposure));
+        assert_eq!(
+            filter_tools(json!([{ "name": "submit_job" }])),
+            json!([{ "name": "submit_job" }])
+        );
+    }
+
+    #[test]
+    fn refusals_carry_a_structured_reason() {
+        let err = permission_error(Some(json!(7)), "delete_shape");
+        assert_eq!(err["id"], 7);
+        assert_eq!(err["error"]["code"], PERMISSION_DENIED);
+        assert_eq!(err["error"]["data"]["reason"], "readOnly");
+        assert_eq!(err["error"]["data"]["tool"], "delete_shape");
+        assert_eq!(err["error"]["data"]["allowedTools"][0], "get_canvas");
+    }
+}
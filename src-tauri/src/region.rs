@@ -0,0 +1,298 @@
+//! Region queries for agents, answered from an R-tree per board.
+//!
+//! `get_shapes_in_region` is answered here instead of by having the webview
+//! scan every shape. A board is indexed the first time it is queried, from
+//! one read of the board, and then kept current from the shape changes the
+//! webview reports with each edit (`api_notify_change`); the index holds each
+//! shape as last reported, so a query needs no round trip. Unlike
+//! `spatial.rs`, which mirrors the tab on screen for viewport culling, any
+//! board an agent has queried stays indexed.
+
+use rstar::primitives::{GeomWithData, Rectangle};
+use rstar::{RTree, RTreeObject, AABB};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::api::SharedApiState;
+use crate::budget;
+use crate::canvas_events::{ChangeKind, ShapeChange};
+use crate::geometry::{shape_bounds, Bounds};
+use crate::resources;
+use crate::settings;
+
+type Entry = GeomWithData<Rectangle<[f64; 2]>, String>;
+
+fn entry(id: &str, bounds: &Bounds) -> Entry {
+    let rect = Rectangle::from_corners([bounds.min_x, bounds.min_y], [bounds.max_x, bounds.max_y]);
+    GeomWithData::new(rect, id.to_string())
+}
+
+/// One board's shapes and their bounds.
+#[derive(Default)]
+struct Board {
+    tree: RTree<Entry>,
+    /// Indexed shapes by id, as last reported.
+    shapes: HashMap<String, (Entry, Value)>,
+}
+
+impl Board {
+    fn load(shapes: &[Value]) -> Self {
+        let mut indexed = HashMap::new();
+        for shape in shapes {
+            let Some(id) = shape.get("id").and_then(Value::as_str) else {
+                continue;
+            };
+            if let Some(bounds) = shape_bounds(shape) {
+                indexed.insert(id.to_string(), (entry(id, &bounds), shape.clone()));
+            }
+        }
+        let tree = RTree::bulk_load(indexed.values().map(|(e, _)| e.clone()).collect());
+        Self {
+            tree,
+            shapes: indexed,
+        }
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some((old, _)) = self.shapes.remove(id) {
+            self.tree.remove(&old);
+        }
+    }
+
+    fn upsert(&mut self, id: &str, shape: &Value) {
+        self.remove(id);
+        if let Some(bounds) = shape_bounds(shape) {
+            let new = entry(id, &bounds);
+            self.tree.insert(new.clone());
+            self.shapes.insert(id.to_string(), (new, shape.clone()));
+        }
+    }
+
+    /// Shapes overlapping `area` (or inside it, with `inside`), top to
+    /// bottom and left to right.
+    fn query(&self, area: &Bounds, inside: bool) -> Vec<Value> {
+        let envelope = AABB::from_corners([area.min_x, area.min_y], [area.max_x, area.max_y]);
+        let mut found: Vec<&Entry> = if inside {
+            self.tree.locate_in_envelope(&envelope).collect()
+        } else {
+            self.tree
+                .locate_in_envelope_intersecting(&envelope)
+                .collect()
+        };
+        found.sort_by(|a, b| {
+            let (a_corner, b_corner) = (a.envelope().lower(), b.envelope().lower());
+            a_corner[1]
+                .total_cmp(&b_corner[1])
+                .then(a_corner[0].total_cmp(&b_corner[0]))
+                .then(a.data.cmp(&b.data))
+        });
+        found
+            .into_iter()
+            .filter_map(|e| self.shapes.get(&e.data).map(|(_, shape)| shape.clone()))
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub struct RegionIndex {
+    boards: HashMap<String, Board>,
+}
+
+impl RegionIndex {
+    /// Apply an edit reported for `tab_id`, if that board is indexed.
+    pub fn apply(&mut self, tab_id: &str, changes: &[ShapeChange]) {
+        let Some(board) = self.boards.get_mut(tab_id) else {
+            return;
+        };
+        for change in changes {
+            match (change.kind, &change.shape) {
+                (ChangeKind::Deleted, _) => board.remove(&change.shape_id),
+                (_, Some(shape)) => board.upsert(&change.shape_id, shape),
+                // Without the new state the board can't be trusted any more
+                (_, None) => {
+                    self.boards.remove(tab_id);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+pub fn region_tools() -> Vec<Value> {
+    vec![json!({
+        "name": "get_shapes_in_region",
+        "description": "The shapes overlapping a rectangle of the board (or entirely inside it, with fullyInside), top to bottom and left to right. Answered from a spatial index, so it stays fast on boards with thousands of shapes; prefer it to get_canvas or list_shapes when you only need one area.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "x": { "type": "number", "description": "Left edge, in canvas pixels" },
+                "y": { "type": "number", "description": "Top edge, in canvas pixels" },
+                "width": { "type": "number", "minimum": 0 },
+                "height": { "type": "number", "minimum": 0 },
+                "fullyInside": { "type": "boolean", "description": "Only shapes entirely inside the rectangle (default false)" },
+                "tabId": { "type": "string", "description": "Tab to read (default: the tab agents are working on)" }
+            },
+            "required": ["x", "y", "width", "height"],
+            "additionalProperties": false
+        }
+    })]
+}
+
+pub async fn call_region_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "get_shapes_in_region" => Some(get_shapes_in_region(state, arguments).await),
+        _ => None,
+    }
+}
+
+fn area(arguments: &Value) -> Result<Bounds, String> {
+    let number = |key: &str| {
+        arguments
+            .get(key)
+            .and_then(Value::as_f64)
+            .ok_or_else(|| format!("Missing required field: {}", key))
+    };
+    let (x, y, width, height) = (
+        number("x")?,
+        number("y")?,
+        number("width")?,
+        number("height")?,
+    );
+    if width < 0.0 || height < 0.0 {
+        return Err("width and height must not be negative".to_string());
+    }
+    Ok(Bounds {
+        min_x: x,
+        min_y: y,
+        max_x: x + width,
+        max_y: y + height,
+    })
+}
+
+async fn get_shapes_in_region(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let mut arguments = arguments.clone();
+    let settings = settings::current(&state.app_handle);
+    let request = budget::take(
+        "get_shapes_in_region",
+        &mut arguments,
+        &settings.response_budget,
+    )?;
+    let area = area(&arguments)?;
+    let inside = arguments
+        .get("fullyInside")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+
+    let document = match state.visibility.document_for(&arguments) {
+        Some(document) => document,
+        // The agents' tab is learned from the webview's first answer
+        None => {
+            resources::canvas(state, None).await?;
+            state
+                .visibility
+                .document_for(&arguments)
+                .ok_or("No board is open")?
+        }
+    };
+    if !state.regions.lock().await.boards.contains_key(&document) {
+        let canvas = resources::canvas(state, Some(&document)).await?;
+        let shapes = canvas
+            .get("shapes")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let board = Board::load(&shapes);
+        state
+            .regions
+            .lock()
+            .await
+            .boards
+            .insert(document.clone(), board);
+    }
+    let shapes = match state.regions.lock().await.boards.get(&document) {
+        Some(board) => board.query(&area, inside),
+        None => Vec::new(),
+    };
+    let result = json!({ "count": shapes.len(), "shapes": shapes });
+    match request {
+        Some(request) => budget::apply(result, &request),
+        None => Ok(result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(id: &str, x: f64, y: f64, w: f64, h: f64) -> Value {
+        json!({ "id": id, "type": "rectangle", "x": x, "y": y, "width": w, "height": h })
+    }
+
+    fn ids(shapes: Vec<Value>) -> Vec<String> {
+        shapes
+            .iter()
+            .map(|s| s["id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    fn change(kind: ChangeKind, shape: Value) -> ShapeChange {
+        ShapeChange {
+            kind,
+            shape_id: shape["id"].as_str().unwrap().to_string(),
+            shape: (kind != ChangeKind::Deleted).then_some(shape),
+        }
+    }
+
+    #[test]
+    fn boards_follow_reported_changes() {
+        let mut index = RegionIndex::default();
+        index.boards.insert(
+            "t1".to_string(),
+            Board::load(&[
+                rect("a", 0.0, 0.0, 10.0, 10.0),
+                rect("b", 50.0, 5.0, 100.0, 10.0),
+            ]),
+        );
+        let area = |x: f64, y: f64, w: f64, h: f64| Bounds {
+            min_x: x,
+            min_y: y,
+            max_x: x + w,
+            max_y: y + h,
+        };
+        let query = |index: &RegionIndex, inside| {
+            ids(index.boards["t1"].query(&area(0.0, 0.0, 100.0, 100.0), inside))
+        };
+        assert_eq!(query(&index, false), ["a", "b"]);
+        assert_eq!(query(&index, true), ["a"]);
+
+        index.apply(
+            "t1",
+            &[
+                change(ChangeKind::Updated, rect("a", 500.0, 500.0, 10.0, 10.0)),
+                change(ChangeKind::Created, rect("c", 20.0, 0.0, 10.0, 10.0)),
+                change(ChangeKind::Deleted, rect("b", 0.0, 0.0, 0.0, 0.0)),
+            ],
+        );
+        assert_eq!(query(&index, false), ["c"]);
+        assert_eq!(index.boards["t1"].tree.size(), 2);
+
+        // Edits to boards nobody queried are not indexed
+        index.apply(
+            "t2",
+            &[change(ChangeKind::Created, rect("d", 0.0, 0.0, 1.0, 1.0))],
+        );
+        assert!(!index.boards.contains_key("t2"));
+
+        let unknown = ShapeChange {
+            kind: ChangeKind::Updated,
+            shape_id: "c".to_string(),
+            shape: None,
+        };
+        index.apply("t1", &[unknown]);
+        assert!(!index.boards.contains_key("t1"));
+    }
+}
@@ -0,0 +1,737 @@
+//! Drawing shapes without the webview.
+//!
+//! `export.rs` already writes shapes as SVG markup; this module wraps that
+//! markup in a plain document (no script, no pan/zoom) and rasterizes it
+//! with resvg. That gives PNGs where there is no canvas to draw on: the
+//! `export_png` tool with `renderer: "native"`, the `render_png` export and
+//! `render_thumbnail` (`render_svg` returns the markup itself). The result follows the SVG export rather than the
+//! webview's hand-drawn look: strokes are clean and fills are flat.
+//!
+//! PNGs carry their resolution: 96 dpi at 1x, 192 at 2x and so on, so a
+//! larger export prints at the same size, unless a `dpi` is given. The
+//! menu's PNG export takes its scale, dpi and background from the
+//! `pngExport` setting rather than from the size of the webview's canvas.
+//!
+//! SVG text names a font family and relies on the viewer having it. With
+//! `outlineText` (on by default for the menu's SVG export, `svgExport` in
+//! settings) the document is run through usvg, which lays the text out
+//! with the fonts found here and writes every glyph as a path, so the
+//! export looks the same everywhere; the text is then no longer selectable.
+//!
+//! A selection is framed by its own bounding box plus the padding: the menu's
+//! Export Selection draws only the selected shapes (as PNG, SVG or PDF),
+//! while `export_png` with `includeOverlapping` also draws whatever else
+//! reaches into the frame, clipped at its edge.
+//!
+//! Image shapes are drawn only from `data:` URLs; a path or web address in
+//! `src` is left blank, so a render never reads files an agent names.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{self, fontdb, ImageHrefResolver};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::sync::{Arc, OnceLock};
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::export::{self, escape, fmt};
+use crate::export_hooks;
+use crate::geometry::{self, Bounds};
+use crate::model::Shape;
+use crate::pdf::{self, PdfExportOptions};
+use crate::settings;
+use crate::units;
+use crate::watermark::{self, Watermark};
+
+/// Browsers refuse canvases much larger than this, and so do we.
+const MAX_DIMENSION: u32 = 8192;
+/// The longest side of a thumbnail when none is given.
+const THUMBNAIL_SIDE: u32 = 256;
+const FONT_FAMILY: &str = "Arial";
+/// CSS pixels per inch, the resolution of a 1x export.
+const BASE_DPI: f64 = 96.0;
+const INCHES_PER_METER: f64 = 39.370_08;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RenderOptions {
+    /// Margin around the shapes, in canvas pixels.
+    pub padding: f64,
+    pub background_color: String,
+    /// Pixels per canvas unit.
+    pub scale: f64,
+    /// The longest side of the image; the scale is lowered to fit.
+    pub max_dimension: u32,
+    /// Resolution written into the PNG; 96 dpi times the scale when unset.
+    pub dpi: Option<u32>,
+    /// Leave the background clear instead of filling `background_color`.
+    pub transparent: bool,
+    /// Write SVG text as glyph outlines rather than `<text>`.
+    pub outline_text: bool,
+    pub watermark: Option<Watermark>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            padding: 40.0,
+            background_color: "#ffffff".to_string(),
+            scale: 1.0,
+            max_dimension: 4096,
+            dpi: None,
+            transparent: false,
+            outline_text: false,
+            watermark: None,
+        }
+    }
+}
+
+/// How the menu's PNG export renders (`pngExport` in settings).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PngExport {
+    /// 1, 2 or 4 in the dialog; anything from 0.5 to 4 is accepted.
+    pub scale: f64,
+    pub dpi: Option<u32>,
+    pub transparent: bool,
+}
+
+impl Default for PngExport {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            dpi: None,
+            transparent: false,
+        }
+    }
+}
+
+impl PngExport {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.5..=4.0).contains(&self.scale) {
+            return Err("PNG export scale must be between 0.5 and 4".to_string());
+        }
+        if self.dpi.is_some_and(|dpi| !(1..=2400).contains(&dpi)) {
+            return Err("PNG export dpi must be between 1 and 2400".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            scale: self.scale,
+            max_dimension: MAX_DIMENSION,
+            dpi: self.dpi,
+            transparent: self.transparent,
+            ..Default::default()
+        }
+    }
+}
+
+/// How the menu's SVG export writes text (`svgExport` in settings).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SvgExport {
+    pub outline_text: bool,
+}
+
+impl Default for SvgExport {
+    fn default() -> Self {
+        Self { outline_text: true }
+    }
+}
+
+/// An encoded PNG and its size in pixels.
+pub struct Png {
+    pub bytes: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The system fonts, loaded on first use.
+fn fonts() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+    Arc::clone(FONTS.get_or_init(|| {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        use_installed_generics(&mut db);
+        Arc::new(db)
+    }))
+}
+
+/// Point the generic families at fonts that are installed. fontdb assumes
+/// Microsoft's core fonts, and text in a family it can't find (including
+/// every family it doesn't know, which falls back to `serif`) is dropped.
+fn use_installed_generics(db: &mut fontdb::Database) {
+    let installed: HashSet<String> = db
+        .faces()
+        .flat_map(|face| face.families.iter().map(|(name, _)| name.clone()))
+        .collect();
+    let pick = |names: &[&str]| {
+        names
+            .iter()
+            .find(|name| installed.contains(**name))
+            .map(|name| name.to_string())
+    };
+    let sans = [
+        "Arial",
+        "Helvetica",
+        "Liberation Sans",
+        "DejaVu Sans",
+        "Noto Sans",
+    ];
+    let Some(any) = pick(&sans).or_else(|| installed.iter().min().cloned()) else {
+        return;
+    };
+    let serif = pick(&[
+        "Times New Roman",
+        "Liberation Serif",
+        "DejaVu Serif",
+        "Noto Serif",
+    ]);
+    let cursive = pick(&["Comic Sans MS", "Comic Neue"]);
+    let monospace = pick(&[
+        "Courier New",
+        "Liberation Mono",
+        "DejaVu Sans Mono",
+        "Noto Sans Mono",
+    ]);
+    db.set_sans_serif_family(any.clone());
+    db.set_serif_family(serif.unwrap_or_else(|| any.clone()));
+    db.set_cursive_family(cursive.unwrap_or_else(|| any.clone()));
+    db.set_fantasy_family(any.clone());
+    db.set_monospace_family(monospace.unwrap_or(any));
+}
+
+fn to_values(shapes: &[Shape]) -> Vec<Value> {
+    shapes
+        .iter()
+        .filter_map(|shape| serde_json::to_value(shape).ok())
+        .collect()
+}
+
+/// Shapes as read from the canvas, typed; any the model rejects are left out.
+pub fn typed(shapes: &[Value]) -> Vec<Shape> {
+    shapes
+        .iter()
+        .filter_map(|shape| serde_json::from_value(shape.clone()).ok())
+        .collect()
+}
+
+/// What a selection export draws: the shapes named in `ids` (or, with
+/// `overlapping`, every shape that reaches into their bounding box) and the
+/// area to frame, before padding.
+pub fn select(
+    shapes: &[Value],
+    ids: &[&str],
+    overlapping: bool,
+) -> Result<(Vec<Value>, Bounds), String> {
+    if ids.is_empty() {
+        return Err("Nothing is selected".to_string());
+    }
+    let missing: Vec<&str> = ids
+        .iter()
+        .copied()
+        .filter(|id| !shapes.iter().any(|s| s["id"] == *id))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Shape not found: {}", missing.join(", ")));
+    }
+    let is_selected = |shape: &Value| shape["id"].as_str().is_some_and(|id| ids.contains(&id));
+    let selected: Vec<Value> = shapes.iter().filter(|s| is_selected(s)).cloned().collect();
+    let bounds = export::content_bounds(&selected);
+    if !overlapping {
+        return Ok((selected, bounds));
+    }
+    let framed = shapes
+        .iter()
+        .filter(|s| {
+            is_selected(s) || geometry::shape_bounds(s).is_some_and(|b| b.intersects(&bounds))
+        })
+        .cloned()
+        .collect();
+    Ok((framed, bounds))
+}
+
+/// A standalone SVG of `shapes` framed on `bounds`, and its size in canvas
+/// units. Anything outside the frame is clipped.
+fn document(shapes: &[Value], bounds: Bounds, options: &RenderOptions) -> (String, f64, f64) {
+    let pad = options.padding.max(0.0);
+    let (x, y) = (bounds.min_x - pad, bounds.min_y - pad);
+    let (w, h) = (bounds.width() + pad * 2.0, bounds.height() + pad * 2.0);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">",
+        fmt(w),
+        fmt(h),
+        fmt(x),
+        fmt(y),
+        fmt(w),
+        fmt(h),
+    );
+    if !options.transparent {
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            fmt(x),
+            fmt(y),
+            fmt(w),
+            fmt(h),
+            escape(&options.background_color),
+        );
+    }
+    svg.push_str(&export::render_content(shapes));
+    if let Some(watermark) = &options.watermark {
+        svg.push_str(&watermark.svg(x, y, w, h));
+    }
+    svg.push_str("</svg>\n");
+    (svg, w, h)
+}
+
+/// `shapes` as a standalone SVG document.
+pub fn svg(shapes: &[Shape], options: &RenderOptions) -> Result<String, String> {
+    let shapes = to_values(shapes);
+    let (svg, _, _) = document(&shapes, export::content_bounds(&shapes), options);
+    finish_svg(svg, options)
+}
+
+/// `svg` as written out: with its text outlined when asked.
+fn finish_svg(svg: String, options: &RenderOptions) -> Result<String, String> {
+    if !options.outline_text {
+        return Ok(svg);
+    }
+    let tree = parse(&svg)?;
+    Ok(tree.to_string(&usvg::WriteOptions::default()))
+}
+
+/// `svg` as usvg reads it, with text laid out in the fonts found here.
+fn parse(svg: &str) -> Result<usvg::Tree, String> {
+    let mut options = usvg::Options {
+        font_family: FONT_FAMILY.to_string(),
+        fontdb: fonts(),
+        ..Default::default()
+    };
+    options.image_href_resolver = ImageHrefResolver {
+        resolve_string: Box::new(|_, _| None),
+        ..Default::default()
+    };
+    usvg::Tree::from_str(svg, &options).map_err(|e| format!("Failed to render the board: {}", e))
+}
+
+fn rasterize(shapes: &[Value], bounds: Bounds, options: &RenderOptions) -> Result<Png, String> {
+    let (svg, w, h) = document(shapes, bounds, options);
+    let limit = options.max_dimension.clamp(1, MAX_DIMENSION) as f64;
+    let scale = options.scale.max(0.01).min(limit / w).min(limit / h);
+    let width = ((w * scale).ceil() as u32).max(1);
+    let height = ((h * scale).ceil() as u32).max(1);
+
+    let tree = parse(&svg)?;
+    let mut pixmap = Pixmap::new(width, height).ok_or("Failed to allocate the image")?;
+    let size = tree.size();
+    resvg::render(
+        &tree,
+        Transform::from_scale(width as f32 / size.width(), height as f32 / size.height()),
+        &mut pixmap.as_mut(),
+    );
+    let bytes = pixmap
+        .encode_png()
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    let dpi = options
+        .dpi
+        .map(f64::from)
+        .unwrap_or((BASE_DPI * scale).round());
+    Ok(Png {
+        bytes: with_dpi(bytes, dpi),
+        width,
+        height,
+    })
+}
+
+/// `png` with a `pHYs` chunk saying it is `dpi` pixels per inch. The chunk
+/// goes straight after `IHDR`, which is always first and always 13 bytes.
+fn with_dpi(png: Vec<u8>, dpi: f64) -> Vec<u8> {
+    const AFTER_IHDR: usize = 8 + 4 + 4 + 13 + 4;
+    if png.len() < AFTER_IHDR || dpi <= 0.0 {
+        return png;
+    }
+    let per_meter = ((dpi * INCHES_PER_METER).round() as u32).to_be_bytes();
+    let mut chunk = b"pHYs".to_vec();
+    chunk.extend_from_slice(&per_meter);
+    chunk.extend_from_slice(&per_meter);
+    chunk.push(1); // the unit is the meter
+
+    let mut out = Vec::with_capacity(png.len() + 21);
+    out.extend_from_slice(&png[..AFTER_IHDR]);
+    out.extend_from_slice(&9u32.to_be_bytes());
+    out.extend_from_slice(&chunk);
+    out.extend_from_slice(&crc32fast::hash(&chunk).to_be_bytes());
+    out.extend_from_slice(&png[AFTER_IHDR..]);
+    out
+}
+
+/// `shapes` as a PNG.
+pub fn png(shapes: &[Shape], options: &RenderOptions) -> Result<Png, String> {
+    let shapes = to_values(shapes);
+    rasterize(&shapes, export::content_bounds(&shapes), options)
+}
+
+/// A selection (see [`select`]) as a PNG.
+pub fn selection_png(
+    shapes: &[Value],
+    ids: &[&str],
+    overlapping: bool,
+    options: &RenderOptions,
+) -> Result<Png, String> {
+    let (shapes, bounds) = select(&to_values(&typed(shapes)), ids, overlapping)?;
+    rasterize(&shapes, bounds, options)
+}
+
+/// A small PNG of `shapes`, at most `max_side` pixels either way.
+pub fn thumbnail(shapes: &[Shape], max_side: u32) -> Result<Png, String> {
+    png(
+        shapes,
+        &RenderOptions {
+            padding: 10.0,
+            scale: 1.0,
+            max_dimension: max_side,
+            ..Default::default()
+        },
+    )
+}
+
+// --- Tool ---
+
+/// `export_png` when it asks for the native renderer, or for a selection
+/// with `includeOverlapping` (the webview can't clip); `None` leaves the
+/// call to the webview.
+pub async fn call_render_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    let native = arguments.get("renderer").and_then(Value::as_str) == Some("native")
+        || arguments.get("includeOverlapping").and_then(Value::as_bool) == Some(true);
+    match tool_name {
+        "export_png" if native => Some(export_png(state, arguments).await),
+        _ => None,
+    }
+}
+
+async fn export_png(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let canvas = bridge_tool_call(state, "get_canvas", json!({})).await?;
+    if let Some(error) = canvas.get("error").and_then(Value::as_str) {
+        return Err(error.to_string());
+    }
+    let shapes: Vec<Value> = canvas
+        .get("shapes")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    png_tool_result(&shapes, arguments, watermark::resolve(&state.app_handle, None))
+}
+
+/// `export_png`'s result for `shapes`, drawn as `arguments` ask.
+pub(crate) fn png_tool_result(
+    shapes: &[Value],
+    arguments: &Value,
+    watermark: Option<Watermark>,
+) -> Result<Value, String> {
+    let ids: Option<Vec<&str>> = arguments
+        .get("shapeIds")
+        .and_then(Value::as_array)
+        .map(|ids| ids.iter().filter_map(Value::as_str).collect());
+    if ids.is_none() && shapes.is_empty() {
+        return Err("Nothing to render: the board is empty".to_string());
+    }
+
+    let defaults = RenderOptions::default();
+    let options = RenderOptions {
+        padding: arguments["padding"].as_f64().unwrap_or(defaults.padding),
+        background_color: arguments["backgroundColor"]
+            .as_str()
+            .unwrap_or(&defaults.background_color)
+            .to_string(),
+        scale: arguments["scale"].as_f64().unwrap_or(1.0).clamp(0.1, 4.0),
+        max_dimension: arguments["maxDimension"].as_u64().unwrap_or(2048).min(4096) as u32,
+        watermark,
+        ..defaults
+    };
+    let (image, shape_count) = match ids {
+        Some(ids) => {
+            let overlapping = arguments["includeOverlapping"].as_bool().unwrap_or(false);
+            (
+                selection_png(shapes, &ids, overlapping, &options)?,
+                ids.len(),
+            )
+        }
+        None => {
+            let shapes = typed(shapes);
+            (png(&shapes, &options)?, shapes.len())
+        }
+    };
+    Ok(json!({
+        "data": STANDARD.encode(&image.bytes),
+        "mimeType": "image/png",
+        "width": image.width,
+        "height": image.height,
+        "shapeCount": shape_count,
+    }))
+}
+
+// --- Tauri commands ---
+
+/// Write `shapes` as a PNG without going through the canvas; without
+/// `options` the `pngExport` setting decides the scale, dpi and background.
+#[tauri::command]
+pub fn render_png(
+    path: String,
+    shapes: Vec<Shape>,
+    options: Option<RenderOptions>,
+    preset: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    let mut options =
+        options.unwrap_or_else(|| settings::current(&app).png_export.render_options());
+    options.watermark = watermark::resolve(&app, options.watermark.take());
+    let image = png(&shapes, &options)?;
+    std::fs::write(&path, image.bytes).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    export_hooks::after_export(&app, path, preset.as_deref());
+    Ok(())
+}
+
+/// Write just the shapes in `shape_ids`, framed by their bounding box plus
+/// `padding` (40 canvas pixels by default), as a PNG, SVG or PDF according
+/// to the file's extension. PNGs follow the `pngExport` setting.
+#[tauri::command]
+pub async fn export_selection(
+    path: String,
+    shapes: Vec<Value>,
+    shape_ids: Vec<String>,
+    padding: Option<f64>,
+    preset: Option<String>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<(), String> {
+    let app = &state.app_handle;
+    let ids: Vec<&str> = shape_ids.iter().map(String::as_str).collect();
+    let current = settings::current(app);
+    let mut options = RenderOptions {
+        watermark: watermark::resolve(app, None),
+        outline_text: current.svg_export.outline_text,
+        ..current.png_export.render_options()
+    };
+    if let Some(padding) = padding {
+        options.padding = padding.max(0.0);
+    }
+    let extension = std::path::Path::new(&path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    let bytes = match extension.as_deref() {
+        Some("png") => selection_png(&shapes, &ids, false, &options)?.bytes,
+        Some("svg") => {
+            let (selected, bounds) = select(&to_values(&typed(&shapes)), &ids, false)?;
+            finish_svg(document(&selected, bounds, &options).0, &options)?.into_bytes()
+        }
+        Some("pdf") => {
+            let (selected, _) = select(&shapes, &ids, false)?;
+            let pdf_options = PdfExportOptions {
+                padding: options.padding,
+                watermark: options.watermark,
+                ..Default::default()
+            };
+            let scale = units::current_scale(state.inner()).await;
+            pdf::render_pdf(&selected, scale.as_ref(), &pdf_options)?
+        }
+        _ => return Err("Export Selection writes .png, .svg or .pdf files".to_string()),
+    };
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    export_hooks::after_export(app, path, preset.as_deref());
+    Ok(())
+}
+
+/// `shapes` as a standalone SVG document, for exporters that want markup.
+#[tauri::command]
+pub fn render_svg(
+    shapes: Vec<Shape>,
+    options: Option<RenderOptions>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let mut options = options.unwrap_or_default();
+    options.watermark = watermark::resolve(&app, options.watermark.take());
+    svg(&shapes, &options)
+}
+
+/// Write `shapes` as a vector SVG, its text outlined unless the
+/// `svgExport` setting says otherwise.
+#[tauri::command]
+pub fn export_svg(
+    path: String,
+    shapes: Vec<Shape>,
+    preset: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    let options = RenderOptions {
+        outline_text: settings::current(&app).svg_export.outline_text,
+        watermark: watermark::resolve(&app, None),
+        ..Default::default()
+    };
+    let document = svg(&shapes, &options)?;
+    std::fs::write(&path, document).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    export_hooks::after_export(&app, path, preset.as_deref());
+    Ok(())
+}
+
+/// A thumbnail of `shapes` as a PNG data URL.
+#[tauri::command]
+pub fn render_thumbnail(shapes: Vec<Shape>, max_side: Option<u32>) -> Result<String, String> {
+    let image = thumbnail(&shapes, max_side.unwrap_or(THUMBNAIL_SIDE))?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        STANDARD.encode(image.bytes)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board() -> Vec<Shape> {
+        typed(&[
+            json!({ "id": "a", "type": "rectangle", "x": 0, "y": 0, "width": 200, "height": 100,
+                "fillColor": "#ff0000", "strokeColor": "#ff0000" }),
+            json!({ "id": "b", "type": "ellipse", "x": 300, "y": 0, "width": 100, "height": 100 }),
+            json!({ "id": "c", "type": "image", "x": 0, "y": 200, "width": 50, "height": 50,
+                "src": "/etc/hosts" }),
+        ])
+    }
+
+    #[test]
+    fn shapes_are_rasterized_without_a_webview() {
+        let shapes = board();
+        assert_eq!(shapes.len(), 3);
+        let options = RenderOptions {
+            padding: 0.0,
+            ..Default::default()
+        };
+        let image = png(&shapes, &options).unwrap();
+        assert!(image.bytes.starts_with(b"\x89PNG"));
+        // The content bounds plus the stroke margin on each side
+        assert_eq!((image.width, image.height), (440, 290));
+
+        let pixmap = Pixmap::decode_png(&image.bytes).unwrap();
+        let inside = pixmap.pixel(120, 70).unwrap();
+        assert_eq!((inside.red(), inside.green(), inside.blue()), (255, 0, 0));
+        let outside = pixmap.pixel(430, 280).unwrap();
+        assert_eq!(
+            (outside.red(), outside.green(), outside.blue()),
+            (255, 255, 255)
+        );
+
+        let small = thumbnail(&shapes, 100).unwrap();
+        assert!(small.width <= 100 && small.height <= 100);
+        assert_eq!(small.width, 100);
+
+        let document = svg(&shapes, &options).unwrap();
+        assert!(document.starts_with("<svg"));
+        assert!(!document.contains("<script"));
+    }
+
+    #[test]
+    fn outlined_text_needs_no_fonts_to_view() {
+        let shapes = typed(&[json!({ "id": "t", "type": "text", "x": 0, "y": 0,
+            "text": "Napkin", "fontSize": 24, "fontFamily": "'Comic Sans MS', cursive" })]);
+        let plain = svg(&shapes, &RenderOptions::default()).unwrap();
+        assert!(plain.contains(">Napkin</tspan>"));
+
+        let options = RenderOptions {
+            outline_text: true,
+            ..Default::default()
+        };
+        let outlined = svg(&shapes, &options).unwrap();
+        assert!(!outlined.contains("<text") && !outlined.contains("Napkin"));
+        if fonts().faces().next().is_some() {
+            // The background, then one path per glyph run at least
+            assert!(outlined.matches("<path").count() > 1);
+        }
+    }
+
+    #[test]
+    fn a_selection_is_framed_by_its_own_bounds() {
+        let shapes = to_values(&board());
+        let options = RenderOptions {
+            padding: 0.0,
+            ..Default::default()
+        };
+        // The ellipse alone: 100 plus the stroke margin each side
+        let image = selection_png(&shapes, &["b"], false, &options).unwrap();
+        assert_eq!((image.width, image.height), (140, 140));
+
+        // Framed on the rectangle, a shape reaching into its margin is drawn
+        // (and clipped) while the ellipse, further out, is not
+        let mut shapes = shapes;
+        shapes.push(
+            json!({ "id": "d", "type": "rectangle", "x": 210, "y": 40, "width": 60, "height": 20 }),
+        );
+        let (only, _) = select(&shapes, &["a"], false).unwrap();
+        assert_eq!(only.len(), 1);
+        let (framed, bounds) = select(&shapes, &["a"], true).unwrap();
+        let ids: Vec<&str> = framed.iter().filter_map(|s| s["id"].as_str()).collect();
+        assert_eq!(ids, vec!["a", "d"]);
+        assert_eq!((bounds.width(), bounds.height()), (240.0, 140.0));
+
+        assert_eq!(
+            select(&shapes, &["a", "zz"], false).err().as_deref(),
+            Some("Shape not found: zz")
+        );
+        assert!(select(&shapes, &[], false).is_err());
+    }
+
+    /// The `pHYs` chunk's pixels per meter, if there is one.
+    fn pixels_per_meter(png: &[u8]) -> Option<u32> {
+        let at = png.windows(4).position(|w| w == b"pHYs")?;
+        Some(u32::from_be_bytes(png[at + 4..at + 8].try_into().unwrap()))
+    }
+
+    #[test]
+    fn exports_carry_their_resolution_and_can_be_transparent() {
+        let shapes = board();
+        let settings = PngExport {
+            scale: 2.0,
+            transparent: true,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+        let image = png(&shapes, &settings.render_options()).unwrap();
+        assert_eq!(image.width, 2 * 520);
+        // 192 dpi
+        assert_eq!(pixels_per_meter(&image.bytes), Some(7559));
+        let pixmap = Pixmap::decode_png(&image.bytes).unwrap();
+        assert_eq!(pixmap.pixel(0, 0).unwrap().alpha(), 0);
+
+        let printed = RenderOptions {
+            dpi: Some(300),
+            ..Default::default()
+        };
+        let image = png(&shapes, &printed).unwrap();
+        assert_eq!(pixels_per_meter(&image.bytes), Some(11811));
+        let pixmap = Pixmap::decode_png(&image.bytes).unwrap();
+        assert_eq!(pixmap.pixel(0, 0).unwrap().alpha(), 255);
+
+        for bad in [
+            PngExport {
+                scale: 8.0,
+                ..Default::default()
+            },
+            PngExport {
+                dpi: Some(0),
+                ..Default::default()
+            },
+        ] {
+            assert!(bad.validate().is_err());
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! A log of every request the server answers.
+//!
+//! Where the audit log records what agents did to the canvas, this records
+//! how the server behaved: each request's HTTP method and path, the
+//! JSON-RPC method and tool it carried, how long it took and how it ended.
+//! Lines go to `logs/api.jsonl` in the app data directory in every build
+//! (the `tauri_plugin_log` output only exists in debug builds). When the
+//! file passes [`MAX_FILE_BYTES`] it is rotated to `api.1.jsonl`, and so on
+//! up to [`KEPT_FILES`] old files; older ones are deleted.
+//!
+//! The middleware sits inside MessagePack decoding, so it always sees JSON,
+//! and inside the body limit, so buffering a request to read it is bounded.
+
+use axum::body::{Body, HttpBody};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap};
+use axum::middleware::Next;
+use axum::response::Response;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::Manager;
+
+use crate::api::SharedApiState;
+use crate::embed;
+
+const LOG_DIR: &str = "logs";
+const LOG_NAME: &str = "api";
+/// The current file is rotated once it grows past this.
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+/// Rotated files kept besides the current one.
+const KEPT_FILES: usize = 3;
+/// Entries `get_api_logs` returns when no limit is given.
+const DEFAULT_LIMIT: usize = 500;
+/// JSON responses larger than this are not read for an error.
+const MAX_INSPECTED_BYTES: u64 = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Ok,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestEntry {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub method: String,
+    pub path: String,
+    /// The JSON-RPC method, or `batch` for several messages at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpc_method: Option<String>,
+    /// The tool a `tools/call` named (comma-separated for a batch).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    /// Until the response headers were ready; a stream may run on.
+    pub duration_ms: u64,
+    pub status: u16,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The JSON-RPC method and tool of a request body, if it is JSON-RPC.
+fn describe(body: &Value) -> (Option<String>, Option<String>) {
+    let tool = |message: &Value| {
+        (message["method"] == "tools/call")
+            .then(|| message["params"]["name"].as_str().map(str::to_string))
+            .flatten()
+    };
+    match body {
+        Value::Array(batch) => {
+            let tools: Vec<String> = batch.iter().filter_map(tool).collect();
+            (
+                Some("batch".to_string()),
+                (!tools.is_empty()).then(|| tools.join(",")),
+            )
+        }
+        message => (
+            message["method"].as_str().map(str::to_string),
+            tool(message),
+        ),
+    }
+}
+
+/// The error a response body reports: a JSON-RPC error, a failed tool
+/// result, or the REST API's `{ "error" }`.
+fn reported_error(body: &Value) -> Option<String> {
+    let messages = match body {
+        Value::Array(batch) => batch.iter().collect(),
+        message => vec![message],
+    };
+    messages.into_iter().find_map(|message| {
+        if let Some(error) = message.get("error") {
+            return Some(
+                error["message"]
+                    .as_str()
+                    .or(error.as_str())
+                    .unwrap_or("error")
+                    .to_string(),
+            );
+        }
+        (message["result"]["isError"] == true).then(|| {
+            message["result"]["content"][0]["text"]
+                .as_str()
+                .unwrap_or("Tool call failed")
+                .to_string()
+        })
+    })
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
+/// Serve `request` and describe how it went.
+pub async fn observe(request: Request, next: Next) -> (Response, RequestEntry) {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let (request, (rpc_method, tool)) = if is_json(request.headers()) {
+        let (parts, body) = request.into_parts();
+        // The body limit has already been applied
+        let bytes = axum::body::to_bytes(body, usize::MAX)
+            .await
+            .unwrap_or_default();
+        let described = serde_json::from_slice(&bytes)
+            .map(|body| describe(&body))
+            .unwrap_or_default();
+        (Request::from_parts(parts, Body::from(bytes)), described)
+    } else {
+        (request, (None, None))
+    };
+
+    let response = next.run(request).await;
+    let status = response.status();
+    let small = response
+        .body()
+        .size_hint()
+        .exact()
+        .is_some_and(|size| size <= MAX_INSPECTED_BYTES);
+    let (response, error) = if small && is_json(response.headers()) {
+        let (parts, body) = response.into_parts();
+        let bytes = axum::body::to_bytes(body, MAX_INSPECTED_BYTES as usize)
+            .await
+            .unwrap_or_default();
+        let error = serde_json::from_slice(&bytes)
+            .ok()
+            .and_then(|body| reported_error(&body));
+        (Response::from_parts(parts, Body::from(bytes)), error)
+    } else {
+        (response, None)
+    };
+    let error = error.or_else(|| {
+        (status.is_client_error() || status.is_server_error()).then(|| status.to_string())
+    });
+    let entry = RequestEntry {
+        timestamp: embed::now_secs(),
+        method,
+        path,
+        rpc_method,
+        tool,
+        duration_ms: started.elapsed().as_millis() as u64,
+        status: status.as_u16(),
+        outcome: if error.is_some() {
+            Outcome::Error
+        } else {
+            Outcome::Ok
+        },
+        error,
+    };
+    (response, entry)
+}
+
+/// Middleware for the app's router: log each request to `ApiLog`.
+pub async fn record(State(state): State<SharedApiState>, request: Request, next: Next) -> Response {
+    let (response, entry) = observe(request, next).await;
+    if let Some(log) = state.app_handle.try_state::<ApiLog>() {
+        if let Err(e) = log.append(&entry) {
+            log::warn!("{}", e);
+        }
+    }
+    response
+}
+
+/// The rotating log files, kept in the app data dir.
+pub struct ApiLog {
+    dir: Option<PathBuf>,
+    /// Serializes appends and rotation with reads.
+    file: Mutex<()>,
+}
+
+impl ApiLog {
+    pub fn load(app: &tauri::AppHandle) -> Self {
+        Self::at(app.path().app_data_dir().ok().map(|dir| dir.join(LOG_DIR)))
+    }
+
+    fn at(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            file: Mutex::new(()),
+        }
+    }
+
+    /// File `n`: the current one for 0, older ones after.
+    fn file_path(dir: &Path, n: usize) -> PathBuf {
+        match n {
+            0 => dir.join(format!("{}.jsonl", LOG_NAME)),
+            n => dir.join(format!("{}.{}.jsonl", LOG_NAME, n)),
+        }
+    }
+
+    fn rotate(dir: &Path) -> std::io::Result<()> {
+        for n in (0..KEPT_FILES).rev() {
+            let from = Self::file_path(dir, n);
+            if from.exists() {
+                std::fs::rename(&from, Self::file_path(dir, n + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn append(&self, entry: &RequestEntry) -> Result<(), String> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let _guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let failed = |e: std::io::Error| format!("Failed to write API log: {}", e);
+        std::fs::create_dir_all(dir).map_err(failed)?;
+        let path = Self::file_path(dir, 0);
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if size >= MAX_FILE_BYTES {
+            Self::rotate(dir).map_err(failed)?;
+        }
+        let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        line.push('\n');
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(failed)
+    }
+
+    /// The newest `limit` entries across the current and rotated files,
+    /// newest first.
+    pub fn read(&self, limit: usize) -> Result<Vec<RequestEntry>, String> {
+        let Some(dir) = &self.dir else {
+            return Ok(Vec::new());
+        };
+        let _guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries = Vec::new();
+        for n in 0..=KEPT_FILES {
+            let file = match std::fs::File::open(Self::file_path(dir, n)) {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("Failed to read API log: {}", e)),
+            };
+            let mut older: Vec<RequestEntry> = BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .filter_map(|line| serde_json::from_str(&line).ok())
+                .collect();
+            older.reverse();
+            entries.extend(older);
+            if entries.len() >= limit {
+                break;
+            }
+        }
+        entries.truncate(limit);
+        Ok(entries)
+    }
+}
+
+// --- Tauri commands ---
+
+/// The most recent requests to the server, newest first.
+#[tauri::command]
+pub fn get_api_logs(
+    limit: Option<usize>,
+    log: tauri::State<'_, ApiLog>,
+) -> Result<Vec<RequestEntry>, String> {
+    log.read(limit.unwrap_or(DEFAULT_LIMIT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use axum::{middleware, Json, Router};
+    use serde_json::json;
+    use std::sync::Arc;
+    use tokio::sync::Mutex as AsyncMutex;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn requests_are_described_with_their_tool_and_outcome() {
+        let seen = Arc::new(AsyncMutex::new(Vec::new()));
+        let app = Router::new()
+            .route(
+                "/mcp",
+                post(|Json(body): Json<Value>| async move {
+                    if body["params"]["name"] == "get_shape" {
+                        Json(json!({ "jsonrpc": "2.0", "id": 1, "result": {
+                            "isError": true,
+                            "content": [{ "type": "text", "text": "No such shape" }],
+                        } }))
+                    } else {
+                        Json(json!({ "jsonrpc": "2.0", "id": 1, "result": {} }))
+                    }
+                }),
+            )
+            .layer(middleware::from_fn({
+                let seen = Arc::clone(&seen);
+                move |request, next| {
+                    let seen = Arc::clone(&seen);
+                    async move {
+                        let (response, entry) = observe(request, next).await;
+                        seen.lock().await.push(entry);
+                        response
+                    }
+                }
+            }));
+        let call = |body: Value| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder()
+                    .method("POST")
+                    .uri("/mcp")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap();
+                app.oneshot(request).await.unwrap().status()
+            }
+        };
+        let tool_call = |name: &str| {
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+                "params": { "name": name, "arguments": {} } })
+        };
+
+        assert_eq!(call(tool_call("list_shapes")).await, StatusCode::OK);
+        assert_eq!(call(tool_call("get_shape")).await, StatusCode::OK);
+        call(json!([tool_call("a"), { "method": "ping" }, tool_call("b")])).await;
+        let seen = seen.lock().await;
+        assert_eq!(seen[0].method, "POST");
+        assert_eq!(seen[0].rpc_method.as_deref(), Some("tools/call"));
+        assert_eq!(seen[0].tool.as_deref(), Some("list_shapes"));
+        assert_eq!(seen[0].outcome, Outcome::Ok);
+        assert_eq!(seen[1].outcome, Outcome::Error);
+        assert_eq!(seen[1].error.as_deref(), Some("No such shape"));
+        assert_eq!(seen[2].rpc_method.as_deref(), Some("batch"));
+        assert_eq!(seen[2].tool.as_deref(), Some("a,b"));
+    }
+
+    #[test]
+    fn the_log_rotates_and_reads_across_files() {
+        let dir = std::env::temp_dir().join(format!("napkin-api-log-{}", uuid::Uuid::new_v4()));
+        let log = ApiLog::at(Some(dir.clone()));
+        assert!(log.read(10).unwrap().is_empty());
+        let entry = |path: &str| RequestEntry {
+            timestamp: 0,
+            method: "GET".to_string(),
+            path: path.to_string(),
+            rpc_method: None,
+            tool: None,
+            duration_ms: 1,
+            status: 200,
+            outcome: Outcome::Ok,
+            error: None,
+        };
+
+        log.append(&entry("/first")).unwrap();
+        // Fill the current file so the next append rotates it
+        let current = ApiLog::file_path(&dir, 0);
+        let file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&current)
+            .unwrap();
+        file.set_len(MAX_FILE_BYTES).unwrap();
+        log.append(&entry("/second")).unwrap();
+        assert!(ApiLog::file_path(&dir, 1).exists());
+
+        let paths: Vec<String> = log.read(10).unwrap().into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["/second", "/first"]);
+        assert_eq!(log.read(1).unwrap().len(), 1);
+
+        for _ in 0..KEPT_FILES + 2 {
+            std::fs::OpenOptions::new()
+                .append(true)
+                .open(&current)
+                .unwrap()
+                .set_len(MAX_FILE_BYTES)
+                .unwrap();
+            log.append(&entry("/more")).unwrap();
+        }
+        assert!(ApiLog::file_path(&dir, KEPT_FILES).exists());
+        assert!(!ApiLog::file_path(&dir, KEPT_FILES + 1).exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
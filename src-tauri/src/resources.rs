@@ -0,0 +1,291 @@
+//! MCP resources: canvas documents as `napkin://` URIs.
+//!
+//! - `napkin://canvas` — the tab agents are working on (as `get_canvas`)
+//! - `napkin://tabs/<id>` — any open tab
+//! - `napkin://files/<percent-encoded path>` — a recently opened or saved file
+//!
+//! Canvas and tabs are read through the webview bridge; files are read from
+//! disk. Only paths in the recent list can be read, so a client cannot use a
+//! file URI to read arbitrary files.
+//!
+//! Boards hidden from agents are left out (see `visibility.rs`).
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::docstore;
+use crate::visibility;
+
+const CANVAS_URI: &str = "napkin://canvas";
+const TAB_PREFIX: &str = "napkin://tabs/";
+const FILE_PREFIX: &str = "napkin://files/";
+const RECENT_FILE: &str = "recent-files.json";
+const MAX_RECENT_FILES: usize = 10;
+const MIME_TYPE: &str = "application/json";
+
+#[derive(Debug, PartialEq)]
+enum ResourceUri {
+    Canvas,
+    Tab(String),
+    File(String),
+}
+
+fn parse_uri(uri: &str) -> Option<ResourceUri> {
+    if uri == CANVAS_URI {
+        return Some(ResourceUri::Canvas);
+    }
+    if let Some(id) = uri.strip_prefix(TAB_PREFIX).filter(|id| !id.is_empty()) {
+        return Some(ResourceUri::Tab(id.to_string()));
+    }
+    let encoded = uri.strip_prefix(FILE_PREFIX).filter(|p| !p.is_empty())?;
+    let path = percent_decode_str(encoded).decode_utf8().ok()?;
+    Some(ResourceUri::File(path.into_owned()))
+}
+
+/// Whether `uri` names a resource this server serves.
+pub fn is_resource_uri(uri: &str) -> bool {
+    parse_uri(uri).is_some()
+}
+
+/// Whether `uri` is a tab hidden from agents, or the canvas while agents
+/// are on one.
+pub fn is_hidden(state: &SharedApiState, uri: &str) -> bool {
+    match parse_uri(uri) {
+        Some(ResourceUri::Canvas) => state.visibility.target_hidden(),
+        Some(ResourceUri::Tab(id)) => state.visibility.is_hidden(&id),
+        _ => false,
+    }
+}
+
+/// The resources an edit to tab `tab_id` changes.
+pub fn tab_uris(tab_id: &str, mcp_active: bool) -> Vec<String> {
+    let mut uris = vec![format!("{}{}", TAB_PREFIX, tab_id)];
+    if mcp_active {
+        uris.push(CANVAS_URI.to_string());
+    }
+    uris
+}
+
+pub fn file_uri(path: &str) -> String {
+    format!(
+        "{}{}",
+        FILE_PREFIX,
+        utf8_percent_encode(path, NON_ALPHANUMERIC)
+    )
+}
+
+// --- Recent files ---
+
+/// Files recently opened or saved, newest first, kept in the app config dir.
+pub struct RecentFiles {
+    path: Option<PathBuf>,
+    files: Mutex<Vec<String>>,
+}
+
+impl RecentFiles {
+    pub fn load(app: &tauri::AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_config_dir()
+            .ok()
+            .map(|dir| dir.join(RECENT_FILE));
+        let files = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            files: Mutex::new(files),
+        }
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn remember(&self, file: &str) {
+        let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        if !push_recent(&mut files, file) {
+            return;
+        }
+        let Some(path) = &self.path else {
+            return;
+        };
+        let saved = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, json!(*files).to_string()));
+        if let Err(e) = saved {
+            log::warn!("Failed to save recent files: {}", e);
+        }
+    }
+}
+
+/// Move `file` to the front of `files`; `false` if it already was there.
+fn push_recent(files: &mut Vec<String>, file: &str) -> bool {
+    if files.first().map(String::as_str) == Some(file) {
+        return false;
+    }
+    files.retain(|f| f != file);
+    files.insert(0, file.to_string());
+    files.truncate(MAX_RECENT_FILES);
+    true
+}
+
+/// Record a file the user opened or saved.
+pub fn remember_file(app: &tauri::AppHandle, path: &str) {
+    if let Some(recent) = app.try_state::<RecentFiles>() {
+        recent.remember(path);
+    }
+}
+
+fn recent_files(state: &SharedApiState) -> Vec<String> {
+    state
+        .app_handle
+        .try_state::<RecentFiles>()
+        .map(|r| r.list())
+        .unwrap_or_default()
+}
+
+/// The serialised canvas of tab `tab_id`, or of the tab agents are working
+/// on, read through the webview.
+pub async fn canvas(state: &SharedApiState, tab_id: Option<&str>) -> Result<Value, String> {
+    let canvas = match tab_id {
+        Some(tab_id) => bridge_tool_call(state, "get_tab", json!({ "tabId": tab_id })).await?,
+        None => bridge_tool_call(state, "get_canvas", json!({})).await?,
+    };
+    match canvas.get("error").and_then(Value::as_str) {
+        Some(error) => Err(error.to_string()),
+        None => Ok(canvas),
+    }
+}
+
+// --- MCP methods ---
+
+/// Result of `resources/list`.
+pub async fn list(state: &SharedApiState) -> Value {
+    let mut resources = Vec::new();
+    if !state.visibility.target_hidden() {
+        resources.push(json!({
+            "uri": CANVAS_URI,
+            "name": "Current canvas",
+            "description": "Shapes, groups and viewport of the tab agents are working on",
+            "mimeType": MIME_TYPE,
+        }));
+    }
+
+    match bridge_tool_call(state, "list_tabs", json!({})).await {
+        Ok(tabs) => {
+            let tabs = tabs.get("tabs").and_then(Value::as_array).cloned();
+            for tab in tabs.unwrap_or_default() {
+                let Some(id) = tab.get("id").and_then(Value::as_str) else {
+                    continue;
+                };
+                if state.visibility.is_hidden(id) {
+                    continue;
+                }
+                let title = tab.get("title").and_then(Value::as_str).unwrap_or(id);
+                resources.push(json!({
+                    "uri": format!("{}{}", TAB_PREFIX, id),
+                    "name": format!("Tab: {}", title),
+                    "mimeType": MIME_TYPE,
+                }));
+            }
+        }
+        Err(e) => log::warn!("resources/list could not list tabs: {}", e),
+    }
+
+    for path in recent_files(state) {
+        let name = Path::new(&path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.clone());
+        resources.push(json!({
+            "uri": file_uri(&path),
+            "name": name,
+            "description": format!("Recent file {}", path),
+            "mimeType": MIME_TYPE,
+        }));
+    }
+
+    json!({ "resources": resources })
+}
+
+/// Result of `resources/read`; `Ok(None)` when there is no such resource.
+pub async fn read(state: &SharedApiState, uri: &str) -> Result<Option<Value>, String> {
+    let text = match parse_uri(uri) {
+        None => return Ok(None),
+        Some(ResourceUri::Canvas) => {
+            let canvas = bridge_tool_call(state, "get_canvas", json!({})).await?;
+            serde_json::to_string_pretty(&canvas).map_err(|e| e.to_string())?
+        }
+        Some(ResourceUri::Tab(id)) => {
+            let canvas = bridge_tool_call(state, "get_tab", json!({ "tabId": id })).await?;
+            if canvas.get("error").is_some() {
+                return Ok(None);
+            }
+            serde_json::to_string_pretty(&canvas).map_err(|e| e.to_string())?
+        }
+        Some(ResourceUri::File(path)) => {
+            if !recent_files(state).contains(&path) {
+                return Ok(None);
+            }
+            let text =
+                tauri::async_runtime::spawn_blocking(move || docstore::read_path(Path::new(&path)))
+                    .await
+                    .map_err(|e| e.to_string())??;
+            match visibility::redact_file(&text) {
+                Some(text) => text,
+                None => return Ok(None),
+            }
+        }
+    };
+    Ok(Some(json!({
+        "contents": [{ "uri": uri, "mimeType": MIME_TYPE, "text": text }]
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uris_are_parsed() {
+        assert_eq!(parse_uri("napkin://canvas"), Some(ResourceUri::Canvas));
+        assert_eq!(
+            parse_uri("napkin://tabs/tab_1"),
+            Some(ResourceUri::Tab("tab_1".to_string()))
+        );
+        assert_eq!(parse_uri("napkin://tabs/"), None);
+        assert_eq!(parse_uri("file:///etc/passwd"), None);
+    }
+
+    #[test]
+    fn file_uris_round_trip() {
+        let path = "/Users/me/My Drawings/plan #2.napkin";
+        let uri = file_uri(path);
+        assert!(!uri.contains(' '));
+        assert_eq!(parse_uri(&uri), Some(ResourceUri::File(path.to_string())));
+    }
+
+    #[test]
+    fn recent_files_are_newest_first_and_bounded() {
+        let mut files = Vec::new();
+        assert!(push_recent(&mut files, "a"));
+        assert!(push_recent(&mut files, "b"));
+        assert!(!push_recent(&mut files, "b"));
+        assert!(push_recent(&mut files, "a"));
+        assert_eq!(files, ["a", "b"]);
+
+        for i in 0..MAX_RECENT_FILES {
+            push_recent(&mut files, &i.to_string());
+        }
+        assert_eq!(files.len(), MAX_RECENT_FILES);
+        assert_eq!(files[0], (MAX_RECENT_FILES - 1).to_string());
+    }
+}
@@ -0,0 +1,399 @@
+//! A small REST API over the MCP tools, for integrations that do not speak
+//! JSON-RPC.
+//!
+//! | Route                         | Tool           | Success            |
+//! |-------------------------------|----------------|--------------------|
+//! | `GET /api/v1/canvas`          | `get_canvas`   | 200                |
+//! | `GET /api/v1/shapes`          | `list_shapes`  | 200                |
+//! | `POST /api/v1/shapes`         | `create_shape` | 201 + `Location`   |
+//! | `GET /api/v1/shapes/{id}`     | `get_shape`    | 200                |
+//! | `PATCH /api/v1/shapes/{id}`   | `update_shape` | 200                |
+//! | `DELETE /api/v1/shapes/{id}`  | `delete_shape` | 204                |
+//! | `GET /api/v1/tabs`            | `list_tabs`    | 200                |
+//! | `POST /api/v1/tabs`           | `create_tab`   | 201                |
+//!
+//! Each request is run as an MCP `tools/call` from a client named
+//! `REST API`, so the bearer token, read-only mode, hidden boards, tool
+//! profiles, approvals, the canvas lock and the audit log all apply as they
+//! do to agents. Failures come back as `{ "error": ..., "code"?, "reason"? }`
+//! (`code` as in `tool_errors`) with a status that follows the code. Calls
+//! refused before the tool runs follow the JSON-RPC error code: 403 for a
+//! policy denial (`PERMISSION_DENIED`: read-only mode, hidden boards), 404
+//! for a tool this version does not implement, 400 for other invalid params
+//! (including a body that is not an object, or a tool outside the client's
+//! profile or disabled) and 500 for anything else. Tool failures follow their
+//! `code`: 403 for a declined approval, 404 for a missing shape, 409 when the
+//! canvas is locked, 504 when the window does not answer, and 422 for
+//! anything else the tool rejects.
+
+use axum::{
+    extract::{Path, Query, State as AxumState},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use crate::api::{self, SharedApiState, INVALID_PARAMS, TOOL_NOT_IMPLEMENTED};
+use crate::read_only::PERMISSION_DENIED;
+use crate::session::McpClient;
+use crate::tool_errors::ErrorCode;
+
+const PREFIX: &str = "/api/v1";
+/// Name REST requests are audited and attributed under.
+const CLIENT_NAME: &str = "REST API";
+
+/// One route of the API, for the OpenAPI document.
+pub(crate) struct Operation {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub tool: &'static str,
+    /// Status of a successful call.
+    pub status: u16,
+}
+
+/// Every route `build_router` registers under [`PREFIX`].
+#[rustfmt::skip]
+pub(crate) const OPERATIONS: &[Operation] = &[
+    Operation { method: "get", path: "/api/v1/canvas", tool: "get_canvas", status: 200 },
+    Operation { method: "get", path: "/api/v1/shapes", tool: "list_shapes", status: 200 },
+    Operation { method: "post", path: "/api/v1/shapes", tool: "create_shape", status: 201 },
+    Operation { method: "get", path: "/api/v1/shapes/{id}", tool: "get_shape", status: 200 },
+    Operation { method: "patch", path: "/api/v1/shapes/{id}", tool: "update_shape", status: 200 },
+    Operation { method: "delete", path: "/api/v1/shapes/{id}", tool: "delete_shape", status: 204 },
+    Operation { method: "get", path: "/api/v1/tabs", tool: "list_tabs", status: 200 },
+    Operation { method: "post", path: "/api/v1/tabs", tool: "create_tab", status: 201 },
+];
+
+/// Status for a failed tool call's code.
+fn error_status(code: ErrorCode) -> StatusCode {
+    match code {
+        ErrorCode::ShapeNotFound => StatusCode::NOT_FOUND,
+        ErrorCode::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        ErrorCode::PermissionDenied => StatusCode::FORBIDDEN,
+        ErrorCode::CanvasLocked => StatusCode::CONFLICT,
+        ErrorCode::InvalidArgument | ErrorCode::Cancelled | ErrorCode::Internal => {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+    }
+}
+
+/// Status for a JSON-RPC error, returned before the tool ran.
+fn rpc_error_status(code: Option<i64>, message: &str) -> StatusCode {
+    match code {
+        Some(PERMISSION_DENIED) => StatusCode::FORBIDDEN,
+        Some(INVALID_PARAMS) if message.starts_with(TOOL_NOT_IMPLEMENTED) => StatusCode::NOT_FOUND,
+        Some(INVALID_PARAMS) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// A failed request: its status and `{ "error", "code"?, "reason"? }` body.
+#[derive(Debug)]
+struct Failure {
+    status: StatusCode,
+    body: Value,
+}
+
+impl Failure {
+    fn new(status: StatusCode, message: &str, reason: Option<&Value>) -> Self {
+        let mut body = json!({ "error": message });
+        if let Some(reason) = reason {
+            body["reason"] = reason.clone();
+        }
+        Self { status, body }
+    }
+}
+
+impl IntoResponse for Failure {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body)).into_response()
+    }
+}
+
+/// Turn a `tools/call` reply into the tool's result, or an error response.
+fn tool_result(reply: &Value) -> Result<Value, Failure> {
+    if let Some(error) = reply.get("error") {
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("Request refused");
+        let code = error.get("code").and_then(Value::as_i64);
+        return Err(Failure::new(
+            rpc_error_status(code, message),
+            message,
+            error.pointer("/data/reason"),
+        ));
+    }
+    let result = reply.get("result").cloned().unwrap_or(Value::Null);
+    let text = result
+        .pointer("/content/0/text")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    if result.get("isError").and_then(Value::as_bool) == Some(true) {
+        let code = result
+            .pointer("/structuredContent/code")
+            .and_then(|code| serde_json::from_value(code.clone()).ok())
+            .unwrap_or_else(|| ErrorCode::classify(text));
+        let mut failure = Failure::new(error_status(code), text, None);
+        failure.body["code"] = json!(code);
+        return Err(failure);
+    }
+    Ok(match result.get("structuredContent") {
+        Some(structured) => structured.clone(),
+        None => serde_json::from_str(text).unwrap_or_else(|_| json!(text)),
+    })
+}
+
+async fn call(state: &SharedApiState, tool: &str, arguments: Value) -> Result<Value, Failure> {
+    let client = McpClient::named(CLIENT_NAME);
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": { "name": tool, "arguments": arguments },
+    });
+    let reply = api::handle_mcp_message(state, request, &client)
+        .await
+        .unwrap_or(Value::Null);
+    tool_result(&reply)
+}
+
+/// Query parameters as arguments to `tool`, with numbers, booleans and
+/// objects parsed where its schema expects them.
+fn query_arguments(tool: &str, query: HashMap<String, String>) -> Value {
+    let tools = api::all_tools();
+    let properties = tools
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|t| t.get("name").and_then(Value::as_str) == Some(tool))
+        .and_then(|t| t.pointer("/inputSchema/properties"));
+    let arguments = query
+        .into_iter()
+        .map(|(key, text)| {
+            let kind = properties
+                .and_then(|p| p.pointer(&format!("/{}/type", key)))
+                .and_then(Value::as_str);
+            let value = match kind {
+                Some("integer" | "number" | "boolean" | "object") => {
+                    serde_json::from_str(&text).unwrap_or(Value::String(text))
+                }
+                _ => Value::String(text),
+            };
+            (key, value)
+        })
+        .collect();
+    Value::Object(arguments)
+}
+
+/// The JSON object body of a request; a 400 response otherwise.
+fn object(body: Option<Json<Value>>) -> Result<Map<String, Value>, Failure> {
+    match body {
+        Some(Json(Value::Object(fields))) => Ok(fields),
+        None => Ok(Map::new()),
+        Some(_) => Err(Failure::new(
+            StatusCode::BAD_REQUEST,
+            "The request body must be a JSON object",
+            None,
+        )),
+    }
+}
+
+fn respond(result: Result<Value, Failure>, status: StatusCode) -> Response {
+    match result {
+        Ok(value) => (status, Json(value)).into_response(),
+        Err(failure) => failure.into_response(),
+    }
+}
+
+pub async fn get_canvas(
+    AxumState(state): AxumState<SharedApiState>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let arguments = query_arguments("get_canvas", query);
+    respond(call(&state, "get_canvas", arguments).await, StatusCode::OK)
+}
+
+pub async fn list_shapes(
+    AxumState(state): AxumState<SharedApiState>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let arguments = query_arguments("list_shapes", query);
+    respond(call(&state, "list_shapes", arguments).await, StatusCode::OK)
+}
+
+pub async fn create_shape(
+    AxumState(state): AxumState<SharedApiState>,
+    body: Option<Json<Value>>,
+) -> Response {
+    let fields = match object(body) {
+        Ok(fields) => fields,
+        Err(failure) => return failure.into_response(),
+    };
+    match call(&state, "create_shape", Value::Object(fields)).await {
+        Ok(shape) => {
+            let location = shape
+                .get("id")
+                .and_then(Value::as_str)
+                .map(|id| format!("{}/shapes/{}", PREFIX, id));
+            let mut response = (StatusCode::CREATED, Json(shape)).into_response();
+            if let Some(value) = location.and_then(|l| l.parse().ok()) {
+                response.headers_mut().insert(header::LOCATION, value);
+            }
+            response
+        }
+        Err(failure) => failure.into_response(),
+    }
+}
+
+pub async fn get_shape(
+    AxumState(state): AxumState<SharedApiState>,
+    Path(id): Path<String>,
+) -> Response {
+    respond(
+        call(&state, "get_shape", json!({ "id": id })).await,
+        StatusCode::OK,
+    )
+}
+
+pub async fn update_shape(
+    AxumState(state): AxumState<SharedApiState>,
+    Path(id): Path<String>,
+    body: Option<Json<Value>>,
+) -> Response {
+    let mut fields = match object(body) {
+        Ok(fields) => fields,
+        Err(failure) => return failure.into_response(),
+    };
+    fields.insert("id".to_string(), json!(id));
+    respond(
+        call(&state, "update_shape", Value::Object(fields)).await,
+        StatusCode::OK,
+    )
+}
+
+pub async fn delete_shape(
+    AxumState(state): AxumState<SharedApiState>,
+    Path(id): Path<String>,
+) -> Response {
+    match call(&state, "delete_shape", json!({ "id": id })).await {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(failure) => failure.into_response(),
+    }
+}
+
+pub async fn list_tabs(AxumState(state): AxumState<SharedApiState>) -> Response {
+    respond(call(&state, "list_tabs", json!({})).await, StatusCode::OK)
+}
+
+pub async fn create_tab(
+    AxumState(state): AxumState<SharedApiState>,
+    body: Option<Json<Value>>,
+) -> Response {
+    let fields = match object(body) {
+        Ok(fields) => fields,
+        Err(failure) => return failure.into_response(),
+    };
+    respond(
+        call(&state, "create_tab", Value::Object(fields)).await,
+        StatusCode::CREATED,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool_errors::ToolError;
+
+    fn reply_text(text: &str, is_error: bool) -> Value {
+        json!({ "result": { "isError": is_error, "content": [{ "type": "text", "text": text }] } })
+    }
+
+    fn reply_error(message: &str) -> Value {
+        json!({ "result": ToolError::new(message).call_result() })
+    }
+
+    #[test]
+    fn tool_failures_map_to_statuses() {
+        let ok = tool_result(&reply_text(r#"{ "id": "s1" }"#, false)).unwrap();
+        assert_eq!(ok, json!({ "id": "s1" }));
+
+        let status = |reply: Value| tool_result(&reply).unwrap_err().status;
+        let missing = tool_result(&reply_error("Shape not found: s9")).unwrap_err();
+        assert_eq!(missing.status, StatusCode::NOT_FOUND);
+        assert_eq!(missing.body["code"], "SHAPE_NOT_FOUND");
+        assert_eq!(
+            status(reply_error("Missing required field: type")),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            status(reply_error("Canvas locked by user (retry after 3s)")),
+            StatusCode::CONFLICT
+        );
+        // The code decides, not the wording
+        let declined = json!({ "result": ToolError {
+            code: ErrorCode::PermissionDenied,
+            message: "Shape s1 not found in your approvals".to_string(),
+        }.call_result() });
+        assert_eq!(status(declined), StatusCode::FORBIDDEN);
+        assert_eq!(
+            status(reply_text("Request timed out", true)),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert_eq!(
+            status(
+                json!({ "error": { "code": PERMISSION_DENIED, "message": "read-only", "data": { "reason": "readOnly" } } })
+            ),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn refusals_map_by_json_rpc_code() {
+        let status = |code: i64, message: &str| {
+            let reply = json!({ "error": { "code": code, "message": message } });
+            tool_result(&reply).unwrap_err().status
+        };
+        assert_eq!(
+            status(PERMISSION_DENIED, "Refused in read-only mode"),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(
+                INVALID_PARAMS,
+                "Tool not implemented by this version of Napkin: get_shape"
+            ),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(INVALID_PARAMS, "Tool disabled in Napkin: delete_shape"),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(-32603, "Internal error"),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn query_parameters_follow_the_tool_schema() {
+        let query = HashMap::from([
+            ("type".to_string(), "sticky".to_string()),
+            ("maxShapes".to_string(), "20".to_string()),
+            (
+                "region".to_string(),
+                r#"{"x":0,"y":0,"width":50,"height":50}"#.to_string(),
+            ),
+        ]);
+        assert_eq!(
+            query_arguments("list_shapes", query),
+            json!({
+                "type": "sticky",
+                "maxShapes": 20,
+                "region": { "x": 0, "y": 0, "width": 50, "height": 50 },
+            })
+        );
+    }
+}
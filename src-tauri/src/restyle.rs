@@ -0,0 +1,284 @@
+//! Restyling many shapes at once.
+//!
+//! `get_common_properties` reports which style properties a selection
+//! shares and where it diverges, so an agent can restyle a diagram without
+//! reading every shape. `bulk_update` applies one set of properties to many
+//! shapes as a single `batch_operations` call: one undo step and one saved
+//! change, instead of one `update_shape` per shape.
+
+use serde_json::{json, Map, Value};
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::constraints;
+use crate::resources;
+
+/// Properties compared by `get_common_properties`.
+const STYLE_PROPERTIES: &[&str] = &[
+    "strokeColor",
+    "strokeWidth",
+    "strokeStyle",
+    "fillColor",
+    "fillStyle",
+    "opacity",
+    "roughness",
+    "fontSize",
+    "fontFamily",
+    "textAlign",
+    "verticalAlign",
+    "stickyColor",
+];
+
+/// Properties `bulk_update` never changes.
+const FIXED_PROPERTIES: &[&str] = &["id", "type"];
+
+/// Numbers compare by value, so `2` and `2.0` are the same width.
+fn same(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn id_of(shape: &Value) -> &str {
+    shape.get("id").and_then(Value::as_str).unwrap_or("")
+}
+
+/// The selected shapes, in `ids` order, and the ids not on the board.
+fn select<'a>(shapes: &'a [Value], ids: &[String]) -> (Vec<&'a Value>, Vec<String>) {
+    let mut found = Vec::new();
+    let mut missing = Vec::new();
+    for id in ids {
+        match shapes.iter().find(|s| id_of(s) == id) {
+            Some(shape) => found.push(shape),
+            None => missing.push(id.clone()),
+        }
+    }
+    (found, missing)
+}
+
+/// Shared and diverging style properties of `shapes`. A property is common
+/// when every shape that has it has the same value; shapes without it (a
+/// font size on a line) are left out of its entry.
+pub fn common_properties(shapes: &[&Value]) -> Value {
+    let mut common = Map::new();
+    let mut diverging = Map::new();
+    for &property in STYLE_PROPERTIES {
+        let mut values: Vec<(&Value, Vec<&str>)> = Vec::new();
+        for shape in shapes {
+            let Some(value) = shape.get(property).filter(|v| !v.is_null()) else {
+                continue;
+            };
+            match values.iter_mut().find(|(v, _)| same(v, value)) {
+                Some((_, ids)) => ids.push(id_of(shape)),
+                None => values.push((value, vec![id_of(shape)])),
+            }
+        }
+        match values.as_slice() {
+            [] => {}
+            [(value, _)] => {
+                common.insert(property.to_string(), (*value).clone());
+            }
+            _ => {
+                let groups = values
+                    .iter()
+                    .map(|(value, ids)| json!({ "value": value, "ids": ids }))
+                    .collect();
+                diverging.insert(property.to_string(), Value::Array(groups));
+            }
+        }
+    }
+
+    let mut types = Map::new();
+    for shape in shapes {
+        let kind = shape
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown");
+        let count = types.get(kind).and_then(Value::as_u64).unwrap_or(0);
+        types.insert(kind.to_string(), json!(count + 1));
+    }
+    json!({
+        "count": shapes.len(),
+        "types": types,
+        "common": common,
+        "diverging": diverging,
+    })
+}
+
+/// `batch_operations` arguments updating each of `ids` with `properties`.
+fn update_operations(ids: &[String], properties: &Map<String, Value>) -> Result<Value, String> {
+    if ids.is_empty() {
+        return Err("ids must list at least one shape".to_string());
+    }
+    if properties.is_empty() {
+        return Err("properties must set at least one property".to_string());
+    }
+    if let Some(key) = FIXED_PROPERTIES
+        .iter()
+        .find(|k| properties.contains_key(**k))
+    {
+        return Err(format!("bulk_update cannot change {}", key));
+    }
+    let operations: Vec<Value> = ids
+        .iter()
+        .map(|id| {
+            let mut data = properties.clone();
+            data.insert("id".to_string(), json!(id));
+            json!({ "action": "update", "data": data })
+        })
+        .collect();
+    Ok(json!({ "operations": operations }))
+}
+
+pub fn restyle_tools() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "get_common_properties",
+            "description": "Compare the style of several shapes before restyling them. Returns the style properties (colors, stroke, fill, opacity, roughness, font, alignment) that all of them share under `common`, and under `diverging` each differing property with its values and the ids having each value. Shapes without a property (a font size on a line) are left out of it. Ids not on the board are listed under `missing`.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "ids": { "type": "array", "items": { "type": "string" }, "description": "Shapes to compare" }
+                },
+                "required": ["ids"],
+                "additionalProperties": false
+            }
+        }),
+        json!({
+            "name": "bulk_update",
+            "description": "Apply the same properties to many shapes in one change (one undo step), e.g. to restyle a whole diagram. Prefer it to repeated update_shape calls. Fails without changing anything if a shape does not exist.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "ids": { "type": "array", "items": { "type": "string" }, "description": "Shapes to update" },
+                    "properties": {
+                        "type": "object",
+                        "description": "Properties to set on every shape, as in update_shape (e.g. { \"strokeColor\": \"#1e88e5\", \"strokeWidth\": 3 })"
+                    }
+                },
+                "required": ["ids", "properties"],
+                "additionalProperties": false
+            }
+        }),
+    ]
+}
+
+pub async fn call_restyle_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "get_common_properties" => Some(get_common_properties(state, arguments).await),
+        "bulk_update" => Some(bulk_update(state, arguments).await),
+        _ => None,
+    }
+}
+
+fn ids_argument(arguments: &Value) -> Result<Vec<String>, String> {
+    serde_json::from_value(arguments.get("ids").cloned().unwrap_or(Value::Null))
+        .map_err(|_| "ids must be an array of shape ids".to_string())
+}
+
+async fn board_shapes(state: &SharedApiState) -> Result<Vec<Value>, String> {
+    let canvas = resources::canvas(state, None).await?;
+    Ok(canvas
+        .get("shapes")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default())
+}
+
+async fn get_common_properties(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let ids = ids_argument(arguments)?;
+    let shapes = board_shapes(state).await?;
+    let (selected, missing) = select(&shapes, &ids);
+    let mut result = common_properties(&selected);
+    result["missing"] = json!(missing);
+    Ok(result)
+}
+
+async fn bulk_update(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let ids = ids_argument(arguments)?;
+    let properties = arguments
+        .get("properties")
+        .and_then(Value::as_object)
+        .ok_or("properties must be an object")?;
+    let operations = update_operations(&ids, properties)?;
+
+    let shapes = board_shapes(state).await?;
+    let (_, missing) = select(&shapes, &ids);
+    if !missing.is_empty() {
+        return Err(format!(
+            "Shapes not found: {}; nothing was changed",
+            missing.join(", ")
+        ));
+    }
+    let result = bridge_tool_call(state, "batch_operations", operations).await?;
+    if let Some(error) = result.get("error").and_then(Value::as_str) {
+        return Err(error.to_string());
+    }
+    if let Err(e) = constraints::resolve_via_bridge(state).await {
+        log::warn!("Constraint re-solve after bulk_update failed: {}", e);
+    }
+    Ok(json!({
+        "updated": ids.len(),
+        "ids": ids,
+        "properties": properties,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn shared_and_diverging_properties() {
+        let shapes = vec![
+            json!({ "id": "a", "type": "rectangle", "strokeColor": "#000", "strokeWidth": 2, "fillColor": "#fff" }),
+            json!({ "id": "b", "type": "rectangle", "strokeColor": "#000", "strokeWidth": 2.0, "fillColor": "#eee" }),
+            json!({ "id": "c", "type": "text", "strokeColor": "#000", "fontSize": 20, "fillColor": "#fff" }),
+        ];
+        let (selected, missing) = select(&shapes, &ids(&["a", "b", "c", "gone"]));
+        assert_eq!(missing, vec!["gone"]);
+        let stats = common_properties(&selected);
+        assert_eq!(stats["count"], 3);
+        assert_eq!(stats["types"], json!({ "rectangle": 2, "text": 1 }));
+        assert_eq!(stats["common"]["strokeColor"], "#000");
+        assert_eq!(stats["common"]["strokeWidth"], 2);
+        assert_eq!(stats["common"]["fontSize"], 20);
+        assert_eq!(
+            stats["diverging"]["fillColor"],
+            json!([
+                { "value": "#fff", "ids": ["a", "c"] },
+                { "value": "#eee", "ids": ["b"] },
+            ])
+        );
+        assert!(stats["common"].get("opacity").is_none());
+    }
+
+    #[test]
+    fn one_update_operation_per_shape() {
+        let properties = json!({ "strokeColor": "#1e88e5" });
+        let properties = properties.as_object().unwrap();
+        let ops = update_operations(&ids(&["a", "b"]), properties).unwrap();
+        assert_eq!(
+            ops["operations"],
+            json!([
+                { "action": "update", "data": { "id": "a", "strokeColor": "#1e88e5" } },
+                { "action": "update", "data": { "id": "b", "strokeColor": "#1e88e5" } },
+            ])
+        );
+        assert!(update_operations(&[], properties).is_err());
+        assert!(update_operations(&ids(&["a"]), &Map::new()).is_err());
+        let retype = json!({ "type": "ellipse" });
+        assert_eq!(
+            update_operations(&ids(&["a"]), retype.as_object().unwrap()).unwrap_err(),
+            "bulk_update cannot change type"
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! Canvas revisions, the change journal, and conditional reads.
+//!
+//! Every edit the webview reports (`api_notify_change`) bumps one revision
+//! number, counted from 1 since Napkin started, and is kept in a journal
+//! with the shapes it touched. `get_canvas` returns the current revision as
+//! `revision`. An agent that already holds revision N can then either call
+//! `get_changes` with `sinceRevision: N` for the shapes created, updated and
+//! deleted since, or `get_canvas` with `sinceRevision: N`, which answers
+//! `{ revision, unchanged: true }` without a round trip to the webview when
+//! nothing changed, and otherwise lists only the changed shapes plus
+//! `deletedIds`. The journal holds the last [`MAX_LOGGED`] edits and at most
+//! [`MAX_LOGGED_CHANGES`] shape changes; for an older revision (or one from
+//! before a restart) `get_canvas` sends the whole board and `get_changes`
+//! fails, so the agent starts over from `get_canvas`.
+
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::api::SharedApiState;
+use crate::canvas_events::{ChangeKind, ShapeChange};
+
+/// Edits kept in the journal.
+const MAX_LOGGED: usize = 1000;
+/// Shape changes kept in the journal, across all edits.
+const MAX_LOGGED_CHANGES: usize = 20_000;
+
+struct Edit {
+    revision: u64,
+    tab_id: String,
+    changes: Vec<ShapeChange>,
+}
+
+#[derive(Default)]
+pub struct Revisions {
+    current: u64,
+    /// Oldest first, one entry per revision.
+    log: VecDeque<Edit>,
+    /// Shape changes in `log`.
+    logged_changes: usize,
+}
+
+/// The net change to a document's shapes over some edits: each shape's
+/// latest state, and the shapes that are gone. A shape created and deleted
+/// in between is left out.
+#[derive(Debug, Default, PartialEq)]
+struct Diff {
+    created: BTreeMap<String, Value>,
+    updated: BTreeMap<String, Value>,
+    deleted: BTreeSet<String>,
+}
+
+impl Diff {
+    fn add(&mut self, change: &ShapeChange) {
+        let id = &change.shape_id;
+        let shape = change.shape.clone().unwrap_or(Value::Null);
+        let created = self.created.remove(id).is_some();
+        self.updated.remove(id);
+        if change.kind == ChangeKind::Deleted {
+            if !created {
+                self.deleted.insert(id.clone());
+            }
+            return;
+        }
+        // A shape recreated after a delete (an undo) is one the client had
+        let existed = !created && (change.kind == ChangeKind::Updated || self.deleted.remove(id));
+        if existed {
+            self.updated.insert(id.clone(), shape);
+        } else {
+            self.created.insert(id.clone(), shape);
+        }
+    }
+
+    fn changed(&self, id: &str) -> bool {
+        self.created.contains_key(id) || self.updated.contains_key(id)
+    }
+}
+
+/// What changed on a document since a revision.
+#[derive(Debug, PartialEq)]
+enum Since {
+    Unchanged,
+    Changed(Diff),
+    /// Not in the journal.
+    Unknown,
+}
+
+impl Revisions {
+    /// Record an edit to `tab_id`; the new revision.
+    pub fn bump(&mut self, tab_id: &str, changes: &[ShapeChange]) -> u64 {
+        self.current += 1;
+        self.logged_changes += changes.len();
+        self.log.push_back(Edit {
+            revision: self.current,
+            tab_id: tab_id.to_string(),
+            changes: changes.to_vec(),
+        });
+        while self.log.len() > MAX_LOGGED
+            || (self.log.len() > 1 && self.logged_changes > MAX_LOGGED_CHANGES)
+        {
+            if let Some(edit) = self.log.pop_front() {
+                self.logged_changes -= edit.changes.len();
+            }
+        }
+        self.current
+    }
+
+    fn since(&self, tab_id: &str, revision: u64) -> Since {
+        let logged_from = self.log.front().map_or(self.current + 1, |e| e.revision);
+        if revision > self.current || revision + 1 < logged_from {
+            return Since::Unknown;
+        }
+        let mut edits = self
+            .log
+            .iter()
+            .filter(|e| e.revision > revision && e.tab_id == tab_id)
+            .peekable();
+        if edits.peek().is_none() {
+            return Since::Unchanged;
+        }
+        let mut diff = Diff::default();
+        for change in edits.flat_map(|e| &e.changes) {
+            diff.add(change);
+        }
+        Since::Changed(diff)
+    }
+}
+
+/// A `get_canvas` call, with the revision it reads.
+pub struct CanvasRead {
+    revision: u64,
+    since: Option<u64>,
+    changes: Since,
+}
+
+impl CanvasRead {
+    /// The whole result, when nothing changed since `sinceRevision`.
+    pub fn unchanged(&self) -> Option<Value> {
+        (self.changes == Since::Unchanged).then(|| {
+            json!({
+                "revision": self.revision,
+                "sinceRevision": self.since,
+                "unchanged": true,
+            })
+        })
+    }
+
+    /// Add the revision to the webview's `result`, keeping only the shapes
+    /// that changed when the call asked for changes.
+    pub fn apply(&self, mut result: Value) -> Value {
+        if !result.is_object() || result.get("error").is_some() {
+            return result;
+        }
+        result["revision"] = json!(self.revision);
+        if let Since::Changed(diff) = &self.changes {
+            if let Some(shapes) = result.get_mut("shapes").and_then(Value::as_array_mut) {
+                shapes.retain(|shape| {
+                    shape
+                        .get("id")
+                        .and_then(Value::as_str)
+                        .is_some_and(|id| diff.changed(id))
+                });
+            }
+            result["sinceRevision"] = json!(self.since);
+            result["deletedIds"] = json!(diff.deleted);
+        }
+        result
+    }
+}
+
+fn since_revision(value: &Value) -> Result<u64, String> {
+    value
+        .as_u64()
+        .ok_or_else(|| "sinceRevision must be a non-negative integer".to_string())
+}
+
+/// For `get_canvas`, take `sinceRevision` out of `arguments` and note the
+/// revision being read. Taken before the webview is asked, so an edit made
+/// meanwhile is reported again next time rather than missed.
+pub async fn prepare(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &mut Value,
+) -> Result<Option<CanvasRead>, String> {
+    if tool_name != "get_canvas" {
+        return Ok(None);
+    }
+    let since = match arguments
+        .as_object_mut()
+        .and_then(|a| a.remove("sinceRevision"))
+    {
+        Some(value) => Some(since_revision(&value)?),
+        None => None,
+    };
+    let revisions = state.revisions.lock().await;
+    let changes = match (since, state.visibility.document_for(arguments)) {
+        (Some(since), Some(document)) => revisions.since(&document, since),
+        _ => Since::Unknown,
+    };
+    Ok(Some(CanvasRead {
+        revision: revisions.current,
+        since,
+        changes,
+    }))
+}
+
+pub fn revision_tools() -> Vec<Value> {
+    vec![json!({
+        "name": "get_changes",
+        "description": "What changed on a board since a revision you already have (from get_canvas or an earlier get_changes): the shapes created and updated since, as they are now, and the ids of shapes deleted. Use it instead of fetching the whole canvas again each turn. Returns the new revision to pass next time. Fails if the revision is too old to be in the change journal; call get_canvas then.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "sinceRevision": { "type": "integer", "minimum": 0, "description": "The revision to compare with" },
+                "tabId": { "type": "string", "description": "Tab to read (default: the tab agents are working on)" }
+            },
+            "required": ["sinceRevision"],
+            "additionalProperties": false
+        }
+    })]
+}
+
+pub async fn call_revision_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "get_changes" => Some(get_changes(state, arguments).await),
+        _ => None,
+    }
+}
+
+async fn get_changes(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let since = since_revision(
+        arguments
+            .get("sinceRevision")
+            .ok_or("Missing required field: sinceRevision")?,
+    )?;
+    let revisions = state.revisions.lock().await;
+    let document = state.visibility.document_for(arguments);
+    let changes = match document {
+        Some(document) => revisions.since(&document, since),
+        None => Since::Unchanged,
+    };
+    let diff = match changes {
+        Since::Unchanged => Diff::default(),
+        Since::Changed(diff) => diff,
+        Since::Unknown => {
+            return Err(format!(
+                "Revision {} is not in the change journal (the canvas is at revision {}); call get_canvas for the whole board",
+                since, revisions.current
+            ))
+        }
+    };
+    Ok(json!({
+        "revision": revisions.current,
+        "sinceRevision": since,
+        "created": diff.created.into_values().collect::<Vec<_>>(),
+        "updated": diff.updated.into_values().collect::<Vec<_>>(),
+        "deleted": diff.deleted,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(kind: ChangeKind, id: &str) -> ShapeChange {
+        ShapeChange {
+            kind,
+            shape_id: id.to_string(),
+            shape: (kind != ChangeKind::Deleted).then(|| json!({ "id": id })),
+        }
+    }
+
+    fn diff(created: &[&str], updated: &[&str], deleted: &[&str]) -> Since {
+        let shapes = |ids: &[&str]| {
+            ids.iter()
+                .map(|id| (id.to_string(), json!({ "id": id })))
+                .collect()
+        };
+        Since::Changed(Diff {
+            created: shapes(created),
+            updated: shapes(updated),
+            deleted: deleted.iter().map(|id| id.to_string()).collect(),
+        })
+    }
+
+    #[test]
+    fn changes_since_a_revision_are_folded_per_shape() {
+        let mut revisions = Revisions::default();
+        assert_eq!(revisions.since("t1", 0), Since::Unchanged);
+        assert_eq!(revisions.bump("t1", &[change(ChangeKind::Created, "a")]), 1);
+        revisions.bump("t2", &[change(ChangeKind::Created, "x")]);
+        assert_eq!(revisions.since("t1", 1), Since::Unchanged);
+
+        revisions.bump(
+            "t1",
+            &[
+                change(ChangeKind::Created, "b"),
+                change(ChangeKind::Deleted, "a"),
+                change(ChangeKind::Updated, "c"),
+            ],
+        );
+        revisions.bump(
+            "t1",
+            &[
+                change(ChangeKind::Updated, "b"),
+                change(ChangeKind::Deleted, "c"),
+                change(ChangeKind::Created, "d"),
+            ],
+        );
+        revisions.bump("t1", &[change(ChangeKind::Deleted, "d")]);
+        assert_eq!(revisions.since("t1", 2), diff(&["b"], &[], &["a", "c"]));
+        assert_eq!(revisions.since("t1", 0), diff(&["b"], &[], &["c"]));
+        assert_eq!(revisions.since("t1", 9), Since::Unknown);
+
+        // Undoing a delete brings back a shape the client already had
+        revisions.bump("t1", &[change(ChangeKind::Created, "a")]);
+        assert_eq!(revisions.since("t1", 2), diff(&["b"], &["a"], &["c"]));
+
+        let read = CanvasRead {
+            revision: 6,
+            since: Some(2),
+            changes: revisions.since("t1", 2),
+        };
+        let result = read.apply(json!({ "shapes": [{ "id": "a" }, { "id": "b" }, { "id": "e" }] }));
+        assert_eq!(
+            result,
+            json!({
+                "shapes": [{ "id": "a" }, { "id": "b" }],
+                "revision": 6,
+                "sinceRevision": 2,
+                "deletedIds": ["c"],
+            })
+        );
+    }
+
+    #[test]
+    fn revisions_older_than_the_journal_are_unknown() {
+        let mut revisions = Revisions::default();
+        for _ in 0..=MAX_LOGGED {
+            revisions.bump("t1", &[]);
+        }
+        assert_eq!(revisions.since("t1", 0), Since::Unknown);
+        assert_eq!(revisions.since("t1", 1), diff(&[], &[], &[]));
+
+        let many: Vec<ShapeChange> = (0..MAX_LOGGED_CHANGES)
+            .map(|n| change(ChangeKind::Updated, &n.to_string()))
+            .collect();
+        let before = revisions.bump("t1", &many);
+        revisions.bump("t1", &many[..1]);
+        assert_eq!(revisions.since("t1", before - 1), Since::Unknown);
+        assert!(matches!(revisions.since("t1", before), Since::Changed(_)));
+    }
+}
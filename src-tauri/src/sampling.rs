@@ -0,0 +1,156 @@
+//! Requests from the server to a client (`sampling/createMessage`).
+//!
+//! A tool can ask the calling agent's model for a completion when the
+//! client declared the `sampling` capability in `initialize`. The request
+//! goes out on the client's notification stream (the session's `GET /mcp`
+//! stream, or the WebSocket, legacy SSE or tunnel connection) and the client
+//! answers with a JSON-RPC response, posted like any other message;
+//! `handle_mcp_message` hands such responses to `deliver`.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::api::SharedApiState;
+use crate::session::McpClient;
+
+/// How long a client has to answer. Models can be slow on long prompts.
+const SAMPLING_TIMEOUT_SECS: u64 = 180;
+
+/// Requests sent to clients and not answered yet, by JSON-RPC id.
+#[derive(Default)]
+pub struct PendingRequests {
+    pending: HashMap<String, oneshot::Sender<Value>>,
+}
+
+impl PendingRequests {
+    fn open(&mut self, id: &str) -> oneshot::Receiver<Value> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.insert(id.to_string(), tx);
+        rx
+    }
+
+    fn close(&mut self, id: &str) {
+        self.pending.remove(id);
+    }
+
+    /// Hand `response` to the request it answers; false if none is waiting.
+    fn answer(&mut self, response: Value) -> bool {
+        let Some(id) = response.get("id").and_then(Value::as_str) else {
+            return false;
+        };
+        match self.pending.remove(id) {
+            Some(tx) => tx.send(response).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Whether `message` is a response to a request of ours, not a request.
+pub fn is_response(message: &Value) -> bool {
+    message.get("method").is_none()
+        && message.get("id").is_some()
+        && (message.get("result").is_some() || message.get("error").is_some())
+}
+
+/// Pass a client's response to the tool waiting for it.
+pub async fn deliver(state: &SharedApiState, response: Value) {
+    if !state.sampling.lock().await.answer(response) {
+        log::debug!("Ignoring a response nobody is waiting for");
+    }
+}
+
+/// Send `sampling/createMessage` with `params` to `client` and wait for
+/// its result.
+pub async fn create_message(
+    state: &SharedApiState,
+    client: &McpClient,
+    params: Value,
+) -> Result<Value, String> {
+    if !client.can_sample() {
+        return Err("The client does not support sampling".to_string());
+    }
+    let id = format!("napkin-sampling-{}", Uuid::new_v4());
+    let rx = state.sampling.lock().await.open(&id);
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "sampling/createMessage",
+        "params": params,
+    });
+    if !state.subscriptions.lock().await.send(client.id(), request) {
+        state.sampling.lock().await.close(&id);
+        return Err("The client has no open stream to receive sampling requests on".to_string());
+    }
+
+    let response = tokio::time::timeout(Duration::from_secs(SAMPLING_TIMEOUT_SECS), rx).await;
+    let response = match response {
+        Ok(Ok(response)) => response,
+        _ => {
+            state.sampling.lock().await.close(&id);
+            return Err(format!(
+                "The client did not answer the sampling request within {}s",
+                SAMPLING_TIMEOUT_SECS
+            ));
+        }
+    };
+    match response.get("error") {
+        Some(error) => Err(format!(
+            "The client declined the sampling request: {}",
+            error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error")
+        )),
+        None => Ok(response.get("result").cloned().unwrap_or(Value::Null)),
+    }
+}
+
+/// Ask `client`'s model to answer `prompt`; returns the text of the reply.
+pub async fn complete(
+    state: &SharedApiState,
+    client: &McpClient,
+    system_prompt: &str,
+    prompt: &str,
+    max_tokens: u32,
+) -> Result<String, String> {
+    let params = json!({
+        "messages": [{
+            "role": "user",
+            "content": { "type": "text", "text": prompt },
+        }],
+        "systemPrompt": system_prompt,
+        "includeContext": "none",
+        "maxTokens": max_tokens,
+    });
+    let result = create_message(state, client, params).await?;
+    result
+        .pointer("/content/text")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| "The client's sampling reply has no text".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn responses_reach_the_waiting_request() {
+        let mut pending = PendingRequests::default();
+        let rx = pending.open("s1");
+        let response = json!({ "jsonrpc": "2.0", "id": "s1", "result": { "content": { "type": "text", "text": "hi" } } });
+        assert!(is_response(&response));
+        assert!(pending.answer(response.clone()));
+        assert_eq!(rx.await.unwrap(), response);
+        assert!(!pending.answer(response));
+
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" });
+        assert!(!is_response(&request));
+        assert!(!is_response(
+            &json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })
+        ));
+    }
+}
@@ -0,0 +1,366 @@
+//! Confinement for export hooks.
+//!
+//! A hook may read and run anything, but write only in the exported file's
+//! folder and the temp directory, and open network connections only when
+//! it is allowed to. Each platform enforces what it can:
+//!
+//! - Linux: Landlock limits writes (Linux 5.13 or later) and a seccomp
+//!   filter refuses IP sockets.
+//! - macOS: the program runs under `sandbox-exec` with a generated profile.
+//! - Windows: the program runs in a job object that kills it and its
+//!   children together and keeps it off the desktop's global state. Files
+//!   and network are not confined there.
+//!
+//! When the confinement cannot be set up the hook does not run.
+
+use std::path::PathBuf;
+
+use crate::export_hooks::restricted_command;
+
+pub struct Sandbox {
+    /// Directories the program may write in, besides the temp directory.
+    pub writable: Vec<PathBuf>,
+    /// Whether the program may open network connections.
+    pub network: bool,
+}
+
+/// Keeps the confinement of a spawned program alive; drop it once the
+/// program has finished.
+pub struct Guard {
+    #[cfg(windows)]
+    _job: windows::Job,
+}
+
+impl Sandbox {
+    fn writable_dirs(&self) -> Vec<PathBuf> {
+        self.writable
+            .iter()
+            .cloned()
+            .chain(std::iter::once(std::env::temp_dir()))
+            .filter(|dir| dir.is_dir())
+            .collect()
+    }
+
+    /// A `restricted_command` for `program` that runs confined.
+    pub fn command(
+        &self,
+        program: &str,
+        args: &[String],
+    ) -> Result<tokio::process::Command, String> {
+        #[cfg(target_os = "linux")]
+        {
+            let ruleset = linux::ruleset(&self.writable_dirs())?;
+            let filter = match self.network {
+                true => None,
+                false => Some(linux::no_network()?),
+            };
+            let mut command = restricted_command(program, args);
+            // SAFETY: `enter` makes only async-signal-safe system calls
+            // and does not allocate.
+            unsafe {
+                command.pre_exec(move || linux::enter(&ruleset, filter.as_deref()));
+            }
+            Ok(command)
+        }
+        #[cfg(target_os = "macos")]
+        {
+            let mut argv = vec![
+                "-p".to_string(),
+                seatbelt_profile(&self.writable_dirs(), self.network),
+                program.to_string(),
+            ];
+            argv.extend_from_slice(args);
+            Ok(restricted_command("/usr/bin/sandbox-exec", &argv))
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        {
+            Ok(restricted_command(program, args))
+        }
+    }
+
+    /// Start `command`, made by `command()`.
+    pub fn spawn(
+        command: &mut tokio::process::Command,
+    ) -> std::io::Result<(tokio::process::Child, Guard)> {
+        #[cfg(windows)]
+        {
+            let job = windows::Job::new()?;
+            let child = command.spawn()?;
+            if let Some(process) = child.raw_handle() {
+                job.assign(process)?;
+            }
+            Ok((child, Guard { _job: job }))
+        }
+        #[cfg(not(windows))]
+        {
+            Ok((command.spawn()?, Guard {}))
+        }
+    }
+}
+
+/// A macOS sandbox profile allowing writes only beneath `writable`, and
+/// IP networking only with `network`.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn seatbelt_profile(writable: &[PathBuf], network: bool) -> String {
+    let quote = |path: &PathBuf| {
+        // `/tmp` and the temp directory are symlinks into `/private`.
+        let path = path.canonicalize().unwrap_or_else(|_| path.clone());
+        let path = path.display().to_string();
+        format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+    };
+    let mut profile = String::from("(version 1)\n(allow default)\n(deny file-write*)\n");
+    profile.push_str("(allow file-write* (literal \"/dev/null\")");
+    for dir in writable {
+        profile.push_str(&format!(" (subpath {})", quote(dir)));
+    }
+    profile.push_str(")\n");
+    if !network {
+        profile.push_str("(deny network-outbound (remote ip \"*:*\"))\n");
+        profile.push_str("(deny network-bind (local ip \"*:*\"))\n");
+    }
+    profile
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use landlock::{
+        Access, AccessFs, PathBeneath, PathFd, Ruleset, RulesetAttr, RulesetCreatedAttr, ABI,
+    };
+    use seccompiler::{
+        BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+        SeccompRule,
+    };
+    use std::os::fd::{AsRawFd, OwnedFd};
+    use std::path::{Path, PathBuf};
+
+    /// Landlock rights up to truncation; later ABIs add device ioctls,
+    /// which would only get in the way.
+    const LANDLOCK_ABI: ABI = ABI::V3;
+
+    /// A Landlock ruleset allowing reads everywhere and writes beneath
+    /// `writable`.
+    pub fn ruleset(writable: &[PathBuf]) -> Result<OwnedFd, String> {
+        let error = |e: &dyn std::fmt::Display| format!("Failed to set up the hook sandbox: {}", e);
+        let rules = [
+            (Path::new("/"), AccessFs::from_read(LANDLOCK_ABI)),
+            (Path::new("/dev/null"), AccessFs::from_file(LANDLOCK_ABI)),
+        ]
+        .into_iter()
+        .chain(
+            writable
+                .iter()
+                .map(|dir| (dir.as_path(), AccessFs::from_all(LANDLOCK_ABI))),
+        );
+        let mut ruleset = Ruleset::default()
+            .handle_access(AccessFs::from_all(LANDLOCK_ABI))
+            .and_then(|r| r.create())
+            .map_err(|e| error(&e))?;
+        for (path, access) in rules {
+            let fd = PathFd::new(path).map_err(|e| error(&e))?;
+            ruleset = ruleset
+                .add_rule(PathBeneath::new(fd, access))
+                .map_err(|e| error(&e))?;
+        }
+        Option::<OwnedFd>::from(ruleset).ok_or_else(|| {
+            "Export hooks need Landlock (Linux 5.13 or later), which this system does not offer"
+                .to_string()
+        })
+    }
+
+    /// A seccomp filter refusing IP and raw sockets, and io_uring, which
+    /// could open them without the `socket` call.
+    pub fn no_network() -> Result<BpfProgram, String> {
+        let error = |e: &dyn std::fmt::Display| format!("Failed to set up the hook sandbox: {}", e);
+        let family = |family: i32| {
+            SeccompCondition::new(0, SeccompCmpArgLen::Dword, SeccompCmpOp::Eq, family as u64)
+                .and_then(|condition| SeccompRule::new(vec![condition]))
+        };
+        let rules = [
+            (
+                libc::SYS_socket,
+                vec![
+                    family(libc::AF_INET).map_err(|e| error(&e))?,
+                    family(libc::AF_INET6).map_err(|e| error(&e))?,
+                    family(libc::AF_PACKET).map_err(|e| error(&e))?,
+                ],
+            ),
+            (libc::SYS_io_uring_setup, vec![]),
+        ];
+        let arch = std::env::consts::ARCH.try_into().map_err(|e| error(&e))?;
+        let filter = SeccompFilter::new(
+            rules.into_iter().collect(),
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::EACCES as u32),
+            arch,
+        )
+        .map_err(|e| error(&e))?;
+        BpfProgram::try_from(filter).map_err(|e| error(&e))
+    }
+
+    /// Confine the calling process, between `fork` and `exec`.
+    pub fn enter(
+        ruleset: &OwnedFd,
+        filter: Option<&[seccompiler::sock_filter]>,
+    ) -> std::io::Result<()> {
+        // SAFETY: plain system calls on a valid file descriptor.
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        match filter {
+            Some(filter) => seccompiler::apply_filter(filter)
+                .map_err(|_| std::io::ErrorKind::PermissionDenied.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectBasicUIRestrictions,
+        JobObjectExtendedLimitInformation, SetInformationJobObject,
+        JOBOBJECT_BASIC_UI_RESTRICTIONS, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        JOB_OBJECT_UILIMIT_DESKTOP, JOB_OBJECT_UILIMIT_DISPLAYSETTINGS,
+        JOB_OBJECT_UILIMIT_EXITWINDOWS, JOB_OBJECT_UILIMIT_GLOBALATOMS, JOB_OBJECT_UILIMIT_HANDLES,
+        JOB_OBJECT_UILIMIT_READCLIPBOARD, JOB_OBJECT_UILIMIT_SYSTEMPARAMETERS,
+    };
+
+    /// A job object whose processes die when it is dropped.
+    pub struct Job(HANDLE);
+
+    // SAFETY: a job handle may be used and closed from any thread.
+    unsafe impl Send for Job {}
+    unsafe impl Sync for Job {}
+
+    impl Job {
+        pub fn new() -> std::io::Result<Self> {
+            // SAFETY: no security attributes and no name.
+            let handle = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+            if handle.is_null() {
+                return Err(std::io::Error::last_os_error());
+            }
+            let job = Job(handle);
+
+            let mut limits = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+            limits.BasicLimitInformation.LimitFlags =
+                JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE | JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION;
+            job.set(JobObjectExtendedLimitInformation, &limits)?;
+            let ui = JOBOBJECT_BASIC_UI_RESTRICTIONS {
+                UIRestrictionsClass: JOB_OBJECT_UILIMIT_DESKTOP
+                    | JOB_OBJECT_UILIMIT_DISPLAYSETTINGS
+                    | JOB_OBJECT_UILIMIT_EXITWINDOWS
+                    | JOB_OBJECT_UILIMIT_GLOBALATOMS
+                    | JOB_OBJECT_UILIMIT_HANDLES
+                    | JOB_OBJECT_UILIMIT_READCLIPBOARD
+                    | JOB_OBJECT_UILIMIT_SYSTEMPARAMETERS,
+            };
+            job.set(JobObjectBasicUIRestrictions, &ui)?;
+            Ok(job)
+        }
+
+        fn set<T>(&self, class: i32, info: &T) -> std::io::Result<()> {
+            // SAFETY: `info` is the structure `class` names.
+            let ok = unsafe {
+                SetInformationJobObject(
+                    self.0,
+                    class,
+                    info as *const T as *const _,
+                    std::mem::size_of::<T>() as u32,
+                )
+            };
+            match ok {
+                0 => Err(std::io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        }
+
+        pub fn assign(&self, process: std::os::windows::io::RawHandle) -> std::io::Result<()> {
+            // SAFETY: both handles are open.
+            match unsafe { AssignProcessToJobObject(self.0, process as HANDLE) } {
+                0 => Err(std::io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            // SAFETY: the handle is open and owned by this job.
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seatbelt_profile_limits_writes_and_network() {
+        let dir = PathBuf::from("/Users/me/Export \"final\"");
+        let profile = seatbelt_profile(&[dir], false);
+        assert!(profile.contains("(deny file-write*)"));
+        assert!(profile.contains("(subpath \"/Users/me/Export \\\"final\\\"\")"));
+        assert!(profile.contains("(deny network-outbound"));
+        assert!(!seatbelt_profile(&[], true).contains("network"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn programs_write_only_where_allowed() {
+        let dir = std::env::temp_dir().join(format!("napkin-sandbox-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Outside the temp directory, which stays writable.
+        let outside = std::env::current_dir()
+            .unwrap()
+            .join(format!("napkin-sandbox-{}", uuid::Uuid::new_v4()));
+
+        let sandbox = Sandbox {
+            writable: vec![dir.clone()],
+            network: false,
+        };
+        let run = |script: String| {
+            let mut command = sandbox.command("sh", &["-c".to_string(), script]).unwrap();
+            async move {
+                let (child, _guard) = Sandbox::spawn(&mut command).unwrap();
+                child.wait_with_output().await.unwrap().status.success()
+            }
+        };
+        assert!(run(format!("echo ok > '{}'", dir.join("inside").display())).await);
+        assert!(!run(format!("echo no > '{}'", outside.display())).await);
+        assert!(!outside.exists());
+        assert!(run("cat /etc/hostname > /dev/null".to_string()).await);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn ip_sockets_need_network_access() {
+        let open_socket = |network: bool| {
+            let sandbox = Sandbox {
+                writable: Vec::new(),
+                network,
+            };
+            let script = "socket(my $s, PF_INET, SOCK_STREAM, 0) or exit 1";
+            let mut command = sandbox
+                .command(
+                    "perl",
+                    &["-MSocket".to_string(), "-e".to_string(), script.to_string()],
+                )
+                .unwrap();
+            async move {
+                let (child, _guard) = Sandbox::spawn(&mut command).unwrap();
+                child.wait_with_output().await.unwrap().status.success()
+            }
+        };
+        assert!(!open_socket(false).await);
+        assert!(open_socket(true).await);
+    }
+}
@@ -0,0 +1,375 @@
+//! Scheduled jobs.
+//!
+//! Jobs are configured in settings (`schedule`), each with a cron expression
+//! in local time: five fields (`minute hour day-of-month month day-of-week`)
+//! or one of `@hourly`, `@daily`, `@weekly` and `@monthly`. The scheduler
+//! wakes at the start of every minute and runs the jobs due then:
+//!
+//! - `checkpoint` — the webview saves open documents and takes a version
+//!   snapshot
+//! - `exportPdf` — a tab is rendered to a dated PDF in a directory
+//! - `digest` — the webview summarises the version history since the same
+//!   time yesterday (`build_digest`) and shows it
+//!
+//! Jobs missed while the app was closed or asleep are not caught up.
+
+use chrono::{Datelike, Local, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::export_hooks;
+use crate::pdf::{self, PdfExportOptions};
+use crate::settings;
+use crate::units;
+use crate::watermark;
+
+const JOB_EVENT: &str = "scheduled-job";
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "job", rename_all = "camelCase")]
+pub enum Job {
+    /// Save open documents and take a version snapshot.
+    Checkpoint,
+    /// Export tab `tab_id` to `<directory>/<title> <date>.pdf`, with the
+    /// hooks of export preset `preset`.
+    #[serde(rename_all = "camelCase")]
+    ExportPdf {
+        tab_id: String,
+        directory: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        preset: Option<String>,
+    },
+    /// Notify the user of the last day's changes.
+    Digest,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledJob {
+    #[serde(flatten)]
+    pub job: Job,
+    pub cron: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// Check every job's cron expression, for settings updates.
+pub fn validate(jobs: &[ScheduledJob]) -> Result<(), String> {
+    for job in jobs {
+        Cron::parse(&job.cron).map_err(|e| format!("Invalid schedule \"{}\": {}", job.cron, e))?;
+    }
+    Ok(())
+}
+
+// --- Cron expressions ---
+
+/// A parsed cron expression; each field is a bit set of allowed values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether day-of-month and day-of-week were `*`. When both are
+    /// restricted, a day matching either runs the job, as in cron.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("expected 5 fields, found {}", fields.len()));
+        };
+        let weekdays = parse_field(weekday, 0, 7, "day of week")?;
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, "minute")?,
+            hours: parse_field(hour, 0, 23, "hour")?,
+            days: parse_field(day, 1, 31, "day of month")?,
+            months: parse_field(month, 1, 12, "month")?,
+            // 7 is Sunday too.
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    pub fn matches(&self, t: &NaiveDateTime) -> bool {
+        let has = |set: u64, value: u32| set & (1 << value) != 0;
+        let day = has(self.days, t.day());
+        let weekday = has(self.weekdays, t.weekday().num_days_from_sunday());
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        has(self.minutes, t.minute())
+            && has(self.hours, t.hour())
+            && has(self.months, t.month())
+            && day_matches
+    }
+}
+
+/// One field: `*`, `n`, `a-b`, any of those with `/step`, or a comma list.
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let number = |s: &str| -> Result<u32, String> {
+        s.parse::<u32>()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(|| format!("{} must be {}-{}, got \"{}\"", name, min, max, s))
+    };
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("invalid step \"{}\" in {}", step, name))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (number(a)?, number(b)?),
+                // `5/15` runs from 5 to the end of the range.
+                None if step.is_some() => (number(range)?, max),
+                None => {
+                    let n = number(range)?;
+                    (n, n)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("{} range {} is backwards", name, range));
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+// --- Running jobs ---
+
+/// Run due jobs at the start of every minute for as long as the app runs.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let now = Local::now();
+            let into_minute =
+                u64::from(now.second()) * 1000 + u64::from(now.timestamp_subsec_millis().min(999));
+            tokio::time::sleep(Duration::from_millis(60_000 - into_minute)).await;
+
+            let now = Local::now().naive_local();
+            for scheduled in settings::current(&app).schedule {
+                let due = scheduled.enabled
+                    && Cron::parse(&scheduled.cron).is_ok_and(|cron| cron.matches(&now));
+                if due {
+                    let app = app.clone();
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = run(&app, &scheduled.job).await {
+                            log::warn!("Scheduled {:?} failed: {}", scheduled.job, e);
+                        }
+                    });
+                }
+            }
+        }
+    });
+}
+
+async fn run(app: &tauri::AppHandle, job: &Job) -> Result<(), String> {
+    match job {
+        Job::Checkpoint => app
+            .emit(JOB_EVENT, json!({ "job": "checkpoint" }))
+            .map_err(|e| e.to_string()),
+        Job::Digest => {
+            let since = Local::now().timestamp_millis() - DAY_MS;
+            app.emit(JOB_EVENT, json!({ "job": "digest", "since": since }))
+                .map_err(|e| e.to_string())
+        }
+        Job::ExportPdf {
+            tab_id,
+            directory,
+            preset,
+        } => {
+            let path = export_tab(app, tab_id, Path::new(directory)).await?;
+            log::info!("Scheduled export wrote {}", path);
+            export_hooks::after_export(app, path, preset.as_deref());
+            Ok(())
+        }
+    }
+}
+
+/// Render a tab to a dated PDF in `directory`; returns the file written.
+async fn export_tab(
+    app: &tauri::AppHandle,
+    tab_id: &str,
+    directory: &Path,
+) -> Result<String, String> {
+    let state = app
+        .try_state::<SharedApiState>()
+        .ok_or("The API bridge is not ready")?;
+    let state = state.inner();
+
+    let tabs = bridge_tool_call(state, "list_tabs", json!({})).await?;
+    let title = tabs
+        .get("tabs")
+        .and_then(Value::as_array)
+        .and_then(|tabs| {
+            tabs.iter()
+                .find(|t| t.get("id").and_then(Value::as_str) == Some(tab_id))
+        })
+        .ok_or_else(|| format!("Tab not found: {}", tab_id))?
+        .get("title")
+        .and_then(Value::as_str)
+        .unwrap_or("Untitled")
+        .to_string();
+    let canvas = bridge_tool_call(state, "get_tab", json!({ "tabId": tab_id })).await?;
+    if let Some(error) = canvas.get("error").and_then(Value::as_str) {
+        return Err(error.to_string());
+    }
+    let shapes = canvas
+        .get("shapes")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    let scale = units::current_scale(state).await;
+    let options = PdfExportOptions {
+        title: Some(title.clone()),
+        watermark: watermark::resolve(app, None),
+        ..Default::default()
+    };
+    let bytes = pdf::render_pdf(&shapes, scale.as_ref(), &options)?;
+    let path = directory.join(export_file_name(&title, &Local::now().naive_local()));
+    std::fs::create_dir_all(directory)
+        .and_then(|_| std::fs::write(&path, bytes))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path.display().to_string())
+}
+
+/// `<title> <YYYY-MM-DD>.pdf`, with characters file systems reject replaced.
+fn export_file_name(title: &str, now: &NaiveDateTime) -> String {
+    let safe: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let safe = safe.trim();
+    format!(
+        "{} {}.pdf",
+        if safe.is_empty() { "Untitled" } else { safe },
+        now.format("%Y-%m-%d")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn cron_fields_and_shortcuts() {
+        let nightly = Cron::parse("30 2 * * *").unwrap();
+        assert!(nightly.matches(&at(2026, 3, 4, 2, 30)));
+        assert!(!nightly.matches(&at(2026, 3, 4, 2, 31)));
+
+        // 2026-03-02 is a Monday.
+        let weekdays = Cron::parse("*/15 9-17 * * 1-5").unwrap();
+        assert!(weekdays.matches(&at(2026, 3, 2, 9, 45)));
+        assert!(!weekdays.matches(&at(2026, 3, 2, 9, 50)));
+        assert!(!weekdays.matches(&at(2026, 3, 1, 9, 45)));
+
+        // Sunday is 0 or 7.
+        assert_eq!(Cron::parse("0 0 * * 7"), Cron::parse("@weekly"));
+        assert!(Cron::parse("@weekly")
+            .unwrap()
+            .matches(&at(2026, 3, 1, 0, 0)));
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // The 1st of the month, or any Friday.
+        let cron = Cron::parse("0 8 1 * 5").unwrap();
+        assert!(cron.matches(&at(2026, 3, 1, 8, 0)));
+        assert!(cron.matches(&at(2026, 3, 6, 8, 0)));
+        assert!(!cron.matches(&at(2026, 3, 7, 8, 0)));
+    }
+
+    #[test]
+    fn invalid_cron_is_rejected() {
+        for bad in [
+            "",
+            "* * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+        ] {
+            assert!(Cron::parse(bad).is_err(), "{:?} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn jobs_deserialize_from_settings() {
+        let jobs: Vec<ScheduledJob> = serde_json::from_value(json!([
+            { "job": "checkpoint", "cron": "@daily" },
+            { "job": "exportPdf", "cron": "0 6 * * 1", "tabId": "tab_1", "directory": "/tmp", "enabled": false, "preset": "print" },
+        ]))
+        .unwrap();
+        assert!(jobs[0].enabled);
+        assert_eq!(
+            jobs[1].job,
+            Job::ExportPdf {
+                tab_id: "tab_1".to_string(),
+                directory: "/tmp".to_string(),
+                preset: Some("print".to_string()),
+            }
+        );
+        assert!(validate(&jobs).is_ok());
+    }
+
+    #[test]
+    fn export_names_are_dated_and_safe() {
+        assert_eq!(
+            export_file_name("Q3: plan/draft", &at(2026, 3, 2, 6, 0)),
+            "Q3_ plan_draft 2026-03-02.pdf"
+        );
+        assert_eq!(
+            export_file_name("  ", &at(2026, 3, 2, 6, 0)),
+            "Untitled 2026-03-02.pdf"
+        );
+    }
+}
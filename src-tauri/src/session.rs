@@ -0,0 +1,304 @@
+//! Streamable HTTP sessions (`Mcp-Session-Id`).
+//!
+//! A successful `initialize` over `POST /mcp` opens a session and returns its
+//! id in the `Mcp-Session-Id` header. Every later POST must echo it: a missing
+//! header is a 400 and an unknown or expired id a 404, which tells the client
+//! to initialize again. `DELETE /mcp` ends the session. WebSocket and stdio
+//! connections are sessions by themselves and are not tracked here.
+
+use axum::{
+    http::{HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::api::{self, SharedApiState};
+use crate::embed;
+
+pub const SESSION_HEADER: HeaderName = HeaderName::from_static("mcp-session-id");
+/// Sessions idle for longer than this are forgotten.
+pub const SESSION_TTL_SECS: u64 = 12 * 60 * 60;
+/// The least recently used session is dropped beyond this many.
+const MAX_SESSIONS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub id: String,
+    /// `clientInfo` from `initialize`, when the client sent one.
+    pub client_name: Option<String>,
+    pub client_version: Option<String>,
+    pub protocol_version: Option<String>,
+    /// The client declared the `sampling` capability.
+    pub sampling: bool,
+    pub created_at: u64,
+    pub last_seen: u64,
+}
+
+#[derive(Default)]
+pub struct SessionRegistry {
+    sessions: HashMap<String, Session>,
+}
+
+impl SessionRegistry {
+    /// Open a session for an `initialize` request with `params`.
+    pub fn open(&mut self, params: &serde_json::Value, now: u64) -> Session {
+        self.sessions
+            .retain(|_, s| now.saturating_sub(s.last_seen) < SESSION_TTL_SECS);
+        if self.sessions.len() >= MAX_SESSIONS {
+            if let Some(oldest) = self
+                .sessions
+                .values()
+                .min_by_key(|s| s.last_seen)
+                .map(|s| s.id.clone())
+            {
+                self.sessions.remove(&oldest);
+            }
+        }
+        let text = |pointer: &str| {
+            params
+                .pointer(pointer)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        let session = Session {
+            id: uuid::Uuid::new_v4().to_string(),
+            client_name: text("/clientInfo/name"),
+            client_version: text("/clientInfo/version"),
+            protocol_version: text("/protocolVersion"),
+            sampling: params.pointer("/capabilities/sampling").is_some(),
+            created_at: now,
+            last_seen: now,
+        };
+        self.sessions.insert(session.id.clone(), session.clone());
+        session
+    }
+
+    /// Mark a session active; `false` if it is unknown or expired.
+    pub fn touch(&mut self, id: &str, now: u64) -> bool {
+        match self.sessions.get_mut(id) {
+            Some(s) if now.saturating_sub(s.last_seen) < SESSION_TTL_SECS => {
+                s.last_seen = now;
+                true
+            }
+            Some(_) => {
+                self.sessions.remove(id);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Session> {
+        self.sessions.get(id).cloned()
+    }
+
+    pub fn close(&mut self, id: &str) -> bool {
+        self.sessions.remove(id).is_some()
+    }
+
+    pub fn list(&self) -> Vec<Session> {
+        self.sessions.values().cloned().collect()
+    }
+}
+
+fn header_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+}
+
+fn missing() -> Response {
+    (StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header").into_response()
+}
+
+fn unknown() -> Response {
+    (StatusCode::NOT_FOUND, "Unknown or expired MCP session").into_response()
+}
+
+/// Check the session header of a non-`initialize` request.
+pub async fn require(state: &SharedApiState, headers: &HeaderMap) -> Result<Session, Response> {
+    let id = header_id(headers).ok_or_else(missing)?;
+    let mut sessions = state.sessions.lock().await;
+    if sessions.touch(id, embed::now_secs()) {
+        sessions.get(id).ok_or_else(unknown)
+    } else {
+        Err(unknown())
+    }
+}
+
+/// `DELETE /mcp`: end the caller's session, its subscriptions and cursors.
+pub async fn end(state: &SharedApiState, headers: &HeaderMap) -> Response {
+    let Some(id) = header_id(headers) else {
+        return missing();
+    };
+    if state.sessions.lock().await.close(id) {
+        api::forget_client(state, id).await;
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        unknown()
+    }
+}
+
+/// The client behind one MCP connection: an HTTP session, or a WebSocket,
+/// legacy SSE or tunnel connection. Its id keys resource subscriptions; its
+/// name, learned from `initialize`, attributes the shapes it creates.
+pub struct McpClient {
+    id: String,
+    name: Mutex<Option<String>>,
+    /// Whether the client can answer `sampling/createMessage`.
+    sampling: AtomicBool,
+}
+
+impl Default for McpClient {
+    fn default() -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: Mutex::new(None),
+            sampling: AtomicBool::new(false),
+        }
+    }
+}
+
+impl McpClient {
+    pub fn for_session(session: &Session) -> Self {
+        Self {
+            id: session.id.clone(),
+            name: Mutex::new(session.client_name.clone()),
+            sampling: AtomicBool::new(session.sampling),
+        }
+    }
+
+    /// A client that does not introduce itself with `initialize`.
+    pub fn named(name: &str) -> Self {
+        Self {
+            name: Mutex::new(Some(name.to_string())),
+            ..Self::default()
+        }
+    }
+
+    /// A copy for work that outlives the request, such as a job.
+    pub fn detached(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            name: Mutex::new(self.name()),
+            sampling: AtomicBool::new(self.can_sample()),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Remember `clientInfo.name` if `body` is (or contains) an `initialize`.
+    pub fn observe(&self, body: &Value) {
+        let messages = match body {
+            Value::Array(batch) => batch.iter().collect(),
+            message => vec![message],
+        };
+        for message in messages {
+            if message.get("method").and_then(|m| m.as_str()) != Some("initialize") {
+                continue;
+            }
+            if let Some(name) = message
+                .pointer("/params/clientInfo/name")
+                .and_then(|n| n.as_str())
+            {
+                *self.name.lock().unwrap_or_else(|e| e.into_inner()) = Some(name.to_string());
+            }
+            let sampling = message.pointer("/params/capabilities/sampling").is_some();
+            self.sampling.store(sampling, Ordering::Relaxed);
+        }
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.name.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn can_sample(&self) -> bool {
+        self.sampling.load(Ordering::Relaxed)
+    }
+}
+
+// --- Tauri command ---
+
+/// Open HTTP sessions, for showing connected clients.
+#[tauri::command]
+pub async fn list_mcp_sessions(
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<Vec<Session>, String> {
+    Ok(state.sessions.lock().await.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sessions_open_touch_and_close() {
+        let mut registry = SessionRegistry::default();
+        let params = json!({
+            "protocolVersion": "2025-03-26",
+            "clientInfo": { "name": "client", "version": "1.2" }
+        });
+        let session = registry.open(&params, 100);
+        assert_eq!(session.client_name.as_deref(), Some("client"));
+        assert_eq!(session.protocol_version.as_deref(), Some("2025-03-26"));
+        assert!(!session.sampling);
+        let sampling = registry.open(&json!({ "capabilities": { "sampling": {} } }), 100);
+        assert!(sampling.sampling);
+        assert!(McpClient::for_session(&sampling).can_sample());
+
+        assert!(registry.touch(&session.id, 200));
+        assert!(!registry.touch("nope", 200));
+        assert!(registry.close(&session.id));
+        assert!(!registry.touch(&session.id, 200));
+    }
+
+    #[test]
+    fn idle_sessions_expire() {
+        let mut registry = SessionRegistry::default();
+        let session = registry.open(&json!({}), 0);
+        assert!(!registry.touch(&session.id, SESSION_TTL_SECS));
+        assert!(registry.list().is_empty());
+    }
+
+    #[test]
+    fn oldest_session_is_evicted_at_capacity() {
+        let mut registry = SessionRegistry::default();
+        let first = registry.open(&json!({}), 0);
+        for i in 1..MAX_SESSIONS as u64 {
+            registry.open(&json!({}), i);
+        }
+        registry.open(&json!({}), MAX_SESSIONS as u64);
+        assert_eq!(registry.list().len(), MAX_SESSIONS);
+        assert!(!registry.touch(&first.id, MAX_SESSIONS as u64));
+    }
+
+    #[test]
+    fn client_name_comes_from_initialize() {
+        let client = McpClient::default();
+        client.observe(&json!({ "method": "tools/list" }));
+        assert_eq!(client.name(), None);
+        client.observe(&json!([{
+            "method": "initialize",
+            "params": { "clientInfo": { "name": "cursor" } }
+        }]));
+        assert_eq!(client.name().as_deref(), Some("cursor"));
+    }
+
+    #[test]
+    fn session_header_is_read_and_trimmed() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(header_id(&headers), None);
+        headers.insert(SESSION_HEADER, " abc ".parse().unwrap());
+        assert_eq!(header_id(&headers), Some("abc"));
+    }
+}
@@ -0,0 +1,253 @@
+//! Persistent backend settings, stored as JSON in the app config directory.
+//!
+//! The frontend keeps its UI preferences in localStorage; this file is for
+//! settings the Rust side needs to act on without asking the webview.
+
+use crate::author::AuthorProfile;
+use crate::bind::{self, BindConfig};
+use crate::body_limit::BodyLimit;
+use crate::budget::ResponseBudget;
+use crate::daily::DailyNotes;
+use crate::export_hooks::ExportPreset;
+use crate::history::RetentionPolicy;
+use crate::legacy_sse::Heartbeat;
+use crate::origins;
+use crate::profiles::{self, ToolExposure};
+use crate::render::{PngExport, SvgExport};
+use crate::scheduler::{self, ScheduledJob};
+use crate::shape_defaults::ShapeDefaults;
+use crate::snippets::{self, Snippet};
+use crate::supervisor::RestartPolicy;
+use crate::tls::{self, TlsConfig};
+use crate::translate::TranslationProvider;
+use crate::tunnel::TunnelConfig;
+use crate::watermark::{self, Watermark};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::Manager;
+
+pub(crate) const SETTINGS_FILE: &str = "settings.json";
+
+/// What to do with a connector whose bound shape no longer exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BindingRepairPolicy {
+    /// Keep the line/arrow where it is and drop the dangling binding.
+    #[default]
+    Unbind,
+    /// Delete the line/arrow entirely.
+    Remove,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub binding_repair_policy: BindingRepairPolicy,
+    /// Defaults for shapes created by agents when arguments are omitted.
+    pub shape_defaults: ShapeDefaults,
+    /// How much version history `compact_history` keeps.
+    pub history_retention: RetentionPolicy,
+    /// Run the MCP server in a supervised child process, so a busy webview
+    /// cannot stall the HTTP listener. Takes effect on the next start.
+    pub mcp_sidecar: bool,
+    /// Host names, besides localhost, the server answers to (`name` or
+    /// `name:port`).
+    pub allowed_hosts: Vec<String>,
+    /// Web pages, besides the app and localhost, that may call the server
+    /// from a browser (`https://name[:port]`).
+    pub allowed_origins: Vec<String>,
+    /// Where the MCP server listens; loopback unless LAN access is opted
+    /// in. Takes effect on the next start.
+    pub mcp_bind: BindConfig,
+    /// Serve HTTPS; takes effect on the next start.
+    pub mcp_tls: TlsConfig,
+    /// Restart the in-process server if it stops on its own.
+    pub mcp_restart: RestartPolicy,
+    /// Keep-alive interval for SSE streams.
+    pub sse_heartbeat: Heartbeat,
+    /// The largest request body the server accepts.
+    pub request_body_limit: BodyLimit,
+    /// Banner or stamp drawn on exports unless an export names its own.
+    pub export_watermark: Watermark,
+    /// Named sets of commands run on exported files (see
+    /// `export_hooks.rs`).
+    pub export_presets: Vec<ExportPreset>,
+    /// Scale, resolution and background of the menu's PNG export.
+    pub png_export: PngExport,
+    /// Whether the menu's SVG export outlines its text.
+    pub svg_export: SvgExport,
+    /// Ask the user before each mutating tool call reaches the canvas.
+    pub ask_before_edits: bool,
+    /// Outbound connection to a remote agent relay.
+    pub tunnel: TunnelConfig,
+    /// Name and color stamped on shapes the user draws.
+    pub author: AuthorProfile,
+    /// Jobs run on a cron schedule (checkpoints, PDF exports, digests).
+    pub schedule: Vec<ScheduledJob>,
+    /// Which MCP tools each client is offered.
+    pub tool_exposure: ToolExposure,
+    /// Default size limit for tools that return shapes.
+    pub response_budget: ResponseBudget,
+    /// Slash-command expansions for the text editor.
+    pub snippets: Vec<Snippet>,
+    /// Command used by `translate_canvas` instead of MCP sampling.
+    pub translation: TranslationProvider,
+    /// Where today's napkin is kept and whether stickies carry over.
+    pub daily_notes: DailyNotes,
+}
+
+impl Settings {
+    /// Apply a partial JSON object on top of the current values.
+    pub fn merged(&self, patch: serde_json::Value) -> Result<Settings, String> {
+        let mut current = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        match (current.as_object_mut(), patch) {
+            (Some(obj), serde_json::Value::Object(patch)) => {
+                for (key, value) in patch {
+                    obj.insert(key, value);
+                }
+            }
+            _ => return Err("Settings update must be a JSON object".to_string()),
+        }
+        let settings: Settings =
+            serde_json::from_value(current).map_err(|e| format!("Invalid settings: {}", e))?;
+        scheduler::validate(&settings.schedule)?;
+        profiles::validate(&settings.tool_exposure)?;
+        snippets::validate(&settings.snippets)?;
+        bind::validate(&settings.mcp_bind)?;
+        tls::validate(&settings.mcp_tls)?;
+        origins::validate(&settings.allowed_origins)?;
+        settings.png_export.validate()?;
+        watermark::validate(&settings.export_watermark)?;
+        Ok(settings)
+    }
+}
+
+pub struct SettingsStore {
+    path: Option<PathBuf>,
+    current: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    pub fn load(app: &tauri::AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_config_dir()
+            .ok()
+            .map(|dir| dir.join(SETTINGS_FILE));
+        let current = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    log::warn!("Ignoring unreadable settings file: {}", e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            current: Mutex::new(current),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.current
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn update(&self, patch: serde_json::Value) -> Result<Settings, String> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        let updated = current.merged(patch)?;
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| format!("Failed to create config dir: {}", e))?;
+            }
+            let json = serde_json::to_string_pretty(&updated).map_err(|e| e.to_string())?;
+            std::fs::write(path, json).map_err(|e| format!("Failed to save settings: {}", e))?;
+        }
+        *current = updated.clone();
+        Ok(updated)
+    }
+}
+
+/// Current settings, for code that only holds an app handle.
+pub fn current(app: &tauri::AppHandle) -> Settings {
+    app.state::<SettingsStore>().get()
+}
+
+// --- Tauri commands ---
+
+#[tauri::command]
+pub fn get_settings(store: tauri::State<'_, SettingsStore>) -> Settings {
+    store.get()
+}
+
+#[tauri::command]
+pub fn update_settings(
+    patch: serde_json::Value,
+    store: tauri::State<'_, SettingsStore>,
+) -> Result<Settings, String> {
+    store.update(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_applies_partial_patch() {
+        let settings = Settings::default()
+            .merged(serde_json::json!({ "bindingRepairPolicy": "remove" }))
+            .unwrap();
+        assert_eq!(settings.binding_repair_policy, BindingRepairPolicy::Remove);
+    }
+
+    #[test]
+    fn merge_rejects_invalid_values() {
+        assert!(Settings::default()
+            .merged(serde_json::json!({ "bindingRepairPolicy": "explode" }))
+            .is_err());
+        assert!(Settings::default().merged(serde_json::json!(42)).is_err());
+    }
+
+    #[test]
+    fn merge_replaces_shape_defaults_profile() {
+        let settings = Settings::default()
+            .merged(serde_json::json!({
+                "shapeDefaults": { "types": { "sticky": { "stickyColor": "#bbdefb" } } }
+            }))
+            .unwrap();
+        assert_eq!(
+            settings.shape_defaults.resolve("sticky").sticky_color.as_deref(),
+            Some("#bbdefb")
+        );
+    }
+
+    #[test]
+    fn merge_rejects_invalid_schedules() {
+        let patch =
+            |cron: &str| serde_json::json!({ "schedule": [{ "job": "digest", "cron": cron }] });
+        assert!(Settings::default().merged(patch("0 9 * * *")).is_ok());
+        assert!(Settings::default().merged(patch("every morning")).is_err());
+    }
+
+    #[test]
+    fn merge_rejects_unknown_tool_profiles() {
+        let patch = |profile: &str| {
+            serde_json::json!({ "toolExposure": { "clients": { "Cursor": profile } } })
+        };
+        assert!(Settings::default().merged(patch("read-only")).is_ok());
+        assert!(Settings::default().merged(patch("readonly")).is_err());
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let settings: Settings = serde_json::from_str("{}").unwrap();
+        assert_eq!(settings, Settings::default());
+    }
+}
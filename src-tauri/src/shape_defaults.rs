@@ -0,0 +1,326 @@
+//! Default properties for shapes created by agents.
+//!
+//! The built-in values mirror `buildShapeFromParams` in
+//! `src/lib/api/handler.ts`. A profile in settings can override them for all
+//! shapes or per shape type; omitted `create_shape` arguments are filled in
+//! from the resolved profile before the call reaches the webview, and the
+//! `create_shape` schema advertises the same values.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Shape types `create_shape` accepts.
+const CREATABLE_TYPES: [&str; 10] = [
+    "rectangle",
+    "ellipse",
+    "triangle",
+    "diamond",
+    "hexagon",
+    "star",
+    "cloud",
+    "cylinder",
+    "sticky",
+    "text",
+];
+
+/// Schema properties whose descriptions carry a default, with their labels.
+const DESCRIBED_PROPS: [(&str, &str); 9] = [
+    ("width", "Width"),
+    ("height", "Height"),
+    ("strokeColor", "Stroke color"),
+    ("strokeWidth", "Stroke width"),
+    ("fillColor", "Fill color"),
+    ("opacity", "Opacity 0-1"),
+    ("roughness", "Roughness 0-3"),
+    ("fontSize", "Font size for text shapes"),
+    ("stickyColor", "Sticky note background color"),
+];
+
+/// A set of shape properties; unset fields fall through to the next layer.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ShapeProps {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stroke_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stroke_width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stroke_style: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill_style: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roughness: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sticky_color: Option<String>,
+}
+
+impl ShapeProps {
+    /// `other`'s set fields take precedence over ours.
+    fn overlay(self, other: &ShapeProps) -> ShapeProps {
+        ShapeProps {
+            width: other.width.or(self.width),
+            height: other.height.or(self.height),
+            stroke_color: other.stroke_color.clone().or(self.stroke_color),
+            stroke_width: other.stroke_width.or(self.stroke_width),
+            stroke_style: other.stroke_style.clone().or(self.stroke_style),
+            fill_color: other.fill_color.clone().or(self.fill_color),
+            fill_style: other.fill_style.clone().or(self.fill_style),
+            opacity: other.opacity.or(self.opacity),
+            roughness: other.roughness.or(self.roughness),
+            font_size: other.font_size.or(self.font_size),
+            sticky_color: other.sticky_color.clone().or(self.sticky_color),
+        }
+    }
+
+    fn to_map(&self) -> Map<String, Value> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => map,
+            _ => Map::new(),
+        }
+    }
+}
+
+/// The user's defaults profile, stored in settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ShapeDefaults {
+    /// Overrides for every shape type.
+    pub all: ShapeProps,
+    /// Overrides for one shape type, applied after `all`.
+    pub types: BTreeMap<String, ShapeProps>,
+}
+
+impl ShapeDefaults {
+    /// Effective defaults for a shape type.
+    pub fn resolve(&self, shape_type: &str) -> ShapeProps {
+        let mut props = builtin(shape_type).overlay(&self.all);
+        if let Some(overrides) = self.types.get(shape_type) {
+            props = props.overlay(overrides);
+        }
+        if !has_box(shape_type) {
+            // Lines take their length from x2/y2; a width default would
+            // silently stretch them.
+            props.width = None;
+            props.height = None;
+        }
+        props
+    }
+
+    /// Fill omitted properties of a shape-creation payload in place.
+    pub fn fill(&self, shape: &mut Value) {
+        let Some(shape_type) = shape.get("type").and_then(|t| t.as_str()) else {
+            return;
+        };
+        let defaults = self.resolve(shape_type).to_map();
+        if let Some(obj) = shape.as_object_mut() {
+            for (key, value) in defaults {
+                obj.entry(key).or_insert(value);
+            }
+        }
+    }
+}
+
+fn has_box(shape_type: &str) -> bool {
+    !matches!(shape_type, "line" | "arrow" | "freedraw")
+}
+
+fn builtin(shape_type: &str) -> ShapeProps {
+    let common = ShapeProps {
+        stroke_color: Some("#000000".to_string()),
+        stroke_width: Some(2.0),
+        stroke_style: Some("solid".to_string()),
+        fill_color: Some("transparent".to_string()),
+        fill_style: Some("hachure".to_string()),
+        opacity: Some(1.0),
+        roughness: Some(1.0),
+        ..ShapeProps::default()
+    };
+    match shape_type {
+        "sticky" => ShapeProps {
+            width: Some(200.0),
+            height: Some(200.0),
+            font_size: Some(16.0),
+            sticky_color: Some("#fff9c4".to_string()),
+            ..common
+        },
+        "text" => ShapeProps {
+            width: Some(200.0),
+            height: Some(30.0),
+            font_size: Some(20.0),
+            ..common
+        },
+        _ => ShapeProps {
+            width: Some(200.0),
+            height: Some(150.0),
+            ..common
+        },
+    }
+}
+
+/// Fill in defaults for tools that create shapes.
+pub fn apply(defaults: &ShapeDefaults, tool_name: &str, arguments: &mut Value) {
+    match tool_name {
+        "create_shape" => defaults.fill(arguments),
+        "batch_operations" => {
+            let ops = arguments
+                .get_mut("operations")
+                .and_then(|o| o.as_array_mut());
+            for op in ops.into_iter().flatten() {
+                if op.get("action").and_then(|a| a.as_str()) == Some("create") {
+                    if let Some(data) = op.get_mut("data") {
+                        defaults.fill(data);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if f.fract() == 0.0 => format!("{}", f as i64),
+            _ => n.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+/// Rewrite the `create_shape` property descriptions to state the effective
+/// defaults, e.g. "Height (default: 150; sticky: 200, text: 30)".
+pub fn describe_tools(mut tools: Value, defaults: &ShapeDefaults) -> Value {
+    let resolved: Vec<(&str, Map<String, Value>)> = CREATABLE_TYPES
+        .iter()
+        .map(|t| (*t, defaults.resolve(t).to_map()))
+        .collect();
+
+    let create = tools.as_array_mut().and_then(|list| {
+        list.iter_mut()
+            .find(|t| t.get("name").and_then(|n| n.as_str()) == Some("create_shape"))
+    });
+    let Some(props) = create
+        .and_then(|t| t.pointer_mut("/inputSchema/properties"))
+        .and_then(|p| p.as_object_mut())
+    else {
+        return tools;
+    };
+
+    for (key, label) in DESCRIBED_PROPS {
+        // Group shape types by their default; the largest group is "the"
+        // default and the rest are listed as exceptions.
+        let mut groups: Vec<(String, Vec<&str>)> = Vec::new();
+        for (shape_type, map) in &resolved {
+            let Some(value) = map.get(key) else { continue };
+            let shown = display(value);
+            match groups.iter_mut().find(|(v, _)| *v == shown) {
+                Some((_, types)) => types.push(shape_type),
+                None => groups.push((shown, vec![shape_type])),
+            }
+        }
+        let Some(main) = (0..groups.len()).max_by_key(|&i| (groups[i].1.len(), usize::MAX - i))
+        else {
+            continue;
+        };
+        let mut text = format!("{} (default: {}", label, groups[main].0);
+        let exceptions: Vec<String> = groups
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != main)
+            .flat_map(|(_, (value, types))| types.iter().map(move |t| format!("{}: {}", t, value)))
+            .collect();
+        if !exceptions.is_empty() {
+            text.push_str("; ");
+            text.push_str(&exceptions.join(", "));
+        }
+        text.push(')');
+        if let Some(prop) = props.get_mut(key) {
+            prop["description"] = Value::String(text);
+        }
+    }
+    tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn profile() -> ShapeDefaults {
+        serde_json::from_value(json!({
+            "all": { "strokeColor": "#1e88e5", "roughness": 0 },
+            "types": { "ellipse": { "width": 120, "height": 120 } }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn profile_layers_over_builtins() {
+        let d = profile();
+        let ellipse = d.resolve("ellipse");
+        assert_eq!(ellipse.width, Some(120.0));
+        assert_eq!(ellipse.stroke_color.as_deref(), Some("#1e88e5"));
+        assert_eq!(ellipse.roughness, Some(0.0));
+        assert_eq!(d.resolve("rectangle").width, Some(200.0));
+        assert_eq!(d.resolve("sticky").height, Some(200.0));
+        assert_eq!(d.resolve("arrow").width, None);
+    }
+
+    #[test]
+    fn fill_keeps_explicit_arguments() {
+        let mut args = json!({ "type": "ellipse", "x": 0, "y": 0, "width": 50 });
+        apply(&profile(), "create_shape", &mut args);
+        assert_eq!(args["width"], 50);
+        assert_eq!(args["height"], 120.0);
+        assert_eq!(args["strokeColor"], "#1e88e5");
+    }
+
+    #[test]
+    fn batch_creates_get_defaults() {
+        let mut args = json!({ "operations": [
+            { "action": "create", "data": { "type": "text", "x": 0, "y": 0 } },
+            { "action": "update", "data": { "id": "a", "x": 5 } }
+        ]});
+        apply(&ShapeDefaults::default(), "batch_operations", &mut args);
+        assert_eq!(args["operations"][0]["data"]["fontSize"], 20.0);
+        assert!(args["operations"][1]["data"].get("strokeColor").is_none());
+    }
+
+    #[test]
+    fn schema_descriptions_reflect_profile() {
+        let tools = json!([{ "name": "create_shape", "inputSchema": { "properties": {
+            "width": { "type": "number" },
+            "height": { "type": "number" },
+            "strokeColor": { "type": "string" }
+        }}}]);
+        let tools = describe_tools(tools, &profile());
+        let props = &tools[0]["inputSchema"]["properties"];
+        assert_eq!(
+            props["strokeColor"]["description"],
+            "Stroke color (default: #1e88e5)"
+        );
+        assert_eq!(
+            props["height"]["description"],
+            "Height (default: 150; ellipse: 120, sticky: 200, text: 30)"
+        );
+    }
+
+    #[test]
+    fn unknown_profile_keys_are_rejected() {
+        assert!(
+            serde_json::from_value::<ShapeDefaults>(json!({ "all": { "colour": "red" } })).is_err()
+        );
+    }
+}
@@ -0,0 +1,555 @@
+//! Access-controlled share links for the live board viewer.
+//!
+//! Unlike embed tokens (stateless, signed), share links live in a registry so
+//! they can be listed, password protected, counted and revoked. Links are kept
+//! in memory and disappear when the app restarts.
+//!
+//! Share passwords are kept as Argon2id hashes, with the cost documents are
+//! encrypted with (`encryption.rs`) and a salt per link. Hashing is slow on
+//! purpose, so it runs on a blocking worker, never under the registry lock,
+//! and only a couple of password checks run at once.
+
+use crate::api::SharedApiState;
+use crate::embed::{fetch_shapes, now_secs};
+use crate::encryption;
+use crate::export::{self, InteractiveExportOptions, InteractiveFormat};
+use crate::watermark;
+use argon2::password_hash::{self, PasswordHasher, PasswordVerifier, SaltString};
+use axum::{
+    extract::{Form, Path, Query, State as AxumState},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use tauri::Emitter;
+use tokio::sync::Semaphore;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// --- Registry ---
+
+#[derive(Debug, Clone)]
+pub struct ShareLink {
+    pub id: String,
+    pub label: Option<String>,
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    pub lock_viewport: bool,
+    pub viewer_count: u64,
+    pub last_viewed_at: Option<u64>,
+    pub revoked: bool,
+    password: Option<PasswordHash>,
+}
+
+/// A share password as an Argon2id PHC string, which carries its own salt
+/// and cost.
+#[derive(Debug, Clone)]
+pub struct PasswordHash {
+    phc: String,
+}
+
+impl PasswordHash {
+    pub fn new(password: &str) -> Result<Self, String> {
+        Self::with_cost(password, encryption::ARGON2_COST)
+    }
+
+    fn with_cost(password: &str, cost: encryption::Argon2Cost) -> Result<Self, String> {
+        let salt =
+            SaltString::encode_b64(uuid::Uuid::new_v4().as_bytes()).map_err(|e| e.to_string())?;
+        let phc = encryption::argon2id(cost, 32)?
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| format!("Failed to hash the password: {}", e))?
+            .to_string();
+        Ok(Self { phc })
+    }
+
+    /// Whether `password` is the one hashed, compared in constant time.
+    fn matches(&self, password: &str) -> bool {
+        let Ok(hash) = password_hash::PasswordHash::new(&self.phc) else {
+            return false;
+        };
+        argon2::Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    }
+}
+
+/// At most this many password checks hash at once, so a burst of guesses
+/// cannot claim all memory and blocking workers.
+static PASSWORD_CHECKS: Semaphore = Semaphore::const_new(2);
+
+/// Check `password` against `hash` off the async runtime.
+async fn verify(hash: PasswordHash, password: String) -> bool {
+    let Ok(_permit) = PASSWORD_CHECKS.acquire().await else {
+        return false;
+    };
+    tokio::task::spawn_blocking(move || hash.matches(&password))
+        .await
+        .unwrap_or(false)
+}
+
+/// What the frontend sees: everything except the password hash.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareLinkInfo {
+    pub id: String,
+    pub label: Option<String>,
+    pub url: Option<String>,
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    pub has_password: bool,
+    pub lock_viewport: bool,
+    pub viewer_count: u64,
+    pub last_viewed_at: Option<u64>,
+    pub revoked: bool,
+    pub expired: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShareAccessError {
+    NotFound,
+    Revoked,
+    Expired,
+    PasswordRequired,
+    WrongPassword,
+}
+
+impl std::fmt::Display for ShareAccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShareAccessError::NotFound => write!(f, "Share link not found"),
+            ShareAccessError::Revoked => write!(f, "Share link has been revoked"),
+            ShareAccessError::Expired => write!(f, "Share link has expired"),
+            ShareAccessError::PasswordRequired => write!(f, "Password required"),
+            ShareAccessError::WrongPassword => write!(f, "Incorrect password"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ShareRegistry {
+    links: HashMap<String, ShareLink>,
+}
+
+impl ShareRegistry {
+    pub fn create(
+        &mut self,
+        label: Option<String>,
+        password: Option<PasswordHash>,
+        ttl_secs: Option<u64>,
+        lock_viewport: bool,
+        now: u64,
+    ) -> ShareLink {
+        let link = ShareLink {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            label,
+            created_at: now,
+            expires_at: ttl_secs.map(|ttl| now.saturating_add(ttl)),
+            lock_viewport,
+            viewer_count: 0,
+            last_viewed_at: None,
+            revoked: false,
+            password,
+        };
+        self.links.insert(link.id.clone(), link.clone());
+        link
+    }
+
+    pub fn list(&self) -> Vec<&ShareLink> {
+        let mut links: Vec<&ShareLink> = self.links.values().collect();
+        links.sort_by_key(|l| l.created_at);
+        links
+    }
+
+    pub fn revoke(&mut self, id: &str) -> bool {
+        match self.links.get_mut(id) {
+            Some(link) => {
+                link.revoked = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Check whether a link exists and may still be viewed, leaving any
+    /// password to the caller.
+    pub fn check_link(&self, id: &str, now: u64) -> Result<&ShareLink, ShareAccessError> {
+        let link = self.links.get(id).ok_or(ShareAccessError::NotFound)?;
+        if link.revoked {
+            return Err(ShareAccessError::Revoked);
+        }
+        if link.expires_at.is_some_and(|exp| exp <= now) {
+            return Err(ShareAccessError::Expired);
+        }
+        Ok(link)
+    }
+
+    /// Check whether a link may be viewed. `password` is only consulted for
+    /// protected links, and hashed right here: async callers use
+    /// `check_link` and `verify` instead.
+    pub fn check_access(
+        &self,
+        id: &str,
+        password: Option<&str>,
+        now: u64,
+    ) -> Result<&ShareLink, ShareAccessError> {
+        let link = self.check_link(id, now)?;
+        if let Some(hash) = &link.password {
+            match password {
+                None => return Err(ShareAccessError::PasswordRequired),
+                Some(p) if !hash.matches(p) => return Err(ShareAccessError::WrongPassword),
+                Some(_) => {}
+            }
+        }
+        Ok(link)
+    }
+
+    /// Count a successful page view and return the updated link.
+    pub fn record_view(&mut self, id: &str, now: u64) -> Option<ShareLink> {
+        let link = self.links.get_mut(id)?;
+        link.viewer_count += 1;
+        link.last_viewed_at = Some(now);
+        Some(link.clone())
+    }
+}
+
+impl ShareLink {
+    /// Describe the link, with its URL under `origin` while the server runs.
+    pub fn info(&self, origin: Option<&str>, now: u64) -> ShareLinkInfo {
+        ShareLinkInfo {
+            id: self.id.clone(),
+            label: self.label.clone(),
+            url: origin.map(|origin| format!("{}/share/{}", origin, self.id)),
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+            has_password: self.password.is_some(),
+            lock_viewport: self.lock_viewport,
+            viewer_count: self.viewer_count,
+            last_viewed_at: self.last_viewed_at,
+            revoked: self.revoked,
+            expired: self.expires_at.is_some_and(|exp| exp <= now),
+        }
+    }
+}
+
+/// Access key handed to a viewer after it passed the password check, so the
+/// live refresh requests don't have to resend the password.
+fn access_key(signing_key: &[u8], id: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(signing_key).expect("HMAC accepts any key length");
+    mac.update(b"share:");
+    mac.update(id.as_bytes());
+    URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
+
+fn access_key_matches(signing_key: &[u8], id: &str, candidate: &str) -> bool {
+    let Ok(candidate) = URL_SAFE_NO_PAD.decode(candidate) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(signing_key).expect("HMAC accepts any key length");
+    mac.update(b"share:");
+    mac.update(id.as_bytes());
+    mac.verify_slice(&candidate).is_ok()
+}
+
+// --- Tauri commands ---
+
+#[tauri::command]
+pub async fn create_share_link(
+    label: Option<String>,
+    password: Option<String>,
+    ttl_secs: Option<u64>,
+    lock_viewport: Option<bool>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<ShareLinkInfo, String> {
+    let password = match password.filter(|p| !p.is_empty()) {
+        Some(password) => Some(
+            tokio::task::spawn_blocking(move || PasswordHash::new(&password))
+                .await
+                .map_err(|e| e.to_string())??,
+        ),
+        None => None,
+    };
+    let origin = state.server_origin().await;
+    let now = now_secs();
+    let mut shares = state.shares.lock().await;
+    let link = shares.create(
+        label,
+        password,
+        ttl_secs,
+        lock_viewport.unwrap_or(false),
+        now,
+    );
+    Ok(link.info(origin.as_deref(), now))
+}
+
+#[tauri::command]
+pub async fn list_share_links(
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<Vec<ShareLinkInfo>, String> {
+    let origin = state.server_origin().await;
+    let now = now_secs();
+    let shares = state.shares.lock().await;
+    Ok(shares
+        .list()
+        .into_iter()
+        .map(|l| l.info(origin.as_deref(), now))
+        .collect())
+}
+
+#[tauri::command]
+pub async fn revoke_share_link(
+    id: String,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<(), String> {
+    let mut shares = state.shares.lock().await;
+    if shares.revoke(&id) {
+        Ok(())
+    } else {
+        Err(format!("Share link not found: {}", id))
+    }
+}
+
+// --- HTTP handlers ---
+
+#[derive(Deserialize)]
+pub struct PasswordForm {
+    password: String,
+}
+
+#[derive(Deserialize)]
+pub struct AccessQuery {
+    access: Option<String>,
+}
+
+pub async fn share_page_handler(
+    AxumState(state): AxumState<SharedApiState>,
+    Path(id): Path<String>,
+) -> Response {
+    serve_share_page(state, id, None).await
+}
+
+pub async fn share_password_handler(
+    AxumState(state): AxumState<SharedApiState>,
+    Path(id): Path<String>,
+    Form(form): Form<PasswordForm>,
+) -> Response {
+    serve_share_page(state, id, Some(form.password)).await
+}
+
+pub async fn share_content_handler(
+    AxumState(state): AxumState<SharedApiState>,
+    Path(id): Path<String>,
+    Query(query): Query<AccessQuery>,
+) -> Response {
+    {
+        let shares = state.shares.lock().await;
+        match shares.check_access(&id, None, now_secs()) {
+            Ok(_) => {}
+            Err(ShareAccessError::PasswordRequired) => {
+                let ok = query
+                    .access
+                    .as_deref()
+                    .is_some_and(|a| access_key_matches(&state.embed_key, &id, a));
+                if !ok {
+                    return access_error_response(ShareAccessError::PasswordRequired);
+                }
+            }
+            Err(e) => return access_error_response(e),
+        }
+    }
+
+    match fetch_shapes(&state).await {
+        Ok(shapes) => (
+            [(header::CONTENT_TYPE, "image/svg+xml; charset=utf-8")],
+            export::render_content(&shapes),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e).into_response(),
+    }
+}
+
+async fn serve_share_page(state: SharedApiState, id: String, password: Option<String>) -> Response {
+    let now = now_secs();
+    let hash = match state.shares.lock().await.check_link(&id, now) {
+        Ok(link) => link.password.clone(),
+        Err(e) => return access_error_response(e),
+    };
+    if let Some(hash) = &hash {
+        let checked = match password {
+            None => Err(ShareAccessError::PasswordRequired),
+            Some(p) if verify(hash.clone(), p.clone()).await => Ok(()),
+            Some(_) => Err(ShareAccessError::WrongPassword),
+        };
+        if let Err(e) = checked {
+            let status = match e {
+                ShareAccessError::WrongPassword => StatusCode::UNAUTHORIZED,
+                _ => StatusCode::OK,
+            };
+            return (
+                status,
+                Html(password_page(&id, e == ShareAccessError::WrongPassword)),
+            )
+                .into_response();
+        }
+    }
+    let access = hash.map(|_| access_key(&state.embed_key, &id));
+    // The link may have been revoked while the password was checked
+    let link = {
+        let mut shares = state.shares.lock().await;
+        if let Err(e) = shares.check_link(&id, now) {
+            return access_error_response(e);
+        }
+        match shares.record_view(&id, now) {
+            Some(link) => link,
+            None => return access_error_response(ShareAccessError::NotFound),
+        }
+    };
+
+    let _ = state.app_handle.emit(
+        "share-link-viewed",
+        serde_json::json!({
+            "id": link.id,
+            "label": link.label,
+            "viewerCount": link.viewer_count,
+            "viewedAt": now,
+        }),
+    );
+
+    let shapes = match fetch_shapes(&state).await {
+        Ok(s) => s,
+        Err(e) => return (StatusCode::BAD_GATEWAY, e).into_response(),
+    };
+
+    let refresh_url = match access {
+        Some(key) => format!("/share/{}/content?access={}", id, key),
+        None => format!("/share/{}/content", id),
+    };
+    let options = InteractiveExportOptions {
+        format: Some(InteractiveFormat::Html),
+        title: link.label.clone(),
+        lock_viewport: link.lock_viewport,
+        refresh_url: Some(refresh_url),
+        watermark: watermark::resolve(&state.app_handle, None),
+        ..Default::default()
+    };
+    Html(export::render_interactive(&shapes, &options)).into_response()
+}
+
+fn access_error_response(err: ShareAccessError) -> Response {
+    let status = match err {
+        ShareAccessError::NotFound => StatusCode::NOT_FOUND,
+        ShareAccessError::Revoked | ShareAccessError::Expired => StatusCode::GONE,
+        ShareAccessError::PasswordRequired | ShareAccessError::WrongPassword => {
+            StatusCode::UNAUTHORIZED
+        }
+    };
+    (status, err.to_string()).into_response()
+}
+
+fn password_page(id: &str, wrong: bool) -> String {
+    let notice = if wrong {
+        "<p class=\"error\">Incorrect password, try again.</p>"
+    } else {
+        ""
+    };
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Napkin board</title>\n<style>\nbody {{ font-family: Arial, sans-serif; display: flex; align-items: center; justify-content: center; height: 100vh; margin: 0; color: #333; }}\nform {{ display: flex; gap: 8px; }}\ninput {{ padding: 6px 10px; border: 1px solid #ccc; border-radius: 4px; }}\nbutton {{ padding: 6px 12px; border: 0; border-radius: 4px; background: #1a73e8; color: #fff; }}\n.error {{ color: #c62828; }}\n</style>\n</head>\n<body>\n<div>\n<p>This board is password protected.</p>\n{notice}\n<form method=\"post\" action=\"/share/{id}\">\n<input type=\"password\" name=\"password\" placeholder=\"Password\" autofocus>\n<button type=\"submit\">View</button>\n</form>\n</div>\n</body>\n</html>\n",
+        notice = notice,
+        id = id,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn password_protected_link_requires_correct_password() {
+        let mut registry = ShareRegistry::default();
+        let cheap = encryption::Argon2Cost {
+            memory_kib: 64,
+            passes: 1,
+            lanes: 1,
+        };
+        let hash = PasswordHash::with_cost("hunter2", cheap).unwrap();
+        assert!(hash.phc.starts_with("$argon2id$"));
+        assert!(!hash.phc.contains("hunter2"));
+        // A fresh salt per link
+        assert_ne!(
+            PasswordHash::with_cost("hunter2", cheap).unwrap().phc,
+            hash.phc
+        );
+        let link = registry.create(None, Some(hash), None, false, 100);
+        assert_eq!(
+            registry.check_access(&link.id, None, 100).unwrap_err(),
+            ShareAccessError::PasswordRequired
+        );
+        assert_eq!(
+            registry
+                .check_access(&link.id, Some("nope"), 100)
+                .unwrap_err(),
+            ShareAccessError::WrongPassword
+        );
+        assert!(registry
+            .check_access(&link.id, Some("hunter2"), 100)
+            .is_ok());
+    }
+
+    #[test]
+    fn expired_and_revoked_links_are_rejected() {
+        let mut registry = ShareRegistry::default();
+        let expiring = registry.create(None, None, Some(60), false, 100);
+        assert!(registry.check_access(&expiring.id, None, 159).is_ok());
+        assert_eq!(
+            registry.check_access(&expiring.id, None, 160).unwrap_err(),
+            ShareAccessError::Expired
+        );
+
+        let revoked = registry.create(None, None, None, false, 100);
+        assert!(registry.revoke(&revoked.id));
+        assert_eq!(
+            registry.check_access(&revoked.id, None, 100).unwrap_err(),
+            ShareAccessError::Revoked
+        );
+        assert!(!registry.revoke("missing"));
+    }
+
+    #[test]
+    fn huge_lifetimes_never_expire_early() {
+        let mut registry = ShareRegistry::default();
+        let link = registry.create(None, None, Some(u64::MAX), false, 100);
+        assert_eq!(link.expires_at, Some(u64::MAX));
+        assert!(registry.check_access(&link.id, None, u64::MAX - 1).is_ok());
+        assert!(!link.info(None, 100).expired);
+    }
+
+    #[test]
+    fn views_are_counted() {
+        let mut registry = ShareRegistry::default();
+        let link = registry.create(Some("Demo".into()), None, None, true, 100);
+        registry.record_view(&link.id, 110);
+        let updated = registry.record_view(&link.id, 120).unwrap();
+        assert_eq!(updated.viewer_count, 2);
+        assert_eq!(updated.last_viewed_at, Some(120));
+
+        let info = updated.info(Some("https://192.168.1.20:21420"), 130);
+        assert_eq!(
+            info.url.as_deref(),
+            Some(&*format!("https://192.168.1.20:21420/share/{}", link.id))
+        );
+        assert_eq!(updated.info(None, 130).url, None);
+        assert!(!info.has_password);
+    }
+
+    #[test]
+    fn access_keys_are_bound_to_link_id() {
+        let key = crate::embed::generate_key();
+        let access = access_key(&key, "abc");
+        assert!(access_key_matches(&key, "abc", &access));
+        assert!(!access_key_matches(&key, "abd", &access));
+    }
+}
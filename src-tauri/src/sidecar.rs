@@ -0,0 +1,1004 @@
+//! Out-of-process MCP server (`napkin --mcp-sidecar`).
+//!
+//! With `mcpSidecar` enabled, `start_api_server` launches this binary again
+//! as a child process that owns the listening socket. The child forwards each
+//! HTTP request to the app over its stdin/stdout as newline-delimited JSON
+//! frames, and the app answers them with the same router it would serve
+//! in-process. A webview that stops answering then costs clients a timeout
+//! rather than a stalled listener, and a crashed child is restarted by the
+//! supervisor here without the app going down with it.
+
+use axum::{
+    body::Body,
+    extract::{ws::WebSocketUpgrade, Query, Request, State as AxumState},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+use tokio::sync::{mpsc, oneshot, watch};
+use tower::ServiceExt;
+
+use crate::api::{self, LegacyMessageQuery, SharedApiState, DEFAULT_PORT, PORT_FALLBACK_ATTEMPTS};
+use crate::approval;
+use crate::auth;
+use crate::bind;
+use crate::canvas_events::{self, CanvasEvents};
+use crate::connections;
+use crate::hosts;
+use crate::legacy_sse::{self, Heartbeat, SseSessions};
+use crate::session::SESSION_HEADER;
+use crate::settings;
+use crate::stdio;
+use crate::subscriptions::Notifier;
+use crate::tls::{self, TlsFiles};
+
+pub(crate) const SIDECAR_FLAG: &str = "--mcp-sidecar";
+const PORT_ENV: &str = "NAPKIN_SIDECAR_PORT";
+const TOKEN_ENV: &str = "NAPKIN_SIDECAR_TOKEN";
+const HEARTBEAT_ENV: &str = "NAPKIN_SIDECAR_HEARTBEAT";
+/// Comma-separated `allowedHosts`.
+const HOSTS_ENV: &str = "NAPKIN_SIDECAR_HOSTS";
+const BIND_ENV: &str = "NAPKIN_SIDECAR_BIND";
+/// Certificate and key files when serving HTTPS.
+const TLS_CERT_ENV: &str = "NAPKIN_SIDECAR_TLS_CERT";
+const TLS_KEY_ENV: &str = "NAPKIN_SIDECAR_TLS_KEY";
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longer than an approval prompt plus the bridge's own timeout, so the
+/// app's error wins the race.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(approval::APPROVAL_TIMEOUT_SECS + 30);
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+/// Give up after this many crashes within `RESTART_WINDOW`.
+const MAX_RESTARTS: usize = 5;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+/// One line on the IPC channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum Frame {
+    /// Child → app, once the socket is bound.
+    Listening { port: u16 },
+    /// Child → app.
+    Request {
+        id: u64,
+        method: String,
+        uri: String,
+        headers: Vec<(String, String)>,
+        /// Base64.
+        body: String,
+    },
+    /// App → child.
+    Response {
+        id: u64,
+        status: u16,
+        headers: Vec<(String, String)>,
+        /// Base64.
+        body: String,
+    },
+    /// App → child: a notification for the client holding `session`.
+    Notify {
+        session: String,
+        message: serde_json::Value,
+    },
+    /// App → child: a canvas event for every stream.
+    Broadcast { message: serde_json::Value },
+    /// App → child: stop accepting and exit.
+    Shutdown,
+}
+
+fn encode(frame: &Frame) -> String {
+    let mut line = serde_json::to_string(frame).unwrap_or_default();
+    line.push('\n');
+    line
+}
+
+/// Connection-level headers that must not be copied between hops.
+fn is_hop_by_hop(name: &str) -> bool {
+    matches!(
+        name.to_ascii_lowercase().as_str(),
+        "connection" | "keep-alive" | "transfer-encoding" | "upgrade" | "content-length"
+    )
+}
+
+fn header_pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| !is_hop_by_hop(name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+fn apply_headers(target: &mut HeaderMap, pairs: &[(String, String)]) {
+    for (name, value) in pairs {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            target.append(name, value);
+        }
+    }
+}
+
+// --- Child process ---
+
+struct HttpReply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// The child's handle on the app.
+struct Parent {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, oneshot::Sender<HttpReply>>>,
+    out: mpsc::UnboundedSender<Frame>,
+}
+
+impl Parent {
+    async fn request(
+        &self,
+        method: &str,
+        uri: &str,
+        headers: Vec<(String, String)>,
+        body: &[u8],
+    ) -> Result<HttpReply, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, tx);
+        let sent = self.out.send(Frame::Request {
+            id,
+            method: method.to_string(),
+            uri: uri.to_string(),
+            headers,
+            body: STANDARD.encode(body),
+        });
+        let reply = match sent {
+            Ok(()) => tokio::time::timeout(REPLY_TIMEOUT, rx)
+                .await
+                .map_err(|_| "Napkin did not respond in time".to_string())
+                .and_then(|r| r.map_err(|_| "Napkin closed the sidecar channel".to_string())),
+            Err(_) => Err("Napkin closed the sidecar channel".to_string()),
+        };
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+        reply
+    }
+
+    fn complete(&self, id: u64, reply: HttpReply) {
+        let waiter = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+        if let Some(tx) = waiter {
+            let _ = tx.send(reply);
+        }
+    }
+}
+
+struct ChildState {
+    parent: Parent,
+    token: String,
+    shutdown: watch::Receiver<bool>,
+    /// Keep-alive interval for SSE streams.
+    heartbeat: Duration,
+    /// `allowedHosts` from the app's settings.
+    allowed_hosts: Vec<String>,
+    /// Listening beyond loopback (see `bind.rs`).
+    lan: bool,
+    /// Legacy SSE streams, each forwarding as its own HTTP session.
+    legacy: Arc<tokio::sync::Mutex<SseSessions<SharedConnection>>>,
+    /// Where notifications for each HTTP session go.
+    streams: Mutex<HashMap<String, Notifier>>,
+    /// Canvas events relayed by the app, for every stream.
+    canvas_events: CanvasEvents,
+}
+
+impl ChildState {
+    fn attach(&self, session: &str, stream: Notifier) {
+        self.streams
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(session.to_string(), stream);
+    }
+
+    fn notify(&self, session: &str, message: serde_json::Value) {
+        let mut streams = self.streams.lock().unwrap_or_else(|e| e.into_inner());
+        let closed = streams
+            .get(session)
+            .is_some_and(|stream| stream.send(message).is_err());
+        if closed {
+            streams.remove(session);
+        }
+    }
+}
+
+type SharedChildState = Arc<ChildState>;
+
+fn unavailable(error: String) -> Response {
+    (StatusCode::GATEWAY_TIMEOUT, error).into_response()
+}
+
+async fn check_host(
+    AxumState(state): AxumState<SharedChildState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    hosts::filter(request, next, &state.allowed_hosts, state.lan).await
+}
+
+/// Everything but the streaming routes is answered by the app.
+async fn forward_http(AxumState(state): AxumState<SharedChildState>, request: Request) -> Response {
+    let (parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => return (StatusCode::PAYLOAD_TOO_LARGE, e.to_string()).into_response(),
+    };
+    let uri = parts
+        .uri
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or("/");
+    match state
+        .parent
+        .request(
+            parts.method.as_str(),
+            uri,
+            header_pairs(&parts.headers),
+            &body,
+        )
+        .await
+    {
+        Ok(reply) => {
+            let mut response = Response::new(Body::from(reply.body));
+            *response.status_mut() =
+                StatusCode::from_u16(reply.status).unwrap_or(StatusCode::BAD_GATEWAY);
+            apply_headers(response.headers_mut(), &reply.headers);
+            response
+        }
+        Err(e) => unavailable(e),
+    }
+}
+
+/// WebSocket frames are forwarded one by one as `POST /mcp`.
+async fn forward_ws(
+    AxumState(state): AxumState<SharedChildState>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if !auth::is_authorized(&headers, &state.token) {
+        return auth::unauthorized();
+    }
+    let shutdown = Some(state.shutdown.clone());
+    ws.on_upgrade(move |socket| async move {
+        // The app sees each frame as a POST, so the socket carries an HTTP
+        // session from `initialize` until it closes.
+        let (connection, notifications) = Connection::open();
+        canvas_events::forward(&state.canvas_events, connection.notifications.clone());
+        let handler_state = Arc::clone(&state);
+        let handler_connection = Arc::clone(&connection);
+        api::serve_json_rpc_socket(socket, shutdown, Some(notifications), move |body| {
+            let state = Arc::clone(&handler_state);
+            let connection = Arc::clone(&handler_connection);
+            async move { forward_message(&state, &connection, body).await }
+        })
+        .await;
+        close_connection(&state, &connection).await;
+    })
+}
+
+/// End the app-side session of a connection that closed.
+async fn close_connection(state: &ChildState, connection: &Connection) {
+    let id = connection
+        .session
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    if let Some(id) = id {
+        state
+            .streams
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+        let _ = state
+            .parent
+            .request("DELETE", "/mcp", mcp_headers(state, Some(&id)), b"")
+            .await;
+    }
+}
+
+/// A client connection carried to the app as one HTTP session.
+struct Connection {
+    session: Mutex<Option<String>>,
+    notifications: Notifier,
+}
+
+type SharedConnection = Arc<Connection>;
+
+impl Connection {
+    fn open() -> (SharedConnection, mpsc::UnboundedReceiver<serde_json::Value>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let connection = Arc::new(Connection {
+            session: Mutex::new(None),
+            notifications: tx,
+        });
+        (connection, rx)
+    }
+}
+
+fn mcp_headers(state: &ChildState, session: Option<&str>) -> Vec<(String, String)> {
+    let mut headers = vec![
+        ("content-type".to_string(), "application/json".to_string()),
+        (
+            "authorization".to_string(),
+            format!("Bearer {}", state.token),
+        ),
+    ];
+    if let Some(id) = session {
+        headers.push((SESSION_HEADER.to_string(), id.to_string()));
+    }
+    headers
+}
+
+async fn forward_message(
+    state: &ChildState,
+    connection: &Connection,
+    body: serde_json::Value,
+) -> Option<serde_json::Value> {
+    let id = connection
+        .session
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let reply = state
+        .parent
+        .request(
+            "POST",
+            "/mcp",
+            mcp_headers(state, id.as_deref()),
+            body.to_string().as_bytes(),
+        )
+        .await;
+    if let Ok(reply) = &reply {
+        let issued = reply
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(SESSION_HEADER.as_str()));
+        if let Some((_, id)) = issued {
+            *connection.session.lock().unwrap_or_else(|e| e.into_inner()) = Some(id.clone());
+            state.attach(id, connection.notifications.clone());
+        }
+    }
+    let error = match reply {
+        Ok(reply) if reply.status == 202 => return None,
+        Ok(reply) if reply.status == 200 => {
+            return serde_json::from_slice(&reply.body).ok();
+        }
+        Ok(reply) => format!("Napkin returned HTTP {}", reply.status),
+        Err(e) => e,
+    };
+    let id = body.get("id").cloned().filter(|id| !id.is_null())?;
+    Some(api::mcp_error(Some(id), -32000, &error))
+}
+
+/// Legacy SSE streams are held here; their messages are forwarded like
+/// WebSocket frames.
+async fn legacy_stream(
+    AxumState(state): AxumState<SharedChildState>,
+    headers: HeaderMap,
+) -> Response {
+    if !auth::is_authorized(&headers, &state.token) {
+        return auth::unauthorized();
+    }
+    let (connection, notifications) = Connection::open();
+    canvas_events::forward(&state.canvas_events, connection.notifications.clone());
+    let sessions = Arc::clone(&state.legacy);
+    let shutdown = Some(state.shutdown.clone());
+    let heartbeat = state.heartbeat;
+    legacy_sse::connect(
+        sessions,
+        connection,
+        shutdown,
+        Some(notifications),
+        heartbeat,
+        move |connection| async move { close_connection(&state, &connection).await },
+    )
+    .await
+    .into_response()
+}
+
+async fn legacy_message(
+    AxumState(state): AxumState<SharedChildState>,
+    headers: HeaderMap,
+    Query(query): Query<LegacyMessageQuery>,
+    axum::Json(body): axum::Json<serde_json::Value>,
+) -> Response {
+    if !auth::is_authorized(&headers, &state.token) {
+        return auth::unauthorized();
+    }
+    let Some((stream, connection)) = state.legacy.lock().await.get(&query.session_id) else {
+        return (StatusCode::NOT_FOUND, "Unknown SSE session").into_response();
+    };
+    tokio::spawn(async move {
+        if let Some(reply) = forward_message(&state, &connection, body).await {
+            let _ = stream.send(legacy_sse::message_event(&reply));
+        }
+    });
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// `GET /mcp` is held here; notifications for its session arrive from the
+/// app as `Notify` frames, canvas events as `Broadcast` frames.
+async fn event_stream(
+    AxumState(state): AxumState<SharedChildState>,
+    headers: HeaderMap,
+) -> Response {
+    if !auth::is_authorized(&headers, &state.token) {
+        return auth::unauthorized();
+    }
+    let (tx, rx) = mpsc::unbounded_channel();
+    if let Some(session) = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()) {
+        state.attach(session, tx.clone());
+    }
+    canvas_events::forward(&state.canvas_events, tx);
+    api::mcp_event_stream(rx, state.heartbeat).into_response()
+}
+
+/// Serve on `listener` (over TLS with `tls`), talking to the app over
+/// `input`/`output`, until the app asks us to stop or closes the channel.
+async fn serve_child(
+    listener: tokio::net::TcpListener,
+    tls: Option<Arc<tokio_rustls::rustls::ServerConfig>>,
+    token: String,
+    heartbeat: Duration,
+    allowed_hosts: Vec<String>,
+    input: impl AsyncRead + Unpin + Send + 'static,
+    mut output: impl AsyncWrite + Unpin + Send + 'static,
+) -> std::io::Result<()> {
+    let local = listener.local_addr()?;
+    let port = local.port();
+    let (out_tx, mut out_rx) = mpsc::unbounded_channel::<Frame>();
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    let state = Arc::new(ChildState {
+        parent: Parent {
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            out: out_tx.clone(),
+        },
+        token,
+        shutdown: shutdown_rx.clone(),
+        heartbeat,
+        allowed_hosts,
+        lan: !local.ip().is_loopback(),
+        legacy: Arc::default(),
+        streams: Mutex::new(HashMap::new()),
+        canvas_events: canvas_events::channel(),
+    });
+
+    let _ = out_tx.send(Frame::Listening { port });
+    tokio::spawn(async move {
+        while let Some(frame) = out_rx.recv().await {
+            if output.write_all(encode(&frame).as_bytes()).await.is_err()
+                || output.flush().await.is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let reader_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(input).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            match serde_json::from_str::<Frame>(&line) {
+                Ok(Frame::Response {
+                    id,
+                    status,
+                    headers,
+                    body,
+                }) => {
+                    let body = STANDARD.decode(body).unwrap_or_default();
+                    reader_state.parent.complete(
+                        id,
+                        HttpReply {
+                            status,
+                            headers,
+                            body,
+                        },
+                    );
+                }
+                Ok(Frame::Notify { session, message }) => {
+                    reader_state.notify(&session, message);
+                }
+                Ok(Frame::Broadcast { message }) => {
+                    let _ = reader_state.canvas_events.send(message);
+                }
+                Ok(Frame::Shutdown) => break,
+                Ok(_) => {}
+                Err(e) => eprintln!("napkin {}: bad frame: {}", SIDECAR_FLAG, e),
+            }
+        }
+        // The app is gone or wants us gone; either way, stop.
+        let _ = shutdown_tx.send(true);
+    });
+
+    let router = Router::new()
+        // Only the SSE stream is served here; POST and DELETE go to the app.
+        .route("/mcp", get(event_stream).fallback(forward_http))
+        .route("/mcp/ws", get(forward_ws))
+        .route("/sse", get(legacy_stream))
+        .route(legacy_sse::MESSAGES_PATH, post(legacy_message))
+        .fallback(forward_http)
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            check_host,
+        ))
+        .with_state(state);
+
+    let mut rx = shutdown_rx;
+    tls::serve(listener, tls, router, async move {
+        while !*rx.borrow() {
+            if rx.changed().await.is_err() {
+                break;
+            }
+        }
+    })
+    .await
+}
+
+/// Entry point for `--mcp-sidecar`; returns the process exit code.
+pub fn run() -> i32 {
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("napkin {}: {}", SIDECAR_FLAG, e);
+            return 1;
+        }
+    };
+    let start = std::env::var(PORT_ENV)
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let Ok(token) = std::env::var(TOKEN_ENV) else {
+        eprintln!("napkin {}: must be started by Napkin", SIDECAR_FLAG);
+        return 1;
+    };
+    let heartbeat = Heartbeat {
+        interval_secs: std::env::var(HEARTBEAT_ENV)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(Heartbeat::default().interval_secs),
+    };
+    let allowed_hosts = std::env::var(HOSTS_ENV)
+        .map(|hosts| hosts.split(',').map(str::to_string).collect())
+        .unwrap_or_default();
+    let ip = std::env::var(BIND_ENV)
+        .ok()
+        .and_then(|ip| ip.parse().ok())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    let tls_files = match (
+        std::env::var_os(TLS_CERT_ENV),
+        std::env::var_os(TLS_KEY_ENV),
+    ) {
+        (Some(cert), Some(key)) => Some(TlsFiles {
+            cert: cert.into(),
+            key: key.into(),
+        }),
+        _ => None,
+    };
+    let result = runtime.block_on(async move {
+        let tls = tls_files.as_ref().map(tls::server_config).transpose()?;
+        let (listener, _) = api::bind_listener(ip, start, PORT_FALLBACK_ATTEMPTS).await?;
+        let heartbeat = heartbeat.interval();
+        serve_child(
+            listener,
+            tls,
+            token,
+            heartbeat,
+            allowed_hosts,
+            tokio::io::stdin(),
+            tokio::io::stdout(),
+        )
+        .await
+        .map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("napkin {}: {}", SIDECAR_FLAG, e);
+            1
+        }
+    }
+}
+
+// --- App side: launch and supervise ---
+
+struct Running {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Lines<BufReader<ChildStdout>>,
+    port: u16,
+}
+
+async fn launch(
+    state: &SharedApiState,
+    start_port: u16,
+    tls: Option<&TlsFiles>,
+) -> Result<Running, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("Cannot locate Napkin: {}", e))?;
+    let settings = settings::current(&state.app_handle);
+    let ip = settings.mcp_bind.ip();
+    bind::check_exposure(ip, state.api_token())?;
+    let mut command = tokio::process::Command::new(exe);
+    if let Some(files) = tls {
+        command
+            .env(TLS_CERT_ENV, &files.cert)
+            .env(TLS_KEY_ENV, &files.key);
+    }
+    let mut child = command
+        .arg(SIDECAR_FLAG)
+        .env(PORT_ENV, start_port.to_string())
+        .env(TOKEN_ENV, state.api_token())
+        .env(
+            HEARTBEAT_ENV,
+            settings.sse_heartbeat.interval_secs.to_string(),
+        )
+        .env(HOSTS_ENV, settings.allowed_hosts.join(","))
+        .env(BIND_ENV, ip.to_string())
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to start MCP sidecar: {}", e))?;
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        return Err("MCP sidecar has no IPC channel".to_string());
+    };
+    let mut lines = BufReader::new(stdout).lines();
+
+    let first = tokio::time::timeout(STARTUP_TIMEOUT, lines.next_line()).await;
+    match first {
+        Ok(Ok(Some(line))) => match serde_json::from_str(&line) {
+            Ok(Frame::Listening { port }) => Ok(Running {
+                child,
+                stdin,
+                lines,
+                port,
+            }),
+            _ => Err(format!("MCP sidecar sent an unexpected greeting: {}", line)),
+        },
+        Ok(_) => Err("MCP sidecar exited during startup".to_string()),
+        Err(_) => Err("MCP sidecar did not start in time".to_string()),
+    }
+}
+
+/// Start the sidecar and its supervisor; returns the port it listens on.
+pub async fn spawn(
+    state: SharedApiState,
+    shutdown: watch::Receiver<bool>,
+    tls: Option<TlsFiles>,
+) -> Result<u16, String> {
+    let ip = settings::current(&state.app_handle).mcp_bind.ip();
+    let running = launch(&state, DEFAULT_PORT, tls.as_ref()).await?;
+    let port = running.port;
+    log::info!(
+        "MCP sidecar listening on {}://{}/mcp",
+        if tls.is_some() { "https" } else { "http" },
+        SocketAddr::new(ip, port)
+    );
+    tauri::async_runtime::spawn(supervise(state, shutdown, running, tls));
+    Ok(port)
+}
+
+/// Answer one forwarded request with the in-process router.
+async fn respond(router: Router, frame: Frame) -> Option<Frame> {
+    let Frame::Request {
+        id,
+        method,
+        uri,
+        headers,
+        body,
+    } = frame
+    else {
+        return None;
+    };
+    let mut request = axum::http::Request::builder()
+        .method(method.as_str())
+        .uri(uri.as_str())
+        .body(Body::from(STANDARD.decode(body).unwrap_or_default()))
+        .ok()?;
+    apply_headers(request.headers_mut(), &headers);
+
+    let response = router.oneshot(request).await.ok()?;
+    let status = response.status().as_u16();
+    let headers = header_pairs(response.headers());
+    let body = axum::body::to_bytes(response.into_body(), MAX_BODY_BYTES)
+        .await
+        .unwrap_or_default();
+    Some(Frame::Response {
+        id,
+        status,
+        headers,
+        body: STANDARD.encode(body),
+    })
+}
+
+enum Exit {
+    Stopped,
+    Crashed,
+}
+
+/// Pump frames between the child and the router until one side goes away.
+/// Notifications for sessions whose streams the child holds, and canvas
+/// events, are relayed to it.
+async fn pump(
+    state: &SharedApiState,
+    router: &Router,
+    running: &mut Running,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Exit {
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<Frame>();
+    let (relay_tx, mut relay_rx) = mpsc::unbounded_channel();
+    state.subscriptions.lock().await.set_relay(Some(relay_tx));
+    let mut events = state.canvas_events.subscribe();
+    loop {
+        tokio::select! {
+            line = running.lines.next_line() => {
+                let Ok(Some(line)) = line else { return Exit::Crashed };
+                match serde_json::from_str::<Frame>(&line) {
+                    Ok(frame @ Frame::Request { .. }) => {
+                        let router = router.clone();
+                        let reply_tx = reply_tx.clone();
+                        tokio::spawn(async move {
+                            if let Some(reply) = respond(router, frame).await {
+                                let _ = reply_tx.send(reply);
+                            }
+                        });
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Ignoring malformed frame from MCP sidecar: {}", e),
+                }
+            }
+            Some(reply) = reply_rx.recv() => {
+                if running.stdin.write_all(encode(&reply).as_bytes()).await.is_err() {
+                    return Exit::Crashed;
+                }
+            }
+            Some((session, message)) = relay_rx.recv() => {
+                let frame = Frame::Notify { session, message };
+                if running.stdin.write_all(encode(&frame).as_bytes()).await.is_err() {
+                    return Exit::Crashed;
+                }
+            }
+            Ok(message) = events.recv() => {
+                let frame = Frame::Broadcast { message };
+                if running.stdin.write_all(encode(&frame).as_bytes()).await.is_err() {
+                    return Exit::Crashed;
+                }
+            }
+            changed = shutdown.changed() => {
+                if changed.is_err() || *shutdown.borrow() {
+                    return Exit::Stopped;
+                }
+            }
+        }
+    }
+}
+
+async fn stop(mut running: Running) {
+    let _ = running
+        .stdin
+        .write_all(encode(&Frame::Shutdown).as_bytes())
+        .await;
+    drop(running.stdin);
+    if tokio::time::timeout(SHUTDOWN_GRACE, running.child.wait())
+        .await
+        .is_err()
+    {
+        let _ = running.child.kill().await;
+    }
+}
+
+/// The server is down for good; make the app's status say so.
+async fn give_up(state: &SharedApiState) {
+    state.server_shutdown.lock().await.take();
+    *state.server_port.lock().await = None;
+    stdio::remove_port_file(&state.app_handle);
+    state.health.set_running(false);
+    connections::update(&state.app_handle, None);
+    state
+        .health
+        .record_error("The server process kept crashing and was stopped");
+}
+
+async fn supervise(
+    state: SharedApiState,
+    mut shutdown: watch::Receiver<bool>,
+    mut running: Running,
+    tls: Option<TlsFiles>,
+) {
+    let router = api::build_router(Arc::clone(&state));
+    let mut crashes: VecDeque<Instant> = VecDeque::new();
+
+    loop {
+        if let Exit::Stopped = pump(&state, &router, &mut running, &mut shutdown).await {
+            state.subscriptions.lock().await.set_relay(None);
+            stop(running).await;
+            log::info!("MCP sidecar stopped");
+            return;
+        }
+        let status = running.child.wait().await;
+        log::warn!("MCP sidecar exited unexpectedly ({:?})", status);
+
+        // Relaunch, preferring the port clients already know.
+        loop {
+            let now = Instant::now();
+            crashes.retain(|t| now.duration_since(*t) < RESTART_WINDOW);
+            crashes.push_back(now);
+            if crashes.len() > MAX_RESTARTS {
+                log::error!("MCP sidecar keeps crashing; giving up");
+                give_up(&state).await;
+                return;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(RESTART_DELAY) => {}
+                _ = shutdown.changed() => return,
+            }
+            match launch(&state, running.port, tls.as_ref()).await {
+                Ok(next) => {
+                    if next.port != running.port {
+                        *state.server_port.lock().await = Some(next.port);
+                        stdio::write_port_file(&state.app_handle, next.port);
+                        connections::update(&state.app_handle, Some(next.port));
+                    }
+                    log::info!("MCP sidecar restarted on port {}", next.port);
+                    running = next;
+                    break;
+                }
+                Err(e) => {
+                    log::warn!("Failed to restart MCP sidecar: {}", e);
+                    state
+                        .health
+                        .record_error(format!("Failed to restart: {}", e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header;
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn frames_round_trip() {
+        let frame = Frame::Request {
+            id: 3,
+            method: "POST".to_string(),
+            uri: "/mcp".to_string(),
+            headers: vec![("authorization".to_string(), "Bearer t".to_string())],
+            body: STANDARD.encode(b"{}"),
+        };
+        let line = encode(&frame);
+        assert!(line.ends_with('\n') && !line.trim_end().contains('\n'));
+        assert_eq!(serde_json::from_str::<Frame>(&line).unwrap(), frame);
+        assert_eq!(encode(&Frame::Shutdown), "{\"kind\":\"shutdown\"}\n");
+
+        let notify = Frame::Notify {
+            session: "s".to_string(),
+            message: crate::subscriptions::updated("napkin://canvas"),
+        };
+        assert_eq!(
+            serde_json::from_str::<Frame>(&encode(&notify)).unwrap(),
+            notify
+        );
+
+        let broadcast = Frame::Broadcast {
+            message: canvas_events::notification("tab", &[]),
+        };
+        assert_eq!(
+            serde_json::from_str::<Frame>(&encode(&broadcast)).unwrap(),
+            broadcast
+        );
+    }
+
+    #[test]
+    fn hop_by_hop_headers_are_dropped() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from_static("4"));
+        headers.insert(header::CONNECTION, HeaderValue::from_static("close"));
+        assert_eq!(
+            header_pairs(&headers),
+            [("content-type".to_string(), "text/html".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn child_forwards_requests_to_the_app() {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let (child_in, mut app_out) = tokio::io::duplex(64 * 1024);
+        let (app_in, child_out) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(serve_child(
+            listener,
+            None,
+            "t".to_string(),
+            Heartbeat::default().interval(),
+            Vec::new(),
+            child_in,
+            child_out,
+        ));
+
+        // Play the app: answer every request with its own path.
+        let mut lines = BufReader::new(app_in).lines();
+        let Frame::Listening { port } =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap()
+        else {
+            panic!("expected a greeting");
+        };
+        tokio::spawn(async move {
+            while let Ok(Some(line)) = lines.next_line().await {
+                if let Ok(Frame::Request { id, uri, .. }) = serde_json::from_str(&line) {
+                    let reply = Frame::Response {
+                        id,
+                        status: 200,
+                        headers: vec![("content-type".to_string(), "text/plain".to_string())],
+                        body: STANDARD.encode(uri),
+                    };
+                    app_out.write_all(encode(&reply).as_bytes()).await.unwrap();
+                }
+            }
+        });
+
+        let mut socket = tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .unwrap();
+        socket
+            .write_all(b"GET /share/abc HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut raw = Vec::new();
+        socket.read_to_end(&mut raw).await.unwrap();
+        let raw = String::from_utf8_lossy(&raw);
+        assert!(raw.starts_with("HTTP/1.1 200"), "{}", raw);
+        assert!(raw.ends_with("/share/abc"), "{}", raw);
+
+        let mut socket = tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .unwrap();
+        socket
+            .write_all(b"POST /mcp HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}")
+            .await
+            .unwrap();
+        let mut raw = Vec::new();
+        socket.read_to_end(&mut raw).await.unwrap();
+        let raw = String::from_utf8_lossy(&raw);
+        assert!(raw.ends_with("/mcp"), "{}", raw);
+    }
+}
@@ -0,0 +1,374 @@
+//! Slash-command snippets for the text editor.
+//!
+//! A snippet maps a trigger such as `/aws3` to text, a template of shapes,
+//! or a script. Snippets are kept in the `snippets` setting. The text editor
+//! calls `expand_snippet` when the user finishes typing a trigger. The
+//! trigger is replaced with the expansion's text, and its shapes are added
+//! around the point it passes.
+//!
+//! Template shapes use `create_shape` arguments, positioned relative to the
+//! insertion point. Scripts run like export hooks: no shell, a minimal
+//! environment, no stdin and a timeout. A script may print JSON
+//! (`{ "text": ..., "shapes": [...] }`) or plain text to insert.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::author;
+use crate::export_hooks;
+use crate::settings::{self, Settings, SettingsStore};
+
+/// Longest trigger accepted, slash included.
+const MAX_TRIGGER_LEN: usize = 32;
+const DEFAULT_SCRIPT_TIMEOUT_SECS: u64 = 10;
+
+fn default_script_timeout() -> u64 {
+    DEFAULT_SCRIPT_TIMEOUT_SECS
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snippet {
+    /// What the user types, starting with `/`.
+    pub trigger: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(flatten)]
+    pub body: SnippetBody,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SnippetBody {
+    /// Text typed in place of the trigger. `{date}`, `{time}` and
+    /// `{author}` are filled in.
+    Text { text: String },
+    /// Shapes added at the insertion point, as `create_shape` arguments
+    /// whose coordinates are offsets from it.
+    #[serde(rename_all = "camelCase")]
+    Shapes {
+        shapes: Vec<Value>,
+        /// Text left in place of the trigger.
+        #[serde(default)]
+        text: String,
+    },
+    /// A program and its arguments. `{trigger}`, `{x}` and `{y}` are
+    /// filled in.
+    #[serde(rename_all = "camelCase")]
+    Script {
+        command: Vec<String>,
+        #[serde(default = "default_script_timeout")]
+        timeout_secs: u64,
+    },
+}
+
+/// What a trigger expands to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Expansion {
+    /// Replaces the trigger in the editor.
+    pub text: String,
+    /// `create_shape` arguments in canvas coordinates, defaults filled in.
+    pub shapes: Vec<Value>,
+}
+
+fn check_trigger(trigger: &str) -> Result<(), String> {
+    if !trigger.starts_with('/') || trigger.len() < 2 {
+        return Err(format!(
+            "Snippet trigger '{}' must be a / followed by a name",
+            trigger
+        ));
+    }
+    if trigger.len() > MAX_TRIGGER_LEN {
+        return Err(format!(
+            "Snippet trigger '{}' is longer than {} characters",
+            trigger, MAX_TRIGGER_LEN
+        ));
+    }
+    if trigger[1..].chars().any(|c| c.is_whitespace() || c == '/') {
+        return Err(format!(
+            "Snippet trigger '{}' cannot contain spaces or another /",
+            trigger
+        ));
+    }
+    Ok(())
+}
+
+/// Check the `snippets` setting: well-formed, unique triggers and runnable
+/// bodies.
+pub fn validate(snippets: &[Snippet]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    for snippet in snippets {
+        check_trigger(&snippet.trigger)?;
+        if !seen.insert(snippet.trigger.as_str()) {
+            return Err(format!("Duplicate snippet trigger '{}'", snippet.trigger));
+        }
+        match &snippet.body {
+            SnippetBody::Shapes { shapes, .. } => {
+                if let Some(i) = shapes
+                    .iter()
+                    .position(|s| s.get("type").and_then(Value::as_str).is_none())
+                {
+                    return Err(format!(
+                        "Shape {} of snippet '{}' has no type",
+                        i + 1,
+                        snippet.trigger
+                    ));
+                }
+            }
+            SnippetBody::Script { command, .. } if command.is_empty() => {
+                return Err(format!("Snippet '{}' has no command", snippet.trigger));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Fill `{date}`, `{time}` and `{author}` in snippet text.
+fn fill_text(text: &str, settings: &Settings) -> String {
+    let now = chrono::Local::now();
+    text.replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H:%M").to_string())
+        .replace("{author}", &author::user(settings).name)
+}
+
+/// `shapes` moved to (`x`, `y`), with the user's shape defaults filled in.
+fn place(shapes: &[Value], x: f64, y: f64, settings: &Settings) -> Vec<Value> {
+    shapes
+        .iter()
+        .filter(|shape| shape.is_object())
+        .map(|shape| {
+            let mut shape = shape.clone();
+            for (key, origin) in [("x", x), ("y", y), ("x2", x), ("y2", y)] {
+                if let Some(offset) = shape.get(key).and_then(Value::as_f64) {
+                    shape[key] = Value::from(origin + offset);
+                } else if key == "x" || key == "y" {
+                    shape[key] = Value::from(origin);
+                }
+            }
+            settings.shape_defaults.fill(&mut shape);
+            shape
+        })
+        .collect()
+}
+
+/// Read a script's output: an `Expansion` as JSON, or plain text.
+fn parse_output(stdout: &str) -> Expansion {
+    let trimmed = stdout.trim();
+    match serde_json::from_str::<Expansion>(trimmed) {
+        Ok(expansion) if trimmed.starts_with('{') => expansion,
+        _ => Expansion {
+            text: trimmed.to_string(),
+            shapes: Vec::new(),
+        },
+    }
+}
+
+async fn run_script(
+    command: &[String],
+    timeout_secs: u64,
+    trigger: &str,
+    x: f64,
+    y: f64,
+) -> Result<Expansion, String> {
+    let argv: Vec<String> = command
+        .iter()
+        .map(|arg| {
+            arg.replace("{trigger}", trigger)
+                .replace("{x}", &x.to_string())
+                .replace("{y}", &y.to_string())
+        })
+        .collect();
+    let Some((program, args)) = argv.split_first() else {
+        return Err(format!("Snippet '{}' has no command", trigger));
+    };
+    let mut cmd = export_hooks::restricted_command(program, args);
+    cmd.env("NAPKIN_SNIPPET_TRIGGER", trigger);
+    let child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", program, e))?;
+    let timeout = Duration::from_secs(timeout_secs.max(1));
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| format!("{} timed out after {}s", program, timeout.as_secs()))?
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match output.status.code() {
+            Some(code) => format!("{} exited with status {}: {}", program, code, stderr.trim()),
+            None => format!("{} was terminated", program),
+        });
+    }
+    Ok(parse_output(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Expand `snippet` at (`x`, `y`).
+pub async fn expand(
+    snippet: &Snippet,
+    x: f64,
+    y: f64,
+    settings: &Settings,
+) -> Result<Expansion, String> {
+    let expansion = match &snippet.body {
+        SnippetBody::Text { text } => Expansion {
+            text: text.clone(),
+            shapes: Vec::new(),
+        },
+        SnippetBody::Shapes { shapes, text } => Expansion {
+            text: text.clone(),
+            shapes: shapes.clone(),
+        },
+        SnippetBody::Script {
+            command,
+            timeout_secs,
+        } => run_script(command, *timeout_secs, &snippet.trigger, x, y).await?,
+    };
+    Ok(Expansion {
+        text: fill_text(&expansion.text, settings),
+        shapes: place(&expansion.shapes, x, y, settings),
+    })
+}
+
+// --- Tauri commands ---
+
+/// Expand `trigger`, typed in the text editor, with shapes placed at
+/// (`x`, `y`) in canvas coordinates. `None` when no snippet has that
+/// trigger, so the editor leaves the text alone.
+#[tauri::command]
+pub async fn expand_snippet(
+    trigger: String,
+    x: f64,
+    y: f64,
+    app: tauri::AppHandle,
+) -> Result<Option<Expansion>, String> {
+    let settings = settings::current(&app);
+    let Some(snippet) = settings.snippets.iter().find(|s| s.trigger == trigger) else {
+        return Ok(None);
+    };
+    expand(snippet, x, y, &settings).await.map(Some)
+}
+
+#[tauri::command]
+pub fn list_snippets(store: tauri::State<'_, SettingsStore>) -> Vec<Snippet> {
+    store.get().snippets
+}
+
+/// Add `snippet`, or replace the one with its trigger (or with `previous`,
+/// when the trigger was renamed).
+#[tauri::command]
+pub fn save_snippet(
+    snippet: Snippet,
+    previous: Option<String>,
+    store: tauri::State<'_, SettingsStore>,
+) -> Result<Vec<Snippet>, String> {
+    let mut snippets = store.get().snippets;
+    let replaces = previous.as_deref().unwrap_or(&snippet.trigger);
+    match snippets.iter().position(|s| s.trigger == replaces) {
+        Some(i) => snippets[i] = snippet,
+        None => snippets.push(snippet),
+    }
+    let patch = serde_json::json!({ "snippets": snippets });
+    Ok(store.update(patch)?.snippets)
+}
+
+#[tauri::command]
+pub fn delete_snippet(
+    trigger: String,
+    store: tauri::State<'_, SettingsStore>,
+) -> Result<Vec<Snippet>, String> {
+    let mut snippets = store.get().snippets;
+    snippets.retain(|s| s.trigger != trigger);
+    let patch = serde_json::json!({ "snippets": snippets });
+    Ok(store.update(patch)?.snippets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn snippet(value: Value) -> Snippet {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn triggers_are_checked() {
+        let text =
+            |trigger: &str| snippet(json!({ "trigger": trigger, "kind": "text", "text": "x" }));
+        assert!(validate(&[text("/aws3"), text("/todo")]).is_ok());
+        assert!(validate(&[text("aws3")]).is_err());
+        assert!(validate(&[text("/")]).is_err());
+        assert!(validate(&[text("/two words")]).is_err());
+        assert_eq!(
+            validate(&[text("/a"), text("/a")]).unwrap_err(),
+            "Duplicate snippet trigger '/a'"
+        );
+        let script = snippet(json!({ "trigger": "/run", "kind": "script", "command": [] }));
+        assert!(validate(&[script]).is_err());
+        let shapes =
+            snippet(json!({ "trigger": "/box", "kind": "shapes", "shapes": [{ "x": 1 }] }));
+        assert!(validate(&[shapes]).is_err());
+    }
+
+    #[tokio::test]
+    async fn templates_are_placed_at_the_point() {
+        let settings = Settings::default();
+        let aws = snippet(json!({
+            "trigger": "/aws3",
+            "kind": "shapes",
+            "text": "AWS",
+            "shapes": [
+                { "type": "rectangle", "text": "S3", "x": 10, "y": 20 },
+                { "type": "arrow", "x": 0, "y": 0, "x2": 100, "y2": 0 },
+                { "type": "sticky" },
+            ],
+        }));
+        let expansion = expand(&aws, 500.0, 300.0, &settings).await.unwrap();
+        assert_eq!(expansion.text, "AWS");
+        let shapes = &expansion.shapes;
+        assert_eq!(
+            (shapes[0]["x"].as_f64(), shapes[0]["y"].as_f64()),
+            (Some(510.0), Some(320.0))
+        );
+        assert_eq!(shapes[0]["width"], 200.0);
+        assert_eq!(shapes[1]["x2"], 600.0);
+        assert_eq!(shapes[1]["y2"], 300.0);
+        assert_eq!(
+            (shapes[2]["x"].as_f64(), shapes[2]["y"].as_f64()),
+            (Some(500.0), Some(300.0))
+        );
+    }
+
+    #[test]
+    fn script_output_may_be_json_or_text() {
+        assert_eq!(parse_output("hello\n").text, "hello");
+        assert_eq!(parse_output("[1, 2]").text, "[1, 2]");
+        let expansion = parse_output(r#"{ "text": "ok", "shapes": [{ "type": "text" }] }"#);
+        assert_eq!(expansion.text, "ok");
+        assert_eq!(expansion.shapes.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn scripts_get_the_trigger_and_point() {
+        let settings = Settings::default();
+        let echo = snippet(json!({
+            "trigger": "/where",
+            "kind": "script",
+            "command": ["sh", "-c", "echo \"$NAPKIN_SNIPPET_TRIGGER at $0,$1\"", "{x}", "{y}"],
+        }));
+        let expansion = expand(&echo, 1.5, 2.0, &settings).await.unwrap();
+        assert_eq!(expansion.text, "/where at 1.5,2");
+
+        let failing = snippet(json!({
+            "trigger": "/fail",
+            "kind": "script",
+            "command": ["sh", "-c", "echo nope >&2; exit 2"],
+        }));
+        let err = expand(&failing, 0.0, 0.0, &settings).await.unwrap_err();
+        assert_eq!(err, "sh exited with status 2: nope");
+    }
+}
@@ -0,0 +1,285 @@
+//! Viewport culling index.
+//!
+//! The webview mirrors its shapes here as they change (`update_spatial_index`)
+//! and asks which ones intersect the visible area (`query_visible_shapes`)
+//! when the viewport moves, so large boards only draw what is on screen. The
+//! index is a uniform grid over rotation-aware bounds from `geometry`; shapes
+//! spanning many cells are kept in a separate list and always tested.
+
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+use crate::api::SharedApiState;
+use crate::geometry::{shape_bounds, Bounds};
+
+/// Grid cell size in canvas pixels.
+const CELL_SIZE: f64 = 512.0;
+/// Shapes covering more cells than this go in the oversized list.
+const MAX_CELLS_PER_SHAPE: i64 = 64;
+/// Queries covering more cells than this scan every entry instead.
+const MAX_CELLS_PER_QUERY: i64 = 4096;
+
+type Cell = (i64, i64);
+
+/// Inclusive cell range covered by some bounds.
+#[derive(Debug, Clone, Copy)]
+struct CellRange {
+    min: Cell,
+    max: Cell,
+}
+
+impl CellRange {
+    fn of(b: &Bounds) -> CellRange {
+        let cell = |v: f64| (v / CELL_SIZE).floor() as i64;
+        CellRange {
+            min: (cell(b.min_x), cell(b.min_y)),
+            max: (cell(b.max_x), cell(b.max_y)),
+        }
+    }
+
+    fn count(&self) -> i64 {
+        (self.max.0 - self.min.0 + 1).saturating_mul(self.max.1 - self.min.1 + 1)
+    }
+
+    fn cells(&self) -> impl Iterator<Item = Cell> + '_ {
+        (self.min.0..=self.max.0).flat_map(move |x| (self.min.1..=self.max.1).map(move |y| (x, y)))
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    bounds: Bounds,
+    /// `None` for oversized shapes.
+    range: Option<CellRange>,
+}
+
+#[derive(Debug, Default)]
+pub struct SpatialIndex {
+    entries: HashMap<String, Entry>,
+    cells: HashMap<Cell, HashSet<String>>,
+    oversized: HashSet<String>,
+}
+
+fn intersects(a: &Bounds, b: &Bounds) -> bool {
+    a.min_x <= b.max_x && a.max_x >= b.min_x && a.min_y <= b.max_y && a.max_y >= b.min_y
+}
+
+impl SpatialIndex {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cells.clear();
+        self.oversized.clear();
+    }
+
+    /// Insert or move a shape. Shapes without an id or geometry are ignored.
+    pub fn upsert(&mut self, shape: &Value) {
+        let Some(id) = shape.get("id").and_then(|v| v.as_str()) else {
+            return;
+        };
+        self.remove(id);
+        let Some(bounds) = shape_bounds(shape) else {
+            return;
+        };
+        let range = CellRange::of(&bounds);
+        let range = if range.count() > MAX_CELLS_PER_SHAPE {
+            self.oversized.insert(id.to_string());
+            None
+        } else {
+            for cell in range.cells() {
+                self.cells.entry(cell).or_default().insert(id.to_string());
+            }
+            Some(range)
+        };
+        self.entries.insert(id.to_string(), Entry { bounds, range });
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        let Some(entry) = self.entries.remove(id) else {
+            return;
+        };
+        match entry.range {
+            Some(range) => {
+                for cell in range.cells() {
+                    if let Some(ids) = self.cells.get_mut(&cell) {
+                        ids.remove(id);
+                        if ids.is_empty() {
+                            self.cells.remove(&cell);
+                        }
+                    }
+                }
+            }
+            None => {
+                self.oversized.remove(id);
+            }
+        }
+    }
+
+    /// IDs of shapes whose bounds intersect `area`, in no particular order.
+    pub fn query(&self, area: &Bounds) -> Vec<String> {
+        let range = CellRange::of(area);
+        if range.count() > MAX_CELLS_PER_QUERY {
+            return self
+                .entries
+                .iter()
+                .filter(|(_, e)| intersects(&e.bounds, area))
+                .map(|(id, _)| id.clone())
+                .collect();
+        }
+
+        let mut visible: HashSet<&str> = HashSet::new();
+        let candidates = range
+            .cells()
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .chain(self.oversized.iter());
+        for id in candidates {
+            if visible.contains(id.as_str()) {
+                continue;
+            }
+            if self
+                .entries
+                .get(id)
+                .is_some_and(|e| intersects(&e.bounds, area))
+            {
+                visible.insert(id);
+            }
+        }
+        visible.into_iter().map(str::to_string).collect()
+    }
+}
+
+// --- Tauri commands ---
+
+/// Apply shape changes from the webview. `reset` drops the current contents
+/// first (tab switch, document load).
+#[tauri::command]
+pub async fn update_spatial_index(
+    upserts: Vec<Value>,
+    removed: Vec<String>,
+    reset: bool,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<usize, String> {
+    let mut index = state.spatial.lock().await;
+    if reset {
+        index.clear();
+    }
+    for id in &removed {
+        index.remove(id);
+    }
+    for shape in &upserts {
+        index.upsert(shape);
+    }
+    Ok(index.len())
+}
+
+/// IDs of shapes intersecting the canvas-space rectangle grown by `margin`.
+#[tauri::command]
+pub async fn query_visible_shapes(
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    margin: f64,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<Vec<String>, String> {
+    if !(width >= 0.0 && height >= 0.0 && margin >= 0.0) {
+        return Err("Viewport size and margin must be non-negative".to_string());
+    }
+    let area = Bounds {
+        min_x: x,
+        min_y: y,
+        max_x: x + width,
+        max_y: y + height,
+    }
+    .expand(margin);
+    Ok(state.spatial.lock().await.query(&area))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    fn rect(id: &str, x: f64, y: f64, w: f64, h: f64) -> Value {
+        json!({ "id": id, "type": "rectangle", "x": x, "y": y, "width": w, "height": h })
+    }
+
+    fn area(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Bounds {
+        Bounds {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    fn sorted(mut ids: Vec<String>) -> Vec<String> {
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn moves_and_removals_update_results() {
+        let mut index = SpatialIndex::default();
+        index.upsert(&rect("a", 0.0, 0.0, 100.0, 100.0));
+        index.upsert(&rect("b", 5000.0, 5000.0, 100.0, 100.0));
+        let view = area(-10.0, -10.0, 800.0, 600.0);
+        assert_eq!(index.query(&view), vec!["a"]);
+
+        index.upsert(&rect("a", 9000.0, 0.0, 100.0, 100.0));
+        assert!(index.query(&view).is_empty());
+
+        index.upsert(&rect("b", 200.0, 200.0, 10.0, 10.0));
+        index.remove("a");
+        assert_eq!(index.query(&view), vec!["b"]);
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn oversized_and_zoomed_out_queries() {
+        let mut index = SpatialIndex::default();
+        index.upsert(&rect("huge", -50_000.0, -50_000.0, 100_000.0, 100_000.0));
+        index.upsert(&rect("small", 10.0, 10.0, 10.0, 10.0));
+        assert_eq!(
+            sorted(index.query(&area(0.0, 0.0, 100.0, 100.0))),
+            vec!["huge", "small"]
+        );
+        let everything = area(-1e6, -1e6, 1e6, 1e6);
+        assert_eq!(index.query(&everything).len(), 2);
+        index.remove("huge");
+        assert_eq!(index.query(&everything), vec!["small"]);
+    }
+
+    proptest! {
+        #[test]
+        fn matches_linear_scan(
+            shapes in prop::collection::vec(
+                (-5000.0..5000.0f64, -5000.0..5000.0f64, 1.0..3000.0f64, 1.0..3000.0f64),
+                1..60,
+            ),
+            (qx, qy, qw, qh) in (-6000.0..6000.0f64, -6000.0..6000.0f64, 0.0..4000.0f64, 0.0..4000.0f64),
+        ) {
+            let mut index = SpatialIndex::default();
+            let shapes: Vec<Value> = shapes
+                .iter()
+                .enumerate()
+                .map(|(i, (x, y, w, h))| rect(&i.to_string(), *x, *y, *w, *h))
+                .collect();
+            for shape in &shapes {
+                index.upsert(shape);
+            }
+            let view = area(qx, qy, qx + qw, qy + qh);
+            let expected: Vec<String> = shapes
+                .iter()
+                .filter(|s| intersects(&shape_bounds(s).unwrap(), &view))
+                .map(|s| s["id"].as_str().unwrap().to_string())
+                .collect();
+            prop_assert_eq!(sorted(index.query(&view)), sorted(expected));
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Startup orchestration and timings.
+//!
+//! `setup` only does what the first frame needs (menu, settings, API state).
+//! Anything else is registered in `DEFERRED` and runs on a blocking worker
+//! once the webview has finished its first page load. Every phase is timed
+//! from launch so `get_startup_timings` can show where cold start
+//! goes; the webview adds its own milestones with `report_startup_phase`.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::Manager;
+
+use crate::api::SharedApiState;
+use crate::assets;
+use crate::scheduler;
+use crate::thumbnails;
+use crate::tunnel;
+use crate::versions;
+
+type DeferredTask = fn(&tauri::AppHandle);
+
+/// Work that can wait until the window is showing.
+const DEFERRED: &[(&str, DeferredTask)] = &[
+    ("api-token", |app| {
+        app.state::<SharedApiState>().api_token();
+    }),
+    ("tunnel", tunnel::resume),
+    ("scheduler", scheduler::start),
+    ("versions", versions::start),
+    ("thumbnails", thumbnails::refresh_recent),
+    ("assets", assets::collect_unused),
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Phase {
+    pub name: String,
+    /// Milliseconds from launch to the start of the phase.
+    pub start_ms: f64,
+    pub duration_ms: f64,
+    /// Ran off the critical path.
+    pub background: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupTimings {
+    pub phases: Vec<Phase>,
+    /// Milliseconds from launch to the first page load, once known.
+    pub first_page_load_ms: Option<f64>,
+}
+
+pub struct Startup {
+    origin: Instant,
+    phases: Mutex<Vec<Phase>>,
+    deferred_started: AtomicBool,
+}
+
+fn millis(from: Instant, to: Instant) -> f64 {
+    to.duration_since(from).as_secs_f64() * 1000.0
+}
+
+impl Startup {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            phases: Mutex::new(Vec::new()),
+            deferred_started: AtomicBool::new(false),
+        }
+    }
+
+    fn record(&self, name: &str, started: Instant, background: bool) {
+        let phase = Phase {
+            name: name.to_string(),
+            start_ms: millis(self.origin, started),
+            duration_ms: millis(started, Instant::now()),
+            background,
+        };
+        log::debug!("startup: {} took {:.1}ms", phase.name, phase.duration_ms);
+        self.phases
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(phase);
+    }
+
+    /// Run and time a phase on the critical path.
+    pub fn measure<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.record(name, started, false);
+        result
+    }
+
+    /// Record a zero-length milestone.
+    pub fn mark(&self, name: &str) {
+        self.record(name, Instant::now(), false);
+    }
+
+    pub fn timings(&self) -> StartupTimings {
+        let phases = self
+            .phases
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let first_page_load_ms = phases
+            .iter()
+            .find(|p| p.name == "first-page-load")
+            .map(|p| p.start_ms);
+        StartupTimings {
+            phases,
+            first_page_load_ms,
+        }
+    }
+}
+
+/// Mark the first page load and start the deferred work, once.
+pub fn on_page_loaded(app: &tauri::AppHandle) {
+    let startup = app.state::<Startup>();
+    if startup.deferred_started.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    startup.mark("first-page-load");
+
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let startup = app.state::<Startup>();
+        for (name, task) in DEFERRED {
+            let started = Instant::now();
+            task(&app);
+            startup.record(name, started, true);
+        }
+    });
+}
+
+// --- Tauri commands ---
+
+#[tauri::command]
+pub fn get_startup_timings(startup: tauri::State<'_, Startup>) -> StartupTimings {
+    startup.timings()
+}
+
+/// Milestones only the webview can see, e.g. "initial-load-complete".
+#[tauri::command]
+pub fn report_startup_phase(name: String, startup: tauri::State<'_, Startup>) {
+    startup.mark(&format!("webview:{}", name));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_are_recorded_in_order() {
+        let startup = Startup::new();
+        let value = startup.measure("settings", || 42);
+        startup.mark("first-page-load");
+        assert_eq!(value, 42);
+
+        let timings = startup.timings();
+        let names: Vec<&str> = timings.phases.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["settings", "first-page-load"]);
+        assert!(timings.phases[1].start_ms >= timings.phases[0].start_ms);
+        assert_eq!(timings.first_page_load_ms, Some(timings.phases[1].start_ms));
+    }
+}
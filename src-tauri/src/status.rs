@@ -0,0 +1,180 @@
+//! Health of the MCP server, for the settings dialog (`get_api_status`) and
+//! for monitors (`GET /healthz`).
+//!
+//! `/healthz` needs no token and then only says the server is up, with its
+//! version and uptime. With the bearer token it returns the full status:
+//! connections, bridge requests waiting on the webview, and the last error.
+
+use axum::{
+    extract::State as AxumState,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Local, SecondsFormat};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::api::{SharedApiState, MCP_PROTOCOL_VERSION, MCP_SERVER_VERSION};
+use crate::auth;
+
+pub const PATH: &str = "/healthz";
+
+/// The latest failure of the server or the webview bridge.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LastError {
+    pub message: String,
+    /// RFC 3339 local time.
+    pub at: String,
+}
+
+/// When the server started and what last went wrong.
+#[derive(Default)]
+pub struct Health {
+    started: Mutex<Option<Instant>>,
+    last_error: Mutex<Option<LastError>>,
+}
+
+impl Health {
+    pub fn set_running(&self, running: bool) {
+        *self.started.lock().unwrap_or_else(|e| e.into_inner()) = running.then(Instant::now);
+    }
+
+    fn uptime_secs(&self) -> Option<u64> {
+        self.started
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .map(|started| started.elapsed().as_secs())
+    }
+
+    pub fn record_error(&self, message: impl Into<String>) {
+        let error = LastError {
+            message: message.into(),
+            at: Local::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        };
+        *self.last_error.lock().unwrap_or_else(|e| e.into_inner()) = Some(error);
+    }
+
+    fn last_error(&self) -> Option<LastError> {
+        self.last_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// Everything known about the running server.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiStatus {
+    pub running: bool,
+    pub port: Option<u16>,
+    /// Seconds since the server started.
+    pub uptime_secs: Option<u64>,
+    pub version: String,
+    /// MCP protocol revision the server speaks.
+    pub protocol_version: String,
+    /// Open HTTP sessions (`Mcp-Session-Id`).
+    pub sessions: usize,
+    /// Open `GET /mcp` streams.
+    pub event_streams: usize,
+    /// Open legacy SSE streams.
+    pub legacy_sse: usize,
+    /// Tool calls sent to the webview and not answered yet.
+    pub pending_requests: usize,
+    pub last_error: Option<LastError>,
+}
+
+/// The part of the status `/healthz` shows without a token.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Liveness {
+    status: &'static str,
+    version: String,
+    protocol_version: String,
+    uptime_secs: Option<u64>,
+}
+
+impl From<&ApiStatus> for Liveness {
+    fn from(status: &ApiStatus) -> Self {
+        Self {
+            status: if status.running { "ok" } else { "stopped" },
+            version: status.version.clone(),
+            protocol_version: status.protocol_version.clone(),
+            uptime_secs: status.uptime_secs,
+        }
+    }
+}
+
+pub async fn current(state: &SharedApiState) -> ApiStatus {
+    ApiStatus {
+        running: state.server_shutdown.lock().await.is_some(),
+        port: *state.server_port.lock().await,
+        uptime_secs: state.health.uptime_secs(),
+        version: MCP_SERVER_VERSION.to_string(),
+        protocol_version: MCP_PROTOCOL_VERSION.to_string(),
+        sessions: state.sessions.lock().await.list().len(),
+        event_streams: state.event_streams.load(Ordering::Relaxed),
+        legacy_sse: state.legacy_sse.lock().await.connected(),
+        pending_requests: state.bridge.lock().await.len(),
+        last_error: state.health.last_error(),
+    }
+}
+
+pub async fn healthz_handler(
+    AxumState(state): AxumState<SharedApiState>,
+    headers: HeaderMap,
+) -> Response {
+    let status = current(&state).await;
+    if auth::is_authorized(&headers, state.api_token()) {
+        Json(status).into_response()
+    } else {
+        Json(Liveness::from(&status)).into_response()
+    }
+}
+
+// --- Tauri command ---
+
+#[tauri::command]
+pub async fn get_api_status(state: tauri::State<'_, SharedApiState>) -> Result<ApiStatus, String> {
+    Ok(current(state.inner()).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_tracks_uptime_and_the_last_error() {
+        let health = Health::default();
+        assert_eq!(health.uptime_secs(), None);
+        health.set_running(true);
+        assert_eq!(health.uptime_secs(), Some(0));
+        health.set_running(false);
+        assert_eq!(health.uptime_secs(), None);
+
+        health.record_error("first");
+        health.record_error("Request timed out");
+        assert_eq!(health.last_error().unwrap().message, "Request timed out");
+    }
+
+    #[test]
+    fn liveness_leaves_out_connection_details() {
+        let status = ApiStatus {
+            running: true,
+            uptime_secs: Some(12),
+            sessions: 3,
+            last_error: Some(LastError {
+                message: "secret path".to_string(),
+                at: String::new(),
+            }),
+            ..ApiStatus::default()
+        };
+        let shown = serde_json::to_value(Liveness::from(&status)).unwrap();
+        assert_eq!(shown["status"], "ok");
+        assert_eq!(shown["uptimeSecs"], 12);
+        assert!(shown.get("sessions").is_none() && shown.get("lastError").is_none());
+    }
+}
@@ -0,0 +1,498 @@
+//! stdio MCP transport (`napkin --mcp-stdio`).
+//!
+//! MCP clients that launch servers as subprocesses speak newline-delimited
+//! JSON-RPC over stdin/stdout. In this mode the binary does not open a window;
+//! it forwards each message to the running app's HTTP endpoint and writes the
+//! reply back, so the tool set and the webview bridge are exactly the ones
+//! HTTP clients get. The app advertises its port and token in its config
+//! directory; `NAPKIN_MCP_PORT` and `NAPKIN_MCP_TOKEN` override them. When
+//! the server speaks HTTPS it also publishes its certificate there (or
+//! `NAPKIN_MCP_CERT` names one), and the proxy trusts exactly that one.
+
+use std::path::PathBuf;
+use tauri::Manager;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::api::DEFAULT_PORT;
+use crate::auth::TOKEN_FILE;
+use crate::session::SESSION_HEADER;
+use crate::tls::{self, TlsFiles};
+
+/// Must match `identifier` in tauri.conf.json; the proxy runs without a
+/// Tauri context, so it locates the config directory itself.
+const APP_IDENTIFIER: &str = "com.napkin.desktop";
+const PORT_FILE: &str = "mcp_port";
+const CERT_FILE: &str = "mcp_cert.pem";
+
+// --- Discovery, written by the app ---
+
+/// Record the port the MCP server bound, for stdio proxies to find.
+pub fn write_port_file(app: &tauri::AppHandle, port: u16) {
+    let Ok(dir) = app.path().app_config_dir() else {
+        return;
+    };
+    let saved = std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(dir.join(PORT_FILE), port.to_string()));
+    if let Err(e) = saved {
+        log::warn!("Failed to record MCP port: {}", e);
+    }
+}
+
+pub fn remove_port_file(app: &tauri::AppHandle) {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let _ = std::fs::remove_file(dir.join(PORT_FILE));
+        let _ = std::fs::remove_file(dir.join(CERT_FILE));
+    }
+}
+
+/// Publish the certificate the server presents, or withdraw it when it
+/// serves plain HTTP.
+pub fn write_cert_file(app: &tauri::AppHandle, files: Option<&TlsFiles>) {
+    let Ok(dir) = app.path().app_config_dir() else {
+        return;
+    };
+    let path = dir.join(CERT_FILE);
+    let saved = match files {
+        Some(files) => std::fs::create_dir_all(&dir)
+            .and_then(|_| std::fs::copy(&files.cert, &path))
+            .map(|_| ()),
+        None => match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    };
+    if let Err(e) = saved {
+        log::warn!("Failed to record the MCP certificate: {}", e);
+    }
+}
+
+/// The directory Tauri's `app_config_dir` resolves to.
+fn config_dir() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let base = if cfg!(target_os = "macos") {
+        home().map(|h| h.join("Library/Application Support"))
+    } else if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| home().map(|h| h.join(".config")))
+    };
+    base.map(|b| b.join(APP_IDENTIFIER))
+}
+
+pub(crate) fn read_config(name: &str) -> Option<String> {
+    let text = std::fs::read_to_string(config_dir()?.join(name)).ok()?;
+    Some(text.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+struct Endpoint {
+    port: u16,
+    token: String,
+    /// PEM certificate to pin when the server speaks HTTPS.
+    cert: Option<Vec<u8>>,
+}
+
+/// Resolved per message so the proxy follows the app across restarts.
+fn endpoint() -> Result<Endpoint, String> {
+    let port = std::env::var("NAPKIN_MCP_PORT")
+        .ok()
+        .or_else(|| read_config(PORT_FILE))
+        .map(|p| {
+            p.parse::<u16>()
+                .map_err(|_| format!("Invalid MCP port: {}", p))
+        })
+        .transpose()?
+        .unwrap_or(DEFAULT_PORT);
+    let token = std::env::var("NAPKIN_MCP_TOKEN")
+        .ok()
+        .or_else(|| read_config(TOKEN_FILE))
+        .ok_or("No MCP token found; start Napkin once or set NAPKIN_MCP_TOKEN")?;
+    let cert = std::env::var_os("NAPKIN_MCP_CERT")
+        .map(PathBuf::from)
+        .or_else(|| Some(config_dir()?.join(CERT_FILE)))
+        .and_then(|path| std::fs::read(path).ok());
+    Ok(Endpoint { port, token, cert })
+}
+
+// --- Minimal HTTP/1.1 client for the loopback endpoint ---
+
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or("Truncated chunked body")?;
+        let size_text = std::str::from_utf8(&body[..line_end]).map_err(|e| e.to_string())?;
+        let size_text = size_text.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_text, 16)
+            .map_err(|_| format!("Bad chunk size: {:?}", size_text))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(out);
+        }
+        if body.len() < size {
+            return Err("Truncated chunked body".to_string());
+        }
+        out.extend_from_slice(&body[..size]);
+        body = body.get(size + 2..).unwrap_or(&[]);
+    }
+}
+
+struct HttpResponse {
+    status: u16,
+    /// `Mcp-Session-Id`, when the server sent one.
+    session: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Status, session and body of a complete HTTP/1.1 response.
+fn parse_response(raw: &[u8]) -> Result<HttpResponse, String> {
+    let head_end = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or("Malformed HTTP response")?;
+    let head = std::str::from_utf8(&raw[..head_end]).map_err(|e| e.to_string())?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or("Malformed HTTP status line")?;
+    let mut chunked = false;
+    let mut session = None;
+    for (name, value) in lines.filter_map(|l| l.split_once(':')) {
+        let name = name.trim();
+        if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked |= value.to_ascii_lowercase().contains("chunked");
+        } else if name.eq_ignore_ascii_case(SESSION_HEADER.as_str()) {
+            session = Some(value.trim().to_string());
+        }
+    }
+    let body = &raw[head_end + 4..];
+    let body = if chunked {
+        decode_chunked(body)?
+    } else {
+        body.to_vec()
+    };
+    Ok(HttpResponse {
+        status,
+        session,
+        body,
+    })
+}
+
+/// Write `request` to `stream` and read until the server closes it.
+async fn exchange(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    request: &str,
+) -> Result<Vec<u8>, String> {
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut raw = Vec::new();
+    stream
+        .read_to_end(&mut raw)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(raw)
+}
+
+async fn send(
+    endpoint: &Endpoint,
+    method: &str,
+    session: Option<&str>,
+    message: &str,
+) -> Result<HttpResponse, String> {
+    let stream = tokio::net::TcpStream::connect(("127.0.0.1", endpoint.port))
+        .await
+        .map_err(|e| format!("Napkin is not reachable on port {}: {}", endpoint.port, e))?;
+    let session = session
+        .map(|id| format!("{}: {}\r\n", SESSION_HEADER, id))
+        .unwrap_or_default();
+    let request = format!(
+        "{} /mcp HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nContent-Type: application/json\r\nAuthorization: Bearer {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        endpoint.port,
+        endpoint.token,
+        session,
+        message.len(),
+        message
+    );
+    let raw = match &endpoint.cert {
+        Some(cert) => exchange(tls::connect_pinned(stream, cert).await?, &request).await?,
+        None => exchange(stream, &request).await?,
+    };
+    parse_response(&raw)
+}
+
+/// The client's HTTP session, remembered so it can be re-established when
+/// the app restarts and forgets it.
+#[derive(Default)]
+struct ClientSession {
+    id: Option<String>,
+    initialize: Option<String>,
+}
+
+const INITIALIZED: &str = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+
+fn is_initialize(message: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(message)
+        .ok()
+        .and_then(|v| {
+            v.get("method")
+                .and_then(|m| m.as_str())
+                .map(|m| m == "initialize")
+        })
+        .unwrap_or(false)
+}
+
+async fn post(
+    endpoint: &Endpoint,
+    session: &mut ClientSession,
+    message: &str,
+) -> Result<HttpResponse, String> {
+    let response = send(endpoint, "POST", session.id.as_deref(), message).await?;
+    if response.session.is_some() {
+        session.id.clone_from(&response.session);
+    }
+    Ok(response)
+}
+
+/// POST one JSON-RPC message; `None` for accepted notifications.
+async fn forward(
+    endpoint: &Endpoint,
+    session: &mut ClientSession,
+    message: &str,
+) -> Result<Option<String>, String> {
+    if is_initialize(message) {
+        *session = ClientSession {
+            id: None,
+            initialize: Some(message.to_string()),
+        };
+    }
+    let mut response = post(endpoint, session, message).await?;
+    if response.status == 404 && session.id.is_some() {
+        // The app restarted; replay the handshake and retry once.
+        session.id = None;
+        if let Some(initialize) = session.initialize.clone() {
+            post(endpoint, session, &initialize).await?;
+            post(endpoint, session, INITIALIZED).await?;
+        }
+        response = post(endpoint, session, message).await?;
+    }
+    match response.status {
+        202 => Ok(None),
+        200 => String::from_utf8(response.body)
+            .map(Some)
+            .map_err(|e| e.to_string()),
+        401 => Err("Napkin rejected the MCP token".to_string()),
+        status => Err(format!(
+            "Napkin returned HTTP {}: {}",
+            status,
+            String::from_utf8_lossy(&response.body)
+        )),
+    }
+}
+
+/// JSON-RPC error for a request we could not deliver. Notifications get no
+/// reply, as the protocol requires.
+fn transport_error(message: &str, error: &str) -> Option<String> {
+    let id = serde_json::from_str::<serde_json::Value>(message)
+        .ok()
+        .and_then(|v| v.get("id").cloned())
+        .filter(|id| !id.is_null())?;
+    Some(
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": -32000, "message": error }
+        })
+        .to_string(),
+    )
+}
+
+async fn serve() -> std::io::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    let mut session = ClientSession::default();
+    while let Some(line) = lines.next_line().await? {
+        let message = line.trim();
+        if message.is_empty() {
+            continue;
+        }
+        let reply = match endpoint() {
+            Ok(endpoint) => forward(&endpoint, &mut session, message).await,
+            Err(e) => Err(e),
+        };
+        let reply = reply.unwrap_or_else(|e| {
+            eprintln!("napkin --mcp-stdio: {}", e);
+            transport_error(message, &e)
+        });
+        if let Some(reply) = reply {
+            // Replies must stay on one line.
+            let reply = reply.replace('\n', "");
+            stdout.write_all(reply.as_bytes()).await?;
+            stdout.write_all(b"\n").await?;
+            stdout.flush().await?;
+        }
+    }
+    // The client hung up; end its session rather than leave it to expire.
+    if let (Some(id), Ok(endpoint)) = (session.id, endpoint()) {
+        let _ = send(&endpoint, "DELETE", Some(&id), "").await;
+    }
+    Ok(())
+}
+
+/// Run the proxy until stdin closes; returns the process exit code.
+pub fn run() -> i32 {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("napkin --mcp-stdio: {}", e);
+            return 1;
+        }
+    };
+    match runtime.block_on(serve()) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("napkin --mcp-stdio: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifier_matches_tauri_config() {
+        let conf: serde_json::Value =
+            serde_json::from_str(include_str!("../tauri.conf.json")).unwrap();
+        assert_eq!(conf["identifier"], APP_IDENTIFIER);
+    }
+
+    #[test]
+    fn parses_plain_and_chunked_responses() {
+        let plain = b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nMcp-Session-Id: s1\r\n\r\n{}";
+        let response = parse_response(plain).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"{}");
+        assert_eq!(response.session.as_deref(), Some("s1"));
+
+        let chunked =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n{\"a\"\r\n3\r\n:1}\r\n0\r\n\r\n";
+        let response = parse_response(chunked).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"{\"a\":1}");
+        assert_eq!(response.session, None);
+
+        assert!(parse_response(b"garbage").is_err());
+    }
+
+    #[test]
+    fn transport_errors_answer_requests_only() {
+        let reply =
+            transport_error(r#"{"jsonrpc":"2.0","id":7,"method":"tools/list"}"#, "down").unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply["id"], 7);
+        assert_eq!(reply["error"]["message"], "down");
+        assert!(transport_error(
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+            "down"
+        )
+        .is_none());
+    }
+
+    #[tokio::test]
+    async fn forwards_to_loopback_server() {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..n]).to_string();
+            assert!(request.contains("Authorization: Bearer t0ken"));
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 11\r\n\r\n{\"id\":1}\n  ")
+                .await
+                .unwrap();
+        });
+        let endpoint = Endpoint {
+            port,
+            token: "t0ken".to_string(),
+            cert: None,
+        };
+        let mut session = ClientSession::default();
+        let reply = forward(&endpoint, &mut session, r#"{"id":1}"#)
+            .await
+            .unwrap();
+        assert_eq!(reply.as_deref().map(str::trim), Some(r#"{"id":1}"#));
+    }
+
+    #[tokio::test]
+    async fn replays_the_handshake_when_the_session_is_lost() {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut sessions = 0;
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let reply = if request.contains(r#""method":"initialize""#) {
+                    sessions += 1;
+                    format!(
+                        "HTTP/1.1 200 OK\r\nmcp-session-id: s{}\r\ncontent-length: 2\r\n\r\n{{}}",
+                        sessions
+                    )
+                } else if request.contains("notifications/initialized") {
+                    "HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\n\r\n".to_string()
+                } else if request.contains("mcp-session-id: s2") {
+                    "HTTP/1.1 200 OK\r\ncontent-length: 4\r\n\r\n\"ok\"".to_string()
+                } else {
+                    // Session s1 was lost in a restart.
+                    "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n".to_string()
+                };
+                socket.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+        let endpoint = Endpoint {
+            port,
+            token: "t".to_string(),
+            cert: None,
+        };
+        let mut session = ClientSession::default();
+        forward(
+            &endpoint,
+            &mut session,
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize"}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(session.id.as_deref(), Some("s1"));
+
+        let reply = forward(
+            &endpoint,
+            &mut session,
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply.as_deref(), Some("\"ok\""));
+        assert_eq!(session.id.as_deref(), Some("s2"));
+    }
+}
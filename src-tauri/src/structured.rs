@@ -0,0 +1,392 @@
+//! Structured tool results.
+//!
+//! A successful `tools/call` returns its result as `structuredContent`, next
+//! to the JSON text block older clients read, and every tool declares the
+//! shape of that result as its `outputSchema`. The schemas describe what a
+//! tool returns when it works without marking fields required, because a
+//! failed call's `structuredContent` is `{ "error", "code" }` (see
+//! `tool_errors`) and must validate too.
+
+use serde_json::{json, Map, Value};
+
+fn object(properties: Value) -> Value {
+    json!({ "type": "object", "properties": properties })
+}
+
+/// A shape as the canvas serializes it; only the fields every shape has.
+fn shape() -> Value {
+    object(json!({
+        "id": { "type": "string" },
+        "type": { "type": "string" },
+        "x": { "type": "number" },
+        "y": { "type": "number" },
+        "width": { "type": "number" },
+        "height": { "type": "number" },
+        "text": { "type": "string" },
+        "createdBy": { "type": "string" },
+    }))
+}
+
+fn shapes() -> Value {
+    json!({ "type": "array", "items": shape() })
+}
+
+fn ids() -> Value {
+    json!({ "type": "array", "items": { "type": "string" } })
+}
+
+/// `{ success, id }`, returned by the tools that change one shape in place.
+fn done(key: &str) -> Value {
+    object(json!({
+        "success": { "type": "boolean" },
+        key: { "type": "string" },
+    }))
+}
+
+/// A job's progress, from `submit_job` and `get_job_status`.
+fn job() -> Value {
+    object(json!({
+        "jobId": { "type": "string" },
+        "tool": { "type": "string" },
+        "status": { "type": "string", "enum": ["running", "succeeded", "failed"] },
+        "submittedAt": { "type": "integer" },
+        "finishedAt": { "type": "integer" },
+    }))
+}
+
+/// The `outputSchema` for `tool`; a bare object for a tool not listed here.
+pub fn output_schema(tool: &str) -> Value {
+    match tool {
+        "get_canvas" => object(json!({
+            "shapes": shapes(),
+            "viewport": object(json!({
+                "x": { "type": "number" },
+                "y": { "type": "number" },
+                "zoom": { "type": "number" },
+            })),
+            "groups": { "type": "array", "items": { "type": "object" } },
+            "shapeCount": { "type": "integer" },
+            "truncated": { "type": "boolean", "description": "Set when the response budget cut the shape list short" },
+            "nextCursor": { "type": "string" },
+            "revision": { "type": "integer" },
+            "sinceRevision": { "type": ["integer", "null"] },
+            "unchanged": { "type": "boolean", "description": "Set when nothing changed since sinceRevision" },
+            "deletedIds": ids(),
+        })),
+        "list_shapes" => object(json!({
+            "shapes": shapes(),
+            "count": { "type": "integer", "description": "Shapes in this response" },
+            "total": { "type": "integer", "description": "Shapes matching the filters" },
+            "nextOffset": { "type": "integer" },
+            "truncated": { "type": "boolean" },
+            "nextCursor": { "type": "string" },
+        })),
+        "get_shape" | "create_shape" | "update_shape" | "create_image" | "create_connection" => {
+            shape()
+        }
+        "delete_shape" | "bring_to_front" | "send_to_back" | "bring_forward" | "send_backward"
+        | "remove_constraint" => done("id"),
+        "set_viewport" => object(json!({
+            "x": { "type": "number" },
+            "y": { "type": "number" },
+            "zoom": { "type": "number" },
+        })),
+        "select_shapes" => object(json!({ "selectedIds": ids() })),
+        "list_tabs" => object(json!({
+            "tabs": {
+                "type": "array",
+                "items": object(json!({
+                    "id": { "type": "string" },
+                    "title": { "type": "string" },
+                    "isDirty": { "type": "boolean" },
+                    "isActive": { "type": "boolean" },
+                    "isMcpActive": { "type": "boolean" },
+                })),
+            },
+            "activeTabId": { "type": ["string", "null"] },
+            "mcpActiveTabId": { "type": ["string", "null"] },
+        })),
+        "create_tab" => object(json!({
+            "id": { "type": "string" },
+            "title": { "type": "string" },
+        })),
+        "switch_tab" => object(json!({
+            "success": { "type": "boolean" },
+            "mcpActiveTabId": { "type": "string" },
+        })),
+        "rename_tab" => object(json!({
+            "success": { "type": "boolean" },
+            "tabId": { "type": "string" },
+            "title": { "type": "string" },
+        })),
+        "group_shapes" => object(json!({
+            "groupId": { "type": "string" },
+            "shapeIds": ids(),
+        })),
+        "ungroup" => done("groupId"),
+        "clear_canvas" => object(json!({ "success": { "type": "boolean" } })),
+        "batch_operations" => object(json!({
+            "results": {
+                "type": "array",
+                "items": object(json!({
+                    "action": { "type": "string", "enum": ["created", "updated", "deleted"] },
+                    "id": { "type": "string" },
+                    "shape": shape(),
+                    "error": { "type": "string" },
+                })),
+            },
+        })),
+        "reorganize" => object(json!({
+            "moved": { "type": "integer" },
+            "changes": { "type": "array", "items": { "type": "object" } },
+        })),
+        "set_snap_settings" => object(json!({
+            "snapToGrid": { "type": "boolean" },
+            "alignmentHints": { "type": "boolean" },
+            "objectSnap": { "type": "boolean" },
+        })),
+        "acquire_canvas_lock" => object(json!({
+            "locked": { "type": "boolean" },
+            "ttlSeconds": { "type": "number" },
+        })),
+        "release_canvas_lock" => object(json!({ "released": { "type": "boolean" } })),
+        "repair_document" => object(json!({
+            "dryRun": { "type": "boolean" },
+            "fixed": { "type": "integer" },
+            "fixes": { "type": "array", "items": { "type": "object" } },
+        })),
+        "add_constraint" => object(json!({
+            "constraint": { "type": "object" },
+            "updatedShapes": { "type": "integer" },
+        })),
+        "list_constraints" => object(json!({
+            "constraints": { "type": "array", "items": { "type": "object" } },
+        })),
+        "fit_shape_to_text" => object(json!({
+            "id": { "type": "string" },
+            "resized": { "type": "boolean" },
+            "width": { "type": ["number", "null"] },
+            "height": { "type": ["number", "null"] },
+        })),
+        "trace_image" => object(json!({
+            "strokes": { "type": "integer" },
+            "strokeWidth": { "type": "number" },
+            "replaced": { "type": "boolean" },
+        })),
+        "cleanup_photo" => object(json!({
+            "id": { "type": "string" },
+            "width": { "type": "number" },
+            "height": { "type": "number" },
+            "replaced": { "type": "boolean" },
+        })),
+        "set_document_scale" => object(json!({
+            "scale": { "type": ["object", "null"] },
+        })),
+        "extract_outline" => object(json!({
+            "outline": { "type": "array", "items": { "type": "object" } },
+            "text": { "type": "string", "description": "The outline as indented Markdown" },
+        })),
+        "partition_canvas" => object(json!({
+            "dryRun": { "type": "boolean" },
+            "sourceTabId": { "type": "string" },
+            "tabs": {
+                "type": "array",
+                "items": object(json!({
+                    "id": { "type": "string" },
+                    "title": { "type": "string" },
+                    "shapeCount": { "type": "integer" },
+                })),
+            },
+            "cutConnectors": { "type": "integer" },
+        })),
+        "get_common_properties" => object(json!({
+            "count": { "type": "integer" },
+            "types": { "type": "object" },
+            "common": { "type": "object" },
+            "diverging": { "type": "object" },
+            "missing": ids(),
+        })),
+        "bulk_update" => object(json!({
+            "updated": { "type": "integer" },
+            "ids": ids(),
+            "properties": { "type": "object" },
+        })),
+        "translate_canvas" => object(json!({
+            "translated": { "type": "integer" },
+            "targetLang": { "type": "string" },
+            "via": { "type": "string" },
+            "untranslated": ids(),
+            "reverted": { "type": "integer", "description": "Shapes restored, when reverting" },
+        })),
+        "get_shape_order" => object(json!({
+            "count": { "type": "integer" },
+            "order": {
+                "type": "array",
+                "items": object(json!({
+                    "id": { "type": "string" },
+                    "type": { "type": "string" },
+                    "text": { "type": "string" },
+                })),
+            },
+        })),
+        "set_shape_order" => object(json!({
+            "count": { "type": "integer" },
+            "changed": { "type": "boolean" },
+        })),
+        "get_shapes_in_region" => object(json!({
+            "shapes": shapes(),
+            "count": { "type": "integer" },
+            "truncated": { "type": "boolean" },
+            "nextCursor": { "type": "string" },
+        })),
+        "get_shape_at_point" => object(json!({
+            "shape": { "anyOf": [shape(), { "type": "null" }] },
+            "below": ids(),
+        })),
+        "lint_canvas" => object(json!({
+            "shapeCount": { "type": "integer" },
+            "counts": { "type": "object", "description": "Findings per rule that was run" },
+            "findings": {
+                "type": "array",
+                "items": object(json!({
+                    "rule": { "type": "string", "enum": ["danglingBinding", "zeroSize", "overlap", "offscreenOrphan", "lowContrast"] },
+                    "severity": { "type": "string", "enum": ["error", "warning", "info"] },
+                    "shapeIds": ids(),
+                    "message": { "type": "string" },
+                })),
+            },
+        })),
+        "export_png" => object(json!({
+            "mimeType": { "type": "string" },
+            "width": { "type": "integer", "description": "In pixels" },
+            "height": { "type": "integer", "description": "In pixels" },
+            "shapeCount": { "type": "integer", "description": "Shapes drawn" },
+        })),
+        "screenshot_viewport" => object(json!({
+            "mimeType": { "type": "string" },
+            "width": { "type": "integer", "description": "In pixels" },
+            "height": { "type": "integer", "description": "In pixels" },
+            "viewport": object(json!({
+                "x": { "type": "number" },
+                "y": { "type": "number" },
+                "zoom": { "type": "number" },
+            })),
+            "visibleRegion": object(json!({
+                "x": { "type": "number" },
+                "y": { "type": "number" },
+                "width": { "type": "number" },
+                "height": { "type": "number" },
+            })),
+            "visibleShapeIds": ids(),
+        })),
+        "get_changes" => object(json!({
+            "revision": { "type": "integer" },
+            "sinceRevision": { "type": "integer" },
+            "created": shapes(),
+            "updated": shapes(),
+            "deleted": ids(),
+        })),
+        "submit_job" | "get_job_status" => job(),
+        "get_job_result" => json!({
+            "type": "object",
+            "description": "The result of the job's tool, as its own outputSchema describes",
+        }),
+        _ => json!({ "type": "object" }),
+    }
+}
+
+/// Declare `tool`'s `outputSchema`.
+pub fn extend_schema(tool: &mut Value) {
+    let Some(name) = tool.get("name").and_then(Value::as_str) else {
+        return;
+    };
+    let schema = output_schema(name);
+    if let Some(fields) = tool.as_object_mut() {
+        fields.insert("outputSchema".to_string(), schema);
+    }
+}
+
+/// The `tools/call` result for a tool's successful `result`: the JSON text,
+/// plus `structuredContent` when the result is an object (MCP requires one).
+pub fn call_result(result: &Value) -> Value {
+    let mut call = Map::new();
+    call.insert(
+        "content".to_string(),
+        json!([{
+            "type": "text",
+            "text": serde_json::to_string_pretty(result).unwrap_or_default(),
+        }]),
+    );
+    if result.is_object() {
+        call.insert("structuredContent".to_string(), result.clone());
+    }
+    Value::Object(call)
+}
+
+/// The `tools/call` result for an image a tool returned as `{ data,
+/// mimeType, ... }`: an image block, with the rest as `structuredContent`
+/// (the base64 data only once).
+pub fn image_result(result: &Value) -> Value {
+    let mut details = result.clone();
+    let data = details
+        .as_object_mut()
+        .and_then(|fields| fields.remove("data"))
+        .unwrap_or_default();
+    json!({
+        "content": [{
+            "type": "image",
+            "data": data,
+            "mimeType": result["mimeType"].as_str().unwrap_or("image/png"),
+        }],
+        "structuredContent": details,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api;
+
+    #[test]
+    fn every_tool_declares_an_output_schema() {
+        let tools = api::all_tools();
+        for tool in tools.as_array().unwrap() {
+            let name = tool["name"].as_str().unwrap();
+            assert_ne!(
+                tool["outputSchema"],
+                json!({ "type": "object" }),
+                "{} has no output schema of its own",
+                name
+            );
+            assert_eq!(tool["outputSchema"]["type"], "object", "{}", name);
+        }
+    }
+
+    #[test]
+    fn images_are_returned_as_image_content() {
+        let result = json!({ "data": "iVBORw0KGgo=", "mimeType": "image/png", "width": 2, "height": 1 });
+        let call = image_result(&result);
+        assert_eq!(
+            call["content"],
+            json!([{ "type": "image", "data": "iVBORw0KGgo=", "mimeType": "image/png" }])
+        );
+        assert_eq!(
+            call["structuredContent"],
+            json!({ "mimeType": "image/png", "width": 2, "height": 1 })
+        );
+    }
+
+    #[test]
+    fn object_results_are_structured() {
+        let result = json!({ "success": true, "id": "s1" });
+        let call = call_result(&result);
+        assert_eq!(call["structuredContent"], result);
+        let text: Value =
+            serde_json::from_str(call["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(text, result);
+
+        assert!(call_result(&json!(["a"]))
+            .get("structuredContent")
+            .is_none());
+    }
+}
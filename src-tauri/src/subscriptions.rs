@@ -0,0 +1,255 @@
+//! Resource subscriptions (`resources/subscribe`) and change notifications.
+//!
+//! A subscription belongs to an `McpClient`: the HTTP session, or the
+//! WebSocket, legacy SSE or tunnel connection. When the webview reports an
+//! edit (`api_notify_change`, which also records the canvas revision and
+//! broadcasts the shape changes) or a file is saved, each subscriber to an
+//! affected URI gets `notifications/resources/updated` on its stream: the
+//! session's `GET /mcp` stream, the legacy SSE stream or the socket. Streams
+//! held by the sidecar process are reached through its relay (`sidecar.rs`).
+
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use tauri::Manager;
+use tokio::sync::mpsc;
+
+use crate::api::SharedApiState;
+use crate::canvas_events::{self, ShapeChange};
+use crate::resources;
+use crate::wal;
+
+/// Where a subscriber's notifications are delivered.
+pub type Notifier = mpsc::UnboundedSender<Value>;
+/// Notifications for subscribers whose stream is in another process, tagged
+/// with the subscriber id.
+pub type Relay = mpsc::UnboundedSender<(String, Value)>;
+
+#[derive(Default)]
+pub struct Subscriptions {
+    uris: HashMap<String, HashSet<String>>,
+    streams: HashMap<String, Notifier>,
+    relay: Option<Relay>,
+}
+
+impl Subscriptions {
+    pub fn subscribe(&mut self, subscriber: &str, uri: &str) {
+        self.uris
+            .entry(subscriber.to_string())
+            .or_default()
+            .insert(uri.to_string());
+    }
+
+    pub fn unsubscribe(&mut self, subscriber: &str, uri: &str) {
+        if let Some(uris) = self.uris.get_mut(subscriber) {
+            uris.remove(uri);
+            if uris.is_empty() {
+                self.uris.remove(subscriber);
+            }
+        }
+    }
+
+    /// Deliver `subscriber`'s notifications to `stream` from now on.
+    pub fn attach(&mut self, subscriber: &str, stream: Notifier) {
+        self.streams.insert(subscriber.to_string(), stream);
+    }
+
+    /// Stop delivering to `stream` if it is still `subscriber`'s; the
+    /// subscriptions stay for a stream the session opens later.
+    pub fn detach(&mut self, subscriber: &str, stream: &Notifier) {
+        if self
+            .streams
+            .get(subscriber)
+            .is_some_and(|s| s.same_channel(stream))
+        {
+            self.streams.remove(subscriber);
+        }
+    }
+
+    /// Drop everything about a subscriber whose connection or session ended.
+    pub fn forget(&mut self, subscriber: &str) {
+        self.uris.remove(subscriber);
+        self.streams.remove(subscriber);
+    }
+
+    /// Send `message` to `subscriber`'s stream, wherever it is; false when
+    /// it has none open.
+    pub fn send(&mut self, subscriber: &str, message: Value) -> bool {
+        match self.streams.get(subscriber) {
+            Some(stream) => {
+                let sent = stream.send(message).is_ok();
+                if !sent {
+                    self.streams.remove(subscriber);
+                }
+                sent
+            }
+            None => self
+                .relay
+                .as_ref()
+                .is_some_and(|relay| relay.send((subscriber.to_string(), message)).is_ok()),
+        }
+    }
+
+    pub fn set_relay(&mut self, relay: Option<Relay>) {
+        self.relay = relay;
+    }
+
+    /// Notify the subscribers of any of `uris`; returns how many
+    /// notifications went out.
+    pub fn notify(&mut self, uris: &[String]) -> usize {
+        let mut sent = 0;
+        let mut closed = Vec::new();
+        for (subscriber, subscribed) in &self.uris {
+            for uri in uris.iter().filter(|uri| subscribed.contains(*uri)) {
+                let message = updated(uri);
+                let delivered = match self.streams.get(subscriber) {
+                    Some(stream) => match stream.send(message) {
+                        Ok(()) => true,
+                        Err(_) => {
+                            closed.push(subscriber.clone());
+                            false
+                        }
+                    },
+                    None => self
+                        .relay
+                        .as_ref()
+                        .is_some_and(|relay| relay.send((subscriber.clone(), message)).is_ok()),
+                };
+                if delivered {
+                    sent += 1;
+                }
+            }
+        }
+        // A closed stream may be reopened (a new `GET /mcp` for the same
+        // session), so only the stream is dropped, not the subscriptions.
+        for subscriber in closed {
+            self.streams.remove(&subscriber);
+        }
+        sent
+    }
+}
+
+/// The `notifications/resources/updated` message for `uri`.
+pub fn updated(uri: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/resources/updated",
+        "params": { "uri": uri },
+    })
+}
+
+/// Notify subscribers from code that only holds an app handle.
+pub fn notify_app(app: &tauri::AppHandle, uris: Vec<String>) {
+    let Some(state) = app.try_state::<SharedApiState>() else {
+        return;
+    };
+    let subscriptions = state.subscriptions.clone();
+    tauri::async_runtime::spawn(async move {
+        subscriptions.lock().await.notify(&uris);
+    });
+}
+
+// --- Tauri command ---
+
+/// The webview reports an edit to tab `tab_id`, with the shapes it touched;
+/// `mcp_active` when it is the tab agents are working on (`napkin://canvas`).
+/// Each report is a new canvas revision and updates the region index.
+/// Edits to a saved `document` are first logged against the tab's id, with
+/// its position `tab_index` (`wal.rs`). Returns how many resource
+/// notifications went out.
+#[tauri::command]
+pub async fn api_notify_change(
+    tab_id: String,
+    mcp_active: bool,
+    changes: Vec<ShapeChange>,
+    document: Option<String>,
+    tab_index: Option<usize>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<usize, String> {
+    if let Some(document) = document {
+        let logged = changes.clone();
+        let tab = tab_id.clone();
+        let result = tauri::async_runtime::spawn_blocking(move || {
+            wal::append(std::path::Path::new(&document), &tab, tab_index, logged)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+        if let Err(e) = result {
+            log::warn!("{}", e);
+        }
+    }
+    state
+        .revisions
+        .lock()
+        .await
+        .bump(&tab_id, &changes);
+    state.regions.lock().await.apply(&tab_id, &changes);
+    if state.visibility.is_hidden(&tab_id) {
+        return Ok(0);
+    }
+    canvas_events::publish(&state.canvas_events, &tab_id, &changes);
+    let uris = resources::tab_uris(&tab_id, mcp_active);
+    Ok(state.subscriptions.lock().await.notify(&uris))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CANVAS: &str = "napkin://canvas";
+
+    #[test]
+    fn subscribers_are_notified_of_their_uris_only() {
+        let mut subs = Subscriptions::default();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        subs.subscribe("a", CANVAS);
+        subs.attach("a", tx);
+        subs.subscribe("b", "napkin://tabs/other");
+
+        assert_eq!(subs.notify(&[CANVAS.to_string()]), 1);
+        assert_eq!(rx.try_recv().unwrap(), updated(CANVAS));
+
+        subs.unsubscribe("a", CANVAS);
+        assert_eq!(subs.notify(&[CANVAS.to_string()]), 0);
+        assert!(!subs.uris.contains_key("a"));
+    }
+
+    #[test]
+    fn subscribers_without_a_stream_go_through_the_relay() {
+        let mut subs = Subscriptions::default();
+        subs.subscribe("remote", CANVAS);
+        assert_eq!(subs.notify(&[CANVAS.to_string()]), 0);
+
+        let (relay, mut relayed) = mpsc::unbounded_channel();
+        subs.set_relay(Some(relay));
+        assert_eq!(subs.notify(&[CANVAS.to_string()]), 1);
+        assert_eq!(
+            relayed.try_recv().unwrap(),
+            ("remote".to_string(), updated(CANVAS))
+        );
+    }
+
+    #[test]
+    fn closed_streams_are_dropped_but_subscriptions_kept() {
+        let mut subs = Subscriptions::default();
+        let (tx, rx) = mpsc::unbounded_channel();
+        subs.subscribe("a", CANVAS);
+        subs.attach("a", tx);
+        drop(rx);
+        assert_eq!(subs.notify(&[CANVAS.to_string()]), 0);
+        assert!(subs.streams.is_empty());
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        subs.attach("a", tx.clone());
+        assert_eq!(subs.notify(&[CANVAS.to_string()]), 1);
+        assert!(rx.try_recv().is_ok());
+
+        let (stale, _) = mpsc::unbounded_channel();
+        subs.detach("a", &stale);
+        assert_eq!(subs.streams.len(), 1);
+        subs.detach("a", &tx);
+        assert!(subs.streams.is_empty());
+
+        subs.forget("a");
+        assert_eq!(subs.notify(&[CANVAS.to_string()]), 0);
+    }
+}
@@ -0,0 +1,177 @@
+//! Watching the in-process server task.
+//!
+//! `start_api_server` hands the listener to [`run`], which serves on it in a
+//! task of its own and waits for that task to end. Ending because the
+//! server was stopped is the normal case. Ending any other way (an I/O error
+//! from the listener, or a panic) used to leave the app believing the server
+//! was up; now the server is restarted on the same port after a growing
+//! delay, unless `mcpRestart` is off or it has failed [`RestartPolicy`]'s
+//! `maxAttempts` times in a row. Then the server's state is cleared as if
+//! it had been stopped and the webview gets an `api-server-stopped` event.
+//! (The sidecar has its own supervisor, in `sidecar.rs`.)
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio_rustls::rustls::ServerConfig;
+
+use crate::api::{self, SharedApiState};
+use crate::connections;
+use crate::settings;
+use crate::stdio;
+use crate::tls;
+
+/// The first delay before a restart; each failure in a row doubles it.
+const FIRST_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// A server that ran this long before failing starts the count over.
+const HEALTHY_AFTER: Duration = Duration::from_secs(60);
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+/// Emitted with `{ reason }` when the server ends without being stopped
+/// and is not restarted.
+const STOPPED_EVENT: &str = "api-server-stopped";
+
+/// Whether the server is restarted when its task ends unexpectedly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RestartPolicy {
+    pub enabled: bool,
+    /// Restarts in a row before giving up.
+    pub max_attempts: u32,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+}
+
+/// The delay before restart number `attempt` (from 1).
+fn backoff(attempt: u32) -> Duration {
+    FIRST_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(MAX_DELAY)
+}
+
+/// Resolves once `shutdown` says to stop.
+async fn stop_requested(mut shutdown: watch::Receiver<bool>) {
+    while !*shutdown.borrow_and_update() {
+        if shutdown.changed().await.is_err() {
+            // Nobody can ask any more; keep serving
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Serve on `listener` until stopped, restarting the server when it ends
+/// on its own.
+pub async fn run(
+    state: SharedApiState,
+    listener: TcpListener,
+    tls_config: Option<Arc<ServerConfig>>,
+    addr: SocketAddr,
+    shutdown: watch::Receiver<bool>,
+) {
+    let scheme = if tls_config.is_some() { "https" } else { "http" };
+    let mut listener = Some(listener);
+    let mut attempts = 0;
+    loop {
+        let listener = match listener.take() {
+            Some(listener) => Ok(listener),
+            None => api::bind_listener(addr.ip(), addr.port(), 0)
+                .await
+                .map(|(listener, _)| listener),
+        };
+        let started = Instant::now();
+        let reason = match listener {
+            Ok(listener) => {
+                log::info!("MCP server listening on {}://{}/mcp", scheme, addr);
+                let router = api::build_router(Arc::clone(&state));
+                let serving = tauri::async_runtime::spawn(tls::serve(
+                    listener,
+                    tls_config.clone(),
+                    router,
+                    stop_requested(shutdown.clone()),
+                ));
+                match serving.await {
+                    Ok(Ok(())) => "the server task ended".to_string(),
+                    Ok(Err(e)) => format!("Server error: {}", e),
+                    Err(e) => format!("The server task failed: {}", e),
+                }
+            }
+            Err(e) => e,
+        };
+        if *shutdown.borrow() {
+            log::info!("MCP server stopped");
+            return;
+        }
+
+        log::error!("MCP server on {} ended unexpectedly: {}", addr, reason);
+        state.health.record_error(reason.clone());
+        if started.elapsed() >= HEALTHY_AFTER {
+            attempts = 0;
+        }
+        attempts += 1;
+        let policy = settings::current(&state.app_handle).mcp_restart;
+        if !policy.enabled || attempts > policy.max_attempts {
+            give_up(&state, &reason).await;
+            return;
+        }
+        let delay = backoff(attempts);
+        log::info!(
+            "Restarting the MCP server in {:?} (attempt {} of {})",
+            delay,
+            attempts,
+            policy.max_attempts
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = stop_requested(shutdown.clone()) => return,
+        }
+    }
+}
+
+/// The server is down for good: clear its state as `stop_api_server` would.
+async fn give_up(state: &SharedApiState, reason: &str) {
+    state.server_shutdown.lock().await.take();
+    *state.server_port.lock().await = None;
+    stdio::remove_port_file(&state.app_handle);
+    state.health.set_running(false);
+    connections::update(&state.app_handle, None);
+    let _ = state
+        .app_handle
+        .emit(STOPPED_EVENT, json!({ "reason": reason }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restarts_back_off_up_to_a_limit() {
+        assert_eq!(backoff(1), Duration::from_millis(500));
+        assert_eq!(backoff(2), Duration::from_secs(1));
+        assert_eq!(backoff(4), Duration::from_secs(4));
+        assert_eq!(backoff(40), MAX_DELAY);
+    }
+
+    #[tokio::test]
+    async fn stopping_is_noticed_whenever_it_was_asked() {
+        let (tx, rx) = watch::channel(false);
+        let waiting = tokio::spawn(stop_requested(rx.clone()));
+        tx.send(true).unwrap();
+        waiting.await.unwrap();
+        // Already asked before anyone waited
+        tokio::time::timeout(Duration::from_secs(1), stop_requested(rx))
+            .await
+            .unwrap();
+    }
+}
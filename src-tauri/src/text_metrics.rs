@@ -0,0 +1,369 @@
+//! Text measurement without a browser.
+//!
+//! The webview measures text with canvas `measureText`; importers, exporters
+//! and MCP tools need the same answers on the Rust side. Widths come from
+//! Helvetica/Arial advance widths (the fallback for the app's default
+//! `sans-serif`), which is close enough to size boxes so labels never clip.
+//! Wrapping and padding mirror `renderShapeText` in renderExport.ts.
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use serde_json::Value;
+
+/// Line height as a multiple of font size (renderExport.ts uses 1.2).
+pub const LINE_HEIGHT: f64 = 1.2;
+/// Inner padding between a shape's edge and its label.
+pub const TEXT_PADDING: f64 = 10.0;
+
+/// Helvetica advance widths for ASCII 0x20..=0x7E, in 1/1000 em.
+#[rustfmt::skip]
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // ' '..'/'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, // '0'..'9'
+    278, 278, 584, 584, 584, 556, 1015, // ':'..'@'
+    667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, // 'A'..'M'
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, // 'N'..'Z'
+    278, 278, 278, 469, 556, 333, // '['..'`'
+    556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, // 'a'..'m'
+    556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, // 'n'..'z'
+    334, 260, 334, 584, // '{'..'~'
+];
+
+fn is_monospace(family: &str) -> bool {
+    let f = family.to_ascii_lowercase();
+    f.contains("mono") || f.contains("courier") || f.contains("consolas")
+}
+
+fn is_wide(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 |
+        0xF900..=0xFAFF | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 |
+        0x1F300..=0x1FAFF)
+}
+
+fn char_width_em(c: char, monospace: bool) -> f64 {
+    if is_wide(c) {
+        return 1.0;
+    }
+    if monospace {
+        return 0.6;
+    }
+    match c as u32 {
+        code @ 0x20..=0x7E => HELVETICA_WIDTHS[(code - 0x20) as usize] as f64 / 1000.0,
+        _ => 0.556,
+    }
+}
+
+/// Width of a single line of text in pixels.
+pub fn line_width(text: &str, font_size: f64, family: &str) -> f64 {
+    let mono = is_monospace(family);
+    text.chars().map(|c| char_width_em(c, mono)).sum::<f64>() * font_size
+}
+
+/// Greedy word wrap, matching the webview: explicit newlines always break,
+/// words are never split, and blank paragraphs are kept.
+pub fn wrap_lines(text: &str, max_width: f64, font_size: f64, family: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        if paragraph.is_empty() {
+            lines.push(String::new());
+            continue;
+        }
+        let mut current = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if current.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", current, word)
+            };
+            if line_width(&candidate, font_size, family) > max_width && !current.is_empty() {
+                lines.push(std::mem::replace(&mut current, word.to_string()));
+            } else {
+                current = candidate;
+            }
+        }
+        if !current.is_empty() {
+            lines.push(current);
+        }
+    }
+    lines
+}
+
+/// Font size the webview falls back to for each shape type.
+pub fn default_font_size(shape_type: &str) -> f64 {
+    match shape_type {
+        "text" => 20.0,
+        "sticky" => 16.0,
+        _ => 14.0,
+    }
+}
+
+/// Default size `create_shape` uses when width/height are omitted.
+fn default_size(shape_type: &str) -> (f64, f64) {
+    match shape_type {
+        "sticky" => (200.0, 200.0),
+        "text" => (200.0, 30.0),
+        _ => (200.0, 150.0),
+    }
+}
+
+/// Fraction of the bounding box usable for text in each shape, since labels
+/// inside an ellipse or diamond have less room than in a rectangle.
+fn text_area_factor(shape_type: &str) -> (f64, f64) {
+    match shape_type {
+        "ellipse" | "cloud" => (0.71, 0.71),
+        "diamond" | "triangle" => (0.5, 0.5),
+        "hexagon" => (0.85, 1.0),
+        "star" => (0.45, 0.45),
+        "cylinder" => (1.0, 0.7),
+        _ => (1.0, 1.0),
+    }
+}
+
+fn fits_text(shape_type: &str) -> bool {
+    !matches!(shape_type, "line" | "arrow" | "freedraw" | "image")
+}
+
+/// Size a shape needs so its label isn't clipped. Only ever grows the shape;
+/// returns `None` when it already fits or has no label.
+pub fn fit_size(shape: &Value) -> Option<(f64, f64)> {
+    let shape_type = shape.get("type").and_then(|t| t.as_str()).unwrap_or("");
+    let text = shape.get("text").and_then(|t| t.as_str()).unwrap_or("");
+    if text.is_empty() || !fits_text(shape_type) {
+        return None;
+    }
+
+    let (default_w, default_h) = default_size(shape_type);
+    let width = shape
+        .get("width")
+        .and_then(|v| v.as_f64())
+        .unwrap_or(default_w);
+    let height = shape
+        .get("height")
+        .and_then(|v| v.as_f64())
+        .unwrap_or(default_h);
+    let font_size = shape
+        .get("fontSize")
+        .and_then(|v| v.as_f64())
+        .unwrap_or_else(|| default_font_size(shape_type));
+    let family = shape
+        .get("fontFamily")
+        .and_then(|v| v.as_str())
+        .unwrap_or("sans-serif");
+
+    // Text shapes have no border, so no padding.
+    let padding = if shape_type == "text" {
+        0.0
+    } else {
+        TEXT_PADDING
+    };
+    let (fx, fy) = text_area_factor(shape_type);
+
+    let longest_word = text
+        .split(['\n', ' '])
+        .map(|w| line_width(w, font_size, family))
+        .fold(0.0, f64::max);
+    let inner_w = (width * fx - padding * 2.0).max(longest_word);
+    let needed_w = (inner_w + padding * 2.0) / fx;
+
+    let lines = wrap_lines(text, inner_w, font_size, family).len() as f64;
+    let needed_h = (lines * font_size * LINE_HEIGHT + padding * 2.0) / fy;
+
+    let new_w = width.max(needed_w.ceil());
+    let new_h = height.max(needed_h.ceil());
+    if new_w > width || new_h > height {
+        Some((new_w, new_h))
+    } else {
+        None
+    }
+}
+
+fn auto_fit_enabled(shape: &Value) -> bool {
+    shape
+        .get("autoFit")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Copy of `shapes` with every `autoFit` shape grown to fit its label.
+pub fn auto_fitted(shapes: &[Value]) -> Vec<Value> {
+    shapes
+        .iter()
+        .map(|shape| {
+            let mut shape = shape.clone();
+            if auto_fit_enabled(&shape) {
+                if let Some((w, h)) = fit_size(&shape) {
+                    shape["width"] = w.into();
+                    shape["height"] = h.into();
+                }
+            }
+            shape
+        })
+        .collect()
+}
+
+// --- MCP integration ---
+
+/// Honor `autoFit` on create/update before the call reaches the webview by
+/// filling in the width/height the label needs.
+pub async fn prepare_tool_call(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &mut Value,
+) -> Result<(), String> {
+    match tool_name {
+        "create_shape" if auto_fit_enabled(arguments) => {
+            if let Some((w, h)) = fit_size(arguments) {
+                arguments["width"] = w.into();
+                arguments["height"] = h.into();
+            }
+        }
+        "update_shape" => {
+            let touches_text = [
+                "text",
+                "fontSize",
+                "fontFamily",
+                "width",
+                "height",
+                "autoFit",
+            ]
+            .iter()
+            .any(|k| arguments.get(*k).is_some());
+            if !touches_text {
+                return Ok(());
+            }
+            let current = get_shape(state, arguments).await?;
+            let mut merged = current;
+            if let (Some(target), Some(patch)) = (merged.as_object_mut(), arguments.as_object()) {
+                for (k, v) in patch {
+                    target.insert(k.clone(), v.clone());
+                }
+            }
+            if auto_fit_enabled(&merged) {
+                if let Some((w, h)) = fit_size(&merged) {
+                    arguments["width"] = w.into();
+                    arguments["height"] = h.into();
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+async fn get_shape(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let id = arguments
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing required field: id")?;
+    let shape = bridge_tool_call(state, "get_shape", serde_json::json!({ "id": id })).await?;
+    if let Some(err) = shape.get("error").and_then(|e| e.as_str()) {
+        return Err(err.to_string());
+    }
+    Ok(shape)
+}
+
+pub fn text_tools() -> Vec<Value> {
+    vec![serde_json::json!({
+        "name": "fit_shape_to_text",
+        "description": "Grow a shape so its text label fits without clipping (wrapping within the current width where possible).",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string", "description": "Shape ID" }
+            },
+            "required": ["id"]
+        }
+    })]
+}
+
+pub async fn call_text_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "fit_shape_to_text" => Some(fit_shape_to_text(state, arguments).await),
+        _ => None,
+    }
+}
+
+async fn fit_shape_to_text(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let shape = get_shape(state, arguments).await?;
+    let id = shape.get("id").cloned().unwrap_or(Value::Null);
+    match fit_size(&shape) {
+        Some((w, h)) => {
+            bridge_tool_call(
+                state,
+                "update_shape",
+                serde_json::json!({ "id": id, "width": w, "height": h }),
+            )
+            .await?;
+            Ok(serde_json::json!({ "id": id, "resized": true, "width": w, "height": h }))
+        }
+        None => Ok(serde_json::json!({
+            "id": id,
+            "resized": false,
+            "width": shape.get("width"),
+            "height": shape.get("height"),
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn measures_with_helvetica_widths() {
+        // "Hi" = 722 + 222 thousandths of an em
+        assert!((line_width("Hi", 10.0, "sans-serif") - 9.44).abs() < 1e-9);
+        assert!((line_width("Hi", 10.0, "monospace") - 12.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn wraps_like_the_webview() {
+        let lines = wrap_lines("one two three\n\nfour", 60.0, 14.0, "sans-serif");
+        assert_eq!(lines, vec!["one two", "three", "", "four"]);
+    }
+
+    #[test]
+    fn fit_grows_height_for_long_labels() {
+        let shape = json!({
+            "type": "rectangle", "width": 120, "height": 40,
+            "text": "a fairly long label that needs several lines"
+        });
+        let (w, h) = fit_size(&shape).unwrap();
+        assert_eq!(w, 120.0);
+        assert!(h > 40.0);
+    }
+
+    #[test]
+    fn fit_widens_for_unbreakable_words() {
+        let shape = json!({ "type": "rectangle", "width": 40, "height": 200, "text": "Supercalifragilistic" });
+        let (w, _) = fit_size(&shape).unwrap();
+        assert!(w > 40.0);
+    }
+
+    #[test]
+    fn fitting_shape_is_left_alone() {
+        let shape = json!({ "type": "rectangle", "width": 200, "height": 150, "text": "OK" });
+        assert_eq!(fit_size(&shape), None);
+        assert_eq!(
+            fit_size(&json!({ "type": "arrow", "text": "long long long" })),
+            None
+        );
+    }
+
+    #[test]
+    fn auto_fitted_only_touches_opted_in_shapes() {
+        let label = "a fairly long label that needs several lines";
+        let shapes = vec![
+            json!({ "type": "rectangle", "width": 60, "height": 20, "text": label, "autoFit": true }),
+            json!({ "type": "rectangle", "width": 60, "height": 20, "text": label }),
+        ];
+        let fitted = auto_fitted(&shapes);
+        assert!(fitted[0]["height"].as_f64().unwrap() > 20.0);
+        assert_eq!(fitted[1]["height"], json!(20));
+    }
+}
@@ -0,0 +1,176 @@
+//! Small previews of saved documents.
+//!
+//! A thumbnail is the active board of a `.napkin` file drawn by the native
+//! renderer (`render.rs`), whatever the file's storage format. They are
+//! cached as PNGs under `<app data>/thumbnails`, named by a hash of the
+//! document's path, and a cached one is used for as long as it is newer
+//! than the document. Saving a document redraws its thumbnail in the
+//! background, and so does startup for recent files whose thumbnail is
+//! missing or stale, so `get_document_thumbnail` rarely has to wait.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+use crate::docstore;
+use crate::render;
+use crate::resources::RecentFiles;
+
+const THUMBNAILS_DIR: &str = "thumbnails";
+/// The longest side of a cached thumbnail, in pixels.
+const SIDE: u32 = 256;
+
+/// Where the thumbnail of `document` is cached in `dir`.
+fn cache_path(dir: &Path, document: &str) -> PathBuf {
+    let digest = Sha256::digest(document.as_bytes());
+    let name: String = digest[..12].iter().map(|b| format!("{:02x}", b)).collect();
+    dir.join(format!("{}.png", name))
+}
+
+/// Whether `cached` was drawn after `document` last changed.
+fn is_fresh(cached: &Path, document: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(cached), modified(document)) {
+        (Some(cached), Some(document)) => cached >= document,
+        _ => false,
+    }
+}
+
+/// Draw the thumbnail of `document` and cache it in `dir`.
+fn generate(dir: &Path, document: &str) -> Result<Vec<u8>, String> {
+    let board = docstore::active_board(Path::new(document))?;
+    let shapes = render::typed(board["shapes"].as_array().map_or(&[], Vec::as_slice));
+    let image = render::thumbnail(&shapes, SIDE)?;
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let cached = cache_path(dir, document);
+    std::fs::write(&cached, &image.bytes)
+        .map_err(|e| format!("Failed to write {}: {}", cached.display(), e))?;
+    Ok(image.bytes)
+}
+
+/// The cached thumbnail of `document` if it is current, otherwise a new one.
+fn load(dir: &Path, document: &str) -> Result<Vec<u8>, String> {
+    let cached = cache_path(dir, document);
+    if is_fresh(&cached, Path::new(document)) {
+        if let Ok(bytes) = std::fs::read(&cached) {
+            return Ok(bytes);
+        }
+    }
+    generate(dir, document)
+}
+
+fn thumbnails_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(THUMBNAILS_DIR))
+        .map_err(|e| format!("No app data directory: {}", e))
+}
+
+/// Redraw the thumbnail of `document` in the background, if it is stale.
+pub fn refresh(app: &tauri::AppHandle, document: String) {
+    let Ok(dir) = thumbnails_dir(app) else {
+        return;
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        if is_fresh(&cache_path(&dir, &document), Path::new(&document)) {
+            return;
+        }
+        if let Err(e) = generate(&dir, &document) {
+            log::debug!("No thumbnail for {}: {}", document, e);
+        }
+    });
+}
+
+/// Delete the thumbnail of `document`, which is no longer to be shown.
+pub fn discard(app: &tauri::AppHandle, document: &str) {
+    if let Ok(dir) = thumbnails_dir(app) {
+        let _ = std::fs::remove_file(cache_path(&dir, document));
+    }
+}
+
+/// Bring the thumbnails of recent files up to date, one after another.
+pub fn refresh_recent(app: &tauri::AppHandle) {
+    let Some(recent) = app.try_state::<RecentFiles>() else {
+        return;
+    };
+    let documents = recent.list();
+    let Ok(dir) = thumbnails_dir(app) else {
+        return;
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        for document in documents {
+            if Path::new(&document).exists()
+                && !is_fresh(&cache_path(&dir, &document), Path::new(&document))
+            {
+                let _ = generate(&dir, &document);
+            }
+        }
+    });
+}
+
+// --- Tauri command ---
+
+/// A preview of the document at `path` as a PNG data URL.
+#[tauri::command]
+pub async fn get_document_thumbnail(app: tauri::AppHandle, path: String) -> Result<String, String> {
+    let dir = thumbnails_dir(&app)?;
+    let bytes = tauri::async_runtime::spawn_blocking(move || load(&dir, &path))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(format!("data:image/png;base64,{}", STANDARD.encode(bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn thumbnails_are_cached_until_the_document_changes() {
+        let root = std::env::temp_dir().join(format!("napkin-thumbs-{}", uuid::Uuid::new_v4()));
+        let dir = root.join(THUMBNAILS_DIR);
+        std::fs::create_dir_all(&root).unwrap();
+        let document = root.join("board.napkin");
+        let board = |width: u32| {
+            json!({
+                "type": "collection",
+                "activeDocumentIndex": 1,
+                "documents": [
+                    { "shapes": [] },
+                    { "shapes": [{ "id": "a", "type": "rectangle", "x": 0, "y": 0,
+                        "width": width, "height": 100 }] },
+                ]
+            })
+        };
+        std::fs::write(&document, board(400).to_string()).unwrap();
+        let path = document.to_string_lossy().to_string();
+
+        let first = load(&dir, &path).unwrap();
+        assert!(first.starts_with(b"\x89PNG"));
+        let cached = cache_path(&dir, &path);
+        assert!(is_fresh(&cached, &document));
+        // Wide board, so the width is the full side
+        let image = image::load_from_memory(&first).unwrap();
+        assert_eq!(image.width(), SIDE);
+        assert!(image.height() < SIDE);
+
+        // An older thumbnail than the document is drawn again
+        std::fs::write(&document, board(100).to_string()).unwrap();
+        let past = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(&cached)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+        assert!(!is_fresh(&cached, &document));
+        let second = load(&dir, &path).unwrap();
+        assert_ne!(first, second);
+        assert!(is_fresh(&cached, &document));
+
+        assert!(load(&dir, &root.join("missing.napkin").to_string_lossy()).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
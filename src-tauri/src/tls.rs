@@ -0,0 +1,486 @@
+//! HTTPS for the MCP server.
+//!
+//! With `mcpTls` enabled (or a `tls` argument to `start_api_server`), the
+//! server speaks TLS on its usual port, which matters once it listens beyond
+//! loopback (see `bind.rs`): the bearer token and the canvas then cross the
+//! network. The certificate is the user's own PEM files when configured, or
+//! else a self-signed one generated on first use and kept in the config
+//! directory. It is generated again when it does not name the address the
+//! server listens on; delete `tls/` there to get a fresh one. A copy of the served
+//! certificate is published next to the port file so the stdio proxy can pin
+//! it instead of trusting a CA.
+
+use axum::extract::connect_info::Connected;
+use axum::serve::{IncomingStream, Listener};
+use axum::Router;
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Manager;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{
+    ring as provider, verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms,
+};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{
+    self, CertificateError, ClientConfig, DigitallySignedStruct, ServerConfig, SignatureScheme,
+};
+use tokio_rustls::{client, server, TlsAcceptor, TlsConnector};
+
+use crate::bind;
+
+const SELF_SIGNED_DIR: &str = "tls";
+const SELF_SIGNED_CERT: &str = "cert.pem";
+const SELF_SIGNED_KEY: &str = "key.pem";
+/// Apple rejects server certificates valid for longer.
+const SELF_SIGNED_DAYS: i64 = 825;
+/// A client that has not finished its handshake by then is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TlsConfig {
+    /// Serve HTTPS instead of HTTP.
+    pub enabled: bool,
+    /// PEM certificate chain; a self-signed certificate is used when empty.
+    pub cert_path: String,
+    /// PEM private key for `cert_path`.
+    pub key_path: String,
+}
+
+/// Check the `mcpTls` setting.
+pub fn validate(config: &TlsConfig) -> Result<(), String> {
+    if config.cert_path.trim().is_empty() != config.key_path.trim().is_empty() {
+        return Err("TLS needs both a certificate and a key file, or neither".to_string());
+    }
+    Ok(())
+}
+
+/// The certificate and key files a server serves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// The files to serve for `config`, if TLS is on: the configured ones, or a
+/// self-signed pair for `ip` in the app's config directory.
+pub fn files(
+    app: &tauri::AppHandle,
+    config: &TlsConfig,
+    ip: IpAddr,
+) -> Result<Option<TlsFiles>, String> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    validate(config)?;
+    if !config.cert_path.trim().is_empty() {
+        return Ok(Some(TlsFiles {
+            cert: PathBuf::from(config.cert_path.trim()),
+            key: PathBuf::from(config.key_path.trim()),
+        }));
+    }
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("No config directory for the TLS certificate: {}", e))?;
+    self_signed_files(&dir.join(SELF_SIGNED_DIR), ip).map(Some)
+}
+
+/// The addresses a certificate for a server bound to `ip` names: loopback,
+/// and `ip` or, bound to every interface, this machine's network address.
+fn certificate_ips(ip: IpAddr) -> Vec<IpAddr> {
+    let mut ips = vec![
+        IpAddr::from([127, 0, 0, 1]),
+        IpAddr::V6(Ipv6Addr::LOCALHOST),
+    ];
+    let reachable = match ip.is_unspecified() {
+        true => bind::network_ip(ip),
+        false => Some(ip),
+    };
+    if let Some(ip) = reachable.filter(|ip| !ips.contains(ip)) {
+        ips.push(ip);
+    }
+    ips
+}
+
+/// The self-signed pair in `dir` for a server bound to `ip`, generated if
+/// missing or if the saved certificate does not name the server's address.
+fn self_signed_files(dir: &Path, ip: IpAddr) -> Result<TlsFiles, String> {
+    let files = TlsFiles {
+        cert: dir.join(SELF_SIGNED_CERT),
+        key: dir.join(SELF_SIGNED_KEY),
+    };
+    let ips = certificate_ips(ip);
+    if files.key.exists() {
+        match std::fs::read(&files.cert) {
+            Ok(cert) if covers(&cert, &ips) => return Ok(files),
+            Ok(_) => log::info!(
+                "The self-signed TLS certificate does not name {}; replacing it",
+                ip
+            ),
+            Err(_) => {}
+        }
+    }
+    let (cert, key) = self_signed(&["localhost"], &ips, chrono::Utc::now())?;
+    std::fs::create_dir_all(dir)
+        .and_then(|_| write_private(&files.key, key.as_bytes()))
+        .and_then(|_| std::fs::write(&files.cert, cert))
+        .map_err(|e| format!("Failed to save the TLS certificate: {}", e))?;
+    log::info!(
+        "Generated a self-signed TLS certificate in {}",
+        dir.display()
+    );
+    Ok(files)
+}
+
+/// Write a file only its owner can read, narrowing an existing file's
+/// permissions too.
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(contents)
+}
+
+// --- Self-signed certificates ---
+
+/// A self-signed ECDSA P-256 certificate for `names` and `ips`, valid from a
+/// day before `now`, and its private key; both PEM.
+fn self_signed(
+    names: &[&str],
+    ips: &[IpAddr],
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(String, String), String> {
+    let failed = |e: rcgen::Error| format!("Failed to generate a TLS certificate: {}", e);
+    let date = |t: chrono::DateTime<chrono::Utc>| {
+        rcgen::date_time_ymd(t.year(), t.month() as u8, t.day() as u8)
+    };
+    let alt_names: Vec<String> = names
+        .iter()
+        .map(|n| n.to_string())
+        .chain(ips.iter().map(IpAddr::to_string))
+        .collect();
+    let mut params = rcgen::CertificateParams::new(alt_names).map_err(failed)?;
+    params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, "Napkin MCP server");
+    params.not_before = date(now - chrono::Duration::days(1));
+    params.not_after = date(now + chrono::Duration::days(SELF_SIGNED_DAYS));
+    params.extended_key_usages = vec![rcgen::ExtendedKeyUsagePurpose::ServerAuth];
+    let key = rcgen::KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256).map_err(failed)?;
+    let cert = params.self_signed(&key).map_err(failed)?;
+    Ok((cert.pem(), key.serialize_pem()))
+}
+
+/// Whether the PEM certificate `cert` is valid for `localhost` and every
+/// address in `ips`.
+fn covers(cert: &[u8], ips: &[IpAddr]) -> bool {
+    let Ok(der) = CertificateDer::from_pem_slice(cert) else {
+        return false;
+    };
+    let Ok(cert) = webpki::EndEntityCert::try_from(&der) else {
+        return false;
+    };
+    let localhost = ServerName::try_from("localhost").expect("a valid DNS name");
+    std::iter::once(localhost)
+        .chain(ips.iter().map(|ip| ServerName::IpAddress((*ip).into())))
+        .all(|name| cert.verify_is_valid_for_subject_name(&name).is_ok())
+}
+
+// --- Serving ---
+
+/// A rustls server configuration for the PEM files in `files`.
+pub fn server_config(files: &TlsFiles) -> Result<Arc<ServerConfig>, String> {
+    let read = |path: &Path| {
+        std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))
+    };
+    let certs = CertificateDer::pem_slice_iter(&read(&files.cert)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Bad certificate in {}: {}", files.cert.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificate in {}", files.cert.display()));
+    }
+    let key = PrivateKeyDer::from_pem_slice(&read(&files.key)?)
+        .map_err(|e| format!("Bad private key in {}: {}", files.key.display(), e))?;
+    let mut config = ServerConfig::builder_with_provider(Arc::new(provider::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("TLS certificate and key do not work together: {}", e))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Accepts TCP connections and completes their TLS handshakes in the
+/// background, so a slow client cannot hold up the others.
+pub struct TlsListener {
+    incoming: mpsc::Receiver<(server::TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl TlsListener {
+    pub fn new(mut listener: TcpListener, config: Arc<ServerConfig>) -> std::io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (tx, incoming) = mpsc::channel(64);
+        let task = tokio::spawn(async move {
+            loop {
+                let (tcp, addr) = Listener::accept(&mut listener).await;
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(tcp)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send((stream, addr)).await;
+                        }
+                        Ok(Err(e)) => log::debug!("TLS handshake with {} failed: {}", addr, e),
+                        Err(_) => log::debug!("TLS handshake with {} timed out", addr),
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            incoming,
+            local_addr,
+            task,
+        })
+    }
+}
+
+impl Drop for TlsListener {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = server::TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(connection) => connection,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// The address a request came from, as `ConnectInfo<PeerAddr>`.
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAddr(pub SocketAddr);
+
+impl Connected<IncomingStream<'_, TcpListener>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        Self(*stream.remote_addr())
+    }
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        Self(*stream.remote_addr())
+    }
+}
+
+/// Serve `router` on `listener`, over TLS with `config` if given, until
+/// `signal` completes.
+pub async fn serve(
+    listener: TcpListener,
+    config: Option<Arc<ServerConfig>>,
+    router: Router,
+    signal: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    match config {
+        Some(config) => {
+            let router = router.into_make_service_with_connect_info::<PeerAddr>();
+            axum::serve(TlsListener::new(listener, config)?, router)
+                .with_graceful_shutdown(signal)
+                .await
+        }
+        None => {
+            let router = router.into_make_service_with_connect_info::<PeerAddr>();
+            axum::serve(listener, router)
+                .with_graceful_shutdown(signal)
+                .await
+        }
+    }
+}
+
+// --- Connecting (stdio proxy) ---
+
+/// Accepts exactly one certificate, whoever issued it.
+#[derive(Debug)]
+struct PinnedCertificate {
+    cert: CertificateDer<'static>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if end_entity.as_ref() == self.cert.as_ref() {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::UnknownIssuer,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+/// Open TLS on `tcp`, trusting only the first certificate in `cert_pem`.
+pub async fn connect_pinned(
+    tcp: TcpStream,
+    cert_pem: &[u8],
+) -> Result<client::TlsStream<TcpStream>, String> {
+    let cert = CertificateDer::from_pem_slice(cert_pem)
+        .map_err(|e| format!("Bad MCP server certificate: {}", e))?;
+    let provider = provider::default_provider();
+    let verifier = PinnedCertificate {
+        cert,
+        algorithms: provider.signature_verification_algorithms,
+    };
+    let config = ClientConfig::builder_with_provider(Arc::new(provider))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+        .connect(ServerName::from(IpAddr::from([127, 0, 0, 1])), tcp)
+        .await
+        .map_err(|e| format!("TLS handshake with Napkin failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn cert_and_key_are_set_together() {
+        let mut config = TlsConfig {
+            enabled: true,
+            cert_path: "/etc/napkin/cert.pem".to_string(),
+            key_path: String::new(),
+        };
+        assert!(validate(&config).is_err());
+        config.key_path = "/etc/napkin/key.pem".to_string();
+        assert!(validate(&config).is_ok());
+        assert!(validate(&TlsConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn certificate_is_replaced_when_it_misses_the_bind_address() {
+        let dir = std::env::temp_dir().join(format!("napkin-tls-{}", uuid::Uuid::new_v4()));
+        let loopback = IpAddr::from([127, 0, 0, 1]);
+        let lan = IpAddr::from([192, 168, 1, 20]);
+        let files = self_signed_files(&dir, loopback).unwrap();
+        let first = std::fs::read(&files.cert).unwrap();
+        assert!(covers(&first, &[loopback, IpAddr::V6(Ipv6Addr::LOCALHOST)]));
+        assert!(!covers(&first, &[lan]));
+
+        self_signed_files(&dir, lan).unwrap();
+        let second = std::fs::read(&files.cert).unwrap();
+        assert_ne!(second, first);
+        assert!(covers(&second, &[loopback, lan]));
+        // Back on loopback the wider certificate still does.
+        self_signed_files(&dir, loopback).unwrap();
+        assert_eq!(std::fs::read(&files.cert).unwrap(), second);
+        assert!(!covers(b"not a certificate", &[]));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn serves_https_that_the_stdio_proxy_can_pin() {
+        let dir = std::env::temp_dir().join(format!("napkin-tls-{}", uuid::Uuid::new_v4()));
+        let files = self_signed_files(&dir, IpAddr::from([0, 0, 0, 0])).unwrap();
+        // A second start reuses the pair.
+        let cert_pem = std::fs::read(&files.cert).unwrap();
+        assert_eq!(
+            self_signed_files(&dir, IpAddr::from([0, 0, 0, 0])).unwrap(),
+            files
+        );
+        assert_eq!(std::fs::read(&files.cert).unwrap(), cert_pem);
+        let config = server_config(&files).unwrap();
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let router = Router::new().route("/mcp", get(|| async { "ok" }));
+        tokio::spawn(serve(
+            listener,
+            Some(config),
+            router,
+            std::future::pending(),
+        ));
+
+        let tcp = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let mut stream = connect_pinned(tcp, &cert_pem).await.unwrap();
+        stream
+            .write_all(b"GET /mcp HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.ends_with("ok"));
+
+        // Any other certificate is refused.
+        let (other, _) = self_signed(&["localhost"], &[], chrono::Utc::now()).unwrap();
+        let tcp = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        assert!(connect_pinned(tcp, other.as_bytes()).await.is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,152 @@
+//! Machine-readable codes for failed tool calls.
+//!
+//! A failed `tools/call` is an `isError` result whose text is the message and
+//! whose `structuredContent` is `{ "error": message, "code": CODE }`, so
+//! agents can branch on the kind of failure instead of parsing English. The
+//! webview tags its `{ error }` results with a `code` where it knows one;
+//! failures raised on this side are classified from their message.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// A shape the call names does not exist.
+    ShapeNotFound,
+    /// The arguments are missing, malformed, or name another missing object.
+    InvalidArgument,
+    /// The window or the user did not answer in time.
+    Timeout,
+    /// The request was dropped before it finished.
+    Cancelled,
+    /// Refused: the canvas is locked by someone else; retry later.
+    CanvasLocked,
+    /// Refused: the user declined, or the call is not allowed.
+    PermissionDenied,
+    /// Napkin itself failed.
+    Internal,
+}
+
+impl ErrorCode {
+    /// The code for an error `message` that did not come with one.
+    pub fn classify(message: &str) -> Self {
+        let message = message.to_lowercase();
+        if message.contains("shape") && message.contains("not found") {
+            Self::ShapeNotFound
+        } else if message.contains("timed out") || message.contains("in time") {
+            Self::Timeout
+        } else if message.contains("channel closed") {
+            Self::Cancelled
+        } else if message.contains("locked") {
+            Self::CanvasLocked
+        } else if ["declined", "refused", "not allowed"]
+            .iter()
+            .any(|word| message.contains(word))
+        {
+            Self::PermissionDenied
+        } else if message.starts_with("failed to") || message.starts_with("internal error") {
+            Self::Internal
+        } else {
+            Self::InvalidArgument
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ToolError {
+    pub fn new(message: impl Into<String>) -> Self {
+        let message = message.into();
+        Self {
+            code: ErrorCode::classify(&message),
+            message,
+        }
+    }
+
+    /// The error a bridged tool reported as its `{ error, code? }` result.
+    fn reported(result: &Value) -> Option<Self> {
+        let message = result.get("error").and_then(Value::as_str)?;
+        let code = result
+            .get("code")
+            .and_then(|code| serde_json::from_value(code.clone()).ok())
+            .unwrap_or_else(|| ErrorCode::classify(message));
+        Some(Self {
+            code,
+            message: message.to_string(),
+        })
+    }
+
+    /// The `isError` result for `tools/call`.
+    pub fn call_result(&self) -> Value {
+        json!({
+            "isError": true,
+            "content": [{ "type": "text", "text": self.message }],
+            "structuredContent": { "error": self.message, "code": self.code },
+        })
+    }
+}
+
+/// A tool's outcome, with `{ error }` results from the webview counted as
+/// failures.
+pub fn check(result: Result<Value, String>) -> Result<Value, ToolError> {
+    let value = result.map_err(ToolError::new)?;
+    match ToolError::reported(&value) {
+        Some(error) => Err(error),
+        None => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_carry_a_code() {
+        let code = |result| check(result).unwrap_err().code;
+        assert_eq!(
+            code(Ok(json!({ "error": "Shape not found: s1" }))),
+            ErrorCode::ShapeNotFound
+        );
+        // The webview's own code wins
+        assert_eq!(
+            code(Ok(
+                json!({ "error": "Failed to load image: 404", "code": "INVALID_ARGUMENT" })
+            )),
+            ErrorCode::InvalidArgument
+        );
+        assert_eq!(
+            code(Err("Request timed out".to_string())),
+            ErrorCode::Timeout
+        );
+        assert_eq!(
+            code(Err("Internal error: bridge channel closed".to_string())),
+            ErrorCode::Cancelled
+        );
+        assert_eq!(
+            code(Err("Canvas locked by user (retry after 3s)".to_string())),
+            ErrorCode::CanvasLocked
+        );
+        assert_eq!(
+            code(Err("Missing required field: type".to_string())),
+            ErrorCode::InvalidArgument
+        );
+        assert_eq!(
+            code(Err("Failed to emit event: closed".to_string())),
+            ErrorCode::Internal
+        );
+        assert_eq!(check(Ok(json!({ "id": "s1" }))), Ok(json!({ "id": "s1" })));
+
+        let result = ToolError::new("The user declined delete_shape").call_result();
+        assert_eq!(result["isError"], true);
+        assert_eq!(result["structuredContent"]["code"], "PERMISSION_DENIED");
+        assert_eq!(
+            result["content"][0]["text"],
+            "The user declined delete_shape"
+        );
+    }
+}
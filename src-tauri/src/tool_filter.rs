@@ -0,0 +1,169 @@
+//! Tools switched off for every agent until Napkin quits.
+//!
+//! Profiles (`profiles.rs`) are saved per client; this is a quick, global
+//! override kept in `ApiState`. `set_enabled_tools` turns everything but
+//! the listed tools off (an allow list), `set_disabled_tools` turns the
+//! listed tools off (a deny list), and a tool must pass both. Disabled tools
+//! are left out of `tools/list` and refused by `tools/call`. Every change is
+//! announced to connected clients with `notifications/tools/list_changed`
+//! so they fetch the list again.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+use crate::api::{self, SharedApiState};
+
+#[derive(Debug, Default)]
+pub struct ToolFilter {
+    /// When set, only these tools are enabled.
+    allow: Option<BTreeSet<String>>,
+    deny: BTreeSet<String>,
+}
+
+impl ToolFilter {
+    pub fn allows(&self, tool: &str) -> bool {
+        self.allow
+            .as_ref()
+            .map_or(true, |allow| allow.contains(tool))
+            && !self.deny.contains(tool)
+    }
+
+    /// `tools` (a `tools/list` array) without the disabled tools.
+    pub fn filter_tools(&self, mut tools: Value) -> Value {
+        if let Some(list) = tools.as_array_mut() {
+            list.retain(|tool| {
+                tool.get("name")
+                    .and_then(Value::as_str)
+                    .is_some_and(|name| self.allows(name))
+            });
+        }
+        tools
+    }
+}
+
+/// `tools` as a set, refusing names that are not tools.
+fn known(tools: Vec<String>, is_tool: impl Fn(&str) -> bool) -> Result<BTreeSet<String>, String> {
+    let unknown: Vec<&str> = tools
+        .iter()
+        .map(String::as_str)
+        .filter(|t| !is_tool(t))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!("Unknown tools: {}", unknown.join(", ")));
+    }
+    Ok(tools.into_iter().collect())
+}
+
+/// The message telling clients to fetch `tools/list` again.
+pub fn list_changed() -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/tools/list_changed",
+    })
+}
+
+async fn update(state: &SharedApiState, change: impl FnOnce(&mut ToolFilter)) -> Vec<ToolState> {
+    change(&mut *state.tool_filter.lock().await);
+    let _ = state.canvas_events.send(list_changed());
+    tool_states(state).await
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolState {
+    pub name: String,
+    pub enabled: bool,
+}
+
+async fn tool_states(state: &SharedApiState) -> Vec<ToolState> {
+    let filter = state.tool_filter.lock().await;
+    api::all_tools()
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|tool| tool.get("name").and_then(Value::as_str))
+        .map(|name| ToolState {
+            name: name.to_string(),
+            enabled: filter.allows(name),
+        })
+        .collect()
+}
+
+// --- Tauri commands ---
+
+/// Every tool and whether agents may use it.
+#[tauri::command]
+pub async fn get_enabled_tools(
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<Vec<ToolState>, String> {
+    Ok(tool_states(state.inner()).await)
+}
+
+/// Enable only `tools`.
+#[tauri::command]
+pub async fn set_enabled_tools(
+    tools: Vec<String>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<Vec<ToolState>, String> {
+    let allow = known(tools, api::is_known_tool)?;
+    Ok(update(state.inner(), |filter| filter.allow = Some(allow)).await)
+}
+
+/// Disable `tools`; an empty list disables none.
+#[tauri::command]
+pub async fn set_disabled_tools(
+    tools: Vec<String>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<Vec<ToolState>, String> {
+    let deny = known(tools, api::is_known_tool)?;
+    Ok(update(state.inner(), |filter| filter.deny = deny).await)
+}
+
+/// Enable every tool again.
+#[tauri::command]
+pub async fn reset_enabled_tools(
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<Vec<ToolState>, String> {
+    Ok(update(state.inner(), |filter| *filter = ToolFilter::default()).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn tools_must_pass_allow_and_deny_lists() {
+        let mut filter = ToolFilter::default();
+        assert!(filter.allows("clear_canvas"));
+
+        filter.deny = set(&["clear_canvas"]);
+        assert!(!filter.allows("clear_canvas"));
+        assert!(filter.allows("create_shape"));
+
+        filter.allow = Some(set(&["get_canvas", "clear_canvas"]));
+        assert!(filter.allows("get_canvas"));
+        assert!(!filter.allows("create_shape"));
+        assert!(!filter.allows("clear_canvas"));
+
+        let tools = json!([{ "name": "get_canvas" }, { "name": "create_shape" }]);
+        assert_eq!(
+            filter.filter_tools(tools),
+            json!([{ "name": "get_canvas" }])
+        );
+    }
+
+    #[test]
+    fn unknown_names_are_refused() {
+        let is_tool = |t: &str| t == "get_canvas";
+        assert_eq!(
+            known(vec!["get_canvas".to_string()], is_tool).unwrap(),
+            set(&["get_canvas"])
+        );
+        let err = known(vec!["get_canvas".to_string(), "nuke".to_string()], is_tool).unwrap_err();
+        assert_eq!(err, "Unknown tools: nuke");
+    }
+}
@@ -0,0 +1,433 @@
+//! Bitmap-to-vector tracing for image shapes.
+//!
+//! Turns a photo of a whiteboard (or any line drawing) into editable freedraw
+//! strokes: the image is thresholded (Otsu), thinned to one-pixel centerlines
+//! (Zhang-Suen), the skeleton is walked into polylines, and each polyline is
+//! simplified (Ramer-Douglas-Peucker) before being mapped back onto the
+//! image's position on the canvas.
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::photo;
+use image::GrayImage;
+use serde_json::Value;
+
+/// Longest side the bitmap is scaled to before tracing.
+const MAX_TRACE_DIM: u32 = 512;
+/// RDP tolerance in trace pixels.
+const SIMPLIFY_EPSILON: f64 = 1.25;
+/// Strokes shorter than this (in trace pixels) are treated as noise.
+const MIN_STROKE_LENGTH: f64 = 6.0;
+/// Cap on strokes per trace so a noisy photo can't flood the canvas.
+const MAX_STROKES: usize = 400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceMode {
+    /// Delete the image and keep only the strokes.
+    Replace,
+    /// Keep the image underneath the strokes.
+    Overlay,
+}
+
+impl TraceMode {
+    fn parse(s: Option<&str>) -> Result<TraceMode, String> {
+        match s.unwrap_or("overlay") {
+            "replace" => Ok(TraceMode::Replace),
+            "overlay" => Ok(TraceMode::Overlay),
+            other => Err(format!(
+                "Unknown trace mode: {} (expected replace or overlay)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    /// Polylines in trace-bitmap pixel coordinates.
+    pub strokes: Vec<Vec<(f64, f64)>>,
+    /// Average ink thickness in trace pixels.
+    pub stroke_width: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+// --- Decoding ---
+
+/// Decode a `data:` URL into a grayscale bitmap no larger than `MAX_TRACE_DIM`.
+pub fn decode_data_url(src: &str) -> Result<GrayImage, String> {
+    let img = photo::decode_data_url(src)?;
+    let img = if img.width().max(img.height()) > MAX_TRACE_DIM {
+        img.thumbnail(MAX_TRACE_DIM, MAX_TRACE_DIM)
+    } else {
+        img
+    };
+    Ok(img.to_luma8())
+}
+
+// --- Tracing ---
+
+pub fn trace(gray: &GrayImage) -> Trace {
+    let (w, h) = gray.dimensions();
+    let mut ink = binarize(gray);
+    let ink_pixels = ink.iter().filter(|&&b| b).count();
+    thin(&mut ink, w as usize, h as usize);
+    let skeleton_pixels = ink.iter().filter(|&&b| b).count();
+
+    let mut strokes: Vec<Vec<(f64, f64)>> = walk_skeleton(&mut ink, w as usize, h as usize)
+        .into_iter()
+        .map(|path| simplify(&path, SIMPLIFY_EPSILON))
+        .filter(|path| path_length(path) >= MIN_STROKE_LENGTH)
+        .collect();
+    // Keep the most significant strokes when there are too many.
+    if strokes.len() > MAX_STROKES {
+        strokes.sort_by(|a, b| path_length(b).total_cmp(&path_length(a)));
+        strokes.truncate(MAX_STROKES);
+    }
+
+    let stroke_width = if skeleton_pixels > 0 {
+        ink_pixels as f64 / skeleton_pixels as f64
+    } else {
+        1.0
+    };
+    Trace {
+        strokes,
+        stroke_width,
+        width: w,
+        height: h,
+    }
+}
+
+/// Otsu threshold; ink is whichever side is the minority so both whiteboards
+/// (dark on light) and blackboards (light on dark) trace.
+fn binarize(gray: &GrayImage) -> Vec<bool> {
+    let threshold = photo::otsu_threshold(gray);
+    let total = gray.pixels().len() as f64;
+    let dark: Vec<bool> = gray.pixels().map(|p| p.0[0] <= threshold).collect();
+    let dark_count = dark.iter().filter(|&&d| d).count();
+    if dark_count as f64 > total / 2.0 {
+        dark.into_iter().map(|d| !d).collect()
+    } else {
+        dark
+    }
+}
+
+const NEIGHBORS: [(isize, isize); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+fn at(img: &[bool], w: usize, h: usize, x: isize, y: isize) -> bool {
+    x >= 0 && y >= 0 && (x as usize) < w && (y as usize) < h && img[y as usize * w + x as usize]
+}
+
+/// Zhang-Suen thinning, in place.
+fn thin(img: &mut [bool], w: usize, h: usize) {
+    loop {
+        let mut changed = false;
+        for step in 0..2 {
+            let mut remove = Vec::new();
+            for y in 0..h {
+                for x in 0..w {
+                    if !img[y * w + x] {
+                        continue;
+                    }
+                    // P2..P9 clockwise from north.
+                    let p: Vec<bool> = NEIGHBORS
+                        .iter()
+                        .map(|(dx, dy)| at(img, w, h, x as isize + dx, y as isize + dy))
+                        .collect();
+                    let count = p.iter().filter(|&&b| b).count();
+                    if !(2..=6).contains(&count) {
+                        continue;
+                    }
+                    let transitions = (0..8).filter(|&i| !p[i] && p[(i + 1) % 8]).count();
+                    if transitions != 1 {
+                        continue;
+                    }
+                    let (n, e, s, west) = (p[0], p[2], p[4], p[6]);
+                    // Step 1: P2*P4*P6 == 0 && P4*P6*P8 == 0; step 2 mirrors it.
+                    let ok = if step == 0 {
+                        !(e && s && (n || west))
+                    } else {
+                        !(n && west && (e || s))
+                    };
+                    if ok {
+                        remove.push(y * w + x);
+                    }
+                }
+            }
+            changed |= !remove.is_empty();
+            for i in remove {
+                img[i] = false;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+fn degree(img: &[bool], w: usize, h: usize, x: usize, y: usize) -> usize {
+    NEIGHBORS
+        .iter()
+        .filter(|(dx, dy)| at(img, w, h, x as isize + dx, y as isize + dy))
+        .count()
+}
+
+/// Walk skeleton pixels into polylines, consuming them. Open strokes are
+/// started from their endpoints so they come out in one piece.
+fn walk_skeleton(img: &mut [bool], w: usize, h: usize) -> Vec<Vec<(f64, f64)>> {
+    let mut paths = Vec::new();
+    for pass in 0..2 {
+        for y in 0..h {
+            for x in 0..w {
+                if !img[y * w + x] {
+                    continue;
+                }
+                // First pass: endpoints only. Second pass: whatever is left (loops).
+                if pass == 0 && degree(img, w, h, x, y) != 1 {
+                    continue;
+                }
+                paths.push(follow(img, w, h, x, y));
+            }
+        }
+    }
+    paths
+}
+
+fn follow(img: &mut [bool], w: usize, h: usize, x: usize, y: usize) -> Vec<(f64, f64)> {
+    let (mut x, mut y) = (x as isize, y as isize);
+    let mut path = vec![(x as f64, y as f64)];
+    img[y as usize * w + x as usize] = false;
+    loop {
+        // Prefer orthogonal steps so diagonals don't skip past corners.
+        let next = [0usize, 2, 4, 6, 1, 3, 5, 7]
+            .iter()
+            .map(|&i| NEIGHBORS[i])
+            .find(|(dx, dy)| at(img, w, h, x + dx, y + dy));
+        let Some((dx, dy)) = next else {
+            break;
+        };
+        x += dx;
+        y += dy;
+        img[y as usize * w + x as usize] = false;
+        path.push((x as f64, y as f64));
+    }
+    path
+}
+
+fn path_length(path: &[(f64, f64)]) -> f64 {
+    path.windows(2)
+        .map(|p| ((p[1].0 - p[0].0).powi(2) + (p[1].1 - p[0].1).powi(2)).sqrt())
+        .sum()
+}
+
+/// Ramer-Douglas-Peucker simplification.
+pub fn simplify(points: &[(f64, f64)], epsilon: f64) -> Vec<(f64, f64)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let (dx, dy) = (last.0 - first.0, last.1 - first.1);
+    let len = (dx * dx + dy * dy).sqrt();
+    let distance = |p: &(f64, f64)| {
+        if len == 0.0 {
+            ((p.0 - first.0).powi(2) + (p.1 - first.1).powi(2)).sqrt()
+        } else {
+            (dy * p.0 - dx * p.1 + last.0 * first.1 - last.1 * first.0).abs() / len
+        }
+    };
+
+    let (index, max) = points[1..points.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i + 1, distance(p)))
+        .fold(
+            (0, 0.0),
+            |best, cur| if cur.1 > best.1 { cur } else { best },
+        );
+
+    if max > epsilon {
+        let mut left = simplify(&points[..=index], epsilon);
+        let right = simplify(&points[index..], epsilon);
+        left.pop();
+        left.extend(right);
+        left
+    } else {
+        vec![first, last]
+    }
+}
+
+// --- MCP tool ---
+
+pub fn trace_tools() -> Vec<Value> {
+    vec![serde_json::json!({
+        "name": "trace_image",
+        "description": "Convert an image shape (e.g. a whiteboard photo) into editable hand-drawn freedraw strokes. Only embedded (data URL) images can be traced.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "shapeId": { "type": "string", "description": "ID of the image shape" },
+                "mode": { "type": "string", "enum": ["overlay", "replace"], "description": "overlay keeps the image under the strokes (default); replace deletes it" },
+                "strokeColor": { "type": "string", "description": "Stroke color (default: #000000)" }
+            },
+            "required": ["shapeId"]
+        }
+    })]
+}
+
+pub async fn call_trace_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "trace_image" => Some(trace_image(state, arguments).await),
+        _ => None,
+    }
+}
+
+async fn trace_image(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let shape_id = arguments
+        .get("shapeId")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing required field: shapeId")?;
+    let mode = TraceMode::parse(arguments.get("mode").and_then(|v| v.as_str()))?;
+    let color = arguments
+        .get("strokeColor")
+        .and_then(|v| v.as_str())
+        .unwrap_or("#000000");
+
+    let shape = bridge_tool_call(state, "get_shape", serde_json::json!({ "id": shape_id })).await?;
+    if let Some(err) = shape.get("error").and_then(|e| e.as_str()) {
+        return Err(err.to_string());
+    }
+    if shape.get("type").and_then(|t| t.as_str()) != Some("image") {
+        return Err(format!("Shape {} is not an image", shape_id));
+    }
+    let src = shape.get("src").and_then(|s| s.as_str()).unwrap_or("");
+
+    // Decoding and thinning are CPU-bound; keep them off the async runtime.
+    let src = src.to_string();
+    let traced = tokio::task::spawn_blocking(move || decode_data_url(&src).map(|g| trace(&g)))
+        .await
+        .map_err(|e| format!("Trace task failed: {}", e))??;
+
+    let num = |k: &str| shape.get(k).and_then(|v| v.as_f64()).unwrap_or(0.0);
+    let (x, y) = (num("x"), num("y"));
+    let sx = num("width") / traced.width.max(1) as f64;
+    let sy = num("height") / traced.height.max(1) as f64;
+    let stroke_width = (traced.stroke_width * (sx + sy) / 2.0).clamp(1.0, 8.0);
+
+    let mut operations: Vec<Value> = traced
+        .strokes
+        .iter()
+        .map(|stroke| {
+            let points: Vec<Value> = stroke
+                .iter()
+                .map(|(px, py)| serde_json::json!({ "x": x + px * sx, "y": y + py * sy }))
+                .collect();
+            serde_json::json!({
+                "action": "create",
+                "data": {
+                    "type": "freedraw",
+                    "x": x,
+                    "y": y,
+                    "points": points,
+                    "strokeColor": color,
+                    "strokeWidth": stroke_width,
+                    "roughness": 1,
+                }
+            })
+        })
+        .collect();
+    if mode == TraceMode::Replace {
+        operations.push(serde_json::json!({ "action": "delete", "data": { "id": shape_id } }));
+    }
+
+    let result = bridge_tool_call(
+        state,
+        "batch_operations",
+        serde_json::json!({ "operations": operations }),
+    )
+    .await?;
+    if let Some(err) = result.get("error").and_then(|e| e.as_str()) {
+        return Err(err.to_string());
+    }
+
+    Ok(serde_json::json!({
+        "strokes": traced.strokes.len(),
+        "strokeWidth": stroke_width,
+        "replaced": mode == TraceMode::Replace,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    fn canvas(w: u32, h: u32) -> GrayImage {
+        GrayImage::from_pixel(w, h, Luma([255]))
+    }
+
+    #[test]
+    fn thick_line_traces_to_single_stroke() {
+        let mut img = canvas(60, 20);
+        for x in 5..55 {
+            for y in 8..12 {
+                img.put_pixel(x, y, Luma([0]));
+            }
+        }
+        let traced = trace(&img);
+        assert_eq!(traced.strokes.len(), 1);
+        // A straight line simplifies down to its endpoints.
+        assert_eq!(traced.strokes[0].len(), 2);
+        assert!(path_length(&traced.strokes[0]) > 40.0);
+        assert!(traced.stroke_width > 2.0);
+    }
+
+    #[test]
+    fn light_on_dark_is_inverted() {
+        let mut img = GrayImage::from_pixel(40, 40, Luma([20]));
+        for y in 5..35 {
+            for x in 19..21 {
+                img.put_pixel(x, y, Luma([240]));
+            }
+        }
+        let traced = trace(&img);
+        assert_eq!(traced.strokes.len(), 1);
+    }
+
+    #[test]
+    fn specks_are_dropped() {
+        let mut img = canvas(30, 30);
+        img.put_pixel(10, 10, Luma([0]));
+        img.put_pixel(20, 20, Luma([0]));
+        assert!(trace(&img).strokes.is_empty());
+    }
+
+    #[test]
+    fn rdp_keeps_corners() {
+        let path: Vec<(f64, f64)> = (0..=10)
+            .map(|i| (i as f64, 0.0))
+            .chain((1..=10).map(|i| (10.0, i as f64)))
+            .collect();
+        assert_eq!(
+            simplify(&path, 0.5),
+            vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]
+        );
+    }
+
+    #[test]
+    fn only_data_urls_are_accepted() {
+        assert!(decode_data_url("https://example.com/a.png").is_err());
+        assert!(decode_data_url("data:image/png,notbase64").is_err());
+    }
+}
@@ -0,0 +1,389 @@
+//! Board translation (`translate_canvas`).
+//!
+//! Gathers the text of a board's shapes, has it translated, and applies the
+//! result as one `batch_operations` call, so a single undo brings the
+//! original back. Each translated shape keeps its original text under
+//! `translation`, and `revert: true` restores it later.
+//!
+//! Translations come from a command configured under `translation` in
+//! settings or, without one, from the calling agent's model through MCP
+//! sampling. The command is run like an export hook (no shell, a minimal
+//! environment, a timeout); it reads `{ "targetLang", "texts": { id: text } }`
+//! on stdin and prints the translated `{ id: text }` object.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::export_hooks;
+use crate::resources;
+use crate::sampling;
+use crate::session::McpClient;
+use crate::settings;
+
+/// Shape field holding the original text and the language translated to.
+const TRANSLATION_FIELD: &str = "translation";
+
+const SYSTEM_PROMPT: &str = "You translate the labels of a diagram. Reply with a JSON object only, without commentary or code fences.";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TranslationProvider {
+    /// Program and arguments; MCP sampling is used when empty.
+    pub command: Vec<String>,
+    pub timeout_secs: u64,
+}
+
+impl Default for TranslationProvider {
+    fn default() -> Self {
+        Self {
+            command: Vec::new(),
+            timeout_secs: 120,
+        }
+    }
+}
+
+/// The text of each shape with some, keyed by id; only `only` when given.
+fn texts(shapes: &[Value], only: Option<&[String]>) -> Map<String, Value> {
+    shapes
+        .iter()
+        .filter_map(|shape| {
+            let id = shape.get("id")?.as_str()?;
+            let text = shape.get("text")?.as_str()?;
+            if text.trim().is_empty() || only.is_some_and(|ids| !ids.iter().any(|i| i == id)) {
+                return None;
+            }
+            Some((id.to_string(), json!(text)))
+        })
+        .collect()
+}
+
+/// The prompt asking a model to translate `texts` into `target_lang`.
+fn prompt(texts: &Map<String, Value>, target_lang: &str) -> String {
+    format!(
+        "Translate each value of this JSON object into {}. Keep the keys unchanged, keep line breaks, and leave code, product names and URLs as they are. Reply with the translated JSON object only.\n\n{}",
+        target_lang,
+        Value::Object(texts.clone())
+    )
+}
+
+/// Room for the reply: roughly a token per two characters of input.
+fn max_tokens(texts: &Map<String, Value>) -> u32 {
+    let chars: usize = texts
+        .values()
+        .filter_map(Value::as_str)
+        .map(|t| t.chars().count() + 16)
+        .sum();
+    (chars / 2 + 256).min(16_000) as u32
+}
+
+/// The `{ id: text }` object in a reply, which models sometimes wrap in a
+/// code fence or a sentence.
+fn parse_translations(reply: &str) -> Result<Map<String, Value>, String> {
+    let start = reply.find('{');
+    let end = reply.rfind('}');
+    let json = match (start, end) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => return Err("The translation reply holds no JSON object".to_string()),
+    };
+    match serde_json::from_str(json) {
+        Ok(Value::Object(map)) => Ok(map),
+        _ => Err("The translation reply is not a JSON object".to_string()),
+    }
+}
+
+/// `batch_operations` updates setting each translated text, keeping the
+/// shape's first original.
+fn translate_operations(
+    shapes: &[Value],
+    translations: &Map<String, Value>,
+    target_lang: &str,
+) -> Vec<Value> {
+    shapes
+        .iter()
+        .filter_map(|shape| {
+            let id = shape.get("id")?.as_str()?;
+            let translated = translations.get(id)?.as_str()?;
+            let original = shape
+                .pointer("/translation/original")
+                .or_else(|| shape.get("text"))?
+                .clone();
+            Some(json!({
+                "action": "update",
+                "data": {
+                    "id": id,
+                    "text": translated,
+                    TRANSLATION_FIELD: { "original": original, "lang": target_lang },
+                },
+            }))
+        })
+        .collect()
+}
+
+/// `batch_operations` updates restoring the original text of translated
+/// shapes (those in `only`, when given).
+fn revert_operations(shapes: &[Value], only: Option<&[String]>) -> Vec<Value> {
+    shapes
+        .iter()
+        .filter_map(|shape| {
+            let id = shape.get("id")?.as_str()?;
+            let original = shape.pointer("/translation/original")?.as_str()?;
+            if only.is_some_and(|ids| !ids.iter().any(|i| i == id)) {
+                return None;
+            }
+            Some(json!({
+                "action": "update",
+                "data": { "id": id, "text": original, TRANSLATION_FIELD: null },
+            }))
+        })
+        .collect()
+}
+
+async fn run_provider(
+    provider: &TranslationProvider,
+    texts: &Map<String, Value>,
+    target_lang: &str,
+) -> Result<Map<String, Value>, String> {
+    let Some((program, args)) = provider.command.split_first() else {
+        return Err("No translation command is configured".to_string());
+    };
+    let mut command = export_hooks::restricted_command(program, args);
+    command.stdin(Stdio::piped());
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", program, e))?;
+    let input = json!({ "targetLang": target_lang, "texts": texts }).to_string();
+    if let Some(mut stdin) = child.stdin.take() {
+        match stdin.write_all(input.as_bytes()).await {
+            // A command that exits without reading says why on stderr
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+            result => result.map_err(|e| format!("Failed to send text to {}: {}", program, e))?,
+        }
+    }
+    let timeout = Duration::from_secs(provider.timeout_secs.max(1));
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| format!("{} timed out after {}s", program, timeout.as_secs()))?
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            stderr.trim().lines().last().unwrap_or("no output")
+        ));
+    }
+    parse_translations(&String::from_utf8_lossy(&output.stdout))
+}
+
+pub fn translate_tools() -> Vec<Value> {
+    vec![json!({
+        "name": "translate_canvas",
+        "description": "Translate the text on the board (labels, text shapes, stickies) into another language, e.g. to present it to a different audience. Uses the translation command configured in Napkin, or otherwise asks your model through MCP sampling (the client must support sampling). Applied as one change that a single undo reverts; each shape keeps its original text under `translation.original`, and `revert: true` puts it back.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "targetLang": { "type": "string", "description": "Language to translate into, as a name or code (e.g. \"German\", \"ja\")" },
+                "shapeIds": { "type": "array", "items": { "type": "string" }, "description": "Shapes to translate (default: every shape with text)" },
+                "revert": { "type": "boolean", "description": "Restore the original text of translated shapes instead; targetLang is ignored" }
+            },
+            "required": ["targetLang"],
+            "additionalProperties": false
+        }
+    })]
+}
+
+pub async fn call_translate_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+    client: &McpClient,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "translate_canvas" => Some(translate_canvas(state, arguments, client).await),
+        _ => None,
+    }
+}
+
+async fn apply(state: &SharedApiState, operations: Vec<Value>) -> Result<usize, String> {
+    let count = operations.len();
+    if count == 0 {
+        return Ok(0);
+    }
+    let result = bridge_tool_call(
+        state,
+        "batch_operations",
+        json!({ "operations": operations }),
+    )
+    .await?;
+    match result.get("error").and_then(Value::as_str) {
+        Some(error) => Err(error.to_string()),
+        None => Ok(count),
+    }
+}
+
+async fn translate_canvas(
+    state: &SharedApiState,
+    arguments: &Value,
+    client: &McpClient,
+) -> Result<Value, String> {
+    let target_lang = arguments
+        .get("targetLang")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .unwrap_or("");
+    let only: Option<Vec<String>> = match arguments.get("shapeIds") {
+        None | Some(Value::Null) => None,
+        Some(ids) => Some(
+            serde_json::from_value(ids.clone())
+                .map_err(|_| "shapeIds must be an array of shape ids".to_string())?,
+        ),
+    };
+    let canvas = resources::canvas(state, None).await?;
+    let shapes = canvas
+        .get("shapes")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    if arguments.get("revert").and_then(Value::as_bool) == Some(true) {
+        let reverted = apply(state, revert_operations(&shapes, only.as_deref())).await?;
+        return Ok(json!({ "reverted": reverted }));
+    }
+    if target_lang.is_empty() {
+        return Err("targetLang is required".to_string());
+    }
+
+    let texts = texts(&shapes, only.as_deref());
+    if texts.is_empty() {
+        return Ok(json!({ "translated": 0, "targetLang": target_lang }));
+    }
+    let provider = settings::current(&state.app_handle).translation;
+    let (translations, via) = if provider.command.is_empty() {
+        if !client.can_sample() {
+            return Err("This client does not support MCP sampling, and no translation command is configured in Napkin's settings (translation.command)".to_string());
+        }
+        let reply = sampling::complete(
+            state,
+            client,
+            SYSTEM_PROMPT,
+            &prompt(&texts, target_lang),
+            max_tokens(&texts),
+        )
+        .await?;
+        (parse_translations(&reply)?, "sampling")
+    } else {
+        (
+            run_provider(&provider, &texts, target_lang).await?,
+            "command",
+        )
+    };
+
+    // Only shapes we asked about, in case the reply invents ids.
+    let translations: Map<String, Value> = translations
+        .into_iter()
+        .filter(|(id, text)| texts.contains_key(id) && text.is_string())
+        .collect();
+    let untranslated: Vec<&String> = texts
+        .keys()
+        .filter(|id| !translations.contains_key(*id))
+        .collect();
+    let translated = apply(
+        state,
+        translate_operations(&shapes, &translations, target_lang),
+    )
+    .await?;
+    Ok(json!({
+        "translated": translated,
+        "targetLang": target_lang,
+        "via": via,
+        "untranslated": untranslated,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board() -> Vec<Value> {
+        vec![
+            json!({ "id": "a", "type": "rectangle", "text": "Start" }),
+            json!({ "id": "b", "type": "sticky", "text": "Ship it\nFriday" }),
+            json!({ "id": "c", "type": "rectangle", "text": "  " }),
+            json!({ "id": "d", "type": "arrow" }),
+            json!({ "id": "e", "type": "text", "text": "Hallo", "translation": { "original": "Hello", "lang": "de" } }),
+        ]
+    }
+
+    #[test]
+    fn only_shapes_with_text_are_sent() {
+        let all = texts(&board(), None);
+        assert_eq!(all.keys().collect::<Vec<_>>(), vec!["a", "b", "e"]);
+        let some = texts(&board(), Some(&["b".to_string(), "d".to_string()]));
+        assert_eq!(Value::Object(some), json!({ "b": "Ship it\nFriday" }));
+        assert!(prompt(&all, "French").contains("into French"));
+    }
+
+    #[test]
+    fn replies_may_be_wrapped() {
+        let reply = "Here you go:\n```json\n{ \"a\": \"Début\" }\n```";
+        assert_eq!(
+            Value::Object(parse_translations(reply).unwrap()),
+            json!({ "a": "Début" })
+        );
+        assert!(parse_translations("Sorry, I can't").is_err());
+        assert!(parse_translations("} {").is_err());
+    }
+
+    #[test]
+    fn originals_are_kept_and_restored() {
+        let translations = parse_translations(r#"{ "a": "Début", "e": "Bonjour" }"#).unwrap();
+        let ops = translate_operations(&board(), &translations, "fr");
+        assert_eq!(
+            ops,
+            vec![
+                json!({ "action": "update", "data": { "id": "a", "text": "Début", "translation": { "original": "Start", "lang": "fr" } } }),
+                json!({ "action": "update", "data": { "id": "e", "text": "Bonjour", "translation": { "original": "Hello", "lang": "fr" } } }),
+            ]
+        );
+        assert_eq!(
+            revert_operations(&board(), None),
+            vec![
+                json!({ "action": "update", "data": { "id": "e", "text": "Hello", "translation": null } })
+            ]
+        );
+        assert!(revert_operations(&board(), Some(&["a".to_string()])).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn commands_read_texts_and_print_translations() {
+        let provider = TranslationProvider {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "cat >/dev/null; echo '{\"a\": \"Inicio\"}'".to_string(),
+            ],
+            ..Default::default()
+        };
+        let texts = texts(&board(), Some(&["a".to_string()]));
+        let translations = run_provider(&provider, &texts, "es").await.unwrap();
+        assert_eq!(Value::Object(translations), json!({ "a": "Inicio" }));
+
+        let failing = TranslationProvider {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo quota exceeded >&2; exit 1".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            run_provider(&failing, &texts, "es").await.unwrap_err(),
+            "sh failed: quota exceeded"
+        );
+    }
+}
@@ -0,0 +1,429 @@
+//! Outbound tunnel to a remote agent relay.
+//!
+//! Lets a cloud-hosted agent reach this machine without an inbound port: the
+//! app dials a user-configured relay over WebSocket, authenticates with the
+//! relay token, and answers the JSON-RPC messages the relay sends down the
+//! socket with the same handler `/mcp` uses. The connection is retried with
+//! backoff until it is stopped from Settings or the tray's kill switch.
+//! Status changes are emitted as `tunnel-status`.
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{Emitter, Manager};
+use tokio::sync::{mpsc, watch};
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest, http::HeaderValue};
+
+use crate::api::{self, SharedApiState};
+use crate::canvas_events;
+use crate::clients::{self, Transport};
+use crate::daily;
+use crate::session::McpClient;
+use crate::settings::{self, SettingsStore};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const STATUS_MENU_ID: &str = "tunnel-status";
+const KILL_MENU_ID: &str = "tunnel-kill";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TunnelConfig {
+    /// Reconnect on launch.
+    pub enabled: bool,
+    /// `wss://` (or `ws://` for a relay on localhost).
+    pub relay_url: String,
+    /// Sent to the relay as `Authorization: Bearer <token>`.
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelState {
+    #[default]
+    Disconnected,
+    Connecting,
+    Connected,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TunnelStatus {
+    pub state: TunnelState,
+    pub relay_url: Option<String>,
+    /// Why the last attempt failed, while retrying.
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Tunnel {
+    stop: Option<watch::Sender<bool>>,
+    status: TunnelStatus,
+}
+
+/// Reject relay URLs we would not want to send the token to.
+pub fn validate(config: &TunnelConfig) -> Result<(), String> {
+    let url = config.relay_url.trim();
+    let is_local = |rest: &str| {
+        let host = rest.split(['/', '?']).next().unwrap_or("");
+        host == "localhost"
+            || host.starts_with("localhost:")
+            || host == "127.0.0.1"
+            || host.starts_with("127.0.0.1:")
+    };
+    match url.split_once("://") {
+        Some(("wss", rest)) if !rest.is_empty() => {}
+        Some(("ws", rest)) if is_local(rest) => {}
+        Some(("ws", _)) => return Err("Remote relays must use wss://".to_string()),
+        _ => return Err("Relay URL must start with wss://".to_string()),
+    }
+    if config.token.trim().is_empty() {
+        return Err("A relay token is required".to_string());
+    }
+    Ok(())
+}
+
+fn next_backoff(current: Duration) -> Duration {
+    (current * 2).min(MAX_BACKOFF)
+}
+
+async fn set_status(state: &SharedApiState, status: TunnelStatus) {
+    state.tunnel.lock().await.status = status.clone();
+    let _ = state.app_handle.emit("tunnel-status", &status);
+    update_tray(&state.app_handle, &status);
+}
+
+/// Connect once and serve until the socket closes or `stop` fires. `Ok` means
+/// we were asked to stop; `connected` records whether the relay accepted us.
+async fn serve(
+    state: &SharedApiState,
+    config: &TunnelConfig,
+    stop: &mut watch::Receiver<bool>,
+    connected: &mut bool,
+) -> Result<(), String> {
+    let mut request = config
+        .relay_url
+        .trim()
+        .into_client_request()
+        .map_err(|e| format!("Invalid relay URL: {}", e))?;
+    let auth = HeaderValue::from_str(&format!("Bearer {}", config.token.trim()))
+        .map_err(|_| "Relay token contains invalid characters".to_string())?;
+    request.headers_mut().insert("authorization", auth);
+
+    let (socket, _) = tokio::select! {
+        connected = tokio_tungstenite::connect_async(request) => {
+            connected.map_err(|e| format!("Could not reach relay: {}", e))?
+        }
+        _ = stop.changed() => return Ok(()),
+    };
+    *connected = true;
+    set_status(
+        state,
+        TunnelStatus {
+            state: TunnelState::Connected,
+            relay_url: Some(config.relay_url.clone()),
+            error: None,
+        },
+    )
+    .await;
+    log::info!("Tunnel connected to {}", config.relay_url);
+
+    let (mut sink, mut frames) = socket.split();
+    let client = Arc::new(McpClient::default());
+    clients::connect(state, client.id(), Transport::Tunnel, None).await;
+    // Replies, resource notifications and canvas events share the outgoing
+    // queue.
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<serde_json::Value>();
+    canvas_events::forward(&state.canvas_events, reply_tx.clone());
+    state
+        .subscriptions
+        .lock()
+        .await
+        .attach(client.id(), reply_tx.clone());
+    let result = loop {
+        tokio::select! {
+            frame = frames.next() => {
+                let text = match frame {
+                    Some(Ok(tungstenite::Message::Text(text))) => text,
+                    Some(Ok(tungstenite::Message::Close(_))) | None => {
+                        break Err("Relay closed the connection".to_string());
+                    }
+                    Some(Err(e)) => break Err(format!("Tunnel error: {}", e)),
+                    Some(Ok(_)) => continue,
+                };
+                let body = match serde_json::from_str::<serde_json::Value>(text.as_str()) {
+                    Ok(body) => body,
+                    Err(e) => {
+                        let _ = reply_tx.send(api::mcp_error(None, -32700, &format!("Parse error: {}", e)));
+                        continue;
+                    }
+                };
+                let state = Arc::clone(state);
+                let client = Arc::clone(&client);
+                let reply_tx = reply_tx.clone();
+                tokio::spawn(async move {
+                    if let Some(reply) = api::handle_mcp_message(&state, body, &client).await {
+                        let _ = reply_tx.send(reply);
+                    }
+                });
+            }
+            Some(reply) = reply_rx.recv() => {
+                if let Err(e) = sink.send(tungstenite::Message::Text(reply.to_string().into())).await {
+                    break Err(format!("Tunnel error: {}", e));
+                }
+            }
+            _ = stop.changed() => {
+                let _ = sink.send(tungstenite::Message::Close(None)).await;
+                break Ok(());
+            }
+        }
+    };
+    api::forget_client(state, client.id()).await;
+    result
+}
+
+async fn run(state: SharedApiState, config: TunnelConfig, mut stop: watch::Receiver<bool>) {
+    let mut backoff = MIN_BACKOFF;
+    let mut last_error = None;
+    loop {
+        set_status(
+            &state,
+            TunnelStatus {
+                state: TunnelState::Connecting,
+                relay_url: Some(config.relay_url.clone()),
+                error: last_error.take(),
+            },
+        )
+        .await;
+        let mut connected = false;
+        let error = match serve(&state, &config, &mut stop, &mut connected).await {
+            Ok(()) => break,
+            Err(e) => e,
+        };
+        if *stop.borrow() {
+            break;
+        }
+        if connected {
+            backoff = MIN_BACKOFF;
+        }
+        log::warn!("{}; retrying in {:?}", error, backoff);
+        set_status(
+            &state,
+            TunnelStatus {
+                state: TunnelState::Connecting,
+                relay_url: Some(config.relay_url.clone()),
+                error: Some(error.clone()),
+            },
+        )
+        .await;
+        last_error = Some(error);
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = stop.changed() => break,
+        }
+        backoff = next_backoff(backoff);
+    }
+    // A replacement tunnel owns the status now.
+    if state.tunnel.lock().await.stop.is_none() {
+        set_status(&state, TunnelStatus::default()).await;
+    }
+    log::info!("Tunnel stopped");
+}
+
+/// Start the tunnel with the configured relay, replacing any running one.
+pub async fn start(state: &SharedApiState) -> Result<(), String> {
+    let config = settings::current(&state.app_handle).tunnel;
+    validate(&config)?;
+    let (stop_tx, stop_rx) = watch::channel(false);
+    if let Some(previous) = state.tunnel.lock().await.stop.replace(stop_tx) {
+        let _ = previous.send(true);
+    }
+    tauri::async_runtime::spawn(run(Arc::clone(state), config, stop_rx));
+    Ok(())
+}
+
+/// Drop the connection. Used by both Settings and the kill switch.
+pub async fn stop(state: &SharedApiState) {
+    if let Some(stop) = state.tunnel.lock().await.stop.take() {
+        let _ = stop.send(true);
+    }
+}
+
+fn set_enabled(app: &tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    let store = app.state::<SettingsStore>();
+    let mut config = store.get().tunnel;
+    config.enabled = enabled;
+    let value = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+    store
+        .update(serde_json::json!({ "tunnel": value }))
+        .map(|_| ())
+}
+
+/// Reconnect on launch if the tunnel was left on.
+pub fn resume(app: &tauri::AppHandle) {
+    if !settings::current(app).tunnel.enabled {
+        return;
+    }
+    let state = Arc::clone(app.state::<SharedApiState>().inner());
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = start(&state).await {
+            log::warn!("Tunnel not resumed: {}", e);
+        }
+    });
+}
+
+// --- Tray ---
+
+struct TrayItems {
+    status: MenuItem<tauri::Wry>,
+    kill: MenuItem<tauri::Wry>,
+}
+
+fn status_label(status: &TunnelStatus) -> String {
+    match (status.state, &status.relay_url) {
+        (TunnelState::Connected, Some(url)) => format!("Remote agents: connected to {}", url),
+        (TunnelState::Connecting, _) => "Remote agents: connecting…".to_string(),
+        _ => "Remote agents: off".to_string(),
+    }
+}
+
+fn update_tray(app: &tauri::AppHandle, status: &TunnelStatus) {
+    if let Some(items) = app.try_state::<TrayItems>() {
+        let _ = items.status.set_text(status_label(status));
+        let _ = items
+            .kill
+            .set_enabled(status.state != TunnelState::Disconnected);
+    }
+}
+
+/// Tray icon with a shortcut to today's napkin, the tunnel status and its
+/// kill switch.
+pub fn build_tray(app: &tauri::App) -> tauri::Result<()> {
+    let status = MenuItem::with_id(
+        app,
+        STATUS_MENU_ID,
+        status_label(&TunnelStatus::default()),
+        false,
+        None::<&str>,
+    )?;
+    let kill = MenuItem::with_id(
+        app,
+        KILL_MENU_ID,
+        "Disconnect Remote Agents",
+        false,
+        None::<&str>,
+    )?;
+    let daily_note = MenuItem::with_id(
+        app,
+        daily::TRAY_MENU_ID,
+        "Today's Napkin",
+        true,
+        None::<&str>,
+    )?;
+    let menu = Menu::with_items(app, &[&daily_note, &status, &kill])?;
+    let mut tray = TrayIconBuilder::with_id("napkin")
+        .tooltip("Napkin")
+        .menu(&menu)
+        .on_menu_event(|app, event| match event.id().as_ref() {
+            KILL_MENU_ID => kill_switch(app),
+            daily::TRAY_MENU_ID => daily::show_today(app),
+            _ => {}
+        });
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+    app.manage(TrayItems { status, kill });
+    Ok(())
+}
+
+fn kill_switch(app: &tauri::AppHandle) {
+    if let Err(e) = set_enabled(app, false) {
+        log::warn!("Failed to save tunnel setting: {}", e);
+    }
+    let state = Arc::clone(app.state::<SharedApiState>().inner());
+    tauri::async_runtime::spawn(async move { stop(&state).await });
+    log::info!("Tunnel disconnected from the tray");
+}
+
+// --- Tauri commands ---
+
+/// Save `config` (if given), turn the tunnel on and connect.
+#[tauri::command]
+pub async fn start_tunnel(
+    config: Option<TunnelConfig>,
+    state: tauri::State<'_, SharedApiState>,
+    store: tauri::State<'_, SettingsStore>,
+) -> Result<(), String> {
+    let mut config = config.unwrap_or_else(|| store.get().tunnel);
+    validate(&config)?;
+    config.enabled = true;
+    let value = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+    store.update(serde_json::json!({ "tunnel": value }))?;
+    start(state.inner()).await
+}
+
+#[tauri::command]
+pub async fn stop_tunnel(state: tauri::State<'_, SharedApiState>) -> Result<(), String> {
+    set_enabled(&state.app_handle, false)?;
+    stop(state.inner()).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_tunnel_status(
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<TunnelStatus, String> {
+    Ok(state.tunnel.lock().await.status.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(url: &str) -> TunnelConfig {
+        TunnelConfig {
+            enabled: true,
+            relay_url: url.to_string(),
+            token: "t".to_string(),
+        }
+    }
+
+    #[test]
+    fn relay_urls_must_be_secure_unless_local() {
+        assert!(validate(&config("wss://relay.example.com/napkin")).is_ok());
+        assert!(validate(&config("ws://localhost:8080/tunnel")).is_ok());
+        assert!(validate(&config("ws://127.0.0.1")).is_ok());
+        assert!(validate(&config("ws://relay.example.com")).is_err());
+        assert!(validate(&config("ws://localhost.evil.com")).is_err());
+        assert!(validate(&config("https://relay.example.com")).is_err());
+        assert!(validate(&config("wss://")).is_err());
+
+        let mut no_token = config("wss://relay.example.com");
+        no_token.token = "  ".to_string();
+        assert!(validate(&no_token).is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(next_backoff(MIN_BACKOFF), Duration::from_secs(2));
+        assert_eq!(next_backoff(Duration::from_secs(50)), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn tray_label_reflects_state() {
+        assert_eq!(status_label(&TunnelStatus::default()), "Remote agents: off");
+        let connected = TunnelStatus {
+            state: TunnelState::Connected,
+            relay_url: Some("wss://r".to_string()),
+            error: None,
+        };
+        assert_eq!(
+            status_label(&connected),
+            "Remote agents: connected to wss://r"
+        );
+    }
+}
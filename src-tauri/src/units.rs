@@ -0,0 +1,413 @@
+//! Real-world units and document scale.
+//!
+//! A document can declare how many canvas pixels make one real unit (e.g.
+//! 50 px = 1 m for a floor plan). The scale lives with the document in the
+//! webview and is mirrored here so agents can pass lengths like `"2.4m"` to
+//! the creation tools and the PDF export can print at true size.
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Geometry arguments that accept unit strings.
+const LENGTH_KEYS: [&str; 6] = ["x", "y", "width", "height", "x2", "y2"];
+/// Tools whose geometry arguments accept unit strings.
+const UNIT_AWARE_TOOLS: [&str; 3] = ["create_shape", "update_shape", "create_image"];
+/// Screen distance the ruler aims to keep between labelled ticks.
+const RULER_MAJOR_SPACING_PX: f64 = 100.0;
+const MAX_RULER_TICKS: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    Mm,
+    Cm,
+    M,
+    #[serde(rename = "in")]
+    Inch,
+    Ft,
+}
+
+impl Unit {
+    pub fn millimetres(self) -> f64 {
+        match self {
+            Unit::Mm => 1.0,
+            Unit::Cm => 10.0,
+            Unit::M => 1000.0,
+            Unit::Inch => 25.4,
+            Unit::Ft => 304.8,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Mm => "mm",
+            Unit::Cm => "cm",
+            Unit::M => "m",
+            Unit::Inch => "in",
+            Unit::Ft => "ft",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Unit> {
+        match s {
+            "mm" => Some(Unit::Mm),
+            "cm" => Some(Unit::Cm),
+            "m" => Some(Unit::M),
+            "in" | "\"" => Some(Unit::Inch),
+            "ft" | "'" => Some(Unit::Ft),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentScale {
+    pub unit: Unit,
+    /// Canvas pixels per one `unit`.
+    pub pixels_per_unit: f64,
+}
+
+impl DocumentScale {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.pixels_per_unit.is_finite() && self.pixels_per_unit > 0.0 {
+            Ok(())
+        } else {
+            Err("pixelsPerUnit must be a positive number".to_string())
+        }
+    }
+
+    /// Real-world millimetres covered by one canvas pixel.
+    pub fn mm_per_pixel(&self) -> f64 {
+        self.unit.millimetres() / self.pixels_per_unit
+    }
+
+    pub fn to_pixels(self, value: f64, unit: Unit) -> f64 {
+        value * unit.millimetres() / self.mm_per_pixel()
+    }
+}
+
+/// Parse a length argument: a plain number is canvas pixels, a string may
+/// carry a unit (`"120cm"`, `"2.5 m"`, `"40px"`).
+pub fn parse_length(value: &Value, scale: Option<&DocumentScale>) -> Result<f64, String> {
+    if let Some(n) = value.as_f64() {
+        return Ok(n);
+    }
+    let s = value
+        .as_str()
+        .ok_or_else(|| format!("Expected a number or length string, got {}", value))?
+        .trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(s.len());
+    let (number, suffix) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid length: {:?}", s))?;
+    match suffix.trim() {
+        "" | "px" => Ok(number),
+        other => {
+            let unit = Unit::parse(other).ok_or_else(|| format!("Unknown unit in {:?}", s))?;
+            let scale = scale.ok_or_else(|| {
+                format!(
+                    "{:?} uses real-world units but the document has no scale; call set_document_scale first",
+                    s
+                )
+            })?;
+            Ok(scale.to_pixels(number, unit))
+        }
+    }
+}
+
+fn convert_lengths(shape: &mut Value, scale: Option<&DocumentScale>) -> Result<(), String> {
+    let Some(obj) = shape.as_object_mut() else {
+        return Ok(());
+    };
+    for key in LENGTH_KEYS {
+        if let Some(value) = obj.get_mut(key) {
+            if value.is_string() {
+                *value = parse_length(value, scale)?.into();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Convert unit strings in a tool call's geometry to canvas pixels.
+pub fn convert_arguments(
+    tool_name: &str,
+    arguments: &mut Value,
+    scale: Option<&DocumentScale>,
+) -> Result<(), String> {
+    if UNIT_AWARE_TOOLS.contains(&tool_name) {
+        return convert_lengths(arguments, scale);
+    }
+    if tool_name == "batch_operations" {
+        let ops = arguments
+            .get_mut("operations")
+            .and_then(|o| o.as_array_mut());
+        for op in ops.into_iter().flatten() {
+            if let Some(data) = op.get_mut("data") {
+                convert_lengths(data, scale)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Let the unit-aware tools' schemas accept length strings.
+pub fn with_lengths(mut tools: Value) -> Value {
+    for tool in tools.as_array_mut().into_iter().flatten() {
+        let name = tool.get("name").and_then(|n| n.as_str()).unwrap_or("");
+        if !UNIT_AWARE_TOOLS.contains(&name) {
+            continue;
+        }
+        let props = tool
+            .pointer_mut("/inputSchema/properties")
+            .and_then(|p| p.as_object_mut());
+        for (key, prop) in props.into_iter().flatten() {
+            if !LENGTH_KEYS.contains(&key.as_str()) {
+                continue;
+            }
+            prop["type"] = serde_json::json!(["number", "string"]);
+            let base = prop
+                .get("description")
+                .and_then(|d| d.as_str())
+                .map(|d| format!("{}. ", d))
+                .unwrap_or_default();
+            prop["description"] = Value::String(format!(
+                "{}Pixels, or a length with a unit (e.g. \"120cm\") when the document has a scale",
+                base
+            ));
+        }
+    }
+    tools
+}
+
+// --- Ruler ---
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RulerTick {
+    /// Canvas-space position in pixels.
+    pub position: f64,
+    pub major: bool,
+    /// Set on major ticks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Smallest 1/2/5 x 10^n step at least `raw`.
+fn nice_step(raw: f64) -> f64 {
+    let magnitude = 10f64.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|step| *step >= raw)
+        .unwrap_or(10.0 * magnitude)
+}
+
+fn format_value(v: f64, step: f64) -> String {
+    let decimals = if step >= 1.0 {
+        0
+    } else {
+        (-step.log10()).ceil() as usize
+    };
+    format!("{:.*}", decimals, v)
+}
+
+/// Ticks for a ruler covering canvas range `start..end` at `zoom`, labelled in
+/// the document's unit (or pixels without a scale).
+pub fn ruler_ticks(
+    start: f64,
+    end: f64,
+    zoom: f64,
+    scale: Option<&DocumentScale>,
+) -> Vec<RulerTick> {
+    if !(start < end && zoom > 0.0) {
+        return Vec::new();
+    }
+    let (pixels_per_unit, symbol) = match scale {
+        Some(s) => (s.pixels_per_unit, s.unit.symbol()),
+        None => (1.0, ""),
+    };
+    let major = nice_step(RULER_MAJOR_SPACING_PX / zoom / pixels_per_unit);
+    let subdivisions = if (major / 10f64.powf(major.log10().floor()) - 2.0).abs() < 1e-9 {
+        4.0
+    } else {
+        5.0
+    };
+    let minor = major / subdivisions;
+
+    let first = (start / pixels_per_unit / minor).floor() as i64;
+    let last = (end / pixels_per_unit / minor).ceil() as i64;
+    (first..=last)
+        .take(MAX_RULER_TICKS)
+        .map(|i| {
+            let value = i as f64 * minor;
+            let is_major = i.rem_euclid(subdivisions as i64) == 0;
+            RulerTick {
+                position: value * pixels_per_unit,
+                major: is_major,
+                label: is_major.then(|| format!("{}{}", format_value(value, major), symbol)),
+            }
+        })
+        .collect()
+}
+
+// --- Shared state ---
+
+pub async fn current_scale(state: &SharedApiState) -> Option<DocumentScale> {
+    *state.scale.lock().await
+}
+
+async fn store_scale(state: &SharedApiState, scale: Option<DocumentScale>) {
+    *state.scale.lock().await = scale;
+}
+
+// --- Tauri commands ---
+
+/// Called by the webview whenever the active document's scale changes
+/// (document load, tab switch, user edit).
+#[tauri::command]
+pub async fn set_document_scale(
+    scale: Option<DocumentScale>,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<(), String> {
+    if let Some(s) = &scale {
+        s.validate()?;
+    }
+    store_scale(state.inner(), scale).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_ruler_ticks(
+    start: f64,
+    end: f64,
+    zoom: f64,
+    state: tauri::State<'_, SharedApiState>,
+) -> Result<Vec<RulerTick>, String> {
+    let scale = current_scale(state.inner()).await;
+    Ok(ruler_ticks(start, end, zoom, scale.as_ref()))
+}
+
+// --- MCP tool ---
+
+pub fn unit_tools() -> Vec<Value> {
+    vec![serde_json::json!({
+        "name": "set_document_scale",
+        "description": "Set the document's real-world scale (e.g. 50 pixels = 1 m for a floor plan), or clear it. Once set, create_shape/update_shape/create_image accept lengths like \"2.4m\" or \"30cm\", rulers show real units and PDF export prints at true size.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "unit": { "type": "string", "enum": ["mm", "cm", "m", "in", "ft"] },
+                "pixelsPerUnit": { "type": "number", "description": "Canvas pixels per one unit" },
+                "clear": { "type": "boolean", "description": "Remove the scale" }
+            },
+            "additionalProperties": false
+        }
+    })]
+}
+
+pub async fn call_unit_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "set_document_scale" => Some(set_scale_tool(state, arguments).await),
+        _ => None,
+    }
+}
+
+async fn set_scale_tool(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let scale = if arguments.get("clear").and_then(|c| c.as_bool()) == Some(true) {
+        None
+    } else {
+        let scale: DocumentScale = serde_json::from_value(arguments.clone())
+            .map_err(|e| format!("Invalid scale: {}", e))?;
+        scale.validate()?;
+        Some(scale)
+    };
+
+    // The document owns the scale; the webview persists it and echoes it back
+    // through `set_document_scale`.
+    let result = bridge_tool_call(
+        state,
+        "set_document_scale",
+        serde_json::json!({ "scale": scale }),
+    )
+    .await?;
+    if let Some(err) = result.get("error").and_then(|e| e.as_str()) {
+        return Err(err.to_string());
+    }
+    store_scale(state, scale).await;
+    Ok(serde_json::json!({ "scale": scale }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn metres() -> DocumentScale {
+        DocumentScale {
+            unit: Unit::M,
+            pixels_per_unit: 50.0,
+        }
+    }
+
+    #[test]
+    fn lengths_convert_through_the_scale() {
+        let scale = metres();
+        assert_eq!(parse_length(&json!(12), None).unwrap(), 12.0);
+        assert_eq!(parse_length(&json!("40px"), None).unwrap(), 40.0);
+        assert!((parse_length(&json!("2.4m"), Some(&scale)).unwrap() - 120.0).abs() < 1e-9);
+        assert!((parse_length(&json!("30 cm"), Some(&scale)).unwrap() - 15.0).abs() < 1e-9);
+        assert!(parse_length(&json!("2m"), None).is_err());
+        assert!(parse_length(&json!("2 parsecs"), Some(&scale)).is_err());
+    }
+
+    #[test]
+    fn batch_and_create_arguments_are_converted() {
+        let scale = metres();
+        let mut args =
+            json!({ "type": "rectangle", "x": "1m", "y": 0, "width": "4m", "text": "2m" });
+        convert_arguments("create_shape", &mut args, Some(&scale)).unwrap();
+        assert_eq!(args["x"], 50.0);
+        assert_eq!(args["width"], 200.0);
+        assert_eq!(args["text"], "2m");
+
+        let mut batch = json!({ "operations": [{ "action": "update", "data": { "id": "a", "height": "10cm" } }] });
+        convert_arguments("batch_operations", &mut batch, Some(&scale)).unwrap();
+        assert_eq!(batch["operations"][0]["data"]["height"], 5.0);
+    }
+
+    #[test]
+    fn ruler_ticks_follow_scale_and_zoom() {
+        let ticks = ruler_ticks(0.0, 500.0, 1.0, Some(&metres()));
+        // 100 screen px = 2 m, so majors every 2 m with 0.5 m minors.
+        let majors: Vec<_> = ticks.iter().filter(|t| t.major).collect();
+        assert_eq!(majors[1].position, 100.0);
+        assert_eq!(majors[1].label.as_deref(), Some("2m"));
+        assert_eq!(ticks[1].position, 25.0);
+
+        let zoomed = ruler_ticks(0.0, 100.0, 10.0, None);
+        let labels: Vec<_> = zoomed.iter().filter_map(|t| t.label.clone()).collect();
+        assert_eq!(&labels[..3], ["0", "10", "20"]);
+    }
+
+    #[test]
+    fn schema_accepts_length_strings() {
+        let tools = json!([{ "name": "create_shape", "inputSchema": { "properties": {
+            "width": { "type": "number", "description": "Width (default: 200)" },
+            "text": { "type": "string" }
+        }}}]);
+        let tools = with_lengths(tools);
+        let props = &tools[0]["inputSchema"]["properties"];
+        assert_eq!(props["width"]["type"], json!(["number", "string"]));
+        assert_eq!(props["text"]["type"], "string");
+    }
+}
@@ -0,0 +1,455 @@
+//! Earlier versions of saved documents (File > Revert To).
+//!
+//! Each save of a document also copies it, as collection JSON, to
+//! `<app data>/versions/<hash of the path>/<UTC time>.napkin`, at most once a
+//! minute and only when it changed. At the top of every hour the open
+//! document is copied too, so changes that reach the file without a save
+//! from the window (agents, scheduled jobs, other apps) are kept as well.
+//! Versions are written like documents, to a temp file renamed into place.
+//! Images in versions refer to the asset store (`assets.rs`), so a
+//! screenshot is not copied with every version. Every version from the last
+//! hour is kept, then the newest of each hour for two days and the newest of
+//! each day for a month; older ones are deleted as new ones arrive.
+//!
+//! Restoring a version first keeps the document as it is now as another
+//! version, so a revert can itself be reverted. The Revert To submenu lists
+//! the versions of the document that was last opened or saved; choosing one
+//! asks the webview (`menu-revert-to`) to confirm and restore it.
+
+use chrono::{DateTime, Duration, Local, NaiveDateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::menu::{IsMenuItem, MenuItem, Submenu};
+use tauri::{Emitter, Manager};
+
+use crate::assets;
+use crate::docstore;
+use crate::document;
+use crate::encryption;
+use crate::resources;
+use crate::subscriptions;
+use crate::thumbnails;
+use crate::wal;
+
+const VERSIONS_DIR: &str = "versions";
+const EXTENSION: &str = "napkin";
+/// Version ids are their UTC save times.
+const ID_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+/// Menu ids of versions are this followed by the version id.
+pub const MENU_PREFIX: &str = "revert_to:";
+const EMPTY_MENU_ID: &str = "revert_to_none";
+/// Emitted with `{ path, id, label }` when a version is chosen from the menu.
+const MENU_EVENT: &str = "menu-revert-to";
+/// The shortest time between two versions kept by saving.
+const MIN_INTERVAL_SECS: i64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentVersion {
+    pub id: String,
+    /// When the version was saved, RFC 3339.
+    pub saved_at: String,
+    pub bytes: u64,
+}
+
+fn parse_id(id: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(id, ID_FORMAT)
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/// Where the versions of `document` are kept under `root`.
+fn document_dir(root: &Path, document: &str) -> PathBuf {
+    let digest = Sha256::digest(document.as_bytes());
+    let name: String = digest[..12].iter().map(|b| format!("{:02x}", b)).collect();
+    root.join(name)
+}
+
+fn version_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.{}", id, EXTENSION))
+}
+
+/// The versions in `dir`, newest first.
+fn list(dir: &Path) -> Vec<DocumentVersion> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut versions: Vec<(DateTime<Utc>, DocumentVersion)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(EXTENSION) {
+                return None;
+            }
+            let id = path.file_stem()?.to_str()?.to_string();
+            let saved = parse_id(&id)?;
+            let bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            Some((
+                saved,
+                DocumentVersion {
+                    id,
+                    saved_at: saved.to_rfc3339(),
+                    bytes,
+                },
+            ))
+        })
+        .collect();
+    versions.sort_by_key(|(saved, _)| std::cmp::Reverse(*saved));
+    versions.into_iter().map(|(_, version)| version).collect()
+}
+
+/// Which of `saved` (newest first) the retention rules keep at `now`.
+fn retained(saved: &[DateTime<Utc>], now: DateTime<Utc>) -> Vec<bool> {
+    let mut buckets = HashSet::new();
+    saved
+        .iter()
+        .enumerate()
+        .map(|(i, time)| {
+            let age = now - *time;
+            if i == 0 || age < Duration::hours(1) {
+                true
+            } else if age < Duration::days(2) {
+                buckets.insert(("hour", time.timestamp().div_euclid(3600)))
+            } else if age < Duration::days(30) {
+                buckets.insert(("day", time.timestamp().div_euclid(86_400)))
+            } else {
+                false
+            }
+        })
+        .collect()
+}
+
+/// Delete the versions in `dir` the retention rules no longer keep.
+fn prune(dir: &Path, now: DateTime<Utc>) {
+    let versions = list(dir);
+    let saved: Vec<DateTime<Utc>> = versions.iter().filter_map(|v| parse_id(&v.id)).collect();
+    for (version, keep) in versions.iter().zip(retained(&saved, now)) {
+        if !keep {
+            let _ = std::fs::remove_file(version_path(dir, &version.id));
+        }
+    }
+}
+
+/// Keep `document` as it is on disk as a new version in `dir`. Unless
+/// `force`d, nothing is kept within a minute of the newest version or when
+/// the document has not changed since it.
+fn snapshot(
+    dir: &Path,
+    document: &Path,
+    now: DateTime<Utc>,
+    force: bool,
+) -> Result<Option<DocumentVersion>, String> {
+    let json = assets::dehydrate_json(&docstore::read_path(document)?)?;
+    if let Some(newest) = list(dir).first() {
+        let recent = parse_id(&newest.id)
+            .is_some_and(|saved| now - saved < Duration::seconds(MIN_INTERVAL_SECS));
+        if !force && recent {
+            return Ok(None);
+        }
+        if std::fs::read_to_string(version_path(dir, &newest.id)).ok() == Some(json.clone()) {
+            return Ok(None);
+        }
+    }
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let id = now.format(ID_FORMAT).to_string();
+    docstore::write_atomic(&version_path(dir, &id), json.as_bytes())?;
+    prune(dir, now);
+    Ok(Some(DocumentVersion {
+        id,
+        saved_at: now.to_rfc3339(),
+        bytes: json.len() as u64,
+    }))
+}
+
+/// Replace `document` with version `id` from `dir`, keeping what it
+/// replaces as a new version saved at `now`.
+fn restore(dir: &Path, document: &Path, id: &str, now: DateTime<Utc>) -> Result<(), String> {
+    if parse_id(id).is_none() {
+        return Err(format!("Not a version id: {}", id));
+    }
+    let json = std::fs::read_to_string(version_path(dir, id))
+        .map_err(|_| format!("Version not found: {}", id))?;
+    if document.exists() {
+        snapshot(dir, document, now, true)?;
+    }
+    docstore::write_path(document, assets::hydrate_json(json))?;
+    wal::clear(document);
+    Ok(())
+}
+
+/// Where the versions of every document are kept.
+pub(crate) fn root(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(VERSIONS_DIR))
+        .map_err(|e| format!("No app data directory: {}", e))
+}
+
+fn versions_dir(app: &tauri::AppHandle, document: &str) -> Result<PathBuf, String> {
+    root(app).map(|root| document_dir(&root, document))
+}
+
+/// Keep the document just saved to `path` as a version, in the background.
+pub fn record(app: &tauri::AppHandle, path: String) {
+    let Ok(dir) = versions_dir(app, &path) else {
+        return;
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = snapshot(&dir, Path::new(&path), Utc::now(), false) {
+            log::warn!("No version kept of {}: {}", path, e);
+        }
+        show(&app, &path);
+    });
+}
+
+/// Keep the open document as a version at the top of every hour, in the
+/// background. Startup calls this once.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let into_hour = Utc::now().timestamp().rem_euclid(3600) as u64;
+            tokio::time::sleep(std::time::Duration::from_secs(3600 - into_hour)).await;
+
+            let Some(path) = open_document(&app) else {
+                continue;
+            };
+            if Path::new(&path).exists() && !encryption::is_encrypted(Path::new(&path)) {
+                record(&app, path);
+            }
+        }
+    });
+}
+
+/// Delete every version of `path`, as when it is password-protected.
+pub fn discard(app: &tauri::AppHandle, path: &str) {
+    if let Ok(dir) = versions_dir(app, path) {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+    show(app, path);
+}
+
+// --- Menu ---
+
+struct RevertMenu {
+    submenu: Submenu<tauri::Wry>,
+    /// The document whose versions are listed.
+    document: Mutex<Option<String>>,
+}
+
+/// The document whose versions the Revert To submenu lists.
+fn open_document(app: &tauri::AppHandle) -> Option<String> {
+    let menu = app.try_state::<RevertMenu>()?;
+    let document = menu.document.lock().ok()?;
+    document.clone()
+}
+
+/// Version `id` as its local save time.
+fn menu_label(id: &str) -> String {
+    match parse_id(id) {
+        Some(saved) => saved
+            .with_timezone(&Local)
+            .format("%a %b %-d, %H:%M")
+            .to_string(),
+        None => id.to_string(),
+    }
+}
+
+fn empty_item<M: Manager<tauri::Wry>>(manager: &M) -> tauri::Result<MenuItem<tauri::Wry>> {
+    MenuItem::with_id(
+        manager,
+        EMPTY_MENU_ID,
+        "No Earlier Versions",
+        false,
+        None::<&str>,
+    )
+}
+
+/// The File > Revert To submenu, empty until a document is opened.
+pub fn submenu(app: &tauri::App) -> tauri::Result<Submenu<tauri::Wry>> {
+    let empty = empty_item(app)?;
+    let submenu = Submenu::with_items(app, "Revert To", true, &[&empty])?;
+    app.manage(RevertMenu {
+        submenu: submenu.clone(),
+        document: Mutex::new(None),
+    });
+    Ok(submenu)
+}
+
+/// List the versions of `path` in the Revert To submenu.
+pub fn show(app: &tauri::AppHandle, path: &str) {
+    let Some(menu) = app.try_state::<RevertMenu>() else {
+        return;
+    };
+    let Ok(dir) = versions_dir(app, path) else {
+        return;
+    };
+    if let Ok(mut document) = menu.document.lock() {
+        *document = Some(path.to_string());
+    }
+    while let Ok(Some(_)) = menu.submenu.remove_at(0) {}
+    let items: Vec<MenuItem<tauri::Wry>> = list(&dir)
+        .iter()
+        .filter_map(|version| {
+            let id = format!("{}{}", MENU_PREFIX, version.id);
+            MenuItem::with_id(app, id, menu_label(&version.id), true, None::<&str>).ok()
+        })
+        .collect();
+    let result = if items.is_empty() {
+        empty_item(app).and_then(|item| menu.submenu.append(&item))
+    } else {
+        let items: Vec<&dyn IsMenuItem<tauri::Wry>> = items
+            .iter()
+            .map(|item| item as &dyn IsMenuItem<tauri::Wry>)
+            .collect();
+        menu.submenu.append_items(&items)
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to update the Revert To menu: {}", e);
+    }
+}
+
+/// A version was chosen from the Revert To submenu.
+pub fn on_menu_click(app: &tauri::AppHandle, menu_id: &str) {
+    let Some(id) = menu_id.strip_prefix(MENU_PREFIX) else {
+        return;
+    };
+    let Some(path) = open_document(app) else {
+        return;
+    };
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.emit(
+            MENU_EVENT,
+            json!({ "path": path, "id": id, "label": menu_label(id) }),
+        );
+    }
+}
+
+// --- Tauri commands ---
+
+/// The versions kept of the document at `path`, newest first.
+#[tauri::command]
+pub fn list_document_versions(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<Vec<DocumentVersion>, String> {
+    Ok(list(&versions_dir(&app, &path)?))
+}
+
+/// Replace the document at `path` with version `id` of it. The webview
+/// opens the document again afterwards.
+#[tauri::command]
+pub async fn restore_document_version(
+    app: tauri::AppHandle,
+    path: String,
+    id: String,
+) -> Result<(), String> {
+    let dir = versions_dir(&app, &path)?;
+    let document = path.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        restore(&dir, Path::new(&document), &id, Utc::now())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    document::forget(&app, &path);
+    subscriptions::notify_app(&app, vec![resources::file_uri(&path)]);
+    thumbnails::refresh(&app, path.clone());
+    show(&app, &path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn old_versions_thin_out_to_hourly_then_daily() {
+        let now = at("2026-10-16T12:00:00Z");
+        let saved = [
+            at("2026-10-16T11:59:00Z"),
+            at("2026-10-16T11:30:00Z"),
+            at("2026-10-16T11:10:00Z"),
+            // Same hour: only the newer survives
+            at("2026-10-16T09:50:00Z"),
+            at("2026-10-16T09:20:00Z"),
+            at("2026-10-15T08:00:00Z"),
+            // Same day, more than two days ago
+            at("2026-10-10T18:00:00Z"),
+            at("2026-10-10T07:00:00Z"),
+            // Older than a month
+            at("2026-09-01T07:00:00Z"),
+        ];
+        assert_eq!(
+            retained(&saved, now),
+            [true, true, true, true, false, true, true, false, false]
+        );
+        // The newest is kept however old
+        assert_eq!(retained(&saved[8..], now), [true]);
+    }
+
+    #[test]
+    fn restoring_keeps_the_current_document_as_a_version() {
+        let root = std::env::temp_dir().join(format!("napkin-versions-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let document = root.join("board.napkin");
+        let path = document.to_string_lossy().to_string();
+        let dir = document_dir(&root.join(VERSIONS_DIR), &path);
+        let board = |x: i64| json!({ "shapes": [{ "id": "a", "type": "rectangle", "x": x }] });
+
+        let start = at("2026-10-16T12:00:00Z");
+        docstore::write_path(&document, board(1).to_string()).unwrap();
+        let first = snapshot(&dir, &document, start, false).unwrap().unwrap();
+        // Too soon, then unchanged
+        docstore::write_path(&document, board(2).to_string()).unwrap();
+        assert!(
+            snapshot(&dir, &document, start + Duration::seconds(30), false)
+                .unwrap()
+                .is_none()
+        );
+        docstore::write_path(&document, board(1).to_string()).unwrap();
+        assert!(
+            snapshot(&dir, &document, start + Duration::minutes(5), false)
+                .unwrap()
+                .is_none()
+        );
+        docstore::write_path(&document, board(2).to_string()).unwrap();
+        let second = snapshot(&dir, &document, start + Duration::minutes(5), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            list(&dir).iter().map(|v| &v.id).collect::<Vec<_>>(),
+            [&second.id, &first.id]
+        );
+
+        docstore::write_path(&document, board(3).to_string()).unwrap();
+        let later = start + Duration::minutes(10);
+        restore(&dir, &document, &first.id, later).unwrap();
+        assert_eq!(
+            docstore::read_path(&document).unwrap(),
+            board(1).to_string()
+        );
+        let kept = list(&dir);
+        assert_eq!(kept.len(), 3);
+        // Written through a temp file that is gone afterwards
+        let files = std::fs::read_dir(&dir).unwrap().flatten().count();
+        assert_eq!(files, 3);
+        let current = std::fs::read_to_string(version_path(&dir, &kept[0].id)).unwrap();
+        assert_eq!(current, board(3).to_string());
+
+        assert!(restore(&dir, &document, "../board", later).is_err());
+        assert!(restore(&dir, &document, "20200101T000000Z", later).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,253 @@
+//! Boards hidden from agents.
+//!
+//! A document marked "hidden from agents" (Menu → Hide Board from Agents,
+//! saved as `metadata.hiddenFromApi`) does not exist as far as MCP clients
+//! are concerned: `list_tabs` and `resources/list` leave it out, its shape
+//! events are dropped, and any call that would read or change it fails with
+//! the same JSON-RPC permission error as read-only mode, with
+//! `data.reason` set to `"hiddenTab"`.
+//!
+//! The webview reports which open tabs are hidden and which one the user
+//! has in front of them (`api_set_tab_visibility`), and hands back the tab
+//! agents are working on with each bridged answer (`api_response`). A call
+//! targets the agents' tab unless it names another with `tabId`.
+
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use crate::api::SharedApiState;
+use crate::read_only::PERMISSION_DENIED;
+
+/// Tools that never touch a board's content. A job's tool is checked when
+/// the job starts.
+const TAB_FREE_TOOLS: &[&str] = &[
+    "list_tabs",
+    "create_tab",
+    "submit_job",
+    "get_job_status",
+    "get_job_result",
+];
+
+/// Tools whose `tabId` names the board they work on, instead of the
+/// agents' tab.
+const TAB_ID_TOOLS: &[&str] = &[
+    "switch_tab",
+    "rename_tab",
+    "extract_outline",
+    "partition_canvas",
+    "get_shape_order",
+];
+
+#[derive(Default)]
+struct Tabs {
+    hidden: HashSet<String>,
+    /// The tab shown in the window.
+    active: Option<String>,
+    /// The tab agents switched to, if not the one shown.
+    mcp: Option<String>,
+}
+
+impl Tabs {
+    fn target(&self) -> Option<&str> {
+        self.mcp.as_deref().or(self.active.as_deref())
+    }
+
+    fn target_hidden(&self) -> bool {
+        self.target().is_some_and(|id| self.hidden.contains(id))
+    }
+
+    /// Whether `tool` called with `arguments` would reach a hidden board.
+    fn refuses(&self, tool: &str, arguments: &Value) -> bool {
+        if let Some(tab_id) = arguments.get("tabId").and_then(Value::as_str) {
+            if self.hidden.contains(tab_id) {
+                return true;
+            }
+            if TAB_ID_TOOLS.contains(&tool) {
+                return false;
+            }
+        }
+        !TAB_FREE_TOOLS.contains(&tool) && self.target_hidden()
+    }
+}
+
+/// Hidden tabs and where agents are working, as last reported by the webview.
+#[derive(Default)]
+pub struct TabVisibility {
+    tabs: Mutex<Tabs>,
+}
+
+impl TabVisibility {
+    fn tabs(&self) -> std::sync::MutexGuard<'_, Tabs> {
+        self.tabs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn is_hidden(&self, tab_id: &str) -> bool {
+        self.tabs().hidden.contains(tab_id)
+    }
+
+    /// Whether the tab agents are working on is hidden.
+    pub fn target_hidden(&self) -> bool {
+        self.tabs().target_hidden()
+    }
+
+    pub fn refuses(&self, tool: &str, arguments: &Value) -> bool {
+        self.tabs().refuses(tool, arguments)
+    }
+
+    /// The tab a call with `arguments` works on: the one it names, otherwise
+    /// the agents' tab.
+    pub fn document_for(&self, arguments: &Value) -> Option<String> {
+        match arguments.get("tabId").and_then(Value::as_str) {
+            Some(tab_id) => Some(tab_id.to_string()),
+            None => self.tabs().target().map(str::to_string),
+        }
+    }
+
+    /// Record the agents' tab reported with a bridged answer.
+    pub fn set_mcp_tab(&self, tab_id: Option<String>) {
+        self.tabs().mcp = tab_id;
+    }
+
+    /// A `list_tabs` result without the hidden tabs.
+    pub fn filter_tabs(&self, mut result: Value) -> Value {
+        let tabs = self.tabs();
+        if let Some(list) = result.get_mut("tabs").and_then(Value::as_array_mut) {
+            list.retain(|tab| {
+                !tab.get("id")
+                    .and_then(Value::as_str)
+                    .is_some_and(|id| tabs.hidden.contains(id))
+            });
+        }
+        for key in ["activeTabId", "mcpActiveTabId"] {
+            let hidden = result
+                .get(key)
+                .and_then(Value::as_str)
+                .is_some_and(|id| tabs.hidden.contains(id));
+            if hidden {
+                result[key] = Value::Null;
+            }
+        }
+        result
+    }
+}
+
+/// The error for a call that would reach a hidden board.
+pub fn permission_error(id: Option<Value>, what: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {
+            "code": PERMISSION_DENIED,
+            "message": format!("{} is not allowed: the board is hidden from agents", what),
+            "data": { "reason": "hiddenTab" },
+        },
+    })
+}
+
+/// A saved file's JSON without its hidden documents; `None` when nothing
+/// is left to show.
+pub fn redact_file(text: &str) -> Option<String> {
+    let Ok(mut file) = serde_json::from_str::<Value>(text) else {
+        return Some(text.to_string());
+    };
+    let Some(documents) = file.get_mut("documents").and_then(Value::as_array_mut) else {
+        return (!is_hidden_document(&file)).then(|| text.to_string());
+    };
+    let before = documents.len();
+    documents.retain(|doc| !is_hidden_document(doc));
+    if documents.len() == before {
+        return Some(text.to_string());
+    }
+    if documents.is_empty() {
+        return None;
+    }
+    file["activeDocumentIndex"] = json!(0);
+    serde_json::to_string_pretty(&file).ok()
+}
+
+fn is_hidden_document(doc: &Value) -> bool {
+    doc.pointer("/metadata/hiddenFromApi") == Some(&Value::Bool(true))
+}
+
+/// Called by the webview when a board is hidden or shown, and when the tab
+/// on screen changes.
+#[tauri::command]
+pub fn api_set_tab_visibility(
+    hidden: Vec<String>,
+    active_tab_id: Option<String>,
+    state: tauri::State<'_, SharedApiState>,
+) {
+    let mut tabs = state.visibility.tabs();
+    tabs.hidden = hidden.into_iter().collect();
+    tabs.active = active_tab_id;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tabs(hidden: &[&str], active: &str, mcp: Option<&str>) -> Tabs {
+        Tabs {
+            hidden: hidden.iter().map(|s| s.to_string()).collect(),
+            active: Some(active.to_string()),
+            mcp: mcp.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn calls_reaching_a_hidden_board_are_refused() {
+        let shown = tabs(&["secret"], "open", None);
+        assert!(!shown.refuses("create_shape", &json!({})));
+        assert!(shown.refuses("get_shape_order", &json!({ "tabId": "secret" })));
+        assert!(shown.refuses("switch_tab", &json!({ "tabId": "secret" })));
+        assert!(!shown.refuses("switch_tab", &json!({ "tabId": "open" })));
+
+        // Agents switched to a hidden board before it was hidden
+        let stuck = tabs(&["secret"], "open", Some("secret"));
+        assert!(stuck.refuses("get_canvas", &json!({})));
+        assert!(stuck.refuses("acquire_canvas_lock", &json!({})));
+        assert!(!stuck.refuses("list_tabs", &json!({})));
+        assert!(!stuck.refuses("create_tab", &json!({ "title": "New" })));
+        assert!(!stuck.refuses("switch_tab", &json!({ "tabId": "open" })));
+        assert!(!stuck.refuses("extract_outline", &json!({ "tabId": "open" })));
+
+        // The user is looking at the hidden board and agents follow them
+        let following = tabs(&["secret"], "secret", None);
+        assert!(following.refuses("get_canvas", &json!({})));
+    }
+
+    #[test]
+    fn hidden_tabs_are_left_out_of_list_tabs() {
+        let visibility = TabVisibility::default();
+        *visibility.tabs() = tabs(&["secret"], "secret", None);
+        let result = visibility.filter_tabs(json!({
+            "tabs": [{ "id": "open" }, { "id": "secret" }],
+            "activeTabId": "secret",
+            "mcpActiveTabId": "open",
+        }));
+        assert_eq!(
+            result,
+            json!({
+                "tabs": [{ "id": "open" }],
+                "activeTabId": null,
+                "mcpActiveTabId": "open",
+            })
+        );
+    }
+
+    #[test]
+    fn hidden_documents_are_removed_from_files() {
+        let hidden = json!({ "shapes": [], "metadata": { "hiddenFromApi": true } });
+        let shown = json!({ "shapes": [], "metadata": { "title": "Plan" } });
+        assert_eq!(redact_file(&hidden.to_string()), None);
+        assert_eq!(redact_file(&shown.to_string()), Some(shown.to_string()));
+
+        let collection = json!({ "documents": [hidden, shown], "activeDocumentIndex": 0 });
+        let redacted: Value =
+            serde_json::from_str(&redact_file(&collection.to_string()).unwrap()).unwrap();
+        assert_eq!(redacted["documents"], json!([shown]));
+        let all_hidden = json!({ "documents": [hidden] });
+        assert_eq!(redact_file(&all_hidden.to_string()), None);
+    }
+}
@@ -0,0 +1,334 @@
+//! Write-ahead log of edits, for crash safety.
+//!
+//! Autosave writes a named document a couple of seconds after the last edit,
+//! and until then the edit lives only in the webview. So every edit the
+//! webview reports (`api_notify_change`) is also appended to
+//! `<document>.wal` next to the document and flushed to disk, one JSON line
+//! per report, and saving the document (`save_document`,
+//! `apply_document_changes`) empties the log again. If the app dies in
+//! between, `load_document` finds the log on the next open, replays it on
+//! top of the last save and saves the result, so at most the last report is
+//! lost.
+//!
+//! Entries name tabs by id, which saves keep in each document's
+//! `metadata.tabId`, so edits land on the right tab even when tabs were
+//! reordered or closed since the last save. The tab's position is kept too,
+//! for documents saved before tabs had ids. A change replaces or removes a
+//! shape by id, so replaying edits that the last save already had changes
+//! nothing, and a line cut short by the crash is skipped.
+
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::assets;
+use crate::canvas_events::{ChangeKind, ShapeChange};
+use crate::docstore;
+use crate::encryption;
+
+/// One `api_notify_change` report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    /// Id of the tab, as saved in `metadata.tabId`.
+    tab: String,
+    /// Position of the tab when the edit was made.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index: Option<usize>,
+    changes: Vec<ShapeChange>,
+}
+
+/// The log of `document`, beside it.
+pub(crate) fn log_path(document: &Path) -> PathBuf {
+    let mut name = document.as_os_str().to_owned();
+    name.push(".wal");
+    PathBuf::from(name)
+}
+
+/// Record edits to tab `tab` (at position `index`) of `document` before
+/// anything else sees them. Edits to password-protected documents are not
+/// logged in the clear.
+pub fn append(
+    document: &Path,
+    tab: &str,
+    index: Option<usize>,
+    changes: Vec<ShapeChange>,
+) -> Result<(), String> {
+    if changes.is_empty() || encryption::is_encrypted(document) {
+        return Ok(());
+    }
+    let path = log_path(document);
+    let entry = Entry {
+        tab: tab.to_string(),
+        index,
+        changes,
+    };
+    let mut line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+    line.push('\n');
+    // So collecting unused assets reads the log too
+    if assets::has_references(&line) {
+        assets::register(document);
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    file.write_all(line.as_bytes())
+        .and_then(|()| file.sync_data())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Forget the edits to `document`, which is now saved with them.
+pub fn clear(document: &Path) {
+    let path = log_path(document);
+    if let Err(e) = std::fs::remove_file(&path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            log::warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// An empty tab for edits to a tab created after the last save.
+fn new_tab(id: &str) -> Value {
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    json!({
+        "version": "1.0.0",
+        "appName": "napkin",
+        "shapes": [],
+        "viewport": { "x": 0, "y": 0, "zoom": 1 },
+        "metadata": { "created": now, "modified": now, "title": "Untitled", "tabId": id },
+    })
+}
+
+/// The document of tab `id`. Without one, the document at `index` when it
+/// was saved without an id (and takes this one), or else a new tab.
+fn tab_document<'a>(
+    documents: &'a mut Vec<Value>,
+    id: &str,
+    index: Option<usize>,
+) -> &'a mut Value {
+    let tab_id = |document: &Value| document.pointer("/metadata/tabId").cloned();
+    let found = documents
+        .iter()
+        .position(|document| tab_id(document) == Some(json!(id)))
+        .or_else(|| {
+            let i = index.filter(|&i| i < documents.len())?;
+            let document = documents[i].as_object_mut()?;
+            let metadata = document.entry("metadata").or_insert_with(|| json!({}));
+            let metadata = metadata.as_object_mut()?;
+            if metadata.contains_key("tabId") {
+                return None;
+            }
+            metadata.insert("tabId".to_string(), json!(id));
+            Some(i)
+        });
+    let i = found.unwrap_or_else(|| {
+        documents.push(new_tab(id));
+        documents.len() - 1
+    });
+    &mut documents[i]
+}
+
+fn apply(documents: &mut Vec<Value>, entry: Entry) {
+    let document = tab_document(documents, &entry.tab, entry.index);
+    if !document["shapes"].is_array() {
+        document["shapes"] = json!([]);
+    }
+    let Some(shapes) = document["shapes"].as_array_mut() else {
+        return;
+    };
+    for change in entry.changes {
+        let index = shapes
+            .iter()
+            .position(|s| s["id"].as_str() == Some(change.shape_id.as_str()));
+        match (change.kind, change.shape, index) {
+            (ChangeKind::Deleted, _, Some(i)) => {
+                shapes.remove(i);
+            }
+            (ChangeKind::Created | ChangeKind::Updated, Some(shape), Some(i)) => shapes[i] = shape,
+            (ChangeKind::Created | ChangeKind::Updated, Some(shape), None) => shapes.push(shape),
+            _ => {}
+        }
+    }
+}
+
+/// Collection `json` with the logged edits to `document` on top, or `None`
+/// when nothing was logged.
+fn replay(document: &Path, json: &str) -> Result<Option<String>, String> {
+    let Ok(log) = std::fs::read_to_string(log_path(document)) else {
+        return Ok(None);
+    };
+    let entries: Vec<Entry> = log
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let mut collection = docstore::as_collection(value)?;
+    let mut documents = match collection.remove("documents") {
+        Some(Value::Array(documents)) => documents,
+        _ => Vec::new(),
+    };
+    for entry in entries {
+        apply(&mut documents, entry);
+    }
+    collection.insert("documents".to_string(), Value::Array(documents));
+    Ok(Some(Value::Object(collection).to_string()))
+}
+
+/// Bring `json`, just read from `document`, up to date with its log, and
+/// save the result so the log can go. Without a log, `json` is unchanged.
+pub fn recover(document: &Path, json: String) -> String {
+    let recovered = match replay(document, &json) {
+        Ok(Some(recovered)) => recovered,
+        Ok(None) => return json,
+        Err(e) => {
+            log::warn!("Not replaying edits to {}: {}", document.display(), e);
+            return json;
+        }
+    };
+    log::info!("Recovered unsaved edits to {}", document.display());
+    match docstore::write_path(document, recovered.clone()) {
+        Ok(()) => clear(document),
+        Err(e) => log::warn!(
+            "Failed to save recovered edits to {}: {}",
+            document.display(),
+            e
+        ),
+    }
+    recovered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(kind: ChangeKind, id: &str, x: i64) -> ShapeChange {
+        ShapeChange {
+            kind,
+            shape_id: id.to_string(),
+            shape: (kind != ChangeKind::Deleted)
+                .then(|| json!({ "id": id, "type": "rectangle", "x": x })),
+        }
+    }
+
+    #[test]
+    fn logged_edits_are_replayed_over_the_last_save() {
+        let dir = std::env::temp_dir().join(format!("napkin-wal-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let document = dir.join("board.napkin");
+        let saved = json!({
+            "shapes": [
+                { "id": "a", "type": "rectangle", "x": 0 },
+                { "id": "b", "type": "rectangle", "x": 0 },
+            ]
+        });
+        docstore::write_path(&document, saved.to_string()).unwrap();
+        assert_eq!(recover(&document, saved.to_string()), saved.to_string());
+
+        let edit = |tab: &str, index: usize, changes: Vec<ShapeChange>| {
+            append(&document, tab, Some(index), changes).unwrap()
+        };
+        edit("t1", 0, vec![change(ChangeKind::Updated, "a", 5)]);
+        edit("t1", 0, vec![]);
+        edit(
+            "t1",
+            0,
+            vec![
+                change(ChangeKind::Deleted, "b", 0),
+                change(ChangeKind::Created, "c", 9),
+            ],
+        );
+        edit("t2", 1, vec![change(ChangeKind::Created, "d", 1)]);
+        // The last line was cut short by the crash
+        let mut log = std::fs::OpenOptions::new()
+            .append(true)
+            .open(log_path(&document))
+            .unwrap();
+        log.write_all(b"{\"tab\":0,\"chan").unwrap();
+
+        let recovered: Value =
+            serde_json::from_str(&recover(&document, saved.to_string())).unwrap();
+        let ids = |tab: usize| -> Vec<(String, i64)> {
+            recovered["documents"][tab]["shapes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|s| {
+                    (
+                        s["id"].as_str().unwrap().to_string(),
+                        s["x"].as_i64().unwrap(),
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(ids(0), [("a".to_string(), 5), ("c".to_string(), 9)]);
+        assert_eq!(ids(1), [("d".to_string(), 1)]);
+        // The tab saved without an id took the logged one
+        assert_eq!(recovered["documents"][0]["metadata"]["tabId"], "t1");
+        assert_eq!(recovered["documents"][1]["metadata"]["tabId"], "t2");
+
+        // Saved with the edits, and the log is gone
+        assert!(!log_path(&document).exists());
+        let reopened = docstore::read_path(&document).unwrap();
+        let reopened: Value = serde_json::from_str(&reopened).unwrap();
+        assert_eq!(reopened, recovered);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn edits_follow_their_tab_after_a_reorder() {
+        let dir = std::env::temp_dir().join(format!("napkin-wal-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let document = dir.join("board.napkin");
+        let saved = json!({
+            "type": "collection",
+            "documents": [
+                { "shapes": [{ "id": "a", "x": 0 }], "metadata": { "tabId": "first" } },
+                { "shapes": [{ "id": "b", "x": 0 }], "metadata": { "tabId": "second" } },
+            ],
+        });
+        docstore::write_path(&document, saved.to_string()).unwrap();
+
+        // The tabs were swapped, then a new tab opened in front of them
+        append(
+            &document,
+            "second",
+            Some(0),
+            vec![change(ChangeKind::Updated, "b", 2)],
+        )
+        .unwrap();
+        append(
+            &document,
+            "first",
+            Some(1),
+            vec![change(ChangeKind::Updated, "a", 1)],
+        )
+        .unwrap();
+        append(
+            &document,
+            "third",
+            Some(0),
+            vec![change(ChangeKind::Created, "c", 3)],
+        )
+        .unwrap();
+
+        let recovered: Value =
+            serde_json::from_str(&recover(&document, saved.to_string())).unwrap();
+        let tab = |i: usize| {
+            let document = &recovered["documents"][i];
+            (
+                document["metadata"]["tabId"].as_str().unwrap().to_string(),
+                document["shapes"][0]["x"].as_i64().unwrap(),
+            )
+        };
+        assert_eq!(tab(0), ("first".to_string(), 1));
+        assert_eq!(tab(1), ("second".to_string(), 2));
+        assert_eq!(tab(2), ("third".to_string(), 3));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
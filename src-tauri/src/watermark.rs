@@ -0,0 +1,381 @@
+//! Watermarks and classification banners on exports.
+//!
+//! Some organisations require every shared diagram to carry a marking such
+//! as "CONFIDENTIAL — internal"; others just want a logo in a corner of
+//! boards shared outside the team. The `exportWatermark` setting is the
+//! default for every export; each export can pass its own. The native
+//! writers draw it themselves (`export.rs`, `pdf.rs`, `render.rs`); the
+//! webview's PNG and SVG exporters ask for `export_watermark_svg` and
+//! composite the overlay.
+//!
+//! A watermark with an `image` (a PNG or JPEG `data:` URL) draws the image
+//! where the text would go, as tall as two lines of it.
+
+use image::{DynamicImage, GenericImageView};
+use serde::{Deserialize, Serialize};
+
+use crate::export::{escape, fmt};
+use crate::photo;
+use crate::settings;
+use crate::text_metrics;
+
+const FONT_FAMILY: &str = "Helvetica";
+/// Bold text runs a little wider than `text_metrics` assumes.
+const BOLD_FACTOR: f64 = 1.08;
+/// An image watermark's height, in multiples of the font size.
+const IMAGE_HEIGHT: f64 = 2.0;
+/// The largest image accepted, as a data URL.
+const MAX_IMAGE_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Placement {
+    /// A banner across the top of the page.
+    #[default]
+    Top,
+    /// A banner across the bottom of the page.
+    Bottom,
+    /// Large text in the middle of the page.
+    Center,
+    /// Large text corner to corner.
+    Diagonal,
+    /// A small stamp in one corner.
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Watermark {
+    pub enabled: bool,
+    pub text: String,
+    pub placement: Placement,
+    /// 0 (invisible) to 1 (opaque).
+    pub opacity: f64,
+    /// Banner background, or the text itself for other placements.
+    pub color: String,
+    /// A logo as a PNG or JPEG `data:` URL, drawn instead of the text.
+    pub image: Option<String>,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            text: "CONFIDENTIAL — internal".to_string(),
+            placement: Placement::Top,
+            opacity: 0.85,
+            color: "#c62828".to_string(),
+            image: None,
+        }
+    }
+}
+
+/// Where the watermark goes on a page, in page units with y pointing down.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    /// Banner background as (x, y, width, height), for banner placements.
+    pub band: Option<(f64, f64, f64, f64)>,
+    /// Center of the text.
+    pub center: (f64, f64),
+    pub font_size: f64,
+    /// Clockwise rotation of the text in degrees.
+    pub angle: f64,
+    /// Estimated width of the text.
+    pub text_width: f64,
+}
+
+impl Watermark {
+    /// Whether there is anything to draw.
+    pub fn is_visible(&self) -> bool {
+        self.enabled && (self.image.is_some() || !self.text.trim().is_empty()) && self.opacity > 0.0
+    }
+
+    /// The image, when the watermark has one that decodes.
+    pub fn decoded_image(&self) -> Option<DynamicImage> {
+        photo::decode_data_url(self.image.as_deref()?).ok()
+    }
+
+    /// An image box's height for text of `font_size`.
+    pub fn image_height(font_size: f64) -> f64 {
+        font_size * IMAGE_HEIGHT
+    }
+
+    pub fn opacity(&self) -> f64 {
+        self.opacity.clamp(0.0, 1.0)
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.trim()
+    }
+
+    /// Text color: white on a banner, the watermark color otherwise.
+    pub fn text_color(&self) -> &str {
+        match self.placement {
+            Placement::Top | Placement::Bottom => "#ffffff",
+            _ => &self.color,
+        }
+    }
+
+    /// Lay the watermark out on the page at (`x`, `y`) sized `w` x `h`. An
+    /// image is laid out like a line of text `IMAGE_HEIGHT` times as tall;
+    /// `text_width` is then the image's width.
+    pub fn layout(&self, x: f64, y: f64, w: f64, h: f64) -> Layout {
+        let image_size = self.decoded_image().map(|image| image.dimensions());
+        let (unit_width, unit_height) = match image_size {
+            Some((iw, ih)) => (IMAGE_HEIGHT * iw as f64 / ih.max(1) as f64, IMAGE_HEIGHT),
+            None => (
+                text_metrics::line_width(self.text(), 1.0, FONT_FAMILY) * BOLD_FACTOR,
+                1.0,
+            ),
+        };
+        let unit_width = unit_width.max(f64::MIN_POSITIVE);
+        let short_side = w.min(h);
+        let fit = |target: f64, room: f64| target.min(room / unit_width);
+        let (band, center, font_size, angle) = match self.placement {
+            Placement::Top | Placement::Bottom => {
+                let font_size = fit((short_side * 0.035).clamp(10.0, 36.0), w * 0.9);
+                let band_h = font_size * 1.8;
+                let band_y = match self.placement {
+                    Placement::Top => y,
+                    _ => y + h - band_h,
+                };
+                (
+                    Some((x, band_y, w, band_h)),
+                    (x + w / 2.0, band_y + band_h / 2.0),
+                    font_size,
+                    0.0,
+                )
+            }
+            Placement::Center => (
+                None,
+                (x + w / 2.0, y + h / 2.0),
+                fit(short_side * 0.12, w * 0.9),
+                0.0,
+            ),
+            Placement::Diagonal => (
+                None,
+                (x + w / 2.0, y + h / 2.0),
+                fit(short_side * 0.2, w.hypot(h) * 0.8),
+                -(h / w.max(f64::MIN_POSITIVE)).atan().to_degrees(),
+            ),
+            corner => {
+                let font_size = fit((short_side * 0.03).clamp(9.0, 24.0), w * 0.45);
+                let (half_w, half_h) =
+                    (unit_width * font_size / 2.0, unit_height * font_size / 2.0);
+                // A line's height from the edges
+                let margin = font_size;
+                let cx = match corner {
+                    Placement::TopLeft | Placement::BottomLeft => x + margin + half_w,
+                    _ => x + w - margin - half_w,
+                };
+                let cy = match corner {
+                    Placement::TopLeft | Placement::TopRight => y + margin + half_h,
+                    _ => y + h - margin - half_h,
+                };
+                (None, (cx, cy), font_size, 0.0)
+            }
+        };
+        Layout {
+            band,
+            center,
+            font_size,
+            angle,
+            text_width: unit_width * font_size,
+        }
+    }
+
+    /// SVG markup drawing the watermark over the area at (`x`, `y`) sized
+    /// `w` x `h`; empty when there is nothing to draw.
+    pub fn svg(&self, x: f64, y: f64, w: f64, h: f64) -> String {
+        if !self.is_visible() {
+            return String::new();
+        }
+        let layout = self.layout(x, y, w, h);
+        let mut out = format!(
+            "<g class=\"napkin-watermark\" opacity=\"{}\" pointer-events=\"none\">",
+            fmt(self.opacity())
+        );
+        if let Some((bx, by, bw, bh)) = layout.band {
+            out.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                fmt(bx),
+                fmt(by),
+                fmt(bw),
+                fmt(bh),
+                escape(&self.color)
+            ));
+        }
+        let (cx, cy) = layout.center;
+        let rotate = if layout.angle != 0.0 {
+            format!(
+                " transform=\"rotate({} {} {})\"",
+                fmt(layout.angle),
+                fmt(cx),
+                fmt(cy)
+            )
+        } else {
+            String::new()
+        };
+        if let Some(image) = self
+            .image
+            .as_deref()
+            .filter(|_| self.decoded_image().is_some())
+        {
+            let image_h = Self::image_height(layout.font_size);
+            out.push_str(&format!(
+                "<image href=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\"{}/></g>\n",
+                escape(image),
+                fmt(cx - layout.text_width / 2.0),
+                fmt(cy - image_h / 2.0),
+                fmt(layout.text_width),
+                fmt(image_h),
+                rotate
+            ));
+            return out;
+        }
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"Helvetica, Arial, sans-serif\" font-weight=\"bold\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"{}\"{}>{}</text></g>\n",
+            fmt(cx),
+            fmt(cy),
+            fmt(layout.font_size),
+            escape(self.text_color()),
+            rotate,
+            escape(self.text())
+        ));
+        out
+    }
+}
+
+pub fn validate(watermark: &Watermark) -> Result<(), String> {
+    let Some(image) = &watermark.image else {
+        return Ok(());
+    };
+    if image.len() > MAX_IMAGE_BYTES {
+        return Err("The watermark image must be under 1 MB".to_string());
+    }
+    photo::decode_data_url(image)
+        .map(|_| ())
+        .map_err(|e| format!("Watermark image: {}", e))
+}
+
+/// The watermark for an export: `requested` when the export names one,
+/// otherwise the `exportWatermark` setting.
+pub fn resolve(app: &tauri::AppHandle, requested: Option<Watermark>) -> Option<Watermark> {
+    let watermark = requested.unwrap_or_else(|| settings::current(app).export_watermark);
+    watermark.is_visible().then_some(watermark)
+}
+
+// --- Tauri command ---
+
+/// A standalone `width` x `height` SVG holding just the watermark, for the
+/// webview's PNG and SVG exporters to draw over the board; `None` when no
+/// watermark applies.
+#[tauri::command]
+pub fn export_watermark_svg(
+    width: f64,
+    height: f64,
+    watermark: Option<Watermark>,
+    app: tauri::AppHandle,
+) -> Option<String> {
+    let watermark = resolve(&app, watermark)?;
+    Some(format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">{}</svg>",
+        watermark.svg(0.0, 0.0, width, height),
+        w = fmt(width),
+        h = fmt(height),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watermark(placement: Placement) -> Watermark {
+        Watermark {
+            enabled: true,
+            placement,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn banners_span_the_page_edge() {
+        let top = watermark(Placement::Top).layout(0.0, 0.0, 800.0, 600.0);
+        let (_, by, bw, bh) = top.band.unwrap();
+        assert_eq!((by, bw), (0.0, 800.0));
+        assert_eq!(top.center, (400.0, bh / 2.0));
+        assert_eq!(top.angle, 0.0);
+
+        let bottom = watermark(Placement::Bottom).layout(10.0, 20.0, 800.0, 600.0);
+        let (bx, by, _, bh) = bottom.band.unwrap();
+        assert_eq!(bx, 10.0);
+        assert_eq!(by + bh, 620.0);
+    }
+
+    #[test]
+    fn text_is_shrunk_to_fit_narrow_pages() {
+        for placement in [
+            Placement::Top,
+            Placement::Bottom,
+            Placement::Center,
+            Placement::Diagonal,
+        ] {
+            let layout = watermark(placement).layout(0.0, 0.0, 120.0, 900.0);
+            assert!(layout.text_width <= 120.0 * 0.9 + 1e-9 || placement == Placement::Diagonal);
+            assert!(layout.text_width <= 120f64.hypot(900.0) * 0.8 + 1e-9);
+        }
+        let diagonal = watermark(Placement::Diagonal).layout(0.0, 0.0, 400.0, 400.0);
+        assert!((diagonal.angle + 45.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn svg_overlay_is_escaped_and_skipped_when_disabled() {
+        let mut wm = watermark(Placement::Center);
+        wm.text = "R&D <only>".to_string();
+        wm.opacity = 0.3;
+        let svg = wm.svg(0.0, 0.0, 500.0, 300.0);
+        assert!(svg.contains("opacity=\"0.3\""));
+        assert!(svg.contains(">R&amp;D &lt;only&gt;</text>"));
+        assert!(svg.contains("fill=\"#c62828\""));
+        assert!(!svg.contains("<rect"));
+
+        assert!(watermark(Placement::Top)
+            .svg(0.0, 0.0, 500.0, 300.0)
+            .contains("<rect"));
+        assert!(Watermark::default().svg(0.0, 0.0, 500.0, 300.0).is_empty());
+        wm.text = "  ".to_string();
+        assert!(!wm.is_visible());
+    }
+
+    #[test]
+    fn corner_stamps_can_be_images() {
+        let stamp = watermark(Placement::BottomRight).layout(0.0, 0.0, 800.0, 600.0);
+        assert!(stamp.band.is_none());
+        let right = stamp.center.0 + stamp.text_width / 2.0;
+        assert!((right - (800.0 - stamp.font_size)).abs() < 1e-9);
+        assert!(stamp.center.1 > 500.0);
+
+        // A 40 x 20 logo, drawn twice the font size tall and so four times as wide
+        let logo = DynamicImage::new_rgba8(40, 20);
+        let mut wm = watermark(Placement::TopLeft);
+        wm.image = Some(photo::encode_png_data_url(&logo).unwrap());
+        assert!(validate(&wm).is_ok());
+        let layout = wm.layout(0.0, 0.0, 800.0, 600.0);
+        assert!((layout.text_width - layout.font_size * 4.0).abs() < 1e-9);
+        assert_eq!(
+            layout.center,
+            (layout.font_size * 3.0, layout.font_size * 2.0)
+        );
+        let svg = wm.svg(0.0, 0.0, 800.0, 600.0);
+        assert!(svg.contains("<image href=\"data:image/png;base64,"));
+        assert!(!svg.contains("<text"));
+
+        wm.text = String::new();
+        assert!(wm.is_visible());
+        wm.image = Some("data:image/png;base64,AAAA".to_string());
+        assert!(validate(&wm).is_err());
+    }
+}
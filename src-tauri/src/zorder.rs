@@ -0,0 +1,196 @@
+//! Stacking order of a board's shapes.
+//!
+//! `get_shape_order` lists every shape from bottom to top, and
+//! `set_shape_order` restacks the whole board from such a list in one undo
+//! step, so an agent arranging many layers does not have to work it out with
+//! repeated `bring_forward`/`send_backward` calls. The list is checked here
+//! (every shape once, nothing else) before the webview applies it with
+//! `apply_shape_order`.
+
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+use crate::api::{bridge_tool_call, SharedApiState};
+use crate::resources;
+
+/// Longest text shown for a shape in `get_shape_order`.
+const LABEL_CHARS: usize = 40;
+
+/// One entry per shape, bottom first: id, type and the start of its text.
+fn order_entries(shapes: &[Value]) -> Vec<Value> {
+    shapes
+        .iter()
+        .filter_map(|shape| {
+            let mut entry = json!({
+                "id": shape.get("id")?.as_str()?,
+                "type": shape.get("type").and_then(Value::as_str).unwrap_or("unknown"),
+            });
+            let text = shape.get("text").and_then(Value::as_str).map(str::trim);
+            if let Some(text) = text.filter(|t| !t.is_empty()) {
+                let mut label: String = text.chars().take(LABEL_CHARS).collect();
+                if text.chars().count() > LABEL_CHARS {
+                    label.push('…');
+                }
+                entry["text"] = json!(label);
+            }
+            Some(entry)
+        })
+        .collect()
+}
+
+/// Check that `ids` lists each of `current` exactly once.
+pub fn validate_order(current: &[&str], ids: &[String]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    let duplicates: Vec<&str> = ids
+        .iter()
+        .filter(|id| !seen.insert(id.as_str()))
+        .map(String::as_str)
+        .collect();
+    if !duplicates.is_empty() {
+        return Err(format!(
+            "Ids listed more than once: {}",
+            duplicates.join(", ")
+        ));
+    }
+    let unknown: Vec<&str> = ids
+        .iter()
+        .map(String::as_str)
+        .filter(|id| !current.contains(id))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!("Shapes not found: {}", unknown.join(", ")));
+    }
+    let missing: Vec<&str> = current
+        .iter()
+        .copied()
+        .filter(|id| !seen.contains(id))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "The order must list every shape on the board; missing: {}",
+            missing.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+pub fn zorder_tools() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "get_shape_order",
+            "description": "List every shape on a board in stacking order, bottom to top (later shapes are drawn over earlier ones), with each shape's id, type and the start of its text.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "tabId": { "type": "string", "description": "Tab to read (default: the tab agents are working on)" }
+                },
+                "additionalProperties": false
+            }
+        }),
+        json!({
+            "name": "set_shape_order",
+            "description": "Restack the whole board at once: pass every shape id, bottom to top, e.g. a get_shape_order result rearranged. Applied as one change (one undo step). Fails without changing anything if an id is missing, unknown or repeated. Prefer it to many bring_forward/send_backward calls.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "ids": { "type": "array", "items": { "type": "string" }, "description": "Every shape id on the board, bottom to top" }
+                },
+                "required": ["ids"],
+                "additionalProperties": false
+            }
+        }),
+    ]
+}
+
+pub async fn call_zorder_tool(
+    state: &SharedApiState,
+    tool_name: &str,
+    arguments: &Value,
+) -> Option<Result<Value, String>> {
+    match tool_name {
+        "get_shape_order" => Some(get_shape_order(state, arguments).await),
+        "set_shape_order" => Some(set_shape_order(state, arguments).await),
+        _ => None,
+    }
+}
+
+fn board_shapes(canvas: &Value) -> Vec<Value> {
+    canvas
+        .get("shapes")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+}
+
+async fn get_shape_order(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let tab_id = arguments.get("tabId").and_then(Value::as_str);
+    let canvas = resources::canvas(state, tab_id).await?;
+    let order = order_entries(&board_shapes(&canvas));
+    Ok(json!({ "count": order.len(), "order": order }))
+}
+
+async fn set_shape_order(state: &SharedApiState, arguments: &Value) -> Result<Value, String> {
+    let ids: Vec<String> =
+        serde_json::from_value(arguments.get("ids").cloned().unwrap_or(Value::Null))
+            .map_err(|_| "ids must be an array of shape ids".to_string())?;
+    let shapes = board_shapes(&resources::canvas(state, None).await?);
+    let current: Vec<&str> = shapes
+        .iter()
+        .filter_map(|s| s.get("id").and_then(Value::as_str))
+        .collect();
+    validate_order(&current, &ids)?;
+    if current.iter().copied().eq(ids.iter().map(String::as_str)) {
+        return Ok(json!({ "count": ids.len(), "changed": false }));
+    }
+    let result = bridge_tool_call(state, "apply_shape_order", json!({ "ids": ids })).await?;
+    if let Some(error) = result.get("error").and_then(Value::as_str) {
+        return Err(error.to_string());
+    }
+    Ok(json!({ "count": ids.len(), "changed": true }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn order_must_list_every_shape_once() {
+        let current = ["a", "b", "c"];
+        assert!(validate_order(&current, &ids(&["c", "a", "b"])).is_ok());
+        assert_eq!(
+            validate_order(&current, &ids(&["a", "b", "a", "c"])).unwrap_err(),
+            "Ids listed more than once: a"
+        );
+        assert_eq!(
+            validate_order(&current, &ids(&["a", "b", "c", "x"])).unwrap_err(),
+            "Shapes not found: x"
+        );
+        assert_eq!(
+            validate_order(&current, &ids(&["b"])).unwrap_err(),
+            "The order must list every shape on the board; missing: a, c"
+        );
+        assert!(validate_order(&[], &[]).is_ok());
+    }
+
+    #[test]
+    fn entries_keep_stacking_order_and_shorten_text() {
+        let long = "x".repeat(LABEL_CHARS + 5);
+        let shapes = vec![
+            json!({ "id": "a", "type": "rectangle", "text": "  " }),
+            json!({ "id": "b", "type": "text", "text": long }),
+            json!({ "type": "ellipse" }),
+        ];
+        let entries = order_entries(&shapes);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], json!({ "id": "a", "type": "rectangle" }));
+        assert_eq!(entries[1]["id"], "b");
+        assert_eq!(
+            entries[1]["text"].as_str().unwrap().chars().count(),
+            LABEL_CHARS + 1
+        );
+    }
+}
@@ -1,14 +1,23 @@
 import { vi } from 'vitest';
 
 // Mock Tauri APIs before any imports that use them
-vi.mock('@tauri-apps/api/event', () => ({ listen: vi.fn() }));
-vi.mock('@tauri-apps/api/core', () => ({ invoke: vi.fn() }));
+vi.mock('@tauri-apps/api/core', () => ({
+  invoke: vi.fn().mockResolvedValue(undefined),
+  Channel: class {
+    onmessage: (message: unknown) => void = () => {};
+  },
+}));
 vi.mock('$lib/shapes/image', () => ({
   createImageFromURL: vi.fn().mockRejectedValue(new Error('not in test')),
+  blobToDataURL: vi.fn().mockResolvedValue('data:image/png;base64,iVBORw0KGgo='),
+}));
+vi.mock('$lib/export/png', () => ({
+  renderPNG: vi.fn().mockResolvedValue({ blob: new Blob(), width: 240, height: 120 }),
 }));
 
 import { get } from 'svelte/store';
-import { handleToolCall, getMcpActiveTabId, resetMcpState } from './handler';
+import { invoke } from '@tauri-apps/api/core';
+import { handleToolCall, getMcpActiveTabId, resetMcpState, diffShapes, initApiHandler, implementedTools } from './handler';
 import { tabStore, createTabSilent, getTabCanvasState } from '$lib/state/tabStore';
 import { canvasStore, clearCanvas, type CanvasState } from '$lib/state/canvasStore';
 import { historyManager } from '$lib/state/history';
@@ -103,6 +112,31 @@ describe('MCP handler — session isolation', () => {
     expect(uiState.shapes.has(shape.id)).toBe(false);
   });
 
+  it('edits to the MCP cursor tab are reported for resource notifications', async () => {
+    const { id: mcpTabId } = await handleToolCall('create_tab', { title: 'MCP' });
+    vi.mocked(invoke).mockClear();
+    await handleToolCall('create_shape', { type: 'rectangle', x: 0, y: 0 });
+
+    expect(invoke).toHaveBeenCalledWith(
+      'api_notify_change',
+      expect.objectContaining({ tabId: mcpTabId, mcpActive: true }),
+    );
+  });
+
+  it('edits to the MCP cursor tab are streamed as shape changes', async () => {
+    const { id: mcpTabId } = await handleToolCall('create_tab', { title: 'MCP' });
+    vi.mocked(invoke).mockClear();
+    const shape = await handleToolCall('create_shape', { type: 'rectangle', x: 0, y: 0 });
+
+    expect(invoke).toHaveBeenCalledWith('api_notify_change', {
+      tabId: mcpTabId,
+      mcpActive: true,
+      changes: [expect.objectContaining({ kind: 'created', shapeId: shape.id })],
+      document: null,
+      tabIndex: 1,
+    });
+  });
+
   it('list_shapes reads from MCP cursor tab', async () => {
     const { id: mcpTabId } = await handleToolCall('create_tab', { title: 'MCP' });
     await handleToolCall('create_shape', { type: 'ellipse', x: 10, y: 10 });
@@ -121,6 +155,45 @@ describe('MCP handler — session isolation', () => {
     expect(result.shapeCount).toBe(2);
   });
 
+  it('get_tab reads any tab without moving the MCP cursor', async () => {
+    const tabId = (await handleToolCall('create_tab', { title: 'Other' })).id;
+    await handleToolCall('create_shape', { type: 'rectangle', x: 0, y: 0 });
+    resetMcpState();
+
+    const result = await handleToolCall('get_tab', { tabId });
+    expect(result.shapeCount).toBe(1);
+    expect(getMcpActiveTabId()).toBeNull();
+    expect((await handleToolCall('get_tab', { tabId: 'no_such_tab' })).error).toBeDefined();
+  });
+
+  it('list_shapes filters by createdBy', async () => {
+    const agent = { name: 'bot', color: '#8e24aa', source: 'agent' };
+    await handleToolCall('create_shape', { type: 'rectangle', x: 0, y: 0, createdBy: agent });
+    await handleToolCall('create_shape', { type: 'ellipse', x: 50, y: 50 });
+
+    expect((await handleToolCall('list_shapes', { createdBy: 'bot' })).count).toBe(1);
+    expect((await handleToolCall('list_shapes', { createdBy: 'agent' })).shapes[0].createdBy).toEqual(agent);
+    expect((await handleToolCall('list_shapes', { createdBy: 'someone' })).count).toBe(0);
+  });
+
+  it('list_shapes pages and filters by region and text', async () => {
+    await handleToolCall('create_shape', { type: 'rectangle', x: 0, y: 0, width: 10, height: 10, text: 'Login page' });
+    await handleToolCall('create_shape', { type: 'rectangle', x: 500, y: 500, width: 10, height: 10, text: 'Logout' });
+    await handleToolCall('create_shape', { type: 'ellipse', x: 20, y: 20, width: 10, height: 10 });
+
+    const first = await handleToolCall('list_shapes', { limit: 2 });
+    expect(first).toMatchObject({ count: 2, total: 3, nextOffset: 2 });
+    const rest = await handleToolCall('list_shapes', { limit: 2, offset: first.nextOffset });
+    expect(rest).toMatchObject({ count: 1, total: 3 });
+    expect(rest.nextOffset).toBeUndefined();
+
+    const near = await handleToolCall('list_shapes', { region: { x: 0, y: 0, width: 100, height: 100 } });
+    expect(near.total).toBe(2);
+    const log = await handleToolCall('list_shapes', { text: 'log' });
+    expect(log.shapes.map((s: any) => s.text)).toEqual(['Login page', 'Logout']);
+    expect((await handleToolCall('list_shapes', { region: { x: 0 } })).code).toBe('INVALID_ARGUMENT');
+  });
+
   // --- Fallback behavior ---
 
   it('with null cursor, operations go to UI active tab', async () => {
@@ -161,3 +234,135 @@ describe('MCP handler — session isolation', () => {
     expect(result.error).toBeDefined();
   });
 });
+
+describe('diffShapes', () => {
+  it('reports created, updated and deleted shapes', () => {
+    const kept = { id: 'kept' } as any;
+    const before = new Map<string, any>([['kept', kept], ['moved', { id: 'moved', x: 0 }], ['gone', { id: 'gone' }]]);
+    const after = new Map<string, any>([['kept', kept], ['moved', { id: 'moved', x: 10 }], ['new', { id: 'new' }]]);
+
+    expect(diffShapes(before, after)).toEqual([
+      { kind: 'updated', shapeId: 'moved', shape: { id: 'moved', x: 10 } },
+      { kind: 'created', shapeId: 'new', shape: { id: 'new' } },
+      { kind: 'deleted', shapeId: 'gone' },
+    ]);
+  });
+});
+
+describe('apply_partition', () => {
+  beforeEach(() => {
+    tabStore.set({
+      tabs: [{ id: 'board', title: 'Board', isDirty: false, canvasState: null }],
+      activeTabId: 'board',
+    });
+    canvasStore.set(makeDefaultCanvasState());
+    historyManager.clear();
+    resetMcpState();
+  });
+
+  it('moves shapes to new tabs and undoes the source change in one step', async () => {
+    const moved = await handleToolCall('create_shape', { type: 'rectangle', x: 0, y: 0 });
+    const kept = await handleToolCall('create_shape', { type: 'ellipse', x: 500, y: 0 });
+    canvasStore.update(s => ({ ...s, groups: new Map([['g1', { id: 'g1', shapeIds: [moved.id] }]]) }));
+    const card = { id: 'card', type: 'rectangle', x: 0, y: 0, width: 180, height: 60, link: { tabId: 'tab_part' } };
+    historyManager.clear();
+
+    const result = await handleToolCall('apply_partition', {
+      tabId: 'board',
+      plan: {
+        tabs: [{ id: 'tab_part', title: 'Part', shapes: [moved], groups: [{ id: 'g1', shapeIds: [moved.id] }], viewport: { x: -40, y: -40, zoom: 1 } }],
+        remove: [moved.id],
+        add: [card],
+        removeGroups: ['g1'],
+        cutConnectors: 0,
+      },
+    });
+
+    expect(result.tabIds).toEqual(['tab_part']);
+    const part = getTabCanvasState('tab_part')!;
+    expect([...part.shapes.keys()]).toEqual([moved.id]);
+    expect(part.groups.has('g1')).toBe(true);
+    expect(get(tabStore).activeTabId).toBe('board');
+
+    const board = get(canvasStore);
+    expect([...board.shapes.keys()]).toEqual([kept.id, 'card']);
+    expect(board.groups.has('g1')).toBe(false);
+
+    historyManager.undo();
+    expect(get(canvasStore).shapes.has(moved.id)).toBe(true);
+    expect(get(canvasStore).shapes.has('card')).toBe(false);
+  });
+});
+
+describe('apply_shape_order', () => {
+  beforeEach(() => {
+    tabStore.set({
+      tabs: [{ id: 'board', title: 'Board', isDirty: false, canvasState: null }],
+      activeTabId: 'board',
+    });
+    canvasStore.set(makeDefaultCanvasState());
+    historyManager.clear();
+    resetMcpState();
+  });
+
+  it('restacks every shape in one undo step', async () => {
+    const a = await handleToolCall('create_shape', { type: 'rectangle', x: 0, y: 0 });
+    const b = await handleToolCall('create_shape', { type: 'ellipse', x: 50, y: 0 });
+    const c = await handleToolCall('create_shape', { type: 'text', x: 100, y: 0, text: 'c' });
+    const order = () => get(canvasStore).shapesArray.map(s => s.id);
+
+    const result = await handleToolCall('apply_shape_order', { ids: [c.id, a.id, b.id] });
+    expect(result).toEqual({ success: true, count: 3 });
+    expect(order()).toEqual([c.id, a.id, b.id]);
+
+    historyManager.undo();
+    expect(order()).toEqual([a.id, b.id, c.id]);
+
+    const partial = await handleToolCall('apply_shape_order', { ids: [b.id, a.id] });
+    expect(partial.error).toBeDefined();
+    expect(order()).toEqual([a.id, b.id, c.id]);
+  });
+});
+
+describe('export_png', () => {
+  beforeEach(() => {
+    tabStore.set({
+      tabs: [{ id: 'board', title: 'Board', isDirty: false, canvasState: null }],
+      activeTabId: 'board',
+    });
+    canvasStore.set(makeDefaultCanvasState());
+    resetMcpState();
+  });
+
+  it('returns the rendered board as base64 PNG data', async () => {
+    expect((await handleToolCall('export_png', {})).error).toBeDefined();
+
+    const a = await handleToolCall('create_shape', { type: 'rectangle', x: 0, y: 0 });
+    await handleToolCall('create_shape', { type: 'ellipse', x: 50, y: 0 });
+    const result = await handleToolCall('export_png', { shapeIds: [a.id] });
+    expect(result).toEqual({ data: 'iVBORw0KGgo=', mimeType: 'image/png', width: 240, height: 120, shapeCount: 1 });
+
+    const missing = await handleToolCall('export_png', { shapeIds: ['nope'] });
+    expect(missing.code).toBe('SHAPE_NOT_FOUND');
+  });
+});
+
+describe('screenshot_viewport', () => {
+  it('needs the board on screen', async () => {
+    const result = await handleToolCall('screenshot_viewport', {});
+    expect(result.error).toBe('The board is not on screen');
+  });
+});
+
+describe('bridge registration', () => {
+  it('hands over a channel and reports the implemented tools', async () => {
+    vi.mocked(invoke).mockClear();
+    await initApiHandler();
+
+    expect(invoke).toHaveBeenCalledWith('api_register_bridge', {
+      tools: implementedTools(),
+      onRequest: expect.objectContaining({ onmessage: expect.any(Function) }),
+    });
+    expect(implementedTools()).toEqual(expect.arrayContaining(['create_shape', 'get_tab', 'set_document_scale', 'apply_partition', 'apply_shape_order']));
+  });
+});
@@ -1,9 +1,10 @@
 /**
  * MCP tool request handler.
  *
- * Listens for "mcp-tool-request" Tauri events from the Rust MCP server,
- * dispatches to the appropriate handler, and sends the result back via
- * the "api_response" Tauri command.
+ * Receives tool requests from the Rust MCP server on a Tauri channel handed
+ * over in "api_register_bridge", dispatches to the appropriate handler, and
+ * sends the result back via the "api_response" Tauri command. After a reload
+ * the server re-sends every request the old page left unanswered.
  *
  * MCP session isolation: The MCP server maintains its own "active tab" cursor
  * (mcpActiveTabId) independent of the UI. MCP create_tab/switch_tab only move
@@ -18,15 +19,19 @@
 import { get } from 'svelte/store';
 import { canvasStore, generateShapeId } from '$lib/state/canvasStore';
 import type { Shape, Group } from '$lib/state/canvasStore';
-import { historyManager, AddShapeCommand, ModifyShapeCommand, DeleteShapeCommand, DeleteShapesCommand, BatchCommand, GroupShapesCommand, UngroupShapesCommand, SnapshotModifyCommand } from '$lib/state/history';
+import { historyManager, AddShapeCommand, ModifyShapeCommand, DeleteShapeCommand, DeleteShapesCommand, BatchCommand, GroupShapesCommand, UngroupShapesCommand, SnapshotModifyCommand, ReorderShapesCommand } from '$lib/state/history';
 import { tabStore, createTabSilent, snapshotActiveTab, renameTab, getTabCanvasState, updateTabCanvasState } from '$lib/state/tabStore';
-import { bringToFront, sendToBack, bringForward, sendBackward, updateShapes } from '$lib/state/canvasStore';
+import { bringToFront, sendToBack, bringForward, sendBackward, updateShapes, orderShapes } from '$lib/state/canvasStore';
 import { getShapeConnectionPoints, getBindingPoint, syncAllArrowBindings } from '$lib/utils/binding';
 import { gridLayout, forceDirectedLayout } from '$lib/utils/layout';
-import { createImageFromURL } from '$lib/shapes/image';
+import { createImageFromURL, blobToDataURL } from '$lib/shapes/image';
+import { renderPNG } from '$lib/export/png';
 import type { ShapeType, ConnectionPoint } from '$lib/types';
-import { listen } from '@tauri-apps/api/event';
-import { invoke } from '@tauri-apps/api/core';
+import { Channel, invoke } from '@tauri-apps/api/core';
+import { repairBindings } from '$lib/state/integrity';
+import { matchesAuthor } from '$lib/state/authorStore';
+import { getCurrentFilePath } from '$lib/state/fileStore';
+import { boundsIntersect, getShapeBounds } from '$lib/shapes/bounds';
 
 interface McpToolRequest {
   request_id: string;
@@ -34,6 +39,13 @@ interface McpToolRequest {
   arguments: any;
 }
 
+/** Kinds of failure, as `code` next to `error`; see src-tauri/src/tool_errors.rs. */
+export type ToolErrorCode = 'SHAPE_NOT_FOUND' | 'INVALID_ARGUMENT' | 'TIMEOUT' | 'CANCELLED' | 'CANVAS_LOCKED' | 'PERMISSION_DENIED' | 'INTERNAL';
+
+function toolError(code: ToolErrorCode, error: string): { error: string; code: ToolErrorCode } {
+  return { error, code };
+}
+
 // --- MCP session state ---
 
 /** MCP's independent tab cursor. null = fall back to UI's active tab. */
@@ -42,6 +54,9 @@ let mcpActiveTabId: string | null = null;
 /** Promise-based request queue — ensures MCP tool calls execute one at a time. */
 let mcpQueue: Promise<void> = Promise.resolve();
 
+/** Requests queued and not answered yet, so one re-sent by the server is not run twice. */
+let seenRequests = new Set<string>();
+
 /** Read-only access to MCP cursor for testing. */
 export function getMcpActiveTabId(): string | null {
   return mcpActiveTabId;
@@ -51,57 +66,161 @@ export function getMcpActiveTabId(): string | null {
 export function resetMcpState(): void {
   mcpActiveTabId = null;
   mcpQueue = Promise.resolve();
+  seenRequests = new Set();
 }
 
 export async function initApiHandler(): Promise<void> {
 
-  listen<McpToolRequest>('mcp-tool-request', async (event) => {
-    const { request_id, tool_name, arguments: args } = event.payload;
+  const channel = new Channel<McpToolRequest>();
+  channel.onmessage = (request) => {
+    const { request_id, tool_name, arguments: args } = request;
+    if (seenRequests.has(request_id)) return;
+    seenRequests.add(request_id);
 
     // Chain onto the queue — ensures sequential execution
     mcpQueue = mcpQueue.then(async () => {
       try {
         const result = await handleToolCall(tool_name, args);
-        await invoke('api_response', { requestId: request_id, result });
+        // Deletes can leave connectors bound to shapes that no longer exist
+        if (tool_name === 'delete_shape' || tool_name === 'batch_operations') {
+          await repairBindings().catch(err => console.error('[api] Binding repair failed:', err));
+        }
+        await invoke('api_response', { requestId: request_id, result, mcpTabId: mcpActiveTabId });
       } catch (error) {
         const message = error instanceof Error ? error.message : String(error);
         await invoke('api_response', {
           requestId: request_id,
-          result: { error: message },
+          result: toolError('INTERNAL', message),
+          mcpTabId: mcpActiveTabId,
         });
+      } finally {
+        seenRequests.delete(request_id);
       }
     });
-  });
+  };
+
+  // Hand over the channel, and tell the server which tools we answer
+  await invoke('api_register_bridge', { tools: implementedTools(), onRequest: channel }).catch(err =>
+    console.error('[api] Bridge registration failed:', err),
+  );
+}
+
+/** Tools answered here. Reported to the server so it can hide any it would bridge but we lack. */
+const TOOL_HANDLERS = new Map<string, (args: any) => any>([
+  ['get_canvas', () => handleGetCanvas()],
+  ['list_shapes', handleListShapes],
+  ['get_shape', handleGetShape],
+  ['create_shape', handleCreateShape],
+  ['create_image', handleCreateImage],
+  ['update_shape', handleUpdateShape],
+  ['delete_shape', handleDeleteShape],
+  ['create_connection', handleCreateConnection],
+  ['set_viewport', handleSetViewport],
+  ['select_shapes', handleSetSelection],
+  ['list_tabs', () => handleListTabs()],
+  ['get_tab', handleGetTab],
+  ['create_tab', handleCreateTab],
+  ['switch_tab', handleSwitchTab],
+  ['rename_tab', handleRenameTab],
+  ['group_shapes', handleGroupShapes],
+  ['ungroup', handleUngroup],
+  ['clear_canvas', () => handleClearCanvas()],
+  ['batch_operations', handleBatchOperations],
+  ['bring_to_front', handleBringToFront],
+  ['send_to_back', handleSendToBack],
+  ['bring_forward', handleBringForward],
+  ['send_backward', handleSendBackward],
+  ['reorganize', handleReorganize],
+  ['set_snap_settings', handleSetSnapSettings],
+  ['set_document_scale', handleSetDocumentScale],
+  ['apply_partition', handleApplyPartition],
+  ['apply_shape_order', handleApplyShapeOrder],
+  ['export_png', handleExportPng],
+  ['screenshot_viewport', handleScreenshotViewport],
+]);
+
+export function implementedTools(): string[] {
+  return [...TOOL_HANDLERS.keys()];
 }
 
 export async function handleToolCall(toolName: string, args: any): Promise<any> {
-  switch (toolName) {
-    case 'get_canvas': return handleGetCanvas();
-    case 'list_shapes': return handleListShapes(args);
-    case 'get_shape': return handleGetShape(args);
-    case 'create_shape': return handleCreateShape(args);
-    case 'create_image': return handleCreateImage(args);
-    case 'update_shape': return handleUpdateShape(args);
-    case 'delete_shape': return handleDeleteShape(args);
-    case 'create_connection': return handleCreateConnection(args);
-    case 'set_viewport': return handleSetViewport(args);
-    case 'select_shapes': return handleSetSelection(args);
-    case 'list_tabs': return handleListTabs();
-    case 'create_tab': return handleCreateTab(args);
-    case 'switch_tab': return handleSwitchTab(args);
-    case 'rename_tab': return handleRenameTab(args);
-    case 'group_shapes': return handleGroupShapes(args);
-    case 'ungroup': return handleUngroup(args);
-    case 'clear_canvas': return handleClearCanvas();
-    case 'batch_operations': return handleBatchOperations(args);
-    case 'bring_to_front': return handleBringToFront(args);
-    case 'send_to_back': return handleSendToBack(args);
-    case 'bring_forward': return handleBringForward(args);
-    case 'send_backward': return handleSendBackward(args);
-    case 'reorganize': return handleReorganize(args);
-    case 'set_snap_settings': return handleSetSnapSettings(args);
-    default: return { error: `Unknown tool: ${toolName}` };
+  const handler = TOOL_HANDLERS.get(toolName);
+  if (!handler) return toolError('INVALID_ARGUMENT', `Unknown tool: ${toolName}`);
+  return handler(args);
+}
+
+// --- Change notifications ---
+
+/** Quiet period before an edit is reported, so a drag sends one notification. */
+const CHANGE_NOTIFY_DELAY_MS = 250;
+
+export interface ShapeChange {
+  kind: 'created' | 'updated' | 'deleted';
+  shapeId: string;
+  /** The new state; absent for deletions. */
+  shape?: any;
+}
+
+/**
+ * Shapes created, updated or deleted between two states. The store replaces
+ * a shape object whenever it changes, so identity is enough.
+ */
+export function diffShapes(before: Map<string, Shape>, after: Map<string, Shape>): ShapeChange[] {
+  const changes: ShapeChange[] = [];
+  for (const [id, shape] of after) {
+    const previous = before.get(id);
+    if (previous !== shape) {
+      changes.push({ kind: previous ? 'updated' : 'created', shapeId: id, shape: serializeShape(shape) });
+    }
+  }
+  for (const id of before.keys()) {
+    if (!after.has(id)) changes.push({ kind: 'deleted', shapeId: id });
   }
+  return changes;
+}
+
+/**
+ * Report an edit to `tabId`: it is recorded in the change journal under a new
+ * canvas revision, subscribed MCP clients get resources/updated, and every
+ * connected client gets the shape changes. Edits to a saved file are also
+ * logged beside it until the next save, for crash recovery.
+ */
+function notifyChange(tabId: string, changes: ShapeChange[]): void {
+  const { tabs, activeTabId } = get(tabStore);
+  const mcpActive = (mcpActiveTabId || activeTabId) === tabId;
+  const document = getCurrentFilePath();
+  const index = tabs.findIndex(t => t.id === tabId);
+  const tabIndex = index >= 0 ? index : null;
+  invoke('api_notify_change', { tabId, mcpActive, changes, document, tabIndex }).catch(() => {});
+}
+
+/**
+ * Report edits to the active tab as they settle. Edits MCP makes to other
+ * tabs are reported by executeOnTab.
+ */
+export function initChangeNotifications(): () => void {
+  let timer: ReturnType<typeof setTimeout> | null = null;
+  let primed = false;
+  let baseline = get(canvasStore).shapes;
+  let baselineTabId = get(tabStore).activeTabId;
+  return canvasStore.subscribe(() => {
+    // The first call is the current state, not an edit
+    if (!primed) {
+      primed = true;
+      return;
+    }
+    if (timer) clearTimeout(timer);
+    timer = setTimeout(() => {
+      timer = null;
+      const tabId = get(tabStore).activeTabId;
+      const shapes = get(canvasStore).shapes;
+      // Switching tabs swaps the whole canvas; that is not a shape change
+      const changes = tabId === baselineTabId ? diffShapes(baseline, shapes) : [];
+      baseline = shapes;
+      baselineTabId = tabId;
+      notifyChange(tabId, changes);
+    }, CHANGE_NOTIFY_DELAY_MS);
+  });
 }
 
 // --- Tab isolation helpers ---
@@ -116,7 +235,7 @@ function resolveCanvasState(): { canvasState: CanvasState; resolvedTabId: string
 
   const canvasState = getTabCanvasState(resolvedTabId);
   if (!canvasState) {
-    return { error: `Tab not found: ${resolvedTabId}` };
+    return toolError('INVALID_ARGUMENT', `Tab not found: ${resolvedTabId}`);
   }
   return { canvasState, resolvedTabId };
 }
@@ -141,11 +260,12 @@ function executeOnTab(
   // Non-active tab — operate on stored state directly
   const canvasState = getTabCanvasState(resolvedTabId);
   if (!canvasState) {
-    return { error: `Tab not found: ${resolvedTabId}` };
+    return toolError('INVALID_ARGUMENT', `Tab not found: ${resolvedTabId}`);
   }
 
   const { state: newState, result } = directFn(canvasState);
   updateTabCanvasState(resolvedTabId, newState);
+  notifyChange(resolvedTabId, diffShapes(canvasState.shapes, newState.shapes));
   return result;
 }
 
@@ -165,7 +285,7 @@ function serializeGroups(groups: Map<string, Group>): any[] {
   }));
 }
 
-function buildShapeFromParams(params: any): Shape {
+export function buildShapeFromParams(params: any): Shape {
   const id = generateShapeId();
   const type: ShapeType = params.type;
 
@@ -185,6 +305,8 @@ function buildShapeFromParams(params: any): Shape {
     text: params.text,
     textAlign: params.textAlign ?? 'center',
     verticalAlign: params.verticalAlign ?? 'middle',
+    autoFit: params.autoFit,
+    createdBy: params.createdBy,
   };
 
   switch (type) {
@@ -241,6 +363,12 @@ function buildShapeFromParams(params: any): Shape {
         endEndpoint: params.endEndpoint ?? { shape: 'arrow', size: 1 },
       } as Shape;
 
+    case 'freedraw':
+      return {
+        ...base,
+        points: Array.isArray(params.points) ? params.points : [],
+      } as Shape;
+
     default:
       return { ...base, width: params.width ?? 200, height: params.height ?? 150 } as Shape;
   }
@@ -251,7 +379,19 @@ function buildShapeFromParams(params: any): Shape {
 function handleGetCanvas(): any {
   const resolved = resolveCanvasState();
   if ('error' in resolved) return resolved;
-  const state = resolved.canvasState;
+  return serializeCanvas(resolved.canvasState);
+}
+
+/** Canvas of any tab, without moving the MCP cursor (for `napkin://tabs/` resources). */
+function handleGetTab(args: any): any {
+  if (!args.tabId) return toolError('INVALID_ARGUMENT', 'Missing required field: tabId');
+  snapshotActiveTab();
+  const state = getTabCanvasState(args.tabId);
+  if (!state) return toolError('INVALID_ARGUMENT', `Tab not found: ${args.tabId}`);
+  return serializeCanvas(state);
+}
+
+function serializeCanvas(state: CanvasState): any {
   return {
     shapes: state.shapesArray.map(serializeShape),
     viewport: state.viewport,
@@ -289,7 +429,31 @@ function handleListShapes(args: any): any {
   if (args?.type) {
     shapes = shapes.filter(s => s.type === args.type);
   }
-  return { shapes: shapes.map(serializeShape), count: shapes.length };
+  if (args?.createdBy) {
+    shapes = shapes.filter(s => matchesAuthor(s, args.createdBy));
+  }
+  if (args?.region !== undefined) {
+    const region = args.region;
+    const valid = region && ['x', 'y', 'width', 'height'].every(k => Number.isFinite(region[k]));
+    if (!valid) return toolError('INVALID_ARGUMENT', 'region needs numeric x, y, width and height');
+    shapes = shapes.filter(s => boundsIntersect(getShapeBounds(s), region));
+  }
+  if (args?.text) {
+    const needle = String(args.text).toLowerCase();
+    shapes = shapes.filter(s => typeof (s as any).text === 'string' && (s as any).text.toLowerCase().includes(needle));
+  }
+  const total = shapes.length;
+  const offset = args?.offset ?? 0;
+  const limit = args?.limit;
+  if (!Number.isInteger(offset) || offset < 0) return toolError('INVALID_ARGUMENT', 'offset must be a non-negative integer');
+  if (limit !== undefined && (!Number.isInteger(limit) || limit < 1)) {
+    return toolError('INVALID_ARGUMENT', 'limit must be a positive integer');
+  }
+  const end = limit === undefined ? total : Math.min(offset + limit, total);
+  const page = shapes.slice(offset, end);
+  const result: any = { shapes: page.map(serializeShape), count: page.length, total };
+  if (end < total) result.nextOffset = end;
+  return result;
 }
 
 function handleGetShape(args: any): any {
@@ -297,12 +461,12 @@ function handleGetShape(args: any): any {
   if ('error' in resolved) return resolved;
   const state = resolved.canvasState;
   const shape = state.shapes.get(args.id);
-  if (!shape) return { error: `Shape not found: ${args.id}` };
+  if (!shape) return toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`);
   return serializeShape(shape);
 }
 
 function handleCreateShape(args: any): any {
-  if (!args.type) return { error: 'Missing required field: type' };
+  if (!args.type) return toolError('INVALID_ARGUMENT', 'Missing required field: type');
   const shape = buildShapeFromParams(args);
   return executeOnTab(
     () => {
@@ -322,7 +486,7 @@ function handleCreateShape(args: any): any {
 
 async function handleCreateImage(args: any): Promise<any> {
   const { url, x, y, width, height } = args;
-  if (!url) return { error: 'Missing required field: url (data URL or http URL)' };
+  if (!url) return toolError('INVALID_ARGUMENT', 'Missing required field: url (data URL or http URL)');
 
   try {
     const shape = await createImageFromURL(url, x ?? 0, y ?? 0);
@@ -330,6 +494,7 @@ async function handleCreateImage(args: any): Promise<any> {
     // Allow explicit size override
     if (width !== undefined) shape.width = width;
     if (height !== undefined) shape.height = height;
+    if (args.createdBy) (shape as any).createdBy = args.createdBy;
 
     return executeOnTab(
       () => {
@@ -347,8 +512,88 @@ async function handleCreateImage(args: any): Promise<any> {
       }
     );
   } catch (e) {
-    return { error: `Failed to load image: ${e instanceof Error ? e.message : String(e)}` };
+    return toolError('INVALID_ARGUMENT', `Failed to load image: ${e instanceof Error ? e.message : String(e)}`);
+  }
+}
+
+async function handleExportPng(args: any): Promise<any> {
+  const resolved = resolveCanvasState();
+  if ('error' in resolved) return resolved;
+  const state = resolved.canvasState;
+
+  let shapes = state.shapesArray;
+  if (Array.isArray(args.shapeIds)) {
+    const missing = args.shapeIds.filter((id: string) => !state.shapes.has(id));
+    if (missing.length > 0) return toolError('SHAPE_NOT_FOUND', `Shape not found: ${missing.join(', ')}`);
+    const wanted = new Set<string>(args.shapeIds);
+    shapes = shapes.filter(s => wanted.has(s.id));
   }
+  if (shapes.length === 0) return toolError('INVALID_ARGUMENT', 'Nothing to render: the board is empty');
+
+  const { blob, width, height } = await renderPNG(shapes, {
+    scale: args.scale ?? 1,
+    padding: args.padding,
+    backgroundColor: args.backgroundColor,
+    maxDimension: args.maxDimension ?? 2048,
+  });
+  const dataUrl = await blobToDataURL(blob);
+  return {
+    data: dataUrl.slice(dataUrl.indexOf(',') + 1),
+    mimeType: 'image/png',
+    width,
+    height,
+    shapeCount: shapes.length,
+  };
+}
+
+/** The board as Canvas.svelte draws it on screen. */
+function boardCanvas(): HTMLCanvasElement | null {
+  return document.querySelector<HTMLCanvasElement>('canvas.canvas');
+}
+
+/**
+ * What the user is looking at: the on-screen canvas as drawn (pan, zoom,
+ * selection and all), scaled down to fit maxDimension.
+ */
+async function handleScreenshotViewport(args: any): Promise<any> {
+  const canvas = boardCanvas();
+  if (!canvas || canvas.width === 0 || canvas.height === 0) {
+    return toolError('INVALID_ARGUMENT', 'The board is not on screen');
+  }
+  const state = get(canvasStore);
+  const { x, y, zoom } = state.viewport;
+  const dpr = window.devicePixelRatio || 1;
+  // Screen = (canvas - viewport) * zoom, in CSS pixels
+  const visibleRegion = { x, y, width: canvas.width / dpr / zoom, height: canvas.height / dpr / zoom };
+
+  const maxDimension = args.maxDimension ?? 2048;
+  const fit = Math.min(1, maxDimension / canvas.width, maxDimension / canvas.height);
+  const width = Math.max(1, Math.round(canvas.width * fit));
+  const height = Math.max(1, Math.round(canvas.height * fit));
+  const shot = document.createElement('canvas');
+  shot.width = width;
+  shot.height = height;
+  const ctx = shot.getContext('2d');
+  if (!ctx) return toolError('INTERNAL', 'Failed to create canvas context');
+  // The board's background is CSS, not pixels
+  ctx.fillStyle = '#ffffff';
+  ctx.fillRect(0, 0, width, height);
+  ctx.drawImage(canvas, 0, 0, width, height);
+  const blob = await new Promise<Blob | null>(resolve => shot.toBlob(resolve, 'image/png'));
+  if (!blob) return toolError('INTERNAL', 'Failed to capture the viewport');
+
+  const dataUrl = await blobToDataURL(blob);
+  return {
+    data: dataUrl.slice(dataUrl.indexOf(',') + 1),
+    mimeType: 'image/png',
+    width,
+    height,
+    viewport: { x, y, zoom },
+    visibleRegion,
+    visibleShapeIds: state.shapesArray
+      .filter(s => boundsIntersect(getShapeBounds(s), visibleRegion))
+      .map(s => s.id),
+  };
 }
 
 function handleUpdateShape(args: any): any {
@@ -359,22 +604,26 @@ function handleUpdateShape(args: any): any {
     'fillColor', 'fillStyle', 'opacity', 'roughness',
     'rotation', 'text', 'textAlign', 'verticalAlign',
     'fontSize', 'fontFamily', 'stickyColor',
-    'routingMode', 'labelPosition',
+    'routingMode', 'labelPosition', 'autoFit',
   ];
   for (const key of allowed) {
     if (args[key] !== undefined) (updates as any)[key] = args[key];
   }
+  // Dangling bindings are cleared with null by repair_document
+  for (const key of ['bindStart', 'bindEnd']) {
+    if (args[key] === null) (updates as any)[key] = undefined;
+  }
 
   return executeOnTab(
     () => {
       const state = get(canvasStore);
-      if (!state.shapes.has(args.id)) return { error: `Shape not found: ${args.id}` };
+      if (!state.shapes.has(args.id)) return toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`);
       historyManager.execute(new ModifyShapeCommand(args.id, updates));
       return serializeShape(get(canvasStore).shapes.get(args.id)!);
     },
     (state) => {
       const shape = state.shapes.get(args.id);
-      if (!shape) return { state, result: { error: `Shape not found: ${args.id}` } };
+      if (!shape) return { state, result: toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`) };
       const updatedShape = { ...shape, ...updates, id: args.id } as Shape;
       const newShapes = new Map(state.shapes);
       newShapes.set(args.id, updatedShape);
@@ -389,12 +638,12 @@ function handleUpdateShape(args: any): any {
 function handleDeleteShape(args: any): any {
   return executeOnTab(
     () => {
-      if (!get(canvasStore).shapes.has(args.id)) return { error: `Shape not found: ${args.id}` };
+      if (!get(canvasStore).shapes.has(args.id)) return toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`);
       historyManager.execute(new DeleteShapeCommand(args.id));
       return { success: true, id: args.id };
     },
     (state) => {
-      if (!state.shapes.has(args.id)) return { state, result: { error: `Shape not found: ${args.id}` } };
+      if (!state.shapes.has(args.id)) return { state, result: toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`) };
       const newShapes = new Map(state.shapes);
       newShapes.delete(args.id);
       const newSelectedIds = new Set(state.selectedIds);
@@ -409,7 +658,7 @@ function handleDeleteShape(args: any): any {
 
 function handleBatchOperations(args: any): any {
   const operations = args.operations;
-  if (!Array.isArray(operations)) return { error: 'Missing required field: operations (array)' };
+  if (!Array.isArray(operations)) return toolError('INVALID_ARGUMENT', 'Missing required field: operations (array)');
 
   return executeOnTab(
     () => {
@@ -421,29 +670,29 @@ function handleBatchOperations(args: any): any {
         const { action, data } = op;
         switch (action) {
           case 'create': {
-            if (!data.type) { results.push({ error: 'Missing type for create' }); continue; }
+            if (!data.type) { results.push(toolError('INVALID_ARGUMENT', 'Missing type for create')); continue; }
             const shape = buildShapeFromParams(data);
             commands.push(new AddShapeCommand(shape));
             results.push({ action: 'created', shape: serializeShape(shape) });
             break;
           }
           case 'update': {
-            if (!data.id) { results.push({ error: 'Missing id for update' }); continue; }
-            if (!get(canvasStore).shapes.has(data.id)) { results.push({ error: `Shape not found: ${data.id}` }); continue; }
+            if (!data.id) { results.push(toolError('INVALID_ARGUMENT', 'Missing id for update')); continue; }
+            if (!get(canvasStore).shapes.has(data.id)) { results.push(toolError('SHAPE_NOT_FOUND', `Shape not found: ${data.id}`)); continue; }
             const { id: _id, ...rest } = data;
             commands.push(new ModifyShapeCommand(data.id, rest));
             results.push({ action: 'updated', id: data.id });
             break;
           }
           case 'delete': {
-            if (!data.id) { results.push({ error: 'Missing id for delete' }); continue; }
-            if (!get(canvasStore).shapes.has(data.id)) { results.push({ error: `Shape not found: ${data.id}` }); continue; }
+            if (!data.id) { results.push(toolError('INVALID_ARGUMENT', 'Missing id for delete')); continue; }
+            if (!get(canvasStore).shapes.has(data.id)) { results.push(toolError('SHAPE_NOT_FOUND', `Shape not found: ${data.id}`)); continue; }
             commands.push(new DeleteShapeCommand(data.id));
             results.push({ action: 'deleted', id: data.id });
             break;
           }
           default:
-            results.push({ error: `Unknown action: ${action}` });
+            results.push(toolError('INVALID_ARGUMENT', `Unknown action: ${action}`));
         }
       }
 
@@ -459,7 +708,7 @@ function handleBatchOperations(args: any): any {
         const { action, data } = op;
         switch (action) {
           case 'create': {
-            if (!data.type) { results.push({ error: 'Missing type for create' }); continue; }
+            if (!data.type) { results.push(toolError('INVALID_ARGUMENT', 'Missing type for create')); continue; }
             const shape = buildShapeFromParams(data);
             const newShapes = new Map(state.shapes);
             newShapes.set(shape.id, shape);
@@ -468,9 +717,9 @@ function handleBatchOperations(args: any): any {
             break;
           }
           case 'update': {
-            if (!data.id) { results.push({ error: 'Missing id for update' }); continue; }
+            if (!data.id) { results.push(toolError('INVALID_ARGUMENT', 'Missing id for update')); continue; }
             const existing = state.shapes.get(data.id);
-            if (!existing) { results.push({ error: `Shape not found: ${data.id}` }); continue; }
+            if (!existing) { results.push(toolError('SHAPE_NOT_FOUND', `Shape not found: ${data.id}`)); continue; }
             const { id: _id, ...rest } = data;
             const updated = { ...existing, ...rest, id: data.id };
             const updShapes = new Map(state.shapes);
@@ -480,8 +729,8 @@ function handleBatchOperations(args: any): any {
             break;
           }
           case 'delete': {
-            if (!data.id) { results.push({ error: 'Missing id for delete' }); continue; }
-            if (!state.shapes.has(data.id)) { results.push({ error: `Shape not found: ${data.id}` }); continue; }
+            if (!data.id) { results.push(toolError('INVALID_ARGUMENT', 'Missing id for delete')); continue; }
+            if (!state.shapes.has(data.id)) { results.push(toolError('SHAPE_NOT_FOUND', `Shape not found: ${data.id}`)); continue; }
             const delShapes = new Map(state.shapes);
             delShapes.delete(data.id);
             state = { ...state, shapes: delShapes, shapesArray: state.shapesArray.filter(s => s.id !== data.id) };
@@ -489,7 +738,7 @@ function handleBatchOperations(args: any): any {
             break;
           }
           default:
-            results.push({ error: `Unknown action: ${action}` });
+            results.push(toolError('INVALID_ARGUMENT', `Unknown action: ${action}`));
         }
       }
 
@@ -500,7 +749,7 @@ function handleBatchOperations(args: any): any {
 
 function handleCreateConnection(args: any): any {
   const { fromShapeId, toShapeId, connectionType, routingMode, text, strokeColor, strokeWidth } = args;
-  if (!fromShapeId || !toShapeId) return { error: 'Missing required fields: fromShapeId, toShapeId' };
+  if (!fromShapeId || !toShapeId) return toolError('INVALID_ARGUMENT', 'Missing required fields: fromShapeId, toShapeId');
 
   const resolved = resolveCanvasState();
   if ('error' in resolved) return resolved;
@@ -508,8 +757,8 @@ function handleCreateConnection(args: any): any {
 
   const fromShape = resolvedState.shapes.get(fromShapeId);
   const toShape = resolvedState.shapes.get(toShapeId);
-  if (!fromShape) return { error: `Source shape not found: ${fromShapeId}` };
-  if (!toShape) return { error: `Target shape not found: ${toShapeId}` };
+  if (!fromShape) return toolError('SHAPE_NOT_FOUND', `Source shape not found: ${fromShapeId}`);
+  if (!toShape) return toolError('SHAPE_NOT_FOUND', `Target shape not found: ${toShapeId}`);
 
   const fromPoint = getBindingPoint(fromShape, 'center', { x: toShape.x + ((toShape as any).width || 0) / 2, y: toShape.y + ((toShape as any).height || 0) / 2 });
   const toPoint = getBindingPoint(toShape, 'center', { x: fromShape.x + ((fromShape as any).width || 0) / 2, y: fromShape.y + ((fromShape as any).height || 0) / 2 });
@@ -531,6 +780,7 @@ function handleCreateConnection(args: any): any {
     bindStart: { shapeId: fromShapeId, point: 'center' as ConnectionPoint },
     bindEnd: { shapeId: toShapeId, point: 'center' as ConnectionPoint },
     text,
+    createdBy: args.createdBy,
   };
 
   const shape: Shape = type === 'arrow'
@@ -613,16 +863,16 @@ function handleCreateTab(args: any): any {
 
 function handleSwitchTab(args: any): any {
   const state = get(tabStore);
-  if (!state.tabs.find(t => t.id === args.tabId)) return { error: `Tab not found: ${args.tabId}` };
+  if (!state.tabs.find(t => t.id === args.tabId)) return toolError('INVALID_ARGUMENT', `Tab not found: ${args.tabId}`);
   mcpActiveTabId = args.tabId;
   return { success: true, mcpActiveTabId: args.tabId };
 }
 
 function handleRenameTab(args: any): any {
-  if (!args.tabId) return { error: 'Missing required field: tabId' };
-  if (!args.title) return { error: 'Missing required field: title' };
+  if (!args.tabId) return toolError('INVALID_ARGUMENT', 'Missing required field: tabId');
+  if (!args.title) return toolError('INVALID_ARGUMENT', 'Missing required field: title');
   const state = get(tabStore);
-  if (!state.tabs.find(t => t.id === args.tabId)) return { error: `Tab not found: ${args.tabId}` };
+  if (!state.tabs.find(t => t.id === args.tabId)) return toolError('INVALID_ARGUMENT', `Tab not found: ${args.tabId}`);
   renameTab(args.tabId, args.title);
   return { success: true, tabId: args.tabId, title: args.title };
 }
@@ -630,16 +880,16 @@ function handleRenameTab(args: any): any {
 // --- Z-index handlers ---
 
 function handleBringToFront(args: any): any {
-  if (!args.id) return { error: 'Missing required field: id' };
+  if (!args.id) return toolError('INVALID_ARGUMENT', 'Missing required field: id');
   return executeOnTab(
     () => {
-      if (!get(canvasStore).shapes.has(args.id)) return { error: `Shape not found: ${args.id}` };
+      if (!get(canvasStore).shapes.has(args.id)) return toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`);
       bringToFront(args.id);
       return { success: true, id: args.id };
     },
     (state) => {
       const shape = state.shapes.get(args.id);
-      if (!shape) return { state, result: { error: `Shape not found: ${args.id}` } };
+      if (!shape) return { state, result: toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`) };
       return {
         state: { ...state, shapesArray: [...state.shapesArray.filter(s => s.id !== args.id), shape] },
         result: { success: true, id: args.id },
@@ -649,16 +899,16 @@ function handleBringToFront(args: any): any {
 }
 
 function handleSendToBack(args: any): any {
-  if (!args.id) return { error: 'Missing required field: id' };
+  if (!args.id) return toolError('INVALID_ARGUMENT', 'Missing required field: id');
   return executeOnTab(
     () => {
-      if (!get(canvasStore).shapes.has(args.id)) return { error: `Shape not found: ${args.id}` };
+      if (!get(canvasStore).shapes.has(args.id)) return toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`);
       sendToBack(args.id);
       return { success: true, id: args.id };
     },
     (state) => {
       const shape = state.shapes.get(args.id);
-      if (!shape) return { state, result: { error: `Shape not found: ${args.id}` } };
+      if (!shape) return { state, result: toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`) };
       return {
         state: { ...state, shapesArray: [shape, ...state.shapesArray.filter(s => s.id !== args.id)] },
         result: { success: true, id: args.id },
@@ -668,16 +918,16 @@ function handleSendToBack(args: any): any {
 }
 
 function handleBringForward(args: any): any {
-  if (!args.id) return { error: 'Missing required field: id' };
+  if (!args.id) return toolError('INVALID_ARGUMENT', 'Missing required field: id');
   return executeOnTab(
     () => {
-      if (!get(canvasStore).shapes.has(args.id)) return { error: `Shape not found: ${args.id}` };
+      if (!get(canvasStore).shapes.has(args.id)) return toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`);
       bringForward(args.id);
       return { success: true, id: args.id };
     },
     (state) => {
       const shape = state.shapes.get(args.id);
-      if (!shape) return { state, result: { error: `Shape not found: ${args.id}` } };
+      if (!shape) return { state, result: toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`) };
       const idx = state.shapesArray.findIndex(s => s.id === args.id);
       if (idx === -1 || idx === state.shapesArray.length - 1) return { state, result: { success: true, id: args.id } };
       const newArray = [...state.shapesArray];
@@ -688,16 +938,16 @@ function handleBringForward(args: any): any {
 }
 
 function handleSendBackward(args: any): any {
-  if (!args.id) return { error: 'Missing required field: id' };
+  if (!args.id) return toolError('INVALID_ARGUMENT', 'Missing required field: id');
   return executeOnTab(
     () => {
-      if (!get(canvasStore).shapes.has(args.id)) return { error: `Shape not found: ${args.id}` };
+      if (!get(canvasStore).shapes.has(args.id)) return toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`);
       sendBackward(args.id);
       return { success: true, id: args.id };
     },
     (state) => {
       const shape = state.shapes.get(args.id);
-      if (!shape) return { state, result: { error: `Shape not found: ${args.id}` } };
+      if (!shape) return { state, result: toolError('SHAPE_NOT_FOUND', `Shape not found: ${args.id}`) };
       const idx = state.shapesArray.findIndex(s => s.id === args.id);
       if (idx <= 0) return { state, result: { success: true, id: args.id } };
       const newArray = [...state.shapesArray];
@@ -709,13 +959,13 @@ function handleSendBackward(args: any): any {
 
 function handleGroupShapes(args: any): any {
   const ids: string[] = args.ids;
-  if (!Array.isArray(ids) || ids.length < 2) return { error: 'Need at least 2 shape IDs to group' };
+  if (!Array.isArray(ids) || ids.length < 2) return toolError('INVALID_ARGUMENT', 'Need at least 2 shape IDs to group');
 
   return executeOnTab(
     () => {
       const state = get(canvasStore);
       for (const id of ids) {
-        if (!state.shapes.has(id)) return { error: `Shape not found: ${id}` };
+        if (!state.shapes.has(id)) return toolError('SHAPE_NOT_FOUND', `Shape not found: ${id}`);
       }
       historyManager.execute(new GroupShapesCommand(ids));
       const newState = get(canvasStore);
@@ -726,7 +976,7 @@ function handleGroupShapes(args: any): any {
     },
     (state) => {
       for (const id of ids) {
-        if (!state.shapes.has(id)) return { state, result: { error: `Shape not found: ${id}` } };
+        if (!state.shapes.has(id)) return { state, result: toolError('SHAPE_NOT_FOUND', `Shape not found: ${id}`) };
       }
       const groupId = `group_${Date.now()}_${Math.random().toString(36).substr(2, 9)}`;
       const newShapes = new Map(state.shapes);
@@ -748,13 +998,13 @@ function handleGroupShapes(args: any): any {
 function handleUngroup(args: any): any {
   return executeOnTab(
     () => {
-      if (!get(canvasStore).groups.has(args.groupId)) return { error: `Group not found: ${args.groupId}` };
+      if (!get(canvasStore).groups.has(args.groupId)) return toolError('INVALID_ARGUMENT', `Group not found: ${args.groupId}`);
       historyManager.execute(new UngroupShapesCommand(args.groupId));
       return { success: true, groupId: args.groupId };
     },
     (state) => {
       const group = state.groups.get(args.groupId);
-      if (!group) return { state, result: { error: `Group not found: ${args.groupId}` } };
+      if (!group) return { state, result: toolError('INVALID_ARGUMENT', `Group not found: ${args.groupId}`) };
       const newShapes = new Map(state.shapes);
       group.shapeIds.forEach(id => {
         const shape = newShapes.get(id);
@@ -779,7 +1029,7 @@ function handleUngroup(args: any): any {
 function handleReorganize(args: any): any {
   const algorithm: string = args.algorithm;
   if (!algorithm || !['grid', 'force-directed'].includes(algorithm)) {
-    return { error: 'Missing or invalid field: algorithm (must be "grid" or "force-directed")' };
+    return toolError('INVALID_ARGUMENT', 'Missing or invalid field: algorithm (must be "grid" or "force-directed")');
   }
 
   const resolved = resolveCanvasState();
@@ -792,7 +1042,7 @@ function handleReorganize(args: any): any {
     targetShapes = args.shapeIds
       .map((id: string) => canvasState.shapes.get(id))
       .filter((s: Shape | undefined): s is Shape => !!s);
-    if (targetShapes.length === 0) return { error: 'No valid shapes found for given shapeIds' };
+    if (targetShapes.length === 0) return toolError('INVALID_ARGUMENT', 'No valid shapes found for given shapeIds');
   } else {
     targetShapes = canvasState.shapesArray;
   }
@@ -880,3 +1130,90 @@ function handleSetSnapSettings(args: any): any {
     objectSnap: newState.objectSnap,
   };
 }
+
+function handleSetDocumentScale(args: any): any {
+  const resolved = resolveCanvasState();
+  if ('error' in resolved) return resolved;
+  const { canvasState, resolvedTabId } = resolved;
+
+  const scale = args.scale || null;
+  const tabState = get(tabStore);
+  if (resolvedTabId === tabState.activeTabId) {
+    canvasStore.update(s => ({ ...s, scale }));
+  } else {
+    updateTabCanvasState(resolvedTabId, { ...canvasState, scale });
+  }
+
+  return { scale };
+}
+
+/**
+ * Apply a plan computed by partition_canvas (partition.rs): create the new
+ * tabs with their shapes, then move everything off the source tab as one
+ * undo step. Internal — not advertised as an MCP tool.
+ */
+/** Restack every shape of the MCP tab in one undo step (`set_shape_order`, validated in Rust). */
+function handleApplyShapeOrder(args: any): any {
+  const ids: string[] = args.ids;
+  if (!Array.isArray(ids)) return toolError('INVALID_ARGUMENT', 'Missing required field: ids');
+  // The board may have changed since Rust checked the order.
+  const mismatch = (state: CanvasState) =>
+    ids.length !== state.shapesArray.length || new Set(ids).size !== ids.length || !ids.every(id => state.shapes.has(id))
+      ? toolError('INVALID_ARGUMENT', 'The order must list every shape on the board exactly once')
+      : null;
+  return executeOnTab(
+    () => {
+      const error = mismatch(get(canvasStore));
+      if (error) return error;
+      historyManager.execute(new ReorderShapesCommand(ids));
+      return { success: true, count: ids.length };
+    },
+    (state) => {
+      const error = mismatch(state);
+      if (error) return { state, result: error };
+      return { state: { ...state, shapesArray: orderShapes(state, ids) }, result: { success: true, count: ids.length } };
+    }
+  );
+}
+
+function handleApplyPartition(args: any): any {
+  const { tabId, plan } = args;
+  if (!tabId || !plan) return toolError('INVALID_ARGUMENT', 'Missing required fields: tabId, plan');
+  const source = getTabCanvasState(tabId);
+  if (!source) return toolError('INVALID_ARGUMENT', `Tab not found: ${tabId}`);
+
+  for (const tab of plan.tabs) {
+    createTabSilent(tab.title, tab.id);
+    const state = getTabCanvasState(tab.id)!;
+    const shapes = tab.shapes as Shape[];
+    updateTabCanvasState(tab.id, {
+      ...state,
+      shapes: new Map(shapes.map(s => [s.id, s])),
+      shapesArray: shapes,
+      groups: new Map((tab.groups as Group[]).map(g => [g.id, g])),
+      viewport: tab.viewport,
+      scale: source.scale,
+    });
+  }
+
+  const removedGroups = new Set<string>(plan.removeGroups);
+  const keepGroups = (groups: Map<string, Group>) =>
+    new Map([...groups].filter(([id]) => !removedGroups.has(id)));
+  const added = plan.add as Shape[];
+
+  if (tabId === get(tabStore).activeTabId) {
+    historyManager.execute(new BatchCommand([
+      new DeleteShapesCommand(plan.remove),
+      ...added.map(shape => new AddShapeCommand(shape)),
+    ]));
+    canvasStore.update(s => ({ ...s, groups: keepGroups(s.groups) }));
+  } else {
+    const removed = new Set<string>(plan.remove);
+    const shapesArray = [...source.shapesArray.filter(s => !removed.has(s.id)), ...added];
+    const shapes = new Map(shapesArray.map(s => [s.id, s]));
+    updateTabCanvasState(tabId, { ...source, shapes, shapesArray, groups: keepGroups(source.groups) });
+    notifyChange(tabId, diffShapes(source.shapes, shapes));
+  }
+
+  return { success: true, tabIds: plan.tabs.map((t: any) => t.id) };
+}
@@ -0,0 +1,50 @@
+/**
+ * Slash-command snippets in the text editor
+ * Triggers and their expansions live on the native side (`snippets.rs`);
+ * this replaces a trigger typed before the caret and adds any shapes.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { isTauri } from '../storage/tauriFile';
+import { historyManager, AddShapeCommand, BatchCommand } from '$lib/state/history';
+import { buildShapeFromParams } from './handler';
+
+interface Expansion {
+  text: string;
+  shapes: any[];
+}
+
+/**
+ * A `/trigger` right before the caret
+ */
+const TRIGGER_BEFORE_CARET = /(^|\s)(\/[^\s/]+)$/;
+
+/**
+ * Expand the trigger before `caret` in `text`, adding its shapes at
+ * (`x`, `y`) in canvas coordinates. Returns the new text and caret, or
+ * null when there is no snippet to expand.
+ */
+export async function expandSnippetAt(
+  text: string,
+  caret: number,
+  x: number,
+  y: number
+): Promise<{ text: string; caret: number } | null> {
+  if (!isTauri()) return null;
+  const match = TRIGGER_BEFORE_CARET.exec(text.slice(0, caret));
+  if (!match) return null;
+  const trigger = match[2];
+
+  const expansion = await invoke<Expansion | null>('expand_snippet', { trigger, x, y });
+  if (!expansion) return null;
+
+  if (expansion.shapes.length > 0) {
+    const commands = expansion.shapes.map(params => new AddShapeCommand(buildShapeFromParams(params)));
+    historyManager.execute(new BatchCommand(commands));
+  }
+  const start = caret - trigger.length;
+  return {
+    text: text.slice(0, start) + expansion.text + text.slice(caret),
+    caret: start + expansion.text.length,
+  };
+}
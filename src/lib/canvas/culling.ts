@@ -3,8 +3,9 @@
  * Only renders shapes whose bounding boxes intersect with the viewport
  */
 
-import type { BoundingBox, Viewport } from '$lib/types';
+import type { BoundingBox, Shape, Viewport } from '$lib/types';
 import { boundingBoxesIntersect } from '$lib/utils/geometry';
+import { invoke } from '@tauri-apps/api/core';
 
 /**
  * Interface for objects that have bounds (shapes)
@@ -116,3 +117,143 @@ export function addMarginToVisibleBounds(
     height: visibleBounds.height + marginY * 2
   };
 }
+
+/**
+ * Boards with fewer shapes than this are drawn without index culling
+ */
+export const SPATIAL_CULLING_THRESHOLD = 2000;
+
+/**
+ * Fields the backend index needs; everything else (image data, text) stays
+ * in the webview
+ */
+function geometryOf(shape: Shape): Record<string, unknown> {
+  const s = shape as any;
+  return {
+    id: s.id,
+    type: s.type,
+    x: s.x,
+    y: s.y,
+    width: s.width,
+    height: s.height,
+    x2: s.x2,
+    y2: s.y2,
+    rotation: s.rotation,
+    points: s.points,
+    controlPoints: s.controlPoints,
+  };
+}
+
+function containsBounds(outer: BoundingBox, inner: BoundingBox): boolean {
+  return inner.x >= outer.x && inner.y >= outer.y &&
+    inner.x + inner.width <= outer.x + outer.width &&
+    inner.y + inner.height <= outer.y + outer.height;
+}
+
+/**
+ * Viewport culling backed by the Rust spatial index.
+ *
+ * `sync` mirrors shape changes to the backend; `setVisibleBounds` asks it
+ * for the shapes around the viewport, reusing the last answer while the
+ * viewport stays inside the queried area. Shapes changed since the last
+ * answer are always drawn, so edits never blink out while a query is in
+ * flight.
+ */
+export class SpatialCuller {
+  private indexed = new Map<string, Shape>();
+  private visible: Set<string> | null = null;
+  private answeredFor = new Map<string, Shape>();
+  private queriedArea: BoundingBox | null = null;
+  private wantedArea: BoundingBox | null = null;
+  private querying = false;
+  private indexVersion = 0;
+
+  constructor(private onChange: () => void) {}
+
+  /**
+   * Mirror the current shapes into the backend index
+   */
+  sync(shapes: Map<string, Shape>): void {
+    if (shapes.size < SPATIAL_CULLING_THRESHOLD) {
+      if (this.indexed.size > 0) {
+        this.indexed = new Map();
+        invoke('update_spatial_index', { upserts: [], removed: [], reset: true }).catch(() => {});
+      }
+      this.visible = null;
+      this.queriedArea = null;
+      this.indexVersion++;
+      return;
+    }
+
+    const upserts: Record<string, unknown>[] = [];
+    const removed: string[] = [];
+    for (const [id, shape] of shapes) {
+      if (this.indexed.get(id) !== shape) upserts.push(geometryOf(shape));
+    }
+    for (const id of this.indexed.keys()) {
+      if (!shapes.has(id)) removed.push(id);
+    }
+    if (upserts.length === 0 && removed.length === 0) return;
+
+    const reset = this.indexed.size === 0;
+    this.indexed = new Map(shapes);
+    invoke('update_spatial_index', { upserts, removed, reset })
+      .then(() => {
+        this.indexVersion++;
+        this.queriedArea = null;
+        this.query();
+      })
+      .catch(err => console.error('Failed to update spatial index:', err));
+  }
+
+  /**
+   * Tell the culler what is on screen; re-queries only when the view has
+   * left the area covered by the last answer
+   */
+  setVisibleBounds(bounds: BoundingBox): void {
+    if (this.indexed.size === 0) return;
+    this.wantedArea = bounds;
+    if (this.queriedArea && containsBounds(this.queriedArea, bounds)) return;
+    this.query();
+  }
+
+  /**
+   * Whether a shape should be drawn this frame
+   */
+  isVisible(shape: Shape): boolean {
+    if (!this.visible) return true;
+    return this.visible.has(shape.id) || this.answeredFor.get(shape.id) !== shape;
+  }
+
+  private query(): void {
+    const area = this.wantedArea;
+    if (!area || this.querying) return;
+    this.querying = true;
+    const margin = Math.max(area.width, area.height) * 0.5;
+    const snapshot = this.indexed;
+    const version = this.indexVersion;
+    invoke<string[]>('query_visible_shapes', { ...area, margin })
+      .then(ids => {
+        this.querying = false;
+        if (this.indexed.size === 0) return; // dropped below the threshold meanwhile
+        this.visible = new Set(ids);
+        this.answeredFor = snapshot;
+        this.queriedArea = version === this.indexVersion ? {
+          x: area.x - margin,
+          y: area.y - margin,
+          width: area.width + margin * 2,
+          height: area.height + margin * 2,
+        } : null;
+        this.onChange();
+        // The index or the view moved on while we were waiting
+        const wanted = this.wantedArea;
+        if (wanted && !(this.queriedArea && containsBounds(this.queriedArea, wanted))) {
+          this.query();
+        }
+      })
+      .catch(err => {
+        this.querying = false;
+        console.error('Spatial query failed:', err);
+      });
+  }
+}
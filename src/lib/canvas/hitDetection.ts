@@ -17,11 +17,17 @@ import { freedrawContainsPoint } from '../shapes/freedraw';
 import { textContainsPoint } from '../shapes/text';
 import { stickyNoteContainsPoint } from '../shapes/stickyNote';
 import { imageContainsPoint } from '../shapes/image';
+import { toLocalPoint } from '../shapes/transform';
 
 /**
  * Check if a point hits a shape
  */
 export function shapeContainsPoint(shape: Shape, x: number, y: number): boolean {
+  // Test in the shape's own frame so rotated shapes are hit where they're drawn
+  if (shape.rotation) {
+    ({ x, y } = toLocalPoint(shape, x, y));
+  }
+
   switch (shape.type) {
     case 'rectangle':
       return rectangleContainsPoint(shape, x, y);
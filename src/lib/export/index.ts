@@ -7,3 +7,4 @@ export { exportToPNG } from './png';
 export { exportToSVG } from './svg';
 export type { ExportPNGOptions } from './png';
 export type { ExportSVGOptions } from './svg';
+export type { WatermarkOptions, WatermarkPlacement } from './watermark';
@@ -6,6 +6,8 @@
 import type { Shape, Viewport } from '../types';
 import { getContentBounds, renderShapesToCanvas } from './renderExport';
 import { isTauri } from '../storage/tauriFile';
+import { watermarkOverlay, drawWatermark, type WatermarkOptions } from './watermark';
+import { invoke } from '@tauri-apps/api/core';
 import { save } from '@tauri-apps/plugin-dialog';
 import { writeFile } from '@tauri-apps/plugin-fs';
 
@@ -14,25 +16,30 @@ export interface ExportPNGOptions {
   filename?: string;
   padding?: number;
   scale?: number;
+  /** Overrides the default export watermark from settings */
+  watermark?: WatermarkOptions;
 }
 
 // Max canvas dimension to avoid browser limits
 const MAX_CANVAS_DIM = 8192;
 
+export interface RenderedPNG {
+  blob: Blob;
+  width: number;
+  height: number;
+}
+
 /**
- * Export shapes to PNG
- * Renders all shapes using the same rough.js pipeline as the main canvas
+ * Render shapes to a PNG blob
+ * Uses the same rough.js pipeline as the main canvas. The scale is lowered
+ * if the image would be larger than maxDimension on either side; minScale
+ * keeps it from going below a floor regardless.
  */
-export async function exportToPNG(
+export async function renderPNG(
   shapes: Shape[],
-  _viewport: Viewport,
-  options: ExportPNGOptions = {}
-): Promise<void> {
-  const {
-    backgroundColor = '#ffffff',
-    filename = 'napkin-export.png',
-    padding = 40,
-  } = options;
+  options: ExportPNGOptions & { maxDimension?: number; minScale?: number } = {}
+): Promise<RenderedPNG> {
+  const { backgroundColor = '#ffffff', padding = 40, maxDimension = MAX_CANVAS_DIM, minScale = 0 } = options;
   let { scale = 2 } = options;
 
   if (shapes.length === 0) {
@@ -44,13 +51,13 @@ export async function exportToPNG(
   const contentWidth = bounds.maxX - bounds.minX + padding * 2;
   const contentHeight = bounds.maxY - bounds.minY + padding * 2;
 
-  // Clamp scale if canvas would exceed browser limits
+  // Clamp scale if canvas would exceed the size limit
   const maxScale = Math.min(
-    MAX_CANVAS_DIM / contentWidth,
-    MAX_CANVAS_DIM / contentHeight,
+    maxDimension / contentWidth,
+    maxDimension / contentHeight,
     scale
   );
-  scale = Math.max(1, maxScale);
+  scale = Math.max(minScale, maxScale);
 
   const canvasWidth = Math.ceil(contentWidth * scale);
   const canvasHeight = Math.ceil(contentHeight * scale);
@@ -80,11 +87,30 @@ export async function exportToPNG(
     throw new Error(`Render failed: ${renderErr instanceof Error ? renderErr.message : String(renderErr)}`);
   }
 
+  const overlay = await watermarkOverlay(contentWidth, contentHeight, options.watermark);
+  if (overlay) {
+    await drawWatermark(ctx, exportCanvas, overlay);
+  }
+
   // Convert to blob
   const blob = await canvasToBlob(exportCanvas);
   if (!blob) {
     throw new Error('Failed to create PNG blob');
   }
+  return { blob, width: canvasWidth, height: canvasHeight };
+}
+
+/**
+ * Export shapes to PNG
+ * Renders all shapes using the same rough.js pipeline as the main canvas
+ */
+export async function exportToPNG(
+  shapes: Shape[],
+  _viewport: Viewport,
+  options: ExportPNGOptions = {}
+): Promise<void> {
+  const { filename = 'napkin-export.png' } = options;
+  const { blob } = await renderPNG(shapes, { ...options, minScale: 1 });
 
   if (isTauri()) {
     try {
@@ -96,6 +122,7 @@ export async function exportToPNG(
       if (filePath) {
         const buffer = await blob.arrayBuffer();
         await writeFile(filePath, new Uint8Array(buffer));
+        await invoke('run_export_hooks', { path: filePath });
       }
     } catch (tauriErr) {
       console.error('Tauri save failed:', tauriErr);
@@ -13,6 +13,7 @@ import { drawEndpointShape, getEffectiveEndpoint } from '../canvas/endpointRende
 import { getElbowPathPoints, getEndAngle, getStartAngle } from '../utils/routing';
 import { loadImage } from '../shapes/image';
 import { getCloudSvgPath, traceCloudPath } from '../shapes/cloud';
+import { getRotationCenter, rotatePoint } from '../shapes/transform';
 
 // --- Stroke style conversion (mirrors roughRenderer.ts) ---
 
@@ -53,43 +54,33 @@ export function getContentBounds(shapes: Shape[]): { minX: number; minY: number;
 
   for (const shape of shapes) {
     const s = shape as any;
+    let points: { x: number; y: number }[];
 
     if (s.type === 'line' || s.type === 'arrow') {
-      const x1 = s.x;
-      const y1 = s.y;
-      const x2 = s.x2 ?? s.x;
-      const y2 = s.y2 ?? s.y;
-      minX = Math.min(minX, x1, x2);
-      minY = Math.min(minY, y1, y2);
-      maxX = Math.max(maxX, x1, x2);
-      maxY = Math.max(maxY, y1, y2);
-      if (s.controlPoints) {
-        for (const cp of s.controlPoints) {
-          minX = Math.min(minX, cp.x);
-          minY = Math.min(minY, cp.y);
-          maxX = Math.max(maxX, cp.x);
-          maxY = Math.max(maxY, cp.y);
-        }
-      }
-    } else if (s.type === 'freedraw' && s.points) {
-      for (const pt of s.points) {
-        minX = Math.min(minX, pt.x);
-        minY = Math.min(minY, pt.y);
-        maxX = Math.max(maxX, pt.x);
-        maxY = Math.max(maxY, pt.y);
-      }
-    } else if (s.type === 'text') {
-      minX = Math.min(minX, s.x);
-      minY = Math.min(minY, s.y);
-      maxX = Math.max(maxX, s.x + (s.width || 200));
-      maxY = Math.max(maxY, s.y + (s.height || 30));
+      points = [{ x: s.x, y: s.y }, { x: s.x2 ?? s.x, y: s.y2 ?? s.y }, ...(s.controlPoints || [])];
+    } else if (s.type === 'freedraw') {
+      points = s.points || [];
     } else {
-      const w = s.width || 0;
-      const h = s.height || 0;
-      minX = Math.min(minX, s.x);
-      minY = Math.min(minY, s.y);
-      maxX = Math.max(maxX, s.x + w);
-      maxY = Math.max(maxY, s.y + h);
+      const w = s.type === 'text' ? (s.width || 200) : (s.width || 0);
+      const h = s.type === 'text' ? (s.height || 30) : (s.height || 0);
+      points = [
+        { x: s.x, y: s.y },
+        { x: s.x + w, y: s.y },
+        { x: s.x + w, y: s.y + h },
+        { x: s.x, y: s.y + h },
+      ];
+    }
+
+    // Rotated shapes contribute their rotated footprint
+    if (s.rotation) {
+      const center = getRotationCenter(shape);
+      points = points.map(p => rotatePoint(p, center, s.rotation));
+    }
+    for (const pt of points) {
+      minX = Math.min(minX, pt.x);
+      minY = Math.min(minY, pt.y);
+      maxX = Math.max(maxX, pt.x);
+      maxY = Math.max(maxY, pt.y);
     }
   }
 
@@ -140,11 +131,10 @@ function renderShape(ctx: CanvasRenderingContext2D, rc: RoughCanvas, shape: any)
 
   // Apply rotation
   if (shape.rotation && shape.rotation !== 0) {
-    const centerX = shape.x + (shape.width || 0) / 2;
-    const centerY = shape.y + (shape.height || 0) / 2;
-    ctx.translate(centerX, centerY);
-    ctx.rotate(shape.rotation);
-    ctx.translate(-centerX, -centerY);
+    const center = getRotationCenter(shape);
+    ctx.translate(center.x, center.y);
+    ctx.rotate((shape.rotation * Math.PI) / 180);
+    ctx.translate(-center.x, -center.y);
   }
 
   switch (shape.type) {
@@ -7,6 +7,8 @@
 import type { Shape, Viewport } from '../types';
 import { getContentBounds, renderShapesToCanvas } from './renderExport';
 import { isTauri } from '../storage/tauriFile';
+import { watermarkOverlay, type WatermarkOptions } from './watermark';
+import { invoke } from '@tauri-apps/api/core';
 import { save } from '@tauri-apps/plugin-dialog';
 import { writeTextFile } from '@tauri-apps/plugin-fs';
 
@@ -15,6 +17,8 @@ export interface ExportSVGOptions {
   filename?: string;
   padding?: number;
   scale?: number;
+  /** Overrides the default export watermark from settings */
+  watermark?: WatermarkOptions;
 }
 
 const MAX_CANVAS_DIM = 8192;
@@ -80,12 +84,13 @@ export async function exportToSVG(
   const dataURL = exportCanvas.toDataURL('image/png');
   const svgWidth = Math.ceil(contentWidth);
   const svgHeight = Math.ceil(contentHeight);
+  const overlay = await watermarkOverlay(svgWidth, svgHeight, options.watermark);
 
   const svgString = `<?xml version="1.0" encoding="UTF-8"?>
 <svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"
      width="${svgWidth}" height="${svgHeight}" viewBox="0 0 ${svgWidth} ${svgHeight}">
   <rect width="100%" height="100%" fill="${backgroundColor}"/>
-  <image width="${svgWidth}" height="${svgHeight}" xlink:href="${dataURL}"/>
+  <image width="${svgWidth}" height="${svgHeight}" xlink:href="${dataURL}"/>${overlay ? `\n  ${overlay}` : ''}
 </svg>`;
 
   const blob = new Blob([svgString], { type: 'image/svg+xml' });
@@ -99,6 +104,7 @@ export async function exportToSVG(
 
       if (filePath) {
         await writeTextFile(filePath, svgString);
+        await invoke('run_export_hooks', { path: filePath });
       }
     } catch (tauriErr) {
       console.error('Tauri save failed:', tauriErr);
@@ -0,0 +1,70 @@
+/**
+ * Watermark overlay for PNG/SVG exports
+ * The native side lays the banner out (same as the interactive SVG and PDF
+ * exports), so the webview exporters only composite the markup it returns.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { isTauri } from '../storage/tauriFile';
+
+export type WatermarkPlacement =
+  | 'top'
+  | 'bottom'
+  | 'center'
+  | 'diagonal'
+  | 'topLeft'
+  | 'topRight'
+  | 'bottomLeft'
+  | 'bottomRight';
+
+export interface WatermarkOptions {
+  enabled?: boolean;
+  text?: string;
+  placement?: WatermarkPlacement;
+  /** 0 (invisible) to 1 (opaque) */
+  opacity?: number;
+  color?: string;
+  /** PNG or JPEG data URL drawn instead of the text */
+  image?: string | null;
+}
+
+/**
+ * SVG markup of the watermark for a width x height export, or null when none
+ * applies. Without an explicit watermark the app's default is used.
+ */
+export async function watermarkOverlay(
+  width: number,
+  height: number,
+  watermark?: WatermarkOptions
+): Promise<string | null> {
+  if (!isTauri()) return null;
+  try {
+    return await invoke<string | null>('export_watermark_svg', { width, height, watermark });
+  } catch (err) {
+    console.error('Failed to render watermark:', err);
+    return null;
+  }
+}
+
+/**
+ * Draw the overlay over the whole canvas, ignoring the context's transform
+ */
+export async function drawWatermark(
+  ctx: CanvasRenderingContext2D,
+  canvas: HTMLCanvasElement,
+  svg: string
+): Promise<void> {
+  const url = URL.createObjectURL(new Blob([svg], { type: 'image/svg+xml' }));
+  try {
+    const image = new Image();
+    await new Promise<void>((resolve, reject) => {
+      image.onload = () => resolve();
+      image.onerror = () => reject(new Error('Failed to load watermark'));
+      image.src = url;
+    });
+    ctx.setTransform(1, 0, 0, 1, 0, 0);
+    ctx.drawImage(image, 0, 0, canvas.width, canvas.height);
+  } finally {
+    URL.revokeObjectURL(url);
+  }
+}
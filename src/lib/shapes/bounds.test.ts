@@ -116,6 +116,16 @@ describe('getShapeBounds', () => {
     const bounds = getShapeBounds(shape);
     expect(bounds).toEqual({ x: 0, y: 0, width: 0, height: 0 });
   });
+
+  it('covers the rotated footprint of a rotated rectangle', () => {
+    // 102x52 stroked box centred on (60,45), turned a quarter
+    const shape = makeRect({ rotation: 90 });
+    const bounds = getShapeBounds(shape);
+    expect(bounds.x).toBeCloseTo(34);
+    expect(bounds.y).toBeCloseTo(-6);
+    expect(bounds.width).toBeCloseTo(52);
+    expect(bounds.height).toBeCloseTo(102);
+  });
 });
 
 describe('getCombinedBounds', () => {
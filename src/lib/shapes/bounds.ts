@@ -3,11 +3,42 @@
  */
 
 import type { Shape, BoundingBox } from '../types';
+import { getRotationCenter, rotateBounds, rotatePoint } from './transform';
 
 /**
- * Get bounding box for any shape
+ * Get bounding box for any shape, covering its rotated footprint
  */
 export function getShapeBounds(shape: Shape): BoundingBox {
+  const rotation = shape.rotation || 0;
+  if (!rotation) return getUnrotatedBounds(shape);
+
+  const center = getRotationCenter(shape);
+  if (shape.type === 'line' || shape.type === 'arrow' || shape.type === 'freedraw') {
+    // Rotate the points themselves; rotating their box would overshoot.
+    const points = shape.type === 'freedraw'
+      ? shape.points
+      : [{ x: shape.x, y: shape.y }, { x: shape.x2, y: shape.y2 }];
+    if (points.length === 0) return getUnrotatedBounds(shape);
+    const rotated = points.map(p => rotatePoint(p, center, rotation));
+    const halfStroke = shape.strokeWidth / 2;
+    const xs = rotated.map(p => p.x);
+    const ys = rotated.map(p => p.y);
+    const minX = Math.min(...xs);
+    const minY = Math.min(...ys);
+    return {
+      x: minX - halfStroke,
+      y: minY - halfStroke,
+      width: Math.max(...xs) - minX + shape.strokeWidth,
+      height: Math.max(...ys) - minY + shape.strokeWidth
+    };
+  }
+  return rotateBounds(getUnrotatedBounds(shape), center, rotation);
+}
+
+/**
+ * Get bounding box for a shape ignoring its rotation
+ */
+export function getUnrotatedBounds(shape: Shape): BoundingBox {
   const halfStroke = shape.strokeWidth / 2;
 
   switch (shape.type) {
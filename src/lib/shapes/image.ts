@@ -2,11 +2,42 @@
  * Image shape - embeds images on the canvas
  */
 
+import { convertFileSrc, invoke } from '@tauri-apps/api/core';
 import type { BoundingBox, ImageShape } from '../types';
 import { generateShapeId } from '../state/canvasStore';
+import { getCurrentFilePath } from '../state/fileStore';
+import { isTauri } from '../storage/tauriFile';
 
 export type { ImageShape };
 
+/** Prefix of an image `src` that refers to the Rust asset store */
+const BLOB_PREFIX = 'napkin-blob:';
+
+/**
+ * The URL an image `src` loads from: stored images are served by the
+ * `napkin-asset` protocol, anything else is used as is
+ */
+export function imageURL(src: string): string {
+  return src.startsWith(BLOB_PREFIX)
+    ? convertFileSrc(src.slice(BLOB_PREFIX.length), 'napkin-asset')
+    : src;
+}
+
+/**
+ * Keep an added image in the asset store and return the `src` to use for it,
+ * so the canvas does not hold its base64. Outside the desktop app, and for
+ * password-protected documents, the data URL itself.
+ */
+export async function storeImage(dataURL: string): Promise<string> {
+  if (!isTauri() || !dataURL.startsWith('data:')) return dataURL;
+  try {
+    return await invoke<string>('store_asset', { dataUrl: dataURL, document: getCurrentFilePath() });
+  } catch (error) {
+    console.error('Keeping the image inline:', error);
+    return dataURL;
+  }
+}
+
 /**
  * Load an image and return HTMLImageElement
  */
@@ -15,7 +46,11 @@ export function loadImage(src: string): Promise<HTMLImageElement> {
     const img = new Image();
     img.onload = () => resolve(img);
     img.onerror = reject;
-    img.src = src;
+    if (src.startsWith(BLOB_PREFIX)) {
+      // Served with CORS headers, so exports can still read the canvas back
+      img.crossOrigin = 'anonymous';
+    }
+    img.src = imageURL(src);
   });
 }
 
@@ -51,8 +86,8 @@ export async function createImageFromFile(
   x: number,
   y: number
 ): Promise<ImageShape> {
-  const dataURL = await fileToDataURL(file);
-  const img = await loadImage(dataURL);
+  const src = await storeImage(await fileToDataURL(file));
+  const img = await loadImage(src);
 
   // Calculate dimensions while maintaining aspect ratio
   const maxWidth = 800;
@@ -73,7 +108,7 @@ export async function createImageFromFile(
     y,
     width,
     height,
-    src: dataURL,
+    src,
     opacity: 1,
     imageElement: img,
     loaded: true,
@@ -88,10 +123,11 @@ export async function createImageFromFile(
  * Create an image shape from a data URL
  */
 export async function createImageFromURL(
-  src: string,
+  url: string,
   x: number,
   y: number
 ): Promise<ImageShape> {
+  const src = await storeImage(url);
   const img = await loadImage(src);
 
   // Calculate dimensions while maintaining aspect ratio
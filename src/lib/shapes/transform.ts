@@ -0,0 +1,82 @@
+/**
+ * Rotation transforms for shapes.
+ *
+ * Mirrors src-tauri/src/geometry.rs: shapes rotate `rotation` degrees about
+ * the centre of their unrotated box, lines and arrows about the midpoint of
+ * their endpoints, freedraw about the centre of its points.
+ */
+
+import type { Shape, BoundingBox } from '../types';
+
+export interface Point {
+  x: number;
+  y: number;
+}
+
+/**
+ * Rotate a point by `degrees` (clockwise on screen) about `center`
+ */
+export function rotatePoint(point: Point, center: Point, degrees: number): Point {
+  if (!degrees) return point;
+  const rad = (degrees * Math.PI) / 180;
+  const cos = Math.cos(rad);
+  const sin = Math.sin(rad);
+  const dx = point.x - center.x;
+  const dy = point.y - center.y;
+  return {
+    x: center.x + dx * cos - dy * sin,
+    y: center.y + dx * sin + dy * cos,
+  };
+}
+
+/**
+ * The point a shape rotates about
+ */
+export function getRotationCenter(shape: Shape): Point {
+  const s = shape as any;
+  switch (shape.type) {
+    case 'line':
+    case 'arrow':
+      return { x: (s.x + (s.x2 ?? s.x)) / 2, y: (s.y + (s.y2 ?? s.y)) / 2 };
+    case 'freedraw': {
+      const points: Point[] = s.points || [];
+      if (points.length === 0) return { x: s.x, y: s.y };
+      const xs = points.map(p => p.x);
+      const ys = points.map(p => p.y);
+      return {
+        x: (Math.min(...xs) + Math.max(...xs)) / 2,
+        y: (Math.min(...ys) + Math.max(...ys)) / 2,
+      };
+    }
+    case 'text':
+      return { x: s.x + (s.width || 200) / 2, y: s.y + (s.height || 30) / 2 };
+    default:
+      return { x: s.x + (s.width || 0) / 2, y: s.y + (s.height || 0) / 2 };
+  }
+}
+
+/**
+ * Map a canvas point into the shape's unrotated frame, so containment tests
+ * written for axis-aligned shapes work on rotated ones
+ */
+export function toLocalPoint(shape: Shape, x: number, y: number): Point {
+  return rotatePoint({ x, y }, getRotationCenter(shape), -(shape.rotation || 0));
+}
+
+/**
+ * Axis-aligned box around an unrotated box after rotating it about `center`
+ */
+export function rotateBounds(box: BoundingBox, center: Point, degrees: number): BoundingBox {
+  if (!degrees) return box;
+  const corners = [
+    { x: box.x, y: box.y },
+    { x: box.x + box.width, y: box.y },
+    { x: box.x + box.width, y: box.y + box.height },
+    { x: box.x, y: box.y + box.height },
+  ].map(p => rotatePoint(p, center, degrees));
+  const xs = corners.map(p => p.x);
+  const ys = corners.map(p => p.y);
+  const minX = Math.min(...xs);
+  const minY = Math.min(...ys);
+  return { x: minX, y: minY, width: Math.max(...xs) - minX, height: Math.max(...ys) - minY };
+}
@@ -0,0 +1,45 @@
+/**
+ * Keeps the backend's list of boards hidden from agents in sync with the tabs
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { get } from 'svelte/store';
+import { canvasStore } from './canvasStore';
+import { tabStore } from './tabStore';
+
+/**
+ * Ids of the open tabs whose documents are hidden from MCP clients. The
+ * active tab's state lives in canvasStore, the others' in the tab store.
+ */
+export function hiddenTabIds(): string[] {
+  const { tabs, activeTabId } = get(tabStore);
+  const activeHidden = get(canvasStore).hiddenFromApi === true;
+  return tabs
+    .filter(tab => (tab.id === activeTabId ? activeHidden : tab.canvasState?.hiddenFromApi === true))
+    .map(tab => tab.id);
+}
+
+/**
+ * Report hidden tabs and the tab on screen to the backend whenever either
+ * changes, so the MCP server can refuse calls that would reach them.
+ * Returns a cleanup function.
+ */
+export function initAgentVisibility(): () => void {
+  let last: string | undefined;
+  const sync = () => {
+    const hidden = hiddenTabIds();
+    const activeTabId = get(tabStore).activeTabId;
+    const key = JSON.stringify([hidden, activeTabId]);
+    if (key === last) return;
+    last = key;
+    invoke('api_set_tab_visibility', { hidden, activeTabId }).catch(err =>
+      console.error('Failed to sync board visibility:', err)
+    );
+  };
+  const stopTabs = tabStore.subscribe(sync);
+  const stopCanvas = canvasStore.subscribe(sync);
+  return () => {
+    stopTabs();
+    stopCanvas();
+  };
+}
@@ -0,0 +1,56 @@
+/**
+ * "Ask before AI edits" prompts raised by the MCP server
+ */
+
+import { writable } from 'svelte/store';
+import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/core';
+
+export type ApprovalDecision = 'allow' | 'allowAlways' | 'deny';
+
+export interface ApprovalRequest {
+  id: string;
+  tool: string;
+  client: string;
+  arguments: Record<string, unknown>;
+}
+
+/**
+ * Tool calls waiting for the user, oldest first
+ */
+export const approvalRequests = writable<ApprovalRequest[]>([]);
+
+function dismiss(id: string): void {
+  approvalRequests.update(requests => requests.filter(r => r.id !== id));
+}
+
+/**
+ * Subscribe to approval prompts from the backend.
+ * Returns a cleanup function.
+ */
+export async function initToolApprovals(): Promise<() => void> {
+  const unlistenRequest = await listen<ApprovalRequest>('tool-approval-request', (event) => {
+    approvalRequests.update(requests => [...requests, event.payload]);
+  });
+  // Prompts left unanswered are denied server-side
+  const unlistenExpired = await listen<string>('tool-approval-expired', (event) => {
+    dismiss(event.payload);
+  });
+
+  return () => {
+    unlistenRequest();
+    unlistenExpired();
+  };
+}
+
+/**
+ * Answer a prompt and take it off the queue
+ */
+export async function respondToApproval(id: string, decision: ApprovalDecision): Promise<void> {
+  dismiss(id);
+  try {
+    await invoke('respond_tool_approval', { id, decision });
+  } catch (err) {
+    console.warn('[approval]', err);
+  }
+}
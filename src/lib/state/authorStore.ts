@@ -0,0 +1,55 @@
+/**
+ * Identity stamped on shapes the user draws
+ *
+ * The Rust side owns author identity (name and color from settings, see
+ * `author.rs`) and stamps shapes created over MCP itself; this store caches
+ * the local user so new shapes can be stamped synchronously.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import type { Author, Shape } from '$lib/types';
+
+let currentAuthor: Author | null = null;
+
+/**
+ * Fetch the local user's identity; call at startup and after it changes in settings
+ */
+export async function loadAuthor(): Promise<void> {
+  try {
+    currentAuthor = await invoke<Author>('get_author');
+  } catch (err) {
+    console.error('Failed to load author:', err);
+  }
+}
+
+/**
+ * The shape with `createdBy` set to the local user, unless it already has an author
+ */
+export function stampAuthor<T extends Shape>(shape: T): T {
+  if (shape.createdBy || !currentAuthor) return shape;
+  return { ...shape, createdBy: currentAuthor };
+}
+
+/**
+ * Distinct authors of the given shapes, in order of first appearance
+ */
+export function authorsOf(shapes: Shape[]): Author[] {
+  const seen = new Map<string, Author>();
+  for (const shape of shapes) {
+    const author = shape.createdBy;
+    if (author && !seen.has(`${author.source}:${author.name}`)) {
+      seen.set(`${author.source}:${author.name}`, author);
+    }
+  }
+  return Array.from(seen.values());
+}
+
+/**
+ * Whether `shape` matches a `createdBy` filter: an author name, or "user" / "agent"
+ */
+export function matchesAuthor(shape: Shape, filter: string): boolean {
+  const author = shape.createdBy;
+  if (!author) return false;
+  const wanted = filter.trim().toLowerCase();
+  return author.source === wanted || author.name.toLowerCase() === wanted;
+}
@@ -1,5 +1,5 @@
 import { writable, derived, type Writable } from 'svelte/store';
-import type { Shape, Viewport, StylePreset, ToolType } from '$lib/types';
+import type { Shape, Viewport, StylePreset, ToolType, DocumentScale } from '$lib/types';
 
 // Re-export types for convenience
 export type { Shape, Viewport, StylePreset, ToolType };
@@ -26,6 +26,8 @@ export interface CanvasState {
   objectSnap: boolean;              // Whether shapes magnetically snap to aligned positions
   presentationMode: boolean;        // Whether presentation mode is active
   toolBeforePresentation?: ToolType; // Tool that was active before entering presentation mode
+  scale?: DocumentScale | null;     // Real-world scale, if the document has one
+  hiddenFromApi?: boolean;          // Whether MCP clients are kept away from this document
 }
 
 // Initial state
@@ -300,6 +302,13 @@ export function enterPresentationMode(): void {
 
 }
 
+/**
+ * Hide the document from MCP clients, or show it to them again
+ */
+export function setHiddenFromApi(hidden: boolean): void {
+  canvasStore.update(state => ({ ...state, hiddenFromApi: hidden }));
+}
+
 /**
  * Exit presentation mode
  */
@@ -396,6 +405,24 @@ export function sendBackward(shapeId: string): void {
   });
 }
 
+/**
+ * Stack shapes in the given order, bottom to top. Shapes not listed stay
+ * above them in their current order.
+ */
+export function orderShapes(state: CanvasState, shapeIds: string[]): Shape[] {
+  const byId = new Map(state.shapesArray.map(s => [s.id, s]));
+  const listed = new Set(shapeIds);
+  const ordered = shapeIds.map(id => byId.get(id)).filter((s): s is Shape => !!s);
+  return [...ordered, ...state.shapesArray.filter(s => !listed.has(s.id))];
+}
+
+/**
+ * Set the z-order of shapes (see orderShapes)
+ */
+export function setShapeOrder(shapeIds: string[]): void {
+  canvasStore.update(state => ({ ...state, shapesArray: orderShapes(state, shapeIds) }));
+}
+
 /**
  * Update multiple shapes at once
  */
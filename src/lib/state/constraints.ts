@@ -0,0 +1,40 @@
+/**
+ * Geometry constraints: re-solve in Rust after the user moves things
+ */
+
+import { get } from 'svelte/store';
+import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/core';
+import { canvasStore, updateShapes } from '$lib/state/canvasStore';
+import type { Shape } from '$lib/state/canvasStore';
+import { isTauri } from '$lib/storage/tauriFile';
+
+/**
+ * Number of active constraints, mirrored from `constraints-changed`.
+ * Lets pointer-up skip the round trip when nothing is constrained.
+ */
+let constraintCount = 0;
+
+/**
+ * Subscribe to constraint changes from the backend.
+ * Returns a cleanup function.
+ */
+export async function initConstraints(): Promise<() => void> {
+  return listen<{ count: number }>('constraints-changed', (event) => {
+    constraintCount = event.payload.count;
+  });
+}
+
+/**
+ * Re-solve constraints against the active canvas and apply the result
+ */
+export async function applyConstraints(): Promise<void> {
+  if (!isTauri() || constraintCount === 0) return;
+
+  const updates = await invoke<Array<{ id: string; changes: Partial<Shape> }>>('solve_constraints', {
+    shapes: get(canvasStore).shapesArray,
+  });
+  if (updates.length > 0) {
+    updateShapes(updates);
+  }
+}
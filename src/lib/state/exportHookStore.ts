@@ -0,0 +1,43 @@
+/**
+ * Results of export post-processing hooks (`export_hooks.rs`)
+ */
+
+import { writable } from 'svelte/store';
+import { listen } from '@tauri-apps/api/event';
+
+export interface HookOutcome {
+  hook: string;
+  path: string;
+  success: boolean;
+  exitCode: number | null;
+  output: string;
+  error?: string;
+}
+
+/**
+ * Outcomes not yet dismissed, oldest first
+ */
+export const hookOutcomes = writable<HookOutcome[]>([]);
+
+/**
+ * Successful outcomes clear themselves after this long
+ */
+const SUCCESS_DISMISS_MS = 6000;
+
+export function dismissOutcome(outcome: HookOutcome): void {
+  hookOutcomes.update(outcomes => outcomes.filter(o => o !== outcome));
+}
+
+/**
+ * Subscribe to hook results from the backend.
+ * Returns a cleanup function.
+ */
+export async function initExportHooks(): Promise<() => void> {
+  return listen<HookOutcome>('export-hook-finished', (event) => {
+    const outcome = event.payload;
+    hookOutcomes.update(outcomes => [...outcomes, outcome]);
+    if (outcome.success) {
+      setTimeout(() => dismissOutcome(outcome), SUCCESS_DISMISS_MS);
+    }
+  });
+}
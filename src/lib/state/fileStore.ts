@@ -3,14 +3,23 @@
  */
 import { writable } from 'svelte/store';
 
+/**
+ * How the current file is stored on disk: the JSON format, a SQLite
+ * database with one row per shape (for very large boards), or an event log
+ * of every change that is replayed on open
+ */
+export type DocumentStorage = 'json' | 'sqlite' | 'journal';
+
 export interface FileState {
   currentFilePath: string | null;
   title: string;
+  storage: DocumentStorage;
 }
 
 const initialState: FileState = {
   currentFilePath: null,
   title: 'Untitled',
+  storage: 'json',
 };
 
 export const fileStore = writable<FileState>(initialState);
@@ -18,15 +27,15 @@ export const fileStore = writable<FileState>(initialState);
 /**
  * Set the current file path and derive title from filename
  */
-export function setFilePath(path: string | null): void {
+export function setFilePath(path: string | null, storage: DocumentStorage = 'json'): void {
   if (path) {
     // Extract filename without extension
     const parts = path.replace(/\\/g, '/').split('/');
     const filename = parts[parts.length - 1];
     const title = filename.replace(/\.(napkin|json)$/i, '');
-    fileStore.set({ currentFilePath: path, title });
+    fileStore.set({ currentFilePath: path, title, storage });
   } else {
-    fileStore.set({ currentFilePath: null, title: 'Untitled' });
+    fileStore.set({ currentFilePath: null, title: 'Untitled', storage: 'json' });
   }
 }
 
@@ -40,3 +49,21 @@ export function getCurrentFilePath(): string | null {
   })();
   return path;
 }
+
+/**
+ * Record that the current file was converted to another storage format
+ */
+export function setStorage(storage: DocumentStorage): void {
+  fileStore.update(state => ({ ...state, storage }));
+}
+
+/**
+ * Get the current file's storage format synchronously
+ */
+export function getCurrentStorage(): DocumentStorage {
+  let storage: DocumentStorage = 'json';
+  fileStore.subscribe(state => {
+    storage = state.storage;
+  })();
+  return storage;
+}
@@ -10,7 +10,9 @@ import {
   canvasStore,
   groupShapes,
   ungroupShapes,
+  setShapeOrder,
 } from './canvasStore';
+import { stampAuthor } from './authorStore';
 
 /**
  * Base command interface
@@ -24,7 +26,11 @@ export interface Command {
  * Command to add a shape
  */
 export class AddShapeCommand implements Command {
-  constructor(private shape: Shape) {}
+  private shape: Shape;
+
+  constructor(shape: Shape) {
+    this.shape = stampAuthor(shape);
+  }
 
   execute(): void {
     addShape(this.shape);
@@ -132,6 +138,28 @@ export class BatchCommand implements Command {
   }
 }
 
+/**
+ * Command to change the z-order of shapes
+ */
+export class ReorderShapesCommand implements Command {
+  private oldOrder: string[];
+
+  constructor(private newOrder: string[]) {
+    // Capture the current order
+    let state: any;
+    canvasStore.subscribe((s) => (state = s))();
+    this.oldOrder = state.shapesArray.map((s: Shape) => s.id);
+  }
+
+  execute(): void {
+    setShapeOrder(this.newOrder);
+  }
+
+  undo(): void {
+    setShapeOrder(this.oldOrder);
+  }
+}
+
 /**
  * Command to delete multiple shapes
  */
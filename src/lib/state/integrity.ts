@@ -0,0 +1,48 @@
+/**
+ * Document integrity: repair connectors bound to shapes that no longer exist
+ */
+
+import { get } from 'svelte/store';
+import { invoke } from '@tauri-apps/api/core';
+import { canvasStore, updateShapes, removeShapes } from '$lib/state/canvasStore';
+import type { Shape } from '$lib/state/canvasStore';
+import { isTauri } from '$lib/storage/tauriFile';
+
+/**
+ * Repair plan returned by the Rust `repair_document` command
+ */
+interface RepairPlan {
+  fixes: Array<{ shapeId: string; end: 'start' | 'end'; missingShapeId: string; action: 'unbind' | 'remove' }>;
+  updates: Array<{ id: string; changes: Record<string, unknown> }>;
+  removedIds: string[];
+}
+
+/**
+ * Check the active canvas for dangling bindings and fix them according to
+ * the binding repair policy in settings. Returns the number of fixes.
+ */
+export async function repairBindings(): Promise<number> {
+  if (!isTauri()) return 0;
+
+  const plan = await invoke<RepairPlan>('repair_document', {
+    shapes: get(canvasStore).shapesArray,
+  });
+
+  if (plan.updates.length > 0) {
+    updateShapes(plan.updates.map(({ id, changes }) => {
+      // Rust clears bindings with null; shapes use undefined for "unbound"
+      const cleaned: Record<string, unknown> = {};
+      for (const [key, value] of Object.entries(changes)) {
+        cleaned[key] = value ?? undefined;
+      }
+      return { id, changes: cleaned as Partial<Shape> };
+    }));
+  }
+  if (plan.removedIds.length > 0) {
+    removeShapes(plan.removedIds);
+  }
+  if (plan.fixes.length > 0) {
+    console.info(`[integrity] Repaired ${plan.fixes.length} dangling binding(s)`, plan.fixes);
+  }
+  return plan.fixes.length;
+}
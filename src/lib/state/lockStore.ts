@@ -0,0 +1,81 @@
+/**
+ * Cooperative canvas lock shared with MCP agents
+ */
+
+import { writable, get } from 'svelte/store';
+import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/core';
+import { isTauri } from '$lib/storage/tauriFile';
+
+/**
+ * Storage key for the opt-in locking setting
+ */
+const LOCK_ENABLED_STORAGE_KEY = 'napkin_canvas_lock_enabled';
+
+export type LockOwner = 'user' | 'agent';
+
+/**
+ * Current lock holder, mirrored from the `canvas-lock-changed` event
+ */
+export const canvasLock = writable<{ owner: LockOwner | null }>({ owner: null });
+
+/**
+ * Whether the user's edits take the lock (blocking agent writes meanwhile)
+ */
+export const lockingEnabled = writable<boolean>(
+  typeof localStorage !== 'undefined' && localStorage.getItem(LOCK_ENABLED_STORAGE_KEY) === 'true'
+);
+
+lockingEnabled.subscribe(enabled => {
+  try {
+    localStorage.setItem(LOCK_ENABLED_STORAGE_KEY, String(enabled));
+  } catch {
+    // localStorage unavailable
+  }
+});
+
+let lockExpiryTimer: ReturnType<typeof setTimeout> | null = null;
+
+/**
+ * Subscribe to lock changes from the backend.
+ * Returns a cleanup function.
+ */
+export async function initCanvasLock(): Promise<() => void> {
+  const unlisten = await listen<{ owner: LockOwner | null; ttlMs: number | null }>('canvas-lock-changed', (event) => {
+    const { owner, ttlMs } = event.payload;
+    canvasLock.set({ owner });
+
+    // Locks lapse server-side without an event; mirror that locally
+    if (lockExpiryTimer) clearTimeout(lockExpiryTimer);
+    lockExpiryTimer = owner && ttlMs ? setTimeout(() => canvasLock.set({ owner: null }), ttlMs) : null;
+  });
+
+  return unlisten;
+}
+
+/**
+ * True when an agent currently holds the canvas
+ */
+export function isLockedByAgent(): boolean {
+  return get(canvasLock).owner === 'agent';
+}
+
+/**
+ * Take the lock for the duration of a user interaction (no-op unless enabled)
+ */
+export function acquireUserLock(): void {
+  if (!isTauri() || !get(lockingEnabled)) return;
+  invoke('acquire_canvas_lock', { owner: 'user' }).catch(err => {
+    console.warn('[lock]', err);
+  });
+}
+
+/**
+ * Release the user's lock at the end of an interaction
+ */
+export function releaseUserLock(): void {
+  if (!isTauri() || get(canvasLock).owner !== 'user') return;
+  invoke('release_canvas_lock', { owner: 'user' }).catch(err => {
+    console.warn('[lock]', err);
+  });
+}
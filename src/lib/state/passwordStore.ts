@@ -0,0 +1,52 @@
+/**
+ * Password prompts for protected documents
+ */
+
+import { writable } from 'svelte/store';
+
+export interface PasswordRequest {
+  /** 'open' asks for a document's password; 'set' chooses a new one */
+  mode: 'open' | 'set';
+  /** Name of the file the password is for */
+  fileName: string;
+  /** Shown above the field, e.g. after a wrong password */
+  message?: string;
+  resolve: (password: string | null) => void;
+}
+
+/**
+ * The prompt on screen, if any
+ */
+export const passwordRequest = writable<PasswordRequest | null>(null);
+
+/**
+ * Ask the user for a password. Resolves with null if they cancel; in 'set'
+ * mode an empty string means no password.
+ */
+export function askForPassword(
+  mode: PasswordRequest['mode'],
+  fileName: string,
+  message?: string,
+): Promise<string | null> {
+  return new Promise(resolve => {
+    passwordRequest.set({ mode, fileName, message, resolve });
+  });
+}
+
+/**
+ * Answer the prompt on screen and close it
+ */
+export function answerPasswordRequest(password: string | null): void {
+  passwordRequest.update(request => {
+    request?.resolve(password);
+    return null;
+  });
+}
+
+/**
+ * The file name of a path, for prompts
+ */
+export function fileNameOf(path: string): string {
+  const parts = path.replace(/\\/g, '/').split('/');
+  return parts[parts.length - 1];
+}
@@ -0,0 +1,45 @@
+/**
+ * Presence store for agent cursors reported over MCP
+ */
+
+import { writable } from 'svelte/store';
+import { listen } from '@tauri-apps/api/event';
+
+/**
+ * Agent cursor as emitted by the Rust `agent-cursor` event
+ */
+export interface AgentCursor {
+  agent: string;
+  x: number;
+  y: number;
+  tool: string;
+  updatedAt: number;
+}
+
+/**
+ * Active agent cursors keyed by agent name
+ */
+export const agentCursors = writable<Record<string, AgentCursor>>({});
+
+/**
+ * Subscribe to presence events from the backend.
+ * Returns a cleanup function.
+ */
+export async function initPresence(): Promise<() => void> {
+  const unlistenCursor = await listen<AgentCursor>('agent-cursor', (event) => {
+    const cursor = event.payload;
+    agentCursors.update(cursors => ({ ...cursors, [cursor.agent]: cursor }));
+  });
+
+  const unlistenExpired = await listen<{ agent: string }>('agent-cursor-expired', (event) => {
+    agentCursors.update(cursors => {
+      const { [event.payload.agent]: _removed, ...rest } = cursors;
+      return rest;
+    });
+  });
+
+  return () => {
+    unlistenCursor();
+    unlistenExpired();
+  };
+}
@@ -0,0 +1,41 @@
+/**
+ * Keeps the backend's copy of the document scale in sync with the active tab
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { canvasStore } from './canvasStore';
+import type { DocumentScale } from '$lib/types';
+
+/**
+ * Ruler tick as returned by the Rust `get_ruler_ticks` command
+ */
+export interface RulerTick {
+  position: number;
+  major: boolean;
+  label?: string;
+}
+
+/**
+ * Push the active document's scale to the backend whenever it changes, so
+ * unit-aware tool arguments and PDF export use it. Returns a cleanup function.
+ */
+export function initDocumentScale(): () => void {
+  let last: string | undefined;
+  return canvasStore.subscribe(state => {
+    const scale: DocumentScale | null = state.scale || null;
+    const key = JSON.stringify(scale);
+    if (key === last) return;
+    last = key;
+    invoke('set_document_scale', { scale }).catch(err =>
+      console.error('Failed to sync document scale:', err)
+    );
+  });
+}
+
+/**
+ * Ruler ticks between two canvas coordinates at the given zoom, labelled in
+ * the document's unit (or pixels when it has no scale)
+ */
+export function getRulerTicks(start: number, end: number, zoom: number): Promise<RulerTick[]> {
+  return invoke<RulerTick[]>('get_ruler_ticks', { start, end, zoom });
+}
@@ -105,9 +105,10 @@ export function createTab(title: string = 'Untitled'): string {
  * Create a new tab without switching the UI to it.
  * The tab is stored with a default empty canvas state (non-null),
  * so it never becomes "live" in canvasStore.
+ * `id` lets the Rust side name tabs it plans (partition_canvas).
  */
-export function createTabSilent(title: string = 'Untitled'): string {
-  const newId = generateTabId();
+export function createTabSilent(title: string = 'Untitled', id?: string): string {
+  const newId = id ?? generateTabId();
 
   // Snapshot current tab first (same as createTab)
   snapshotActiveTab();
@@ -341,7 +342,7 @@ export function markAllTabsClean(): void {
  * Clears existing tabs, creates new ones, and loads the active tab into canvasStore.
  */
 export function restoreTabsFromCollection(
-  documents: Array<{ shapes: Map<string, any>; shapesArray: any[]; viewport: any; metadata: any; stylePreset?: any; groups?: Map<string, any> }>,
+  documents: Array<{ shapes: Map<string, any>; shapesArray: any[]; viewport: any; metadata: any; stylePreset?: any; scale?: any; groups?: Map<string, any> }>,
   activeIndex: number
 ): void {
   if (documents.length === 0) return;
@@ -349,9 +350,12 @@ export function restoreTabsFromCollection(
   // Clamp activeIndex
   const safeIndex = Math.max(0, Math.min(activeIndex, documents.length - 1));
 
-  // Build new tabs
+  // Build new tabs, keeping saved ids unless a document was duplicated
+  const seen = new Set<string>();
   const newTabs: Tab[] = documents.map((doc, i) => {
-    const id = generateTabId();
+    const saved = doc.metadata?.tabId;
+    const id = typeof saved === 'string' && !seen.has(saved) ? saved : generateTabId();
+    seen.add(id);
     const title = doc.metadata?.title || 'Untitled';
     const isActive = i === safeIndex;
 
@@ -376,6 +380,8 @@ export function restoreTabsFromCollection(
         groups: doc.groups || new Map(),
         activeTool: 'select' as const,
         stylePreset: doc.stylePreset ? { ...defaultStylePreset, ...doc.stylePreset } : defaultStylePreset,
+        scale: doc.scale || null,
+        hiddenFromApi: doc.metadata?.hiddenFromApi === true,
         showGrid: true,
         presentationMode: false,
       } as CanvasState,
@@ -411,6 +417,8 @@ export function restoreTabsFromCollection(
     stylePreset: activeDoc.stylePreset
       ? { ...defaultStylePresetForActive, ...activeDoc.stylePreset }
       : defaultStylePresetForActive,
+    scale: activeDoc.scale || null,
+    hiddenFromApi: activeDoc.metadata?.hiddenFromApi === true,
   }));
 
   historyManager.clear();
@@ -2,19 +2,20 @@
  * Dual-mode auto-save system
  * Uses Tauri file system for desktop app, IndexedDB for web browser
  *
- * Desktop: saves ALL tabs as a collection (NapkinCollection format)
+ * Desktop: saves ALL tabs as a collection (NapkinCollection format), or only
+ * the changed shapes when the file is a SQLite document
  * Browser: saves single document to IndexedDB (legacy)
  */
 
 import { isTauri } from './tauriFile';
 import { saveAutosave as saveIndexedDB, loadAutosave as loadIndexedDB } from './indexedDB';
 import { exportCollectionToJSON, importFromJSONFlexible, serializeCanvasState, deserializeCanvasState } from './jsonExport';
-import { getCurrentFilePath } from '../state/fileStore';
+import { docStoreSync } from './docStore';
+import { getCurrentFilePath, getCurrentStorage } from '../state/fileStore';
 import { canvasStore } from '../state/canvasStore';
 import { getAllTabsWithState, tabStore } from '../state/tabStore';
 import { get } from 'svelte/store';
-import { writeTextFile, readTextFile, exists } from '@tauri-apps/plugin-fs';
-import { appDataDir } from '@tauri-apps/api/path';
+import { invoke } from '@tauri-apps/api/core';
 
 /**
  * Auto-save to appropriate storage.
@@ -23,26 +24,34 @@ import { appDataDir } from '@tauri-apps/api/path';
  */
 export async function autoSave(): Promise<void> {
   if (isTauri()) {
-    // Build collection JSON from all tabs
     const tabs = getAllTabsWithState();
     const tabState = get(tabStore);
     const activeIndex = tabState.tabs.findIndex(t => t.id === tabState.activeTabId);
+
+    // SQLite documents and event logs take incremental writes
+    const currentPath = getCurrentFilePath();
+    const storage = getCurrentStorage();
+    if (currentPath && (storage === 'sqlite' || storage === 'journal')) {
+      await docStoreSync.save(currentPath, tabs, Math.max(0, activeIndex));
+      return;
+    }
+
+    // Build collection JSON from all tabs
     const json = exportCollectionToJSON(
-      tabs.map(t => ({ title: t.title, canvasState: t.canvasState })),
+      tabs.map(t => ({ id: t.id, title: t.title, canvasState: t.canvasState })),
       Math.max(0, activeIndex)
     );
 
-    // If we have a named file, save there
-    const currentPath = getCurrentFilePath();
+    // If we have a named file, save there unless it already matches
     if (currentPath) {
-      await writeTextFile(currentPath, json);
+      if (await invoke<boolean>('document_is_dirty', { path: currentPath, json })) {
+        await invoke('save_document', { path: currentPath, json });
+      }
       return;
     }
 
     // Otherwise save to recovery file
-    const appDataPath = await appDataDir();
-    const filePath = `${appDataPath}/autosave.napkin`;
-    await writeTextFile(filePath, json);
+    await invoke('save_recovery', { json });
   } else {
     // Browser: Save to IndexedDB (keep existing single-doc approach)
     const state = get(canvasStore);
@@ -65,13 +74,8 @@ export async function loadAutoSave(): Promise<{
 } | null> {
   if (isTauri()) {
     try {
-      const appDataPath = await appDataDir();
-      const filePath = `${appDataPath}/autosave.napkin`;
-
-      const fileExists = await exists(filePath);
-      if (!fileExists) return null;
-
-      const json = await readTextFile(filePath);
+      const json = await invoke<string | null>('load_recovery');
+      if (json === null) return null;
       return importFromJSONFlexible(json);
     } catch (e) {
       console.error('Failed to load autosave from Tauri:', e);
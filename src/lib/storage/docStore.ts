@@ -0,0 +1,118 @@
+/**
+ * Incremental saves for SQLite-backed documents and event logs
+ *
+ * A SQLite document keeps one row per shape and an event log records each
+ * change, so autosave only needs to send what changed. Shapes are immutable in the stores (every edit replaces the
+ * object), so comparing references against the last write finds the changed
+ * shapes without serializing the whole board.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import type { Shape } from '../types';
+import type { CanvasState } from '../state/canvasStore';
+import { serializeCanvasState, serializeShape } from './jsonExport';
+import type { SerializedShape } from './schema';
+
+interface DocumentChange {
+  index: number;
+  document: object;
+  upserts: SerializedShape[];
+  removed: string[];
+  order?: string[];
+}
+
+interface DocumentChanges {
+  collection: { activeDocumentIndex: number };
+  documentCount: number;
+  documents: DocumentChange[];
+}
+
+interface Baseline {
+  shapes: Map<string, Shape>;
+  order: string[];
+}
+
+function shapesOf(state: CanvasState): Shape[] {
+  return state.shapesArray ?? Array.from(state.shapes.values());
+}
+
+function baselineOf(state: CanvasState): Baseline {
+  const shapes = shapesOf(state);
+  return {
+    shapes: new Map(shapes.map(s => [s.id, s])),
+    order: shapes.map(s => s.id),
+  };
+}
+
+function sameOrder(a: string[], b: string[]): boolean {
+  return a.length === b.length && a.every((id, i) => id === b[i]);
+}
+
+/**
+ * Tracks what was last written to one SQLite document, per document index
+ */
+export class DocStoreSync {
+  private path: string | null = null;
+  private baselines: Baseline[] = [];
+
+  /**
+   * Record the tabs as already on disk, e.g. right after opening or
+   * converting the file
+   */
+  prime(path: string, tabs: Array<{ canvasState: CanvasState | null }>): void {
+    this.path = path;
+    this.baselines = tabs.map(t => t.canvasState ? baselineOf(t.canvasState) : { shapes: new Map(), order: [] });
+  }
+
+  /**
+   * Write the changes since the last save to `path`
+   */
+  async save(
+    path: string,
+    tabs: Array<{ title: string; canvasState: CanvasState | null }>,
+    activeIndex: number
+  ): Promise<void> {
+    if (path !== this.path) {
+      this.path = path;
+      this.baselines = [];
+    }
+
+    const next: Baseline[] = [];
+    const changes: DocumentChanges = {
+      collection: { activeDocumentIndex: activeIndex },
+      documentCount: tabs.length,
+      documents: [],
+    };
+
+    tabs.forEach((tab, index) => {
+      if (!tab.canvasState) return;
+      const current = baselineOf(tab.canvasState);
+      const previous = this.baselines[index] ?? { shapes: new Map(), order: [] };
+      next[index] = current;
+
+      const upserts: SerializedShape[] = [];
+      for (const [id, shape] of current.shapes) {
+        if (previous.shapes.get(id) !== shape) upserts.push(serializeShape(shape));
+      }
+      const removed = previous.order.filter(id => !current.shapes.has(id));
+
+      // The document row, without its shapes
+      const document = serializeCanvasState({ ...tab.canvasState, shapesArray: [], shapes: new Map() });
+      document.metadata.title = tab.title;
+      delete (document as any).shapes;
+
+      changes.documents.push({
+        index,
+        document,
+        upserts,
+        removed,
+        ...(sameOrder(previous.order, current.order) ? {} : { order: current.order }),
+      });
+    });
+
+    await invoke('apply_document_changes', { path, changes });
+    this.baselines = next;
+  }
+}
+
+export const docStoreSync = new DocStoreSync();
@@ -0,0 +1,65 @@
+/**
+ * Periodic version history compaction
+ *
+ * Runs the Rust `compact_history` command with the retention policy from
+ * settings, so long-lived boards don't carry an ever-growing history.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import type { VersionHistory } from './schema';
+
+const FIRST_RUN_DELAY_MS = 30 * 1000;
+const INTERVAL_MS = 10 * 60 * 1000;
+
+/**
+ * Result of a compaction pass, as reported by the backend
+ */
+export interface CompactionReport {
+  snapshotsBefore: number;
+  snapshotsAfter: number;
+  bytesBefore: number;
+  bytesAfter: number;
+  reclaimedBytes: number;
+}
+
+/**
+ * Compact a history using the retention policy from settings
+ */
+export async function compactHistory(
+  history: VersionHistory
+): Promise<{ history: VersionHistory; report: CompactionReport }> {
+  return invoke('compact_history', { history });
+}
+
+/**
+ * Compact the history in the background every few minutes. The result is
+ * discarded if a snapshot was taken while the backend was working.
+ * Returns a cleanup function.
+ */
+export function startHistoryMaintenance(
+  getHistory: () => VersionHistory,
+  setHistory: (history: VersionHistory) => void
+): () => void {
+  const run = async () => {
+    const before = getHistory();
+    if (before.snapshots.length < 2) return;
+    try {
+      const { history, report } = await compactHistory(before);
+      if (getHistory() !== before || report.reclaimedBytes === 0) return;
+      setHistory(history);
+      console.log(
+        `[history] Compacted ${report.snapshotsBefore} -> ${report.snapshotsAfter} snapshots, ` +
+        `reclaimed ${report.reclaimedBytes} bytes`
+      );
+    } catch (err) {
+      console.error('[history] Compaction failed:', err);
+    }
+  };
+
+  const first = setTimeout(run, FIRST_RUN_DELAY_MS);
+  const interval = setInterval(run, INTERVAL_MS);
+  return () => {
+    clearTimeout(first);
+    clearInterval(interval);
+  };
+}
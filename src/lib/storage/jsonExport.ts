@@ -34,7 +34,7 @@ interface CanvasState {
  * @param shape - The shape to serialize
  * @returns Serialized shape data
  */
-function serializeShape(shape: Shape): SerializedShape {
+export function serializeShape(shape: Shape): SerializedShape {
   // Create a shallow copy to avoid modifying the original
   const serialized: SerializedShape = {
     id: shape.id,
@@ -140,11 +140,13 @@ export function serializeCanvasState(state: CanvasState): NapkinDocument {
       opacity: state.stylePreset.opacity,
       roughness: state.stylePreset.roughness,
     } : undefined,
+    scale: state.scale || undefined,
     metadata: {
       created: state.metadata?.created || now,
       modified: now,
       title: state.metadata?.title || "Untitled",
       description: state.metadata?.description,
+      ...(state.hiddenFromApi ? { hiddenFromApi: true } : {}),
     },
   };
 }
@@ -161,6 +163,7 @@ export function deserializeCanvasState(document: NapkinDocument): {
   viewport: Viewport;
   metadata: any;
   stylePreset?: any;
+  scale?: any;
   groups?: Map<string, {id: string; shapeIds: string[]}>;
 } {
   // Deserialize all shapes
@@ -199,6 +202,7 @@ export function deserializeCanvasState(document: NapkinDocument): {
     viewport: runtimeViewport as any,
     metadata: document.metadata,
     stylePreset: (document as any).stylePreset || undefined,
+    scale: document.scale || undefined,
     groups,
   };
 }
@@ -228,6 +232,7 @@ export function importFromJSON(json: string): {
   viewport: Viewport;
   metadata: any;
   stylePreset?: any;
+  scale?: any;
 } {
   let parsed: any;
 
@@ -280,6 +285,7 @@ export function uploadJSON(): Promise<{
   viewport: Viewport;
   metadata: any;
   stylePreset?: any;
+  scale?: any;
 }> {
   return new Promise((resolve, reject) => {
     // Create file input
@@ -347,6 +353,7 @@ export async function pasteFromClipboard(): Promise<{
   viewport: Viewport;
   metadata: any;
   stylePreset?: any;
+  scale?: any;
 } | null> {
   let text: string;
 
@@ -368,10 +375,11 @@ export async function pasteFromClipboard(): Promise<{
 }
 
 /**
- * Export all tabs as a collection JSON string
+ * Export all tabs as a collection JSON string. A tab's `id` is kept in its
+ * document's metadata, so logged edits find the tab after a reorder.
  */
 export function exportCollectionToJSON(
-  tabs: Array<{title: string; canvasState: any}>,
+  tabs: Array<{id?: string; title: string; canvasState: any}>,
   activeIndex: number,
   history?: VersionHistory
 ): string {
@@ -379,6 +387,7 @@ export function exportCollectionToJSON(
   const documents = tabs.map(tab => {
     const doc = serializeCanvasState(tab.canvasState);
     doc.metadata.title = tab.title;
+    if (tab.id) doc.metadata.tabId = tab.id;
     return doc;
   });
 
@@ -404,11 +413,11 @@ export function exportCollectionToJSON(
  */
 export function importFromJSONFlexible(json: string): {
   type: 'single';
-  state: {shapes: Map<string, Shape>; shapesArray: Shape[]; viewport: Viewport; metadata: any; stylePreset?: any};
+  state: {shapes: Map<string, Shape>; shapesArray: Shape[]; viewport: Viewport; metadata: any; stylePreset?: any; scale?: any};
   history?: VersionHistory;
 } | {
   type: 'collection';
-  documents: Array<{shapes: Map<string, Shape>; shapesArray: Shape[]; viewport: Viewport; metadata: any; stylePreset?: any}>;
+  documents: Array<{shapes: Map<string, Shape>; shapesArray: Shape[]; viewport: Viewport; metadata: any; stylePreset?: any; scale?: any}>;
   activeIndex: number;
   history?: VersionHistory;
 } {
@@ -0,0 +1,62 @@
+/**
+ * Scheduled jobs that run in the webview
+ *
+ * The backend scheduler (`scheduler.rs`) emits `scheduled-job` when a
+ * checkpoint or daily digest is due. PDF exports run entirely in the backend.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import type { VersionHistory } from './schema';
+
+/**
+ * Changes to one document over the digest period
+ */
+export interface DocumentDigest {
+  title: string;
+  added: number;
+  removed: number;
+  modified: number;
+}
+
+/**
+ * Summary of the version history since a point in time
+ */
+export interface Digest {
+  snapshots: number;
+  documents: DocumentDigest[];
+  summary: string;
+}
+
+type ScheduledJobEvent = { job: 'checkpoint' } | { job: 'digest'; since: number };
+
+/**
+ * Summarise the changes in a history since `since` (ms since the epoch)
+ */
+export async function buildDigest(history: VersionHistory, since: number): Promise<Digest> {
+  return invoke('build_digest', { history, since });
+}
+
+/**
+ * Run scheduled jobs as the backend asks for them. Returns a cleanup function.
+ */
+export async function initScheduledJobs(jobs: {
+  /** Save open documents and take a version snapshot */
+  checkpoint: () => Promise<void>;
+  getHistory: () => VersionHistory;
+  showDigest: (digest: Digest) => void;
+}): Promise<() => void> {
+  return listen<ScheduledJobEvent>('scheduled-job', async (event) => {
+    const request = event.payload;
+    try {
+      if (request.job === 'checkpoint') {
+        await jobs.checkpoint();
+        console.log('[schedule] Checkpoint saved');
+      } else if (request.job === 'digest') {
+        jobs.showDigest(await buildDigest(jobs.getHistory(), request.since));
+      }
+    } catch (err) {
+      console.error(`[schedule] ${request.job} failed:`, err);
+    }
+  });
+}
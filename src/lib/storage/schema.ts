@@ -48,6 +48,10 @@ export interface DocumentMetadata {
   title?: string;
   /** Optional description */
   description?: string;
+  /** Whether MCP clients are kept away from this document */
+  hiddenFromApi?: boolean;
+  /** Id of the tab the document was saved from, kept across reopening */
+  tabId?: string;
 }
 
 /**
@@ -75,6 +79,11 @@ export interface NapkinDocument {
     opacity?: number;
     roughness?: number;
   };
+  /** Optional real-world scale (e.g. 1 cm = 10 px) */
+  scale?: {
+    unit: string;
+    pixelsPerUnit: number;
+  } | null;
 }
 
 /**
@@ -4,7 +4,34 @@
  */
 
 import {save, open} from '@tauri-apps/plugin-dialog';
-import {writeTextFile, readTextFile} from '@tauri-apps/plugin-fs';
+import {writeTextFile} from '@tauri-apps/plugin-fs';
+import {invoke} from '@tauri-apps/api/core';
+import type {DocumentStorage} from '../state/fileStore';
+import {getCurrentFilePath} from '../state/fileStore';
+import {askForPassword, fileNameOf} from '../state/passwordStore';
+
+/** A failed document command, as sent by the Rust `document` module */
+export interface DocumentError {
+  kind: 'notFound' | 'unreadable' | 'invalid' | 'writeFailed' | 'passwordRequired' | 'wrongPassword';
+  message: string;
+}
+
+function errorKind(error: unknown): DocumentError['kind'] | null {
+  if (error && typeof error === 'object' && 'kind' in error) {
+    return (error as DocumentError).kind;
+  }
+  return null;
+}
+
+/**
+ * The message of an error from a document command or anything else
+ */
+export function documentErrorMessage(error: unknown): string {
+  if (error && typeof error === 'object' && 'message' in error) {
+    return String((error as {message: unknown}).message);
+  }
+  return String(error);
+}
 
 /**
  * Check if running in Tauri environment
@@ -41,12 +68,20 @@ export async function saveDrawingFile(json: string): Promise<string | null> {
 
   if (!filePath) return null; // User cancelled
 
+  // A copy of a protected file is offered the same protection
+  let password: string | null = null;
+  const currentPath = getCurrentFilePath();
+  if (currentPath && await invoke<boolean>('document_is_protected', {path: currentPath})) {
+    password = await askForPassword('set', fileNameOf(filePath), `${fileNameOf(currentPath)} is password-protected. Protect the copy too?`);
+    if (password === null) return null;
+  }
+
   // Write to file
   try {
-    await writeTextFile(filePath, json);
+    await invoke('save_document', {path: filePath, json, password: password || null});
   } catch (writeError) {
-    console.error('writeTextFile failed for path:', filePath, writeError);
-    throw new Error(`Failed to write file: ${writeError instanceof Error ? writeError.message : String(writeError)}`);
+    console.error('save_document failed for path:', filePath, writeError);
+    throw new Error(documentErrorMessage(writeError));
   }
 
   return filePath;
@@ -54,7 +89,8 @@ export async function saveDrawingFile(json: string): Promise<string | null> {
 
 /**
  * Save drawing to a specific file path (no dialog).
- * Takes a pre-built JSON string and writes to the specified path.
+ * Takes a pre-built JSON string and writes to the specified path,
+ * keeping the file's storage format (JSON, SQLite or event log).
  */
 export async function saveToFile(json: string, filePath: string): Promise<void> {
   if (!isTauri()) {
@@ -62,18 +98,39 @@ export async function saveToFile(json: string, filePath: string): Promise<void>
   }
 
   try {
-    await writeTextFile(filePath, json);
+    await invoke('save_document', {path: filePath, json});
   } catch (writeError) {
-    console.error('writeTextFile failed for path:', filePath, writeError);
-    throw new Error(`Failed to write file: ${writeError instanceof Error ? writeError.message : String(writeError)}`);
+    console.error('save_document failed for path:', filePath, writeError);
+    throw new Error(documentErrorMessage(writeError));
+  }
+}
+
+/**
+ * Read a drawing file as collection JSON, whichever format it is stored in,
+ * asking for the password of a protected file
+ */
+export async function readDrawingFile(filePath: string): Promise<{json: string; storage: DocumentStorage}> {
+  let password: string | null = null;
+  for (;;) {
+    try {
+      const {json, format} = await invoke<{json: string; format: DocumentStorage}>('load_document', {path: filePath, password});
+      return {json, storage: format};
+    } catch (readError) {
+      const kind = errorKind(readError);
+      if (kind !== 'passwordRequired' && kind !== 'wrongPassword') {
+        throw new Error(documentErrorMessage(readError));
+      }
+      password = await askForPassword('open', fileNameOf(filePath), kind === 'wrongPassword' ? 'Wrong password, try again.' : undefined);
+      if (password === null) throw new Error(`Opening ${fileNameOf(filePath)} was cancelled`);
+    }
   }
 }
 
 /**
  * Open drawing from file using native dialog.
- * Returns raw JSON string and filePath instead of parsed state.
+ * Returns raw JSON string, filePath and storage format instead of parsed state.
  */
-export async function openDrawingFile(): Promise<{json: string; filePath: string} | null> {
+export async function openDrawingFile(): Promise<{json: string; filePath: string; storage: DocumentStorage} | null> {
   if (!isTauri()) {
     throw new Error('Tauri file system not available');
   }
@@ -92,9 +149,9 @@ export async function openDrawingFile(): Promise<{json: string; filePath: string
   if (!filePath || Array.isArray(filePath)) return null; // User cancelled or multiple files
 
   // Read file
-  const json = await readTextFile(filePath as string);
+  const {json, storage} = await readDrawingFile(filePath as string);
 
-  return {json, filePath: filePath as string};
+  return {json, filePath: filePath as string, storage};
 }
 
 /**
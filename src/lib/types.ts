@@ -38,6 +38,15 @@ export interface EndpointConfig {
   size: number; // Multiplier: 0.5 to 3, default 1
 }
 
+/**
+ * Who created a shape: the local user or an MCP client
+ */
+export interface Author {
+  name: string;
+  color: string;
+  source: 'user' | 'agent';
+}
+
 /**
  * Base properties for all shapes
  */
@@ -61,6 +70,17 @@ export interface BaseShape {
   textAlign?: TextAlign; // Horizontal text alignment within the shape (default: 'center')
   verticalAlign?: VerticalAlign; // Vertical text alignment within the shape (default: 'middle')
   labelPosition?: LabelPosition; // Where text renders relative to shape (default: 'inside')
+  autoFit?: boolean; // Grow the shape so its text label always fits (sized by the Rust text metrics)
+  createdBy?: Author; // Set once at creation (see authorStore / author.rs)
+  link?: ShapeLink; // Reference to another tab (set by partition_canvas)
+}
+
+/**
+ * Where a reference card points: a tab, and optionally a shape on it
+ */
+export interface ShapeLink {
+  tabId: string;
+  shapeId?: string;
 }
 
 /**
@@ -246,6 +266,14 @@ export type Shape =
 /**
  * Bounding box for hit detection and culling
  */
+/**
+ * Real-world scale of a document, e.g. 10 pixels per mm
+ */
+export interface DocumentScale {
+  unit: 'mm' | 'cm' | 'm' | 'in' | 'ft';
+  pixelsPerUnit: number;
+}
+
 export interface BoundingBox {
   x: number;
   y: number;
@@ -4,6 +4,7 @@
 
 import type { Shape } from '../types';
 import type { Shape as StoreShape } from '../state/canvasStore';
+import { getRotationCenter, rotateBounds, rotatePoint } from '../shapes/transform';
 
 interface BoundingBox {
   x: number;
@@ -13,15 +14,21 @@ interface BoundingBox {
 }
 
 /**
- * Get the bounding box for a shape
+ * Get the bounding box for a shape, covering its rotated footprint
  */
 export function getShapeBounds(shape: Shape | StoreShape): BoundingBox {
+  const rotation = shape.rotation || 0;
   if (shape.type === 'line') {
     const lineShape = shape as any;
-    const x1 = lineShape.x;
-    const y1 = lineShape.y;
-    const x2 = lineShape.x2 || x1;
-    const y2 = lineShape.y2 || y1;
+    let x1 = lineShape.x;
+    let y1 = lineShape.y;
+    let x2 = lineShape.x2 || x1;
+    let y2 = lineShape.y2 || y1;
+    if (rotation) {
+      const center = getRotationCenter(shape as Shape);
+      ({ x: x1, y: y1 } = rotatePoint({ x: x1, y: y1 }, center, rotation));
+      ({ x: x2, y: y2 } = rotatePoint({ x: x2, y: y2 }, center, rotation));
+    }
 
     return {
       x: Math.min(x1, x2),
@@ -31,12 +38,27 @@ export function getShapeBounds(shape: Shape | StoreShape): BoundingBox {
     };
   }
 
-  return {
+  const box = {
     x: shape.x,
     y: shape.y,
     width: (shape as any).width || 0,
     height: (shape as any).height || 0
   };
+  return rotateBounds(box, getRotationCenter(shape as Shape), rotation);
+}
+
+/**
+ * Shape x that puts the left of its (rotated) bounds at `left`
+ */
+function xForLeft(shape: Shape | StoreShape, left: number): number {
+  return shape.x + left - getShapeBounds(shape).x;
+}
+
+/**
+ * Shape y that puts the top of its (rotated) bounds at `top`
+ */
+function yForTop(shape: Shape | StoreShape, top: number): number {
+  return shape.y + top - getShapeBounds(shape).y;
 }
 
 /**
@@ -72,7 +94,7 @@ export function alignLeft(shapes: (Shape | StoreShape)[]): Array<{ id: string; c
 
   return shapes.map(shape => ({
     id: shape.id,
-    changes: { x: leftEdge }
+    changes: { x: xForLeft(shape, leftEdge) }
   }));
 }
 
@@ -89,7 +111,7 @@ export function alignRight(shapes: (Shape | StoreShape)[]): Array<{ id: string;
     const shapeBounds = getShapeBounds(shape);
     return {
       id: shape.id,
-      changes: { x: rightEdge - shapeBounds.width }
+      changes: { x: xForLeft(shape, rightEdge - shapeBounds.width) }
     };
   });
 }
@@ -105,7 +127,7 @@ export function alignTop(shapes: (Shape | StoreShape)[]): Array<{ id: string; ch
 
   return shapes.map(shape => ({
     id: shape.id,
-    changes: { y: topEdge }
+    changes: { y: yForTop(shape, topEdge) }
   }));
 }
 
@@ -122,7 +144,7 @@ export function alignBottom(shapes: (Shape | StoreShape)[]): Array<{ id: string;
     const shapeBounds = getShapeBounds(shape);
     return {
       id: shape.id,
-      changes: { y: bottomEdge - shapeBounds.height }
+      changes: { y: yForTop(shape, bottomEdge - shapeBounds.height) }
     };
   });
 }
@@ -140,7 +162,7 @@ export function alignCenterHorizontal(shapes: (Shape | StoreShape)[]): Array<{ i
     const shapeBounds = getShapeBounds(shape);
     return {
       id: shape.id,
-      changes: { x: centerX - shapeBounds.width / 2 }
+      changes: { x: xForLeft(shape, centerX - shapeBounds.width / 2) }
     };
   });
 }
@@ -158,7 +180,7 @@ export function alignCenterVertical(shapes: (Shape | StoreShape)[]): Array<{ id:
     const shapeBounds = getShapeBounds(shape);
     return {
       id: shape.id,
-      changes: { y: centerY - shapeBounds.height / 2 }
+      changes: { y: yForTop(shape, centerY - shapeBounds.height / 2) }
     };
   });
 }
@@ -182,7 +204,7 @@ export function distributeHorizontally(shapes: (Shape | StoreShape)[]): Array<{
   return sorted.map((shape, i) => {
     const changes = i === 0 || i === sorted.length - 1
       ? {}
-      : { x: currentX };
+      : { x: xForLeft(shape, currentX) };
 
     currentX += bounds[i].width + spacing;
 
@@ -212,7 +234,7 @@ export function distributeVertically(shapes: (Shape | StoreShape)[]): Array<{ id
   return sorted.map((shape, i) => {
     const changes = i === 0 || i === sorted.length - 1
       ? {}
-      : { y: currentY };
+      : { y: yForTop(shape, currentY) };
 
     currentY += bounds[i].height + spacing;
 